@@ -0,0 +1,37 @@
+// AudioWorkletProcessor rendering the audio of an application compiled to WebAssembly.
+//
+// The application copies this script next to its bindings, generated with `wasm-bindgen --target web`,
+// and exports a type that owns a `kiro_audio::WorkletRenderer`, such as:
+//
+//   #[wasm_bindgen]
+//   pub struct Renderer(WorkletRenderer<StudioCallback>);
+//
+//   #[wasm_bindgen]
+//   impl Renderer {
+//     #[wasm_bindgen(constructor)]
+//     pub fn new(sample_rate: f32, channels: usize) -> Renderer { ... }
+//
+//     pub fn process(&mut self, outputs: &js_sys::Array) -> bool {
+//       self.0.process(outputs)
+//     }
+//   }
+//
+// The driver is created with the compiled module of the application in the processor options:
+//
+//   WorkletDriver::new(context, channels, &options) // with options = { module: WebAssembly.Module }
+
+import { initSync, Renderer } from "./app.js";
+
+class KiroWorkletProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    initSync({ module: options.processorOptions.module });
+    this.renderer = new Renderer(sampleRate, options.outputChannelCount[0]);
+  }
+
+  process(inputs, outputs) {
+    return this.renderer.process(outputs);
+  }
+}
+
+registerProcessor("kiro-worklet-processor", KiroWorkletProcessor);
@@ -1,5 +1,7 @@
 #[derive(Debug, Clone)]
 pub struct AudioConfig {
+  /// Name of the output device, or the default one when not given
+  pub device: Option<String>,
   pub sample_rate: u32,
   pub buffer_size: usize,
 }
@@ -12,6 +14,7 @@ impl AudioConfig {
 impl Default for AudioConfig {
   fn default() -> Self {
     Self {
+      device: None,
       sample_rate: AudioConfig::DEFAULT_SAMPLE_RATE,
       buffer_size: AudioConfig::DEFAULT_BUFFER_SIZE,
     }
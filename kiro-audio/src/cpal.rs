@@ -1,6 +1,9 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::time::Duration;
+
 use cpal::{
-  BufferSize, Device, OutputCallbackInfo, SampleRate, Stream, StreamConfig, SupportedStreamConfig,
+  BufferSize, Device, OutputCallbackInfo, SampleRate, Stream, StreamConfig, StreamInstant,
+  SupportedStreamConfig,
 };
 
 use crate::{AudioConfig, AudioError, AudioHandler, AudioOutputConfig, Result};
@@ -16,13 +19,9 @@ impl AudioDriver {
     config: AudioConfig,
     mut handler: Handler,
   ) -> Result<Self> {
-    let host = cpal::default_host();
-
-    let device = host
-      .default_output_device()
-      .ok_or(AudioError::NoDefaultOutputDevice)?;
+    let device = Self::device_from_config(&config)?;
     println!(
-      "Using default output device: '{}'",
+      "Using output device: '{}'",
       device.name().unwrap_or_else(|_| "unknown".to_string())
     );
 
@@ -37,9 +36,27 @@ impl AudioDriver {
     output_config.buffer_size = BufferSize::Fixed(config.buffer_size as u32);
     println!("Using default output stream config: {:#?}", output_config);
 
+    let sample_rate = f64::from(output_config.sample_rate.0);
+    // instant and duration of the previous buffer
+    let mut previous: Option<(StreamInstant, Duration)> = None;
     let output_stream = device.build_output_stream(
       &output_config,
-      move |data: &mut [f32], _: &OutputCallbackInfo| handler.process(data, channels),
+      move |data: &mut [f32], info: &OutputCallbackInfo| {
+        let callback = info.timestamp().callback;
+        if let Some((previous_callback, previous_duration)) = previous {
+          // a callback arriving much later than the duration of the previous buffer means that the device ran out of data
+          let elapsed = callback.duration_since(&previous_callback);
+          if matches!(elapsed, Some(elapsed) if elapsed > previous_duration * 3 / 2) {
+            handler.xrun();
+          }
+        }
+        let num_frames = data.len() / channels.max(1);
+        previous = Some((
+          callback,
+          Duration::from_secs_f64(num_frames as f64 / sample_rate),
+        ));
+        handler.process(data, channels)
+      },
       move |err| eprintln!("an error occurred on stream: {:?}", err),
     )?;
 
@@ -80,9 +97,22 @@ impl AudioDriver {
     self.output_stream.play().map_err(AudioError::PlayStream)
   }
 
-  fn device_from_config(_config: &AudioConfig) -> Result<Device> {
-    cpal::default_host()
-      .default_output_device()
-      .ok_or(AudioError::NoDefaultOutputDevice)
+  /// Names of the output devices available, to choose one with [`AudioConfig::device`].
+  pub fn output_devices() -> Result<Vec<String>> {
+    let devices = cpal::default_host().output_devices()?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+  }
+
+  fn device_from_config(config: &AudioConfig) -> Result<Device> {
+    let host = cpal::default_host();
+    match config.device.as_ref() {
+      Some(name) => host
+        .output_devices()?
+        .find(|device| matches!(device.name(), Ok(device_name) if &device_name == name))
+        .ok_or_else(|| AudioError::OutputDeviceNotFound(name.clone())),
+      None => host
+        .default_output_device()
+        .ok_or(AudioError::NoDefaultOutputDevice),
+    }
   }
 }
@@ -1,12 +1,16 @@
 use thiserror::Error;
 
-use ::cpal::{BuildStreamError, DefaultStreamConfigError, PlayStreamError};
+use ::cpal::{BuildStreamError, DefaultStreamConfigError, DevicesError, PlayStreamError};
 
 mod config;
 mod cpal;
+#[cfg(target_arch = "wasm32")]
+mod worklet;
 
 pub use crate::config::AudioConfig;
 pub use crate::cpal::AudioDriver;
+#[cfg(target_arch = "wasm32")]
+pub use crate::worklet::{WorkletDriver, WorkletRenderer, WORKLET_PROCESSOR_NAME};
 
 type Result<T> = core::result::Result<T, AudioError>;
 
@@ -15,6 +19,12 @@ pub enum AudioError {
   #[error("No default output device")]
   NoDefaultOutputDevice,
 
+  #[error("Output device not found: {0}")]
+  OutputDeviceNotFound(String),
+
+  #[error("Error listing the devices")]
+  Devices(#[from] DevicesError),
+
   #[error("No default stream config")]
   NoDefaultStreamConfig(#[from] DefaultStreamConfigError),
 
@@ -23,10 +33,17 @@ pub enum AudioError {
 
   #[error("Error playing stream")]
   PlayStream(#[from] PlayStreamError),
+
+  #[cfg(target_arch = "wasm32")]
+  #[error("Web audio error: {0}")]
+  Web(String),
 }
 
 pub trait AudioHandler: Send {
   fn process(&mut self, data: &mut [f32], channels: usize);
+
+  /// Called before processing a buffer when the previous ones were not delivered in time to the device.
+  fn xrun(&mut self) {}
 }
 
 pub struct AudioOutputConfig {
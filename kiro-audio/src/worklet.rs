@@ -0,0 +1,128 @@
+use js_sys::{Array, Float32Array, Object, Promise};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{AudioContext, AudioWorkletNode, AudioWorkletNodeOptions, MessagePort};
+
+use crate::{AudioError, AudioHandler, Result};
+
+/// Name of the processor registered by the worklet script, see `js/worklet-processor.js`.
+pub const WORKLET_PROCESSOR_NAME: &str = "kiro-worklet-processor";
+
+/// Number of frames that an AudioWorkletProcessor renders at once.
+const RENDER_QUANTUM: usize = 128;
+
+fn web_error(value: JsValue) -> AudioError {
+  AudioError::Web(format!("{:?}", value))
+}
+
+/// Driver that plays through an AudioWorkletNode, so the audio is rendered in the audio thread of the browser
+/// rather than in its main thread, as with the WebAudio host of [`AudioDriver`](crate::AudioDriver).
+///
+/// The node runs the processor of the worklet script, which renders with a [`WorkletRenderer`].
+pub struct WorkletDriver {
+  context: AudioContext,
+  node: AudioWorkletNode,
+  channels: usize,
+}
+
+impl WorkletDriver {
+  /// Load the worklet script into the audio worklet of the context.
+  ///
+  /// The promise has to be resolved before creating the driver with [`WorkletDriver::new`].
+  pub fn add_module(context: &AudioContext, module_url: &str) -> Result<Promise> {
+    let worklet = context.audio_worklet().map_err(web_error)?;
+    worklet.add_module(module_url).map_err(web_error)
+  }
+
+  /// Create the node running the processor of the worklet script and connect it to the destination of the context.
+  ///
+  /// The processor options are given to the constructor of the processor, such as the compiled module of the application.
+  pub fn new(context: AudioContext, channels: usize, processor_options: &Object) -> Result<Self> {
+    let options = AudioWorkletNodeOptions::new();
+    options.set_number_of_inputs(0);
+    options.set_number_of_outputs(1);
+    options.set_output_channel_count(&Array::of1(&JsValue::from(channels as u32)));
+    options.set_processor_options(Some(processor_options));
+    let node = AudioWorkletNode::new_with_options(&context, WORKLET_PROCESSOR_NAME, &options)
+      .map_err(web_error)?;
+    node
+      .connect_with_audio_node(&context.destination())
+      .map_err(web_error)?;
+    Ok(Self {
+      context,
+      node,
+      channels,
+    })
+  }
+
+  pub fn sample_rate(&self) -> u32 {
+    self.context.sample_rate() as u32
+  }
+
+  pub fn num_input_channels(&self) -> usize {
+    0
+  }
+
+  pub fn num_output_channels(&self) -> usize {
+    self.channels
+  }
+
+  /// Resume the audio context, which the browsers only allow after a user gesture.
+  pub fn start(&self) -> Result<Promise> {
+    self.context.resume().map_err(web_error)
+  }
+
+  /// Port to send messages to the processor, and receive messages from it.
+  pub fn port(&self) -> Result<MessagePort> {
+    self.node.port().map_err(web_error)
+  }
+}
+
+/// Renders an [`AudioHandler`] from the AudioWorkletProcessor of the worklet script.
+///
+/// The application instantiates its module in the worklet, and exports a type that owns the renderer
+/// and forwards the outputs given to the `process` method of the processor, see `js/worklet-processor.js`.
+pub struct WorkletRenderer<H> {
+  handler: H,
+  channels: usize,
+  interleaved: Vec<f32>,
+  channel: Vec<f32>,
+}
+
+impl<H: AudioHandler> WorkletRenderer<H> {
+  pub fn new(handler: H, channels: usize) -> Self {
+    Self {
+      handler,
+      channels,
+      interleaved: vec![0.0; RENDER_QUANTUM * channels],
+      channel: vec![0.0; RENDER_QUANTUM],
+    }
+  }
+
+  /// Render the next quantum into the channels of the first output, returning whether to keep rendering.
+  pub fn process(&mut self, outputs: &Array) -> bool {
+    let output = match outputs.get(0).dyn_into::<Array>() {
+      Ok(output) => output,
+      Err(_) => return true,
+    };
+    let num_frames = output
+      .get(0)
+      .dyn_into::<Float32Array>()
+      .map(|channel| channel.length() as usize)
+      .unwrap_or(RENDER_QUANTUM)
+      .min(RENDER_QUANTUM);
+
+    let interleaved = &mut self.interleaved[0..num_frames * self.channels];
+    self.handler.process(interleaved, self.channels);
+
+    for (index, channel) in output.iter().take(self.channels).enumerate() {
+      if let Ok(channel) = channel.dyn_into::<Float32Array>() {
+        let samples = interleaved.iter().skip(index).step_by(self.channels);
+        for (sample, interleaved_sample) in self.channel.iter_mut().zip(samples) {
+          *sample = *interleaved_sample;
+        }
+        channel.copy_from(&self.channel[0..num_frames]);
+      }
+    }
+    true
+  }
+}
@@ -1,5 +1,6 @@
 use crate::float::Float;
 use crate::funcs::decibels::Decibels;
+use crate::funcs::pan_law;
 
 #[derive(Debug, Default)]
 pub struct DCA<F: Float> {
@@ -99,14 +100,9 @@ impl<F: Float> DCA<F> {
   fn update_pan(&mut self) {
     if self.pan_invalidated {
       self.pan_invalidated = false;
-      let pan_total = (self.pan + self.pan_mod).max(F::one().neg()).min(F::one());
-
-      let pi_over_four = F::PI / F::val(4.0);
-      let pan_left = (pi_over_four * (pan_total + F::one())).cos();
-      let pan_right = (pi_over_four * (pan_total + F::one())).sin();
-
-      self.pan_left = pan_left.max(F::zero()).min(F::one());
-      self.pan_right = pan_right.max(F::zero()).min(F::one());
+      let (pan_left, pan_right) = pan_law::equal_power(self.pan + self.pan_mod);
+      self.pan_left = pan_left;
+      self.pan_right = pan_right;
       // println!("pan = {:?}, {:?}", self.pan_left, self.pan_right);
     }
   }
@@ -0,0 +1,126 @@
+use crate::dynamics::time_coefficient;
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+/// Feed-forward compressor, reducing the gain of the signal above a threshold by a ratio.
+///
+/// It only computes the gain from the level of a detector signal, which is usually the signal itself,
+/// but can be any other one to use it as a sidechain. The gain reduction is smoothed in decibels.
+#[derive(Debug, Clone)]
+pub struct Compressor<F> {
+  sample_rate: F,
+  threshold: F,
+  ratio: F,
+  attack_coef: F,
+  release_coef: F,
+  makeup: F,
+  /// Decibels of gain reduction, as a positive value
+  reduction: F,
+}
+
+impl<F: Float> Compressor<F> {
+  pub const DEFAULT_THRESHOLD: f64 = -18.0;
+  pub const DEFAULT_RATIO: f64 = 4.0;
+  pub const DEFAULT_ATTACK: f64 = 0.01;
+  pub const DEFAULT_RELEASE: f64 = 0.1;
+
+  /// Level in decibels considered as silence, to avoid the logarithm of zero
+  const MIN_DECIBELS: f64 = -120.0;
+
+  pub fn new(sample_rate: F) -> Self {
+    Compressor {
+      sample_rate,
+      threshold: F::val(Self::DEFAULT_THRESHOLD),
+      ratio: F::val(Self::DEFAULT_RATIO),
+      attack_coef: time_coefficient(sample_rate, F::val(Self::DEFAULT_ATTACK)),
+      release_coef: time_coefficient(sample_rate, F::val(Self::DEFAULT_RELEASE)),
+      makeup: F::zero(),
+      reduction: F::zero(),
+    }
+  }
+
+  /// Level in decibels above which the gain is reduced
+  pub fn set_threshold(&mut self, decibels: F) {
+    self.threshold = decibels;
+  }
+
+  /// Decibels of the input above the threshold for every decibel of the output, from 1 onwards
+  pub fn set_ratio(&mut self, ratio: F) {
+    self.ratio = ratio.max(F::one());
+  }
+
+  pub fn set_attack(&mut self, attack_seconds: F) {
+    self.attack_coef = time_coefficient(self.sample_rate, attack_seconds);
+  }
+
+  pub fn set_release(&mut self, release_seconds: F) {
+    self.release_coef = time_coefficient(self.sample_rate, release_seconds);
+  }
+
+  /// Decibels of gain applied after the compression
+  pub fn set_makeup(&mut self, decibels: F) {
+    self.makeup = decibels;
+  }
+
+  pub fn reset(&mut self) {
+    self.reduction = F::zero();
+  }
+
+  /// Current gain reduction in decibels, as a positive value.
+  pub fn reduction(&self) -> F {
+    self.reduction
+  }
+
+  /// Gain to apply to the signal for the next level of the detector, including the makeup gain.
+  pub fn process(&mut self, detector: F) -> F {
+    let level = Decibels::from_amplitude(detector)
+      .value()
+      .max(F::val(Self::MIN_DECIBELS));
+    let over = (level - self.threshold).max(F::zero());
+    let target = over - over / self.ratio;
+    let coef = if target > self.reduction {
+      self.attack_coef
+    } else {
+      self.release_coef
+    };
+    self.reduction = target + coef * (self.reduction - target);
+    Decibels::new(self.makeup - self.reduction).to_amplitude()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Compressor;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_below_the_threshold() {
+    let mut compressor = Compressor::<f64>::new(1000.0);
+    compressor.set_makeup(6.0);
+    for _ in 0..1000 {
+      assert_approx_eq!(compressor.process(0.1), 10.0f64.powf(6.0 / 20.0));
+    }
+    assert_eq!(compressor.reduction(), 0.0);
+  }
+
+  #[test]
+  fn test_reduce_by_the_ratio() {
+    let mut compressor = Compressor::<f64>::new(1000.0);
+    compressor.set_threshold(-20.0);
+    compressor.set_ratio(4.0);
+    compressor.set_attack(0.0);
+    compressor.set_release(0.1);
+
+    // 12 dB above the threshold are reduced to 3 dB
+    assert_approx_eq!(compressor.reduction(), 0.0);
+    let gain = compressor.process(10.0f64.powf(-8.0 / 20.0));
+    assert_approx_eq!(compressor.reduction(), 9.0);
+    assert_approx_eq!(gain, 10.0f64.powf(-9.0 / 20.0));
+
+    // and released back slowly
+    for _ in 0..100 {
+      compressor.process(0.0);
+    }
+    assert_approx_eq!(compressor.reduction(), 9.0 * (-1.0f64).exp(), 1e-6);
+  }
+}
@@ -0,0 +1,165 @@
+use crate::dynamics::time_coefficient;
+use crate::float::Float;
+use crate::funcs::decibels::Decibels;
+
+/// Brickwall limiter that looks ahead of the signal, so the gain is reduced before the peaks arrive
+/// and the output never goes above the ceiling.
+///
+/// The signal is delayed by the lookahead, see [`Limiter::latency`]. The gain needed by every sample
+/// is held for the lookahead and then averaged along it, so it ramps down smoothly before a peak,
+/// while still being below the gain needed by every sample. It is released with a one pole smoother.
+#[derive(Debug, Clone)]
+pub struct Limiter<F> {
+  sample_rate: F,
+  ceiling: F,
+  release_coef: F,
+  /// Delay line for every channel
+  delays: Vec<Vec<F>>,
+  /// Gains needed by the samples in the delay lines
+  needed: Vec<F>,
+  /// Minimums of the needed gains along the lookahead, to be averaged
+  held: Vec<F>,
+  held_sum: F,
+  position: usize,
+  gain: F,
+}
+
+impl<F: Float> Limiter<F> {
+  pub const DEFAULT_LOOKAHEAD: f64 = 0.005;
+  pub const DEFAULT_RELEASE: f64 = 0.05;
+
+  /// Create a limiter for some number of channels, looking ahead of the signal for some seconds.
+  pub fn new(sample_rate: F, channels: usize, lookahead_seconds: F) -> Self {
+    let latency = (lookahead_seconds * sample_rate)
+      .round()
+      .to_usize()
+      .unwrap_or(0);
+    let length = latency + 1;
+    Limiter {
+      sample_rate,
+      ceiling: F::one(),
+      release_coef: time_coefficient(sample_rate, F::val(Self::DEFAULT_RELEASE)),
+      delays: vec![vec![F::zero(); length]; channels],
+      needed: vec![F::one(); length],
+      held: vec![F::one(); length],
+      held_sum: F::val(length),
+      position: 0,
+      gain: F::one(),
+    }
+  }
+
+  /// Samples the output is delayed from the input.
+  pub fn latency(&self) -> usize {
+    self.needed.len() - 1
+  }
+
+  /// Decibels that the output never goes above
+  pub fn set_ceiling(&mut self, decibels: F) {
+    self.ceiling = Decibels::new(decibels).to_amplitude();
+  }
+
+  pub fn set_release(&mut self, release_seconds: F) {
+    self.release_coef = time_coefficient(self.sample_rate, release_seconds);
+  }
+
+  pub fn reset(&mut self) {
+    for delay in self.delays.iter_mut() {
+      delay.iter_mut().for_each(|sample| *sample = F::zero());
+    }
+    self.needed.iter_mut().for_each(|gain| *gain = F::one());
+    self.held.iter_mut().for_each(|gain| *gain = F::one());
+    self.held_sum = F::val(self.held.len());
+    self.position = 0;
+    self.gain = F::one();
+  }
+
+  /// Current gain reduction in decibels, as a positive value.
+  pub fn reduction(&self) -> F {
+    -Decibels::from_amplitude(self.gain).value()
+  }
+
+  /// Limit a frame with a sample for every channel, in place,
+  /// returning the frame from [`Limiter::latency`] samples ago.
+  pub fn process(&mut self, frame: &mut [F]) {
+    let length = self.needed.len();
+    let position = self.position;
+
+    let peak = frame
+      .iter()
+      .fold(F::zero(), |peak, sample| peak.max(sample.abs()));
+    self.needed[position] = if peak > self.ceiling {
+      self.ceiling / peak
+    } else {
+      F::one()
+    };
+
+    let held = self
+      .needed
+      .iter()
+      .fold(F::one(), |held, needed| held.min(*needed));
+    self.held_sum = self.held_sum + held - self.held[position];
+    self.held[position] = held;
+    if position == 0 {
+      // avoid the rounding errors to accumulate in the running sum
+      self.held_sum = self.held.iter().fold(F::zero(), |sum, held| sum + *held);
+    }
+    let average = self.held_sum / F::val(length);
+
+    self.gain = if average < self.gain {
+      average
+    } else {
+      average + self.release_coef * (self.gain - average)
+    };
+
+    let oldest = (position + 1) % length;
+    for (sample, delay) in frame.iter_mut().zip(self.delays.iter_mut()) {
+      delay[position] = *sample;
+      *sample = delay[oldest] * self.gain;
+    }
+    self.position = oldest;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Limiter;
+
+  #[test]
+  fn test_latency() {
+    let mut limiter = Limiter::<f64>::new(1000.0, 1, 0.005);
+    assert_eq!(limiter.latency(), 5);
+    let outputs = (0..10)
+      .map(|index| {
+        let mut frame = [index as f64 * 0.01];
+        limiter.process(&mut frame);
+        frame[0]
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(&outputs[..5], &[0.0; 5]);
+    assert_eq!(&outputs[5..], &[0.0, 0.01, 0.02, 0.03, 0.04]);
+  }
+
+  #[test]
+  fn test_never_above_the_ceiling() {
+    let mut limiter = Limiter::<f64>::new(1000.0, 2, 0.01);
+    limiter.set_ceiling(-6.0);
+    let ceiling = 10.0f64.powf(-6.0 / 20.0);
+    let mut outputs = Vec::new();
+    for index in 0..2000 {
+      let phase = index as f64 * 0.05;
+      // a burst going beyond the ceiling, only in the right channel
+      let burst = if (500..1000).contains(&index) {
+        4.0
+      } else {
+        0.25
+      };
+      let mut frame = [phase.sin() * 0.25, phase.sin() * burst];
+      limiter.process(&mut frame);
+      assert!(frame.iter().all(|sample| sample.abs() <= ceiling + 1e-9));
+      outputs.push(frame[1]);
+    }
+    // the gain is reduced just ahead of the burst, and released after it
+    assert!(outputs[1200..].iter().any(|sample| sample.abs() > 0.2));
+    assert!(outputs[300..495].iter().any(|sample| sample.abs() > 0.2));
+  }
+}
@@ -0,0 +1,17 @@
+pub mod compressor;
+pub mod limiter;
+
+pub use compressor::Compressor;
+pub use limiter::Limiter;
+
+use crate::float::Float;
+
+/// Coefficient of a one pole smoother that covers `1 - 1/e` of a step in some time.
+fn time_coefficient<F: Float>(sample_rate: F, seconds: F) -> F {
+  let samples = seconds * sample_rate;
+  if samples <= F::zero() {
+    F::zero()
+  } else {
+    (-F::one() / samples).exp()
+  }
+}
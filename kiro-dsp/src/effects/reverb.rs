@@ -0,0 +1,215 @@
+use crate::float::Float;
+
+const NUM_LINES: usize = 8;
+
+/// Lengths of the delay lines at the largest size, in seconds, chosen to be mutually prime in samples
+/// for the usual sample rates so the echoes don't pile up.
+const LINE_SECONDS: [f64; NUM_LINES] = [
+  0.0297, 0.0371, 0.0411, 0.0437, 0.0533, 0.0599, 0.0677, 0.0731,
+];
+
+/// Scale of the lengths of the delay lines at the smallest size
+const MIN_SIZE_SCALE: f64 = 0.3;
+
+/// Stereo reverb made of a feedback delay network of eight lines mixed through a Hadamard matrix,
+/// with a low-pass filter in every line to damp the high frequencies as the sound decays.
+///
+/// The delay lines are allocated when created, for the largest size.
+pub struct FdnReverb<F: Float> {
+  sample_rate: F,
+  lines: Vec<Vec<F>>,
+  /// Position of the lines where the next sample is written
+  head: usize,
+  lengths: [usize; NUM_LINES],
+  gains: [F; NUM_LINES],
+  /// State of the damping filter of every line
+  damped: [F; NUM_LINES],
+  decay_time_sec: F,
+  damping: F,
+  size: F,
+}
+
+impl<F: Float> FdnReverb<F> {
+  pub fn new(sample_rate: F) -> Self {
+    let lines = LINE_SECONDS
+      .iter()
+      .map(|seconds| {
+        let length = (F::val(*seconds) * sample_rate)
+          .ceil()
+          .to_usize()
+          .unwrap_or(0);
+        vec![F::zero(); length.max(1) + 1]
+      })
+      .collect();
+    let mut reverb = Self {
+      sample_rate,
+      lines,
+      head: 0,
+      lengths: [1; NUM_LINES],
+      gains: [F::zero(); NUM_LINES],
+      damped: [F::zero(); NUM_LINES],
+      decay_time_sec: F::val(2.0),
+      damping: F::val(0.5),
+      size: F::one(),
+    };
+    reverb.update_lines();
+    reverb
+  }
+
+  /// Set the time for the reverb to decay by 60 dB
+  pub fn set_decay_time_sec(&mut self, time_sec: F) {
+    self.decay_time_sec = time_sec.max(F::val(0.01));
+    self.update_lines();
+  }
+
+  pub fn get_decay_time_sec(&self) -> F {
+    self.decay_time_sec
+  }
+
+  /// Set how much the high frequencies are damped, from 0.0 (bright) to 1.0 (dark)
+  pub fn set_damping(&mut self, damping: F) {
+    self.damping = damping.max(F::zero()).min(F::val(0.99));
+  }
+
+  pub fn get_damping(&self) -> F {
+    self.damping
+  }
+
+  /// Set the size of the room, from 0.0 (small) to 1.0 (large)
+  pub fn set_size(&mut self, size: F) {
+    self.size = size.max(F::zero()).min(F::one());
+    self.update_lines();
+  }
+
+  pub fn get_size(&self) -> F {
+    self.size
+  }
+
+  pub fn reset(&mut self) {
+    for line in self.lines.iter_mut() {
+      for sample in line.iter_mut() {
+        *sample = F::zero();
+      }
+    }
+    self.damped = [F::zero(); NUM_LINES];
+    self.head = 0;
+  }
+
+  /// Process a stereo sample, returning the reverberated one.
+  pub fn process(&mut self, left: F, right: F) -> (F, F) {
+    let mut outputs = [F::zero(); NUM_LINES];
+    for (index, output) in outputs.iter_mut().enumerate() {
+      let line = &self.lines[index];
+      let position = (self.head + line.len() - self.lengths[index]) % line.len();
+      let sample = line[position];
+      let damped = sample + (self.damped[index] - sample) * self.damping;
+      self.damped[index] = damped;
+      *output = damped;
+    }
+
+    let mut mixed = outputs;
+    hadamard(&mut mixed);
+
+    let (mut out_left, mut out_right) = (F::zero(), F::zero());
+    for (index, line) in self.lines.iter_mut().enumerate() {
+      let input = if index % 2 == 0 { left } else { right };
+      let head = self.head % line.len();
+      line[head] = input + mixed[index] * self.gains[index];
+      if index % 2 == 0 {
+        out_left = out_left + outputs[index];
+      } else {
+        out_right = out_right + outputs[index];
+      }
+    }
+    self.head = self.head.wrapping_add(1);
+
+    let scale = F::val(2.0 / NUM_LINES as f64);
+    (out_left * scale, out_right * scale)
+  }
+
+  /// Update the lengths of the lines for the size, and their gains to decay in the decay time.
+  fn update_lines(&mut self) {
+    let scale = F::val(MIN_SIZE_SCALE) + (F::one() - F::val(MIN_SIZE_SCALE)) * self.size;
+    for (index, line) in self.lines.iter().enumerate() {
+      let max_length = line.len() - 1;
+      let length = (F::val(max_length) * scale).round().to_usize().unwrap_or(1);
+      self.lengths[index] = length.max(1).min(max_length);
+      let seconds = F::val(self.lengths[index]) / self.sample_rate;
+      // -60 dB after the decay time
+      self.gains[index] = F::val(10.0).powf(F::val(-3.0) * seconds / self.decay_time_sec);
+    }
+  }
+}
+
+/// Mix the signals through a normalized Hadamard matrix, which preserves their energy.
+fn hadamard<F: Float>(values: &mut [F; NUM_LINES]) {
+  let mut step = 1;
+  while step < NUM_LINES {
+    for start in (0..NUM_LINES).step_by(step * 2) {
+      for index in start..start + step {
+        let (a, b) = (values[index], values[index + step]);
+        values[index] = a + b;
+        values[index + step] = a - b;
+      }
+    }
+    step *= 2;
+  }
+  let norm = F::val(NUM_LINES as f64).sqrt().recip();
+  for value in values.iter_mut() {
+    *value = *value * norm;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn energy(reverb: &mut FdnReverb<f64>, from: usize, to: usize) -> f64 {
+    let mut energy = 0.0;
+    for index in 0..to {
+      let input = if index == 0 { 1.0 } else { 0.0 };
+      let (left, right) = reverb.process(input, input);
+      if index >= from {
+        energy += left * left + right * right;
+      }
+    }
+    energy
+  }
+
+  #[test]
+  fn hadamard_preserves_the_energy() {
+    let mut values = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    hadamard(&mut values);
+    let energy = values.iter().map(|value| value * value).sum::<f64>();
+    assert_approx_eq!(energy, 1.0);
+  }
+
+  #[test]
+  fn decay_along_the_decay_time() {
+    let mut reverb = FdnReverb::new(1000.0);
+    reverb.set_decay_time_sec(1.0);
+    let early = energy(&mut reverb, 0, 500);
+    reverb.reset();
+    let late = energy(&mut reverb, 500, 1000);
+    assert!(early > 0.0);
+    assert!(late > 0.0);
+    assert!(late < early);
+
+    reverb.reset();
+    reverb.set_decay_time_sec(4.0);
+    let longer = energy(&mut reverb, 500, 1000);
+    assert!(longer > late);
+  }
+
+  #[test]
+  fn damp_the_high_frequencies() {
+    let mut reverb = FdnReverb::new(1000.0);
+    reverb.set_damping(0.0);
+    let bright = energy(&mut reverb, 200, 1000);
+    reverb.reset();
+    reverb.set_damping(0.9);
+    let dark = energy(&mut reverb, 200, 1000);
+    assert!(dark < bright);
+  }
+}
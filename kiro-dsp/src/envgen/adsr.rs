@@ -9,7 +9,9 @@ pub enum Mode {
 #[derive(Debug, Clone, Copy)]
 enum State {
   Off,
+  Delay,
   Attack,
+  Hold,
   Decay,
   Sustain,
   Release,
@@ -90,6 +92,12 @@ impl<F: Float> ADR<F> {
   }
 }
 
+/// Envelope generator with delay, attack, hold, decay, sustain and release stages (DAHDSR),
+/// where the attack, decay and release follow exponential curves shaped by the [`Mode`].
+///
+/// The delay and hold stages are skipped while their times are zero, leaving a classic ADSR.
+/// It can be driven by [`EnvGen::start`] and [`EnvGen::note_off`], or by a gate,
+/// which can change at any sample of a block with [`EnvGen::generate_block`].
 #[derive(Debug, Clone)]
 pub struct EnvGen<F: Float> {
   sample_rate: F,
@@ -97,7 +105,13 @@ pub struct EnvGen<F: Float> {
   reset_to_zero: bool,
   legato: bool,
   mode: Mode,
+  gate: bool,
 
+  /// Samples of the delay and hold stages
+  delay_samples: usize,
+  hold_samples: usize,
+  /// Samples left for the current delay or hold stage
+  stage_samples: usize,
   attack: ADR<F>,
   decay: ADR<F>,
   release: ADR<F>,
@@ -118,6 +132,10 @@ impl<F: Float> EnvGen<F> {
       reset_to_zero: false,
       legato: false,
       mode,
+      gate: false,
+      delay_samples: 0,
+      hold_samples: 0,
+      stage_samples: 0,
       attack: ADR::attack(sample_rate, mode, attack_time_ms),
       decay: ADR::decay(sample_rate, mode, decay_time_ms, sustain_level),
       release: ADR::release(sample_rate, mode, release_time_ms),
@@ -140,10 +158,20 @@ impl<F: Float> EnvGen<F> {
     self.release = ADR::release(self.sample_rate, mode, self.release.time_sec);
   }
 
+  /// Time from the start until the attack begins
+  pub fn set_delay_time_sec(&mut self, time_sec: F) {
+    self.delay_samples = self.time_samples(time_sec);
+  }
+
   pub fn set_attack_time_sec(&mut self, time_sec: F) {
     self.attack = ADR::attack(self.sample_rate, self.mode, time_sec);
   }
 
+  /// Time the envelope stays at the peak between the attack and the decay
+  pub fn set_hold_time_sec(&mut self, time_sec: F) {
+    self.hold_samples = self.time_samples(time_sec);
+  }
+
   pub fn set_decay_time_sec(&mut self, time_sec: F) {
     self.decay = ADR::decay(self.sample_rate, self.mode, time_sec, self.sustain_level);
   }
@@ -166,6 +194,11 @@ impl<F: Float> EnvGen<F> {
     }
   }
 
+  /// Keep going without restarting when started again while active
+  pub fn set_legato(&mut self, legato: bool) {
+    self.legato = legato;
+  }
+
   pub fn get_sustain_level(&self) -> F {
     self.sustain_level
   }
@@ -181,10 +214,31 @@ impl<F: Float> EnvGen<F> {
   pub fn start(&mut self) {
     if !self.legato || !self.is_active() {
       self.reset();
-      self.state = State::Attack;
+      self.stage_samples = self.delay_samples;
+      self.state = if self.delay_samples > 0 {
+        State::Delay
+      } else {
+        State::Attack
+      };
+    }
+  }
+
+  /// Start the envelope when the gate opens, and release it when it closes.
+  pub fn set_gate(&mut self, gate: bool) {
+    if gate != self.gate {
+      self.gate = gate;
+      if gate {
+        self.start();
+      } else {
+        self.note_off();
+      }
     }
   }
 
+  pub fn gate(&self) -> bool {
+    self.gate
+  }
+
   //  pub fn stop(&mut self) {
   //    self.state = State::Off;
   //  }
@@ -225,10 +279,27 @@ impl<F: Float> EnvGen<F> {
           self.output = F::zero();
         }
       }
+      State::Delay => {
+        self.stage_samples = self.stage_samples.saturating_sub(1);
+        if self.stage_samples == 0 {
+          self.state = State::Attack;
+        }
+      }
       State::Attack => {
         self.output = self.attack.offset + self.output * self.attack.coefficient;
         if self.output >= F::one() || self.attack.time_sec <= F::zero() {
           self.output = F::one();
+          self.stage_samples = self.hold_samples;
+          self.state = if self.hold_samples > 0 {
+            State::Hold
+          } else {
+            State::Decay
+          };
+        }
+      }
+      State::Hold => {
+        self.stage_samples = self.stage_samples.saturating_sub(1);
+        if self.stage_samples == 0 {
           self.state = State::Decay;
         }
       }
@@ -265,6 +336,18 @@ impl<F: Float> EnvGen<F> {
     self.output
   }
 
+  /// Generate a block of the envelope, with the gate changing at the given offsets within the block,
+  /// which need to be sorted. The change at an offset applies from the sample at that offset.
+  pub fn generate_block(&mut self, output: &mut [F], gates: &[(usize, bool)]) {
+    let mut gates = gates.iter().peekable();
+    for (offset, sample) in output.iter_mut().enumerate() {
+      while let Some((_, gate)) = gates.next_if(|(gate_offset, _)| *gate_offset <= offset) {
+        self.set_gate(*gate);
+      }
+      *sample = self.generate();
+    }
+  }
+
   pub fn biased_output(&self) -> F {
     self.output - self.sustain_level
   }
@@ -278,8 +361,104 @@ impl<F: Float> EnvGen<F> {
     )
   }
 
+  fn time_samples(&self, time_sec: F) -> usize {
+    (time_sec * self.sample_rate)
+      .round()
+      .max(F::zero())
+      .to_usize()
+      .unwrap_or(0)
+  }
+
   #[inline]
   fn shutdown_time_sec() -> F {
     F::val(0.01)
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::EnvGen;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn envelope() -> EnvGen<f64> {
+    let mut envelope = EnvGen::new(1000.0);
+    envelope.set_attack_time_sec(0.01);
+    envelope.set_decay_time_sec(0.01);
+    envelope.set_sustain_level(0.5);
+    envelope.set_release_time_sec(0.01);
+    envelope
+  }
+
+  fn generate(envelope: &mut EnvGen<f64>, num_samples: usize) -> Vec<f64> {
+    (0..num_samples).map(|_| envelope.generate()).collect()
+  }
+
+  #[test]
+  fn test_stages() {
+    let mut envelope = envelope();
+    assert!(envelope.is_off());
+    envelope.start();
+    let attack = generate(&mut envelope, 10);
+    assert!(attack.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_approx_eq!(attack[9], 1.0);
+
+    let decay = generate(&mut envelope, 10);
+    assert!(decay.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert_approx_eq!(decay[9], 0.5);
+    assert!(generate(&mut envelope, 100)
+      .iter()
+      .all(|level| *level == 0.5));
+
+    envelope.note_off();
+    assert!(!envelope.is_active());
+    let release = generate(&mut envelope, 10);
+    assert!(release.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert_eq!(release[9], 0.0);
+    assert!(envelope.is_off());
+  }
+
+  #[test]
+  fn test_delay_and_hold() {
+    let mut envelope = envelope();
+    envelope.set_delay_time_sec(0.005);
+    envelope.set_hold_time_sec(0.005);
+    envelope.start();
+    let output = generate(&mut envelope, 30);
+    assert!(output[..5].iter().all(|level| *level == 0.0));
+    assert_approx_eq!(output[14], 1.0);
+    assert!(output[15..20].iter().all(|level| *level == 1.0));
+    assert!(output[20] < 1.0);
+  }
+
+  #[test]
+  fn test_retrigger_and_legato() {
+    let mut envelope = envelope();
+    envelope.start();
+    generate(&mut envelope, 100);
+
+    // retriggered from the current level, without jumping to zero
+    envelope.start();
+    let output = envelope.generate();
+    assert!(output > 0.5 && output < 1.0);
+
+    let mut envelope = self::envelope();
+    envelope.set_legato(true);
+    envelope.start();
+    generate(&mut envelope, 100);
+    envelope.start();
+    assert_eq!(envelope.generate(), 0.5);
+  }
+
+  #[test]
+  fn test_sample_accurate_gate() {
+    let mut envelope = envelope();
+    let mut output = [0.0; 64];
+    envelope.generate_block(&mut output, &[(10, true), (40, false)]);
+    assert!(output[..10].iter().all(|level| *level == 0.0));
+    assert!(output[10] > 0.0);
+    assert_eq!(output[39], 0.5);
+    assert!(output[40] < 0.5);
+    assert_eq!(output[63], 0.0);
+    assert!(!envelope.gate());
+  }
+}
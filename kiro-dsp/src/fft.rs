@@ -0,0 +1,303 @@
+use crate::float::Float;
+
+/// In place radix-2 fast Fourier transform, scaled by the size when inverse.
+///
+/// The size of the real and imaginary parts must be the same power of two.
+/// The twiddle factors are computed on every call, so [`RealFft`] should be used to transform many blocks.
+pub fn fft<F: Float>(re: &mut [F], im: &mut [F], inverse: bool) {
+  assert!(
+    re.len().is_power_of_two(),
+    "The size must be a power of two"
+  );
+  let twiddles = twiddles(re.len());
+  transform(re, im, &twiddles, inverse);
+}
+
+/// Factors `e^(-2πik/size)` for `k` in `0..size/2`, as their real and imaginary parts.
+fn twiddles<F: Float>(size: usize) -> Vec<(F, F)> {
+  let angle = -F::val(2.0) * F::PI / F::val(size);
+  (0..size / 2)
+    .map(|index| {
+      let (im, re) = (angle * F::val(index)).sin_cos();
+      (re, im)
+    })
+    .collect()
+}
+
+fn transform<F: Float>(re: &mut [F], im: &mut [F], twiddles: &[(F, F)], inverse: bool) {
+  let size = re.len();
+  assert_eq!(im.len(), size);
+  assert_eq!(twiddles.len(), size / 2);
+
+  let mut target = 0;
+  for index in 1..size {
+    let mut bit = size >> 1;
+    while target & bit != 0 {
+      target ^= bit;
+      bit >>= 1;
+    }
+    target |= bit;
+    if index < target {
+      re.swap(index, target);
+      im.swap(index, target);
+    }
+  }
+
+  // the inverse transform uses the conjugate of the twiddles
+  let sign = if inverse { -F::one() } else { F::one() };
+  let mut length = 2;
+  while length <= size {
+    let stride = size / length;
+    for start in (0..size).step_by(length) {
+      for offset in 0..length / 2 {
+        let (w_re, w_im) = twiddles[offset * stride];
+        let w_im = w_im * sign;
+        let (even, odd) = (start + offset, start + offset + length / 2);
+        let odd_re = re[odd] * w_re - im[odd] * w_im;
+        let odd_im = re[odd] * w_im + im[odd] * w_re;
+        re[odd] = re[even] - odd_re;
+        im[odd] = im[even] - odd_im;
+        re[even] = re[even] + odd_re;
+        im[even] = im[even] + odd_im;
+      }
+    }
+    length *= 2;
+  }
+
+  if inverse {
+    let scale = F::val(size).recip();
+    for (re, im) in re.iter_mut().zip(im.iter_mut()) {
+      *re = *re * scale;
+      *im = *im * scale;
+    }
+  }
+}
+
+/// Window applied to a block of samples before transforming it,
+/// trading the width of the peaks for less leakage between the bins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+  Rectangular,
+  Hann,
+  Hamming,
+  Blackman,
+}
+
+impl Window {
+  /// Coefficients of the window in its periodic form, for blocks that follow each other.
+  pub fn coefficients<F: Float>(&self, size: usize) -> Vec<F> {
+    (0..size)
+      .map(|index| {
+        let phase = F::val(2.0) * F::PI * F::val(index) / F::val(size);
+        match self {
+          Window::Rectangular => F::one(),
+          Window::Hann => F::val(0.5) - F::val(0.5) * phase.cos(),
+          Window::Hamming => F::val(0.54) - F::val(0.46) * phase.cos(),
+          Window::Blackman => {
+            F::val(0.42) - F::val(0.5) * phase.cos() + F::val(0.08) * (F::val(2.0) * phase).cos()
+          }
+        }
+      })
+      .collect()
+  }
+
+  /// Mean of the coefficients, which is how much the window scales the amplitude of a sine.
+  pub fn gain<F: Float>(&self) -> F {
+    match self {
+      Window::Rectangular => F::one(),
+      Window::Hann => F::val(0.5),
+      Window::Hamming => F::val(0.54),
+      Window::Blackman => F::val(0.42),
+    }
+  }
+}
+
+/// Transform of blocks of real samples, keeping the buffers and the twiddle factors between them.
+#[derive(Debug, Clone)]
+pub struct RealFft<F> {
+  re: Vec<F>,
+  im: Vec<F>,
+  twiddles: Vec<(F, F)>,
+}
+
+impl<F: Float> RealFft<F> {
+  /// The size must be a power of two.
+  pub fn new(size: usize) -> Self {
+    assert!(size.is_power_of_two(), "The size must be a power of two");
+    RealFft {
+      re: vec![F::zero(); size],
+      im: vec![F::zero(); size],
+      twiddles: twiddles(size),
+    }
+  }
+
+  pub fn size(&self) -> usize {
+    self.re.len()
+  }
+
+  /// Bins from 0 Hz up to the Nyquist frequency, the rest being their mirror.
+  pub fn bins(&self) -> usize {
+    self.size() / 2 + 1
+  }
+
+  /// Transform a block of samples, padding with zeros when there are not enough of them.
+  pub fn forward<I>(&mut self, samples: I)
+  where
+    I: IntoIterator<Item = F>,
+  {
+    let mut samples = samples.into_iter();
+    for (re, im) in self.re.iter_mut().zip(self.im.iter_mut()) {
+      *re = samples.next().unwrap_or_else(F::zero);
+      *im = F::zero();
+    }
+    transform(&mut self.re, &mut self.im, &self.twiddles, false);
+  }
+
+  /// Transform the spectrum back into the samples, which are written into the output.
+  pub fn inverse(&mut self, output: &mut [F]) {
+    transform(&mut self.re, &mut self.im, &self.twiddles, true);
+    for (sample, re) in output.iter_mut().zip(self.re.iter()) {
+      *sample = *re;
+    }
+  }
+
+  /// Real and imaginary parts of every bin, including the mirrored ones.
+  pub fn spectrum(&self) -> (&[F], &[F]) {
+    (&self.re, &self.im)
+  }
+
+  /// Real and imaginary parts of every bin, to be modified before the inverse transform.
+  pub fn spectrum_mut(&mut self) -> (&mut [F], &mut [F]) {
+    (&mut self.re, &mut self.im)
+  }
+
+  /// Magnitude of the bins up to the Nyquist frequency, where a sine of amplitude 1 reaches 1.
+  pub fn magnitudes(&self, output: &mut [F]) {
+    let scale = F::val(2.0) / F::val(self.size());
+    let bins = self.re.iter().zip(self.im.iter()).take(self.bins());
+    for (magnitude, (re, im)) in output.iter_mut().zip(bins) {
+      *magnitude = re.hypot(*im) * scale;
+    }
+  }
+}
+
+/// Magnitude spectrum of blocks of samples, windowed before the transform.
+#[derive(Debug, Clone)]
+pub struct SpectrumAnalyzer<F> {
+  fft: RealFft<F>,
+  window: Vec<F>,
+  window_gain: F,
+}
+
+impl<F: Float> SpectrumAnalyzer<F> {
+  /// The size must be a power of two.
+  pub fn new(size: usize, window: Window) -> Self {
+    SpectrumAnalyzer {
+      fft: RealFft::new(size),
+      window: window.coefficients(size),
+      window_gain: window.gain(),
+    }
+  }
+
+  pub fn size(&self) -> usize {
+    self.fft.size()
+  }
+
+  pub fn bins(&self) -> usize {
+    self.fft.bins()
+  }
+
+  /// Write the magnitude of the bins of a block of samples up to the Nyquist frequency,
+  /// compensating the gain of the window, so a sine of amplitude 1 reaches 1.
+  pub fn magnitude_spectrum<I>(&mut self, samples: I, output: &mut [F])
+  where
+    I: IntoIterator<Item = F>,
+  {
+    let windowed = samples
+      .into_iter()
+      .zip(self.window.iter())
+      .map(|(sample, window)| sample * *window);
+    self.fft.forward(windowed);
+    self.fft.magnitudes(output);
+    let scale = self.window_gain.recip();
+    for magnitude in output.iter_mut() {
+      *magnitude = *magnitude * scale;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn sine(bin: f64, size: usize) -> impl Iterator<Item = f64> {
+    (0..size)
+      .map(move |index| (2.0 * std::f64::consts::PI * bin * index as f64 / size as f64).sin())
+  }
+
+  #[test]
+  fn test_forward_and_inverse() {
+    let input = (0..16).map(|index| (index % 5) as f64).collect::<Vec<_>>();
+    let mut fft = RealFft::new(16);
+    fft.forward(input.iter().copied());
+    let (re, im) = fft.spectrum();
+    assert_approx_eq!(re[0], input.iter().sum::<f64>());
+    // the spectrum of a real signal is symmetric
+    assert_approx_eq!(re[3], re[13]);
+    assert_approx_eq!(im[3], -im[13]);
+
+    let mut output = vec![0.0; 16];
+    fft.inverse(&mut output);
+    for (sample, expected) in output.iter().zip(input.iter()) {
+      assert_approx_eq!(sample, expected);
+    }
+  }
+
+  #[test]
+  fn test_fft_matches_the_transform_of_real_fft() {
+    let input = (0..32)
+      .map(|index| ((index * 7) % 11) as f64)
+      .collect::<Vec<_>>();
+    let mut fft_re = input.clone();
+    let mut fft_im = vec![0.0; 32];
+    fft(&mut fft_re, &mut fft_im, false);
+    let mut real_fft = RealFft::new(32);
+    real_fft.forward(input.iter().copied());
+    let (re, im) = real_fft.spectrum();
+    for bin in 0..32 {
+      assert_approx_eq!(fft_re[bin], re[bin]);
+      assert_approx_eq!(fft_im[bin], im[bin]);
+    }
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_fft_rejects_sizes_not_power_of_two() {
+    fft(&mut [0.0_f64; 12], &mut [0.0_f64; 12], false);
+  }
+
+  #[test]
+  fn test_magnitude_spectrum() {
+    let mut analyzer = SpectrumAnalyzer::new(256, Window::Hann);
+    let mut spectrum = vec![0.0; analyzer.bins()];
+    analyzer.magnitude_spectrum(sine(32.0, 256).map(|sample| 0.5 * sample), &mut spectrum);
+    assert_approx_eq!(spectrum[32], 0.5);
+    assert!(spectrum[40] < 1e-9);
+
+    let mut analyzer = SpectrumAnalyzer::new(256, Window::Rectangular);
+    analyzer.magnitude_spectrum(sine(20.0, 256), &mut spectrum);
+    assert_approx_eq!(spectrum[20], 1.0);
+    assert!(spectrum[21] < 1e-9);
+  }
+
+  #[test]
+  fn test_windows() {
+    for window in [Window::Hann, Window::Hamming, Window::Blackman] {
+      let coefficients = window.coefficients::<f64>(64);
+      let mean = coefficients.iter().sum::<f64>() / 64.0;
+      assert_approx_eq!(mean, window.gain::<f64>());
+      assert_approx_eq!(coefficients[32], 1.0);
+    }
+  }
+}
@@ -3,6 +3,7 @@ use crate::float::Float;
 #[derive(Debug)]
 pub struct FreqControl<F: Float> {
   freq: F,
+  /// Multiplier of the frequency for the modulation, which is 1 while unmodulated
   modulation: F,
   invalidated: bool,
 }
@@ -11,7 +12,7 @@ impl<F: Float> FreqControl<F> {
   pub fn new(freq: F) -> Self {
     FreqControl {
       freq,
-      modulation: F::zero(),
+      modulation: F::one(),
       invalidated: true,
     }
   }
@@ -57,3 +58,20 @@ impl<F: Float> FreqControl<F> {
     F::val(1_000)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keep_the_frequency_until_modulated() {
+    let mut freq = FreqControl::new(1_000.0_f32);
+    assert_eq!(freq.get_modulated_freq(), 1_000.0);
+
+    freq.set_semitones_modulation(12.0);
+    assert!((freq.get_modulated_freq() - 2_000.0).abs() < 1e-3);
+
+    freq.set_semitones_modulation(0.0);
+    assert_eq!(freq.get_modulated_freq(), 1_000.0);
+  }
+}
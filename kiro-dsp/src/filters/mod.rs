@@ -2,4 +2,5 @@ pub mod freq_control;
 pub mod oberheim_sem;
 pub mod q_control;
 pub mod saturation;
+pub mod svf;
 pub mod va_one_pole;
@@ -1,5 +1,15 @@
 use crate::float::Float;
 
+/// Amplitude for some decibels.
+pub fn db_to_lin<F: Float>(db: F) -> F {
+  F::val(10.0).powf(db / F::val(20.0))
+}
+
+/// Decibels for some amplitude, where its sign is ignored and silence is minus infinity.
+pub fn lin_to_db<F: Float>(amp: F) -> F {
+  F::val(20.0) * amp.abs().log10()
+}
+
 pub struct Decibels<F>(F);
 
 impl<F: Float> Decibels<F> {
@@ -8,14 +18,29 @@ impl<F: Float> Decibels<F> {
   }
 
   pub fn from_amplitude(amp: F) -> Decibels<F> {
-    Decibels(F::val(20.0) * amp.abs().log10())
+    Decibels(lin_to_db(amp))
   }
 
   pub fn to_amplitude(&self) -> F {
-    F::val(10.0).powf(self.0 / F::val(20.0))
+    db_to_lin(self.0)
   }
 
   pub fn value(&self) -> F {
     self.0
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_conversions() {
+    assert_approx_eq!(db_to_lin(-6.0_f64), 0.501187, 1e-6);
+    assert_approx_eq!(db_to_lin(20.0_f64), 10.0);
+    assert_approx_eq!(lin_to_db(-0.5_f64), -6.0206, 1e-4);
+    assert_eq!(lin_to_db(0.0_f64), f64::NEG_INFINITY);
+    assert_approx_eq!(lin_to_db(db_to_lin(-42.0_f64)), -42.0);
+  }
+}
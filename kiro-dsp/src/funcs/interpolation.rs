@@ -14,3 +14,12 @@ pub fn linear_interpolation<F: Float>(x1: F, x2: F, y1: F, y2: F, x: F) -> F {
     dx * y2 + (F::one() - dx) * y1
   }
 }
+
+/// 4-point, 3rd-order Hermite interpolation between `x0` and `x1` at the fractional position `t`.
+pub fn cubic_hermite_interpolation<F: Float>(xm1: F, x0: F, x1: F, x2: F, t: F) -> F {
+  let half = F::val(0.5);
+  let c1 = half * (x1 - xm1);
+  let c2 = xm1 - F::val(2.5) * x0 + F::val(2.0) * x1 - half * x2;
+  let c3 = half * (x2 - xm1) + F::val(1.5) * (x0 - x1);
+  ((c3 * t + c2) * t + c1) * t + x0
+}
@@ -1,5 +1,6 @@
 pub mod concave_transforms;
 pub mod decibels;
 pub mod interpolation;
+pub mod pan_law;
 pub mod parabolic_sine;
 pub mod signal_polarity;
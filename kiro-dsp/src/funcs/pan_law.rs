@@ -0,0 +1,54 @@
+use crate::float::Float;
+
+/// Gains for the left and right channels with a constant power pan law,
+/// so the perceived loudness doesn't change while panning, with a -3 dB attenuation at the center.
+///
+/// The pan goes from `-1` (left) to `1` (right).
+pub fn equal_power<F: Float>(pan: F) -> (F, F) {
+  let pan = pan.max(F::one().neg()).min(F::one());
+  let angle = F::PI / F::val(4.0) * (pan + F::one());
+  (angle.cos().max(F::zero()), angle.sin().max(F::zero()))
+}
+
+/// Gains for the left and right channels with a pan law between the constant power
+/// and the linear ones, with a -4.5 dB attenuation at the center.
+///
+/// The pan goes from `-1` (left) to `1` (right).
+pub fn minus_4_5_db<F: Float>(pan: F) -> (F, F) {
+  let pan = pan.max(F::one().neg()).min(F::one());
+  let (left, right) = equal_power(pan);
+  let half = F::val(0.5);
+  let (linear_left, linear_right) = (half - half * pan, half + half * pan);
+  ((left * linear_left).sqrt(), (right * linear_right).sqrt())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::funcs::decibels::lin_to_db;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_equal_power() {
+    let (left, right) = equal_power(0.0_f64);
+    assert_approx_eq!(lin_to_db(left), -3.0103, 1e-4);
+    assert_approx_eq!(left, right);
+    for pan in [-1.0_f64, -0.3, 0.6, 1.0] {
+      let (left, right) = equal_power(pan);
+      assert_approx_eq!(left * left + right * right, 1.0);
+    }
+    let (left, right) = equal_power(2.0_f64);
+    assert_approx_eq!(left, 0.0);
+    assert_approx_eq!(right, 1.0);
+  }
+
+  #[test]
+  fn test_minus_4_5_db() {
+    let (left, right) = minus_4_5_db(0.0_f64);
+    assert_approx_eq!(lin_to_db(left), -4.5154, 1e-4);
+    assert_approx_eq!(left, right);
+    let (left, right) = minus_4_5_db(-1.0_f64);
+    assert_approx_eq!(left, 1.0);
+    assert_approx_eq!(right, 0.0);
+  }
+}
@@ -0,0 +1,95 @@
+use crate::float::Float;
+use crate::funcs::decibels::db_to_lin;
+use crate::smoother::{LinearSteps, LinearStepsSmoother};
+
+/// Gain set in decibels and applied as an amplitude, smoothed to avoid clicks when it changes.
+pub struct Gain<F: Float> {
+  decibels: F,
+  amplitude: LinearStepsSmoother<F>,
+}
+
+impl<F: Float> Gain<F> {
+  /// Decibels at and below which the gain is silence
+  pub const MIN_DECIBELS: f64 = -96.0;
+  /// Seconds that a change of the gain lasts
+  pub const SMOOTHING_TIME: f64 = 0.01;
+
+  pub fn new(sample_rate: F, decibels: F) -> Self {
+    let smoothing = LinearSteps::from_time(sample_rate, F::val(Self::SMOOTHING_TIME));
+    Self {
+      decibels,
+      amplitude: LinearStepsSmoother::new(Self::to_amplitude(decibels), smoothing),
+    }
+  }
+
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    self.amplitude.set_sample_rate(sample_rate);
+  }
+
+  pub fn decibels(&self) -> F {
+    self.decibels
+  }
+
+  /// Set the gain in decibels, which is reached after the smoothing time
+  pub fn set_decibels(&mut self, decibels: F) {
+    if decibels != self.decibels {
+      self.decibels = decibels;
+      self.amplitude.set_target(Self::to_amplitude(decibels));
+    }
+  }
+
+  /// Jump to the gain without smoothing
+  pub fn reset(&mut self) {
+    self.amplitude.reset(Self::to_amplitude(self.decibels));
+  }
+
+  /// Next amplitude of the transition towards the gain
+  pub fn next_amplitude(&mut self) -> F {
+    self.amplitude.next_value()
+  }
+
+  pub fn process(&mut self, sample: F) -> F {
+    sample * self.next_amplitude()
+  }
+
+  pub fn process_slice(&mut self, buffer: &mut [F]) {
+    for sample in buffer.iter_mut() {
+      *sample = self.process(*sample);
+    }
+  }
+
+  /// Amplitude of a gain in decibels, without smoothing, being silence at and below the minimum.
+  pub fn to_amplitude(decibels: F) -> F {
+    if decibels <= F::val(Self::MIN_DECIBELS) {
+      F::zero()
+    } else {
+      db_to_lin(decibels)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_smooth_the_changes() {
+    let mut gain = Gain::new(1000.0, 0.0_f64);
+    assert_eq!(gain.process(0.5), 0.5);
+
+    gain.set_decibels(-6.0);
+    let mut buffer = [1.0; 20];
+    gain.process_slice(&mut buffer);
+    assert!(buffer.windows(2).take(9).all(|pair| pair[1] < pair[0]));
+    assert_approx_eq!(buffer[9], db_to_lin(-6.0));
+    assert!(buffer[10..].iter().all(|sample| *sample == db_to_lin(-6.0)));
+
+    assert_eq!(Gain::to_amplitude(-6.0_f64), db_to_lin(-6.0));
+    assert_eq!(Gain::to_amplitude(Gain::<f64>::MIN_DECIBELS - 1.0), 0.0);
+
+    gain.set_decibels(Gain::<f64>::MIN_DECIBELS);
+    gain.reset();
+    assert_eq!(gain.process(1.0), 0.0);
+  }
+}
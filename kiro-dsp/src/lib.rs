@@ -2,12 +2,17 @@
 
 pub mod blep;
 pub mod dca;
+pub mod dynamics;
 pub mod effects;
 pub mod envgen;
+pub mod fft;
 pub mod filters;
 pub mod float;
 pub mod funcs;
+pub mod gain;
 pub mod meters;
 pub mod oscillators;
+pub mod oversampler;
+pub mod resampler;
 pub mod smoother;
 pub mod waveforms;
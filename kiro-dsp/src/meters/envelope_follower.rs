@@ -0,0 +1,89 @@
+use crate::float::Float;
+
+/// Peak envelope follower with independent attack and release times.
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower<F> {
+  sample_rate: F,
+  attack_coef: F,
+  release_coef: F,
+  envelope: F,
+}
+
+impl<F: Float> EnvelopeFollower<F> {
+  pub fn new(sample_rate: F, attack_seconds: F, release_seconds: F) -> Self {
+    EnvelopeFollower {
+      sample_rate,
+      attack_coef: Self::coefficient(sample_rate, attack_seconds),
+      release_coef: Self::coefficient(sample_rate, release_seconds),
+      envelope: F::zero(),
+    }
+  }
+
+  pub fn set_attack(&mut self, attack_seconds: F) {
+    self.attack_coef = Self::coefficient(self.sample_rate, attack_seconds);
+  }
+
+  pub fn set_release(&mut self, release_seconds: F) {
+    self.release_coef = Self::coefficient(self.sample_rate, release_seconds);
+  }
+
+  pub fn reset(&mut self) {
+    self.envelope = F::zero();
+  }
+
+  pub fn process(&mut self, value: F) -> F {
+    let value = value.abs();
+    let coef = if value > self.envelope {
+      self.attack_coef
+    } else {
+      self.release_coef
+    };
+    self.envelope = value + coef * (self.envelope - value);
+    self.envelope
+  }
+
+  pub fn get(&self) -> F {
+    self.envelope
+  }
+
+  fn coefficient(sample_rate: F, seconds: F) -> F {
+    let samples = seconds * sample_rate;
+    if samples <= F::zero() {
+      F::zero()
+    } else {
+      (-F::one() / samples).exp()
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::EnvelopeFollower;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_instant() {
+    let mut follower = EnvelopeFollower::<f64>::new(1000.0, 0.0, 0.0);
+    assert_approx_eq!(follower.process(-0.5), 0.5);
+    assert_approx_eq!(follower.process(0.25), 0.25);
+  }
+
+  #[test]
+  fn test_attack_and_release() {
+    let mut follower = EnvelopeFollower::<f64>::new(1000.0, 0.01, 0.1);
+    for _ in 0..10 {
+      follower.process(1.0);
+    }
+    // after the attack time the envelope reaches 1 - 1/e
+    assert_approx_eq!(follower.get(), 1.0 - (-1.0f64).exp(), 1e-6);
+
+    for _ in 0..1000 {
+      follower.process(1.0);
+    }
+    let peak = follower.get();
+    for _ in 0..100 {
+      follower.process(0.0);
+    }
+    assert_approx_eq!(follower.get(), peak * (-1.0f64).exp(), 1e-6);
+  }
+}
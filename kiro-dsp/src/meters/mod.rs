@@ -1,4 +1,6 @@
+pub mod envelope_follower;
 pub mod level_meter;
 pub mod rms_online;
 
+pub use envelope_follower::EnvelopeFollower;
 pub use level_meter::PeakMeter;
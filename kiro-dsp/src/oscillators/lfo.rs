@@ -1,15 +1,50 @@
+use kiro_time::Tempo;
+
 use crate::float::Float;
 use crate::oscillators::clamp_modulo;
-use crate::oscillators::osc_waveform::OscWaveform;
 
 // TODO add another waveform for quadrature phase output
 
-// TODO Mode: free-running, synchronized, one-shot
+// TODO Mode: one-shot
+
+/// Waveforms of an [`Lfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+  Sine,
+  Triangle,
+  Saw,
+  Square,
+  /// A random value held along every cycle
+  SampleAndHold,
+  /// A random value every cycle, with smooth transitions between them
+  SmoothRandom,
+}
+
+/// Range of the output of an [`Lfo`], before applying the depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+  /// From -1 to 1
+  Bipolar,
+  /// From 0 to 1
+  Unipolar,
+}
 
+/// Rate of an [`Lfo`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate<F> {
+  /// Cycles per second
+  Free(F),
+  /// Beats per cycle, following the tempo
+  Synced(F),
+}
+
+/// Low frequency oscillator, free-running or synced to a tempo.
 #[derive(Debug, Clone)]
 pub struct Lfo<F: Float> {
-  waveform: OscWaveform<F>,
-  rate: F,
+  shape: Shape,
+  polarity: Polarity,
+  rate: Rate<F>,
+  tempo: F,
   phase: F,
   depth: F,
 
@@ -17,41 +52,67 @@ pub struct Lfo<F: Float> {
   phase_inc: F,
   phase_inc_invalidated: bool,
   inv_sample_rate: F,
+
+  random: u32,
+  /// Random values at the start and the end of the current cycle
+  held: F,
+  next: F,
 }
 
 impl<F: Float> Lfo<F> {
+  const RANDOM_SEED: u32 = 0x9e37_79b9;
+
   pub fn new(sample_rate: F) -> Self {
-    let waveform = OscWaveform::default();
-    let modulo = waveform.initial_modulo();
-    Lfo {
-      waveform,
-      rate: F::one(),
+    let mut lfo = Lfo {
+      shape: Shape::Sine,
+      polarity: Polarity::Bipolar,
+      rate: Rate::Free(F::one()),
+      tempo: F::val(120.0),
       phase: F::zero(),
       depth: F::one(),
 
-      modulo,
+      modulo: F::zero(),
       phase_inc: F::zero(),
       phase_inc_invalidated: true,
       inv_sample_rate: sample_rate.recip(),
-    }
+
+      random: Self::RANDOM_SEED,
+      held: F::zero(),
+      next: F::zero(),
+    };
+    lfo.reset();
+    lfo
   }
 
-  /// Set the waveform
-  pub fn set_waveform(&mut self, waveform: OscWaveform<F>) {
-    self.waveform = waveform;
-    self.reset_modulo();
-    // FIXME figure out how to avoid clips after changing the waveform and the module
+  /// Set the shape
+  pub fn set_shape(&mut self, shape: Shape) {
+    self.shape = shape;
   }
 
-  /// Set the rate
-  pub fn set_rate(&mut self, rate: F) {
+  pub fn shape(&self) -> Shape {
+    self.shape
+  }
+
+  /// Set the polarity
+  pub fn set_polarity(&mut self, polarity: Polarity) {
+    self.polarity = polarity;
+  }
+
+  /// Set the rate, either in hertz or in beats per cycle
+  pub fn set_rate(&mut self, rate: Rate<F>) {
     self.rate = rate;
     self.phase_inc_invalidated = true;
   }
 
-  /// Set the phase
+  /// Set the tempo followed by a synced rate
+  pub fn set_tempo(&mut self, tempo: Tempo) {
+    self.tempo = F::val(f64::from(tempo));
+    self.phase_inc_invalidated = true;
+  }
+
+  /// Set the phase offset, as a fraction of a cycle
   pub fn set_phase(&mut self, phase: F) {
-    self.phase = phase;
+    self.phase = phase - phase.floor();
   }
 
   /// Set the depth (amplitude)
@@ -65,23 +126,151 @@ impl<F: Float> Lfo<F> {
     self.phase_inc_invalidated = true;
   }
 
-  /// Reset the LFO
+  /// Cycles per second for the current rate and tempo
+  pub fn frequency(&self) -> F {
+    match self.rate {
+      Rate::Free(frequency) => frequency,
+      Rate::Synced(beats) => self.tempo / (F::val(60.0) * beats),
+    }
+  }
+
+  /// Reset the LFO to the start of the cycle, and the random values to their initial sequence
   pub fn reset(&mut self) {
-    self.reset_modulo();
+    self.modulo = F::zero();
+    self.random = Self::RANDOM_SEED;
+    self.held = self.next_random();
+    self.next = self.next_random();
   }
 
   /// Generate the next value
   pub fn generate(&mut self) -> F {
     if self.phase_inc_invalidated {
-      self.phase_inc = self.rate * self.inv_sample_rate;
+      self.phase_inc = self.frequency() * self.inv_sample_rate;
+      self.phase_inc_invalidated = false;
     }
 
-    let signal = self.waveform.generate(self.modulo, self.phase_inc);
-    self.modulo = clamp_modulo(self.modulo + self.phase_inc);
+    let signal = self.shape_value(clamp_modulo(self.modulo + self.phase));
+    self.modulo = self.modulo + self.phase_inc;
+    if self.modulo >= F::one() {
+      self.modulo = self.modulo.fract();
+      self.held = self.next;
+      self.next = self.next_random();
+    }
+
+    let signal = match self.polarity {
+      Polarity::Bipolar => signal,
+      Polarity::Unipolar => (signal + F::one()) * F::val(0.5),
+    };
     signal * self.depth
   }
 
-  fn reset_modulo(&mut self) {
-    self.modulo = clamp_modulo(self.waveform.initial_modulo() + self.phase);
+  /// Bipolar value of the shape at some position of the cycle.
+  /// The random shapes follow the running cycle, ignoring the phase offset.
+  fn shape_value(&self, modulo: F) -> F {
+    let two = F::val(2.0);
+    match self.shape {
+      Shape::Sine => (two * F::PI * modulo).sin(),
+      Shape::Triangle => F::one() - F::val(4.0) * (modulo - F::val(0.5)).abs(),
+      Shape::Saw => two * modulo - F::one(),
+      Shape::Square if modulo < F::val(0.5) => F::one(),
+      Shape::Square => F::one().neg(),
+      Shape::SampleAndHold => self.held,
+      Shape::SmoothRandom => {
+        // smoothstep, for a continuous slope at the joins
+        let x = self.modulo;
+        let weight = x * x * (F::val(3.0) - two * x);
+        self.held + (self.next - self.held) * weight
+      }
+    }
+  }
+
+  /// Next value of a xorshift generator, in the `-1..1` range.
+  fn next_random(&mut self) -> F {
+    let mut x = self.random;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.random = x;
+    F::val(f64::from(x) / f64::from(u32::MAX) * 2.0 - 1.0)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn generate(lfo: &mut Lfo<f64>, len: usize) -> Vec<f64> {
+    (0..len).map(|_| lfo.generate()).collect()
+  }
+
+  #[test]
+  fn test_shapes() {
+    let mut lfo = Lfo::new(8.0);
+    lfo.set_shape(Shape::Triangle);
+    assert_eq!(
+      generate(&mut lfo, 8),
+      vec![-1.0, -0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5]
+    );
+
+    lfo.set_shape(Shape::Saw);
+    lfo.set_phase(0.25);
+    assert_eq!(generate(&mut lfo, 4), vec![-0.5, -0.25, 0.0, 0.25]);
+
+    lfo.reset();
+    lfo.set_phase(0.0);
+    lfo.set_shape(Shape::Square);
+    lfo.set_polarity(Polarity::Unipolar);
+    lfo.set_depth(0.5);
+    assert_eq!(
+      generate(&mut lfo, 8),
+      vec![0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]
+    );
+
+    lfo.set_shape(Shape::Sine);
+    lfo.set_polarity(Polarity::Bipolar);
+    lfo.set_depth(1.0);
+    let output = generate(&mut lfo, 8);
+    assert_approx_eq!(output[2], 1.0);
+    assert_approx_eq!(output[6], -1.0);
+  }
+
+  #[test]
+  fn test_random_shapes() {
+    let mut lfo = Lfo::new(8.0);
+    lfo.set_rate(Rate::Free(2.0));
+    lfo.set_shape(Shape::SampleAndHold);
+    let output = generate(&mut lfo, 8);
+    assert!(output[0..4].iter().all(|value| *value == output[0]));
+    assert!(output[4..8].iter().all(|value| *value == output[4]));
+    assert_ne!(output[0], output[4]);
+    let first = output[0];
+
+    lfo.reset();
+    lfo.set_rate(Rate::Free(0.01));
+    lfo.set_shape(Shape::SmoothRandom);
+    let output = generate(&mut lfo, 2000);
+    // starts from the same value than the sample and hold, without jumps afterwards
+    assert_eq!(output[0], first);
+    assert!(output.iter().all(|value| (-1.0..=1.0).contains(value)));
+    assert!(output
+      .windows(2)
+      .all(|pair| (pair[1] - pair[0]).abs() < 0.01));
+    assert_ne!(output[0], output[800]);
+  }
+
+  #[test]
+  fn test_sync_to_the_tempo() {
+    let mut lfo = Lfo::new(8.0_f64);
+    lfo.set_shape(Shape::Square);
+    lfo.set_tempo(Tempo::new(240));
+    // a cycle every beat of a quarter of a second
+    lfo.set_rate(Rate::Synced(1.0));
+    assert_approx_eq!(lfo.frequency(), 4.0);
+    assert_eq!(generate(&mut lfo, 4), vec![1.0, -1.0, 1.0, -1.0]);
+
+    lfo.set_tempo(Tempo::new(120));
+    lfo.set_rate(Rate::Synced(0.5));
+    assert_approx_eq!(lfo.frequency(), 4.0);
   }
 }
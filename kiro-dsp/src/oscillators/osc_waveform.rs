@@ -1,9 +1,11 @@
 use crate::float::Float;
+use crate::waveforms::pulse_blep::PulseBlep;
 use crate::waveforms::saw_blep::SawBlep;
 use crate::waveforms::saw_trivial::SawTrivial;
 use crate::waveforms::sine_parabolic::SineParabolic;
 use crate::waveforms::triangle_dpw2x::TriangleDpw2x;
 use crate::waveforms::triangle_trivial::TriangleTrivial;
+use crate::waveforms::wavetable::WavetableWaveform;
 use crate::waveforms::Waveform;
 
 #[derive(Debug, Clone)]
@@ -11,8 +13,10 @@ pub enum OscWaveform<F: Float> {
   SineParabolic(SineParabolic),
   SawTrivial(SawTrivial),
   SawBlep(SawBlep<F>),
+  PulseBlep(PulseBlep<F>),
   TriangleTrivial(TriangleTrivial),
   TriangleDpw2x(TriangleDpw2x<F>),
+  Wavetable(WavetableWaveform<F>),
 }
 
 impl<F: Float> Default for OscWaveform<F> {
@@ -27,8 +31,10 @@ impl<F: Float> OscWaveform<F> {
       OscWaveform::SineParabolic(wf) => wf.initial_modulo(),
       OscWaveform::SawTrivial(wf) => wf.initial_modulo(),
       OscWaveform::SawBlep(wf) => wf.initial_modulo(),
+      OscWaveform::PulseBlep(wf) => wf.initial_modulo(),
       OscWaveform::TriangleTrivial(wf) => wf.initial_modulo(),
       OscWaveform::TriangleDpw2x(wf) => wf.initial_modulo(),
+      OscWaveform::Wavetable(wf) => wf.initial_modulo(),
     }
   }
 
@@ -37,8 +43,10 @@ impl<F: Float> OscWaveform<F> {
       OscWaveform::SineParabolic(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::SawTrivial(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::SawBlep(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::PulseBlep(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::TriangleTrivial(wf) => wf.generate(modulo, phase_inc),
       OscWaveform::TriangleDpw2x(wf) => wf.generate(modulo, phase_inc),
+      OscWaveform::Wavetable(wf) => wf.generate(modulo, phase_inc),
     }
   }
 }
@@ -12,6 +12,8 @@ pub struct PitchedOscillator<F: Float> {
   amp_mod: F,
 
   modulo: F,
+  /// Whether the phase wrapped around the end of the cycle when generating the last value
+  wrapped: bool,
   phase_inc: F,
   phase_inc_invalidated: bool,
   inv_sample_rate: F,
@@ -31,6 +33,7 @@ impl<F: Float> PitchedOscillator<F> {
       amp_mod: F::zero(),
 
       modulo,
+      wrapped: false,
       phase_inc: F::zero(),
       phase_inc_invalidated: true,
       inv_sample_rate: sample_rate.recip(),
@@ -45,6 +48,11 @@ impl<F: Float> PitchedOscillator<F> {
     // self.phase_inc_invalidated = true; // TODO really necessary ???
   }
 
+  /// Get the waveform, to update its own settings
+  pub fn waveform_mut(&mut self) -> &mut OscWaveform<F> {
+    &mut self.waveform
+  }
+
   /// Set the pitch frequency
   pub fn set_pitch_frequency(&mut self, pitch_freq: F) {
     self.pitch_freq = pitch_freq;
@@ -142,6 +150,16 @@ impl<F: Float> PitchedOscillator<F> {
     self.modulo = self.waveform.initial_modulo();
   }
 
+  /// Restart the cycle, as when hard synced to another oscillator
+  pub fn restart_cycle(&mut self) {
+    self.modulo = F::zero();
+  }
+
+  /// Whether the last generated value completed a cycle, to hard sync other oscillators
+  pub fn has_wrapped(&self) -> bool {
+    self.wrapped
+  }
+
   /// Generate the next value
   pub fn generate(&mut self) -> F {
     if self.phase_inc_invalidated {
@@ -149,7 +167,9 @@ impl<F: Float> PitchedOscillator<F> {
     }
 
     let signal = self.waveform.generate(self.modulo, self.phase_inc);
-    self.modulo = clamp_modulo(self.modulo + self.phase_inc);
+    let modulo = self.modulo + self.phase_inc;
+    self.wrapped = modulo >= F::one() || modulo < F::zero();
+    self.modulo = clamp_modulo(modulo);
     signal * (self.amplitude + self.amp_mod)
   }
 
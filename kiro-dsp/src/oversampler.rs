@@ -0,0 +1,325 @@
+use crate::float::Float;
+
+/// Times the sample rate is raised by an [`Oversampler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Factor {
+  X2,
+  X4,
+}
+
+impl Factor {
+  pub fn times(&self) -> usize {
+    match self {
+      Factor::X2 => 2,
+      Factor::X4 => 4,
+    }
+  }
+}
+
+/// Runs a process at a multiple of the sample rate, so the harmonics generated by nonlinear
+/// processes such as waveshapers or saturating filters fold back much less into the audible band.
+///
+/// The signal is upsampled and downsampled by cascaded stages of 2x, each one with a half-band
+/// filter in its polyphase form, so the taps that are zero or hit the stuffed zeros are skipped.
+#[derive(Debug, Clone)]
+pub struct Oversampler<F> {
+  factor: Factor,
+  stages: Vec<(HalfBandUp<F>, HalfBandDown<F>)>,
+}
+
+impl<F: Float> Oversampler<F> {
+  /// Taps at each side of the center of the half-band filter of the first stage
+  const FIRST_STAGE_SIDE: usize = 15;
+  /// Taps at each side of the center for the next stages, which have a wider transition band
+  const NEXT_STAGES_SIDE: usize = 7;
+
+  pub fn new(factor: Factor) -> Self {
+    let stages = (0..factor.times().trailing_zeros())
+      .map(|stage| {
+        let side = if stage == 0 {
+          Self::FIRST_STAGE_SIDE
+        } else {
+          Self::NEXT_STAGES_SIDE
+        };
+        let taps = half_band_taps(side);
+        (HalfBandUp::new(&taps), HalfBandDown::new(&taps))
+      })
+      .collect();
+    Oversampler { factor, stages }
+  }
+
+  pub fn factor(&self) -> Factor {
+    self.factor
+  }
+
+  /// Samples of the original rate the output is delayed from the input, which can be fractional.
+  pub fn latency(&self) -> F {
+    // every stage delays the signal by its side, both when upsampling and downsampling,
+    // in samples of its own rate
+    let mut rate = F::one();
+    let mut latency = F::zero();
+    for (up, _) in self.stages.iter() {
+      rate = rate * F::val(2.0);
+      latency = latency + F::val(2 * up.side) / rate;
+    }
+    latency
+  }
+
+  pub fn reset(&mut self) {
+    for (up, down) in self.stages.iter_mut() {
+      up.reset();
+      down.reset();
+    }
+  }
+
+  /// Upsample a sample, run the process for every sample at the higher rate,
+  /// and return the result downsampled back.
+  pub fn process<P>(&mut self, input: F, mut process: P) -> F
+  where
+    P: FnMut(F) -> F,
+  {
+    let mut buffer = [F::zero(); 4];
+    let mut upsampled = [F::zero(); 4];
+    buffer[0] = input;
+    let mut len = 1;
+    for (up, _) in self.stages.iter_mut() {
+      for (index, sample) in buffer[..len].iter().enumerate() {
+        let [first, second] = up.process(*sample);
+        upsampled[2 * index] = first;
+        upsampled[2 * index + 1] = second;
+      }
+      len *= 2;
+      buffer[..len].copy_from_slice(&upsampled[..len]);
+    }
+
+    for sample in buffer[..len].iter_mut() {
+      *sample = process(*sample);
+    }
+
+    for (_, down) in self.stages.iter_mut().rev() {
+      len /= 2;
+      for index in 0..len {
+        buffer[index] = down.process([buffer[2 * index], buffer[2 * index + 1]]);
+      }
+    }
+    buffer[0]
+  }
+
+  /// Run the process oversampled for a buffer, in place.
+  pub fn process_slice<P>(&mut self, buffer: &mut [F], mut process: P)
+  where
+    P: FnMut(F) -> F,
+  {
+    for sample in buffer.iter_mut() {
+      *sample = self.process(*sample, &mut process);
+    }
+  }
+}
+
+/// Taps of a half-band low pass filter with a Blackman window, with some taps at each side
+/// of the center, which needs to be odd. The taps at an even distance from the center are zero.
+fn half_band_taps<F: Float>(side: usize) -> Vec<F> {
+  debug_assert!(side % 2 == 1);
+  let len = 2 * side + 1;
+  let mut taps = (0..len)
+    .map(|index| {
+      let distance = index as f64 - side as f64;
+      if index == side {
+        0.5
+      } else if distance % 2.0 == 0.0 {
+        0.0
+      } else {
+        let phase = 2.0 * std::f64::consts::PI * index as f64 / (len - 1) as f64;
+        let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+        let x = std::f64::consts::FRAC_PI_2 * distance;
+        x.sin() / (std::f64::consts::PI * distance) * window
+      }
+    })
+    .collect::<Vec<_>>();
+  // the side taps sum a half, for a unity gain at DC
+  let sum = taps.iter().sum::<f64>() - 0.5;
+  for (index, tap) in taps.iter_mut().enumerate() {
+    if index != side {
+      *tap *= 0.5 / sum;
+    }
+  }
+  taps.into_iter().map(F::val).collect()
+}
+
+/// Upsampler by 2, from the side taps of a half-band filter.
+#[derive(Debug, Clone)]
+struct HalfBandUp<F> {
+  side: usize,
+  /// Non-zero taps except the center one, applied to the history for the even output samples
+  taps: Vec<F>,
+  history: Vec<F>,
+  position: usize,
+}
+
+impl<F: Float> HalfBandUp<F> {
+  fn new(taps: &[F]) -> Self {
+    let side = taps.len() / 2;
+    // the taps at an odd index from the first one, in the even phase
+    let taps = taps.iter().step_by(2).rev().copied().collect::<Vec<_>>();
+    let len = taps.len();
+    HalfBandUp {
+      side,
+      taps,
+      history: vec![F::zero(); len],
+      position: 0,
+    }
+  }
+
+  fn reset(&mut self) {
+    self
+      .history
+      .iter_mut()
+      .for_each(|sample| *sample = F::zero());
+    self.position = 0;
+  }
+
+  /// Upsample a sample into the pair of samples of the higher rate.
+  fn process(&mut self, input: F) -> [F; 2] {
+    let len = self.history.len();
+    self.history[self.position] = input;
+    self.position = (self.position + 1) % len;
+    // the history from the oldest sample to the newest
+    let (newest, oldest) = self.history.split_at(self.position);
+    let even = oldest
+      .iter()
+      .chain(newest.iter())
+      .zip(self.taps.iter())
+      .fold(F::zero(), |sum, (sample, tap)| sum + *sample * *tap);
+    // the center tap is a half, leaving just a delayed input once doubled for the stuffed zeros
+    let center = self.history[(self.position + len - 1 - (self.side - 1) / 2) % len];
+    [even * F::val(2.0), center]
+  }
+}
+
+/// Downsampler by 2, from the taps of a half-band filter.
+#[derive(Debug, Clone)]
+struct HalfBandDown<F> {
+  /// Side taps, applied to the history of the first sample of every pair
+  taps: Vec<F>,
+  history: Vec<F>,
+  position: usize,
+  /// The second sample of every pair, delayed to meet the center tap
+  delay: Vec<F>,
+  delay_position: usize,
+}
+
+impl<F: Float> HalfBandDown<F> {
+  fn new(taps: &[F]) -> Self {
+    let side = taps.len() / 2;
+    let taps = taps.iter().step_by(2).rev().copied().collect::<Vec<_>>();
+    let len = taps.len();
+    HalfBandDown {
+      taps,
+      history: vec![F::zero(); len],
+      position: 0,
+      delay: vec![F::zero(); side / 2 + 1],
+      delay_position: 0,
+    }
+  }
+
+  fn reset(&mut self) {
+    self
+      .history
+      .iter_mut()
+      .for_each(|sample| *sample = F::zero());
+    self.position = 0;
+    self.delay.iter_mut().for_each(|sample| *sample = F::zero());
+    self.delay_position = 0;
+  }
+
+  fn process(&mut self, [first, second]: [F; 2]) -> F {
+    self.history[self.position] = first;
+    self.position = (self.position + 1) % self.history.len();
+    let (newest, oldest) = self.history.split_at(self.position);
+    let sum = oldest
+      .iter()
+      .chain(newest.iter())
+      .zip(self.taps.iter())
+      .fold(F::zero(), |sum, (sample, tap)| sum + *sample * *tap);
+
+    let delayed = self.delay[self.delay_position];
+    self.delay[self.delay_position] = second;
+    self.delay_position = (self.delay_position + 1) % self.delay.len();
+
+    sum + delayed * F::val(0.5)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  fn sine(bin: usize, size: usize) -> impl Iterator<Item = f64> {
+    (0..).map(move |index| {
+      (2.0 * std::f64::consts::PI * bin as f64 * index as f64 / size as f64).sin()
+    })
+  }
+
+  /// Magnitude of a bin of the spectrum of some samples
+  fn magnitude(samples: &[f64], bin: usize) -> f64 {
+    let size = samples.len() as f64;
+    let (re, im) = samples
+      .iter()
+      .enumerate()
+      .fold((0.0, 0.0), |(re, im), (index, sample)| {
+        let phase = 2.0 * std::f64::consts::PI * bin as f64 * index as f64 / size;
+        (re + sample * phase.cos(), im - sample * phase.sin())
+      });
+    re.hypot(im) * 2.0 / size
+  }
+
+  #[test]
+  fn test_latency() {
+    let mut oversampler = Oversampler::<f64>::new(Factor::X2);
+    assert_approx_eq!(oversampler.latency(), 15.0);
+    let output = sine(10, 1000)
+      .take(300)
+      .map(|sample| oversampler.process(sample, |sample| sample))
+      .collect::<Vec<_>>();
+    for (delayed, expected) in output[100..].iter().zip(sine(10, 1000).skip(85)) {
+      assert_approx_eq!(*delayed, expected, 1e-3);
+    }
+
+    let mut oversampler = Oversampler::<f64>::new(Factor::X4);
+    assert_approx_eq!(oversampler.latency(), 18.5);
+    let mut output = [1.0; 100];
+    oversampler.process_slice(&mut output, |sample| sample);
+    assert_approx_eq!(output[99], 1.0, 1e-6);
+  }
+
+  #[test]
+  fn test_reduce_the_aliasing() {
+    let size = 4096;
+    let shape = |sample: f64| (3.0 * sample).tanh();
+    let input = sine(480, size).take(size + 100).collect::<Vec<_>>();
+    // the 7th harmonic folds back into the bin 736
+    let naive = input[100..]
+      .iter()
+      .map(|sample| shape(*sample))
+      .collect::<Vec<_>>();
+    let mut oversampler = Oversampler::new(Factor::X4);
+    let mut oversampled = input.clone();
+    oversampler.process_slice(&mut oversampled, shape);
+
+    let (naive_alias, oversampled_alias) =
+      (magnitude(&naive, 736), magnitude(&oversampled[100..], 736));
+    assert!(
+      oversampled_alias * 10.0 < naive_alias,
+      "{} {}",
+      oversampled_alias,
+      naive_alias
+    );
+    // while the fundamental is kept
+    assert_approx_eq!(
+      magnitude(&oversampled[100..], 480),
+      magnitude(&naive, 480),
+      0.01
+    );
+  }
+}
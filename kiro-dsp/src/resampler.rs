@@ -0,0 +1,204 @@
+use crate::filters::svf::StateVariableFilter;
+use crate::float::Float;
+use crate::funcs::interpolation::cubic_hermite_interpolation;
+
+/// Streaming sample rate converter using cubic Hermite interpolation.
+///
+/// It is meant to sit at the boundaries between parts running at different sample rates,
+/// for example a device that changed its rate in the middle of a session.
+///
+/// When downsampling, the input goes first through a low-pass filter below the Nyquist frequency
+/// of the output, so the frequencies that don't fit in the output rate don't fold back as aliasing.
+pub struct Resampler<F: Float> {
+  step: F,
+  position: F,
+  history: [F; 4],
+  anti_alias: Option<[StateVariableFilter<F>; 2]>,
+}
+
+impl<F: Float> Resampler<F> {
+  /// Delay introduced by the interpolation, in input samples.
+  pub const LATENCY: usize = 2;
+
+  /// Cutoff of the anti-aliasing filter, relative to the output sample rate
+  const ANTI_ALIAS_CUTOFF: f64 = 0.45;
+  /// Q factors of the two sections of a fourth order Butterworth low-pass
+  const ANTI_ALIAS_Q: [f64; 2] = [0.541_196, 1.306_563];
+
+  pub fn new(input_rate: F, output_rate: F) -> Self {
+    Resampler {
+      step: input_rate / output_rate,
+      position: F::zero(),
+      history: [F::zero(); 4],
+      anti_alias: Self::anti_alias(input_rate, output_rate),
+    }
+  }
+
+  pub fn set_rates(&mut self, input_rate: F, output_rate: F) {
+    self.step = input_rate / output_rate;
+    self.anti_alias = Self::anti_alias(input_rate, output_rate);
+  }
+
+  fn anti_alias(input_rate: F, output_rate: F) -> Option<[StateVariableFilter<F>; 2]> {
+    if output_rate < input_rate {
+      let cutoff = output_rate * F::val(Self::ANTI_ALIAS_CUTOFF);
+      let [q1, q2] = Self::ANTI_ALIAS_Q;
+      Some([
+        StateVariableFilter::new(input_rate, cutoff, F::val(q1)),
+        StateVariableFilter::new(input_rate, cutoff, F::val(q2)),
+      ])
+    } else {
+      None
+    }
+  }
+
+  /// Ratio between the output and the input sample rates.
+  pub fn ratio(&self) -> F {
+    F::one() / self.step
+  }
+
+  /// Maximum number of output samples for a number of input samples.
+  pub fn max_output_len(&self, input_len: usize) -> usize {
+    (F::val(input_len) / self.step)
+      .ceil()
+      .to_usize()
+      .unwrap_or(0)
+      + 1
+  }
+
+  pub fn reset(&mut self) {
+    self.position = F::zero();
+    self.history = [F::zero(); 4];
+    if let Some(filters) = self.anti_alias.as_mut() {
+      for filter in filters.iter_mut() {
+        filter.reset();
+      }
+    }
+  }
+
+  /// Push the next input sample, and emit as many output samples as they fit until it.
+  pub fn process<E>(&mut self, input: F, mut emit: E)
+  where
+    E: FnMut(F),
+  {
+    let input = match self.anti_alias.as_mut() {
+      Some([first, second]) => second.process(first.process(input)),
+      None => input,
+    };
+    self.history = [self.history[1], self.history[2], self.history[3], input];
+    let [xm1, x0, x1, x2] = self.history;
+    while self.position < F::one() {
+      emit(cubic_hermite_interpolation(xm1, x0, x1, x2, self.position));
+      self.position = self.position + self.step;
+    }
+    self.position = self.position - F::one();
+  }
+
+  /// Number of output samples emitted by the next input sample.
+  fn next_output_len(&self) -> usize {
+    if self.position < F::one() {
+      ((F::one() - self.position) / self.step)
+        .ceil()
+        .to_usize()
+        .unwrap_or(0)
+    } else {
+      0
+    }
+  }
+
+  /// Resample as much of the input as fits into the output,
+  /// returning the number of input samples consumed and of output samples written.
+  ///
+  /// An output of [`Resampler::max_output_len`] samples is enough to take the whole input.
+  pub fn process_slice(&mut self, input: &[F], output: &mut [F]) -> (usize, usize) {
+    let mut written = 0;
+    for (consumed, sample) in input.iter().enumerate() {
+      if written + self.next_output_len() > output.len() {
+        return (consumed, written);
+      }
+      self.process(*sample, |value| {
+        output[written] = value;
+        written += 1;
+      });
+    }
+    (input.len(), written)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Resampler;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_same_rate_delays() {
+    let mut resampler = Resampler::<f64>::new(48000.0, 48000.0);
+    let input = (0..16).map(|i| i as f64).collect::<Vec<f64>>();
+    let mut output = vec![0.0; resampler.max_output_len(input.len())];
+    let (consumed, written) = resampler.process_slice(&input, &mut output);
+    assert_eq!((consumed, written), (input.len(), input.len()));
+    for (index, value) in output
+      .iter()
+      .enumerate()
+      .take(written)
+      .skip(Resampler::<f64>::LATENCY)
+    {
+      assert_approx_eq!(*value, input[index - Resampler::<f64>::LATENCY]);
+    }
+  }
+
+  #[test]
+  fn test_upsample_length_and_shape() {
+    let mut resampler = Resampler::<f64>::new(24000.0, 48000.0);
+    assert_approx_eq!(resampler.ratio(), 2.0);
+
+    let freq = 1000.0 / 24000.0;
+    let input = (0..240)
+      .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64).sin())
+      .collect::<Vec<f64>>();
+    let mut output = vec![0.0; resampler.max_output_len(input.len())];
+    let (_, written) = resampler.process_slice(&input, &mut output);
+    assert_eq!(written, 480);
+
+    for (index, value) in output.iter().enumerate().take(written).skip(8) {
+      let time = index as f64 / 2.0 - Resampler::<f64>::LATENCY as f64;
+      let expected = (2.0 * std::f64::consts::PI * freq * time).sin();
+      assert_approx_eq!(*value, expected, 1e-2);
+    }
+  }
+
+  #[test]
+  fn test_downsample_length() {
+    let mut resampler = Resampler::<f32>::new(48000.0, 32000.0);
+    let mut output = [0.0; 256];
+    assert_eq!(
+      resampler.process_slice(&[0.0; 300], &mut output),
+      (300, 200)
+    );
+  }
+
+  #[test]
+  fn test_stop_when_the_output_is_full() {
+    let mut resampler = Resampler::<f64>::new(24000.0, 48000.0);
+    let mut output = [0.0; 5];
+    // every input sample emits two output samples
+    assert_eq!(resampler.process_slice(&[1.0; 4], &mut output), (2, 4));
+    assert_eq!(resampler.process_slice(&[1.0; 2], &mut output[4..]), (0, 0));
+  }
+
+  #[test]
+  fn test_filter_the_aliasing_when_downsampling() {
+    // a tone above the Nyquist frequency of the output would fold back at 4 kHz
+    let freq = 20_000.0 / 48_000.0;
+    let input = (0..4800)
+      .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64).sin())
+      .collect::<Vec<f64>>();
+    let mut resampler = Resampler::<f64>::new(48_000.0, 24_000.0);
+    let mut output = vec![0.0; resampler.max_output_len(input.len())];
+    let (_, written) = resampler.process_slice(&input, &mut output);
+    let peak = output[written / 2..written]
+      .iter()
+      .fold(0.0_f64, |peak, value| peak.max(value.abs()));
+    assert!(peak < 0.1, "{}", peak);
+  }
+}
@@ -4,6 +4,8 @@ use num_traits::ToPrimitive;
 pub type LinearStepsSmoother<F> = Smoother<F, LinearSteps<F>>;
 pub type ExponentialStepsSmoother<F> = Smoother<F, ExponentialStepsSmoothing<F>>;
 pub type Ln2Smoother<F> = Smoother<F, Ln2Smothing<F>>;
+pub type OnePoleSmoother<F> = Smoother<F, OnePoleSmoothing<F>>;
+pub type SlewSmoother<F> = Smoother<F, SlewLimiting<F>>;
 
 pub struct Smoother<F, S> {
   value: F,
@@ -30,6 +32,14 @@ where
     self.strategy.reset();
   }
 
+  /// Adapt the smoothing to a new sample rate, restarting any transition from the current value.
+  pub fn set_sample_rate(&mut self, sample_rate: F) {
+    self.strategy.set_sample_rate(sample_rate);
+    if self.value != self.target {
+      self.strategy.target_updated(self.value, self.target);
+    }
+  }
+
   pub fn set_target(&mut self, target: F) {
     self.target = target;
     self.strategy.target_updated(self.value, self.target);
@@ -57,6 +67,9 @@ where
 
 pub trait SmoothingStrategy<F> {
   fn reset(&mut self);
+  /// Recalculate the steps or coefficients given in time for a new sample rate.
+  /// The strategies created from a number of steps keep them.
+  fn set_sample_rate(&mut self, sample_rate: F);
   fn target_updated(&mut self, value: F, target: F);
   fn next_value(&mut self, value: F, target: F) -> F;
 }
@@ -65,6 +78,7 @@ pub struct NoSmoothing;
 
 impl<F> SmoothingStrategy<F> for NoSmoothing {
   fn reset(&mut self) {}
+  fn set_sample_rate(&mut self, _sample_rate: F) {}
   fn target_updated(&mut self, _value: F, _target: F) {}
   fn next_value(&mut self, _value: F, target: F) -> F {
     target
@@ -76,6 +90,8 @@ pub struct LinearSteps<F> {
   num_steps: usize,
   current_step: usize,
   value_delta: F,
+  /// Time of the transition, when given instead of the number of steps
+  time: Option<F>,
 }
 
 impl<F> LinearSteps<F>
@@ -87,11 +103,15 @@ where
       num_steps,
       current_step: num_steps,
       value_delta: F::zero(),
+      time: None,
     }
   }
 
   pub fn from_time(sample_rate: F, time: F) -> Self {
-    Self::new(F::floor(sample_rate * time).to_usize().unwrap_or(0))
+    Self {
+      time: Some(time),
+      ..Self::new(steps_from_time(sample_rate, time))
+    }
   }
 }
 
@@ -103,6 +123,13 @@ where
     self.current_step = self.num_steps;
   }
 
+  fn set_sample_rate(&mut self, sample_rate: F) {
+    if let Some(time) = self.time {
+      self.num_steps = steps_from_time(sample_rate, time);
+      self.current_step = self.current_step.min(self.num_steps);
+    }
+  }
+
   fn target_updated(&mut self, value: F, target: F) {
     self.current_step = 0;
     let num_steps = F::from(self.num_steps).unwrap_or(F::one());
@@ -124,6 +151,8 @@ pub struct ExponentialStepsSmoothing<F> {
   num_steps: usize,
   current_step: usize,
   value_delta: F,
+  /// Time of the transition, when given instead of the number of steps
+  time: Option<F>,
 }
 
 impl<F> ExponentialStepsSmoothing<F>
@@ -135,11 +164,15 @@ where
       num_steps,
       current_step: num_steps,
       value_delta: F::zero(),
+      time: None,
     }
   }
 
   pub fn from_time(sample_rate: F, time: F) -> Self {
-    Self::new(F::floor(sample_rate * time).to_usize().unwrap_or(0))
+    Self {
+      time: Some(time),
+      ..Self::new(steps_from_time(sample_rate, time))
+    }
   }
 }
 
@@ -151,6 +184,13 @@ where
     self.current_step = self.num_steps;
   }
 
+  fn set_sample_rate(&mut self, sample_rate: F) {
+    if let Some(time) = self.time {
+      self.num_steps = steps_from_time(sample_rate, time);
+      self.current_step = self.current_step.min(self.num_steps);
+    }
+  }
+
   fn target_updated(&mut self, value: F, target: F) {
     self.current_step = 0;
     let num_steps = F::from(self.num_steps).unwrap_or(F::one());
@@ -172,6 +212,7 @@ pub struct Ln2Smothing<F> {
   target: F,
   value: F,
   factor: F,
+  time: F,
 }
 
 impl<F> Ln2Smothing<F>
@@ -182,9 +223,14 @@ where
     Self {
       target: F::zero(),
       value: F::zero(),
-      factor: F::val(F::LN_2) / F::min(sample_rate * time, F::one()),
+      factor: Self::factor(sample_rate, time),
+      time,
     }
   }
+
+  fn factor(sample_rate: F, time: F) -> F {
+    F::val(F::LN_2) / F::min(sample_rate * time, F::one())
+  }
 }
 
 impl<F> SmoothingStrategy<F> for Ln2Smothing<F>
@@ -195,6 +241,10 @@ where
     self.value = self.target;
   }
 
+  fn set_sample_rate(&mut self, sample_rate: F) {
+    self.factor = Self::factor(sample_rate, self.time);
+  }
+
   fn target_updated(&mut self, value: F, target: F) {
     self.target = target;
     self.value = value;
@@ -209,3 +259,161 @@ where
     }
   }
 }
+
+/// One-pole low pass filter approaching the target exponentially,
+/// with the time it takes to cover the 63% of the distance to it.
+#[derive(Clone)]
+pub struct OnePoleSmoothing<F> {
+  time: F,
+  coefficient: F,
+}
+
+impl<F> OnePoleSmoothing<F>
+where
+  F: Float,
+{
+  /// Distance to the target at which the value jumps into it
+  const THRESHOLD: f64 = 1e-6;
+
+  pub fn new(sample_rate: F, time: F) -> Self {
+    Self {
+      time,
+      coefficient: Self::coefficient(sample_rate, time),
+    }
+  }
+
+  fn coefficient(sample_rate: F, time: F) -> F {
+    let samples = sample_rate * time;
+    if samples > F::one() {
+      F::one() - (-samples.recip()).exp()
+    } else {
+      F::one()
+    }
+  }
+}
+
+impl<F> SmoothingStrategy<F> for OnePoleSmoothing<F>
+where
+  F: Float,
+{
+  fn reset(&mut self) {}
+
+  fn set_sample_rate(&mut self, sample_rate: F) {
+    self.coefficient = Self::coefficient(sample_rate, self.time);
+  }
+
+  fn target_updated(&mut self, _value: F, _target: F) {}
+
+  fn next_value(&mut self, value: F, target: F) -> F {
+    let value = value + (target - value) * self.coefficient;
+    if (target - value).abs() <= F::val(Self::THRESHOLD) {
+      target
+    } else {
+      value
+    }
+  }
+}
+
+/// Slew limiter, moving towards the target at a maximum rate in units per second,
+/// which can be different for rising and falling values.
+#[derive(Clone)]
+pub struct SlewLimiting<F> {
+  rise_rate: F,
+  fall_rate: F,
+  rise_step: F,
+  fall_step: F,
+}
+
+impl<F> SlewLimiting<F>
+where
+  F: Float,
+{
+  pub fn new(sample_rate: F, rate: F) -> Self {
+    Self {
+      rise_rate: rate,
+      fall_rate: rate,
+      rise_step: rate / sample_rate,
+      fall_step: rate / sample_rate,
+    }
+  }
+
+  /// Set a different rate for the falling values
+  pub fn with_fall_rate(mut self, sample_rate: F, rate: F) -> Self {
+    self.fall_rate = rate;
+    self.fall_step = rate / sample_rate;
+    self
+  }
+}
+
+impl<F> SmoothingStrategy<F> for SlewLimiting<F>
+where
+  F: Float,
+{
+  fn reset(&mut self) {}
+
+  fn set_sample_rate(&mut self, sample_rate: F) {
+    self.rise_step = self.rise_rate / sample_rate;
+    self.fall_step = self.fall_rate / sample_rate;
+  }
+
+  fn target_updated(&mut self, _value: F, _target: F) {}
+
+  fn next_value(&mut self, value: F, target: F) -> F {
+    let delta = target - value;
+    if delta > self.rise_step {
+      value + self.rise_step
+    } else if delta < self.fall_step.neg() {
+      value - self.fall_step
+    } else {
+      target
+    }
+  }
+}
+
+fn steps_from_time<F: Float>(sample_rate: F, time: F) -> usize {
+  F::floor(sample_rate * time).to_usize().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn test_one_pole() {
+    let mut smoother = OnePoleSmoother::new(0.0, OnePoleSmoothing::new(1000.0, 0.01));
+    smoother.set_target(1.0);
+    let values = (0..10).map(|_| smoother.next_value()).collect::<Vec<f64>>();
+    assert_approx_eq!(values[9], 1.0 - (-1.0f64).exp());
+    assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+    // it reaches the target eventually
+    assert!((0..200).filter_map(|_| smoother.next_value_opt()).count() < 200);
+    assert_eq!(smoother.next_value(), 1.0);
+  }
+
+  #[test]
+  fn test_slew_limiting() {
+    let slew = SlewLimiting::new(10.0, 5.0).with_fall_rate(10.0, 1.0);
+    let mut smoother = SlewSmoother::new(0.0, slew);
+    smoother.set_target(1.0);
+    let values = (0..3).map(|_| smoother.next_value()).collect::<Vec<f64>>();
+    assert_eq!(values, vec![0.5, 1.0, 1.0]);
+    smoother.set_target(0.0);
+    assert_approx_eq!(smoother.next_value(), 0.9);
+  }
+
+  #[test]
+  fn test_sample_rate_change() {
+    let mut smoother = LinearStepsSmoother::new(0.0_f64, LinearSteps::from_time(10.0, 0.4));
+    smoother.set_target(1.0);
+    assert_approx_eq!(smoother.next_value(), 0.25);
+    // the rest of the transition restarts with the steps for the new rate
+    smoother.set_sample_rate(20.0);
+    assert_approx_eq!(smoother.next_value(), 0.34375);
+
+    let mut smoother = SlewSmoother::new(0.0_f64, SlewLimiting::new(10.0, 1.0));
+    smoother.set_sample_rate(100.0);
+    smoother.set_target(1.0);
+    assert_approx_eq!(smoother.next_value(), 0.01);
+  }
+}
@@ -1,12 +1,14 @@
 use crate::float::Float;
 
 pub mod exponential;
+pub mod pulse_blep;
 pub mod saw_blep;
 pub mod saw_trivial;
 pub mod sine_parabolic;
 pub mod square_trivial;
 pub mod triangle_dpw2x;
 pub mod triangle_trivial;
+pub mod wavetable;
 
 pub trait Waveform<F: Float> {
   fn initial_modulo(&self) -> F {
@@ -0,0 +1,101 @@
+use crate::blep::PolyBLEP;
+use crate::float::Float;
+use crate::waveforms::Waveform;
+
+/// Pulse waveform with its rising and falling edges corrected with a 2-point polynomial BLEP,
+/// high while the modulo is below the pulse width, and low for the rest of the cycle.
+#[derive(Debug, Clone)]
+pub struct PulseBlep<F: Float> {
+  /// pulse width between [MIN_PULSE_WIDTH, 1.0 - MIN_PULSE_WIDTH]
+  pulse_width: F,
+}
+
+impl<F: Float> Default for PulseBlep<F> {
+  fn default() -> Self {
+    PulseBlep {
+      pulse_width: F::val(0.5),
+    }
+  }
+}
+
+impl<F: Float> PulseBlep<F> {
+  /// Narrowest pulse, to keep both edges apart
+  pub const MIN_PULSE_WIDTH: f32 = 0.01;
+
+  /// pulse width between [0.0, 1.0], a 0.5 being a square
+  pub fn new(pulse_width: F) -> Self {
+    PulseBlep::default().with_pulse_width(pulse_width)
+  }
+
+  /// pulse width between [0.0, 1.0], a 0.5 being a square
+  pub fn with_pulse_width(mut self, pulse_width: F) -> Self {
+    self.set_pulse_width(pulse_width);
+    self
+  }
+
+  /// pulse width between [0.0, 1.0], a 0.5 being a square
+  pub fn set_pulse_width(&mut self, pulse_width: F) {
+    let min = F::val(Self::MIN_PULSE_WIDTH);
+    self.pulse_width = pulse_width.max(min).min(F::one() - min);
+  }
+
+  pub fn pulse_width(&self) -> F {
+    self.pulse_width
+  }
+}
+
+impl<F: Float> Waveform<F> for PulseBlep<F> {
+  fn generate(&mut self, modulo: F, phase_inc: F) -> F {
+    let phase_inc = phase_inc.abs();
+    let signal = if modulo < self.pulse_width {
+      F::one()
+    } else {
+      F::one().neg()
+    };
+
+    // the rising edge is at the beginning of the cycle, and the falling one at the pulse width
+    let rising = PolyBLEP::residual(modulo, phase_inc, F::one(), true);
+    let mut falling_modulo = modulo - self.pulse_width;
+    if falling_modulo < F::zero() {
+      falling_modulo = falling_modulo + F::one();
+    }
+    let falling = PolyBLEP::residual(falling_modulo, phase_inc, F::one(), false);
+
+    signal + rising + falling
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cycle(pulse_width: f64, phase_inc: f64) -> Vec<f64> {
+    let mut pulse = PulseBlep::new(pulse_width);
+    let num_samples = (1.0 / phase_inc).round() as usize;
+    (0..num_samples)
+      .map(|index| pulse.generate(index as f64 * phase_inc, phase_inc))
+      .collect()
+  }
+
+  #[test]
+  fn smooth_the_edges() {
+    let samples = cycle(0.255, 0.01);
+    assert_eq!(samples[10], 1.0);
+    assert_eq!(samples[50], -1.0);
+    // the samples next to the edges are pulled towards the other side
+    assert_eq!(samples[0], 0.0);
+    assert!(samples[25] < 1.0 && samples[26] > -1.0);
+  }
+
+  #[test]
+  fn follow_the_pulse_width() {
+    for pulse_width in [0.1, 0.5, 0.8] {
+      let samples = cycle(pulse_width, 0.001);
+      let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+      assert!((mean - (2.0 * pulse_width - 1.0)).abs() < 0.01, "{}", mean);
+    }
+
+    let pulse = PulseBlep::new(1.5f32);
+    assert_eq!(pulse.pulse_width(), 1.0 - PulseBlep::<f32>::MIN_PULSE_WIDTH);
+  }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::fft::fft;
+use crate::float::Float;
+use crate::funcs::interpolation::cubic_hermite_interpolation;
+use crate::waveforms::Waveform;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WavetableError {
+  /// The table has no frames
+  Empty,
+  /// The size of the frames is not a power of two of at least [`Wavetable::MIN_FRAME_SIZE`]
+  InvalidFrameSize(usize),
+  /// Not all the frames have the same size
+  MismatchedFrames,
+}
+
+impl fmt::Display for WavetableError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WavetableError::Empty => write!(f, "The wavetable has no frames"),
+      WavetableError::InvalidFrameSize(size) => {
+        write!(f, "Invalid size for the frames of a wavetable: {}", size)
+      }
+      WavetableError::MismatchedFrames => {
+        write!(f, "The frames of a wavetable have different sizes")
+      }
+    }
+  }
+}
+
+impl std::error::Error for WavetableError {}
+
+/// Frames of a wavetable with their harmonics limited to some number.
+#[derive(Debug, Clone)]
+struct MipLevel<F: Float> {
+  harmonics: usize,
+  /// Every frame is wrapped around with its last sample before the first one,
+  /// and its first two samples after the last one, to interpolate
+  frames: Vec<Vec<F>>,
+}
+
+/// A sequence of single cycle frames to morph between, as a source for [`WavetableWaveform`].
+///
+/// Every frame is kept in several mip levels, each one with half of the harmonics of the previous
+/// one, so the oscillator can choose the one that doesn't alias at the frequency being played.
+/// They are built when creating the table, so it is meant to be done outside of the audio thread.
+#[derive(Debug, Clone)]
+pub struct Wavetable<F: Float> {
+  frame_size: usize,
+  num_frames: usize,
+  levels: Vec<MipLevel<F>>,
+}
+
+impl<F: Float> Wavetable<F> {
+  pub const MIN_FRAME_SIZE: usize = 8;
+  /// Size of the frames of the usual wavetables found around, and of the built-in ones
+  pub const DEFAULT_FRAME_SIZE: usize = 2048;
+
+  /// Build a table from its frames, which need to have the same size, a power of two.
+  pub fn from_frames(frames: &[Vec<F>]) -> Result<Self, WavetableError> {
+    let frame_size = frames.first().map(Vec::len).ok_or(WavetableError::Empty)?;
+    if frame_size < Self::MIN_FRAME_SIZE || !frame_size.is_power_of_two() {
+      return Err(WavetableError::InvalidFrameSize(frame_size));
+    }
+    if frames.iter().any(|frame| frame.len() != frame_size) {
+      return Err(WavetableError::MismatchedFrames);
+    }
+
+    let spectra = frames
+      .iter()
+      .map(|frame| {
+        let mut re = frame.clone();
+        let mut im = vec![F::zero(); frame_size];
+        fft(&mut re, &mut im, false);
+        (re, im)
+      })
+      .collect::<Vec<_>>();
+
+    let mut levels = Vec::new();
+    let mut harmonics = frame_size / 2;
+    while harmonics >= 1 {
+      let frames = spectra
+        .iter()
+        .map(|(re, im)| band_limit(re, im, harmonics))
+        .collect();
+      levels.push(MipLevel { harmonics, frames });
+      harmonics /= 2;
+    }
+
+    Ok(Self {
+      frame_size,
+      num_frames: frames.len(),
+      levels,
+    })
+  }
+
+  /// Build a table from consecutive frames of some size, as they are stored in audio files.
+  ///
+  /// Samples left after the last whole frame are ignored.
+  pub fn from_samples(samples: &[F], frame_size: usize) -> Result<Self, WavetableError> {
+    if frame_size == 0 {
+      return Err(WavetableError::InvalidFrameSize(frame_size));
+    }
+    let frames = samples
+      .chunks_exact(frame_size)
+      .map(<[F]>::to_vec)
+      .collect::<Vec<_>>();
+    Self::from_frames(&frames)
+  }
+
+  /// Built-in table morphing from a sine, into a triangle, a saw and a square.
+  pub fn basic_shapes(frame_size: usize) -> Result<Self, WavetableError> {
+    let shapes: [fn(F) -> F; 4] = [
+      |modulo| (F::val(2.0) * F::PI * modulo).sin(),
+      |modulo| F::one() - F::val(4.0) * (modulo - F::val(0.5)).abs(),
+      |modulo| F::val(2.0) * modulo - F::one(),
+      |modulo| {
+        if modulo < F::val(0.5) {
+          F::one()
+        } else {
+          -F::one()
+        }
+      },
+    ];
+    let frames = shapes
+      .iter()
+      .map(|shape| {
+        (0..frame_size)
+          .map(|index| shape(F::val(index) / F::val(frame_size)))
+          .collect()
+      })
+      .collect::<Vec<_>>();
+    Self::from_frames(&frames)
+  }
+
+  pub fn frame_size(&self) -> usize {
+    self.frame_size
+  }
+
+  pub fn num_frames(&self) -> usize {
+    self.num_frames
+  }
+
+  /// Value of the table for a position between its frames and a modulo along the cycle,
+  /// taken from the mip level with the most harmonics that don't alias for the phase increment.
+  ///
+  /// The samples of the frames are read with cubic interpolation, and the frames are morphed linearly.
+  pub fn value(&self, position: F, modulo: F, phase_inc: F) -> F {
+    let nyquist = F::val(0.5);
+    let phase_inc = phase_inc.abs();
+    let level = self
+      .levels
+      .iter()
+      .find(|level| F::val(level.harmonics) * phase_inc <= nyquist)
+      .unwrap_or_else(|| &self.levels[self.levels.len() - 1]);
+
+    let last_frame = self.num_frames - 1;
+    let position = position.max(F::zero()).min(F::one()) * F::val(last_frame);
+    let frame = position.floor().to_usize().unwrap_or(0).min(last_frame);
+    let frame_fraction = position - F::val(frame);
+
+    let index = modulo.max(F::zero()).min(F::one()) * F::val(self.frame_size);
+    let sample = index
+      .floor()
+      .to_usize()
+      .unwrap_or(0)
+      .min(self.frame_size - 1);
+    let sample_fraction = index - F::val(sample);
+
+    // the padded frames have an extra sample before the first one
+    let interpolate = |frame: &[F]| {
+      let points = &frame[sample..sample + 4];
+      cubic_hermite_interpolation(points[0], points[1], points[2], points[3], sample_fraction)
+    };
+    let current = interpolate(&level.frames[frame]);
+    if frame < last_frame {
+      let next = interpolate(&level.frames[frame + 1]);
+      current + (next - current) * frame_fraction
+    } else {
+      current
+    }
+  }
+}
+
+/// Waveform reading a [`Wavetable`], at a position between its frames that can be morphed.
+#[derive(Debug, Clone)]
+pub struct WavetableWaveform<F: Float> {
+  wavetable: Arc<Wavetable<F>>,
+  position: F,
+}
+
+impl<F: Float> WavetableWaveform<F> {
+  pub fn new(wavetable: Arc<Wavetable<F>>) -> Self {
+    Self {
+      wavetable,
+      position: F::zero(),
+    }
+  }
+
+  pub fn wavetable(&self) -> &Arc<Wavetable<F>> {
+    &self.wavetable
+  }
+
+  /// Set the position between the first (0.0) and the last (1.0) frames of the table
+  pub fn set_position(&mut self, position: F) {
+    self.position = position;
+  }
+
+  pub fn get_position(&self) -> F {
+    self.position
+  }
+}
+
+impl<F: Float> Waveform<F> for WavetableWaveform<F> {
+  fn generate(&mut self, modulo: F, phase_inc: F) -> F {
+    self.wavetable.value(self.position, modulo, phase_inc)
+  }
+}
+
+/// Keep the harmonics of a spectrum up to some number, returning the frame for them
+/// wrapped around as needed by the interpolation.
+fn band_limit<F: Float>(re: &[F], im: &[F], harmonics: usize) -> Vec<F> {
+  let size = re.len();
+  let (mut re, mut im) = (re.to_vec(), im.to_vec());
+  for bin in 1..size {
+    // the bins above the middle hold the negative frequencies of the harmonics
+    if bin.min(size - bin) > harmonics {
+      re[bin] = F::zero();
+      im[bin] = F::zero();
+    }
+  }
+  fft(&mut re, &mut im, true);
+  let mut frame = Vec::with_capacity(size + 3);
+  frame.push(re[size - 1]);
+  frame.extend_from_slice(&re);
+  frame.extend_from_slice(&re[..2]);
+  frame
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use assert_approx_eq::assert_approx_eq;
+
+  #[test]
+  fn validate_the_frames() {
+    assert_eq!(
+      Wavetable::<f32>::from_frames(&[]).unwrap_err(),
+      WavetableError::Empty
+    );
+    assert_eq!(
+      Wavetable::<f32>::from_samples(&[0.0; 30], 10).unwrap_err(),
+      WavetableError::InvalidFrameSize(10)
+    );
+    assert_eq!(
+      Wavetable::<f32>::from_frames(&[vec![0.0; 8], vec![0.0; 16]]).unwrap_err(),
+      WavetableError::MismatchedFrames
+    );
+    let wavetable = Wavetable::<f32>::from_samples(&[0.0; 20], 8).unwrap();
+    assert_eq!(wavetable.num_frames(), 2);
+  }
+
+  #[test]
+  fn morph_between_frames() {
+    let wavetable = Wavetable::<f64>::from_frames(&[vec![0.5; 8], vec![-0.5; 8]]).unwrap();
+    assert_approx_eq!(wavetable.value(0.0, 0.3, 0.001), 0.5);
+    assert_approx_eq!(wavetable.value(0.5, 0.3, 0.001), 0.0);
+    assert_approx_eq!(wavetable.value(1.0, 0.3, 0.001), -0.5);
+  }
+
+  #[test]
+  fn interpolate_the_samples() {
+    let sine = (0..8)
+      .map(|index| (2.0 * std::f64::consts::PI * index as f64 / 8.0).sin())
+      .collect::<Vec<_>>();
+    let wavetable = Wavetable::<f64>::from_frames(&[sine]).unwrap();
+    for index in 0..32 {
+      let modulo = index as f64 / 32.0;
+      let expected = (2.0 * std::f64::consts::PI * modulo).sin();
+      assert_approx_eq!(wavetable.value(0.0, modulo, 0.001), expected, 0.01);
+    }
+    // wrapping around the end of the cycle
+    assert_approx_eq!(wavetable.value(0.0, 0.99, 0.001), -0.0628, 0.01);
+  }
+
+  #[test]
+  fn limit_the_harmonics_for_the_frequency() {
+    let wavetable = Wavetable::<f64>::basic_shapes(64).unwrap();
+    let saw = 2.0 / 3.0;
+    // with all the harmonics the saw is close to its naive shape
+    assert!((wavetable.value(saw, 0.25, 0.001) - -0.5).abs() < 0.05);
+    // at high frequencies only the fundamental is left, as a sine
+    let sine = wavetable.value(saw, 0.25, 0.4);
+    let fundamental = -2.0 / std::f64::consts::PI;
+    assert!((sine - fundamental).abs() < 0.05);
+    assert_eq!(wavetable.levels[wavetable.levels.len() - 1].harmonics, 1);
+  }
+
+  #[test]
+  fn transform_back_and_forth() {
+    let frame: [f64; 8] = [0.1, 0.7, -0.3, 0.2, 0.0, -0.9, 0.4, 0.5];
+    let (mut re, mut im) = (frame.to_vec(), vec![0.0; 8]);
+    fft(&mut re, &mut im, false);
+    fft(&mut re, &mut im, true);
+    for (value, expected) in re.iter().zip(frame.iter()) {
+      assert_approx_eq!(*value, *expected);
+    }
+  }
+}
@@ -0,0 +1,116 @@
+#ifndef KIRO_ENGINE_H
+#define KIRO_ENGINE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum KiroStatus {
+  KIRO_STATUS_OK = 0,
+  KIRO_STATUS_NULL_POINTER = 1,
+  KIRO_STATUS_INVALID_STRING = 2,
+  KIRO_STATUS_ENGINE_ERROR = 3,
+  /* The call panicked, leaving whatever it was doing unfinished */
+  KIRO_STATUS_PANIC = 4,
+} KiroStatus;
+
+typedef struct KiroEngine KiroEngine;
+typedef struct KiroModule KiroModule;
+typedef struct KiroNode KiroNode;
+typedef struct KiroRenderer KiroRenderer;
+typedef struct KiroProcessorContext KiroProcessorContext;
+
+typedef struct KiroEngineConfig {
+  size_t ring_buffer_capacity;
+  size_t audio_buffer_size;
+  size_t audio_input_channels;
+  size_t audio_output_channels;
+  size_t event_buffer_size;
+  uint32_t sample_rate;
+} KiroEngineConfig;
+
+typedef struct KiroAudioPortDescriptor {
+  const char *name;
+  size_t channels;
+} KiroAudioPortDescriptor;
+
+typedef struct KiroParamDescriptor {
+  const char *name;
+  float initial;
+  float min;
+  float max;
+} KiroParamDescriptor;
+
+typedef struct KiroProcessorDescriptor {
+  const KiroAudioPortDescriptor *audio_inputs;
+  size_t num_audio_inputs;
+  const KiroAudioPortDescriptor *audio_outputs;
+  size_t num_audio_outputs;
+  const KiroParamDescriptor *parameters;
+  size_t num_parameters;
+} KiroProcessorDescriptor;
+
+typedef struct KiroProcessorCallbacks {
+  void *user_data;
+  /* Called from the audio thread for every block */
+  void (*render)(void *user_data, KiroProcessorContext *context);
+  /* Called when the processor is released, can be NULL */
+  void (*drop)(void *user_data);
+} KiroProcessorCallbacks;
+
+/* Engine */
+
+KiroEngineConfig kiro_engine_config_default(void);
+KiroEngine *kiro_engine_new(const KiroEngineConfig *config);
+void kiro_engine_free(KiroEngine *engine);
+KiroRenderer *kiro_engine_take_renderer(KiroEngine *engine);
+KiroStatus kiro_engine_update_render_plan(KiroEngine *engine);
+
+/* Graph */
+
+KiroModule *kiro_engine_create_module(KiroEngine *engine, const char *name);
+KiroNode *kiro_engine_create_processor(KiroEngine *engine,
+                                       const char *name,
+                                       const KiroProcessorDescriptor *descriptor,
+                                       KiroProcessorCallbacks callbacks);
+KiroModule *kiro_module_create_module(KiroModule *module, const char *name);
+KiroNode *kiro_module_create_processor(KiroModule *module,
+                                       const char *name,
+                                       const KiroProcessorDescriptor *descriptor,
+                                       KiroProcessorCallbacks callbacks);
+void kiro_module_free(KiroModule *module);
+
+KiroStatus kiro_engine_connect_output(KiroEngine *engine, KiroNode *source, const char *output);
+KiroStatus kiro_engine_disconnect_output(KiroEngine *engine, KiroNode *source, const char *output);
+
+KiroStatus kiro_node_set_parameter(KiroNode *node, size_t index, float value);
+KiroStatus kiro_node_connect(KiroNode *source,
+                             const char *output,
+                             KiroNode *destination,
+                             const char *input);
+KiroStatus kiro_node_disconnect(KiroNode *source,
+                                const char *output,
+                                KiroNode *destination,
+                                const char *input);
+void kiro_node_free(KiroNode *node);
+
+/* Processor context (only valid within the render callback) */
+
+size_t kiro_context_num_samples(const KiroProcessorContext *context);
+float kiro_context_parameter(const KiroProcessorContext *context, size_t index);
+const float *kiro_context_audio_input(const KiroProcessorContext *context, size_t port, size_t channel);
+float *kiro_context_audio_output(KiroProcessorContext *context, size_t port, size_t channel);
+
+/* Audio callback */
+
+void kiro_renderer_process(KiroRenderer *renderer, float *output, size_t num_frames, size_t channels);
+void kiro_renderer_free(KiroRenderer *renderer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KIRO_ENGINE_H */
@@ -0,0 +1,512 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use kiro_engine::{
+  AudioNodeIn, AudioNodeOut, Engine, EngineConfig, Error, Module, ModuleDescriptor, ProcessorNode,
+};
+
+use crate::processor::{CallbackProcessor, KiroProcessorCallbacks, KiroProcessorDescriptor};
+use crate::renderer::KiroRenderer;
+use crate::status::{catch_panic, KiroStatus};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KiroEngineConfig {
+  pub ring_buffer_capacity: usize,
+  pub audio_buffer_size: usize,
+  pub audio_input_channels: usize,
+  pub audio_output_channels: usize,
+  pub event_buffer_size: usize,
+  pub sample_rate: u32,
+}
+
+impl From<EngineConfig> for KiroEngineConfig {
+  fn from(config: EngineConfig) -> Self {
+    Self {
+      ring_buffer_capacity: config.ring_buffer_capacity,
+      audio_buffer_size: config.audio_buffer_size,
+      audio_input_channels: config.audio_input_channels,
+      audio_output_channels: config.audio_output_channels,
+      event_buffer_size: config.event_buffer_size,
+      sample_rate: config.sample_rate,
+    }
+  }
+}
+
+impl From<KiroEngineConfig> for EngineConfig {
+  fn from(config: KiroEngineConfig) -> Self {
+    Self {
+      ring_buffer_capacity: config.ring_buffer_capacity,
+      audio_buffer_size: config.audio_buffer_size,
+      audio_input_channels: config.audio_input_channels,
+      audio_output_channels: config.audio_output_channels,
+      event_buffer_size: config.event_buffer_size,
+      sample_rate: config.sample_rate,
+      ..EngineConfig::default()
+    }
+  }
+}
+
+pub struct KiroEngine(Engine);
+
+pub struct KiroModule(Module);
+
+pub struct KiroNode(ProcessorNode);
+
+#[no_mangle]
+pub extern "C" fn kiro_engine_config_default() -> KiroEngineConfig {
+  // building the default config doesn't panic, and there is no value to fall back to
+  EngineConfig::default().into()
+}
+
+/// # Safety
+///
+/// `config` must be either null, to use the default configuration, or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_new(config: *const KiroEngineConfig) -> *mut KiroEngine {
+  catch_panic(std::ptr::null_mut(), || {
+    let config = config
+      .as_ref()
+      .map(|config| EngineConfig::from(*config))
+      .unwrap_or_default();
+    Box::into_raw(Box::new(KiroEngine(Engine::new(config))))
+  })
+}
+
+/// # Safety
+///
+/// `engine` must be a pointer returned by `kiro_engine_new` or null.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_free(engine: *mut KiroEngine) {
+  catch_panic((), || {
+    if !engine.is_null() {
+      drop(Box::from_raw(engine));
+    }
+  })
+}
+
+/// Returns the renderer to be used from the audio thread, or null if it was already taken.
+///
+/// # Safety
+///
+/// `engine` must be a valid pointer returned by `kiro_engine_new`.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_take_renderer(engine: *mut KiroEngine) -> *mut KiroRenderer {
+  catch_panic(std::ptr::null_mut(), || {
+    match engine.as_mut().and_then(|engine| engine.0.take_renderer()) {
+      Some(renderer) => Box::into_raw(Box::new(KiroRenderer(renderer))),
+      None => std::ptr::null_mut(),
+    }
+  })
+}
+
+/// # Safety
+///
+/// `engine` must be a valid pointer returned by `kiro_engine_new`.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_update_render_plan(engine: *mut KiroEngine) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || match engine.as_mut() {
+    Some(engine) => engine.0.update_render_plan().into(),
+    None => KiroStatus::NullPointer,
+  })
+}
+
+/// Creates a module in the root of the engine graph, returning null on failure.
+///
+/// # Safety
+///
+/// `engine` must be a valid pointer returned by `kiro_engine_new` and `name` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_create_module(
+  engine: *mut KiroEngine,
+  name: *const c_char,
+) -> *mut KiroModule {
+  catch_panic(std::ptr::null_mut(), || {
+    match (engine.as_mut(), to_str(name)) {
+      (Some(engine), Some(name)) => engine
+        .0
+        .create_module(name, ModuleDescriptor::new())
+        .map_or(std::ptr::null_mut(), into_module),
+      _ => std::ptr::null_mut(),
+    }
+  })
+}
+
+/// Creates a processor in the root of the engine graph, returning null on failure.
+///
+/// # Safety
+///
+/// `engine` must be a valid pointer returned by `kiro_engine_new`, `name` a nul-terminated string,
+/// and `descriptor` a valid pointer to a descriptor. The `callbacks` must be safe to call from the audio thread.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_create_processor(
+  engine: *mut KiroEngine,
+  name: *const c_char,
+  descriptor: *const KiroProcessorDescriptor,
+  callbacks: KiroProcessorCallbacks,
+) -> *mut KiroNode {
+  catch_panic(std::ptr::null_mut(), || {
+    let processor = CallbackProcessor::new(descriptor, callbacks);
+    match (engine.as_mut(), to_str(name), processor) {
+      (Some(engine), Some(name), Some(processor)) => engine
+        .0
+        .create_processor(name, processor)
+        .map_or(std::ptr::null_mut(), into_node),
+      _ => std::ptr::null_mut(),
+    }
+  })
+}
+
+/// Creates a child module, returning null on failure.
+///
+/// # Safety
+///
+/// `module` must be a valid module pointer and `name` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_module_create_module(
+  module: *mut KiroModule,
+  name: *const c_char,
+) -> *mut KiroModule {
+  catch_panic(std::ptr::null_mut(), || {
+    match (module.as_mut(), to_str(name)) {
+      (Some(module), Some(name)) => module
+        .0
+        .create_module(name, ModuleDescriptor::new())
+        .map_or(std::ptr::null_mut(), into_module),
+      _ => std::ptr::null_mut(),
+    }
+  })
+}
+
+/// Creates a processor inside a module, returning null on failure.
+///
+/// # Safety
+///
+/// Same requirements as `kiro_engine_create_processor` but for a valid module pointer.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_module_create_processor(
+  module: *mut KiroModule,
+  name: *const c_char,
+  descriptor: *const KiroProcessorDescriptor,
+  callbacks: KiroProcessorCallbacks,
+) -> *mut KiroNode {
+  catch_panic(std::ptr::null_mut(), || {
+    let processor = CallbackProcessor::new(descriptor, callbacks);
+    match (module.as_mut(), to_str(name), processor) {
+      (Some(module), Some(name), Some(processor)) => module
+        .0
+        .create_processor(name, processor)
+        .map_or(std::ptr::null_mut(), into_node),
+      _ => std::ptr::null_mut(),
+    }
+  })
+}
+
+/// # Safety
+///
+/// `module` must be a module pointer returned by this API or null.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_module_free(module: *mut KiroModule) {
+  catch_panic((), || {
+    if !module.is_null() {
+      drop(Box::from_raw(module));
+    }
+  })
+}
+
+/// # Safety
+///
+/// `node` must be a valid node pointer returned by this API.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_node_set_parameter(
+  node: *mut KiroNode,
+  index: usize,
+  value: f32,
+) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || match node.as_ref() {
+    Some(node) => node.0.set_parameter(index, value).into(),
+    None => KiroStatus::NullPointer,
+  })
+}
+
+/// Connects an audio output of a node to an audio input of another one in the same module,
+/// replacing whatever was connected to the input.
+///
+/// # Safety
+///
+/// `source` and `destination` must be valid node pointers returned by this API,
+/// and `output` and `input` nul-terminated strings with the names of their ports.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_node_connect(
+  source: *mut KiroNode,
+  output: *const c_char,
+  destination: *mut KiroNode,
+  input: *const c_char,
+) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || {
+    with_ports(source, output, destination, input, |output, input| {
+      output.connect(input)
+    })
+  })
+}
+
+/// Removes the connection from an audio output of a node to an audio input of another one.
+///
+/// # Safety
+///
+/// Same requirements as `kiro_node_connect`.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_node_disconnect(
+  source: *mut KiroNode,
+  output: *const c_char,
+  destination: *mut KiroNode,
+  input: *const c_char,
+) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || {
+    with_ports(source, output, destination, input, |output, input| {
+      output.disconnect(input)
+    })
+  })
+}
+
+/// Connects an audio output of a node in the root of the engine graph to the audio outputs of the engine.
+///
+/// # Safety
+///
+/// `engine` must be a valid pointer returned by `kiro_engine_new`, `source` a valid node pointer,
+/// and `output` a nul-terminated string with the name of the port.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_connect_output(
+  engine: *mut KiroEngine,
+  source: *mut KiroNode,
+  output: *const c_char,
+) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || {
+    with_engine_output(engine, source, output, |output, input| {
+      output.connect(input)
+    })
+  })
+}
+
+/// Removes the connection from an audio output of a node to the audio outputs of the engine.
+///
+/// # Safety
+///
+/// Same requirements as `kiro_engine_connect_output`.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_engine_disconnect_output(
+  engine: *mut KiroEngine,
+  source: *mut KiroNode,
+  output: *const c_char,
+) -> KiroStatus {
+  catch_panic(KiroStatus::Panic, || {
+    with_engine_output(engine, source, output, |output, input| {
+      output.disconnect(input)
+    })
+  })
+}
+
+/// # Safety
+///
+/// `node` must be a node pointer returned by this API or null.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_node_free(node: *mut KiroNode) {
+  catch_panic((), || {
+    if !node.is_null() {
+      drop(Box::from_raw(node));
+    }
+  })
+}
+
+unsafe fn with_ports<F>(
+  source: *mut KiroNode,
+  output: *const c_char,
+  destination: *mut KiroNode,
+  input: *const c_char,
+  f: F,
+) -> KiroStatus
+where
+  F: FnOnce(&AudioNodeOut, &AudioNodeIn) -> Result<(), Error>,
+{
+  match (source.as_ref(), destination.as_ref()) {
+    (Some(source), Some(destination)) => match (to_str(output), to_str(input)) {
+      (Some(output), Some(input)) => source
+        .0
+        .audio_output(output)
+        .and_then(|output| f(&output, &destination.0.audio_input(input)?))
+        .into(),
+      _ => KiroStatus::InvalidString,
+    },
+    _ => KiroStatus::NullPointer,
+  }
+}
+
+unsafe fn with_engine_output<F>(
+  engine: *mut KiroEngine,
+  source: *mut KiroNode,
+  output: *const c_char,
+  f: F,
+) -> KiroStatus
+where
+  F: FnOnce(&AudioNodeOut, &AudioNodeIn) -> Result<(), Error>,
+{
+  match (engine.as_ref(), source.as_ref()) {
+    (Some(engine), Some(source)) => match to_str(output) {
+      Some(output) => source
+        .0
+        .audio_output(output)
+        .and_then(|output| f(&output, &engine.0.audio_outputs()?))
+        .into(),
+      None => KiroStatus::InvalidString,
+    },
+    _ => KiroStatus::NullPointer,
+  }
+}
+
+fn into_module(module: Module) -> *mut KiroModule {
+  Box::into_raw(Box::new(KiroModule(module)))
+}
+
+fn into_node(node: ProcessorNode) -> *mut KiroNode {
+  Box::into_raw(Box::new(KiroNode(node)))
+}
+
+pub(crate) unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+  if s.is_null() {
+    None
+  } else {
+    CStr::from_ptr(s).to_str().ok()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ffi::CString;
+
+  use crate::processor::{KiroAudioPortDescriptor, KiroParamDescriptor};
+  use crate::renderer::kiro_renderer_free;
+
+  use super::*;
+
+  #[test]
+  fn create_processor_and_set_parameter() {
+    let engine = unsafe { kiro_engine_new(std::ptr::null()) };
+    assert!(!engine.is_null());
+
+    let name = CString::new("osc").unwrap();
+    let out_name = CString::new("out").unwrap();
+    let param_name = CString::new("freq").unwrap();
+    let audio_outputs = [KiroAudioPortDescriptor {
+      name: out_name.as_ptr(),
+      channels: 1,
+    }];
+    let parameters = [KiroParamDescriptor {
+      name: param_name.as_ptr(),
+      initial: 440.0,
+      min: 20.0,
+      max: 20_000.0,
+    }];
+    let descriptor = KiroProcessorDescriptor {
+      audio_inputs: std::ptr::null(),
+      num_audio_inputs: 0,
+      audio_outputs: audio_outputs.as_ptr(),
+      num_audio_outputs: audio_outputs.len(),
+      parameters: parameters.as_ptr(),
+      num_parameters: parameters.len(),
+    };
+    let callbacks = KiroProcessorCallbacks {
+      user_data: std::ptr::null_mut(),
+      render: None,
+      drop: None,
+    };
+
+    unsafe {
+      let node = kiro_engine_create_processor(engine, name.as_ptr(), &descriptor, callbacks);
+      assert!(!node.is_null());
+      assert_eq!(kiro_node_set_parameter(node, 0, 880.0), KiroStatus::Ok);
+      assert_eq!(
+        kiro_node_set_parameter(node, 1, 880.0),
+        KiroStatus::EngineError
+      );
+      kiro_node_free(node);
+
+      let renderer = kiro_engine_take_renderer(engine);
+      assert!(!renderer.is_null());
+      assert!(kiro_engine_take_renderer(engine).is_null());
+      kiro_renderer_free(renderer);
+
+      kiro_engine_free(engine);
+    }
+  }
+
+  #[test]
+  fn connect_and_disconnect_nodes() {
+    let port_name = CString::new("audio").unwrap();
+    let ports = [KiroAudioPortDescriptor {
+      name: port_name.as_ptr(),
+      channels: 1,
+    }];
+    let descriptor = KiroProcessorDescriptor {
+      audio_inputs: ports.as_ptr(),
+      num_audio_inputs: ports.len(),
+      audio_outputs: ports.as_ptr(),
+      num_audio_outputs: ports.len(),
+      parameters: std::ptr::null(),
+      num_parameters: 0,
+    };
+    let callbacks = KiroProcessorCallbacks {
+      user_data: std::ptr::null_mut(),
+      render: None,
+      drop: None,
+    };
+    let source_name = CString::new("source").unwrap();
+    let destination_name = CString::new("destination").unwrap();
+    let missing = CString::new("missing").unwrap();
+
+    unsafe {
+      let engine = kiro_engine_new(std::ptr::null());
+      let source =
+        kiro_engine_create_processor(engine, source_name.as_ptr(), &descriptor, callbacks);
+      let destination =
+        kiro_engine_create_processor(engine, destination_name.as_ptr(), &descriptor, callbacks);
+      let port = port_name.as_ptr();
+
+      assert_eq!(
+        kiro_node_connect(source, port, destination, port),
+        KiroStatus::Ok
+      );
+      assert_eq!(
+        kiro_engine_connect_output(engine, destination, port),
+        KiroStatus::Ok
+      );
+      assert_eq!(kiro_engine_update_render_plan(engine), KiroStatus::Ok);
+
+      assert_eq!(
+        kiro_node_disconnect(source, port, destination, port),
+        KiroStatus::Ok
+      );
+      assert_eq!(
+        kiro_engine_disconnect_output(engine, destination, port),
+        KiroStatus::Ok
+      );
+      assert_eq!(kiro_engine_update_render_plan(engine), KiroStatus::Ok);
+
+      assert_eq!(
+        kiro_node_connect(source, missing.as_ptr(), destination, port),
+        KiroStatus::EngineError
+      );
+      assert_eq!(
+        kiro_node_connect(source, std::ptr::null(), destination, port),
+        KiroStatus::InvalidString
+      );
+      assert_eq!(
+        kiro_node_connect(std::ptr::null_mut(), port, destination, port),
+        KiroStatus::NullPointer
+      );
+
+      kiro_node_free(source);
+      kiro_node_free(destination);
+      kiro_engine_free(engine);
+    }
+  }
+}
@@ -0,0 +1,13 @@
+//! C API for the Kiro engine.
+//!
+//! See `include/kiro_engine.h` for the C declarations.
+
+mod engine;
+mod processor;
+mod renderer;
+mod status;
+
+pub use engine::*;
+pub use processor::*;
+pub use renderer::*;
+pub use status::KiroStatus;
@@ -0,0 +1,208 @@
+use std::os::raw::{c_char, c_void};
+
+use kiro_engine::{AudioDescriptor, NodeDescriptor, ParamDescriptor, Processor, ProcessorContext};
+
+use crate::engine::to_str;
+use crate::status::catch_panic;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KiroAudioPortDescriptor {
+  pub name: *const c_char,
+  pub channels: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KiroParamDescriptor {
+  pub name: *const c_char,
+  pub initial: f32,
+  pub min: f32,
+  pub max: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KiroProcessorDescriptor {
+  pub audio_inputs: *const KiroAudioPortDescriptor,
+  pub num_audio_inputs: usize,
+  pub audio_outputs: *const KiroAudioPortDescriptor,
+  pub num_audio_outputs: usize,
+  pub parameters: *const KiroParamDescriptor,
+  pub num_parameters: usize,
+}
+
+/// Callbacks implementing a processor.
+///
+/// `render` is called from the audio thread for every block, and `drop`, if not null,
+/// when the processor is released so the `user_data` can be freed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct KiroProcessorCallbacks {
+  pub user_data: *mut c_void,
+  pub render: Option<unsafe extern "C" fn(*mut c_void, *mut KiroProcessorContext)>,
+  pub drop: Option<unsafe extern "C" fn(*mut c_void)>,
+}
+
+pub struct KiroProcessorContext<'a, 'b>(&'a mut ProcessorContext<'b>);
+
+pub(crate) struct CallbackProcessor {
+  descriptor: NodeDescriptor,
+  callbacks: KiroProcessorCallbacks,
+}
+
+impl CallbackProcessor {
+  pub(crate) unsafe fn new(
+    descriptor: *const KiroProcessorDescriptor,
+    callbacks: KiroProcessorCallbacks,
+  ) -> Option<Self> {
+    let descriptor = descriptor.as_ref()?;
+
+    let audio_inputs = audio_descriptors(descriptor.audio_inputs, descriptor.num_audio_inputs)?;
+    let audio_outputs = audio_descriptors(descriptor.audio_outputs, descriptor.num_audio_outputs)?;
+    let parameters = slice(descriptor.parameters, descriptor.num_parameters)
+      .iter()
+      .map(|param| {
+        to_str(param.name).map(|name| {
+          ParamDescriptor::new(name)
+            .initial(param.initial)
+            .min(param.min)
+            .max(param.max)
+        })
+      })
+      .collect::<Option<Vec<ParamDescriptor>>>()?;
+
+    let descriptor = NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(audio_inputs)
+          .static_outputs(audio_outputs)
+      })
+      .with_parameters(parameters);
+
+    Some(Self {
+      descriptor,
+      callbacks,
+    })
+  }
+}
+
+impl Processor for CallbackProcessor {
+  fn descriptor(&self) -> NodeDescriptor {
+    self.descriptor.clone()
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    if let Some(render) = self.callbacks.render {
+      let mut context = KiroProcessorContext(context);
+      unsafe { render(self.callbacks.user_data, &mut context) }
+    }
+  }
+}
+
+impl Drop for CallbackProcessor {
+  fn drop(&mut self) {
+    if let Some(drop) = self.callbacks.drop {
+      unsafe { drop(self.callbacks.user_data) }
+    }
+  }
+}
+
+/// # Safety
+///
+/// `context` must be the pointer received by the render callback.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_context_num_samples(context: *const KiroProcessorContext) -> usize {
+  catch_panic(0, || {
+    context
+      .as_ref()
+      .map_or(0, |context| context.0.num_samples())
+  })
+}
+
+/// # Safety
+///
+/// `context` must be the pointer received by the render callback, and `index` a valid parameter index.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_context_parameter(
+  context: *const KiroProcessorContext,
+  index: usize,
+) -> f32 {
+  catch_panic(0.0, || {
+    context.as_ref().map_or(0.0, |context| {
+      if index < context.0.num_parameters() {
+        context.0.parameter(index).get()
+      } else {
+        0.0
+      }
+    })
+  })
+}
+
+/// Returns the samples of a channel of an audio input, or null if it doesn't exist.
+/// The buffer contains `kiro_context_num_samples` samples.
+///
+/// # Safety
+///
+/// `context` must be the pointer received by the render callback.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_context_audio_input(
+  context: *const KiroProcessorContext,
+  port: usize,
+  channel: usize,
+) -> *const f32 {
+  catch_panic(std::ptr::null(), || match context.as_ref() {
+    Some(context) if port < context.0.num_audio_inputs() => {
+      let port = context.0.audio_input(port);
+      if channel < port.len() {
+        port.channel(channel).as_slice().as_ptr()
+      } else {
+        std::ptr::null()
+      }
+    }
+    _ => std::ptr::null(),
+  })
+}
+
+/// Returns the samples of a channel of an audio output, or null if it doesn't exist.
+/// The buffer contains `kiro_context_num_samples` samples.
+///
+/// # Safety
+///
+/// `context` must be the pointer received by the render callback.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_context_audio_output(
+  context: *mut KiroProcessorContext,
+  port: usize,
+  channel: usize,
+) -> *mut f32 {
+  catch_panic(std::ptr::null_mut(), || match context.as_mut() {
+    Some(context) if port < context.0.num_audio_outputs() => {
+      let port = context.0.audio_output(port);
+      if channel < port.len() {
+        port.channel_mut(channel).as_mut_slice().as_mut_ptr()
+      } else {
+        std::ptr::null_mut()
+      }
+    }
+    _ => std::ptr::null_mut(),
+  })
+}
+
+unsafe fn audio_descriptors(
+  ports: *const KiroAudioPortDescriptor,
+  len: usize,
+) -> Option<Vec<AudioDescriptor>> {
+  slice(ports, len)
+    .iter()
+    .map(|port| to_str(port.name).map(|name| AudioDescriptor::new(name, port.channels)))
+    .collect()
+}
+
+unsafe fn slice<'a, T>(data: *const T, len: usize) -> &'a [T] {
+  if data.is_null() || len == 0 {
+    &[]
+  } else {
+    std::slice::from_raw_parts(data, len)
+  }
+}
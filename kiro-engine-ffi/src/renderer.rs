@@ -0,0 +1,65 @@
+use kiro_engine::Renderer;
+
+use crate::status::catch_panic;
+
+pub struct KiroRenderer(pub(crate) Renderer);
+
+/// # Safety
+///
+/// `renderer` must be a pointer returned by `kiro_engine_take_renderer` or null.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_renderer_free(renderer: *mut KiroRenderer) {
+  catch_panic((), || {
+    if !renderer.is_null() {
+      drop(Box::from_raw(renderer));
+    }
+  })
+}
+
+/// Renders the next block into an interleaved output buffer of `num_frames * channels` samples.
+/// To be called from the audio callback. The output is left silent if the rendering panics.
+///
+/// # Safety
+///
+/// `renderer` must be a valid renderer pointer and `output` point to at least `num_frames * channels` samples.
+#[no_mangle]
+pub unsafe extern "C" fn kiro_renderer_process(
+  renderer: *mut KiroRenderer,
+  output: *mut f32,
+  num_frames: usize,
+  channels: usize,
+) {
+  if let Some(renderer) = renderer.as_mut() {
+    if output.is_null() {
+      return;
+    }
+    let output = std::slice::from_raw_parts_mut(output, num_frames * channels);
+    let rendered = catch_panic(false, || {
+      renderer.process(output, channels, num_frames);
+      true
+    });
+    if !rendered {
+      output.iter_mut().for_each(|s| *s = 0.0);
+    }
+  }
+}
+
+impl KiroRenderer {
+  fn process(&mut self, output: &mut [f32], channels: usize, num_samples: usize) {
+    for audio_input in self.0.get_audio_inputs() {
+      audio_input.get_mut().fill_first(num_samples, 0.0);
+    }
+
+    self.0.render(num_samples);
+
+    output.iter_mut().for_each(|s| *s = 0.0);
+    let audio_outputs = self.0.get_audio_outputs();
+    for (channel_index, output_buffer) in audio_outputs.iter().enumerate().take(channels) {
+      let mut output_offset = channel_index;
+      for sample in output_buffer.iter().take(num_samples) {
+        output[output_offset] = *sample;
+        output_offset += channels;
+      }
+    }
+  }
+}
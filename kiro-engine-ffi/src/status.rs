@@ -0,0 +1,47 @@
+use std::panic::{self, AssertUnwindSafe};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KiroStatus {
+  Ok = 0,
+  NullPointer = 1,
+  InvalidString = 2,
+  EngineError = 3,
+  /// The call panicked, leaving whatever it was doing unfinished
+  Panic = 4,
+}
+
+impl<T> From<Result<T, kiro_engine::Error>> for KiroStatus {
+  fn from(result: Result<T, kiro_engine::Error>) -> Self {
+    match result {
+      Ok(_) => KiroStatus::Ok,
+      Err(_) => KiroStatus::EngineError,
+    }
+  }
+}
+
+/// Run the body of an exported function, returning `fallback` if it panics,
+/// as unwinding into the foreign caller is undefined behaviour.
+pub(crate) fn catch_panic<T, F>(fallback: T, body: F) -> T
+where
+  F: FnOnce() -> T,
+{
+  panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn return_the_fallback_on_panic() {
+    assert_eq!(
+      catch_panic(KiroStatus::Panic, || KiroStatus::Ok),
+      KiroStatus::Ok
+    );
+    assert_eq!(
+      catch_panic(KiroStatus::Panic, || -> KiroStatus { panic!("boom") }),
+      KiroStatus::Panic
+    );
+  }
+}
@@ -0,0 +1,169 @@
+//! Detection of allocations in the audio thread, to enforce that processors never allocate,
+//! deallocate nor reallocate memory while rendering.
+//!
+//! It requires installing the [`AuditAllocator`] as the global allocator of the application:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: kiro_engine::alloc_audit::AuditAllocator = kiro_engine::alloc_audit::AuditAllocator;
+//! ```
+//!
+//! Then the renderer audits every processor while it renders, and panics or logs according to the [`AuditMode`].
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What to do when a processor allocates while rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditMode {
+  Panic,
+  Log,
+}
+
+static LOG_MODE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_mode(mode: AuditMode) {
+  LOG_MODE.store(mode == AuditMode::Log, Ordering::Relaxed);
+}
+
+pub fn mode() -> AuditMode {
+  if LOG_MODE.load(Ordering::Relaxed) {
+    AuditMode::Log
+  } else {
+    AuditMode::Panic
+  }
+}
+
+thread_local! {
+  /// Number of nested audits in the current thread
+  static AUDITING: Cell<usize> = const { Cell::new(0) };
+  /// Allocations, deallocations and reallocations while auditing in the current thread
+  static VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Global allocator that counts the allocations happening while auditing.
+pub struct AuditAllocator;
+
+impl AuditAllocator {
+  fn check(&self) {
+    // the thread locals might be already destroyed when the thread finishes
+    let auditing = AUDITING
+      .try_with(|auditing| auditing.get() > 0)
+      .unwrap_or(false);
+    if auditing {
+      VIOLATIONS
+        .try_with(|violations| violations.set(violations.get() + 1))
+        .ok();
+    }
+  }
+}
+
+unsafe impl GlobalAlloc for AuditAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    self.check();
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    self.check();
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    self.check();
+    System.alloc_zeroed(layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    self.check();
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+/// Audit of the current thread, which finishes when dropped, even if the audited function panics.
+struct Auditing {
+  /// Violations of the enclosing audit, if any
+  previous: usize,
+}
+
+impl Auditing {
+  fn start() -> Self {
+    let previous = VIOLATIONS.with(|violations| violations.replace(0));
+    AUDITING.with(|auditing| auditing.set(auditing.get() + 1));
+    Self { previous }
+  }
+
+  fn violations(&self) -> usize {
+    VIOLATIONS.with(|violations| violations.get())
+  }
+}
+
+impl Drop for Auditing {
+  fn drop(&mut self) {
+    AUDITING
+      .try_with(|auditing| auditing.set(auditing.get() - 1))
+      .ok();
+    VIOLATIONS
+      .try_with(|violations| violations.set(self.previous))
+      .ok();
+  }
+}
+
+/// Run a function counting the allocations, deallocations and reallocations in the current thread.
+pub fn count_allocations<R, F: FnOnce() -> R>(f: F) -> (R, usize) {
+  let auditing = Auditing::start();
+  let result = f();
+  let count = auditing.violations();
+  (result, count)
+}
+
+/// Run the rendering of a node and panic or log, according to the [`AuditMode`], if it allocated.
+pub fn audit<R, F: FnOnce() -> R>(node_index: usize, f: F) -> R {
+  let (result, count) = count_allocations(f);
+  if count > 0 {
+    match mode() {
+      AuditMode::Panic => panic!(
+        "Node {} allocated {} times while rendering",
+        node_index, count
+      ),
+      AuditMode::Log => eprintln!(
+        "Node {} allocated {} times while rendering",
+        node_index, count
+      ),
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[global_allocator]
+  static ALLOCATOR: AuditAllocator = AuditAllocator;
+
+  #[test]
+  fn count() {
+    let mut values = Vec::with_capacity(4);
+    let (_, count) = count_allocations(|| values.push(1));
+    assert_eq!(count, 0);
+
+    let (_, count) = count_allocations(|| vec![1, 2, 3].len());
+    assert_eq!(count, 2);
+  }
+
+  #[test]
+  fn stop_auditing_when_the_function_panics() {
+    let result = std::panic::catch_unwind(|| count_allocations(|| panic!("render failed")));
+    assert!(result.is_err());
+    assert_eq!(AUDITING.with(|auditing| auditing.get()), 0);
+  }
+
+  #[test]
+  #[should_panic(expected = "Node 3 allocated 1 times while rendering")]
+  fn audit_panics() {
+    let mut values = Vec::new();
+    audit(3, || values.push(1));
+  }
+}
@@ -1,26 +1,246 @@
 use ringbuf::RingBuffer;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+use kiro_midi::TimestampNanos;
+use kiro_time::SampleRate;
 
 use crate::config::EngineConfig;
 use crate::error::Result;
-use crate::graph::{Graph, ModuleDescriptor};
+use crate::graph::connection::{Connection, ModuleIn, NodeIn, NodeOut};
+use crate::graph::document::{GraphDocument, NodeDocument};
+use crate::graph::port::{InputSource, NodeLike, PortAccessor, PortDescriptor, PortType, Ports};
+use crate::graph::{self, Graph, ModuleDescriptor, ModuleKey, NodeDescriptor, NodeKey};
 use crate::node::ProcessorNode;
-use crate::processor::Processor;
-use crate::rendering::controller::{AudioBufferKey, Controller};
+use crate::observer::{GraphEvent, ObserverId, Observers};
+use crate::processor::{BoxedProcessor, Processor};
+use crate::rendering::buffers::events::Event;
+use crate::rendering::controller::{
+  self, Controller, OutputEvent, ParamGesture, ParamKey, ProcessorFault, StateLoaded,
+};
+use crate::rendering::controller_plan::{NodeProcessor, RenderPlanner};
+use crate::rendering::diagnostics::{Diagnostics, RenderLoad};
+use crate::rendering::meters::MeterLevel;
 use crate::rendering::renderer::Renderer;
-use crate::{Error, Module};
+use crate::{
+  AudioNodeIn, AudioNodeOut, EngineSnapshot, Error, EventsNodeOut, MacroParameter, Module,
+  PolyModule, PolyVoice,
+};
 
 pub(crate) struct InnerEngine {
   pub(crate) graph: Graph,
   pub(crate) controller: Controller,
+  pub(crate) processors: HashMap<NodeKey, NodeProcessor>,
+  pub(crate) observers: Observers,
+}
+
+impl InnerEngine {
+  /// Notify the observers about a change, building the event only when there is any of them.
+  pub(crate) fn notify<F>(&mut self, event: F) -> Result<()>
+  where
+    F: FnOnce(&Graph) -> graph::error::Result<GraphEvent>,
+  {
+    if !self.observers.is_empty() {
+      let event = event(&self.graph)?;
+      self.observers.notify(&event);
+    }
+    Ok(())
+  }
+
+  /// Connect two ports, summing the source with the ones already connected to the input when `summed`.
+  pub(crate) fn connect<D>(&mut self, connection: Connection<D>, summed: bool) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    if summed {
+      self.graph.add(connection)?;
+    } else {
+      self.graph.connect(connection)?;
+    }
+    self.notify(|graph| {
+      Ok(GraphEvent::Connected {
+        port_type: D::port_type(),
+        connection: graph.connection_document(&connection)?,
+      })
+    })
+  }
+
+  pub(crate) fn disconnect<D>(&mut self, connection: Connection<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+    InputSource<D>: PartialEq,
+  {
+    self.graph.disconnect(connection)?;
+    self.notify_disconnected(&[connection])
+  }
+
+  pub(crate) fn clear_module_input<D>(&mut self, module_in: ModuleIn<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let connections = self.graph.clear_module_input_source(module_in)?;
+    self.notify_disconnected(connections.as_slice())
+  }
+
+  pub(crate) fn clear_node_input<D>(&mut self, node_in: NodeIn<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let connections = self.graph.clear_node_input_source(node_in)?;
+    self.notify_disconnected(connections.as_slice())
+  }
+
+  fn notify_disconnected<D>(&mut self, connections: &[Connection<D>]) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    for connection in connections {
+      self.notify(|graph| {
+        Ok(GraphEvent::Disconnected {
+          port_type: D::port_type(),
+          connection: graph.connection_document(connection)?,
+        })
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Set the value of a parameter of a module into the parameters of the nodes bound to it.
+  pub(crate) fn forward_module_param(&mut self, module_key: ModuleKey, index: usize) -> Result<()> {
+    let module = self.graph.get_module(module_key)?;
+    let value = module.param_values[index];
+    let bindings = module.param_bindings[index].clone();
+    let values = bindings
+      .iter()
+      .map(|binding| {
+        let node = self.graph.get_node(binding.node)?;
+        let param_key = self
+          .processors
+          .get(&binding.node)
+          .and_then(|processor| processor.parameters.get(binding.index))
+          .ok_or_else(|| graph::Error::ParamNotFound(node.full_name(), binding.index))?;
+        let value = node.descriptor.parameters[binding.index].constrain(value);
+        Ok((*param_key, value))
+      })
+      .collect::<Result<Vec<(ParamKey, f32)>>>()?;
+
+    self.controller.set_parameter_values(&values)?;
+    for (binding, (_, value)) in bindings.iter().zip(values) {
+      self.notify(|graph| {
+        Ok(GraphEvent::ParamChanged {
+          node: graph.get_node(binding.node)?.full_name(),
+          index: binding.index,
+          value,
+        })
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Remove a node from the graph, and its processor, parameters and meters from the controller.
+  pub(crate) fn remove_node(&mut self, node_key: NodeKey) -> Result<()> {
+    let path = self.graph.get_node(node_key)?.full_name();
+    self.graph.remove_node(node_key)?;
+    if let Some(node_processor) = self.processors.remove(&node_key) {
+      self.controller.remove_processor(node_processor.processor)?;
+      self
+        .controller
+        .remove_parameters(node_processor.parameters.as_slice());
+    }
+    self.controller.remove_meters(node_key);
+    self.notify(|_| Ok(GraphEvent::NodeRemoved(path)))
+  }
+
+  /// Remove a module from the graph, together with everything inside it.
+  pub(crate) fn remove_module(&mut self, module_key: ModuleKey) -> Result<()> {
+    for node_key in self.graph.module_nodes(module_key) {
+      self.remove_node(node_key)?;
+    }
+    self.graph.remove_module(module_key)?;
+    Ok(())
+  }
+
+  /// Replace the processor of a node and its descriptor, which must have the same ports.
+  ///
+  /// The parameters with the same id keep their values, while the rest are created or removed.
+  /// The old processor is dropped once the render plan using it is retired by the renderer.
+  pub(crate) fn replace_processor(
+    &mut self,
+    node_key: NodeKey,
+    descriptor: NodeDescriptor,
+    processor: BoxedProcessor,
+  ) -> Result<NodeProcessor> {
+    let old_processor = self
+      .processors
+      .get(&node_key)
+      .cloned()
+      .ok_or(graph::Error::NodeNotFound(node_key))?;
+    let old_parameters = self.graph.get_node(node_key)?.descriptor.parameters.clone();
+    self
+      .graph
+      .replace_node_descriptor(node_key, descriptor.clone())?;
+
+    let mut old_param_keys = old_processor
+      .parameters
+      .iter()
+      .cloned()
+      .map(Some)
+      .collect::<Vec<Option<ParamKey>>>();
+    let parameters = descriptor
+      .parameters
+      .iter()
+      .map(|param| {
+        old_parameters
+          .iter()
+          .position(|old_param| old_param.id == param.id)
+          .and_then(|index| old_param_keys[index].take())
+          .unwrap_or_else(|| self.controller.add_parameters(&[param.initial])[0])
+      })
+      .collect::<Vec<ParamKey>>();
+    let removed_param_keys = old_param_keys
+      .into_iter()
+      .flatten()
+      .collect::<Vec<ParamKey>>();
+    self
+      .controller
+      .remove_parameters(removed_param_keys.as_slice());
+
+    self.controller.remove_processor(old_processor.processor)?;
+    let node_processor = NodeProcessor {
+      processor: self.controller.add_boxed_processor(processor),
+      parameters,
+    };
+    self.processors.insert(node_key, node_processor.clone());
+    Ok(node_processor)
+  }
+
+  /// Remove every processor and module, keeping only the root module and the graph inputs and outputs.
+  pub(crate) fn clear(&mut self) -> Result<()> {
+    let node_keys = self.processors.keys().copied().collect::<Vec<NodeKey>>();
+    for node_key in node_keys {
+      self.remove_node(node_key)?;
+    }
+    let root_key = self.graph.get_root_module();
+    for module_key in self.graph.module_children(root_key) {
+      self.graph.remove_module(module_key)?;
+    }
+    Ok(())
+  }
 }
 
 pub struct Engine {
   inner: Rc<RefCell<InnerEngine>>,
   renderer: Option<Renderer>,
-  audio_input_buffer_key: AudioBufferKey,
+  diagnostics: Diagnostics,
+  render_load: RenderLoad,
 }
 
 impl Engine {
@@ -28,16 +248,31 @@ impl Engine {
     let ring_buffer_capacity = config.ring_buffer_capacity;
     let (forward_tx, forward_rx) = RingBuffer::new(ring_buffer_capacity).split();
     let (backward_tx, backward_rx) = RingBuffer::new(ring_buffer_capacity).split();
+    let (garbage_tx, garbage_rx) = RingBuffer::new(config.garbage_capacity).split();
     let graph = Graph::new(config.audio_input_channels, config.audio_output_channels);
-    let mut controller = Controller::new(forward_tx, backward_rx, config.clone());
-    let audio_input_buffer_key = controller.add_audio_buffer();
-    let inner = Rc::new(RefCell::new(InnerEngine { graph, controller }));
-    let renderer = Some(Renderer::new(backward_tx, forward_rx, config));
+    let controller = Controller::new(forward_tx, backward_rx, garbage_rx, config.clone());
+    let inner = Rc::new(RefCell::new(InnerEngine {
+      graph,
+      controller,
+      processors: HashMap::new(),
+      observers: Observers::default(),
+    }));
+    let (diagnostics, dropout_reporter) = Diagnostics::new(config.diagnostics_capacity);
+    let render_load = RenderLoad::default();
+    let renderer = Some(Renderer::new(
+      backward_tx,
+      forward_rx,
+      garbage_tx,
+      dropout_reporter,
+      render_load.clone(),
+      config,
+    ));
 
     Self {
       inner,
       renderer,
-      audio_input_buffer_key,
+      diagnostics,
+      render_load,
     }
   }
 
@@ -45,10 +280,53 @@ impl Engine {
     self.renderer.take()
   }
 
+  pub fn diagnostics(&mut self) -> &mut Diagnostics {
+    &mut self.diagnostics
+  }
+
+  /// Render load measured by the renderer while its watchdog is enabled.
+  pub fn render_load(&self) -> RenderLoad {
+    self.render_load.clone()
+  }
+
+  pub fn sample_rate(&self) -> SampleRate {
+    self.inner.deref().borrow().controller.sample_rate()
+  }
+
+  /// Propagate a new sample rate to the processors and the renderer without rebuilding the engine.
+  pub fn set_sample_rate(&mut self, sample_rate: SampleRate) -> Result<()> {
+    let mut engine = self.inner.borrow_mut();
+    engine
+      .controller
+      .set_sample_rate(sample_rate)
+      .map_err(Error::Controller)
+  }
+
   pub fn create_module(&mut self, name: &str, descriptor: ModuleDescriptor) -> Result<Module> {
     self.root_module().create_module(name, descriptor)
   }
 
+  /// Create a macro to drive several parameters from a single value in the `0..1` range.
+  pub fn create_macro(&mut self, id: &str, value: f32) -> MacroParameter {
+    MacroParameter::new(self.inner.clone(), id, value)
+  }
+
+  /// Create a module replicating the voices built by `template` a number of times.
+  pub fn create_poly_module<F>(
+    &mut self,
+    name: &str,
+    num_voices: usize,
+    channels: usize,
+    template: F,
+  ) -> Result<PolyModule>
+  where
+    F: FnMut(&mut Module, usize) -> Result<PolyVoice>,
+  {
+    self
+      .root_module()
+      .create_poly_module(name, num_voices, channels, template)
+  }
+
   pub fn create_processor<P>(&mut self, name: &str, processor: P) -> Result<ProcessorNode>
   where
     P: Processor + 'static,
@@ -56,19 +334,435 @@ impl Engine {
     self.root_module().create_processor(name, processor)
   }
 
-  pub fn update_render_plan(&mut self) -> Result<()> {
+  /// Create a node for a processor that only works at `sample_rate`.
+  /// See [`Module::create_resampled_processor`].
+  pub fn create_resampled_processor<P>(
+    &mut self,
+    name: &str,
+    processor: P,
+    sample_rate: SampleRate,
+  ) -> Result<ProcessorNode>
+  where
+    P: Processor + 'static,
+  {
+    self
+      .root_module()
+      .create_resampled_processor(name, processor, sample_rate)
+  }
+
+  /// Receive the events that reach the events outputs of the graph.
+  ///
+  /// Events are delivered when calling [`Engine::process_messages`].
+  pub fn subscribe_events(&mut self) -> Receiver<OutputEvent> {
+    self.inner.borrow_mut().controller.subscribe_events()
+  }
+
+  /// Receive the gestures of the user on the parameters of the nodes.
+  pub fn subscribe_gestures(&mut self) -> Receiver<ParamGesture> {
+    self.inner.borrow_mut().controller.subscribe_gestures()
+  }
+
+  /// Receive the processors that panicked while rendering, and have been bypassed.
+  pub fn subscribe_faults(&mut self) -> Receiver<ProcessorFault> {
+    self.inner.borrow_mut().controller.subscribe_faults()
+  }
+
+  /// Call `observer` for every change made to the graph or the parameters through the public API,
+  /// so user interfaces can stay in sync without polling.
+  ///
+  /// Observers are called while the engine is being changed, so they must not call it back.
+  pub fn subscribe<F>(&mut self, observer: F) -> ObserverId
+  where
+    F: FnMut(&GraphEvent) + 'static,
+  {
+    self
+      .inner
+      .borrow_mut()
+      .observers
+      .subscribe(Box::new(observer))
+  }
+
+  /// Stop calling an observer, returning whether it was subscribed.
+  pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+    self.inner.borrow_mut().observers.unsubscribe(id)
+  }
+
+  /// Process the messages sent back by the renderer, to be called periodically from the non real-time side.
+  pub fn process_messages(&mut self) {
+    self.inner.borrow_mut().controller.process_messages()
+  }
+
+  /// Measure the peak and RMS levels of every channel of an audio output, from the next render plan.
+  pub fn enable_meter(&mut self, output: &AudioNodeOut) -> Result<()> {
+    let mut engine = self.inner.borrow_mut();
+    let node_out = NodeOut(output.node_key, output.port_key);
+    let node = engine.graph.get_node(output.node_key)?;
+    let port = node.get_output_port(output.port_key)?;
+    let channels = port.descriptor.channels();
+    engine.controller.enable_meter(node_out, channels);
+    Ok(())
+  }
+
+  /// Stop measuring the levels of an audio output, from the next render plan.
+  pub fn disable_meter(&mut self, output: &AudioNodeOut) {
+    let node_out = NodeOut(output.node_key, output.port_key);
+    self.inner.borrow_mut().controller.disable_meter(node_out);
+  }
+
+  /// Levels of every channel of a metered audio output for the last rendered block.
+  pub fn meter(&self, output: &AudioNodeOut) -> Option<Vec<MeterLevel>> {
+    let node_out = NodeOut(output.node_key, output.port_key);
     let mut engine = self.inner.borrow_mut();
     engine
       .controller
-      .send_render_plan(
-        vec![],
-        vec![self.audio_input_buffer_key],
-        vec![],
-        vec![],
-        vec![],
-      )
-      .map_err(Error::Controller)?;
-    todo!()
+      .meter_levels(node_out)
+      .map(|levels| levels.to_vec())
+  }
+
+  /// Input of the node sending its audio to the outputs of the engine.
+  pub fn audio_outputs(&self) -> Result<AudioNodeIn> {
+    let engine = self.inner.deref().borrow();
+    let node_key = engine.graph.get_outputs_node();
+    let port_key = engine.graph.node_audio_input(node_key, "audio-out")?;
+    Ok(crate::ports::NodeIn {
+      engine: self.inner.clone(),
+      node_key,
+      port_key,
+    })
+  }
+
+  /// Output of the node receiving the audio sent to the engine, such as the audio input.
+  pub fn audio_inputs(&self) -> Result<AudioNodeOut> {
+    let engine = self.inner.deref().borrow();
+    let node_key = engine.graph.get_inputs_node();
+    let port_key = engine.graph.node_audio_output(node_key, "audio-in")?;
+    Ok(crate::ports::NodeOut {
+      engine: self.inner.clone(),
+      node_key,
+      port_key,
+    })
+  }
+
+  /// Output of the node receiving the events sent to the engine, such as the MIDI input.
+  pub fn events_inputs(&self) -> Result<EventsNodeOut> {
+    let engine = self.inner.deref().borrow();
+    let node_key = engine.graph.get_inputs_node();
+    let port_key = engine.graph.node_events_output(node_key, "events-in")?;
+    Ok(crate::ports::NodeOut {
+      engine: self.inner.clone(),
+      node_key,
+      port_key,
+    })
+  }
+
+  /// Find a module by its path, as in `root/synth`.
+  pub fn find_module(&self, path: &str) -> Option<Module> {
+    let key = self.inner.deref().borrow().graph.find_module(path)?;
+    Some(Module {
+      engine: self.inner.clone(),
+      key,
+    })
+  }
+
+  /// Find the node of a processor by its path, as in `root/synth/voice-0`.
+  pub fn find_node(&self, path: &str) -> Option<ProcessorNode> {
+    let engine = self.inner.deref().borrow();
+    let node_key = engine.graph.find_node(path)?;
+    let node_processor = engine.processors.get(&node_key)?;
+    Some(ProcessorNode {
+      engine: self.inner.clone(),
+      node_key,
+      processor_key: node_processor.processor,
+      param_keys: node_processor.parameters.clone(),
+    })
+  }
+
+  /// Build a render plan from the current graph and send it to the renderer.
+  pub fn update_render_plan(&mut self) -> Result<()> {
+    let mut engine = self.inner.borrow_mut();
+    let InnerEngine {
+      graph,
+      controller,
+      processors,
+      ..
+    } = &mut *engine;
+    let plan = RenderPlanner::new(graph, processors).plan(controller)?;
+    controller.send_render_plan(plan).map_err(Error::Controller)
+  }
+
+  /// Render the current graph without an audio device, as fast as possible,
+  /// returning the samples for every audio output channel of the graph.
+  ///
+  /// The events are sent to the first events input of the graph, and their timestamps are
+  /// relative to the beginning of the rendering. The renderer must not have been taken.
+  pub fn render_offline(&mut self, num_samples: usize, events: &[Event]) -> Result<Vec<Vec<f32>>> {
+    self.render_offline_with_inputs(&[], num_samples, events)
+  }
+
+  /// Render the current graph offline as with [`Engine::render_offline`],
+  /// sending the samples of every channel of `inputs` to the audio inputs of the graph.
+  ///
+  /// Missing channels and samples beyond the end of the inputs are rendered as silence.
+  pub fn render_offline_with_inputs(
+    &mut self,
+    inputs: &[Vec<f32>],
+    num_samples: usize,
+    events: &[Event],
+  ) -> Result<Vec<Vec<f32>>> {
+    self.update_render_plan()?;
+    let max_block = self.inner.borrow().controller.max_block();
+    let sample_rate = self.sample_rate();
+    let renderer = self.renderer.as_mut().ok_or(Error::RendererNotAvailable)?;
+
+    // receive the render plan
+    renderer.render(0);
+    renderer.set_block_timestamp(0);
+
+    let mut outputs = vec![Vec::with_capacity(num_samples); renderer.get_audio_outputs().len()];
+    let mut position = 0;
+    while position < num_samples {
+      let block_size = max_block.min(num_samples - position);
+      let block_start = renderer.block_timestamp();
+      let block_end = block_start
+        + (block_size as u128 * 1_000_000_000 / u128::from(sample_rate)) as TimestampNanos;
+
+      for (channel, input) in renderer.get_audio_inputs().iter().enumerate() {
+        let buffer = input.get_mut();
+        buffer.fill(0.0);
+        if let Some(samples) = inputs.get(channel) {
+          let start = position.min(samples.len());
+          let end = (position + block_size).min(samples.len());
+          buffer.as_mut_slice()[..end - start].copy_from_slice(&samples[start..end]);
+        }
+      }
+      for (index, input) in renderer.get_events_inputs().iter().enumerate() {
+        let buffer = input.get_mut();
+        buffer.clear();
+        if index == 0 {
+          for event in events {
+            if event.timestamp >= block_start && event.timestamp < block_end {
+              buffer.insert_sorted(*event).ok();
+            }
+          }
+        }
+      }
+
+      renderer.render(block_size);
+
+      for (output, buffer) in outputs.iter_mut().zip(renderer.get_audio_outputs()) {
+        output.extend_from_slice(&buffer.get_mut().as_slice()[..block_size]);
+      }
+      position += block_size;
+    }
+
+    self.process_messages();
+    Ok(outputs)
+  }
+
+  /// Describe the current graph using paths rather than keys, together with the parameter values,
+  /// so it can be saved to disk and imported later.
+  pub fn export_graph(&self) -> Result<GraphDocument> {
+    let engine = self.inner.deref().borrow();
+    let document = engine.graph.export_document(|node_key| {
+      engine
+        .processors
+        .get(&node_key)
+        .map(|node_processor| {
+          node_processor
+            .parameters
+            .iter()
+            .filter_map(|param_key| engine.controller.get_parameter_value(*param_key).ok())
+            .map(|value| value.get())
+            .collect()
+        })
+        .unwrap_or_default()
+    })?;
+    Ok(document)
+  }
+
+  /// Recreate the modules, nodes and connections of a document into the current graph.
+  ///
+  /// Processors can not be serialized, so `factory` is asked for a new processor for every node.
+  pub fn import_graph<F>(&mut self, document: &GraphDocument, mut factory: F) -> Result<()>
+  where
+    F: FnMut(&NodeDocument) -> Option<BoxedProcessor>,
+  {
+    for module_document in document.modules.iter() {
+      let mut engine = self.inner.borrow_mut();
+      let parent_key = engine.graph.module_from_path(&module_document.path)?;
+      let module_key = engine.graph.create_module(
+        parent_key,
+        &module_document.name,
+        module_document.descriptor.clone(),
+      )?;
+      engine
+        .graph
+        .import_module_ports(module_key, &module_document.dynamic_ports)?;
+      for (index, value) in module_document.values.iter().enumerate() {
+        engine.graph.set_module_param(module_key, index, *value)?;
+      }
+    }
+
+    for node_document in document.nodes.iter() {
+      let mut processor = factory(node_document)
+        .ok_or_else(|| Error::ProcessorNotAvailable(node_document.full_name()))?;
+      if let Some(state) = node_document.state.as_ref() {
+        if let Some(current) = processor.state() {
+          current
+            .decode(state)
+            .and_then(|mut state| processor.set_state(&mut state))
+            .map_err(|err| Error::State(node_document.full_name(), err))?;
+        }
+      }
+      let key = self
+        .inner
+        .deref()
+        .borrow()
+        .graph
+        .module_from_path(&node_document.path)?;
+      let mut module = Module {
+        engine: self.inner.clone(),
+        key,
+      };
+      let node = module.create_boxed_processor(
+        &node_document.name,
+        node_document.descriptor.clone(),
+        processor,
+      )?;
+      for (index, value) in node_document.values.iter().enumerate() {
+        node.set_parameter(index, *value)?;
+      }
+      let mut engine = self.inner.borrow_mut();
+      engine
+        .graph
+        .import_node_ports(node.node_key, &node_document.dynamic_ports)?;
+    }
+
+    let mut engine = self.inner.borrow_mut();
+    engine.graph.import_connections(document)?;
+    let connections = document
+      .audio_connections
+      .iter()
+      .map(|connection| (PortType::Audio, connection))
+      .chain(
+        document
+          .events_connections
+          .iter()
+          .map(|connection| (PortType::Events, connection)),
+      );
+    for (port_type, connection) in connections {
+      engine.notify(|_| {
+        Ok(GraphEvent::Connected {
+          port_type,
+          connection: connection.clone(),
+        })
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Capture the internal state of the processors of the nodes in a document exported from this engine,
+  /// as with [`Processor::state`], so it can be persisted in projects and presets.
+  pub fn save_states(&mut self, document: &mut GraphDocument) -> Result<()> {
+    for node_document in document.nodes.iter_mut() {
+      let path = node_document.full_name();
+      let node = self.find_node(&path).ok_or(Error::NodeNotFound(path))?;
+      node_document.state = self.save_processor_state(&node)?;
+    }
+    Ok(())
+  }
+
+  /// Capture the internal state of the processor of a node, as with [`Processor::state`].
+  ///
+  /// The state is kept by the controller, so the renderer keeps rendering and it doesn't need to be available.
+  pub fn save_processor_state(&mut self, node: &ProcessorNode) -> Result<Option<Vec<u8>>> {
+    let result = self
+      .inner
+      .deref()
+      .borrow()
+      .controller
+      .save_processor_state(node.processor_key);
+    self.map_state_error(node, result)
+  }
+
+  /// Restore the internal state of the processor of a node, as with [`Processor::set_state`],
+  /// without waiting for it.
+  ///
+  /// The state is decoded right away, so decoding errors are returned from here.
+  /// Processors in a render plan swap it from the renderer between two blocks, so they keep rendering,
+  /// and the result is received through [`Engine::subscribe_states`] when calling [`Engine::process_messages`].
+  /// The rest of processors load it right away.
+  pub fn load_processor_state(&mut self, node: &ProcessorNode, state: Vec<u8>) -> Result<()> {
+    let result = self
+      .inner
+      .borrow_mut()
+      .controller
+      .load_processor_state(node.processor_key, &state);
+    self.map_state_error(node, result)
+  }
+
+  /// Receive the results of loading the states of the processors of the nodes.
+  pub fn subscribe_states(&mut self) -> Receiver<StateLoaded> {
+    self.inner.borrow_mut().controller.subscribe_states()
+  }
+
+  fn map_state_error<T>(
+    &self,
+    node: &ProcessorNode,
+    result: std::result::Result<T, controller::Error>,
+  ) -> Result<T> {
+    match result {
+      Ok(value) => Ok(value),
+      Err(controller::Error::State(_, err)) => Err(Error::State(self.node_full_name(node)?, err)),
+      Err(err) => Err(Error::Controller(err)),
+    }
+  }
+
+  fn node_full_name(&self, node: &ProcessorNode) -> Result<String> {
+    let engine = self.inner.deref().borrow();
+    Ok(engine.graph.get_node(node.node_key)?.full_name())
+  }
+
+  /// Capture the graph together with the current parameter values.
+  pub fn snapshot(&self) -> Result<EngineSnapshot> {
+    Ok(EngineSnapshot {
+      graph: self.export_graph()?,
+    })
+  }
+
+  /// Set the parameter values captured by a snapshot back into the nodes with the same paths.
+  ///
+  /// The graph must still contain every node of the snapshot, otherwise nothing is changed.
+  /// Use [`Engine::restore_with`] when the graph has changed since the snapshot.
+  pub fn restore(&mut self, snapshot: &EngineSnapshot) -> Result<()> {
+    let nodes = snapshot
+      .graph
+      .nodes
+      .iter()
+      .map(|node_document| {
+        let path = node_document.full_name();
+        self
+          .find_node(&path)
+          .map(|node| (node, node_document.values.as_slice()))
+          .ok_or(Error::NodeNotFound(path))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    for (node, values) in nodes {
+      for (index, value) in values.iter().enumerate() {
+        node.set_parameter(index, *value)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Replace the whole graph by the one captured in a snapshot, as in [`Engine::import_graph`].
+  pub fn restore_with<F>(&mut self, snapshot: &EngineSnapshot, factory: F) -> Result<()>
+  where
+    F: FnMut(&NodeDocument) -> Option<BoxedProcessor>,
+  {
+    self.inner.borrow_mut().clear()?;
+    self.import_graph(&snapshot.graph, factory)
   }
 
   #[inline]
@@ -85,3 +779,1457 @@ impl Default for Engine {
     Self::new(EngineConfig::default())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+  use crate::graph::port::DynamicPorts;
+  use crate::processor::{decode_state, encode_state, swap_state, SharedState, StateSnapshot};
+  use crate::processors::EnvelopeFollowerProcessor;
+  use crate::{
+    AudioDescriptor, AutomationCurve, AutomationData, EventData, EventsDescriptor, NodeDescriptor,
+    ParamDescriptor, ParamRef, ProcessorContext, TransportMessage,
+  };
+  use crate::{
+    ConnectionDocument, ConnectionKind, DropoutCause, GestureKind, MacroRange, MessageKind,
+    ParamScale, StateError,
+  };
+  use kiro_time::Tempo;
+  use std::any::Any;
+
+  #[test]
+  fn report_the_state_of_the_renderer_on_xruns() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+
+    let planned = Arc::new(Mutex::new(Vec::new()));
+    engine
+      .create_processor("planned", PrepareProcessor(planned))
+      .unwrap();
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+    renderer.report_xrun();
+
+    let dropouts = engine.diagnostics().dropouts().cloned().collect::<Vec<_>>();
+    assert_eq!(dropouts.len(), 1);
+    let dropout = &dropouts[0];
+    assert_eq!(dropout.cause, DropoutCause::Xrun);
+    assert_eq!(dropout.num_samples, 16);
+    assert_eq!(dropout.node_times.len(), dropout.plan_nodes);
+    assert!(dropout.plan_nodes > 0);
+    assert_eq!(
+      dropout.last_messages.last(),
+      Some(&MessageKind::MoveRenderPlan)
+    );
+  }
+
+  struct PrepareProcessor(Arc<Mutex<Vec<SampleRate>>>);
+
+  impl Processor for PrepareProcessor {
+    fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+      self.0.lock().unwrap().push(sample_rate);
+    }
+
+    fn render(&mut self, _context: &mut ProcessorContext) {}
+  }
+
+  #[test]
+  fn set_sample_rate() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+
+    let planned = Arc::new(Mutex::new(Vec::new()));
+    engine
+      .create_processor("planned", PrepareProcessor(planned.clone()))
+      .unwrap();
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+
+    let unplanned = Arc::new(Mutex::new(Vec::new()));
+    engine
+      .create_processor("unplanned", PrepareProcessor(unplanned.clone()))
+      .unwrap();
+
+    engine.set_sample_rate(48_000).unwrap();
+    assert_eq!(engine.sample_rate(), 48_000);
+    renderer.render(16);
+
+    // prepared again by the renderer
+    assert_eq!(*planned.lock().unwrap(), vec![44_100, 48_000]);
+    assert!(unplanned.lock().unwrap().is_empty());
+
+    engine.update_render_plan().unwrap();
+    assert_eq!(*planned.lock().unwrap(), vec![44_100, 48_000]);
+    assert_eq!(*unplanned.lock().unwrap(), vec![48_000]);
+  }
+
+  struct RecordProcessor(Arc<Mutex<Vec<f32>>>);
+
+  impl Processor for RecordProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-in")]))
+        .with_parameters(vec![ParamDescriptor::new("level")])
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      let mut values = self.0.lock().unwrap();
+      values.extend_from_slice(context.parameter_buffer(0));
+    }
+  }
+
+  #[test]
+  fn automation_events() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 1_000,
+      ..EngineConfig::default()
+    });
+    let mut renderer = engine.take_renderer().unwrap();
+
+    let values = Arc::new(Mutex::new(Vec::with_capacity(16)));
+    let node = engine
+      .create_processor("record", RecordProcessor(values.clone()))
+      .unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let events_in = inner
+        .graph
+        .node_events_outputs(inner.graph.get_inputs_node());
+      let node_events_in = inner.graph.node_events_inputs(node.node_key);
+      inner
+        .graph
+        .connect_events(events_in.unwrap()[0].to(node_events_in.unwrap()[0]))
+        .unwrap();
+    }
+    engine.update_render_plan().unwrap();
+    // receive the render plan
+    renderer.render(0);
+
+    // one sample per millisecond, and the block starting at 10ms
+    renderer.set_block_timestamp(10_000_000);
+    let events = [
+      (12_000_000, 1.0, AutomationCurve::Linear),
+      (14_000_000, 0.25, AutomationCurve::Step),
+    ];
+    {
+      let buffer = renderer.get_events_inputs()[0].get_mut();
+      buffer.clear();
+      for (timestamp, value, curve) in events.iter().cloned() {
+        let data = AutomationData::new(ParamRef(0), value, curve);
+        let event = Event {
+          timestamp,
+          data: EventData::Automation(data),
+        };
+        buffer.push(event).unwrap();
+      }
+    }
+    renderer.render(6);
+    assert_eq!(renderer.block_timestamp(), 16_000_000);
+
+    renderer.get_events_inputs()[0].get_mut().clear();
+    renderer.render(2);
+
+    assert_eq!(
+      *values.lock().unwrap(),
+      vec![0.0, 0.5, 1.0, 1.0, 0.25, 0.25, 0.25, 0.25]
+    );
+  }
+
+  #[test]
+  fn current_parameter_values() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let level = LevelProcessor {
+      parameters: vec!["level"],
+      gain: 1.0,
+    };
+    let source = engine.create_processor("source", level).unwrap();
+    let mut target = engine.create_processor("target", ToneProcessor).unwrap();
+    source.set_parameter(0, 0.25).unwrap();
+    let source_out = source.audio_output("audio-out").unwrap();
+    target.modulate_parameter(0, source_out, -1.0).unwrap();
+    assert_eq!(target.current_parameter(0).unwrap(), 1.0);
+
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(target.current_parameter(0).unwrap(), 0.75);
+    assert_eq!(source.current_parameter(0).unwrap(), 0.25);
+    assert!(target.current_parameter(1).is_err());
+  }
+
+  #[test]
+  fn modulate_parameters_from_the_envelope_of_a_signal() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    // the target is created first, but rendered after the follower modulating it
+    let mut target = engine.create_processor("target", ToneProcessor).unwrap();
+    let level = LevelProcessor {
+      parameters: vec!["level"],
+      gain: 1.0,
+    };
+    let source = engine.create_processor("source", level).unwrap();
+    let follower = engine
+      .create_processor("follower", EnvelopeFollowerProcessor::new(44_100.0))
+      .unwrap();
+    follower
+      .set_parameter(EnvelopeFollowerProcessor::ATTACK_INDEX, 0.0)
+      .unwrap();
+    source
+      .audio_output("audio-out")
+      .unwrap()
+      .connect(
+        &follower
+          .audio_input(EnvelopeFollowerProcessor::AUDIO_IN_NAME)
+          .unwrap(),
+      )
+      .unwrap();
+    let envelope = follower
+      .audio_output(EnvelopeFollowerProcessor::AUDIO_OUT_NAME)
+      .unwrap();
+    target.modulate_parameter(0, envelope, -1.0).unwrap();
+
+    source.set_parameter(0, 0.5).unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(target.current_parameter(0).unwrap(), 0.5);
+
+    // the value set is the base of the modulation, rather than overwritten by it
+    target.set_parameter(0, 0.75).unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(target.current_parameter(0).unwrap(), 0.25);
+  }
+
+  #[test]
+  fn drive_parameters_from_macros() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let filter = engine.create_processor("filter", ToneProcessor).unwrap();
+    let eq = engine.create_processor("eq", ToneProcessor).unwrap();
+    let mut brightness = engine.create_macro("brightness", 0.0);
+    brightness
+      .add_target(&filter, 0, MacroRange::new(0.0, 1.0))
+      .unwrap();
+    brightness
+      .add_target(
+        &eq,
+        0,
+        MacroRange::new(1.0, 0.0).scale(ParamScale::Exponential(2.0)),
+      )
+      .unwrap();
+    assert!(brightness
+      .add_target(&eq, 1, MacroRange::new(0.0, 1.0))
+      .is_err());
+    assert_eq!(brightness.num_targets(), 2);
+
+    brightness.set_value(0.5).unwrap();
+    assert_eq!(brightness.target_value(&filter, 0), Some(0.5));
+    assert_eq!(brightness.target_value(&eq, 0), Some(0.75));
+    assert_eq!(filter.current_parameter(0).unwrap(), 1.0);
+
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(filter.current_parameter(0).unwrap(), 0.5);
+    assert_eq!(eq.current_parameter(0).unwrap(), 0.75);
+
+    assert_eq!(brightness.remove_targets(&eq), 1);
+    brightness.set_value(2.0).unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(filter.current_parameter(0).unwrap(), 1.0);
+    assert_eq!(eq.current_parameter(0).unwrap(), 0.75);
+  }
+
+  struct ToneProcessor;
+
+  impl Processor for ToneProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| {
+          ports
+            .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+            .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+        })
+        .with_parameters(vec![ParamDescriptor::new("level").initial(1.0)])
+    }
+
+    fn render(&mut self, _context: &mut ProcessorContext) {}
+  }
+
+  #[test]
+  fn bind_module_parameters() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let mut voice = engine
+      .create_module(
+        "voice",
+        ModuleDescriptor::new().with_parameters(vec![ParamDescriptor::new("cutoff").initial(0.5)]),
+      )
+      .unwrap();
+    let mut filter = voice
+      .create_module("filter", ModuleDescriptor::new())
+      .unwrap();
+    let osc = voice.create_processor("osc", ToneProcessor).unwrap();
+    let lpf = filter.create_processor("lpf", ToneProcessor).unwrap();
+    let outside = engine.create_processor("outside", ToneProcessor).unwrap();
+
+    voice.bind_parameter(0, &osc, 0).unwrap();
+    voice.bind_parameter(0, &lpf, 0).unwrap();
+    assert!(voice.bind_parameter(0, &outside, 0).is_err());
+    assert!(voice.bind_parameter(1, &osc, 0).is_err());
+    assert!(voice.bind_parameter(0, &osc, 1).is_err());
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(osc.current_parameter(0).unwrap(), 0.5);
+    assert_eq!(lpf.current_parameter(0).unwrap(), 0.5);
+
+    voice.set_parameter(0, 2.0).unwrap();
+    assert_eq!(voice.parameter(0).unwrap(), 1.0);
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(osc.current_parameter(0).unwrap(), 1.0);
+    assert_eq!(lpf.current_parameter(0).unwrap(), 1.0);
+
+    let document = engine.export_graph().unwrap();
+    let voice_document = &document.modules[0];
+    assert_eq!(voice_document.values, vec![1.0]);
+    assert_eq!(voice_document.param_bindings.len(), 2);
+    let mut imported_engine = Engine::default();
+    imported_engine
+      .import_graph(&document, |_| Some(Box::new(ToneProcessor)))
+      .unwrap();
+    assert_eq!(imported_engine.export_graph().unwrap(), document);
+
+    voice.unbind_parameter(0).unwrap();
+    voice.set_parameter(0, 0.25).unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    assert_eq!(osc.current_parameter(0).unwrap(), 1.0);
+  }
+
+  #[test]
+  fn export_and_import_graph() {
+    let mut engine = Engine::default();
+    let mut synth = engine
+      .create_module(
+        "synth",
+        ModuleDescriptor::new().with_audio_ports(|ports| {
+          ports
+            .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+            .dynamic_inputs(DynamicPorts::Limited(1))
+        }),
+      )
+      .unwrap();
+    let osc_a = synth.create_processor("osc-a", ToneProcessor).unwrap();
+    let osc_b = synth.create_processor("osc-b", ToneProcessor).unwrap();
+    let mut mixer = synth.create_processor("mixer", ToneProcessor).unwrap();
+    osc_a.set_parameter(0, 0.75).unwrap();
+    mixer
+      .modulate_parameter(0, osc_b.audio_output("audio-out").unwrap(), 0.5)
+      .unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let graph = &mut inner.graph;
+      let side_in = graph
+        .create_module_audio_input(synth.key, AudioDescriptor::new("side", 1))
+        .unwrap();
+      let osc_a_in = graph.node_audio_inputs(osc_a.node_key).unwrap()[0];
+      let osc_a_out = graph.node_audio_outputs(osc_a.node_key).unwrap()[0];
+      let osc_b_out = graph.node_audio_outputs(osc_b.node_key).unwrap()[0];
+      let mixer_in = graph.node_audio_inputs(mixer.node_key).unwrap()[0];
+      let mixer_out = graph.node_audio_outputs(mixer.node_key).unwrap()[0];
+      let synth_out = graph.module_audio_outputs(synth.key).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_audio(side_in.bind(osc_a_in)).unwrap();
+      graph.connect_audio(osc_a_out.to(mixer_in)).unwrap();
+      graph.add_audio(osc_b_out.to(mixer_in)).unwrap();
+      graph.connect_audio(mixer_out.bind(synth_out)).unwrap();
+      graph.connect_audio(synth_out.to(outputs_in)).unwrap();
+    }
+
+    let document = engine.export_graph().unwrap();
+    assert_eq!(document.modules.len(), 1);
+    assert_eq!(document.modules[0].full_name(), "/root/synth");
+    assert_eq!(document.nodes.len(), 3);
+    assert_eq!(document.audio_connections.len(), 5);
+    assert!(document.audio_connections.contains(&ConnectionDocument {
+      kind: ConnectionKind::NodeOutToNodeIn,
+      source: "/root/synth/osc-b:audio-out".to_string(),
+      destination: "/root/synth/mixer:audio-in".to_string(),
+      summed: true,
+    }));
+
+    let json = serde_json::to_string(&document).unwrap();
+    let loaded: GraphDocument = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, document);
+
+    let mut imported_engine = Engine::default();
+    let mut created = Vec::new();
+    imported_engine
+      .import_graph(&loaded, |node| {
+        created.push(node.full_name());
+        Some(Box::new(ToneProcessor))
+      })
+      .unwrap();
+    assert_eq!(
+      created,
+      vec![
+        "/root/synth/mixer",
+        "/root/synth/osc-a",
+        "/root/synth/osc-b"
+      ]
+    );
+    assert_eq!(imported_engine.export_graph().unwrap(), document);
+    imported_engine.update_render_plan().unwrap();
+  }
+
+  #[test]
+  fn import_graph_without_processor() {
+    let mut engine = Engine::default();
+    engine.create_processor("tone", ToneProcessor).unwrap();
+    let document = engine.export_graph().unwrap();
+
+    let result = Engine::default().import_graph(&document, |_| None);
+    assert!(matches!(result, Err(Error::ProcessorNotAvailable(path)) if path == "/root/tone"));
+  }
+
+  struct EmitProcessor;
+
+  impl Processor for EmitProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_events_ports(|ports| ports.static_outputs(vec![EventsDescriptor::new("events-out")]))
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      let event = Event {
+        timestamp: 5,
+        data: EventData::Transport(TransportMessage::Start),
+      };
+      // the events from the previous block were cleared
+      assert!(context.events_output(0).buffer().is_empty());
+      context.events_output(0).push(event).unwrap();
+    }
+  }
+
+  #[test]
+  fn output_events() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+    let events = engine.subscribe_events();
+
+    let node = engine.create_processor("emit", EmitProcessor).unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let node_events_out = inner.graph.node_events_outputs(node.node_key).unwrap()[0];
+      let outputs_node = inner.graph.get_outputs_node();
+      let events_out = inner.graph.node_events_inputs(outputs_node).unwrap()[0];
+      inner
+        .graph
+        .connect_events(node_events_out.to(events_out))
+        .unwrap();
+    }
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+    renderer.render(16);
+    engine.process_messages();
+
+    let expected = OutputEvent {
+      port: 0,
+      event: Event {
+        timestamp: 5,
+        data: EventData::Transport(TransportMessage::Start),
+      },
+    };
+    assert_eq!(
+      events.try_iter().collect::<Vec<_>>(),
+      vec![expected, expected]
+    );
+  }
+
+  #[derive(Default)]
+  struct ScheduleProcessor {
+    scheduled: bool,
+  }
+
+  impl Processor for ScheduleProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_events_ports(|ports| ports.static_outputs(vec![EventsDescriptor::new("events-out")]))
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      if !self.scheduled {
+        self.scheduled = true;
+        let event = Event {
+          timestamp: context.block_timestamp() + 1_000_000,
+          data: EventData::Transport(TransportMessage::Stop),
+        };
+        context.events_output(0).schedule(event).unwrap();
+      }
+    }
+  }
+
+  #[test]
+  fn schedule_events_for_later_blocks() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+    let events = engine.subscribe_events();
+
+    let node = engine
+      .create_processor("arpeggiator", ScheduleProcessor::default())
+      .unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let node_events_out = inner.graph.node_events_outputs(node.node_key).unwrap()[0];
+      let outputs_node = inner.graph.get_outputs_node();
+      let events_out = inner.graph.node_events_inputs(outputs_node).unwrap()[0];
+      inner
+        .graph
+        .connect_events(node_events_out.to(events_out))
+        .unwrap();
+    }
+    engine.update_render_plan().unwrap();
+
+    // blocks of 16 samples last about 0.36ms at 44100 Hz
+    renderer.render(16);
+    renderer.render(16);
+    engine.process_messages();
+    assert_eq!(events.try_iter().count(), 0);
+
+    // the scheduled events survive the changes of render plan
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+    renderer.render(16);
+    engine.process_messages();
+    let expected = OutputEvent {
+      port: 0,
+      event: Event {
+        timestamp: 1_000_000,
+        data: EventData::Transport(TransportMessage::Stop),
+      },
+    };
+    assert_eq!(events.try_iter().collect::<Vec<_>>(), vec![expected]);
+  }
+
+  struct ConstantProcessor(f32);
+
+  impl Processor for ConstantProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)]))
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      context.audio_output(0).channel_mut(0).fill(self.0);
+      context.audio_output(0).channel_mut(1).fill(-self.0);
+    }
+  }
+
+  #[test]
+  fn meters() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+    let node = engine
+      .create_processor("constant", ConstantProcessor(0.5))
+      .unwrap();
+    let output = node.audio_output("audio-out").unwrap();
+    assert_eq!(engine.meter(&output), None);
+
+    engine.enable_meter(&output).unwrap();
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+
+    let level = MeterLevel {
+      peak: 0.5,
+      rms: 0.5,
+    };
+    assert_eq!(engine.meter(&output), Some(vec![level, level]));
+
+    engine.disable_meter(&output);
+    assert_eq!(engine.meter(&output), None);
+  }
+
+  /// Outputs a gate during the blocks receiving some event
+  struct GateProcessor;
+
+  impl Processor for GateProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)]))
+        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-in")]))
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      let gate = if context.events_input(0).iter().next().is_some() {
+        1.0
+      } else {
+        0.0
+      };
+      context.audio_output(0).channel_mut(0).fill(gate);
+      context.audio_output(0).channel_mut(1).fill(-gate);
+    }
+  }
+
+  #[test]
+  fn render_offline() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 1_000,
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let node = engine.create_processor("gate", GateProcessor).unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let graph = &mut inner.graph;
+      let events_in = graph.node_events_outputs(graph.get_inputs_node()).unwrap()[0];
+      let node_events_in = graph.node_events_inputs(node.node_key).unwrap()[0];
+      let node_out = graph.node_audio_outputs(node.node_key).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_events(events_in.to(node_events_in)).unwrap();
+      graph.connect_audio(node_out.to(outputs_in)).unwrap();
+    }
+
+    let events = [Event {
+      timestamp: 5_000_000,
+      data: EventData::Transport(TransportMessage::Start),
+    }];
+    let outputs = engine.render_offline(10, &events).unwrap();
+
+    let gate = vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0];
+    let inverted_gate = gate.iter().map(|value| -value).collect::<Vec<f32>>();
+    assert_eq!(outputs, vec![gate, inverted_gate]);
+  }
+
+  /// Outputs the milliseconds played, or -1 while stopped
+  struct PlayheadProcessor;
+
+  impl Processor for PlayheadProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)]))
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      let transport = context.transport();
+      let value = if transport.is_playing() {
+        transport.clock().to_seconds() as f32 * 1_000.0
+      } else {
+        -1.0
+      };
+      context.audio_output(0).channel_mut(0).fill(value);
+      let tempo = f32::from(transport.tempo().get_value());
+      context.audio_output(0).channel_mut(1).fill(tempo);
+    }
+  }
+
+  #[test]
+  fn follow_the_transport() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 1_000,
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let node = engine
+      .create_processor("playhead", PlayheadProcessor)
+      .unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let graph = &mut inner.graph;
+      let node_out = graph.node_audio_outputs(node.node_key).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_audio(node_out.to(outputs_in)).unwrap();
+    }
+
+    let events = [
+      Event {
+        timestamp: 5_000_000,
+        data: EventData::Transport(TransportMessage::Start),
+      },
+      Event {
+        timestamp: 9_000_000,
+        data: EventData::Transport(TransportMessage::Tempo(Tempo::new(90))),
+      },
+    ];
+    let outputs = engine.render_offline(10, &events).unwrap();
+
+    let clock = vec![-1.0, -1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 0.0, 4.0, 4.0];
+    let tempo = vec![
+      120.0, 120.0, 120.0, 120.0, 120.0, 120.0, 120.0, 120.0, 90.0, 90.0,
+    ];
+    assert_eq!(outputs, vec![clock, tempo]);
+  }
+
+  /// Outputs the value of its level parameter multiplied by a gain
+  struct LevelProcessor {
+    parameters: Vec<&'static str>,
+    gain: f32,
+  }
+
+  impl Processor for LevelProcessor {
+    fn descriptor(&self) -> NodeDescriptor {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 1)]))
+        .with_parameters(
+          self
+            .parameters
+            .iter()
+            .copied()
+            .map(ParamDescriptor::new)
+            .collect(),
+        )
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      let index = self
+        .parameters
+        .iter()
+        .position(|id| *id == "level")
+        .unwrap();
+      let level = context.parameter(index).get();
+      context
+        .audio_output(0)
+        .channel_mut(0)
+        .fill(level * self.gain);
+    }
+  }
+
+  #[test]
+  fn observe_graph_changes() {
+    let mut engine = Engine::default();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let observed = events.clone();
+    let id = engine.subscribe(move |event| observed.borrow_mut().push(event.clone()));
+
+    let mut root = engine.root_module();
+    let level = LevelProcessor {
+      parameters: vec!["level"],
+      gain: 1.0,
+    };
+    let level = root.create_processor("level", level).unwrap();
+    let mut ones = root.create_processor("ones", OnesProcessor).unwrap();
+    level.set_parameter(0, 0.5).unwrap();
+    let level_out: NodeOut<AudioDescriptor> = level.audio_output("audio-out").unwrap().into();
+    let ones_in: NodeIn<AudioDescriptor> = ones.audio_input("audio-in").unwrap().into();
+    root.add_audio(level_out.to(ones_in)).unwrap();
+    ones.clear_audio_input("audio-in").unwrap();
+    ones.remove().unwrap();
+
+    let connection = ConnectionDocument {
+      kind: ConnectionKind::NodeOutToNodeIn,
+      source: "/root/level:audio-out".to_string(),
+      destination: "/root/ones:audio-in".to_string(),
+      summed: false,
+    };
+    assert_eq!(
+      events.borrow().as_slice(),
+      &[
+        GraphEvent::NodeAdded("/root/level".to_string()),
+        GraphEvent::NodeAdded("/root/ones".to_string()),
+        GraphEvent::ParamChanged {
+          node: "/root/level".to_string(),
+          index: 0,
+          value: 0.5
+        },
+        GraphEvent::Connected {
+          port_type: PortType::Audio,
+          connection: connection.clone()
+        },
+        GraphEvent::Disconnected {
+          port_type: PortType::Audio,
+          connection
+        },
+        GraphEvent::NodeRemoved("/root/ones".to_string()),
+      ]
+    );
+
+    assert!(engine.unsubscribe(id));
+    level.set_parameter(0, 1.0).unwrap();
+    assert_eq!(events.borrow().len(), 6);
+  }
+
+  #[test]
+  fn replace_processor() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 2,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let processor = LevelProcessor {
+      parameters: vec!["level", "color"],
+      gain: 1.0,
+    };
+    let descriptor = processor.descriptor();
+    let mut node = engine
+      .root_module()
+      .create_boxed_processor("level", descriptor, Box::new(processor))
+      .unwrap();
+    node.set_parameter(0, 0.5).unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let graph = &mut inner.graph;
+      let node_out = graph.node_audio_outputs(node.node_key).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_audio(node_out.to(outputs_in)).unwrap();
+    }
+    assert_eq!(engine.render_offline(2, &[]).unwrap(), vec![vec![0.5, 0.5]]);
+
+    let level_key = node.parameter_key(0);
+    node
+      .replace_processor(LevelProcessor {
+        parameters: vec!["gain", "level"],
+        gain: 2.0,
+      })
+      .unwrap();
+    assert_eq!(node.parameter_key(1), level_key);
+    assert_eq!(node.descriptor().unwrap().parameters[0].id, "gain");
+    assert_eq!(engine.render_offline(2, &[]).unwrap(), vec![vec![1.0, 1.0]]);
+
+    let result = node.replace_processor(ToneProcessor);
+    assert!(matches!(
+      result,
+      Err(Error::Graph(graph::Error::IncompatibleDescriptor(_)))
+    ));
+  }
+
+  #[test]
+  fn render_a_processor_at_its_own_sample_rate() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 48_000,
+      audio_buffer_size: 64,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let level = LevelProcessor {
+      parameters: vec!["level"],
+      gain: 1.0,
+    };
+    let node = engine
+      .create_resampled_processor("level", level, 32_000)
+      .unwrap();
+    node.set_parameter(0, 0.5).unwrap();
+    let latency = node.descriptor().unwrap().latency;
+    assert!(latency > 0);
+    node
+      .audio_output("audio-out")
+      .unwrap()
+      .connect(&engine.audio_outputs().unwrap())
+      .unwrap();
+
+    let output = engine.render_offline(64 * 8, &[]).unwrap();
+    assert_eq!(output[0].len(), 64 * 8);
+    assert!(output[0][64 * 4..]
+      .iter()
+      .all(|sample| (sample - 0.5).abs() < 1e-3));
+  }
+
+  /// Outputs ones whatever its input is
+  struct OnesProcessor;
+
+  impl Processor for OnesProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new().with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+          .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+      })
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      context.audio_output(0).channel_mut(0).fill(1.0);
+    }
+  }
+
+  fn connect_between_input_and_output(engine: &Engine, node: &ProcessorNode) {
+    let mut inner = engine.inner.borrow_mut();
+    let graph = &mut inner.graph;
+    let inputs_out = graph.node_audio_outputs(graph.get_inputs_node()).unwrap()[0];
+    let node_in = graph.node_audio_inputs(node.node_key).unwrap()[0];
+    let node_out = graph.node_audio_outputs(node.node_key).unwrap()[0];
+    let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+    graph.connect_audio(inputs_out.to(node_in)).unwrap();
+    graph.connect_audio(node_out.to(outputs_in)).unwrap();
+  }
+
+  #[test]
+  fn connect_to_the_audio_outputs() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let node = engine.create_processor("ones", OnesProcessor).unwrap();
+    let node_out = node.audio_output("audio-out").unwrap();
+    node_out.connect(&engine.audio_outputs().unwrap()).unwrap();
+    assert_eq!(engine.render_offline(4, &[]).unwrap(), vec![vec![1.0; 4]]);
+  }
+
+  #[test]
+  fn render_offline_with_inputs() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    engine
+      .audio_inputs()
+      .unwrap()
+      .connect(&engine.audio_outputs().unwrap())
+      .unwrap();
+    let inputs = vec![vec![0.5, 0.25, -0.5, 1.0, 0.75]];
+    assert_eq!(
+      engine.render_offline_with_inputs(&inputs, 7, &[]).unwrap(),
+      vec![vec![0.5, 0.25, -0.5, 1.0, 0.75, 0.0, 0.0]]
+    );
+  }
+
+  #[test]
+  fn adapt_mono_outputs_to_stereo_inputs() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 2,
+      ..EngineConfig::default()
+    });
+    let node = engine.create_processor("ones", OnesProcessor).unwrap();
+    connect_between_input_and_output(&engine, &node);
+    assert_eq!(
+      engine.render_offline(4, &[]).unwrap(),
+      vec![vec![1.0; 4], vec![1.0; 4]]
+    );
+  }
+
+  #[test]
+  fn bypass() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 1_000,
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let node = engine.create_processor("ones", OnesProcessor).unwrap();
+    connect_between_input_and_output(&engine, &node);
+    assert_eq!(engine.render_offline(4, &[]).unwrap(), vec![vec![1.0; 4]]);
+
+    node.set_bypass(true).unwrap();
+    assert!(node.is_bypassed());
+    let outputs = engine.render_offline(12, &[]).unwrap();
+    // it fades out into the input during 10ms
+    let expected = (1..=12).map(|index| (1.0 - 0.1 * index as f32).max(0.0));
+    for (output, expected) in outputs[0].iter().zip(expected) {
+      assert!(
+        (output - expected).abs() < 1e-5,
+        "{} != {}",
+        output,
+        expected
+      );
+    }
+
+    node.set_bypass(false).unwrap();
+    let outputs = engine.render_offline(12, &[]).unwrap();
+    assert!((outputs[0][0] - 0.1).abs() < 1e-5);
+    assert_eq!(outputs[0][11], 1.0);
+  }
+
+  #[test]
+  fn dry_wet() {
+    let descriptor = NodeDescriptor::new()
+      .with_dry_wet()
+      .with_parameters(vec![ParamDescriptor::new("gain")]);
+    assert_eq!(descriptor.parameters[1].id, NodeDescriptor::DRY_WET_ID);
+    assert_eq!(descriptor.dry_wet, Some(1));
+
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let mut renderer = engine.take_renderer().unwrap();
+    let descriptor = OnesProcessor::static_descriptor().with_dry_wet();
+    let node = engine
+      .root_module()
+      .create_boxed_processor("ones", descriptor, Box::new(OnesProcessor))
+      .unwrap();
+    connect_between_input_and_output(&engine, &node);
+    node.set_parameter(0, 0.25).unwrap();
+    engine.update_render_plan().unwrap();
+
+    renderer.render(0);
+    renderer.get_audio_inputs()[0].get_mut().fill(0.5);
+    renderer.render(4);
+    assert_eq!(
+      &renderer.get_audio_outputs()[0].as_slice()[0..4],
+      &[0.625; 4]
+    );
+  }
+
+  struct PanicProcessor;
+
+  impl Processor for PanicProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      OnesProcessor::static_descriptor()
+    }
+
+    fn render(&mut self, _context: &mut ProcessorContext) {
+      panic!("Processor failure");
+    }
+  }
+
+  #[test]
+  fn bypass_processors_that_panic() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let faults = engine.subscribe_faults();
+    let mut node = engine.create_processor("panic", PanicProcessor).unwrap();
+    connect_between_input_and_output(&engine, &node);
+
+    assert_eq!(engine.render_offline(8, &[]).unwrap(), vec![vec![0.0; 8]]);
+    assert_eq!(
+      faults.try_recv(),
+      Ok(ProcessorFault {
+        processor: node.processor_key()
+      })
+    );
+    assert!(node.is_faulted());
+    assert!(node.is_bypassed());
+
+    node.replace_processor(OnesProcessor).unwrap();
+    assert!(!node.is_faulted());
+    assert_eq!(engine.render_offline(4, &[]).unwrap(), vec![vec![1.0; 4]]);
+  }
+
+  #[test]
+  fn reject_plans_with_required_inputs_not_connected() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_input_channels: 1,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1).required()])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+    let node = engine
+      .root_module()
+      .create_boxed_processor("effect", descriptor, Box::new(OnesProcessor))
+      .unwrap();
+    assert!(matches!(
+      engine.update_render_plan(),
+      Err(Error::Controller(
+        crate::rendering::controller::Error::RequiredInputNotConnected(_, _)
+      ))
+    ));
+
+    connect_between_input_and_output(&engine, &node);
+    assert!(engine.update_render_plan().is_ok());
+  }
+
+  #[test]
+  fn render_offline_without_renderer() {
+    let mut engine = Engine::default();
+    engine.take_renderer();
+    assert!(matches!(
+      engine.render_offline(16, &[]),
+      Err(Error::RendererNotAvailable)
+    ));
+  }
+
+  #[derive(Default)]
+  struct Seed(u64);
+
+  impl StateSnapshot for Seed {
+    fn encode(&self) -> std::result::Result<Vec<u8>, StateError> {
+      encode_state(&self.0)
+    }
+
+    fn decode(&self, state: &[u8]) -> std::result::Result<SharedState, StateError> {
+      Ok(Arc::new(Seed(decode_state(state)?)))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+      self
+    }
+  }
+
+  #[derive(Default)]
+  struct SeedProcessor {
+    seed: Arc<Seed>,
+  }
+
+  impl SeedProcessor {
+    fn new(seed: u64) -> Self {
+      Self {
+        seed: Arc::new(Seed(seed)),
+      }
+    }
+  }
+
+  impl Processor for SeedProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 1)]))
+    }
+
+    fn state(&self) -> Option<SharedState> {
+      Some(self.seed.clone())
+    }
+
+    fn set_state(&mut self, state: &mut SharedState) -> std::result::Result<(), StateError> {
+      swap_state(&mut self.seed, state)
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      context
+        .audio_output(0)
+        .channel_mut(0)
+        .fill(self.seed.0 as f32);
+    }
+  }
+
+  #[test]
+  fn save_and_load_processor_states() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_buffer_size: 4,
+      ..EngineConfig::default()
+    });
+    let node = engine
+      .create_processor("noise", SeedProcessor::new(7))
+      .unwrap();
+    let tone = engine.create_processor("tone", ToneProcessor).unwrap();
+    let states = engine.subscribe_states();
+    assert_eq!(
+      engine.save_processor_state(&node).unwrap(),
+      Some(b"7".to_vec())
+    );
+    assert_eq!(engine.save_processor_state(&tone).unwrap(), None);
+    assert!(matches!(
+      engine.load_processor_state(&node, b"seed".to_vec()),
+      Err(Error::State(name, StateError::Decode(_))) if name == "/root/noise"
+    ));
+    engine.load_processor_state(&node, b"3".to_vec()).unwrap();
+    let loaded = StateLoaded {
+      processor: node.processor_key,
+      result: Ok(()),
+    };
+    assert_eq!(states.try_iter().collect::<Vec<_>>(), vec![loaded.clone()]);
+    assert_eq!(
+      engine.save_processor_state(&node).unwrap(),
+      Some(b"3".to_vec())
+    );
+
+    // the renderer swaps the states once the processor is in a render plan
+    engine.update_render_plan().unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    engine.load_processor_state(&node, b"11".to_vec()).unwrap();
+    engine.render_offline(4, &[]).unwrap();
+    engine.process_messages();
+    assert_eq!(states.try_iter().collect::<Vec<_>>(), vec![loaded]);
+    assert_eq!(
+      engine.save_processor_state(&node).unwrap(),
+      Some(b"11".to_vec())
+    );
+
+    let mut document = engine.export_graph().unwrap();
+    engine.save_states(&mut document).unwrap();
+    let mut imported_engine = Engine::default();
+    imported_engine
+      .import_graph(&document, |node| match node.name.as_str() {
+        "noise" => Some(Box::new(SeedProcessor::default())),
+        _ => Some(Box::new(ToneProcessor)),
+      })
+      .unwrap();
+    let imported_node = imported_engine.find_node("root/noise").unwrap();
+    assert_eq!(
+      imported_engine
+        .save_processor_state(&imported_node)
+        .unwrap(),
+      Some(b"11".to_vec())
+    );
+  }
+
+  #[test]
+  fn save_processor_states_without_waiting_for_the_renderer() {
+    let mut engine = Engine::new(EngineConfig {
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let node = engine
+      .create_processor("noise", SeedProcessor::new(7))
+      .unwrap();
+    node
+      .audio_output("audio-out")
+      .unwrap()
+      .connect(&engine.audio_outputs().unwrap())
+      .unwrap();
+    let states = engine.subscribe_states();
+    engine.update_render_plan().unwrap();
+    let mut renderer = engine.take_renderer().unwrap();
+    assert_eq!(
+      engine.save_processor_state(&node).unwrap(),
+      Some(b"7".to_vec())
+    );
+
+    engine.load_processor_state(&node, b"11".to_vec()).unwrap();
+    assert_eq!(
+      engine.save_processor_state(&node).unwrap(),
+      Some(b"11".to_vec())
+    );
+    engine.process_messages();
+    assert!(states.try_recv().is_err());
+
+    // the processor keeps rendering while the state is swapped
+    renderer.render(16);
+    engine.process_messages();
+    assert_eq!(
+      states.try_iter().collect::<Vec<_>>(),
+      vec![StateLoaded {
+        processor: node.processor_key,
+        result: Ok(()),
+      }]
+    );
+    let output = &renderer.get_audio_outputs()[0].as_slice()[0..16];
+    assert!(output.iter().all(|sample| *sample == 11.0));
+  }
+
+  struct DropProcessor(Arc<Mutex<bool>>);
+
+  impl Processor for DropProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)]))
+        .with_parameters(vec![ParamDescriptor::new("level")])
+    }
+
+    fn render(&mut self, _context: &mut ProcessorContext) {}
+  }
+
+  impl Drop for DropProcessor {
+    fn drop(&mut self) {
+      *self.0.lock().unwrap() = true;
+    }
+  }
+
+  #[test]
+  fn remove_node_once_the_renderer_retires_its_plan() {
+    let mut engine = Engine::default();
+    let mut renderer = engine.take_renderer().unwrap();
+    let dropped = Arc::new(Mutex::new(false));
+    let node = engine
+      .create_processor("drop", DropProcessor(dropped.clone()))
+      .unwrap();
+    engine
+      .enable_meter(&node.audio_output("audio-out").unwrap())
+      .unwrap();
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+
+    let param_key = node.param_keys[0];
+    node.remove().unwrap();
+    assert!(engine.find_node("root/drop").is_none());
+    assert!(engine
+      .inner
+      .borrow()
+      .controller
+      .get_parameter_value(param_key)
+      .is_err());
+
+    // the current plan is still rendering it
+    engine.process_messages();
+    assert!(!*dropped.lock().unwrap());
+
+    engine.update_render_plan().unwrap();
+    renderer.render(16);
+    engine.process_messages();
+    assert!(*dropped.lock().unwrap());
+  }
+
+  #[test]
+  fn remove_module_with_its_nodes() {
+    let mut engine = Engine::default();
+    let dropped = Arc::new(Mutex::new(false));
+    let mut module = engine
+      .create_module("synth", ModuleDescriptor::new())
+      .unwrap();
+    let mut voices = module
+      .create_module("voices", ModuleDescriptor::new())
+      .unwrap();
+    voices
+      .create_processor("voice", DropProcessor(dropped.clone()))
+      .unwrap();
+
+    module.remove().unwrap();
+    assert!(engine.find_module("root/synth/voices").is_none());
+    assert!(engine.find_node("root/synth/voices/voice").is_none());
+    assert!(*dropped.lock().unwrap());
+  }
+
+  #[test]
+  fn remove_a_connected_module() {
+    let mut engine = Engine::default();
+    let mut synth = engine
+      .create_module(
+        "synth",
+        ModuleDescriptor::new().with_audio_ports(|ports| {
+          ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)])
+        }),
+      )
+      .unwrap();
+    let mut voices = synth
+      .create_module(
+        "voices",
+        ModuleDescriptor::new().with_audio_ports(|ports| {
+          ports.static_outputs(vec![AudioDescriptor::new("audio-out", 2)])
+        }),
+      )
+      .unwrap();
+    let voice = voices.create_processor("voice", ToneProcessor).unwrap();
+    {
+      let mut inner = engine.inner.borrow_mut();
+      let graph = &mut inner.graph;
+      let voice_out = graph.node_audio_outputs(voice.node_key).unwrap()[0];
+      let voices_out = graph.module_audio_outputs(voices.key).unwrap()[0];
+      let synth_out = graph.module_audio_outputs(synth.key).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_audio(voice_out.bind(voices_out)).unwrap();
+      graph.connect_audio(voices_out.bind(synth_out)).unwrap();
+      graph.connect_audio(synth_out.to(outputs_in)).unwrap();
+    }
+    engine.update_render_plan().unwrap();
+
+    // the output of the synth is left bound to the removed voices
+    voices.remove().unwrap();
+    engine.update_render_plan().unwrap();
+
+    // the outputs node is left connected to the removed synth
+    synth.remove().unwrap();
+    engine.update_render_plan().unwrap();
+    assert_eq!(
+      engine.render_offline(4, &[]).unwrap(),
+      vec![vec![0.0; 4]; 2]
+    );
+  }
+
+  #[test]
+  fn parameter_gestures() {
+    let mut engine = Engine::default();
+    let gestures = engine.subscribe_gestures();
+    let node = engine
+      .create_processor("record", RecordProcessor(Arc::new(Mutex::new(Vec::new()))))
+      .unwrap();
+    let param_key = node.parameter_key(0).unwrap();
+    let param_value = engine
+      .inner
+      .borrow()
+      .controller
+      .get_parameter_value(param_key)
+      .unwrap();
+
+    node.begin_gesture(0).unwrap();
+    assert!(param_value.is_in_gesture());
+    node.set_parameter(0, 0.5).unwrap();
+    node.end_gesture(0).unwrap();
+    assert!(!param_value.is_in_gesture());
+    assert!(node.begin_gesture(1).is_err());
+
+    let gesture = |kind, value| ParamGesture {
+      param: param_key,
+      kind,
+      value,
+    };
+    assert_eq!(
+      gestures.try_iter().collect::<Vec<_>>(),
+      vec![
+        gesture(GestureKind::Begin, 0.0),
+        gesture(GestureKind::End, 0.5)
+      ]
+    );
+  }
+
+  #[test]
+  fn snapshot_and_restore() {
+    let mut engine = Engine::default();
+    let mut synth = engine
+      .create_module("synth", ModuleDescriptor::new())
+      .unwrap();
+    let osc = synth.create_processor("osc", ToneProcessor).unwrap();
+    osc.set_parameter(0, 0.25).unwrap();
+
+    let snapshot = engine.snapshot().unwrap();
+    assert_eq!(snapshot.graph.nodes[0].values, vec![0.25]);
+
+    osc.set_parameter(0, 0.75).unwrap();
+    engine.restore(&snapshot).unwrap();
+    assert_eq!(engine.snapshot().unwrap(), snapshot);
+
+    osc.remove().unwrap();
+    assert!(matches!(
+      engine.restore(&snapshot),
+      Err(Error::NodeNotFound(path)) if path == "/root/synth/osc"
+    ));
+  }
+
+  #[test]
+  fn restore_with_factory() {
+    let mut engine = Engine::default();
+    let mut synth = engine
+      .create_module("synth", ModuleDescriptor::new())
+      .unwrap();
+    let osc = synth.create_processor("osc", ToneProcessor).unwrap();
+    osc.set_parameter(0, 0.25).unwrap();
+    let snapshot = engine.snapshot().unwrap();
+
+    synth.create_processor("filter", ToneProcessor).unwrap();
+    engine.create_module("fx", ModuleDescriptor::new()).unwrap();
+    engine
+      .restore_with(&snapshot, |_| Some(Box::new(ToneProcessor)))
+      .unwrap();
+
+    assert_eq!(engine.snapshot().unwrap(), snapshot);
+    assert!(engine.find_module("/root/fx").is_none());
+    assert_eq!(engine.inner.borrow().processors.len(), 1);
+  }
+}
@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use crate::graph;
+use crate::processor::StateError;
 use crate::rendering::controller;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -12,4 +13,19 @@ pub enum Error {
 
   #[error("Controller: {0}")]
   Controller(#[from] controller::Error),
+
+  #[error("Node not found: {0}")]
+  NodeNotFound(String),
+
+  #[error("Shared parameter not found: {0}")]
+  SharedParameterNotFound(usize),
+
+  #[error("Processor not available for node '{0}'")]
+  ProcessorNotAvailable(String),
+
+  #[error("The renderer has been taken")]
+  RendererNotAvailable,
+
+  #[error("State of node '{0}': {1}")]
+  State(String, StateError),
 }
@@ -18,6 +18,17 @@ pub enum Connection<D> {
   NodeOutToNodeIn(NodeOut<D>, NodeIn<D>),
 }
 
+impl<D> Clone for Connection<D>
+where
+  D: PortDescriptor,
+{
+  fn clone(&self) -> Self {
+    *self
+  }
+}
+
+impl<D> Copy for Connection<D> where D: PortDescriptor {}
+
 pub type ModuleAudioIn = ModuleIn<AudioDescriptor>;
 pub type ModuleAudioOut = ModuleOut<AudioDescriptor>;
 pub type NodeAudioIn = NodeIn<AudioDescriptor>;
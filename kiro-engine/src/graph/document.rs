@@ -0,0 +1,659 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::connection::{Connection, ModuleIn, ModuleOut, NodeIn, NodeOut};
+use crate::graph::error::{Error, Result};
+use crate::graph::module::{Module, ModuleKey};
+use crate::graph::node::{Node, NodeKey};
+use crate::graph::port::{
+  port_path, AudioDescriptor, DescriptorPorts, EventsDescriptor, InputPort, InputSource, NodeLike,
+  OutputPort, OutputSource, PortAccessor, PortDescriptor, Ports,
+};
+use crate::graph::{Graph, ModuleDescriptor, NodeDescriptor};
+use crate::key_store::KeyStoreWithId;
+
+/// Structure of a graph referring to modules, nodes and ports by their paths rather than keys,
+/// so it can be saved and loaded again into a different engine.
+///
+/// Ports are referenced as `<module or node path>:<port id>`, as in `/root/synth/osc:audio-out`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct GraphDocument {
+  /// Modules, sorted so parents come before their children
+  pub modules: Vec<ModuleDocument>,
+  pub nodes: Vec<NodeDocument>,
+  pub audio_connections: Vec<ConnectionDocument>,
+  pub events_connections: Vec<ConnectionDocument>,
+  pub modulations: Vec<ModulationDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleDocument {
+  /// Path of the parent module
+  pub path: String,
+  pub name: String,
+  pub descriptor: ModuleDescriptor,
+  pub dynamic_ports: DynamicPortsDocument,
+  /// Values of the parameters, in the same order as declared in the descriptor
+  #[serde(default)]
+  pub values: Vec<f32>,
+  #[serde(default)]
+  pub param_bindings: Vec<ParamBindingDocument>,
+}
+
+impl ModuleDocument {
+  pub fn full_name(&self) -> String {
+    format!("{}/{}", self.path, self.name)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDocument {
+  /// Path of the parent module
+  pub path: String,
+  pub name: String,
+  pub descriptor: NodeDescriptor,
+  pub dynamic_ports: DynamicPortsDocument,
+  /// Values of the parameters, in the same order as declared in the descriptor
+  pub values: Vec<f32>,
+  /// Internal state of the processor, as captured by [`crate::Engine::save_states`]
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub state: Option<Vec<u8>>,
+}
+
+impl NodeDocument {
+  pub fn full_name(&self) -> String {
+    format!("{}/{}", self.path, self.name)
+  }
+}
+
+/// Ports created after the module or node, in addition to the static ones from the descriptor.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DynamicPortsDocument {
+  pub audio_inputs: Vec<AudioDescriptor>,
+  pub audio_outputs: Vec<AudioDescriptor>,
+  pub events_inputs: Vec<EventsDescriptor>,
+  pub events_outputs: Vec<EventsDescriptor>,
+}
+
+impl DynamicPortsDocument {
+  fn new(ports: &Ports, descriptor: &DescriptorPorts) -> Self {
+    Self {
+      audio_inputs: dynamic_inputs(&ports.audio_input_ports, &descriptor.audio.static_inputs),
+      audio_outputs: dynamic_outputs(&ports.audio_output_ports, &descriptor.audio.static_outputs),
+      events_inputs: dynamic_inputs(&ports.events_input_ports, &descriptor.events.static_inputs),
+      events_outputs: dynamic_outputs(
+        &ports.events_output_ports,
+        &descriptor.events.static_outputs,
+      ),
+    }
+  }
+}
+
+/// The kind of [`Connection`] between two ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionKind {
+  ModuleOutBindModuleOut,
+  ModuleOutToModuleIn,
+  ModuleOutToNodeIn,
+  ModuleInBindModuleIn,
+  ModuleInBindNodeIn,
+  NodeOutBindModuleOut,
+  NodeOutToModuleIn,
+  NodeOutToNodeIn,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionDocument {
+  pub kind: ConnectionKind,
+  pub source: String,
+  pub destination: String,
+  /// Whether it is added to the sources already connected to the destination
+  pub summed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamBindingDocument {
+  /// Id of the parameter of the module
+  pub param: String,
+  /// Path of the node inside the module
+  pub node: String,
+  /// Id of the parameter of the node
+  pub node_param: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModulationDocument {
+  /// Path of the audio output of the modulating node
+  pub source: String,
+  /// Path of the modulated node
+  pub node: String,
+  /// Id of the modulated parameter
+  pub param: String,
+  pub depth: f32,
+}
+
+impl Graph {
+  /// Describe the modules, nodes, connections and modulations of the graph, except for the root
+  /// module and the graph inputs and outputs nodes, which are always available.
+  ///
+  /// The parameter values of every node are provided by `values`.
+  pub fn export_document<F>(&self, mut values: F) -> Result<GraphDocument>
+  where
+    F: FnMut(NodeKey) -> Vec<f32>,
+  {
+    let mut modules = self.modules.iter().collect::<Vec<_>>();
+    modules.sort_by_cached_key(|(_, module)| module.full_name());
+    let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+    nodes.sort_by_cached_key(|(_, node)| node.full_name());
+
+    let mut document = GraphDocument::default();
+
+    for (module_key, module) in modules.iter().cloned() {
+      if module_key != self.root_module {
+        document.modules.push(ModuleDocument {
+          path: module.path.clone(),
+          name: module.name.clone(),
+          descriptor: module.descriptor.clone(),
+          dynamic_ports: DynamicPortsDocument::new(&module.ports, &module.descriptor.ports),
+          values: module.param_values.clone(),
+          param_bindings: self.export_param_bindings(module)?,
+        });
+      }
+      self.export_module_connections::<AudioDescriptor>(module, &mut document.audio_connections)?;
+      self
+        .export_module_connections::<EventsDescriptor>(module, &mut document.events_connections)?;
+    }
+
+    for (node_key, node) in nodes.iter().cloned() {
+      if node_key != self.inputs_node && node_key != self.outputs_node {
+        document.nodes.push(NodeDocument {
+          path: node.path.clone(),
+          name: node.name.clone(),
+          descriptor: node.descriptor.clone(),
+          dynamic_ports: DynamicPortsDocument::new(&node.ports, &node.descriptor.ports),
+          values: values(node_key),
+          state: None,
+        });
+      }
+      self.export_node_connections::<AudioDescriptor>(node, &mut document.audio_connections)?;
+      self.export_node_connections::<EventsDescriptor>(node, &mut document.events_connections)?;
+
+      let mut modulations = node.param_modulations.iter().collect::<Vec<_>>();
+      modulations.sort_by_key(|(index, _)| **index);
+      for (index, modulation) in modulations {
+        let source = self.node_out_path(modulation.source)?;
+        let param = node
+          .descriptor
+          .parameters
+          .get(*index)
+          .ok_or_else(|| Error::ParamNotFound(node.full_name(), *index))?;
+        document.modulations.push(ModulationDocument {
+          source,
+          node: node.full_name(),
+          param: param.id.clone(),
+          depth: modulation.depth,
+        });
+      }
+    }
+
+    Ok(document)
+  }
+
+  /// Create the dynamic ports of a module that was created from a document.
+  pub fn import_module_ports(
+    &mut self,
+    module_key: ModuleKey,
+    ports: &DynamicPortsDocument,
+  ) -> Result<()> {
+    for descriptor in ports.audio_inputs.iter() {
+      self.create_module_audio_input(module_key, descriptor.clone())?;
+    }
+    for descriptor in ports.audio_outputs.iter() {
+      self.create_module_audio_output(module_key, descriptor.clone())?;
+    }
+    for descriptor in ports.events_inputs.iter() {
+      self.create_module_events_input(module_key, descriptor.clone())?;
+    }
+    for descriptor in ports.events_outputs.iter() {
+      self.create_module_events_output(module_key, descriptor.clone())?;
+    }
+    Ok(())
+  }
+
+  /// Create the dynamic ports of a node that was created from a document.
+  pub fn import_node_ports(
+    &mut self,
+    node_key: NodeKey,
+    ports: &DynamicPortsDocument,
+  ) -> Result<()> {
+    for descriptor in ports.audio_inputs.iter() {
+      self.create_node_audio_input(node_key, descriptor.clone())?;
+    }
+    for descriptor in ports.audio_outputs.iter() {
+      self.create_node_audio_output(node_key, descriptor.clone())?;
+    }
+    for descriptor in ports.events_inputs.iter() {
+      self.create_node_events_input(node_key, descriptor.clone())?;
+    }
+    for descriptor in ports.events_outputs.iter() {
+      self.create_node_events_output(node_key, descriptor.clone())?;
+    }
+    Ok(())
+  }
+
+  /// Connect the ports, modulate the parameters and bind the module parameters described in a document,
+  /// once all its modules and nodes are available.
+  pub fn import_connections(&mut self, document: &GraphDocument) -> Result<()> {
+    for connection in document.audio_connections.iter() {
+      self.import_connection::<AudioDescriptor>(connection)?;
+    }
+    for connection in document.events_connections.iter() {
+      self.import_connection::<EventsDescriptor>(connection)?;
+    }
+    for modulation in document.modulations.iter() {
+      let source = self.resolve_node_out(&modulation.source)?;
+      let node_key = self.node_from_path(&modulation.node)?;
+      let node = self.get_node(node_key)?;
+      let index = node
+        .descriptor
+        .parameters
+        .iter()
+        .position(|param| param.id == modulation.param)
+        .ok_or_else(|| Error::PathNotFound(format!("{}:{}", modulation.node, modulation.param)))?;
+      self.modulate_param(source, node_key, index, modulation.depth)?;
+    }
+    for module_document in document.modules.iter() {
+      let module_key = self.module_from_path(&module_document.full_name())?;
+      for binding in module_document.param_bindings.iter() {
+        let index = self
+          .get_module(module_key)?
+          .descriptor
+          .parameters
+          .iter()
+          .position(|param| param.id == binding.param)
+          .ok_or_else(|| {
+            Error::PathNotFound(format!("{}:{}", module_document.full_name(), binding.param))
+          })?;
+        let node_key = self.node_from_path(&binding.node)?;
+        let node_index = self
+          .get_node(node_key)?
+          .descriptor
+          .parameters
+          .iter()
+          .position(|param| param.id == binding.node_param)
+          .ok_or_else(|| Error::PathNotFound(format!("{}:{}", binding.node, binding.node_param)))?;
+        self.bind_module_param(module_key, index, node_key, node_index)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn export_param_bindings(&self, module: &Module) -> Result<Vec<ParamBindingDocument>> {
+    let mut documents = Vec::new();
+    for (param, bindings) in module
+      .descriptor
+      .parameters
+      .iter()
+      .zip(module.param_bindings.iter())
+    {
+      for binding in bindings.iter() {
+        let node = self.get_node(binding.node)?;
+        let node_param = node
+          .descriptor
+          .parameters
+          .get(binding.index)
+          .ok_or_else(|| Error::ParamNotFound(node.full_name(), binding.index))?;
+        documents.push(ParamBindingDocument {
+          param: param.id.clone(),
+          node: node.full_name(),
+          node_param: node_param.id.clone(),
+        });
+      }
+    }
+    Ok(documents)
+  }
+
+  pub(crate) fn module_from_path(&self, path: &str) -> Result<ModuleKey> {
+    self
+      .find_module(path)
+      .ok_or_else(|| Error::PathNotFound(path.to_string()))
+  }
+
+  pub(crate) fn node_from_path(&self, path: &str) -> Result<NodeKey> {
+    self
+      .find_node(path)
+      .ok_or_else(|| Error::PathNotFound(path.to_string()))
+  }
+
+  fn export_module_connections<D>(
+    &self,
+    module: &Module,
+    connections: &mut Vec<ConnectionDocument>,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    for (_, port) in module.ports.get_input().ordered_iter() {
+      let destination = port_path(module, port);
+      for (index, source) in port.sources().enumerate() {
+        let (kind, source) = match source {
+          InputSource::ModuleBinding(module_in) => (
+            ConnectionKind::ModuleInBindModuleIn,
+            self.module_in_path(*module_in)?,
+          ),
+          InputSource::ModuleConnection(module_out) => (
+            ConnectionKind::ModuleOutToModuleIn,
+            self.module_out_path(*module_out)?,
+          ),
+          InputSource::NodeConnection(node_out) => (
+            ConnectionKind::NodeOutToModuleIn,
+            self.node_out_path(*node_out)?,
+          ),
+        };
+        connections.push(ConnectionDocument {
+          kind,
+          source,
+          destination: destination.clone(),
+          summed: index > 0,
+        });
+      }
+    }
+
+    for (_, port) in module.ports.get_output().ordered_iter() {
+      let destination = port_path(module, port);
+      let (kind, source) = match port.source.as_ref() {
+        Some(OutputSource::ModuleBinding(module_out)) => (
+          ConnectionKind::ModuleOutBindModuleOut,
+          self.module_out_path(*module_out)?,
+        ),
+        Some(OutputSource::NodeBinding(node_out)) => (
+          ConnectionKind::NodeOutBindModuleOut,
+          self.node_out_path(*node_out)?,
+        ),
+        None => continue,
+      };
+      connections.push(ConnectionDocument {
+        kind,
+        source,
+        destination,
+        summed: false,
+      });
+    }
+
+    Ok(())
+  }
+
+  fn export_node_connections<D>(
+    &self,
+    node: &Node,
+    connections: &mut Vec<ConnectionDocument>,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    for (_, port) in node.ports.get_input().ordered_iter() {
+      let destination = port_path(node, port);
+      for (index, source) in port.sources().enumerate() {
+        let (kind, source) = match source {
+          InputSource::ModuleBinding(module_in) => (
+            ConnectionKind::ModuleInBindNodeIn,
+            self.module_in_path(*module_in)?,
+          ),
+          InputSource::ModuleConnection(module_out) => (
+            ConnectionKind::ModuleOutToNodeIn,
+            self.module_out_path(*module_out)?,
+          ),
+          InputSource::NodeConnection(node_out) => (
+            ConnectionKind::NodeOutToNodeIn,
+            self.node_out_path(*node_out)?,
+          ),
+        };
+        connections.push(ConnectionDocument {
+          kind,
+          source,
+          destination: destination.clone(),
+          summed: index > 0,
+        });
+      }
+    }
+    Ok(())
+  }
+
+  fn import_connection<D>(&mut self, connection: &ConnectionDocument) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let source = connection.source.as_str();
+    let destination = connection.destination.as_str();
+    let resolved: Connection<D> = match connection.kind {
+      ConnectionKind::ModuleOutBindModuleOut => Connection::ModuleOutBindModuleOut(
+        self.resolve_module_out(source)?,
+        self.resolve_module_out(destination)?,
+      ),
+      ConnectionKind::ModuleOutToModuleIn => Connection::ModuleOutToModuleIn(
+        self.resolve_module_out(source)?,
+        self.resolve_module_in(destination)?,
+      ),
+      ConnectionKind::ModuleOutToNodeIn => Connection::ModuleOutToNodeIn(
+        self.resolve_module_out(source)?,
+        self.resolve_node_in(destination)?,
+      ),
+      ConnectionKind::ModuleInBindModuleIn => Connection::ModuleInBindModuleIn(
+        self.resolve_module_in(source)?,
+        self.resolve_module_in(destination)?,
+      ),
+      ConnectionKind::ModuleInBindNodeIn => Connection::ModuleInBindNodeIn(
+        self.resolve_module_in(source)?,
+        self.resolve_node_in(destination)?,
+      ),
+      ConnectionKind::NodeOutBindModuleOut => Connection::NodeOutBindModuleOut(
+        self.resolve_node_out(source)?,
+        self.resolve_module_out(destination)?,
+      ),
+      ConnectionKind::NodeOutToModuleIn => Connection::NodeOutToModuleIn(
+        self.resolve_node_out(source)?,
+        self.resolve_module_in(destination)?,
+      ),
+      ConnectionKind::NodeOutToNodeIn => Connection::NodeOutToNodeIn(
+        self.resolve_node_out(source)?,
+        self.resolve_node_in(destination)?,
+      ),
+    };
+
+    if connection.summed {
+      self.add(resolved)
+    } else {
+      self.connect(resolved)
+    }
+  }
+
+  /// Describe a connection using the paths of its ports rather than their keys.
+  pub fn connection_document<D>(&self, connection: &Connection<D>) -> Result<ConnectionDocument>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let (kind, source, destination) = match connection {
+      Connection::ModuleOutBindModuleOut(src, dst) => (
+        ConnectionKind::ModuleOutBindModuleOut,
+        self.module_out_path(*src)?,
+        self.module_out_path(*dst)?,
+      ),
+      Connection::ModuleOutToModuleIn(src, dst) => (
+        ConnectionKind::ModuleOutToModuleIn,
+        self.module_out_path(*src)?,
+        self.module_in_path(*dst)?,
+      ),
+      Connection::ModuleOutToNodeIn(src, dst) => (
+        ConnectionKind::ModuleOutToNodeIn,
+        self.module_out_path(*src)?,
+        self.node_in_path(*dst)?,
+      ),
+      Connection::ModuleInBindModuleIn(src, dst) => (
+        ConnectionKind::ModuleInBindModuleIn,
+        self.module_in_path(*src)?,
+        self.module_in_path(*dst)?,
+      ),
+      Connection::ModuleInBindNodeIn(src, dst) => (
+        ConnectionKind::ModuleInBindNodeIn,
+        self.module_in_path(*src)?,
+        self.node_in_path(*dst)?,
+      ),
+      Connection::NodeOutBindModuleOut(src, dst) => (
+        ConnectionKind::NodeOutBindModuleOut,
+        self.node_out_path(*src)?,
+        self.module_out_path(*dst)?,
+      ),
+      Connection::NodeOutToModuleIn(src, dst) => (
+        ConnectionKind::NodeOutToModuleIn,
+        self.node_out_path(*src)?,
+        self.module_in_path(*dst)?,
+      ),
+      Connection::NodeOutToNodeIn(src, dst) => (
+        ConnectionKind::NodeOutToNodeIn,
+        self.node_out_path(*src)?,
+        self.node_in_path(*dst)?,
+      ),
+    };
+    Ok(ConnectionDocument {
+      kind,
+      source,
+      destination,
+      summed: false,
+    })
+  }
+
+  fn module_in_path<D>(&self, module_in: ModuleIn<D>) -> Result<String>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let module = self.get_module(module_in.module_key())?;
+    let port = module.get_input_port(module_in.input_port_key())?;
+    Ok(port_path(module, port))
+  }
+
+  fn module_out_path<D>(&self, module_out: ModuleOut<D>) -> Result<String>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let module = self.get_module(module_out.module_key())?;
+    let port = module.get_output_port(module_out.output_port_key())?;
+    Ok(port_path(module, port))
+  }
+
+  fn node_in_path<D>(&self, node_in: NodeIn<D>) -> Result<String>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let node = self.get_node(node_in.node_key())?;
+    let port = node.get_input_port(node_in.input_port_key())?;
+    Ok(port_path(node, port))
+  }
+
+  fn node_out_path<D>(&self, node_out: NodeOut<D>) -> Result<String>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let node = self.get_node(node_out.node_key())?;
+    let port = node.get_output_port(node_out.output_port_key())?;
+    Ok(port_path(node, port))
+  }
+
+  fn resolve_module_in<D>(&self, path: &str) -> Result<ModuleIn<D>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let (owner, port) = split_port_path(path)?;
+    let module_key = self.module_from_path(owner)?;
+    let module = self.get_module(module_key)?;
+    let port_key = module
+      .ports
+      .get_input()
+      .key_from_id(port)
+      .ok_or_else(|| Error::PortNotFound(path.to_string()))?;
+    Ok(ModuleIn(module_key, port_key))
+  }
+
+  fn resolve_module_out<D>(&self, path: &str) -> Result<ModuleOut<D>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let (owner, port) = split_port_path(path)?;
+    let module_key = self.module_from_path(owner)?;
+    let module = self.get_module(module_key)?;
+    let port_key = module
+      .ports
+      .get_output()
+      .key_from_id(port)
+      .ok_or_else(|| Error::PortNotFound(path.to_string()))?;
+    Ok(ModuleOut(module_key, port_key))
+  }
+
+  fn resolve_node_in<D>(&self, path: &str) -> Result<NodeIn<D>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let (owner, port) = split_port_path(path)?;
+    let node_key = self.node_from_path(owner)?;
+    let node = self.get_node(node_key)?;
+    let port_key = node
+      .ports
+      .get_input()
+      .key_from_id(port)
+      .ok_or_else(|| Error::PortNotFound(path.to_string()))?;
+    Ok(NodeIn(node_key, port_key))
+  }
+
+  fn resolve_node_out<D>(&self, path: &str) -> Result<NodeOut<D>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let (owner, port) = split_port_path(path)?;
+    let node_key = self.node_from_path(owner)?;
+    let node = self.get_node(node_key)?;
+    let port_key = node
+      .ports
+      .get_output()
+      .key_from_id(port)
+      .ok_or_else(|| Error::PortNotFound(path.to_string()))?;
+    Ok(NodeOut(node_key, port_key))
+  }
+}
+
+fn split_port_path(path: &str) -> Result<(&str, &str)> {
+  path
+    .rsplit_once(':')
+    .ok_or_else(|| Error::PathNotFound(path.to_string()))
+}
+
+fn dynamic_inputs<D>(ports: &KeyStoreWithId<InputPort<D>>, static_ports: &[D]) -> Vec<D>
+where
+  D: PortDescriptor,
+{
+  ports
+    .ordered_iter()
+    .skip(static_ports.len())
+    .map(|(_, port)| port.descriptor.clone())
+    .collect()
+}
+
+fn dynamic_outputs<D>(ports: &KeyStoreWithId<OutputPort<D>>, static_ports: &[D]) -> Vec<D>
+where
+  D: PortDescriptor,
+{
+  ports
+    .ordered_iter()
+    .skip(static_ports.len())
+    .map(|(_, port)| port.descriptor.clone())
+    .collect()
+}
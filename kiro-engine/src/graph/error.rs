@@ -13,6 +13,12 @@ pub enum Error {
   #[error("Node not found: {0}")]
   NodeNotFound(NodeKey),
 
+  #[error("Path not found: {0}")]
+  PathNotFound(String),
+
+  #[error("Path already exists: {0}")]
+  PathAlreadyExists(String),
+
   #[error("Port not found: {0}")]
   PortNotFound(String),
 
@@ -25,15 +31,30 @@ pub enum Error {
   #[error("Connection between {0} and {1} is out of scope. They require a sibling relationship")]
   ConnectionOutOfScope(String, String),
 
+  #[error("Connection between {0} and {1} has incompatible ports")]
+  IncompatiblePorts(String, String),
+
   #[error("{0} input source for {1} is already defined")]
   InputSourceAlreadyDefined(String, String),
 
   #[error("{0} output source for {1} is already defined")]
   OutputSourceAlreadyDefined(String, String),
 
+  #[error("{0} connection to {1} not found")]
+  ConnectionNotFound(String, String),
+
   #[error("Input port not found for module '{0}': {1}")]
   InputPortNotFound(String, String),
 
   #[error("Output port not found for module '{0}': {1}")]
   OutputPortNotFound(String, String),
+
+  #[error("The graph contains a cycle through: {0}")]
+  CycleDetected(String),
+
+  #[error("Descriptor is not compatible with the ports of node '{0}'")]
+  IncompatibleDescriptor(String),
+
+  #[error("Parameter not found for node '{0}': {1}")]
+  ParamNotFound(String, usize),
 }
@@ -1,4 +1,5 @@
 pub mod connection;
+pub mod document;
 pub mod error;
 pub mod module;
 pub mod node;
@@ -13,13 +14,13 @@ pub use node::{NodeDescriptor, NodeKey};
 use std::collections::HashMap;
 
 use crate::graph::connection::{
-  AudioConnection, EventsConnection, ModuleAudioIn, ModuleAudioOut, ModuleEventsIn,
+  AudioConnection, Connection, EventsConnection, ModuleAudioIn, ModuleAudioOut, ModuleEventsIn,
   ModuleEventsOut, ModuleIn, ModuleOut, NodeAudioIn, NodeAudioOut, NodeEventsIn, NodeEventsOut,
   NodeIn, NodeOut,
 };
 use crate::graph::error::Result;
-use crate::graph::module::Module;
-use crate::graph::node::Node;
+use crate::graph::module::{Module, ParamBinding};
+use crate::graph::node::{Node, ParamModulation};
 use crate::graph::port::{
   port_path, AudioDescriptor, AudioInputPortKey, AudioOutputPortKey, DescriptorPortAccessor,
   DescriptorPorts, DynamicPorts, EventsDescriptor, EventsInputPortKey, EventsOutputPortKey,
@@ -49,9 +50,9 @@ impl Graph {
       "inputs".to_string(),
       NodeDescriptor::new()
         .with_audio_ports(|ports| {
-          ports.static_inputs(vec![AudioDescriptor::new("audio-in", audio_input_channels)])
+          ports.static_outputs(vec![AudioDescriptor::new("audio-in", audio_input_channels)])
         })
-        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-in")])),
+        .with_events_ports(|ports| ports.static_outputs(vec![EventsDescriptor::new("events-in")])),
       root_module,
       root_path.clone(),
     ));
@@ -59,12 +60,12 @@ impl Graph {
       "outputs".to_string(),
       NodeDescriptor::new()
         .with_audio_ports(|ports| {
-          ports.static_outputs(vec![AudioDescriptor::new(
+          ports.static_inputs(vec![AudioDescriptor::new(
             "audio-out",
             audio_output_channels,
           )])
         })
-        .with_events_ports(|ports| ports.static_outputs(vec![EventsDescriptor::new("events-out")])),
+        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-out")])),
       root_module,
       root_path,
     ));
@@ -112,7 +113,10 @@ impl Graph {
   /// Remove a module from the graph.
   /// It will remove all the children modules, nodes and connections recursively.
   pub fn remove_module(&mut self, module_key: ModuleKey) -> Result<()> {
-    // TODO remove recursively
+    for child_key in self.module_children(module_key) {
+      self.remove_module(child_key)?;
+    }
+
     let module_nodes = self
       .nodes
       .iter()
@@ -120,14 +124,45 @@ impl Graph {
       .collect::<Vec<NodeKey>>();
 
     for node_key in module_nodes {
-      self.nodes.remove(node_key);
+      self.remove_node(node_key)?;
     }
 
     self
       .modules
       .remove(module_key)
-      .map(|_| ())
-      .ok_or(Error::ModuleNotFound(module_key))
+      .ok_or(Error::ModuleNotFound(module_key))?;
+
+    for node in self.nodes.values_mut() {
+      node.ports.remove_module_sources(module_key);
+    }
+    for module in self.modules.values_mut() {
+      module.ports.remove_module_sources(module_key);
+    }
+
+    Ok(())
+  }
+
+  /// Find a module by its path, as in `root/synth`. The leading `/` is optional.
+  pub fn find_module(&self, path: &str) -> Option<ModuleKey> {
+    let path = path.trim_start_matches('/');
+    self
+      .modules
+      .iter()
+      .find(|(_, module)| module.full_name().trim_start_matches('/') == path)
+      .map(|(key, _)| key)
+  }
+
+  /// Change the name of a module, and the paths of all its children modules and nodes.
+  pub fn rename_module(&mut self, module_key: ModuleKey, name: &str) -> Result<()> {
+    let module = self.get_module(module_key)?;
+    if module.name == name {
+      return Ok(());
+    }
+    let full_name = format!("{}/{}", module.path, name);
+    self.check_path_available(&full_name)?;
+    self.get_module_mut(module_key)?.name = name.to_string();
+    self.update_children_paths(module_key, full_name);
+    Ok(())
   }
 
   /// Add a new dynamic audio input to the module.
@@ -140,10 +175,10 @@ impl Graph {
     let module = self.get_module_mut(module_key)?;
     Self::enough_dynamic_input_ports::<_, AudioDescriptor>(module)
       .then(|| {
-        let port_key = module.ports.audio_input_ports.add(InputPort {
-          descriptor,
-          source: None,
-        });
+        let port_key = module
+          .ports
+          .audio_input_ports
+          .add(InputPort::new(descriptor));
         ModuleIn(module_key, port_key)
       })
       .ok_or(Error::DynamicPortsNotAvailable)
@@ -178,10 +213,10 @@ impl Graph {
     let module = self.get_module_mut(module_key)?;
     Self::enough_dynamic_input_ports::<_, EventsDescriptor>(module)
       .then(|| {
-        let port_key = module.ports.events_input_ports.add(InputPort {
-          descriptor,
-          source: None,
-        });
+        let port_key = module
+          .ports
+          .events_input_ports
+          .add(InputPort::new(descriptor));
         ModuleIn(module_key, port_key)
       })
       .ok_or(Error::DynamicPortsNotAvailable)
@@ -213,7 +248,7 @@ impl Graph {
       module
         .ports
         .audio_input_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| ModuleIn(module_key, *port_key))
         .collect(),
     )
@@ -226,7 +261,7 @@ impl Graph {
       module
         .ports
         .audio_output_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| ModuleOut(module_key, *port_key))
         .collect(),
     )
@@ -239,7 +274,7 @@ impl Graph {
       module
         .ports
         .events_input_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| ModuleIn(module_key, *port_key))
         .collect(),
     )
@@ -252,7 +287,7 @@ impl Graph {
       module
         .ports
         .events_output_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| ModuleOut(module_key, *port_key))
         .collect(),
     )
@@ -327,15 +362,210 @@ impl Graph {
     }
   }
 
+  /// Find a node by its path, as in `root/synth/voice-0`. The leading `/` is optional.
+  pub fn find_node(&self, path: &str) -> Option<NodeKey> {
+    let path = path.trim_start_matches('/');
+    self
+      .nodes
+      .iter()
+      .find(|(_, node)| node.full_name().trim_start_matches('/') == path)
+      .map(|(key, _)| key)
+  }
+
+  /// Change the name of a node.
+  pub fn rename_node(&mut self, node_key: NodeKey, name: &str) -> Result<()> {
+    let node = self.get_node(node_key)?;
+    if node.name == name {
+      return Ok(());
+    }
+    self.check_path_available(&format!("{}/{}", node.path, name))?;
+    self.get_node_mut(node_key)?.name = name.to_string();
+    Ok(())
+  }
+
   /// Remove a node from the graph.
   /// It will remove all the connections.
   pub fn remove_node(&mut self, key: NodeKey) -> Result<()> {
-    // TODO remove connections
-    self
-      .nodes
-      .remove(key)
-      .map(|_| ())
-      .ok_or(Error::NodeNotFound(key))
+    self.nodes.remove(key).ok_or(Error::NodeNotFound(key))?;
+
+    for node in self.nodes.values_mut() {
+      node.ports.remove_node_sources(key);
+      node
+        .param_modulations
+        .retain(|_, modulation| modulation.source.node_key() != key);
+    }
+    for module in self.modules.values_mut() {
+      module.ports.remove_node_sources(key);
+      for bindings in module.param_bindings.iter_mut() {
+        bindings.retain(|binding| binding.node != key);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Bind a parameter of a module to a parameter of a node inside it, directly or through other modules,
+  /// so the values of the first are forwarded to the second.
+  pub fn bind_module_param(
+    &mut self,
+    module_key: ModuleKey,
+    index: usize,
+    node_key: NodeKey,
+    node_index: usize,
+  ) -> Result<()> {
+    let module = self.get_module(module_key)?;
+    let param = module
+      .descriptor
+      .parameters
+      .get(index)
+      .ok_or_else(|| Error::ParamNotFound(module.full_name(), index))?;
+    let node = self.get_node(node_key)?;
+    let node_param = node
+      .descriptor
+      .parameters
+      .get(node_index)
+      .ok_or_else(|| Error::ParamNotFound(node.full_name(), node_index))?;
+
+    if !self.module_nodes(module_key).contains(&node_key) {
+      return Err(Error::BindingOutOfScope(
+        port_path(module, param),
+        port_path(node, node_param),
+      ));
+    }
+
+    let binding = ParamBinding {
+      node: node_key,
+      index: node_index,
+    };
+    let bindings = &mut self.get_module_mut(module_key)?.param_bindings[index];
+    if !bindings.contains(&binding) {
+      bindings.push(binding);
+    }
+    Ok(())
+  }
+
+  /// Remove all the bindings of a parameter of a module, returning them.
+  pub fn unbind_module_param(
+    &mut self,
+    module_key: ModuleKey,
+    index: usize,
+  ) -> Result<Vec<ParamBinding>> {
+    let module = self.get_module_mut(module_key)?;
+    match module.param_bindings.get_mut(index) {
+      Some(bindings) => Ok(std::mem::take(bindings)),
+      None => Err(Error::ParamNotFound(module.full_name(), index)),
+    }
+  }
+
+  /// Change the value of a parameter of a module, returning it once constrained to the parameter range.
+  pub fn set_module_param(
+    &mut self,
+    module_key: ModuleKey,
+    index: usize,
+    value: f32,
+  ) -> Result<f32> {
+    let module = self.get_module_mut(module_key)?;
+    match module.descriptor.parameters.get(index) {
+      Some(param) => {
+        let value = param.constrain(value);
+        module.param_values[index] = value;
+        Ok(value)
+      }
+      None => Err(Error::ParamNotFound(module.full_name(), index)),
+    }
+  }
+
+  /// Modulate a parameter of a node at audio rate from the first channel of an audio output.
+  /// Both nodes need to be siblings.
+  pub fn modulate_param(
+    &mut self,
+    source: NodeAudioOut,
+    node_key: NodeKey,
+    index: usize,
+    depth: f32,
+  ) -> Result<()> {
+    let src_node = self.get_node(source.node_key())?;
+    let src_port = src_node.get_output_port(source.output_port_key())?;
+    let dst_node = self.get_node(node_key)?;
+    let param = dst_node
+      .descriptor
+      .parameters
+      .get(index)
+      .ok_or_else(|| Error::ParamNotFound(dst_node.full_name(), index))?;
+
+    if src_node.parent != dst_node.parent {
+      Err(Error::ConnectionOutOfScope(
+        port_path(src_node, src_port),
+        port_path(dst_node, param),
+      ))
+    } else if dst_node.param_modulations.contains_key(&index) {
+      Err(Error::InputSourceAlreadyDefined(
+        "Modulation".to_string(),
+        port_path(dst_node, param),
+      ))
+    } else {
+      let dst_node = self.get_node_mut(node_key)?;
+      dst_node
+        .param_modulations
+        .insert(index, ParamModulation { source, depth });
+      Ok(())
+    }
+  }
+
+  /// Replace the descriptor of a node by another one with the same ports,
+  /// keeping the modulations of the parameters with the same id.
+  pub fn replace_node_descriptor(
+    &mut self,
+    node_key: NodeKey,
+    descriptor: NodeDescriptor,
+  ) -> Result<()> {
+    let node = self.get_node_mut(node_key)?;
+    if node.descriptor.ports != descriptor.ports {
+      return Err(Error::IncompatibleDescriptor(node.full_name()));
+    }
+    let old_parameters = std::mem::replace(&mut node.descriptor.parameters, descriptor.parameters);
+    node.param_modulations = std::mem::take(&mut node.param_modulations)
+      .into_iter()
+      .filter_map(|(index, modulation)| {
+        let id = old_parameters[index].id.as_str();
+        node
+          .descriptor
+          .parameters
+          .iter()
+          .position(|param| param.id == id)
+          .map(|index| (index, modulation))
+      })
+      .collect();
+
+    let parameters = node.descriptor.parameters.clone();
+    for module in self.modules.values_mut() {
+      for bindings in module.param_bindings.iter_mut() {
+        *bindings = std::mem::take(bindings)
+          .into_iter()
+          .filter_map(|binding| {
+            if binding.node != node_key {
+              return Some(binding);
+            }
+            let id = old_parameters[binding.index].id.as_str();
+            parameters
+              .iter()
+              .position(|param| param.id == id)
+              .map(|index| ParamBinding {
+                node: node_key,
+                index,
+              })
+          })
+          .collect();
+      }
+    }
+    Ok(())
+  }
+
+  /// Remove the modulation of a parameter, if any.
+  pub fn clear_param_modulation(&mut self, node_key: NodeKey, index: usize) -> Result<()> {
+    let node = self.get_node_mut(node_key)?;
+    node.param_modulations.remove(&index);
+    Ok(())
   }
 
   /// Return all the audio inputs in the same order as they were declared and created
@@ -345,7 +575,7 @@ impl Graph {
       node
         .ports
         .audio_input_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| NodeIn(node_key, *port_key))
         .collect(),
     )
@@ -358,7 +588,7 @@ impl Graph {
       node
         .ports
         .audio_output_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| NodeOut(node_key, *port_key))
         .collect(),
     )
@@ -371,7 +601,7 @@ impl Graph {
       node
         .ports
         .events_input_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| NodeIn(node_key, *port_key))
         .collect(),
     )
@@ -384,7 +614,7 @@ impl Graph {
       node
         .ports
         .events_output_ports
-        .keys()
+        .ordered_keys()
         .map(|port_key| NodeOut(node_key, *port_key))
         .collect(),
     )
@@ -400,10 +630,7 @@ impl Graph {
     let node = self.get_node_mut(node_key)?;
     Self::enough_dynamic_input_ports::<_, AudioDescriptor>(node)
       .then(|| {
-        let port_key = node.ports.audio_input_ports.add(InputPort {
-          descriptor,
-          source: None,
-        });
+        let port_key = node.ports.audio_input_ports.add(InputPort::new(descriptor));
         NodeIn(node_key, port_key)
       })
       .ok_or(Error::DynamicPortsNotAvailable)
@@ -438,10 +665,10 @@ impl Graph {
     let node = self.get_node_mut(node_key)?;
     Self::enough_dynamic_input_ports::<_, EventsDescriptor>(node)
       .then(|| {
-        let port_key = node.ports.events_input_ports.add(InputPort {
-          descriptor,
-          source: None,
-        });
+        let port_key = node
+          .ports
+          .events_input_ports
+          .add(InputPort::new(descriptor));
         NodeIn(node_key, port_key)
       })
       .ok_or(Error::DynamicPortsNotAvailable)
@@ -507,61 +734,65 @@ impl Graph {
   }
 
   pub fn connect_audio(&mut self, connection: AudioConnection) -> Result<()> {
-    match connection {
-      AudioConnection::ModuleOutBindModuleOut(src_module_out, dst_module_out) => {
-        self.connect_module_out_bind_module_out(src_module_out, dst_module_out)
-      }
-      AudioConnection::NodeOutBindModuleOut(src_node_out, dst_module_out) => {
-        self.connect_node_out_bind_module_out(src_node_out, dst_module_out)
-      }
-      AudioConnection::ModuleInBindModuleIn(src_module_in, dst_module_in) => {
-        self.connect_module_in_bind_module_in(src_module_in, dst_module_in)
-      }
-      AudioConnection::ModuleInBindNodeIn(src_module_in, dst_node_in) => {
-        self.connect_module_in_bind_node_in(src_module_in, dst_node_in)
-      }
-      AudioConnection::ModuleOutToNodeIn(src_module_out, dst_node_in) => {
-        self.connect_module_out_to_node_in(src_module_out, dst_node_in)
-      }
-      AudioConnection::ModuleOutToModuleIn(src_module_out, dst_module_in) => {
-        self.connect_module_out_to_module_in(src_module_out, dst_module_in)
-      }
-      AudioConnection::NodeOutToNodeIn(src_node_out, dst_node_in) => {
-        self.connect_node_out_to_node_in(src_node_out, dst_node_in)
-      }
-      AudioConnection::NodeOutToModuleIn(src_node_out, dst_module_in) => {
-        self.connect_node_out_to_module_in(src_node_out, dst_module_in)
-      }
-    }
+    self.connect(connection)
   }
 
   pub fn connect_events(&mut self, connection: EventsConnection) -> Result<()> {
-    match connection {
-      EventsConnection::ModuleOutBindModuleOut(src_module_out, dst_module_out) => {
-        self.connect_module_out_bind_module_out(src_module_out, dst_module_out)
-      }
-      EventsConnection::NodeOutBindModuleOut(src_node_out, dst_module_out) => {
-        self.connect_node_out_bind_module_out(src_node_out, dst_module_out)
-      }
-      EventsConnection::ModuleInBindModuleIn(src_module_in, dst_module_in) => {
-        self.connect_module_in_bind_module_in(src_module_in, dst_module_in)
-      }
-      EventsConnection::ModuleInBindNodeIn(src_module_in, dst_node_in) => {
-        self.connect_module_in_bind_node_in(src_module_in, dst_node_in)
-      }
-      EventsConnection::ModuleOutToNodeIn(src_module_out, dst_node_in) => {
-        self.connect_module_out_to_node_in(src_module_out, dst_node_in)
-      }
-      EventsConnection::ModuleOutToModuleIn(src_module_out, dst_module_in) => {
-        self.connect_module_out_to_module_in(src_module_out, dst_module_in)
-      }
-      EventsConnection::NodeOutToNodeIn(src_node_out, dst_node_in) => {
-        self.connect_node_out_to_node_in(src_node_out, dst_node_in)
-      }
-      EventsConnection::NodeOutToModuleIn(src_node_out, dst_module_in) => {
-        self.connect_node_out_to_module_in(src_node_out, dst_module_in)
-      }
-    }
+    self.connect(connection)
+  }
+
+  /// Connect an audio source to an input even if it already has one, so they are summed.
+  ///
+  /// Bindings can not be summed, so they behave as [`Graph::connect_audio`].
+  pub fn add_audio(&mut self, connection: AudioConnection) -> Result<()> {
+    self.add(connection)
+  }
+
+  pub fn disconnect_audio(&mut self, connection: AudioConnection) -> Result<()> {
+    self.disconnect(connection)
+  }
+
+  /// Remove the sources connected or bound to a module input, returning their connections.
+  pub fn clear_module_input_source<D>(
+    &mut self,
+    module_in: ModuleIn<D>,
+  ) -> Result<Vec<Connection<D>>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let module = self.get_module_mut(module_in.module_key())?;
+    let port = module.get_input_port_mut(module_in.input_port_key())?;
+    let connections = port
+      .sources()
+      .map(|source| match *source {
+        InputSource::ModuleBinding(src) => Connection::ModuleInBindModuleIn(src, module_in),
+        InputSource::ModuleConnection(src) => Connection::ModuleOutToModuleIn(src, module_in),
+        InputSource::NodeConnection(src) => Connection::NodeOutToModuleIn(src, module_in),
+      })
+      .collect();
+    port.clear_sources();
+    Ok(connections)
+  }
+
+  /// Remove the sources connected or bound to a node input, returning their connections.
+  pub fn clear_node_input_source<D>(&mut self, node_in: NodeIn<D>) -> Result<Vec<Connection<D>>>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let node = self.get_node_mut(node_in.node_key())?;
+    let port = node.get_input_port_mut(node_in.input_port_key())?;
+    let connections = port
+      .sources()
+      .map(|source| match *source {
+        InputSource::ModuleBinding(src) => Connection::ModuleInBindNodeIn(src, node_in),
+        InputSource::ModuleConnection(src) => Connection::ModuleOutToNodeIn(src, node_in),
+        InputSource::NodeConnection(src) => Connection::NodeOutToNodeIn(src, node_in),
+      })
+      .collect();
+    port.clear_sources();
+    Ok(connections)
   }
 
   pub fn get_topology(&self) -> Result<Topology> {
@@ -695,22 +926,26 @@ impl Graph {
     maybe_output
   }
 
-  fn input_port_node<D>(&self, port: &InputPort<D>) -> Result<Option<NodeKey>>
+  /// Resolve the node outputs connected to an input port, following the bindings through modules.
+  ///
+  /// There can be many when sources are summed, in the order they were added and without duplicates.
+  pub(crate) fn input_port_sources<D>(&self, port: &InputPort<D>) -> Result<Vec<NodeOut<D>>>
   where
     D: PortDescriptor,
     Ports: PortAccessor<D>,
   {
-    port
-      .source
-      .as_ref()
-      .map_or(Ok(None), |source| match source {
+    let mut node_outs = Vec::new();
+    for source in port.sources() {
+      let source_node_outs = match source {
         InputSource::ModuleBinding(module_in) => {
           let module = self.get_module(module_in.module_key())?;
           module
             .ports
             .get_input()
             .get(module_in.input_port_key())
-            .map_or(Ok(None), |input_port| self.input_port_node(input_port))
+            .map_or(Ok(Vec::new()), |input_port| {
+              self.input_port_sources(input_port)
+            })?
         }
         InputSource::ModuleConnection(module_out) => {
           let module = self.get_module(module_out.module_key())?;
@@ -718,13 +953,23 @@ impl Graph {
             .ports
             .get_output()
             .get(module_out.output_port_key())
-            .map_or(Ok(None), |output_port| self.output_port_node(output_port))
+            .map_or(Ok(None), |output_port| self.output_port_source(output_port))?
+            .into_iter()
+            .collect()
         }
-        InputSource::NodeConnection(node_out) => Ok(Some(node_out.node_key())),
-      })
+        InputSource::NodeConnection(node_out) => vec![*node_out],
+      };
+      for node_out in source_node_outs {
+        if !node_outs.contains(&node_out) {
+          node_outs.push(node_out);
+        }
+      }
+    }
+    Ok(node_outs)
   }
 
-  fn output_port_node<D>(&self, port: &OutputPort<D>) -> Result<Option<NodeKey>>
+  /// Resolve the node output bound to an output port, following the bindings through modules.
+  pub(crate) fn output_port_source<D>(&self, port: &OutputPort<D>) -> Result<Option<NodeOut<D>>>
   where
     D: PortDescriptor,
     Ports: PortAccessor<D>,
@@ -739,12 +984,135 @@ impl Graph {
             .ports
             .get_output()
             .get(module_out.output_port_key())
-            .map_or(Ok(None), |output_port| self.output_port_node(output_port))
+            .map_or(Ok(None), |output_port| self.output_port_source(output_port))
         }
-        OutputSource::NodeBinding(node_out) => Ok(Some(node_out.node_key())),
+        OutputSource::NodeBinding(node_out) => Ok(Some(*node_out)),
       })
   }
 
+  pub(crate) fn connect<D>(&mut self, connection: Connection<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    match connection {
+      Connection::ModuleOutBindModuleOut(src_module_out, dst_module_out) => {
+        self.connect_module_out_bind_module_out(src_module_out, dst_module_out)
+      }
+      Connection::NodeOutBindModuleOut(src_node_out, dst_module_out) => {
+        self.connect_node_out_bind_module_out(src_node_out, dst_module_out)
+      }
+      Connection::ModuleInBindModuleIn(src_module_in, dst_module_in) => {
+        self.connect_module_in_bind_module_in(src_module_in, dst_module_in)
+      }
+      Connection::ModuleInBindNodeIn(src_module_in, dst_node_in) => {
+        self.connect_module_in_bind_node_in(src_module_in, dst_node_in)
+      }
+      Connection::ModuleOutToNodeIn(src_module_out, dst_node_in) => {
+        self.connect_module_out_to_node_in(src_module_out, dst_node_in)
+      }
+      Connection::ModuleOutToModuleIn(src_module_out, dst_module_in) => {
+        self.connect_module_out_to_module_in(src_module_out, dst_module_in)
+      }
+      Connection::NodeOutToNodeIn(src_node_out, dst_node_in) => {
+        self.connect_node_out_to_node_in(src_node_out, dst_node_in)
+      }
+      Connection::NodeOutToModuleIn(src_node_out, dst_module_in) => {
+        self.connect_node_out_to_module_in(src_node_out, dst_module_in)
+      }
+    }
+  }
+
+  pub(crate) fn add<D>(&mut self, connection: Connection<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    match connection {
+      Connection::ModuleOutToNodeIn(src_module_out, dst_node_in) => self
+        .connect_module_out_to_node_in(src_module_out, dst_node_in)
+        .or_else(|err| {
+          self.add_node_in_source(
+            dst_node_in,
+            InputSource::ModuleConnection(src_module_out),
+            err,
+          )
+        }),
+      Connection::ModuleOutToModuleIn(src_module_out, dst_module_in) => self
+        .connect_module_out_to_module_in(src_module_out, dst_module_in)
+        .or_else(|err| {
+          self.add_module_in_source(
+            dst_module_in,
+            InputSource::ModuleConnection(src_module_out),
+            err,
+          )
+        }),
+      Connection::NodeOutToNodeIn(src_node_out, dst_node_in) => self
+        .connect_node_out_to_node_in(src_node_out, dst_node_in)
+        .or_else(|err| {
+          self.add_node_in_source(dst_node_in, InputSource::NodeConnection(src_node_out), err)
+        }),
+      Connection::NodeOutToModuleIn(src_node_out, dst_module_in) => self
+        .connect_node_out_to_module_in(src_node_out, dst_module_in)
+        .or_else(|err| {
+          self.add_module_in_source(
+            dst_module_in,
+            InputSource::NodeConnection(src_node_out),
+            err,
+          )
+        }),
+      binding => self.connect(binding),
+    }
+  }
+
+  /// Add a summed source to a module input, when connecting it failed because it already had one.
+  fn add_module_in_source<D>(
+    &mut self,
+    dst_module_in: ModuleIn<D>,
+    source: InputSource<D>,
+    connect_error: Error,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let port_key = dst_module_in.input_port_key();
+    let dst_module = self.get_module_mut(dst_module_in.module_key())?;
+    let dst_port = dst_module.get_input_port(port_key)?;
+    if matches!(connect_error, Error::InputSourceAlreadyDefined(_, _))
+      && !dst_port.has_source(&source)
+    {
+      dst_module.get_input_port_mut(port_key)?.summed.push(source);
+      Ok(())
+    } else {
+      Err(connect_error)
+    }
+  }
+
+  /// Add a summed source to a node input, when connecting it failed because it already had one.
+  fn add_node_in_source<D>(
+    &mut self,
+    dst_node_in: NodeIn<D>,
+    source: InputSource<D>,
+    connect_error: Error,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+  {
+    let port_key = dst_node_in.input_port_key();
+    let dst_node = self.get_node_mut(dst_node_in.node_key())?;
+    let dst_port = dst_node.get_input_port(port_key)?;
+    if matches!(connect_error, Error::InputSourceAlreadyDefined(_, _))
+      && !dst_port.has_source(&source)
+    {
+      dst_node.get_input_port_mut(port_key)?.summed.push(source);
+      Ok(())
+    } else {
+      Err(connect_error)
+    }
+  }
+
   fn connect_module_out_bind_module_out<D>(
     &mut self,
     src_module_out: ModuleOut<D>,
@@ -768,6 +1136,10 @@ impl Graph {
         let dst_path = port_path(dst_module, dst_port);
         Err(Error::BindingOutOfScope(src_path, dst_path))?
       }
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_module, src_port);
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::OutputSourceAlreadyDefined(
@@ -801,6 +1173,10 @@ impl Graph {
       let src_path = port_path(src_node, src_port);
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::BindingOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_node, src_port);
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::OutputSourceAlreadyDefined(
@@ -838,6 +1214,10 @@ impl Graph {
         let dst_path = port_path(dst_module, dst_port);
         Err(Error::BindingOutOfScope(src_path, dst_path))
       }
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_module, src_port);
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -871,6 +1251,10 @@ impl Graph {
       let src_path = port_path(src_module, src_port);
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::BindingOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_module, src_port);
+      let dst_path = port_path(dst_node, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -904,6 +1288,10 @@ impl Graph {
       let src_path = port_path(src_module, src_port);
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::ConnectionOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_module, src_port);
+      let dst_path = port_path(dst_node, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -937,6 +1325,10 @@ impl Graph {
       let src_path = port_path(src_module, src_port);
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::ConnectionOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_module, src_port);
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -970,6 +1362,10 @@ impl Graph {
       let src_path = port_path(src_node, src_port);
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::ConnectionOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_node, src_port);
+      let dst_path = port_path(dst_node, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_node, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -1003,6 +1399,10 @@ impl Graph {
       let src_path = port_path(src_node, src_port);
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::ConnectionOutOfScope(src_path, dst_path))
+    } else if !dst_port.descriptor.accepts(&src_port.descriptor) {
+      let src_path = port_path(src_node, src_port);
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::IncompatiblePorts(src_path, dst_path))
     } else if dst_port.source.is_some() {
       let dst_path = port_path(dst_module, dst_port);
       Err(Error::InputSourceAlreadyDefined(
@@ -1017,6 +1417,188 @@ impl Graph {
     }
   }
 
+  pub(crate) fn disconnect<D>(&mut self, connection: Connection<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+    InputSource<D>: PartialEq,
+    OutputSource<D>: PartialEq,
+  {
+    match connection {
+      Connection::ModuleOutBindModuleOut(src_module_out, dst_module_out) => self
+        .disconnect_module_out(dst_module_out, OutputSource::ModuleBinding(src_module_out))
+        .or_else(|err| {
+          // the binding could have been connected in the opposite direction
+          self
+            .disconnect_module_out(src_module_out, OutputSource::ModuleBinding(dst_module_out))
+            .map_err(|_| err)
+        }),
+      Connection::NodeOutBindModuleOut(src_node_out, dst_module_out) => {
+        self.disconnect_module_out(dst_module_out, OutputSource::NodeBinding(src_node_out))
+      }
+      Connection::ModuleInBindModuleIn(src_module_in, dst_module_in) => self
+        .disconnect_module_in(dst_module_in, InputSource::ModuleBinding(src_module_in))
+        .or_else(|err| {
+          // the binding could have been connected in the opposite direction
+          self
+            .disconnect_module_in(src_module_in, InputSource::ModuleBinding(dst_module_in))
+            .map_err(|_| err)
+        }),
+      Connection::ModuleInBindNodeIn(src_module_in, dst_node_in) => {
+        self.disconnect_node_in(dst_node_in, InputSource::ModuleBinding(src_module_in))
+      }
+      Connection::ModuleOutToNodeIn(src_module_out, dst_node_in) => {
+        self.disconnect_node_in(dst_node_in, InputSource::ModuleConnection(src_module_out))
+      }
+      Connection::ModuleOutToModuleIn(src_module_out, dst_module_in) => {
+        self.disconnect_module_in(dst_module_in, InputSource::ModuleConnection(src_module_out))
+      }
+      Connection::NodeOutToNodeIn(src_node_out, dst_node_in) => {
+        self.disconnect_node_in(dst_node_in, InputSource::NodeConnection(src_node_out))
+      }
+      Connection::NodeOutToModuleIn(src_node_out, dst_module_in) => {
+        self.disconnect_module_in(dst_module_in, InputSource::NodeConnection(src_node_out))
+      }
+    }
+  }
+
+  fn disconnect_module_in<D>(
+    &mut self,
+    dst_module_in: ModuleIn<D>,
+    source: InputSource<D>,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+    InputSource<D>: PartialEq,
+  {
+    let port_key = dst_module_in.input_port_key();
+    let dst_module = self.get_module_mut(dst_module_in.module_key())?;
+    let dst_port = dst_module.get_input_port(port_key)?;
+    if dst_port.has_source(&source) {
+      dst_module
+        .get_input_port_mut(port_key)?
+        .remove_source(&source);
+      Ok(())
+    } else {
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::ConnectionNotFound(
+        D::port_type().name().to_string(),
+        dst_path,
+      ))
+    }
+  }
+
+  fn disconnect_node_in<D>(&mut self, dst_node_in: NodeIn<D>, source: InputSource<D>) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+    InputSource<D>: PartialEq,
+  {
+    let port_key = dst_node_in.input_port_key();
+    let dst_node = self.get_node_mut(dst_node_in.node_key())?;
+    let dst_port = dst_node.get_input_port(port_key)?;
+    if dst_port.has_source(&source) {
+      dst_node
+        .get_input_port_mut(port_key)?
+        .remove_source(&source);
+      Ok(())
+    } else {
+      let dst_path = port_path(dst_node, dst_port);
+      Err(Error::ConnectionNotFound(
+        D::port_type().name().to_string(),
+        dst_path,
+      ))
+    }
+  }
+
+  fn disconnect_module_out<D>(
+    &mut self,
+    dst_module_out: ModuleOut<D>,
+    source: OutputSource<D>,
+  ) -> Result<()>
+  where
+    D: PortDescriptor,
+    Ports: PortAccessor<D>,
+    OutputSource<D>: PartialEq,
+  {
+    let port_key = dst_module_out.output_port_key();
+    let dst_module = self.get_module_mut(dst_module_out.module_key())?;
+    let dst_port = dst_module.get_output_port(port_key)?;
+    if dst_port.source.as_ref() == Some(&source) {
+      dst_module.get_output_port_mut(port_key)?.source = None;
+      Ok(())
+    } else {
+      let dst_path = port_path(dst_module, dst_port);
+      Err(Error::ConnectionNotFound(
+        D::port_type().name().to_string(),
+        dst_path,
+      ))
+    }
+  }
+
+  fn check_path_available(&self, path: &str) -> Result<()> {
+    if self.find_module(path).is_some() || self.find_node(path).is_some() {
+      Err(Error::PathAlreadyExists(path.to_string()))
+    } else {
+      Ok(())
+    }
+  }
+
+  fn update_children_paths(&mut self, module_key: ModuleKey, path: String) {
+    for node in self.nodes.values_mut() {
+      if node.parent == module_key {
+        node.path = path.clone();
+      }
+    }
+
+    let children = self
+      .modules
+      .iter()
+      .filter(|(_, module)| module.parent == Some(module_key))
+      .map(|(key, _)| key)
+      .collect::<Vec<ModuleKey>>();
+    for child_key in children {
+      if let Some(child) = self.modules.get_mut(child_key) {
+        child.path = path.clone();
+        let child_path = child.full_name();
+        self.update_children_paths(child_key, child_path);
+      }
+    }
+  }
+
+  /// Modules directly inside a module.
+  pub(crate) fn module_children(&self, module_key: ModuleKey) -> Vec<ModuleKey> {
+    self
+      .modules
+      .iter()
+      .filter(|(_, module)| module.parent == Some(module_key))
+      .map(|(key, _)| key)
+      .collect()
+  }
+
+  /// Nodes inside a module, including the ones inside its children modules recursively.
+  pub(crate) fn module_nodes(&self, module_key: ModuleKey) -> Vec<NodeKey> {
+    let mut nodes = self
+      .nodes
+      .iter()
+      .filter(|(_, node)| node.parent == module_key)
+      .map(|(key, _)| key)
+      .collect::<Vec<NodeKey>>();
+    for (child_key, _) in self
+      .modules
+      .iter()
+      .filter(|(_, module)| module.parent == Some(module_key))
+    {
+      nodes.extend(self.module_nodes(child_key));
+    }
+    nodes
+  }
+
+  pub(crate) fn get_nodes(&self) -> impl Iterator<Item = (NodeKey, &Node)> {
+    self.nodes.iter()
+  }
+
   pub(crate) fn get_module(&self, key: ModuleKey) -> Result<&Module> {
     self.modules.get(key).ok_or(Error::ModuleNotFound(key))
   }
@@ -1086,8 +1668,321 @@ impl Graph {
 mod tests {
   use crate::graph::module::ModuleDescriptor;
   use crate::graph::node::NodeDescriptor;
-  use crate::graph::port::AudioDescriptor;
-  use crate::graph::Graph;
+  use crate::graph::port::{AudioDescriptor, ChannelLayout, InputSource, NodeLike};
+  use crate::graph::{Error, Graph};
+  use crate::ParamDescriptor;
+
+  #[test]
+  fn disconnect() {
+    let mut g = Graph::new(2, 2);
+
+    let test_module_descriptor = ModuleDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+
+    let m1 = g
+      .create_module(g.get_root_module(), "m1", test_module_descriptor)
+      .unwrap();
+
+    let m1_audio_in = g.module_audio_inputs(m1).unwrap();
+    let m1_audio_out = g.module_audio_outputs(m1).unwrap();
+
+    let test_node_descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+
+    let n1 = g
+      .create_node(m1, "n1", test_node_descriptor.clone())
+      .unwrap();
+    let n1_audio_in = g.node_audio_inputs(n1).unwrap();
+    let n1_audio_out = g.node_audio_outputs(n1).unwrap();
+
+    let n2 = g.create_node(m1, "n2", test_node_descriptor).unwrap();
+    let n2_audio_in = g.node_audio_inputs(n2).unwrap();
+    let n2_audio_out = g.node_audio_outputs(n2).unwrap();
+
+    g.connect_audio(m1_audio_in[0].bind(n1_audio_in[0]))
+      .unwrap();
+    g.connect_audio(n1_audio_out[0].to(n2_audio_in[0])).unwrap();
+    g.connect_audio(n2_audio_out[0].bind(m1_audio_out[0]))
+      .unwrap();
+
+    g.disconnect_audio(n1_audio_out[0].to(n2_audio_in[0]))
+      .unwrap();
+    assert!(matches!(
+      g.disconnect_audio(n1_audio_out[0].to(n2_audio_in[0])),
+      Err(Error::ConnectionNotFound(_, _))
+    ));
+
+    // the input is free to be re-patched
+    g.connect_audio(m1_audio_in[0].bind(n2_audio_in[0]))
+      .unwrap();
+
+    g.disconnect_audio(m1_audio_in[0].bind(n1_audio_in[0]))
+      .unwrap();
+    g.disconnect_audio(n2_audio_out[0].bind(m1_audio_out[0]))
+      .unwrap();
+    let n1_input_port = g
+      .get_node(n1)
+      .unwrap()
+      .get_input_port(n1_audio_in[0].input_port_key())
+      .unwrap();
+    assert!(n1_input_port.source.is_none());
+  }
+
+  #[test]
+  fn clear_input_source() {
+    let mut g = Graph::new(2, 2);
+
+    let test_node_descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+
+    let root = g.get_root_module();
+    let n1 = g
+      .create_node(root, "n1", test_node_descriptor.clone())
+      .unwrap();
+    let n2 = g.create_node(root, "n2", test_node_descriptor).unwrap();
+    let n1_audio_out = g.node_audio_outputs(n1).unwrap();
+    let n2_audio_in = g.node_audio_inputs(n2).unwrap();
+
+    g.connect_audio(n1_audio_out[0].to(n2_audio_in[0])).unwrap();
+    g.clear_node_input_source(n2_audio_in[0]).unwrap();
+    // clearing an input without source is not an error
+    g.clear_node_input_source(n2_audio_in[0]).unwrap();
+
+    assert!(matches!(
+      g.disconnect_audio(n1_audio_out[0].to(n2_audio_in[0])),
+      Err(Error::ConnectionNotFound(_, _))
+    ));
+    g.connect_audio(n1_audio_out[0].to(n2_audio_in[0])).unwrap();
+  }
+
+  #[test]
+  fn remove_node_in_the_middle_of_a_chain() {
+    let mut g = Graph::new(2, 2);
+
+    let test_module_descriptor = ModuleDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+
+    let m1 = g
+      .create_module(g.get_root_module(), "m1", test_module_descriptor)
+      .unwrap();
+    let m1_audio_out = g.module_audio_outputs(m1).unwrap();
+
+    let test_node_descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+
+    let n1 = g
+      .create_node(m1, "n1", test_node_descriptor.clone())
+      .unwrap();
+    let n2 = g
+      .create_node(m1, "n2", test_node_descriptor.clone())
+      .unwrap();
+    let n3 = g.create_node(m1, "n3", test_node_descriptor).unwrap();
+
+    let n1_audio_out = g.node_audio_outputs(n1).unwrap();
+    let n2_audio_in = g.node_audio_inputs(n2).unwrap();
+    let n2_audio_out = g.node_audio_outputs(n2).unwrap();
+    let n3_audio_in = g.node_audio_inputs(n3).unwrap();
+    let n3_audio_out = g.node_audio_outputs(n3).unwrap();
+
+    g.connect_audio(n1_audio_out[0].to(n2_audio_in[0])).unwrap();
+    g.connect_audio(n2_audio_out[0].to(n3_audio_in[0])).unwrap();
+    g.connect_audio(n3_audio_out[0].bind(m1_audio_out[0]))
+      .unwrap();
+
+    g.remove_node(n2).unwrap();
+
+    let n3_input_port = g
+      .get_node(n3)
+      .unwrap()
+      .get_input_port(n3_audio_in[0].input_port_key())
+      .unwrap();
+    assert!(n3_input_port.source.is_none());
+    assert!(matches!(g.remove_node(n2), Err(Error::NodeNotFound(_))));
+
+    let topology = g.get_topology().unwrap();
+    assert!(!topology.nodes.contains(&n2));
+
+    // the freed input can be connected again
+    g.connect_audio(n1_audio_out[0].to(n3_audio_in[0])).unwrap();
+
+    g.remove_node(n3).unwrap();
+    let m1_output_port = g
+      .get_module(m1)
+      .unwrap()
+      .get_output_port(m1_audio_out[0].output_port_key())
+      .unwrap();
+    assert!(m1_output_port.source.is_none());
+  }
+
+  #[test]
+  fn add_summed_sources() {
+    let mut g = Graph::new(2, 2);
+
+    let m1 = g
+      .create_module(
+        g.get_root_module(),
+        "m1",
+        ModuleDescriptor::new()
+          .with_audio_ports(|ports| ports.static_inputs(vec![AudioDescriptor::new("audio-in", 1)])),
+      )
+      .unwrap();
+    let m1_audio_in = g.module_audio_inputs(m1).unwrap();
+
+    let test_node_descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+    let n1 = g
+      .create_node(m1, "n1", test_node_descriptor.clone())
+      .unwrap();
+    let n2 = g
+      .create_node(m1, "n2", test_node_descriptor.clone())
+      .unwrap();
+    let n3 = g.create_node(m1, "n3", test_node_descriptor).unwrap();
+    let n1_audio_out = g.node_audio_outputs(n1).unwrap();
+    let n2_audio_out = g.node_audio_outputs(n2).unwrap();
+    let n3_audio_in = g.node_audio_inputs(n3).unwrap();
+
+    g.add_audio(n1_audio_out[0].to(n3_audio_in[0])).unwrap();
+    g.add_audio(n2_audio_out[0].to(n3_audio_in[0])).unwrap();
+    assert!(matches!(
+      g.add_audio(n2_audio_out[0].to(n3_audio_in[0])),
+      Err(Error::InputSourceAlreadyDefined(_, _))
+    ));
+    // bindings can not be summed
+    assert!(matches!(
+      g.add_audio(m1_audio_in[0].bind(n3_audio_in[0])),
+      Err(Error::InputSourceAlreadyDefined(_, _))
+    ));
+
+    let n3_input_port = |g: &Graph| {
+      g.get_node(n3)
+        .unwrap()
+        .get_input_port(n3_audio_in[0].input_port_key())
+        .unwrap()
+        .clone()
+    };
+    let sources = g.input_port_sources(&n3_input_port(&g)).unwrap();
+    assert_eq!(sources, vec![n1_audio_out[0], n2_audio_out[0]]);
+
+    // the next source takes the place of the main one
+    g.disconnect_audio(n1_audio_out[0].to(n3_audio_in[0]))
+      .unwrap();
+    let port = n3_input_port(&g);
+    assert_eq!(
+      port.source,
+      Some(InputSource::NodeConnection(n2_audio_out[0]))
+    );
+    assert!(port.summed.is_empty());
+
+    g.add_audio(n1_audio_out[0].to(n3_audio_in[0])).unwrap();
+    g.remove_node(n2).unwrap();
+    let sources = g.input_port_sources(&n3_input_port(&g)).unwrap();
+    assert_eq!(sources, vec![n1_audio_out[0]]);
+  }
+
+  #[test]
+  fn connect_compatible_channel_layouts() {
+    let mut g = Graph::new(2, 2);
+    let mut create_node = |name: &str, input: ChannelLayout, output: ChannelLayout| {
+      let descriptor = NodeDescriptor::new().with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new("audio-in", 1).with_layout(input)])
+          .static_outputs(vec![
+            AudioDescriptor::new("audio-out", 1).with_layout(output)
+          ])
+      });
+      let node = g
+        .create_node(g.get_root_module(), name, descriptor)
+        .unwrap();
+      (
+        g.node_audio_inputs(node).unwrap()[0],
+        g.node_audio_outputs(node).unwrap()[0],
+      )
+    };
+    let surround = ChannelLayout::Named(vec!["L".into(), "R".into(), "C".into()]);
+    let (mono_in, mono_out) = create_node("mono", ChannelLayout::Mono, ChannelLayout::Mono);
+    let (stereo_in, stereo_out) =
+      create_node("stereo", ChannelLayout::Stereo, ChannelLayout::Stereo);
+    let (surround_in, _) = create_node("surround", surround.clone(), surround);
+    let (discrete_in, _) = create_node("discrete", ChannelLayout::Discrete(2), ChannelLayout::Mono);
+
+    assert!(matches!(
+      g.connect_audio(stereo_out.to(mono_in)),
+      Err(Error::IncompatiblePorts(_, _))
+    ));
+    assert!(matches!(
+      g.connect_audio(stereo_out.to(surround_in)),
+      Err(Error::IncompatiblePorts(_, _))
+    ));
+    assert!(matches!(
+      g.add_audio(mono_out.to(surround_in)),
+      Err(Error::IncompatiblePorts(_, _))
+    ));
+    g.connect_audio(mono_out.to(stereo_in)).unwrap();
+    g.connect_audio(stereo_out.to(discrete_in)).unwrap();
+  }
+
+  #[test]
+  fn modulate_param() {
+    let mut g = Graph::new(2, 2);
+
+    let m1 = g
+      .create_module(g.get_root_module(), "m1", ModuleDescriptor::new())
+      .unwrap();
+    let m2 = g
+      .create_module(g.get_root_module(), "m2", ModuleDescriptor::new())
+      .unwrap();
+
+    let lfo_descriptor = NodeDescriptor::new()
+      .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 1)]));
+    let osc_descriptor = NodeDescriptor::new().with_parameters(vec![ParamDescriptor::new("freq")]);
+
+    let lfo = g.create_node(m1, "lfo", lfo_descriptor.clone()).unwrap();
+    let osc = g.create_node(m1, "osc", osc_descriptor).unwrap();
+    let other_lfo = g.create_node(m2, "lfo", lfo_descriptor).unwrap();
+
+    let lfo_out = g.node_audio_outputs(lfo).unwrap();
+    let other_lfo_out = g.node_audio_outputs(other_lfo).unwrap();
+
+    assert!(matches!(
+      g.modulate_param(lfo_out[0], osc, 1, 0.5),
+      Err(Error::ParamNotFound(_, 1))
+    ));
+    assert!(matches!(
+      g.modulate_param(other_lfo_out[0], osc, 0, 0.5),
+      Err(Error::ConnectionOutOfScope(_, _))
+    ));
+
+    g.modulate_param(lfo_out[0], osc, 0, 0.5).unwrap();
+    assert!(matches!(
+      g.modulate_param(lfo_out[0], osc, 0, 0.5),
+      Err(Error::InputSourceAlreadyDefined(_, _))
+    ));
+
+    g.clear_param_modulation(osc, 0).unwrap();
+    g.modulate_param(lfo_out[0], osc, 0, 0.25).unwrap();
+
+    // removing the source drops the modulation
+    g.remove_node(lfo).unwrap();
+    assert!(g.get_node(osc).unwrap().param_modulations.is_empty());
+  }
 
   #[test]
   fn bind() {
@@ -1143,4 +2038,72 @@ mod tests {
     g.connect_audio(m2_audio_out[0].bind(m1_audio_out[0]))
       .unwrap();
   }
+
+  #[test]
+  fn remove_module_recursively() {
+    let mut g = Graph::new(2, 2);
+    let synth = g
+      .create_module(g.get_root_module(), "synth", ModuleDescriptor::new())
+      .unwrap();
+    let voices = g
+      .create_module(synth, "voices", ModuleDescriptor::new())
+      .unwrap();
+    let voice = g
+      .create_node(voices, "voice-0", NodeDescriptor::new())
+      .unwrap();
+    let filter = g
+      .create_node(synth, "filter", NodeDescriptor::new())
+      .unwrap();
+
+    let mut nodes = g.module_nodes(synth);
+    nodes.sort_unstable();
+    let mut expected = vec![voice, filter];
+    expected.sort_unstable();
+    assert_eq!(nodes, expected);
+
+    g.remove_module(synth).unwrap();
+    assert!(matches!(
+      g.get_module(voices),
+      Err(Error::ModuleNotFound(_))
+    ));
+    assert!(matches!(g.get_node(voice), Err(Error::NodeNotFound(_))));
+    assert!(matches!(g.get_node(filter), Err(Error::NodeNotFound(_))));
+  }
+
+  #[test]
+  fn find_and_rename() {
+    let mut g = Graph::new(2, 2);
+    let synth = g
+      .create_module(g.get_root_module(), "synth", ModuleDescriptor::new())
+      .unwrap();
+    let voices = g
+      .create_module(synth, "voices", ModuleDescriptor::new())
+      .unwrap();
+    let voice = g
+      .create_node(voices, "voice-0", NodeDescriptor::new())
+      .unwrap();
+    let filter = g
+      .create_node(synth, "filter", NodeDescriptor::new())
+      .unwrap();
+
+    assert_eq!(g.find_module("root/synth"), Some(synth));
+    assert_eq!(g.find_module("/root/synth/voices"), Some(voices));
+    assert_eq!(g.find_node("root/synth/voices/voice-0"), Some(voice));
+    assert_eq!(g.find_node("root/synth/voice-0"), None);
+
+    g.rename_module(synth, "poly").unwrap();
+    assert_eq!(g.find_module("root/synth"), None);
+    assert_eq!(g.find_module("root/poly/voices"), Some(voices));
+    assert_eq!(g.find_node("root/poly/voices/voice-0"), Some(voice));
+    assert_eq!(g.get_node(filter).unwrap().full_name(), "/root/poly/filter");
+
+    g.rename_node(voice, "voice-1").unwrap();
+    assert_eq!(g.find_node("root/poly/voices/voice-1"), Some(voice));
+
+    assert!(matches!(
+      g.rename_node(filter, "voices"),
+      Err(Error::PathAlreadyExists(path)) if path == "/root/poly/voices"
+    ));
+    g.rename_node(filter, "filter").unwrap();
+  }
 }
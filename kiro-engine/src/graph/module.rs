@@ -1,4 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 use crate::graph::node::NodeKey;
+use crate::graph::param::ParamDescriptor;
 use crate::graph::port::{
   AudioDescriptor, DescriptorPorts, EventsDescriptor, GenericDescriptorPorts, NodeLike, Ports,
 };
@@ -15,6 +18,10 @@ pub struct Module {
   pub path: String,
   pub nodes: HashSet<NodeKey>,
   pub ports: Ports,
+  /// Values of the parameters, in the same order as declared in the descriptor
+  pub param_values: Vec<f32>,
+  /// Parameters of the inner nodes bound to each parameter of the module
+  pub param_bindings: Vec<Vec<ParamBinding>>,
 }
 
 impl Module {
@@ -31,6 +38,13 @@ impl Module {
       descriptor.ports.events.static_outputs.as_slice(),
     );
 
+    let param_values = descriptor
+      .parameters
+      .iter()
+      .map(|param| param.initial)
+      .collect();
+    let param_bindings = vec![Vec::new(); descriptor.parameters.len()];
+
     Self {
       name: name.into(),
       descriptor,
@@ -38,6 +52,8 @@ impl Module {
       path,
       nodes: HashSet::new(),
       ports,
+      param_values,
+      param_bindings,
     }
   }
 }
@@ -60,15 +76,25 @@ impl NodeLike for Module {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Parameter of a node forwarded from a parameter of one of the modules containing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamBinding {
+  pub node: NodeKey,
+  pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModuleDescriptor {
   pub ports: DescriptorPorts,
+  #[serde(default)]
+  pub parameters: Vec<ParamDescriptor>,
 }
 
 impl ModuleDescriptor {
   pub fn new() -> Self {
     Self {
       ports: DescriptorPorts::new(),
+      parameters: Vec::new(),
     }
   }
 
@@ -87,4 +113,10 @@ impl ModuleDescriptor {
     self.ports.events = f(self.ports.events);
     self
   }
+
+  /// Parameters exposed by the module, which forward their values to the parameters of the inner nodes bound to them.
+  pub fn with_parameters(mut self, parameters: Vec<ParamDescriptor>) -> Self {
+    self.parameters = parameters;
+    self
+  }
 }
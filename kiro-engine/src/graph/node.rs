@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::connection::NodeAudioOut;
 use crate::graph::module::ModuleKey;
-use crate::graph::param::ParamDescriptor;
+use crate::graph::param::{ParamDescriptor, ParamUnit};
 use crate::graph::port::{
   AudioDescriptor, DescriptorPorts, EventsDescriptor, GenericDescriptorPorts, NodeLike, Ports,
 };
@@ -7,6 +12,13 @@ use crate::key_gen::Key;
 
 pub type NodeKey = Key<Node>;
 
+/// Audio output of a node modulating a parameter of another node at audio rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamModulation {
+  pub source: NodeAudioOut,
+  pub depth: f32,
+}
+
 #[derive(Debug)]
 pub struct Node {
   pub name: String,
@@ -14,6 +26,8 @@ pub struct Node {
   pub parent: ModuleKey,
   pub path: String,
   pub ports: Ports,
+  /// Modulations by parameter index
+  pub param_modulations: HashMap<usize, ParamModulation>,
 }
 
 impl Node {
@@ -36,6 +50,7 @@ impl Node {
       parent: module,
       path,
       ports,
+      param_modulations: HashMap::new(),
     }
   }
 }
@@ -58,22 +73,58 @@ impl NodeLike for Node {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NodeDescriptor {
   pub parameters: Vec<ParamDescriptor>,
   pub ports: DescriptorPorts,
+  /// Index of the dry/wet parameter managed by the engine, if any
+  #[serde(default)]
+  pub dry_wet: Option<usize>,
+  /// Samples the outputs are delayed from the inputs, for the application to align them with other paths
+  #[serde(default)]
+  pub latency: usize,
 }
 
 impl NodeDescriptor {
+  pub const DRY_WET_ID: &'static str = "dry-wet";
+
   pub fn new() -> Self {
     Self {
       parameters: Vec::new(),
       ports: DescriptorPorts::new(),
+      dry_wet: None,
+      latency: 0,
     }
   }
 
+  /// Set the parameters of the processor, which go before the dry/wet parameter if any.
   pub fn with_parameters(mut self, params: Vec<ParamDescriptor>) -> Self {
+    let dry_wet = self.dry_wet.map(|index| self.parameters.remove(index));
     self.parameters = params;
+    if let Some(dry_wet) = dry_wet {
+      self.dry_wet = Some(self.parameters.len());
+      self.parameters.push(dry_wet);
+    }
+    self
+  }
+
+  /// Add a dry/wet parameter after the rest of parameters, with which the engine crossfades
+  /// every audio input into the output with the same index, so processors don't need to mix them.
+  pub fn with_dry_wet(mut self) -> Self {
+    if self.dry_wet.is_none() {
+      self.dry_wet = Some(self.parameters.len());
+      self.parameters.push(
+        ParamDescriptor::new(Self::DRY_WET_ID)
+          .initial(1.0)
+          .unit(ParamUnit::Percent),
+      );
+    }
+    self
+  }
+
+  /// Report that the outputs are delayed some samples from the inputs, such as by a lookahead.
+  pub fn with_latency(mut self, latency: usize) -> Self {
+    self.latency = latency;
     self
   }
 
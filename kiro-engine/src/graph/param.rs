@@ -1,15 +1,82 @@
+use serde::{Deserialize, Serialize};
+
 use crate::key_store::HasId;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Unit of the values of a parameter, used when formatting and parsing them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParamUnit {
+  None,
+  Hertz,
+  Decibels,
+  /// Values in the `0..1` range presented as `0..100%`
+  Percent,
+  Seconds,
+  Milliseconds,
+  Semitones,
+  Cents,
+  Custom(String),
+}
+
+impl ParamUnit {
+  pub fn label(&self) -> &str {
+    match self {
+      Self::None => "",
+      Self::Hertz => "Hz",
+      Self::Decibels => "dB",
+      Self::Percent => "%",
+      Self::Seconds => "s",
+      Self::Milliseconds => "ms",
+      Self::Semitones => "st",
+      Self::Cents => "ct",
+      Self::Custom(label) => label.as_str(),
+    }
+  }
+
+  fn display_value(&self, value: f32) -> f32 {
+    match self {
+      Self::Percent => value * 100.0,
+      _ => value,
+    }
+  }
+
+  fn parsed_value(&self, value: f32) -> f32 {
+    match self {
+      Self::Percent => value / 100.0,
+      _ => value,
+    }
+  }
+}
+
+/// How the values of a parameter are distributed along the normalized `0..1` range of a control.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ParamScale {
+  Linear,
+  /// Useful for frequencies or times. Requires a positive range.
+  Logarithmic,
+  /// Power curve with the given exponent, giving more resolution to the lower values when greater than 1.
+  Exponential(f32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParamDescriptor {
   pub id: String,
   pub initial: f32,
   pub min: f32,
   pub max: f32,
   pub center: f32,
+  pub unit: ParamUnit,
+  pub scale: ParamScale,
+  /// Number of discrete steps between min and max, for parameters that don't take any value in the range.
+  pub steps: Option<usize>,
+  /// Labels of the enumerated values, from min to max.
+  pub values: Vec<String>,
+  /// Number of decimals when formatting values.
+  pub precision: usize,
 }
 
 impl ParamDescriptor {
+  pub const DEFAULT_PRECISION: usize = 2;
+
   pub fn new<S: Into<String>>(id: S) -> Self {
     Self {
       id: id.into(),
@@ -17,6 +84,11 @@ impl ParamDescriptor {
       min: 0.0,
       max: 1.0,
       center: 0.0,
+      unit: ParamUnit::None,
+      scale: ParamScale::Linear,
+      steps: None,
+      values: Vec::new(),
+      precision: Self::DEFAULT_PRECISION,
     }
   }
 
@@ -39,6 +111,123 @@ impl ParamDescriptor {
     self.center = center;
     self
   }
+
+  pub fn unit(mut self, unit: ParamUnit) -> Self {
+    self.unit = unit;
+    self
+  }
+
+  pub fn scale(mut self, scale: ParamScale) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  pub fn steps(mut self, steps: usize) -> Self {
+    self.steps = Some(steps);
+    self
+  }
+
+  /// Enumerated values, mapped to the integers from `0` to `labels.len() - 1`.
+  pub fn values<S: Into<String>>(mut self, labels: Vec<S>) -> Self {
+    self.values = labels.into_iter().map(Into::into).collect();
+    self.min = 0.0;
+    self.max = self.values.len().saturating_sub(1) as f32;
+    self.steps = Some(self.values.len().saturating_sub(1));
+    self
+  }
+
+  pub fn precision(mut self, precision: usize) -> Self {
+    self.precision = precision;
+    self
+  }
+
+  /// Clamp a value into the range of the parameter, and snap it to the closest step if any.
+  pub fn constrain(&self, value: f32) -> f32 {
+    let value = value.clamp(self.min, self.max);
+    match self.steps {
+      Some(steps) if steps > 0 && self.max > self.min => {
+        let step = (self.max - self.min) / steps as f32;
+        self.min + ((value - self.min) / step).round() * step
+      }
+      _ => value,
+    }
+  }
+
+  /// Convert a value of the parameter into the `0..1` range of a control, according to its scale.
+  pub fn normalize(&self, value: f32) -> f32 {
+    if self.max <= self.min {
+      return 0.0;
+    }
+    let value = self.constrain(value);
+    match self.scale {
+      ParamScale::Linear => (value - self.min) / (self.max - self.min),
+      ParamScale::Logarithmic => (value / self.min).ln() / (self.max / self.min).ln(),
+      ParamScale::Exponential(exponent) => {
+        ((value - self.min) / (self.max - self.min)).powf(1.0 / exponent)
+      }
+    }
+  }
+
+  /// Convert a normalized value from the `0..1` range of a control into a value of the parameter.
+  pub fn denormalize(&self, normalized: f32) -> f32 {
+    let normalized = normalized.clamp(0.0, 1.0);
+    let value = match self.scale {
+      ParamScale::Linear => self.min + normalized * (self.max - self.min),
+      ParamScale::Logarithmic => self.min * (self.max / self.min).powf(normalized),
+      ParamScale::Exponential(exponent) => {
+        self.min + normalized.powf(exponent) * (self.max - self.min)
+      }
+    };
+    self.constrain(value)
+  }
+
+  /// Text to present a value to the user, including the unit.
+  pub fn format_value(&self, value: f32) -> String {
+    let value = self.constrain(value);
+    match self.value_label(value) {
+      Some(label) => label.to_string(),
+      None => {
+        let number = format!("{:.*}", self.precision, self.unit.display_value(value));
+        match self.unit.label() {
+          "" => number,
+          label => format!("{} {}", number, label),
+        }
+      }
+    }
+  }
+
+  /// Parse a value entered by the user, either a label of the enumerated values,
+  /// or a number with an optional unit. The result is constrained to the parameter range.
+  pub fn parse_value(&self, text: &str) -> Option<f32> {
+    let text = text.trim();
+    if let Some(index) = self
+      .values
+      .iter()
+      .position(|label| label.eq_ignore_ascii_case(text))
+    {
+      return Some(index as f32);
+    }
+
+    let unit_label = self.unit.label();
+    let number = text
+      .strip_suffix(unit_label)
+      .filter(|_| !unit_label.is_empty())
+      .unwrap_or(text)
+      .trim();
+    number
+      .parse::<f32>()
+      .ok()
+      .filter(|value| value.is_finite())
+      .map(|value| self.constrain(self.unit.parsed_value(value)))
+  }
+
+  fn value_label(&self, value: f32) -> Option<&str> {
+    if self.values.is_empty() {
+      None
+    } else {
+      self.values.get(value.round() as usize).map(String::as_str)
+    }
+  }
 }
 
 impl HasId for ParamDescriptor {
@@ -46,3 +235,69 @@ impl HasId for ParamDescriptor {
     self.id.as_str()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn format_and_parse_with_units() {
+    let freq = ParamDescriptor::new("freq")
+      .min(20.0)
+      .max(20_000.0)
+      .unit(ParamUnit::Hertz)
+      .precision(1);
+    assert_eq!(freq.format_value(440.0), "440.0 Hz");
+    assert_eq!(freq.format_value(1.0), "20.0 Hz");
+    assert_eq!(freq.parse_value("440 Hz"), Some(440.0));
+    assert_eq!(freq.parse_value(" 1000 "), Some(1000.0));
+    assert_eq!(freq.parse_value("99999"), Some(20_000.0));
+    assert_eq!(freq.parse_value("loud"), None);
+
+    let mix = ParamDescriptor::new("mix")
+      .unit(ParamUnit::Percent)
+      .precision(0);
+    assert_eq!(mix.format_value(0.25), "25 %");
+    assert_eq!(mix.parse_value("50%"), Some(0.5));
+  }
+
+  #[test]
+  fn enumerated_values() {
+    let shape = ParamDescriptor::new("shape").values(vec!["Sine", "Saw", "Square"]);
+    assert_eq!(shape.max, 2.0);
+    assert_eq!(shape.format_value(1.2), "Saw");
+    assert_eq!(shape.parse_value("square"), Some(2.0));
+    assert_eq!(shape.parse_value("1"), Some(1.0));
+    assert_eq!(shape.denormalize(0.8), 2.0);
+  }
+
+  #[test]
+  fn steps() {
+    let semitones = ParamDescriptor::new("semitones")
+      .min(-12.0)
+      .max(12.0)
+      .steps(24);
+    assert_eq!(semitones.constrain(3.4), 3.0);
+    assert_eq!(semitones.parse_value("-4.6"), Some(-5.0));
+  }
+
+  #[test]
+  fn scales() {
+    let freq = ParamDescriptor::new("freq")
+      .min(20.0)
+      .max(20_000.0)
+      .scale(ParamScale::Logarithmic);
+    assert!((freq.denormalize(0.5) - 632.4555).abs() < 0.01);
+    assert!((freq.normalize(632.4555) - 0.5).abs() < 1e-4);
+
+    let time = ParamDescriptor::new("time")
+      .max(4.0)
+      .scale(ParamScale::Exponential(2.0));
+    assert_eq!(time.denormalize(0.5), 1.0);
+    assert_eq!(time.normalize(1.0), 0.5);
+
+    let linear = ParamDescriptor::new("linear").min(-1.0);
+    assert_eq!(linear.normalize(0.0), 0.5);
+    assert_eq!(linear.denormalize(0.25), -0.5);
+  }
+}
@@ -1,5 +1,9 @@
+use serde::{Deserialize, Serialize};
+
 use crate::graph::connection::{ModuleIn, ModuleOut, NodeOut};
 use crate::graph::error::{Error, Result};
+use crate::graph::module::ModuleKey;
+use crate::graph::node::NodeKey;
 use crate::key_gen::Key;
 use crate::key_store::{HasId, KeyStoreWithId};
 
@@ -28,6 +32,53 @@ pub type EventsOutputSource = OutputSource<EventsDescriptor>;
 pub struct InputPort<D> {
   pub descriptor: D,
   pub source: Option<InputSource<D>>,
+  /// Additional sources, summed with the main one for audio, or merged for events.
+  pub summed: Vec<InputSource<D>>,
+}
+
+impl<D> InputPort<D>
+where
+  D: PortDescriptor,
+{
+  pub fn new(descriptor: D) -> Self {
+    Self {
+      descriptor,
+      source: None,
+      summed: Vec::new(),
+    }
+  }
+
+  /// The main source followed by the summed ones.
+  pub fn sources(&self) -> impl Iterator<Item = &InputSource<D>> {
+    self.source.iter().chain(self.summed.iter())
+  }
+
+  pub fn has_source(&self, source: &InputSource<D>) -> bool {
+    self.sources().any(|other| other == source)
+  }
+
+  /// Remove a source, returning whether it was there.
+  ///
+  /// When the main source is removed, the first summed one takes its place.
+  pub fn remove_source(&mut self, source: &InputSource<D>) -> bool {
+    if self.source.as_ref() == Some(source) {
+      self.source = if self.summed.is_empty() {
+        None
+      } else {
+        Some(self.summed.remove(0))
+      };
+      true
+    } else {
+      let len = self.summed.len();
+      self.summed.retain(|other| other != source);
+      self.summed.len() != len
+    }
+  }
+
+  pub fn clear_sources(&mut self) {
+    self.source = None;
+    self.summed.clear();
+  }
 }
 
 impl<D> HasId for InputPort<D>
@@ -127,10 +178,7 @@ impl Ports {
   ) -> Self {
     let mut audio_input_ports = KeyStoreWithId::new();
     for descriptor in audio_input.iter() {
-      audio_input_ports.add(AudioInputPort {
-        descriptor: descriptor.clone(),
-        source: None,
-      });
+      audio_input_ports.add(AudioInputPort::new(descriptor.clone()));
     }
 
     let mut audio_output_ports = KeyStoreWithId::new();
@@ -143,10 +191,7 @@ impl Ports {
 
     let mut events_input_ports = KeyStoreWithId::new();
     for descriptor in events_input.iter() {
-      events_input_ports.add(EventsInputPort {
-        descriptor: descriptor.clone(),
-        source: None,
-      });
+      events_input_ports.add(EventsInputPort::new(descriptor.clone()));
     }
 
     let mut events_output_ports = KeyStoreWithId::new();
@@ -164,8 +209,87 @@ impl Ports {
       events_output_ports,
     }
   }
+
+  /// Remove any connection or binding coming from the outputs of a node.
+  pub fn remove_node_sources(&mut self, node_key: NodeKey) {
+    Self::remove_node_input_sources(&mut self.audio_input_ports, node_key);
+    Self::remove_node_output_sources(&mut self.audio_output_ports, node_key);
+    Self::remove_node_input_sources(&mut self.events_input_ports, node_key);
+    Self::remove_node_output_sources(&mut self.events_output_ports, node_key);
+  }
+
+  fn remove_node_input_sources<D>(ports: &mut KeyStoreWithId<InputPort<D>>, node_key: NodeKey)
+  where
+    D: PortDescriptor,
+  {
+    for port in ports.values_mut() {
+      let removed = port
+        .sources()
+        .filter(|source| matches!(source, InputSource::NodeConnection(node_out) if node_out.node_key() == node_key))
+        .cloned()
+        .collect::<Vec<InputSource<D>>>();
+      for source in removed.iter() {
+        port.remove_source(source);
+      }
+    }
+  }
+
+  fn remove_node_output_sources<D>(ports: &mut KeyStoreWithId<OutputPort<D>>, node_key: NodeKey)
+  where
+    D: PortDescriptor,
+  {
+    for port in ports.values_mut() {
+      if matches!(port.source, Some(OutputSource::NodeBinding(node_out)) if node_out.node_key() == node_key)
+      {
+        port.source = None;
+      }
+    }
+  }
+
+  /// Remove any connection or binding coming from the inputs or outputs of a module.
+  pub fn remove_module_sources(&mut self, module_key: ModuleKey) {
+    Self::remove_module_input_sources(&mut self.audio_input_ports, module_key);
+    Self::remove_module_output_sources(&mut self.audio_output_ports, module_key);
+    Self::remove_module_input_sources(&mut self.events_input_ports, module_key);
+    Self::remove_module_output_sources(&mut self.events_output_ports, module_key);
+  }
+
+  fn remove_module_input_sources<D>(ports: &mut KeyStoreWithId<InputPort<D>>, module_key: ModuleKey)
+  where
+    D: PortDescriptor,
+  {
+    for port in ports.values_mut() {
+      let removed = port
+        .sources()
+        .filter(|source| match source {
+          InputSource::ModuleBinding(module_in) => module_in.module_key() == module_key,
+          InputSource::ModuleConnection(module_out) => module_out.module_key() == module_key,
+          InputSource::NodeConnection(_) => false,
+        })
+        .cloned()
+        .collect::<Vec<InputSource<D>>>();
+      for source in removed.iter() {
+        port.remove_source(source);
+      }
+    }
+  }
+
+  fn remove_module_output_sources<D>(
+    ports: &mut KeyStoreWithId<OutputPort<D>>,
+    module_key: ModuleKey,
+  ) where
+    D: PortDescriptor,
+  {
+    for port in ports.values_mut() {
+      if matches!(port.source, Some(OutputSource::ModuleBinding(module_out)) if module_out.module_key() == module_key)
+      {
+        port.source = None;
+      }
+    }
+  }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PortType {
   Audio,
   Events,
@@ -180,27 +304,116 @@ impl PortType {
   }
 }
 
-pub trait PortDescriptor: HasId + Clone {
+pub trait PortDescriptor: HasId + Clone + PartialEq {
   fn with_id<S: Into<String>>(self, id: S) -> Self;
   fn port_type() -> PortType;
+
+  /// Whether a port with this descriptor can be connected from a port with the `source` one.
+  fn accepts(&self, _source: &Self) -> bool {
+    true
+  }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// How the channels of an audio port are arranged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChannelLayout {
+  Mono,
+  Stereo,
+  /// Channels without any particular arrangement, compatible with any layout with the same number of them
+  Discrete(usize),
+  /// Channels arranged as in the given names, as in `["L", "R", "C", "LFE", "Ls", "Rs"]`
+  Named(Vec<String>),
+}
+
+impl ChannelLayout {
+  /// Layout for a number of channels, being mono or stereo for one or two of them.
+  pub fn from_channels(channels: usize) -> Self {
+    match channels {
+      1 => ChannelLayout::Mono,
+      2 => ChannelLayout::Stereo,
+      _ => ChannelLayout::Discrete(channels),
+    }
+  }
+
+  pub fn channels(&self) -> usize {
+    match self {
+      ChannelLayout::Mono => 1,
+      ChannelLayout::Stereo => 2,
+      ChannelLayout::Discrete(channels) => *channels,
+      ChannelLayout::Named(names) => names.len(),
+    }
+  }
+
+  /// Whether an input with this layout can read from an output with the `source` one.
+  ///
+  /// Layouts with the same number of channels are compatible when they are the same or any of them is discrete,
+  /// and mono sources are also compatible with stereo inputs, as they are adapted by duplicating their channel.
+  pub fn accepts(&self, source: &ChannelLayout) -> bool {
+    match (self, source) {
+      (ChannelLayout::Stereo, ChannelLayout::Mono) => true,
+      (ChannelLayout::Discrete(_), _) | (_, ChannelLayout::Discrete(_)) => {
+        self.channels() == source.channels()
+      }
+      _ => self == source,
+    }
+  }
+
+  /// Channel of an output with the `source` layout to be read by one channel of this layout.
+  pub fn source_channel(&self, source: &ChannelLayout, channel: usize) -> usize {
+    match (self, source) {
+      (ChannelLayout::Stereo, ChannelLayout::Mono) => 0,
+      _ => channel,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct AudioDescriptor {
   id: String,
-  channels: usize,
+  layout: ChannelLayout,
+  /// Whether the input needs a source for the node to render properly
+  #[serde(default)]
+  required: bool,
 }
 
 impl AudioDescriptor {
   pub fn new<S: Into<String>>(id: S, channels: usize) -> Self {
     Self {
       id: id.into(),
-      channels,
+      layout: ChannelLayout::from_channels(channels),
+      required: false,
     }
   }
 
+  pub fn mono<S: Into<String>>(id: S) -> Self {
+    Self::new(id, 1)
+  }
+
+  pub fn stereo<S: Into<String>>(id: S) -> Self {
+    Self::new(id, 2)
+  }
+
+  pub fn with_layout(mut self, layout: ChannelLayout) -> Self {
+    self.layout = layout;
+    self
+  }
+
+  /// Make the input required, so render plans where it is not connected are rejected.
+  pub fn required(mut self) -> Self {
+    self.required = true;
+    self
+  }
+
+  pub fn is_required(&self) -> bool {
+    self.required
+  }
+
+  pub fn layout(&self) -> &ChannelLayout {
+    &self.layout
+  }
+
   pub fn channels(&self) -> usize {
-    self.channels
+    self.layout.channels()
   }
 }
 
@@ -213,6 +426,10 @@ impl PortDescriptor for AudioDescriptor {
   fn port_type() -> PortType {
     PortType::Audio
   }
+
+  fn accepts(&self, source: &Self) -> bool {
+    self.layout.accepts(&source.layout)
+  }
 }
 
 impl HasId for AudioDescriptor {
@@ -221,14 +438,30 @@ impl HasId for AudioDescriptor {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct EventsDescriptor {
   id: String,
+  /// Whether the input needs a source for the node to render properly
+  #[serde(default)]
+  required: bool,
 }
 
 impl EventsDescriptor {
   pub fn new<S: Into<String>>(id: S) -> Self {
-    Self { id: id.into() }
+    Self {
+      id: id.into(),
+      required: false,
+    }
+  }
+
+  /// Make the input required, so render plans where it is not connected are rejected.
+  pub fn required(mut self) -> Self {
+    self.required = true;
+    self
+  }
+
+  pub fn is_required(&self) -> bool {
+    self.required
   }
 }
 
@@ -249,7 +482,7 @@ impl HasId for EventsDescriptor {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DynamicPorts {
   None,
   Limited(usize),
@@ -260,7 +493,7 @@ pub trait DescriptorPortAccessor<D> {
   fn get_port(&self) -> &GenericDescriptorPorts<D>;
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DescriptorPorts {
   pub audio: GenericDescriptorPorts<AudioDescriptor>,
   pub events: GenericDescriptorPorts<EventsDescriptor>,
@@ -287,7 +520,7 @@ impl DescriptorPortAccessor<EventsDescriptor> for DescriptorPorts {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GenericDescriptorPorts<D> {
   pub static_inputs: Vec<D>,
   pub dynamic_inputs: DynamicPorts,
@@ -42,6 +42,10 @@ impl<T> KeyStore<T> {
     self.data.values()
   }
 
+  pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+    self.data.values_mut()
+  }
+
   pub fn contains_key(&self, key: Key<T>) -> bool {
     self.data.contains_key(&key)
   }
@@ -116,11 +120,30 @@ impl<T: HasId> KeyStoreWithId<T> {
     self.key_store.keys()
   }
 
+  /// Keys in the same order as the items were added.
+  pub fn ordered_keys(&self) -> impl Iterator<Item = &Key<T>> {
+    let mut keys = self.key_store.keys().collect::<Vec<&Key<T>>>();
+    keys.sort_unstable();
+    keys.into_iter()
+  }
+
+  /// Items in the same order as they were added.
+  pub fn ordered_iter(&self) -> impl Iterator<Item = (Key<T>, &T)> {
+    let mut items = self.key_store.iter().collect::<Vec<(Key<T>, &T)>>();
+    items.sort_unstable_by_key(|(key, _)| *key);
+    items.into_iter()
+  }
+
   #[inline]
   pub fn values(&self) -> impl Iterator<Item = &T> {
     self.key_store.values()
   }
 
+  #[inline]
+  pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+    self.key_store.values_mut()
+  }
+
   #[inline]
   pub fn contains_key(&self, key: Key<T>) -> bool {
     self.key_store.contains_key(key)
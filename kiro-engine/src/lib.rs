@@ -1,31 +1,59 @@
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
 mod config;
 mod engine;
 mod error;
 mod graph;
 mod key_gen;
 mod key_store;
+mod macros;
 mod module;
 mod node;
+mod observer;
+mod poly;
 mod ports;
 pub mod processor;
+pub mod processors;
 mod rendering;
+mod snapshot;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use crate::config::EngineConfig;
 pub use crate::engine::Engine;
 pub use crate::error::Error;
-pub use crate::graph::param::ParamDescriptor;
-pub use crate::graph::port::{AudioDescriptor, EventsDescriptor};
+pub use crate::graph::document::{
+  ConnectionDocument, ConnectionKind, DynamicPortsDocument, GraphDocument, ModulationDocument,
+  ModuleDocument, NodeDocument, ParamBindingDocument,
+};
+pub use crate::graph::param::{ParamDescriptor, ParamScale, ParamUnit};
+pub use crate::graph::port::{AudioDescriptor, ChannelLayout, EventsDescriptor, PortType};
 pub use crate::graph::{ModuleDescriptor, NodeDescriptor};
+pub use crate::macros::{MacroParameter, MacroRange};
 pub use crate::module::Module;
 pub use crate::node::ProcessorNode;
+pub use crate::observer::{GraphEvent, ObserverId};
+pub use crate::poly::{PolyModule, PolyVoice};
 pub use crate::ports::{
   AudioNodeIn, AudioNodeOut, EventsNodeIn, EventsNodeOut, ModuleIn, ModuleOut, NodeIn, NodeOut,
 };
-pub use crate::processor::{context::ProcessorContext, Processor};
-pub use crate::rendering::buffers::events::{Event, EventData};
+pub use crate::processor::{
+  context::ProcessorContext, swap_state, Processor, SharedState, StateError, StateSnapshot,
+};
+pub use crate::rendering::buffers::events::{
+  AutomationCurve, AutomationData, Event, EventData, ParamRef, TransportMessage,
+};
+pub use crate::rendering::diagnostics::{
+  Diagnostics, DropoutCause, DropoutSnapshot, MessageKind, RenderLoad,
+};
+pub use crate::rendering::meters::MeterLevel;
 pub use crate::rendering::param_value::ParamValue;
+pub use crate::rendering::transport::Transport;
+pub use crate::snapshot::EngineSnapshot;
 
 // FIXME make them private
-pub use rendering::controller::Controller;
+pub use rendering::controller::{
+  Controller, GestureKind, OutputEvent, ParamGesture, ProcessorFault, StateLoaded,
+};
 pub use rendering::controller_plan::PlanNode;
 pub use rendering::renderer::Renderer;
@@ -0,0 +1,175 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::engine::InnerEngine;
+use crate::error::Result;
+use crate::graph::port::NodeLike;
+use crate::graph::NodeKey;
+use crate::observer::GraphEvent;
+use crate::rendering::controller::ParamKey;
+use crate::{ParamDescriptor, ParamScale, ProcessorNode};
+
+/// Range of values followed by a parameter driven from a macro, as the macro goes from 0 to 1.
+///
+/// The range can be inverted, with `min` greater than `max`, so the parameter goes down as the macro goes up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacroRange {
+  pub min: f32,
+  pub max: f32,
+  pub scale: ParamScale,
+}
+
+impl MacroRange {
+  pub fn new(min: f32, max: f32) -> Self {
+    Self {
+      min,
+      max,
+      scale: ParamScale::Linear,
+    }
+  }
+
+  #[must_use]
+  pub fn scale(mut self, scale: ParamScale) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  /// Value of the parameter for a value of the macro.
+  pub fn map(&self, value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    match self.scale {
+      ParamScale::Linear => self.min + value * (self.max - self.min),
+      ParamScale::Logarithmic => self.min * (self.max / self.min).powf(value),
+      ParamScale::Exponential(exponent) => self.min + value.powf(exponent) * (self.max - self.min),
+    }
+  }
+}
+
+struct MacroTarget {
+  node_key: NodeKey,
+  index: usize,
+  param_key: ParamKey,
+  descriptor: ParamDescriptor,
+  range: MacroRange,
+}
+
+/// Control driving several parameters of different nodes at once,
+/// as a single "brightness" knob for the filters of all the voices of a synth.
+///
+/// The parameters are changed all together before rendering the next block.
+pub struct MacroParameter {
+  engine: Rc<RefCell<InnerEngine>>,
+  id: String,
+  value: f32,
+  targets: Vec<MacroTarget>,
+}
+
+impl MacroParameter {
+  pub(crate) fn new(engine: Rc<RefCell<InnerEngine>>, id: &str, value: f32) -> Self {
+    Self {
+      engine,
+      id: id.to_string(),
+      value: value.clamp(0.0, 1.0),
+      targets: Vec::new(),
+    }
+  }
+
+  pub fn id(&self) -> &str {
+    self.id.as_str()
+  }
+
+  /// Value of the macro, in the `0..1` range.
+  pub fn value(&self) -> f32 {
+    self.value
+  }
+
+  /// Drive one of the parameters of a node from this macro along a range.
+  ///
+  /// The parameter takes the value for the current value of the macro once it is changed again.
+  pub fn add_target(
+    &mut self,
+    node: &ProcessorNode,
+    index: usize,
+    range: MacroRange,
+  ) -> Result<()> {
+    let param_key = node.existing_parameter_key(index)?;
+    let descriptor = node.descriptor()?.parameters[index].clone();
+    self.targets.push(MacroTarget {
+      node_key: node.node_key,
+      index,
+      param_key,
+      descriptor,
+      range,
+    });
+    Ok(())
+  }
+
+  /// Stop driving the parameters of a node, returning how many of them were driven.
+  pub fn remove_targets(&mut self, node: &ProcessorNode) -> usize {
+    let len = self.targets.len();
+    self
+      .targets
+      .retain(|target| target.node_key != node.node_key);
+    len - self.targets.len()
+  }
+
+  pub fn num_targets(&self) -> usize {
+    self.targets.len()
+  }
+
+  /// Change the value of the macro, and the value of all the parameters driven by it at once.
+  pub fn set_value(&mut self, value: f32) -> Result<()> {
+    self.value = value.clamp(0.0, 1.0);
+    let values = self
+      .targets
+      .iter()
+      .map(|target| {
+        let value = target.descriptor.constrain(target.range.map(self.value));
+        (target.param_key, value)
+      })
+      .collect::<Vec<(ParamKey, f32)>>();
+
+    let mut engine = self.engine.borrow_mut();
+    engine.controller.set_parameter_values(&values)?;
+    for (target, (_, value)) in self.targets.iter().zip(values) {
+      engine.notify(|graph| {
+        Ok(GraphEvent::ParamChanged {
+          node: graph.get_node(target.node_key)?.full_name(),
+          index: target.index,
+          value,
+        })
+      })?;
+    }
+    Ok(())
+  }
+
+  /// Value that a parameter of a node takes for the current value of the macro, if driven by it.
+  pub fn target_value(&self, node: &ProcessorNode, index: usize) -> Option<f32> {
+    self
+      .targets
+      .iter()
+      .find(|target| target.node_key == node.node_key && target.index == index)
+      .map(|target| target.descriptor.constrain(target.range.map(self.value)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn map_values_into_ranges() {
+    let range = MacroRange::new(0.0, 10.0);
+    assert_eq!(range.map(0.5), 5.0);
+    assert_eq!(range.map(2.0), 10.0);
+
+    let inverted = MacroRange::new(1.0, 0.0);
+    assert_eq!(inverted.map(0.25), 0.75);
+
+    let exponential = MacroRange::new(0.0, 1.0).scale(ParamScale::Exponential(2.0));
+    assert_eq!(exponential.map(0.5), 0.25);
+
+    let logarithmic = MacroRange::new(20.0, 20000.0).scale(ParamScale::Logarithmic);
+    assert!((logarithmic.map(0.5) - 632.4555).abs() < 0.01);
+  }
+}
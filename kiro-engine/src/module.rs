@@ -2,13 +2,22 @@ use std::cell::RefCell;
 use std::ops::Deref;
 use std::rc::Rc;
 
+use kiro_time::SampleRate;
+
 use crate::engine::InnerEngine;
 use crate::error::Result;
-use crate::graph::ModuleKey;
+use crate::graph::connection::{AudioConnection, EventsConnection};
+use crate::graph::port::NodeLike;
+use crate::graph::{self, ModuleKey};
 use crate::node::ProcessorNode;
+use crate::observer::GraphEvent;
+use crate::poly::{PolyModule, PolyVoice};
 use crate::ports::{AudioModuleIn, AudioModuleOut, EventsModuleIn, EventsModuleOut};
 use crate::ports::{ModuleIn, ModuleOut};
-use crate::{ModuleDescriptor, Processor};
+use crate::processor::BoxedProcessor;
+use crate::processors::ResampledProcessor;
+use crate::rendering::controller_plan::NodeProcessor;
+use crate::{ModuleDescriptor, NodeDescriptor, Processor};
 
 pub struct Module {
   pub(crate) engine: Rc<RefCell<InnerEngine>>,
@@ -38,12 +47,68 @@ impl Module {
     Ok(module.path.clone())
   }
 
+  /// Change the name of the module, which also changes the path of everything inside it.
+  pub fn rename(&mut self, name: &str) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.graph.rename_module(self.key, name)?;
+    Ok(())
+  }
+
+  /// Remove the module from the graph, together with all the modules and nodes inside it.
+  ///
+  /// The processors keep rendering until the render plan is updated.
+  pub fn remove(self) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.remove_module(self.key)
+  }
+
   pub fn descriptor(&self) -> Result<ModuleDescriptor> {
     let engine = self.engine.deref().borrow();
     let module = engine.graph.get_module(self.key)?;
     Ok(module.descriptor.clone())
   }
 
+  /// Value of one of the parameters of the module.
+  pub fn parameter(&self, index: usize) -> Result<f32> {
+    let engine = self.engine.deref().borrow();
+    let module = engine.graph.get_module(self.key)?;
+    match module.param_values.get(index) {
+      Some(value) => Ok(*value),
+      None => Err(graph::Error::ParamNotFound(module.full_name(), index).into()),
+    }
+  }
+
+  /// Change one of the parameters of the module, forwarding the value to the parameters bound to it.
+  pub fn set_parameter(&self, index: usize, value: f32) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.graph.set_module_param(self.key, index, value)?;
+    engine.forward_module_param(self.key, index)
+  }
+
+  /// Bind one of the parameters of the module to a parameter of a node inside it,
+  /// so the module can expose a curated set of parameters to the outside.
+  ///
+  /// The node parameter takes the value of the module parameter right away.
+  pub fn bind_parameter(
+    &mut self,
+    index: usize,
+    node: &ProcessorNode,
+    node_index: usize,
+  ) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine
+      .graph
+      .bind_module_param(self.key, index, node.node_key, node_index)?;
+    engine.forward_module_param(self.key, index)
+  }
+
+  /// Stop forwarding one of the parameters of the module to the parameters bound to it.
+  pub fn unbind_parameter(&mut self, index: usize) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.graph.unbind_module_param(self.key, index)?;
+    Ok(())
+  }
+
   pub fn create_module(&mut self, name: &str, descriptor: ModuleDescriptor) -> Result<Module> {
     let mut engine = self.engine.borrow_mut();
     let key = engine.graph.create_module(self.key, name, descriptor)?;
@@ -57,10 +122,38 @@ impl Module {
   where
     P: Processor + 'static,
   {
-    let mut engine = self.engine.borrow_mut();
     let descriptor = processor.descriptor();
+    self.create_boxed_processor(name, descriptor, Box::new(processor))
+  }
 
-    let processor_key = engine.controller.add_processor(processor);
+  /// Create a node for a processor that only works at `sample_rate`,
+  /// resampling its audio from and to the sample rate of the engine.
+  ///
+  /// The node reports the latency added by the resampling, which the graph doesn't compensate.
+  pub fn create_resampled_processor<P>(
+    &mut self,
+    name: &str,
+    processor: P,
+    sample_rate: SampleRate,
+  ) -> Result<ProcessorNode>
+  where
+    P: Processor + 'static,
+  {
+    let graph_rate = self.engine.deref().borrow().controller.sample_rate();
+    let processor = ResampledProcessor::new(processor, sample_rate, graph_rate);
+    self.create_processor(name, processor)
+  }
+
+  /// Create a node for a processor which type is not known at compile time,
+  /// such as the ones created when importing a graph.
+  pub fn create_boxed_processor(
+    &mut self,
+    name: &str,
+    descriptor: NodeDescriptor,
+    processor: BoxedProcessor,
+  ) -> Result<ProcessorNode> {
+    let mut engine = self.engine.borrow_mut();
+    let processor_key = engine.controller.add_boxed_processor(processor);
     let initial_values = descriptor
       .parameters
       .iter()
@@ -69,6 +162,14 @@ impl Module {
     let param_keys = engine.controller.add_parameters(initial_values.as_slice());
 
     let node_key = engine.graph.create_node(self.key, name, descriptor)?;
+    engine.processors.insert(
+      node_key,
+      NodeProcessor {
+        processor: processor_key,
+        parameters: param_keys.clone(),
+      },
+    );
+    engine.notify(|graph| Ok(GraphEvent::NodeAdded(graph.get_node(node_key)?.full_name())))?;
 
     Ok(ProcessorNode {
       engine: self.engine.clone(),
@@ -78,6 +179,38 @@ impl Module {
     })
   }
 
+  /// Find a processor node directly inside this module by its name.
+  pub fn find_node(&self, name: &str) -> Option<ProcessorNode> {
+    let engine = self.engine.deref().borrow();
+    let module = engine.graph.get_module(self.key).ok()?;
+    let node_key = engine
+      .graph
+      .find_node(&format!("{}/{}", module.full_name(), name))?;
+    let node_processor = engine.processors.get(&node_key)?;
+    Some(ProcessorNode {
+      engine: self.engine.clone(),
+      node_key,
+      processor_key: node_processor.processor,
+      param_keys: node_processor.parameters.clone(),
+    })
+  }
+
+  /// Create a module replicating the voices built by `template` a number of times.
+  ///
+  /// See [`PolyModule`] for how the events and audio of the voices are routed.
+  pub fn create_poly_module<F>(
+    &mut self,
+    name: &str,
+    num_voices: usize,
+    channels: usize,
+    template: F,
+  ) -> Result<PolyModule>
+  where
+    F: FnMut(&mut Module, usize) -> Result<PolyVoice>,
+  {
+    PolyModule::create(self, name, num_voices, channels, template)
+  }
+
   pub fn audio_input(&self, name: &str) -> Result<AudioModuleIn> {
     let engine = self.engine.deref().borrow();
     let port_key = engine.graph.module_audio_input(self.key, name)?;
@@ -117,4 +250,42 @@ impl Module {
       port_key,
     })
   }
+
+  /// Connect an audio source to an input even if it already has one, so they are summed.
+  pub fn add_audio(&mut self, connection: AudioConnection) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.connect(connection, true)
+  }
+
+  /// Connect an events source to an input even if it already has one, so their events are merged.
+  pub fn add_events(&mut self, connection: EventsConnection) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.connect(connection, true)
+  }
+
+  /// Remove an audio connection or binding, so the graph can be re-patched while live.
+  pub fn disconnect_audio(&mut self, connection: AudioConnection) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.disconnect(connection)
+  }
+
+  /// Remove an events connection or binding, so the graph can be re-patched while live.
+  pub fn disconnect_events(&mut self, connection: EventsConnection) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.disconnect(connection)
+  }
+
+  /// Remove whatever is connected or bound to an audio input of this module.
+  pub fn clear_audio_input(&mut self, name: &str) -> Result<()> {
+    let module_in = self.audio_input(name)?.into();
+    let mut engine = self.engine.borrow_mut();
+    engine.clear_module_input(module_in)
+  }
+
+  /// Remove whatever is connected or bound to an events input of this module.
+  pub fn clear_events_input(&mut self, name: &str) -> Result<()> {
+    let module_in = self.events_input(name)?.into();
+    let mut engine = self.engine.borrow_mut();
+    engine.clear_module_input(module_in)
+  }
 }
@@ -4,9 +4,12 @@ use std::rc::Rc;
 
 use crate::engine::InnerEngine;
 use crate::error::Result;
-use crate::graph::NodeKey;
+use crate::graph::port::NodeLike;
+use crate::graph::{self, NodeKey};
 use crate::module::Module;
+use crate::observer::GraphEvent;
 use crate::ports::{NodeIn, NodeOut};
+use crate::processor::{BoxedProcessor, Processor};
 use crate::rendering::controller::{ParamKey, ProcessorKey};
 use crate::{AudioNodeIn, AudioNodeOut, EventsNodeIn, EventsNodeOut, NodeDescriptor};
 
@@ -39,12 +42,146 @@ impl ProcessorNode {
     Ok(node.path.clone())
   }
 
+  pub fn rename(&mut self, name: &str) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.graph.rename_node(self.node_key, name)?;
+    Ok(())
+  }
+
+  /// Remove the node from the graph, releasing its processor and parameters.
+  ///
+  /// The processor keeps rendering until the render plan is updated.
+  pub fn remove(self) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.remove_node(self.node_key)
+  }
+
+  /// Replace the processor of this node without disturbing its connections,
+  /// as when reloading the code of a processor during development.
+  ///
+  /// The new processor must have the same ports, and the parameters with the same id keep their values.
+  /// It starts rendering once the render plan is updated.
+  pub fn replace_processor<P>(&mut self, processor: P) -> Result<()>
+  where
+    P: Processor + 'static,
+  {
+    let descriptor = processor.descriptor();
+    self.replace_boxed_processor(descriptor, Box::new(processor))
+  }
+
+  /// Replace the processor of this node by one which type is not known at compile time.
+  pub fn replace_boxed_processor(
+    &mut self,
+    descriptor: NodeDescriptor,
+    processor: BoxedProcessor,
+  ) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    let node_processor = engine.replace_processor(self.node_key, descriptor, processor)?;
+    self.processor_key = node_processor.processor;
+    self.param_keys = node_processor.parameters;
+    Ok(())
+  }
+
+  /// Replace the outputs of the processor by its inputs with the same index,
+  /// crossfading between them to avoid clicks, so effects can be toggled while playing.
+  pub fn set_bypass(&self, bypass: bool) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.controller.set_bypass(self.processor_key, bypass)?;
+    Ok(())
+  }
+
+  pub fn is_bypassed(&self) -> bool {
+    self
+      .engine
+      .deref()
+      .borrow()
+      .controller
+      .is_bypassed(self.processor_key)
+  }
+
+  /// Whether the processor panicked while rendering, so it has been bypassed since then.
+  pub fn is_faulted(&self) -> bool {
+    self
+      .engine
+      .deref()
+      .borrow()
+      .controller
+      .is_faulted(self.processor_key)
+  }
+
+  /// Key of the processor of this node, as found in the [`crate::ProcessorFault`] notifications.
+  pub fn processor_key(&self) -> ProcessorKey {
+    self.processor_key
+  }
+
   pub fn descriptor(&self) -> Result<NodeDescriptor> {
     let engine = self.engine.deref().borrow();
     let node = engine.graph.get_node(self.node_key)?;
     Ok(node.descriptor.clone())
   }
 
+  pub fn set_parameter(&self, index: usize, value: f32) -> Result<()> {
+    let param_key = self.existing_parameter_key(index)?;
+    let mut engine = self.engine.borrow_mut();
+    engine.controller.set_parameter_value(param_key, value)?;
+    engine.notify(|graph| {
+      Ok(GraphEvent::ParamChanged {
+        node: graph.get_node(self.node_key)?.full_name(),
+        index,
+        value,
+      })
+    })
+  }
+
+  /// Value last set for a parameter, before any automation and modulation.
+  pub fn parameter(&self, index: usize) -> Result<f32> {
+    let param_key = self.existing_parameter_key(index)?;
+    let engine = self.engine.deref().borrow();
+    let param_value = engine.controller.get_parameter_value(param_key)?;
+    Ok(param_value.get())
+  }
+
+  /// Effective value of a parameter for the last rendered block, as changed by automation and modulation,
+  /// so the controls of a user interface can follow it.
+  pub fn current_parameter(&self, index: usize) -> Result<f32> {
+    let param_key = self.existing_parameter_key(index)?;
+    let engine = self.engine.deref().borrow();
+    let param_value = engine.controller.get_parameter_value(param_key)?;
+    Ok(param_value.current())
+  }
+
+  /// Key of one of the parameters of this node, to identify it in the [`crate::ParamGesture`]s.
+  pub fn parameter_key(&self, index: usize) -> Option<ParamKey> {
+    self.param_keys.get(index).cloned()
+  }
+
+  /// The user started touching one of the parameters, as when grabbing a knob.
+  pub fn begin_gesture(&self, index: usize) -> Result<()> {
+    let param_key = self.existing_parameter_key(index)?;
+    let mut engine = self.engine.borrow_mut();
+    engine.controller.begin_gesture(param_key)?;
+    Ok(())
+  }
+
+  /// The user released one of the parameters.
+  pub fn end_gesture(&self, index: usize) -> Result<()> {
+    let param_key = self.existing_parameter_key(index)?;
+    let mut engine = self.engine.borrow_mut();
+    engine.controller.end_gesture(param_key)?;
+    Ok(())
+  }
+
+  pub(crate) fn existing_parameter_key(&self, index: usize) -> Result<ParamKey> {
+    match self.parameter_key(index) {
+      Some(param_key) => Ok(param_key),
+      None => {
+        let engine = self.engine.deref().borrow();
+        let node = engine.graph.get_node(self.node_key)?;
+        Err(graph::Error::ParamNotFound(node.full_name(), index).into())
+      }
+    }
+  }
+
   pub fn audio_input(&self, name: &str) -> Result<AudioNodeIn> {
     let engine = self.engine.deref().borrow();
     let port_key = engine.graph.node_audio_input(self.node_key, name)?;
@@ -84,4 +221,41 @@ impl ProcessorNode {
       port_key,
     })
   }
+
+  /// Remove whatever is connected or bound to an audio input of this node.
+  pub fn clear_audio_input(&mut self, name: &str) -> Result<()> {
+    let node_in = self.audio_input(name)?.into();
+    let mut engine = self.engine.borrow_mut();
+    engine.clear_node_input(node_in)
+  }
+
+  /// Remove whatever is connected or bound to an events input of this node.
+  pub fn clear_events_input(&mut self, name: &str) -> Result<()> {
+    let node_in = self.events_input(name)?.into();
+    let mut engine = self.engine.borrow_mut();
+    engine.clear_node_input(node_in)
+  }
+
+  /// Modulate one of the parameters of this node at audio rate from the first channel of an audio output.
+  ///
+  /// The output must belong to a node in the same module.
+  pub fn modulate_parameter(
+    &mut self,
+    index: usize,
+    source: AudioNodeOut,
+    depth: f32,
+  ) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine
+      .graph
+      .modulate_param(source.into(), self.node_key, index, depth)?;
+    Ok(())
+  }
+
+  /// Remove the audio rate modulation of one of the parameters of this node.
+  pub fn clear_parameter_modulation(&mut self, index: usize) -> Result<()> {
+    let mut engine = self.engine.borrow_mut();
+    engine.graph.clear_param_modulation(self.node_key, index)?;
+    Ok(())
+  }
 }
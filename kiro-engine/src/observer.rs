@@ -0,0 +1,62 @@
+use crate::graph::document::ConnectionDocument;
+use crate::graph::port::PortType;
+
+/// Change made to the graph or the parameters through the public API of the engine.
+///
+/// Nodes and ports are referred to by their paths, as in the [`crate::GraphDocument`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphEvent {
+  NodeAdded(String),
+  NodeRemoved(String),
+  Connected {
+    port_type: PortType,
+    connection: ConnectionDocument,
+  },
+  Disconnected {
+    port_type: PortType,
+    connection: ConnectionDocument,
+  },
+  ParamChanged {
+    node: String,
+    index: usize,
+    value: f32,
+  },
+}
+
+/// Identifies an observer subscribed with [`crate::Engine::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+type Observer = Box<dyn FnMut(&GraphEvent)>;
+
+#[derive(Default)]
+pub(crate) struct Observers {
+  next_id: usize,
+  observers: Vec<(ObserverId, Observer)>,
+}
+
+impl Observers {
+  pub fn subscribe(&mut self, observer: Observer) -> ObserverId {
+    let id = ObserverId(self.next_id);
+    self.next_id += 1;
+    self.observers.push((id, observer));
+    id
+  }
+
+  /// Remove an observer, returning whether it was subscribed.
+  pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+    let len = self.observers.len();
+    self.observers.retain(|(observer_id, _)| *observer_id != id);
+    self.observers.len() != len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.observers.is_empty()
+  }
+
+  pub fn notify(&mut self, event: &GraphEvent) {
+    for (_, observer) in self.observers.iter_mut() {
+      observer(event);
+    }
+  }
+}
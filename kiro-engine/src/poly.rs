@@ -0,0 +1,327 @@
+use std::ops::Deref;
+
+use crate::error::Result;
+use crate::graph::connection::{ModuleIn, ModuleOut, NodeIn, NodeOut};
+use crate::graph::port::NodeLike;
+use crate::module::Module;
+use crate::node::ProcessorNode;
+use crate::processors::{MixerProcessor, VoiceAllocatorProcessor};
+use crate::{
+  AudioDescriptor, AudioNodeOut, Error, EventsDescriptor, EventsNodeIn, ModuleDescriptor,
+};
+
+/// Events input and audio output of a voice, created by the template of a [`PolyModule`].
+pub struct PolyVoice {
+  pub events_in: EventsNodeIn,
+  pub audio_out: AudioNodeOut,
+}
+
+/// Module replicating a template module a number of times, as for the voices of a synthesizer.
+///
+/// The notes received by its events input are distributed among the voices by a [`VoiceAllocatorProcessor`],
+/// and the audio of the voices is summed into its audio output by a [`MixerProcessor`]:
+///
+/// ```text
+/// events-in -> voice-allocator -> voice-0 .. voice-N -> voice-mixer -> audio-out
+/// ```
+///
+/// Parameters of the voices can be shared, so a single value is set into all of them at once.
+pub struct PolyModule {
+  module: Module,
+  voices: Vec<Module>,
+  allocator: ProcessorNode,
+  mixer: ProcessorNode,
+  shared_parameters: Vec<SharedParameter>,
+}
+
+/// Parameter with the same index in the nodes with the same name of every voice
+struct SharedParameter {
+  id: String,
+  nodes: Vec<ProcessorNode>,
+  index: usize,
+}
+
+impl PolyModule {
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+
+  const ALLOCATOR_NAME: &'static str = "voice-allocator";
+  const MIXER_NAME: &'static str = "voice-mixer";
+
+  /// Create the module inside a parent one, calling `template` to build every voice
+  /// inside its own module, named as in `voice-0`, `voice-1`, ...
+  pub(crate) fn create<F>(
+    parent: &mut Module,
+    name: &str,
+    num_voices: usize,
+    channels: usize,
+    mut template: F,
+  ) -> Result<Self>
+  where
+    F: FnMut(&mut Module, usize) -> Result<PolyVoice>,
+  {
+    let mut module = parent.create_module(name, Self::descriptor(channels))?;
+    let allocator = module.create_processor(
+      Self::ALLOCATOR_NAME,
+      VoiceAllocatorProcessor::new(num_voices),
+    )?;
+    let mixer =
+      module.create_processor(Self::MIXER_NAME, MixerProcessor::new(num_voices, channels))?;
+
+    let mut voices = Vec::with_capacity(num_voices);
+    for index in 0..num_voices {
+      let mut voice =
+        module.create_module(&format!("voice-{}", index), Self::descriptor(channels))?;
+      let PolyVoice {
+        events_in,
+        audio_out,
+      } = template(&mut voice, index)?;
+
+      let mut engine = module.engine.borrow_mut();
+      let graph = &engine.graph;
+      let events_in: NodeIn<EventsDescriptor> = events_in.into();
+      let audio_out: NodeOut<AudioDescriptor> = audio_out.into();
+      let voice_in = ModuleIn(
+        voice.key,
+        graph.module_events_input(voice.key, Self::EVENTS_IN_NAME)?,
+      );
+      let voice_out = ModuleOut(
+        voice.key,
+        graph.module_audio_output(voice.key, Self::AUDIO_OUT_NAME)?,
+      );
+      let allocator_out = graph.node_events_outputs(allocator.node_key)?[index];
+      let mixer_in = graph.node_audio_inputs(mixer.node_key)?[index];
+      engine.connect(allocator_out.to(voice_in), false)?;
+      engine.connect(voice_in.bind(events_in), false)?;
+      engine.connect(audio_out.bind(voice_out), false)?;
+      engine.connect(voice_out.to(mixer_in), false)?;
+      drop(engine);
+
+      voices.push(voice);
+    }
+
+    {
+      let mut engine = module.engine.borrow_mut();
+      let graph = &engine.graph;
+      let events_in = ModuleIn(
+        module.key,
+        graph.module_events_input(module.key, Self::EVENTS_IN_NAME)?,
+      );
+      let audio_out = ModuleOut(
+        module.key,
+        graph.module_audio_output(module.key, Self::AUDIO_OUT_NAME)?,
+      );
+      let allocator_in = graph.node_events_inputs(allocator.node_key)?[0];
+      let mixer_out = graph.node_audio_outputs(mixer.node_key)?[0];
+      engine.connect(events_in.bind(allocator_in), false)?;
+      engine.connect(mixer_out.bind(audio_out), false)?;
+    }
+
+    Ok(Self {
+      module,
+      voices,
+      allocator,
+      mixer,
+      shared_parameters: Vec::new(),
+    })
+  }
+
+  fn descriptor(channels: usize) -> ModuleDescriptor {
+    ModuleDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, channels)])
+      })
+      .with_events_ports(|ports| {
+        ports.static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+      })
+  }
+
+  pub fn module(&self) -> &Module {
+    &self.module
+  }
+
+  pub fn num_voices(&self) -> usize {
+    self.voices.len()
+  }
+
+  /// Module of one of the voices.
+  pub fn voice(&self, index: usize) -> Option<&Module> {
+    self.voices.get(index)
+  }
+
+  pub fn allocator(&self) -> &ProcessorNode {
+    &self.allocator
+  }
+
+  pub fn mixer(&self) -> &ProcessorNode {
+    &self.mixer
+  }
+
+  /// Share a parameter of the node called `node` in every voice,
+  /// returning the index to refer to it when setting its value.
+  pub fn share_parameter(&mut self, id: &str, node: &str, index: usize) -> Result<usize> {
+    let nodes = self
+      .voices
+      .iter()
+      .map(|voice| {
+        let path = {
+          let engine = voice.engine.deref().borrow();
+          format!(
+            "{}/{}",
+            engine.graph.get_module(voice.key)?.full_name(),
+            node
+          )
+        };
+        let node = voice.find_node(node).ok_or(Error::NodeNotFound(path))?;
+        node.existing_parameter_key(index)?;
+        Ok(node)
+      })
+      .collect::<Result<Vec<ProcessorNode>>>()?;
+
+    self.shared_parameters.push(SharedParameter {
+      id: id.to_string(),
+      nodes,
+      index,
+    });
+    Ok(self.shared_parameters.len() - 1)
+  }
+
+  /// Find a shared parameter by its id.
+  pub fn shared_parameter(&self, id: &str) -> Option<usize> {
+    self
+      .shared_parameters
+      .iter()
+      .position(|parameter| parameter.id == id)
+  }
+
+  /// Set the value of a shared parameter into every voice.
+  pub fn set_parameter(&self, shared_index: usize, value: f32) -> Result<()> {
+    let parameter = self
+      .shared_parameters
+      .get(shared_index)
+      .ok_or(Error::SharedParameterNotFound(shared_index))?;
+    for node in parameter.nodes.iter() {
+      node.set_parameter(parameter.index, value)?;
+    }
+    Ok(())
+  }
+
+  /// Remove the module from the graph, together with all its voices.
+  pub fn remove(self) -> Result<()> {
+    self.module.remove()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+  use kiro_midi::messages::{Message, MessageType};
+
+  use super::*;
+  use crate::{
+    Engine, EngineConfig, Event, EventData, NodeDescriptor, ParamDescriptor, Processor,
+    ProcessorContext,
+  };
+
+  /// Outputs its level while playing a note
+  #[derive(Default)]
+  struct NoteProcessor {
+    playing: bool,
+  }
+
+  impl Processor for NoteProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| ports.static_outputs(vec![AudioDescriptor::new("audio-out", 1)]))
+        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-in")]))
+        .with_parameters(vec![ParamDescriptor::new("level").initial(1.0)])
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      for event in context.events_input(0).iter() {
+        if let EventData::Midi(Message {
+          mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+          ..
+        }) = event.data
+        {
+          self.playing = matches!(message, ChannelVoiceMessage::NoteOn { .. });
+        }
+      }
+      let level = if self.playing {
+        context.parameter(0).get()
+      } else {
+        0.0
+      };
+      context.audio_output(0).channel_mut(0).fill(level);
+    }
+  }
+
+  fn note_on(timestamp: u64, note: u8) -> Event {
+    Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(
+        0,
+        0,
+        ChannelVoiceMessage::NoteOn {
+          note,
+          velocity: 1,
+          attr_type: 0,
+          attr_data: 0,
+        },
+      )),
+    }
+  }
+
+  #[test]
+  fn poly_module() {
+    let mut engine = Engine::new(EngineConfig {
+      sample_rate: 1_000,
+      audio_buffer_size: 2,
+      audio_output_channels: 1,
+      ..EngineConfig::default()
+    });
+    let mut poly = engine
+      .create_poly_module("synth", 3, 1, |voice, _index| {
+        let node = voice.create_processor("note", NoteProcessor::default())?;
+        Ok(PolyVoice {
+          events_in: node.events_input("events-in")?,
+          audio_out: node.audio_output("audio-out")?,
+        })
+      })
+      .unwrap();
+    assert_eq!(poly.num_voices(), 3);
+    assert!(engine.find_node("/root/synth/voice-2/note").is_some());
+
+    let level = poly.share_parameter("level", "note", 0).unwrap();
+    assert_eq!(poly.shared_parameter("level"), Some(level));
+    poly.set_parameter(level, 0.25).unwrap();
+    assert!(poly.share_parameter("color", "note", 1).is_err());
+    assert!(poly.share_parameter("level", "osc", 0).is_err());
+
+    {
+      let mut inner = poly.module().engine.borrow_mut();
+      let graph = &mut inner.graph;
+      let synth = poly.module().key;
+      let synth_in = ModuleIn(
+        synth,
+        graph.module_events_input(synth, "events-in").unwrap(),
+      );
+      let synth_out = ModuleOut(
+        synth,
+        graph.module_audio_output(synth, "audio-out").unwrap(),
+      );
+      let events_in = graph.node_events_outputs(graph.get_inputs_node()).unwrap()[0];
+      let outputs_in = graph.node_audio_inputs(graph.get_outputs_node()).unwrap()[0];
+      graph.connect_events(events_in.to(synth_in)).unwrap();
+      graph.connect_audio(synth_out.to(outputs_in)).unwrap();
+    }
+
+    // two notes on at the second block, so they are played by two voices
+    let events = [note_on(2_000_000, 60), note_on(2_000_000, 64)];
+    let outputs = engine.render_offline(4, &events).unwrap();
+    assert_eq!(outputs, vec![vec![0.0, 0.0, 0.5, 0.5]]);
+  }
+}
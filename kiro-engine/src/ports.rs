@@ -5,7 +5,9 @@ use std::rc::Rc;
 use crate::engine::InnerEngine;
 use crate::error::Result;
 use crate::graph::connection::{self, Connection};
-use crate::graph::port::{InputPortKey, OutputPortKey, PortDescriptor};
+use crate::graph::port::{
+  InputPortKey, InputSource, OutputPortKey, PortAccessor, PortDescriptor, Ports,
+};
 use crate::graph::{ModuleKey, NodeKey};
 use crate::{AudioDescriptor, EventsDescriptor};
 
@@ -62,6 +64,19 @@ pub struct ModuleOut<D> {
   pub(crate) port_key: OutputPortKey<D>,
 }
 
+impl<D> ModuleOut<D>
+where
+  D: PortDescriptor,
+  Ports: PortAccessor<D>,
+{
+  /// Connect to the input of a node in the parent module, replacing whatever was connected to it.
+  pub fn connect(&self, input: &NodeIn<D>) -> Result<()> {
+    let output = connection::ModuleOut(self.module_key, self.port_key);
+    let connection = output.to(connection::NodeIn(input.node_key, input.port_key));
+    self.engine.borrow_mut().connect(connection, false)
+  }
+}
+
 impl<D> From<ModuleOut<D>> for connection::ModuleOut<D> {
   fn from(module_out: ModuleOut<D>) -> Self {
     connection::ModuleOut(module_out.module_key, module_out.port_key)
@@ -88,6 +103,42 @@ pub struct NodeOut<D> {
   pub(crate) port_key: OutputPortKey<D>,
 }
 
+impl<D> NodeOut<D>
+where
+  D: PortDescriptor,
+  Ports: PortAccessor<D>,
+{
+  /// Connect to the input of a node in the same module, replacing whatever was connected to it.
+  pub fn connect(&self, input: &NodeIn<D>) -> Result<()> {
+    let output = connection::NodeOut(self.node_key, self.port_key);
+    let connection = output.to(connection::NodeIn(input.node_key, input.port_key));
+    self.engine.borrow_mut().connect(connection, false)
+  }
+
+  /// Connect to the input of a node in the same module, summing it with the other sources of the input.
+  pub fn add(&self, input: &NodeIn<D>) -> Result<()> {
+    let output = connection::NodeOut(self.node_key, self.port_key);
+    let connection = output.to(connection::NodeIn(input.node_key, input.port_key));
+    self.engine.borrow_mut().connect(connection, true)
+  }
+
+  /// Connect to the input of a child module in the same module, replacing whatever was connected to it.
+  pub fn connect_module(&self, input: &ModuleIn<D>) -> Result<()> {
+    let output = connection::NodeOut(self.node_key, self.port_key);
+    let connection = output.to(connection::ModuleIn(input.module_key, input.port_key));
+    self.engine.borrow_mut().connect(connection, false)
+  }
+  /// Remove the connection to the input of a node, leaving the other sources of the input.
+  pub fn disconnect(&self, input: &NodeIn<D>) -> Result<()>
+  where
+    InputSource<D>: PartialEq,
+  {
+    let output = connection::NodeOut(self.node_key, self.port_key);
+    let connection = output.to(connection::NodeIn(input.node_key, input.port_key));
+    self.engine.borrow_mut().disconnect(connection)
+  }
+}
+
 impl<D> From<NodeOut<D>> for connection::NodeOut<D> {
   fn from(node_out: NodeOut<D>) -> Self {
     connection::NodeOut(node_out.node_key, node_out.port_key)
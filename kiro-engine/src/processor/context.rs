@@ -1,23 +1,34 @@
 use crate::ParamValue;
 use std::sync::Arc;
 
+use kiro_midi::TimestampNanos;
+use kiro_time::SampleRate;
+
 use crate::processor::ports::audio::AudioPort;
 use crate::processor::ports::events::EventsPort;
+use crate::processor::ports::param::ParamRenderPort;
 use crate::processor::ports::{Input, Output};
+use crate::rendering::buffers::events::Event;
+use crate::rendering::transport::Transport;
 
 pub struct ProcessorContext<'a> {
   num_samples: usize,
   parameters: &'a [Arc<ParamValue>],
+  parameter_ports: &'a [ParamRenderPort],
   audio_inputs: &'a [AudioPort<Input>],
   audio_outputs: &'a [AudioPort<Output>],
   events_inputs: &'a [EventsPort<Input>],
   events_outputs: &'a [EventsPort<Output>],
+  block_timestamp: TimestampNanos,
+  sample_rate: SampleRate,
+  transport: Transport,
 }
 
 impl<'a> ProcessorContext<'a> {
   pub fn new(
     num_samples: usize,
     parameters: &'a [Arc<ParamValue>],
+    parameter_ports: &'a [ParamRenderPort],
     audio_inputs: &'a [AudioPort<Input>],
     audio_outputs: &'a [AudioPort<Output>],
     events_inputs: &'a [EventsPort<Input>],
@@ -26,17 +37,60 @@ impl<'a> ProcessorContext<'a> {
     Self {
       num_samples,
       parameters,
+      parameter_ports,
       audio_inputs,
       audio_outputs,
       events_inputs,
       events_outputs,
+      block_timestamp: 0,
+      sample_rate: SampleRate::default(),
+      transport: Transport::new(),
     }
   }
 
+  /// Set the timestamp of the first sample of the block, and the sample rate,
+  /// to place the events within the block.
+  #[must_use]
+  pub fn with_timing(mut self, block_timestamp: TimestampNanos, sample_rate: SampleRate) -> Self {
+    self.block_timestamp = block_timestamp;
+    self.sample_rate = sample_rate;
+    self
+  }
+
+  /// Set the state of the transport at the beginning of the block.
+  #[must_use]
+  pub fn with_transport(mut self, transport: Transport) -> Self {
+    self.transport = transport;
+    self
+  }
+
+  /// Tempo, time signature, playhead position and play state at the beginning of the block.
+  pub fn transport(&self) -> &Transport {
+    &self.transport
+  }
+
   pub fn num_samples(&self) -> usize {
     self.num_samples
   }
 
+  /// Timestamp of the first sample of the block.
+  pub fn block_timestamp(&self) -> TimestampNanos {
+    self.block_timestamp
+  }
+
+  pub fn sample_rate(&self) -> SampleRate {
+    self.sample_rate
+  }
+
+  /// Sample of the block where an event happens.
+  ///
+  /// Events from before the block happen at its first sample, and the ones from after it at its last sample.
+  pub fn event_offset(&self, event: &Event) -> usize {
+    event
+      .sample_offset(self.block_timestamp, self.sample_rate)
+      .min(self.num_samples.saturating_sub(1))
+  }
+
   pub fn num_parameters(&self) -> usize {
     self.parameters.len()
   }
@@ -45,6 +99,15 @@ impl<'a> ProcessorContext<'a> {
     &self.parameters[index]
   }
 
+  pub(crate) fn parameters(&self) -> &'a [Arc<ParamValue>] {
+    self.parameters
+  }
+
+  /// Values of a parameter for every sample of the block, including any audio rate modulation.
+  pub fn parameter_buffer(&self, index: usize) -> &'a [f32] {
+    self.parameter_ports[index].as_slice()
+  }
+
   pub fn num_audio_inputs(&self) -> usize {
     self.audio_inputs.len()
   }
@@ -69,6 +132,20 @@ impl<'a> ProcessorContext<'a> {
     &self.events_inputs[index]
   }
 
+  pub(crate) fn events_inputs(&self) -> &'a [EventsPort<Input>] {
+    self.events_inputs
+  }
+
+  /// Events of an input together with the sample of the block where they happen, ordered by timestamp.
+  pub fn events_input_with_offsets(
+    &self,
+    index: usize,
+  ) -> impl Iterator<Item = (usize, &'a Event)> + '_ {
+    self.events_inputs[index]
+      .iter()
+      .map(move |event| (self.event_offset(event), event))
+  }
+
   pub fn num_events_outputs(&self) -> usize {
     self.events_outputs.len()
   }
@@ -76,4 +153,38 @@ impl<'a> ProcessorContext<'a> {
   pub fn events_output(&self, index: usize) -> &'a EventsPort<Output> {
     &self.events_outputs[index]
   }
+
+  pub(crate) fn events_outputs(&self) -> &'a [EventsPort<Output>] {
+    self.events_outputs
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rendering::buffers::events::{EventData, EventsBuffer, TransportMessage};
+  use crate::rendering::owned_data::OwnedData;
+
+  #[test]
+  fn events_with_offsets() {
+    let mut buffers = OwnedData::new();
+    let key = buffers.add(EventsBuffer::with_capacity(4));
+    let buffer = buffers.get(key).unwrap();
+    for timestamp in [500_000, 2_000_000, 3_999_999, 9_000_000] {
+      let event = Event {
+        timestamp: 10_000_000 + timestamp,
+        data: EventData::Transport(TransportMessage::Start),
+      };
+      buffer.get_mut().push(event).unwrap();
+    }
+    let events_inputs = [EventsPort::new(buffer)];
+
+    let context = ProcessorContext::new(4, &[], &[], &[], &[], &events_inputs, &[])
+      .with_timing(10_000_000, 1_000);
+    let offsets = context
+      .events_input_with_offsets(0)
+      .map(|(offset, _)| offset)
+      .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0, 2, 3, 3]);
+  }
 }
@@ -1,13 +1,88 @@
 pub(crate) mod context;
 pub mod ports;
 
+use std::any::Any;
 use std::fmt::Formatter;
+use std::sync::Arc;
+
+use kiro_time::SampleRate;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
 
 use crate::graph::NodeDescriptor;
 pub use context::ProcessorContext;
 
 pub type BoxedProcessor = Box<dyn Processor + 'static>;
 
+/// The errors are kept as text, so they can be sent from the renderer to every subscriber.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum StateError {
+  #[error("Failed to encode the state: {0}")]
+  Encode(String),
+
+  #[error("Failed to decode the state: {0}")]
+  Decode(String),
+
+  #[error("Invalid state: {0}")]
+  Invalid(String),
+
+  #[error("The state is not for this processor")]
+  Mismatch,
+
+  #[error("The processor panicked")]
+  Panicked,
+}
+
+/// Encode the state of a processor into bytes, see [`StateSnapshot::encode`].
+pub fn encode_state<T: Serialize>(state: &T) -> Result<Vec<u8>, StateError> {
+  serde_json::to_vec(state).map_err(|err| StateError::Encode(err.to_string()))
+}
+
+/// Decode the state of a processor from bytes, see [`StateSnapshot::decode`].
+pub fn decode_state<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StateError> {
+  serde_json::from_slice(bytes).map_err(|err| StateError::Decode(err.to_string()))
+}
+
+/// State of a processor shared with the controller, see [`Processor::state`].
+pub type SharedState = Arc<dyn StateSnapshot>;
+
+/// Internal state of a processor that is not kept in the parameters, such as a tuning,
+/// the path of a sample or a random seed, so it can be persisted in projects and presets.
+///
+/// It never changes once created, so the processor and the controller can share it,
+/// and it is only encoded and decoded by the controller, outside of the audio context.
+pub trait StateSnapshot: Send + Sync + 'static {
+  /// Encode the state, as with [`encode_state`].
+  fn encode(&self) -> Result<Vec<u8>, StateError>;
+
+  /// Decode a state of the same kind as this one, as with [`decode_state`].
+  fn decode(&self, state: &[u8]) -> Result<SharedState, StateError>;
+
+  /// Let the processors recover the type of their state, see [`swap_state`].
+  fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+/// Replace the state of a processor by another one of the same type, see [`Processor::set_state`].
+///
+/// The previous state is left in place of the new one, so it is dropped outside of the audio context,
+/// and only the counts of the `Arc`s change, so it doesn't allocate.
+pub fn swap_state<T: StateSnapshot>(
+  current: &mut Arc<T>,
+  state: &mut SharedState,
+) -> Result<(), StateError> {
+  if !state.clone().into_any().is::<T>() {
+    return Err(StateError::Mismatch);
+  }
+  let previous: SharedState = current.clone();
+  let next = std::mem::replace(state, previous);
+  *current = next
+    .into_any()
+    .downcast::<T>()
+    .map_err(|_| StateError::Mismatch)?;
+  Ok(())
+}
+
 pub trait Processor {
   /// Return the static node descriptor.
   fn static_descriptor() -> NodeDescriptor
@@ -26,7 +101,30 @@ pub trait Processor {
     Self::static_descriptor()
   }
 
-  // TODO fn prepare(&mut self, );
+  /// Prepare for rendering with the given sample rate and maximum number of samples per block.
+  /// Called before the processor is placed into a render plan, and whenever the sample rate changes.
+  /// Not running in the audio context, so it is fine to allocate here.
+  fn prepare(&mut self, _sample_rate: SampleRate, _max_block: usize) {}
+
+  /// Clear any internal state (delay lines, envelopes, phases, ...), as if nothing was rendered before.
+  /// Called after [`Processor::prepare`].
+  fn reset(&mut self) {}
+
+  /// Internal state that is not kept in the parameters, see [`StateSnapshot`].
+  ///
+  /// It is taken by the controller when the processor is added, and after that it only changes
+  /// through [`Processor::set_state`], so the controller can save it without asking the renderer.
+  /// Not running in the audio context.
+  fn state(&self) -> Option<SharedState> {
+    None
+  }
+
+  /// Replace the internal state by one decoded by the controller from the current one,
+  /// leaving the previous one in its place, see [`swap_state`].
+  /// Running in the audio context between two blocks, so it must neither allocate nor drop the previous state.
+  fn set_state(&mut self, _state: &mut SharedState) -> Result<(), StateError> {
+    Err(StateError::Mismatch)
+  }
 
   /// Render the next period.
   /// Running in the audio context.
@@ -27,16 +27,16 @@ impl<IO> AudioRenderBuffer<IO> {
   pub fn is_empty(&self) -> bool {
     self.num_samples == 0
   }
+
+  pub fn as_slice(&self) -> &[f32] {
+    self.buffer.deref().as_slice()[0..self.num_samples].as_ref()
+  }
 }
 
 impl AudioRenderBuffer<Input> {
   pub fn iter(&self) -> impl Iterator<Item = &f32> {
     self.buffer.deref().iter().take(self.num_samples)
   }
-
-  pub fn as_slice(&self) -> &[f32] {
-    self.buffer.deref().as_slice()[0..self.num_samples].as_ref()
-  }
 }
 
 impl AudioRenderBuffer<Output> {
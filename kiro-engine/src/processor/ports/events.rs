@@ -1,12 +1,16 @@
 use std::marker::PhantomData;
 
-use crate::processor::ports::Output;
-use crate::rendering::buffers::events::{EventsBuffer, Iter};
+use kiro_midi::TimestampNanos;
+
+use crate::processor::ports::{Input, Output};
+use crate::rendering::buffers::events::{Event, EventsBuffer, Iter};
 use crate::rendering::owned_data::Ref;
 
 #[derive(Debug)]
 pub struct EventsPort<IO> {
   buffer: Ref<EventsBuffer>,
+  /// Events emitted for later blocks
+  scheduled: Option<Ref<EventsBuffer>>,
   _mode: PhantomData<IO>,
 }
 
@@ -14,6 +18,7 @@ impl<IO> EventsPort<IO> {
   pub fn new(buffer: Ref<EventsBuffer>) -> Self {
     Self {
       buffer,
+      scheduled: None,
       _mode: PhantomData,
     }
   }
@@ -28,8 +33,46 @@ impl<IO> EventsPort<IO> {
   }
 }
 
+impl EventsPort<Input> {
+  /// Sort the events received out of order before they reach the processor.
+  pub(crate) fn sort(&self) {
+    self.buffer.get_mut().sort();
+  }
+}
+
 impl EventsPort<Output> {
   pub fn buffer_mut(&mut self) -> &mut EventsBuffer {
     self.buffer.get_mut()
   }
+
+  /// Emit an event from a processor, returning it back if the buffer is full.
+  pub fn push(&self, event: Event) -> Result<(), Event> {
+    self.buffer.get_mut().push(event)
+  }
+
+  /// Hold the events scheduled for later blocks in a buffer that outlives the render plans.
+  pub(crate) fn with_scheduled(mut self, scheduled: Ref<EventsBuffer>) -> Self {
+    self.scheduled = Some(scheduled);
+    self
+  }
+
+  /// Emit an event from a processor at any later time, as for arpeggiators or sequencers,
+  /// returning it back if there is no room to hold it.
+  ///
+  /// The event reaches the buffer once the block including its timestamp is rendered.
+  pub fn schedule(&self, event: Event) -> Result<(), Event> {
+    match self.scheduled.as_ref() {
+      Some(scheduled) => scheduled.get_mut().push(event),
+      None => self.push(event),
+    }
+  }
+
+  /// Move the scheduled events earlier than `timestamp` into the buffer.
+  pub(crate) fn deliver_scheduled(&self, timestamp: TimestampNanos) {
+    if let Some(scheduled) = self.scheduled.as_ref() {
+      scheduled
+        .get_mut()
+        .move_earlier(timestamp, self.buffer.get_mut());
+    }
+  }
 }
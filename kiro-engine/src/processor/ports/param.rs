@@ -3,13 +3,24 @@ use std::sync::Arc;
 
 use crate::key_gen::Key;
 use crate::rendering::buffers::audio::AudioBuffer;
+use crate::rendering::buffers::events::AutomationCurve;
 use crate::rendering::owned_data::Ref;
 use crate::ParamValue;
 
+/// Audio signal modulating a parameter, as in `base + depth * amount` clamped to `min..=max`.
+#[derive(Debug, Clone)]
+pub struct ParamModulation {
+  pub buffer: Ref<AudioBuffer>,
+  pub depth: f32,
+  pub min: f32,
+  pub max: f32,
+}
+
 #[derive(Debug, Clone)]
 pub enum ParamData {
   FromValue(Arc<ParamValue>, Ref<AudioBuffer>),
   FromOutput(Ref<AudioBuffer>),
+  Modulated(Arc<ParamValue>, ParamModulation, Ref<AudioBuffer>),
 }
 
 impl ParamData {
@@ -21,34 +32,114 @@ impl ParamData {
     Self::FromOutput(buffer)
   }
 
+  pub fn modulated(
+    value: Arc<ParamValue>,
+    modulation: ParamModulation,
+    slice_buffer: Ref<AudioBuffer>,
+  ) -> Self {
+    Self::Modulated(value, modulation, slice_buffer)
+  }
+
   pub fn allocated_buffer_key(&self) -> Option<Key<AudioBuffer>> {
     match self {
       Self::FromValue(_, buffer) => Some(buffer.key),
       Self::FromOutput(_) => None,
+      Self::Modulated(_, _, buffer) => Some(buffer.key),
     }
   }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AutomationPoint {
+  offset: usize,
+  value: f32,
+  curve: AutomationCurve,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParamRenderPort {
   num_samples: usize,
   data: ParamData,
+  automation_start: f32,
+  automation: Vec<AutomationPoint>,
 }
 
 impl ParamRenderPort {
+  /// Maximum number of automation points per block, so no allocations happen while rendering.
+  pub const AUTOMATION_CAPACITY: usize = 64;
+
   pub fn new(data: ParamData) -> Self {
     Self {
       num_samples: 0,
       data,
+      automation_start: 0.0,
+      automation: Vec::with_capacity(Self::AUTOMATION_CAPACITY),
     }
   }
 
+  /// Prepare the port for a new block, discarding the automation of the previous one.
   pub(crate) fn set_num_samples(&mut self, num_samples: usize) {
     self.num_samples = num_samples;
+    self.automation.clear();
+  }
+
+  /// Schedule the parameter to reach a value at a sample offset of the current block.
+  ///
+  /// The parameter value is updated to the one of the latest point,
+  /// so the automation persists in the following blocks.
+  /// Returns whether the point could be scheduled.
+  pub(crate) fn automate(&mut self, offset: usize, value: f32, curve: AutomationCurve) -> bool {
+    let param_value = match &self.data {
+      ParamData::FromValue(param_value, _) | ParamData::Modulated(param_value, _, _) => param_value,
+      ParamData::FromOutput(_) => return false,
+    };
+
+    if self.automation.len() == self.automation.capacity() {
+      return false;
+    }
+
+    if self.automation.is_empty() {
+      self.automation_start = param_value.get();
+    }
+    let offset = offset.min(self.num_samples);
+    let index = self
+      .automation
+      .partition_point(|point| point.offset <= offset);
+    self.automation.insert(
+      index,
+      AutomationPoint {
+        offset,
+        value,
+        curve,
+      },
+    );
+    if let Some(last_point) = self.automation.last() {
+      param_value.set(last_point.value);
+    }
+    true
+  }
+
+  /// Value for the last sample of the block, including automation and modulation,
+  /// or `None` when the block is empty.
+  pub fn last_value(&self) -> Option<f32> {
+    let last = self.num_samples.checked_sub(1)?;
+    match &self.data {
+      ParamData::FromValue(value, _) => Some(value.get()),
+      ParamData::FromOutput(buffer) => buffer.deref().as_slice().get(last).cloned(),
+      ParamData::Modulated(value, modulation, _) => {
+        let amount = modulation.buffer.deref().as_slice().get(last)?;
+        Some((value.get() + modulation.depth * amount).clamp(modulation.min, modulation.max))
+      }
+    }
   }
 
   pub fn iter(&self) -> Iter {
     match &self.data {
+      ParamData::FromValue(..) if !self.automation.is_empty() => Iter::Buffer {
+        iter: self.as_slice().iter().take(self.num_samples),
+        last_value: f32::MIN,
+        updated: true,
+      },
       ParamData::FromValue(value, _slice_buffer) => Iter::Value {
         value: value.get(),
         len: self.num_samples,
@@ -62,18 +153,55 @@ impl ParamRenderPort {
           updated: true,
         }
       }
+      ParamData::Modulated(..) => Iter::Buffer {
+        iter: self.as_slice().iter().take(self.num_samples),
+        last_value: f32::MIN,
+        updated: true,
+      },
     }
   }
 
   pub fn as_slice(&self) -> &[f32] {
     match &self.data {
       ParamData::FromValue(value, slice_buffer) => {
-        slice_buffer
-          .get_mut()
-          .fill_first(self.num_samples, value.get());
+        let slice = &mut slice_buffer.get_mut().as_mut_slice()[0..self.num_samples];
+        self.fill_values(slice, value);
         &slice_buffer.deref().as_slice()[0..self.num_samples]
       }
       ParamData::FromOutput(buffer) => &buffer.deref().as_slice()[0..self.num_samples],
+      ParamData::Modulated(value, modulation, slice_buffer) => {
+        let amounts = modulation.buffer.deref().as_slice();
+        let slice = &mut slice_buffer.get_mut().as_mut_slice()[0..self.num_samples];
+        self.fill_values(slice, value);
+        for (value, amount) in slice.iter_mut().zip(amounts.iter()) {
+          *value = (*value + modulation.depth * amount).clamp(modulation.min, modulation.max);
+        }
+        &slice_buffer.deref().as_slice()[0..self.num_samples]
+      }
+    }
+  }
+
+  fn fill_values(&self, slice: &mut [f32], value: &ParamValue) {
+    if self.automation.is_empty() {
+      slice.fill(value.get());
+    } else {
+      let mut value = self.automation_start;
+      let mut position = 0;
+      for point in self.automation.iter() {
+        let segment = &mut slice[position..point.offset];
+        match point.curve {
+          AutomationCurve::Step => segment.fill(value),
+          AutomationCurve::Linear => {
+            let step = (point.value - value) / segment.len() as f32;
+            for (index, sample) in segment.iter_mut().enumerate() {
+              *sample = value + step * index as f32;
+            }
+          }
+        }
+        value = point.value;
+        position = point.offset;
+      }
+      slice[position..].fill(value);
     }
   }
 }
@@ -176,3 +304,77 @@ impl<'a> Iterator for Iter<'a> {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rendering::owned_data::OwnedData;
+
+  #[test]
+  fn modulated_slice() {
+    let mut buffers = OwnedData::new();
+    let mut amounts = AudioBuffer::with_capacity(4);
+    amounts
+      .as_mut_slice()
+      .copy_from_slice(&[0.0, 0.5, 1.0, -1.0]);
+    let amounts_key = buffers.add(amounts);
+    let slice_key = buffers.add(AudioBuffer::with_capacity(4));
+    let amounts = buffers.get(amounts_key).unwrap();
+    let slice = buffers.get(slice_key).unwrap();
+
+    let value = Arc::new(ParamValue::new(0.5));
+    let modulation = ParamModulation {
+      buffer: amounts,
+      depth: 0.5,
+      min: 0.0,
+      max: 1.0,
+    };
+    let mut port = ParamRenderPort::new(ParamData::modulated(value.clone(), modulation, slice));
+    port.set_num_samples(3);
+
+    assert_eq!(port.as_slice(), &[0.5, 0.75, 1.0]);
+
+    value.set(0.25);
+    port.set_num_samples(4);
+    assert_eq!(
+      port.iter().collect::<Vec<f32>>(),
+      vec![0.25, 0.5, 0.75, 0.0]
+    );
+  }
+
+  #[test]
+  fn automated_slice() {
+    let mut buffers = OwnedData::new();
+    let slice_key = buffers.add(AudioBuffer::with_capacity(8));
+    let slice = buffers.get(slice_key).unwrap();
+
+    let value = Arc::new(ParamValue::new(0.0));
+    let mut port = ParamRenderPort::new(ParamData::from_value(value.clone(), slice));
+    port.set_num_samples(8);
+
+    // scheduled out of order
+    assert!(port.automate(6, 0.5, AutomationCurve::Step));
+    assert!(port.automate(4, 1.0, AutomationCurve::Linear));
+
+    assert_eq!(port.as_slice(), &[0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 0.5, 0.5]);
+    assert_eq!(value.get(), 0.5);
+
+    // the automation only lasts for one block
+    port.set_num_samples(2);
+    assert_eq!(port.iter().collect::<Vec<f32>>(), vec![0.5, 0.5]);
+  }
+
+  #[test]
+  fn value_slice() {
+    let mut buffers = OwnedData::new();
+    let slice_key = buffers.add(AudioBuffer::with_capacity(4));
+    let slice = buffers.get(slice_key).unwrap();
+    let mut port = ParamRenderPort::new(ParamData::from_value(
+      Arc::new(ParamValue::new(0.25)),
+      slice,
+    ));
+    port.set_num_samples(2);
+
+    assert_eq!(port.as_slice(), &[0.25, 0.25]);
+  }
+}
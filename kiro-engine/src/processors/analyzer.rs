@@ -0,0 +1,186 @@
+use kiro_dsp::fft::{SpectrumAnalyzer, Window};
+
+use crate::processor::ProcessorContext;
+use crate::rendering::triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter};
+use crate::{AudioDescriptor, NodeDescriptor, Processor};
+
+/// Latest samples and spectrum of the input of an [`AnalyzerProcessor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+  /// Latest samples of the mix of the channels, the oldest first
+  pub samples: Vec<f32>,
+  /// Magnitude of every bin of the spectrum, from 0 Hz up to the Nyquist frequency
+  pub spectrum: Vec<f32>,
+}
+
+impl Analysis {
+  fn new(size: usize) -> Self {
+    Self {
+      samples: vec![0.0; size],
+      spectrum: vec![0.0; size / 2 + 1],
+    }
+  }
+}
+
+/// Reads the latest samples published by an [`AnalyzerProcessor`] from any thread,
+/// computing their spectrum there rather than in the audio thread.
+pub struct AnalysisReader {
+  samples: TripleBufferReader<Vec<f32>>,
+  // Hann window, to keep the leakage between the bins low
+  analyzer: SpectrumAnalyzer<f32>,
+  analysis: Analysis,
+}
+
+impl AnalysisReader {
+  pub fn analysis(&mut self) -> &Analysis {
+    let samples = self.samples.read();
+    self.analysis.samples.copy_from_slice(samples);
+    self
+      .analyzer
+      .magnitude_spectrum(samples.iter().copied(), &mut self.analysis.spectrum);
+    &self.analysis
+  }
+}
+
+/// Captures the latest samples of its input for user interfaces to show a scope or a spectrum,
+/// without touching the audio thread.
+///
+/// The samples are published after every block, and the spectrum is computed by the [`AnalysisReader`].
+pub struct AnalyzerProcessor {
+  channels: usize,
+  /// Circular buffer with the latest samples
+  history: Vec<f32>,
+  position: usize,
+  writer: TripleBufferWriter<Vec<f32>>,
+}
+
+impl AnalyzerProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+
+  pub const DEFAULT_SIZE: usize = 1024;
+
+  /// Create an analyzer of some number of samples, rounded up to a power of two,
+  /// together with the reader for its analysis.
+  pub fn new(channels: usize, size: usize) -> (Self, AnalysisReader) {
+    let size = size.max(2).next_power_of_two();
+    let (writer, samples) = triple_buffer(vec![0.0; size]);
+    let processor = Self {
+      channels,
+      history: vec![0.0; size],
+      position: 0,
+      writer,
+    };
+    let reader = AnalysisReader {
+      samples,
+      analyzer: SpectrumAnalyzer::new(size, Window::Hann),
+      analysis: Analysis::new(size),
+    };
+    (processor, reader)
+  }
+
+  pub fn size(&self) -> usize {
+    self.history.len()
+  }
+
+  fn descriptor_with_channels(channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new().with_audio_ports(|ports| {
+      ports.static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, channels)])
+    })
+  }
+}
+
+impl Processor for AnalyzerProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(self.channels)
+  }
+
+  fn reset(&mut self) {
+    self.history.iter_mut().for_each(|sample| *sample = 0.0);
+    self.position = 0;
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let size = self.history.len();
+    // only the latest samples of a block longer than the history are kept
+    let num_samples = context.num_samples();
+    let skip = num_samples.saturating_sub(size);
+    let count = num_samples - skip;
+    let gain = (input.len().max(1) as f32).recip();
+    for offset in 0..count {
+      self.history[(self.position + offset) % size] = 0.0;
+    }
+    for channel in 0..input.len() {
+      let samples = input.channel(channel);
+      for (offset, sample) in samples.iter().skip(skip).take(count).enumerate() {
+        self.history[(self.position + offset) % size] += sample * gain;
+      }
+    }
+    self.position = (self.position + count) % size;
+
+    // the oldest samples follow the position of the next one to capture
+    let (newest, oldest) = self.history.split_at(self.position);
+    let samples = self.writer.write();
+    let (first, second) = samples.split_at_mut(oldest.len());
+    first.copy_from_slice(oldest);
+    second.copy_from_slice(newest);
+    self.writer.publish();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::ProcessorHarness;
+
+  #[test]
+  fn capture_the_samples_and_the_spectrum() {
+    let (analyzer, mut reader) = AnalyzerProcessor::new(2, 1000);
+    assert_eq!(analyzer.size(), 1024);
+    let mut harness = ProcessorHarness::new(analyzer)
+      .with_sample_rate(1024)
+      .with_block_size(256);
+
+    // a sine in the bin 64, only in the left channel
+    let sine = (0..2048)
+      .map(|index| (2.0 * std::f32::consts::PI * 64.0 * index as f32 / 1024.0).sin())
+      .collect::<Vec<_>>();
+    harness.feed_audio(AnalyzerProcessor::AUDIO_IN_INDEX, 0, &sine);
+
+    harness.render(1);
+    let analysis = reader.analysis();
+    assert_eq!(
+      &analysis.samples[768..],
+      &sine[..256].iter().map(|s| s / 2.0).collect::<Vec<_>>()[..]
+    );
+    // the first samples are already in the spectrum, even if blurred by the silence before them
+    assert!(analysis.spectrum[64] > 0.0);
+
+    harness.render(4);
+    let analysis = reader.analysis();
+    let peak = analysis
+      .spectrum
+      .iter()
+      .enumerate()
+      .fold((0, 0.0), |peak, (bin, magnitude)| {
+        if *magnitude > peak.1 {
+          (bin, *magnitude)
+        } else {
+          peak
+        }
+      });
+    assert_eq!(peak.0, 64);
+    assert!((peak.1 - 0.5).abs() < 0.01, "{}", peak.1);
+  }
+}
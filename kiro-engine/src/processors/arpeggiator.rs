@@ -0,0 +1,451 @@
+use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+use kiro_midi::messages::{Message, MessageType};
+use kiro_midi::TimestampNanos;
+
+use crate::processor::ports::events::EventsPort;
+use crate::processor::ports::Output;
+use crate::processor::ProcessorContext;
+use crate::{
+  Event, EventData, EventsDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor,
+};
+
+/// Order in which an [`ArpeggiatorProcessor`] plays the held notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpeggiatorMode {
+  Up,
+  Down,
+  /// Up and then down, without repeating the notes at the ends
+  UpDown,
+  Random,
+}
+
+impl ArpeggiatorMode {
+  pub const ALL: [ArpeggiatorMode; 4] = [
+    ArpeggiatorMode::Up,
+    ArpeggiatorMode::Down,
+    ArpeggiatorMode::UpDown,
+    ArpeggiatorMode::Random,
+  ];
+
+  pub fn from_value(value: f32) -> Self {
+    let index = value.round().max(0.0) as usize;
+    Self::ALL[index.min(Self::ALL.len() - 1)]
+  }
+
+  pub fn value(&self) -> f32 {
+    Self::ALL.iter().position(|mode| mode == self).unwrap_or(0) as f32
+  }
+
+  pub fn label(&self) -> &'static str {
+    match self {
+      ArpeggiatorMode::Up => "up",
+      ArpeggiatorMode::Down => "down",
+      ArpeggiatorMode::UpDown => "up-down",
+      ArpeggiatorMode::Random => "random",
+    }
+  }
+}
+
+/// Plays the held notes one after the other at a rate synced to the tempo of the transport,
+/// over a range of octaves, and with a gate length as a fraction of every step.
+///
+/// It receives the notes through its events input and sends the arpeggio to its events output,
+/// together with any other event it receives, so it can be placed in front of any instrument.
+pub struct ArpeggiatorProcessor {
+  /// Notes being held ordered by pitch, together with their velocity
+  held: Vec<(u8, u16)>,
+  /// Group and channel of the last note received, where the arpeggio is sent
+  group: u8,
+  channel: u8,
+  /// Number of steps played since the first note was held
+  step: usize,
+  /// Samples left until the next step
+  countdown: f64,
+  /// Note being played together with the samples left until it is released
+  playing: Option<(u8, f64)>,
+  random: u32,
+}
+
+impl ArpeggiatorProcessor {
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const EVENTS_IN_INDEX: usize = 0;
+  pub const EVENTS_OUT_NAME: &'static str = "events-out";
+  pub const EVENTS_OUT_INDEX: usize = 0;
+
+  pub const MODE_INDEX: usize = 0;
+  pub const OCTAVES_INDEX: usize = 1;
+  pub const DIVISION_INDEX: usize = 2;
+  /// Length of the notes as a fraction of the steps
+  pub const GATE_INDEX: usize = 3;
+
+  pub const MAX_OCTAVES: usize = 4;
+
+  /// Note lengths of the steps, together with their duration in beats
+  pub const DIVISIONS: [(&'static str, f64); 6] = [
+    ("1/32", 0.125),
+    ("1/16", 0.25),
+    ("1/8T", 1.0 / 3.0),
+    ("1/8", 0.5),
+    ("1/4", 1.0),
+    ("1/2", 2.0),
+  ];
+
+  const MAX_HELD_NOTES: usize = 128;
+  const RANDOM_SEED: u32 = 0x9e37_79b9;
+
+  pub fn new() -> Self {
+    Self {
+      held: Vec::with_capacity(Self::MAX_HELD_NOTES),
+      group: 0,
+      channel: 0,
+      step: 0,
+      countdown: 0.0,
+      playing: None,
+      random: Self::RANDOM_SEED,
+    }
+  }
+
+  /// Duration of the steps in samples, for the division and the tempo of the transport.
+  fn step_samples(context: &ProcessorContext) -> f64 {
+    let division = context
+      .parameter(Self::DIVISION_INDEX)
+      .get()
+      .round()
+      .max(0.0) as usize;
+    let (_, beats) = Self::DIVISIONS[division.min(Self::DIVISIONS.len() - 1)];
+    let seconds_per_beat = 60.0 / f64::from(context.transport().tempo());
+    beats * seconds_per_beat * f64::from(context.sample_rate())
+  }
+
+  /// Keep track of the held notes, and let through any other event.
+  fn receive(&mut self, event: &Event, output: &EventsPort<Output>) {
+    if let EventData::Midi(Message {
+      group,
+      mtype: MessageType::ChannelVoice(ChannelVoice { channel, message }),
+    }) = event.data
+    {
+      match message {
+        ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+          if self.held.is_empty() {
+            // a new arpeggio starts right away
+            self.step = 0;
+            self.countdown = 0.0;
+          }
+          self.group = group;
+          self.channel = channel;
+          match self.held.binary_search_by_key(&note, |(held, _)| *held) {
+            Ok(index) => self.held[index].1 = velocity,
+            Err(index) if self.held.len() < Self::MAX_HELD_NOTES => {
+              self.held.insert(index, (note, velocity))
+            }
+            Err(_) => {}
+          }
+          return;
+        }
+        ChannelVoiceMessage::NoteOff { note, .. } => {
+          self.held.retain(|(held, _)| *held != note);
+          return;
+        }
+        _ => {}
+      }
+    }
+    output.push(*event).ok();
+  }
+
+  /// Note and velocity for the next step of the arpeggio.
+  fn next_note(&mut self, mode: ArpeggiatorMode, octaves: usize) -> (u8, u16) {
+    let num_held = self.held.len();
+    let len = num_held * octaves;
+    let index = match mode {
+      ArpeggiatorMode::Up => self.step % len,
+      ArpeggiatorMode::Down => len - 1 - self.step % len,
+      ArpeggiatorMode::UpDown if len > 1 => {
+        let period = 2 * len - 2;
+        let position = self.step % period;
+        if position < len {
+          position
+        } else {
+          period - position
+        }
+      }
+      ArpeggiatorMode::UpDown => 0,
+      ArpeggiatorMode::Random => self.next_random() as usize % len,
+    };
+    self.step = self.step.wrapping_add(1);
+    let (note, velocity) = self.held[index % num_held];
+    let note = usize::from(note) + 12 * (index / num_held);
+    (note.min(127) as u8, velocity)
+  }
+
+  /// Next value of a xorshift generator.
+  fn next_random(&mut self) -> u32 {
+    let mut x = self.random;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.random = x;
+    x
+  }
+
+  fn note_event(&self, timestamp: TimestampNanos, note: u8, velocity: u16, on: bool) -> Event {
+    let message = if on {
+      ChannelVoiceMessage::NoteOn {
+        note,
+        velocity,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    } else {
+      ChannelVoiceMessage::NoteOff {
+        note,
+        velocity: 0,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    };
+    Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(self.group, self.channel, message)),
+    }
+  }
+}
+
+impl Default for ArpeggiatorProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for ArpeggiatorProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_events_ports(|ports| {
+        ports
+          .static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+          .static_outputs(vec![EventsDescriptor::new(Self::EVENTS_OUT_NAME)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("mode").values(
+          ArpeggiatorMode::ALL
+            .iter()
+            .map(|mode| mode.label())
+            .collect::<Vec<_>>(),
+        ),
+        ParamDescriptor::new("octaves")
+          .initial(1.0)
+          .min(1.0)
+          .max(Self::MAX_OCTAVES as f32)
+          .steps(Self::MAX_OCTAVES - 1),
+        ParamDescriptor::new("division")
+          .values(
+            Self::DIVISIONS
+              .iter()
+              .map(|(label, _)| *label)
+              .collect::<Vec<_>>(),
+          )
+          .initial(1.0),
+        ParamDescriptor::new("gate")
+          .initial(0.5)
+          .min(0.05)
+          .max(1.0)
+          .unit(ParamUnit::Percent),
+      ])
+  }
+
+  fn reset(&mut self) {
+    self.held.clear();
+    self.step = 0;
+    self.countdown = 0.0;
+    self.playing = None;
+    self.random = Self::RANDOM_SEED;
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let mode = ArpeggiatorMode::from_value(context.parameter(Self::MODE_INDEX).get());
+    let octaves = (context
+      .parameter(Self::OCTAVES_INDEX)
+      .get()
+      .round()
+      .max(1.0) as usize)
+      .min(Self::MAX_OCTAVES);
+    let gate = f64::from(context.parameter(Self::GATE_INDEX).get().clamp(0.05, 1.0));
+    let step_samples = Self::step_samples(context).max(1.0);
+    let block_timestamp = context.block_timestamp();
+    let nanos_per_sample = 1e9 / f64::from(context.sample_rate().max(1));
+
+    let output = context.events_output(Self::EVENTS_OUT_INDEX);
+    let mut events = context
+      .events_input_with_offsets(Self::EVENTS_IN_INDEX)
+      .peekable();
+    for index in 0..context.num_samples() {
+      while let Some((_, event)) = events.next_if(|(offset, _)| *offset <= index) {
+        self.receive(event, output);
+      }
+
+      let timestamp = block_timestamp + (index as f64 * nanos_per_sample) as TimestampNanos;
+      if let Some((note, remaining)) = self.playing.as_mut() {
+        *remaining -= 1.0;
+        if *remaining <= 0.0 {
+          let note = *note;
+          output.push(self.note_event(timestamp, note, 0, false)).ok();
+          self.playing = None;
+        }
+      }
+
+      if self.held.is_empty() {
+        continue;
+      }
+      if self.countdown <= 0.0 {
+        if let Some((note, _)) = self.playing.take() {
+          output.push(self.note_event(timestamp, note, 0, false)).ok();
+        }
+        let (note, velocity) = self.next_note(mode, octaves);
+        output
+          .push(self.note_event(timestamp, note, velocity, true))
+          .ok();
+        self.playing = Some((note, (step_samples * gate).round().max(1.0)));
+        self.countdown += step_samples;
+      }
+      self.countdown -= 1.0;
+    }
+    // events timestamped after the end of the block
+    for (_, event) in events {
+      self.receive(event, output);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+
+  /// Sixteenth notes at the default tempo of 120 BPM are 125 samples long at 1 kHz.
+  const STEP: u64 = 125;
+
+  fn arpeggiator(mode: ArpeggiatorMode, notes: &[u8]) -> (ArpeggiatorProcessor, TestContext) {
+    let arpeggiator = ArpeggiatorProcessor::new();
+    let mut context = TestContext::new(&arpeggiator.descriptor(), 1000);
+    context.set_timing(0, 1000);
+    context.set_parameter(ArpeggiatorProcessor::MODE_INDEX, mode.value());
+    let events = notes
+      .iter()
+      .map(|note| note_at(0, *note, true))
+      .collect::<Vec<_>>();
+    context.set_events(0, &events);
+    (arpeggiator, context)
+  }
+
+  fn note_at(timestamp: TimestampNanos, note: u8, on: bool) -> Event {
+    let message = if on {
+      ChannelVoiceMessage::NoteOn {
+        note,
+        velocity: u16::MAX,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    } else {
+      ChannelVoiceMessage::NoteOff {
+        note,
+        velocity: 0,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    };
+    Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(0, 0, message)),
+    }
+  }
+
+  /// Sample, note and whether it is on for the notes sent by the arpeggiator.
+  fn played(context: &TestContext) -> Vec<(u64, u8, bool)> {
+    context
+      .events_output(0)
+      .iter()
+      .filter_map(|event| match event.data {
+        EventData::Midi(Message {
+          mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+          ..
+        }) => match message {
+          ChannelVoiceMessage::NoteOn { note, .. } => Some((event.timestamp, note, true)),
+          ChannelVoiceMessage::NoteOff { note, .. } => Some((event.timestamp, note, false)),
+          _ => None,
+        },
+        _ => None,
+      })
+      .map(|(timestamp, note, on)| (timestamp / 1_000_000, note, on))
+      .collect()
+  }
+
+  fn notes_on(context: &TestContext) -> Vec<u8> {
+    played(context)
+      .into_iter()
+      .filter(|(_, _, on)| *on)
+      .map(|(_, note, _)| note)
+      .collect()
+  }
+
+  #[test]
+  fn play_the_held_notes_in_order() {
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::Up, &[64, 60, 67]);
+    context.render(&mut processor);
+    assert_eq!(notes_on(&context), vec![60, 64, 67, 60, 64, 67, 60, 64]);
+
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::Down, &[64, 60, 67]);
+    context.render(&mut processor);
+    assert_eq!(notes_on(&context), vec![67, 64, 60, 67, 64, 60, 67, 64]);
+
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::UpDown, &[64, 60, 67]);
+    context.render(&mut processor);
+    assert_eq!(notes_on(&context), vec![60, 64, 67, 64, 60, 64, 67, 64]);
+
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::Random, &[64, 60, 67]);
+    context.render(&mut processor);
+    let notes = notes_on(&context);
+    assert_eq!(notes.len(), 8);
+    assert!(notes.iter().all(|note| [60, 64, 67].contains(note)));
+  }
+
+  #[test]
+  fn span_several_octaves() {
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::Up, &[60, 64]);
+    context.set_parameter(ArpeggiatorProcessor::OCTAVES_INDEX, 2.0);
+    context.render(&mut processor);
+    assert_eq!(notes_on(&context), vec![60, 64, 72, 76, 60, 64, 72, 76]);
+  }
+
+  #[test]
+  fn release_the_notes_after_the_gate() {
+    let (mut processor, mut context) = arpeggiator(ArpeggiatorMode::Up, &[60]);
+    context.set_parameter(ArpeggiatorProcessor::GATE_INDEX, 0.2);
+    context.render(&mut processor);
+    let played = played(&context);
+    assert_eq!(
+      &played[0..4],
+      &[
+        (0, 60, true),
+        (STEP / 5, 60, false),
+        (STEP, 60, true),
+        (STEP + STEP / 5, 60, false),
+      ]
+    );
+
+    // the arpeggio stops when the notes are released, letting through the rest of events
+    let pitch_bend = Event {
+      timestamp: 1_000_000_000,
+      data: EventData::Midi(Message::channel_voice(
+        0,
+        0,
+        ChannelVoiceMessage::PitchBend { data: 0 },
+      )),
+    };
+    context.set_timing(1_000_000_000, 1000);
+    context.set_events(0, &[note_at(1_000_000_000, 60, false), pitch_bend]);
+    context.render(&mut processor);
+    assert_eq!(context.events_output(0), vec![pitch_bend]);
+  }
+}
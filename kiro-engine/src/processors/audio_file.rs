@@ -0,0 +1,292 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use kiro_time::SampleRate;
+
+#[derive(Debug, Error)]
+pub enum AudioFileError {
+  #[error("I/O: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("WAV: {0}")]
+  Wav(#[from] hound::Error),
+
+  #[error("FLAC: {0}")]
+  Flac(#[from] claxon::Error),
+
+  #[error("Unsupported audio file format: {0}")]
+  UnsupportedFormat(String),
+}
+
+pub type Result<T> = core::result::Result<T, AudioFileError>;
+
+enum Decoder {
+  Wav(hound::WavReader<BufReader<File>>),
+  Flac(claxon::FlacReader<File>),
+}
+
+/// Decodes wav and flac files into interleaved samples in the range [-1, 1].
+///
+/// It is meant to be used outside of the audio thread.
+pub(crate) struct AudioFileReader {
+  path: PathBuf,
+  decoder: Decoder,
+  channels: usize,
+  sample_rate: SampleRate,
+  scale: f32,
+  /// Decoded samples not read yet, as flac decodes whole blocks
+  pending: Vec<f32>,
+  pending_position: usize,
+  block_buffer: Vec<i32>,
+}
+
+impl AudioFileReader {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref().to_path_buf();
+    let decoder = Self::open_decoder(&path)?;
+    let (channels, sample_rate, bits_per_sample) = match &decoder {
+      Decoder::Wav(reader) => {
+        let spec = reader.spec();
+        (
+          spec.channels as usize,
+          spec.sample_rate,
+          spec.bits_per_sample,
+        )
+      }
+      Decoder::Flac(reader) => {
+        let info = reader.streaminfo();
+        (
+          info.channels as usize,
+          info.sample_rate,
+          info.bits_per_sample as u16,
+        )
+      }
+    };
+
+    Ok(Self {
+      path,
+      decoder,
+      channels,
+      sample_rate,
+      scale: 1.0 / (1u64 << (bits_per_sample.max(1) - 1)) as f32,
+      pending: Vec::new(),
+      pending_position: 0,
+      block_buffer: Vec::new(),
+    })
+  }
+
+  fn open_decoder(path: &Path) -> Result<Decoder> {
+    let extension = path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .unwrap_or_default()
+      .to_lowercase();
+    match extension.as_str() {
+      "wav" | "wave" => Ok(Decoder::Wav(hound::WavReader::open(path)?)),
+      "flac" => Ok(Decoder::Flac(claxon::FlacReader::open(path)?)),
+      _ => Err(AudioFileError::UnsupportedFormat(
+        path.to_string_lossy().to_string(),
+      )),
+    }
+  }
+
+  pub fn channels(&self) -> usize {
+    self.channels
+  }
+
+  pub fn sample_rate(&self) -> SampleRate {
+    self.sample_rate
+  }
+
+  /// Fill the interleaved samples with whole frames, returning the number of samples read,
+  /// which is zero at the end of the file.
+  pub fn read(&mut self, samples: &mut [f32]) -> Result<usize> {
+    let len = samples.len() - samples.len() % self.channels;
+    let mut count = 0;
+    while count < len {
+      if self.pending_position < self.pending.len() {
+        let available = (self.pending.len() - self.pending_position).min(len - count);
+        let pending = &self.pending[self.pending_position..self.pending_position + available];
+        samples[count..count + available].copy_from_slice(pending);
+        self.pending_position += available;
+        count += available;
+      } else if !self.decode()? {
+        break;
+      }
+    }
+    Ok(count)
+  }
+
+  /// Decode the next samples into the pending ones, returning false at the end of the file.
+  fn decode(&mut self) -> Result<bool> {
+    self.pending.clear();
+    self.pending_position = 0;
+    match &mut self.decoder {
+      Decoder::Wav(reader) => {
+        const WAV_BLOCK_FRAMES: usize = 1024;
+        let len = WAV_BLOCK_FRAMES * self.channels;
+        if reader.spec().sample_format == hound::SampleFormat::Float {
+          for sample in reader.samples::<f32>().take(len) {
+            self.pending.push(sample?);
+          }
+        } else {
+          let scale = self.scale;
+          for sample in reader.samples::<i32>().take(len) {
+            self.pending.push(sample? as f32 * scale);
+          }
+        }
+      }
+      Decoder::Flac(reader) => {
+        let buffer = std::mem::take(&mut self.block_buffer);
+        if let Some(block) = reader.blocks().read_next_or_eof(buffer)? {
+          for frame in 0..block.duration() {
+            for channel in 0..block.channels() {
+              self
+                .pending
+                .push(block.sample(channel, frame) as f32 * self.scale);
+            }
+          }
+          self.block_buffer = block.into_buffer();
+        }
+      }
+    }
+    Ok(!self.pending.is_empty())
+  }
+
+  /// Move to the given frame, or to the end of the file if it is beyond it.
+  pub fn seek(&mut self, frame: u64) -> Result<()> {
+    self.pending.clear();
+    self.pending_position = 0;
+    match &mut self.decoder {
+      Decoder::Wav(reader) => {
+        let frame = frame.min(u64::from(reader.duration())) as u32;
+        reader.seek(frame)?;
+      }
+      Decoder::Flac(_) => {
+        // flac files can not be seeked, so decode them from the beginning until reaching the frame
+        self.decoder = Self::open_decoder(&self.path)?;
+        let mut remaining = frame as usize * self.channels;
+        while remaining > 0 && self.decode()? {
+          let skipped = remaining.min(self.pending.len());
+          self.pending_position = skipped;
+          remaining -= skipped;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Decode a whole audio file into mono samples, averaging its channels,
+/// together with its sample rate.
+///
+/// It is meant for short files, such as wavetables, loaded outside of the audio thread.
+pub fn read_mono_samples<P: AsRef<Path>>(path: P) -> Result<(Vec<f32>, SampleRate)> {
+  let (channels, sample_rate) = read_samples(path)?;
+  let num_channels = channels.len().max(1) as f32;
+  let num_samples = channels.first().map_or(0, Vec::len);
+  let samples = (0..num_samples)
+    .map(|index| channels.iter().map(|channel| channel[index]).sum::<f32>() / num_channels)
+    .collect();
+  Ok((samples, sample_rate))
+}
+
+/// Decode a whole audio file into the samples of every channel, together with its sample rate.
+///
+/// It is meant to be used outside of the audio thread, such as for offline processing.
+pub fn read_samples<P: AsRef<Path>>(path: P) -> Result<(Vec<Vec<f32>>, SampleRate)> {
+  let mut reader = AudioFileReader::open(path)?;
+  let channels = reader.channels().max(1);
+  let mut samples = vec![Vec::new(); channels];
+  let mut buffer = vec![0.0; 1024 * channels];
+  loop {
+    let count = reader.read(&mut buffer)?;
+    if count == 0 {
+      break;
+    }
+    for frame in buffer[..count].chunks_exact(channels) {
+      for (channel, sample) in samples.iter_mut().zip(frame.iter()) {
+        channel.push(*sample);
+      }
+    }
+  }
+  Ok((samples, reader.sample_rate()))
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+  use std::path::PathBuf;
+
+  use kiro_time::SampleRate;
+
+  /// Write a 16 bits wav file into the temporary directory and return its path.
+  pub fn write_wav(name: &str, channels: u16, sample_rate: SampleRate, samples: &[f32]) -> PathBuf {
+    let path =
+      std::env::temp_dir().join(format!("kiro-engine-{}-{}.wav", name, std::process::id()));
+    let spec = hound::WavSpec {
+      channels,
+      sample_rate,
+      bits_per_sample: 16,
+      sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for sample in samples {
+      writer
+        .write_sample((sample * f32::from(i16::MAX)) as i16)
+        .unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::testing::write_wav;
+  use super::*;
+
+  #[test]
+  fn read_and_seek_wav() {
+    let samples = (0..8).map(|i| i as f32 / 8.0).collect::<Vec<_>>();
+    let path = write_wav("read-and-seek", 2, 1_000, &samples);
+
+    let mut reader = AudioFileReader::open(&path).unwrap();
+    assert_eq!(reader.channels(), 2);
+    assert_eq!(reader.sample_rate(), 1_000);
+
+    let mut buffer = [0.0; 5];
+    assert_eq!(reader.read(&mut buffer).unwrap(), 4);
+    assert_eq!(reader.read(&mut buffer).unwrap(), 4);
+    assert_eq!(reader.read(&mut buffer).unwrap(), 0);
+
+    reader.seek(3).unwrap();
+    assert_eq!(reader.read(&mut buffer).unwrap(), 2);
+    assert!((buffer[0] - 0.75).abs() < 0.001);
+    assert!((buffer[1] - 0.875).abs() < 0.001);
+
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn read_mono_samples_from_a_file() {
+    let path = write_wav("read-mono", 2, 1_000, &[0.5, 0.25, -0.5, 0.0, 0.125, 0.125]);
+    let (samples, sample_rate) = read_mono_samples(&path).unwrap();
+    assert_eq!(sample_rate, 1_000);
+    assert_eq!(samples.len(), 3);
+    assert!((samples[0] - 0.375).abs() < 0.001);
+    assert!((samples[1] - -0.25).abs() < 0.001);
+    assert!((samples[2] - 0.125).abs() < 0.001);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn unsupported_format() {
+    assert!(matches!(
+      AudioFileReader::open("song.mp3"),
+      Err(AudioFileError::UnsupportedFormat(_))
+    ));
+  }
+}
@@ -0,0 +1,159 @@
+use kiro_time::SampleRate;
+
+use crate::processor::BoxedProcessor;
+use crate::processors::{
+  ChorusProcessor, CompressorProcessor, DelayProcessor, EnvelopeFollowerProcessor, GainProcessor,
+  LfoProcessor, LimiterProcessor, MixerProcessor, NullProcessor, ReverbProcessor,
+  SplitterProcessor, StereoPannerProcessor,
+};
+use crate::NodeDescriptor;
+
+/// Names of the processors created by [`builtin_processor`].
+pub const BUILTIN_PROCESSORS: [&str; 12] = [
+  "chorus",
+  "compressor",
+  "delay",
+  "envelope-follower",
+  "gain",
+  "lfo",
+  "limiter",
+  "mixer",
+  "null",
+  "pan",
+  "reverb",
+  "splitter",
+];
+
+/// Create one of the built-in processors for a node, such as the ones of an effect chain
+/// imported with [`crate::Engine::import_graph`].
+///
+/// The processor is chosen by the name of the node, which can end with a number to tell apart
+/// several nodes of the same kind, as in `gain` or `gain-2`. The number of channels, inputs and outputs
+/// of the processors that have them are taken from the descriptor of the node.
+pub fn builtin_processor(
+  name: &str,
+  descriptor: &NodeDescriptor,
+  sample_rate: SampleRate,
+) -> Option<BoxedProcessor> {
+  let audio = &descriptor.ports.audio;
+  let input_channels = audio
+    .static_inputs
+    .first()
+    .map_or(2, |port| port.channels());
+  let output_channels = audio
+    .static_outputs
+    .first()
+    .map_or(2, |port| port.channels());
+  let processor: BoxedProcessor = match processor_kind(name) {
+    "chorus" => Box::new(ChorusProcessor::new()),
+    "compressor" => Box::new(CompressorProcessor::new()),
+    "delay" => Box::new(DelayProcessor::new()),
+    "envelope-follower" => Box::new(EnvelopeFollowerProcessor::new(sample_rate as f32)),
+    "gain" => Box::new(GainProcessor::new(input_channels)),
+    "lfo" => Box::new(LfoProcessor::new()),
+    "limiter" => Box::new(LimiterProcessor::new(sample_rate as f32)),
+    "mixer" => Box::new(MixerProcessor::new(
+      audio.static_inputs.len(),
+      output_channels,
+    )),
+    "null" => Box::new(NullProcessor::new(output_channels)),
+    "pan" => Box::new(StereoPannerProcessor::new()),
+    "reverb" => Box::new(ReverbProcessor::new()),
+    "splitter" => Box::new(SplitterProcessor::new(
+      audio.static_outputs.len(),
+      input_channels,
+    )),
+    _ => return None,
+  };
+  Some(processor)
+}
+
+/// Name of a node without the number at its end, if any.
+fn processor_kind(name: &str) -> &str {
+  match name.rsplit_once('-') {
+    Some((kind, number)) if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) => {
+      kind
+    }
+    _ => name,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::*;
+  use crate::testing::{assert_golden, ProcessorHarness};
+  use crate::Processor;
+
+  const GOLDEN_BLOCKS: usize = 8;
+  const GOLDEN_BLOCK_SIZE: usize = 64;
+
+  /// Render a processor fed with a sine burst in every input channel,
+  /// comparing every output channel against its golden file.
+  fn assert_renders_golden<P: Processor>(name: &str, processor: P) {
+    assert_renders_golden_blocks(name, processor, GOLDEN_BLOCKS);
+  }
+
+  fn assert_renders_golden_blocks<P: Processor>(name: &str, processor: P, blocks: usize) {
+    let descriptor = processor.descriptor();
+    let mut harness = ProcessorHarness::new(processor)
+      .with_sample_rate(44_100)
+      .with_block_size(GOLDEN_BLOCK_SIZE);
+    let len = blocks * GOLDEN_BLOCK_SIZE;
+    for (port, input) in descriptor.ports.audio.static_inputs.iter().enumerate() {
+      for channel in 0..input.channels() {
+        // a different frequency for every channel, followed by silence to capture the tails
+        let frequency = 440.0 * (1 + port * input.channels() + channel) as f32;
+        let burst = (0..len / 2)
+          .map(|index| {
+            0.5 * (2.0 * std::f32::consts::PI * frequency * index as f32 / 44_100.0).sin()
+          })
+          .collect::<Vec<_>>();
+        harness.feed_audio(port, channel, &burst);
+      }
+    }
+
+    let output = harness.render(blocks);
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden");
+    for (port, output_port) in descriptor.ports.audio.static_outputs.iter().enumerate() {
+      for channel in 0..output_port.channels() {
+        let path = golden.join(format!("{}-{}-{}.txt", name, port, channel));
+        assert_golden(path, output.channel(port, channel), 1e-4);
+      }
+    }
+  }
+
+  #[test]
+  fn render_the_builtin_processors_like_the_golden_files() {
+    assert_renders_golden("chorus", ChorusProcessor::new());
+    assert_renders_golden("compressor", CompressorProcessor::new());
+    assert_renders_golden("delay", DelayProcessor::new());
+    assert_renders_golden(
+      "envelope-follower",
+      EnvelopeFollowerProcessor::new(44_100.0),
+    );
+    assert_renders_golden("gain", GainProcessor::new(2));
+    assert_renders_golden("lfo", LfoProcessor::new());
+    assert_renders_golden("limiter", LimiterProcessor::new(44_100.0));
+    assert_renders_golden("mixer", MixerProcessor::new(2, 2));
+    assert_renders_golden("null", NullProcessor::new(2));
+    assert_renders_golden("pan", StereoPannerProcessor::new());
+    // the delay lines of the reverb are longer than the rest of renders
+    assert_renders_golden_blocks("reverb", ReverbProcessor::new(), 48);
+    assert_renders_golden("splitter", SplitterProcessor::new(2, 2));
+  }
+
+  #[test]
+  fn create_processors_by_name() {
+    let descriptor = MixerProcessor::new(3, 1).descriptor();
+    assert!(builtin_processor("mixer-2", &descriptor, 44_100).is_some());
+    assert!(builtin_processor("envelope-follower", &descriptor, 44_100).is_some());
+    assert!(builtin_processor("synth", &descriptor, 44_100).is_none());
+    for name in BUILTIN_PROCESSORS {
+      assert!(builtin_processor(name, &descriptor, 44_100).is_some());
+    }
+    assert_eq!(processor_kind("gain-12"), "gain");
+    assert_eq!(processor_kind("gain-"), "gain-");
+  }
+}
@@ -0,0 +1,188 @@
+use std::f32::consts::PI;
+
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamScale, ParamUnit, Processor};
+
+/// Stereo chorus made of several taps of a short delay line, with their delay times modulated
+/// by sine LFOs out of phase between them, and between both channels to widen the sound.
+pub struct ChorusProcessor {
+  sample_rate: f32,
+  lines: [Vec<f32>; 2],
+  /// Position of the lines where the next sample is written
+  head: usize,
+  /// Position along the cycle of the LFOs, in the `0..1` range
+  phase: f32,
+}
+
+impl ChorusProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const RATE_INDEX: usize = 0;
+  pub const DEPTH_INDEX: usize = 1;
+  pub const MIX_INDEX: usize = 2;
+
+  /// Number of modulated taps mixed for every channel
+  pub const NUM_TAPS: usize = 3;
+  /// Delay of the taps in seconds when not modulated
+  pub const BASE_DELAY: f32 = 0.007;
+  /// Swing of the delay of the taps in seconds at the full depth
+  pub const MAX_SWING: f32 = 0.005;
+
+  /// Offset between the phases of the LFOs of the left and right channels
+  const STEREO_PHASE: f32 = 0.25;
+
+  pub fn new() -> Self {
+    Self {
+      sample_rate: 0.0,
+      lines: [Vec::new(), Vec::new()],
+      head: 0,
+      phase: 0.0,
+    }
+  }
+}
+
+impl Default for ChorusProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for ChorusProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 2)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("rate")
+          .initial(0.5)
+          .min(0.05)
+          .max(5.0)
+          .unit(ParamUnit::Hertz)
+          .scale(ParamScale::Logarithmic),
+        ParamDescriptor::new("depth")
+          .initial(0.5)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("mix")
+          .initial(0.5)
+          .unit(ParamUnit::Percent),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    let sample_rate = sample_rate as f32;
+    self.sample_rate = sample_rate;
+    let length = ((Self::BASE_DELAY + Self::MAX_SWING) * sample_rate).ceil() as usize + 2;
+    self.lines = [vec![0.0; length], vec![0.0; length]];
+    self.head = 0;
+  }
+
+  fn reset(&mut self) {
+    for line in self.lines.iter_mut() {
+      line.fill(0.0);
+    }
+    self.head = 0;
+    self.phase = 0.0;
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let length = self.lines[0].len();
+    if length == 0 {
+      return;
+    }
+
+    let phase_inc = context.parameter(Self::RATE_INDEX).get() / self.sample_rate;
+    let depth = context.parameter(Self::DEPTH_INDEX).get();
+    let mix = context.parameter(Self::MIX_INDEX).get();
+    let base_delay = Self::BASE_DELAY * self.sample_rate;
+    let swing = Self::MAX_SWING * self.sample_rate * depth;
+    let max_delay = (length - 2) as f32;
+    let tap_gain = 1.0 / Self::NUM_TAPS as f32;
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let inputs = [input_left.as_slice(), input_right.as_slice()];
+    let outputs = [output_left.as_mut_slice(), output_right.as_mut_slice()];
+    let num_samples = outputs[0].len();
+    for index in 0..num_samples {
+      for channel in 0..2 {
+        let line = &mut self.lines[channel];
+        let dry = inputs[channel][index];
+        line[self.head] = dry;
+        let channel_phase = self.phase + Self::STEREO_PHASE * channel as f32;
+        let mut wet = 0.0;
+        for tap in 0..Self::NUM_TAPS {
+          let tap_phase = channel_phase + tap as f32 / Self::NUM_TAPS as f32;
+          let lfo = 0.5 + 0.5 * (2.0 * PI * tap_phase).sin();
+          let delay = (base_delay + swing * lfo).max(1.0).min(max_delay);
+          let position = (self.head + length) as f32 - delay;
+          let (before, fraction) = (position.floor() as usize, position.fract());
+          let previous = line[before % length];
+          let next = line[(before + 1) % length];
+          wet += (previous + (next - previous) * fraction) * tap_gain;
+        }
+        outputs[channel][index] = dry * (1.0 - mix) + wet * mix;
+      }
+      self.head = (self.head + 1) % length;
+      self.phase = (self.phase + phase_inc).fract();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+
+  fn chorus(block_size: usize) -> (ChorusProcessor, TestContext) {
+    let mut chorus = ChorusProcessor::new();
+    chorus.prepare(1000, block_size);
+    chorus.reset();
+    let mut context = TestContext::new(&chorus.descriptor(), block_size);
+    context.set_parameter(ChorusProcessor::MIX_INDEX, 1.0);
+    let mut impulse = vec![0.0; block_size];
+    impulse[0] = 1.0;
+    context.set_input(0, 0, &impulse);
+    context.set_input(0, 1, &impulse);
+    (chorus, context)
+  }
+
+  #[test]
+  fn delay_the_taps_without_depth() {
+    let (mut processor, mut context) = chorus(20);
+    context.set_parameter(ChorusProcessor::DEPTH_INDEX, 0.0);
+    context.render(&mut processor);
+    // all the taps at the base delay of 7 samples
+    for channel in 0..2 {
+      let output = context.output(0, channel);
+      assert!((output[7] - 1.0).abs() < 1e-6);
+      assert_eq!(output.iter().filter(|sample| **sample != 0.0).count(), 1);
+    }
+  }
+
+  #[test]
+  fn spread_the_taps_across_the_channels() {
+    let (mut processor, mut context) = chorus(20);
+    context.set_parameter(ChorusProcessor::DEPTH_INDEX, 1.0);
+    context.render(&mut processor);
+    let (left, right) = (context.output(0, 0), context.output(0, 1));
+    // the taps read the impulse at different times, within the range of the swing
+    assert!(left.iter().all(|sample| *sample < 1.0));
+    assert!(left[0..7].iter().all(|sample| *sample == 0.0));
+    assert!(left[13..20].iter().all(|sample| *sample == 0.0));
+    assert!((left.iter().sum::<f32>() - 1.0).abs() < 1e-2);
+    assert_ne!(left, right);
+  }
+}
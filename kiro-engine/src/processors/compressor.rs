@@ -0,0 +1,199 @@
+use kiro_dsp::dynamics::Compressor;
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamScale, ParamUnit, Processor};
+
+/// Stereo feed-forward compressor, with the same gain for both channels.
+///
+/// The gain follows the peaks of the input, or of the sidechain input when enabled,
+/// so another signal of the graph can duck this one.
+pub struct CompressorProcessor {
+  compressor: Compressor<f32>,
+  threshold: f32,
+  ratio: f32,
+  attack: f32,
+  release: f32,
+  makeup: f32,
+}
+
+impl CompressorProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const SIDECHAIN_IN_NAME: &'static str = "sidechain-in";
+  pub const SIDECHAIN_IN_INDEX: usize = 1;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const THRESHOLD_INDEX: usize = 0;
+  pub const RATIO_INDEX: usize = 1;
+  pub const ATTACK_INDEX: usize = 2;
+  pub const RELEASE_INDEX: usize = 3;
+  pub const MAKEUP_INDEX: usize = 4;
+  pub const SIDECHAIN_INDEX: usize = 5;
+
+  pub fn new() -> Self {
+    Self {
+      compressor: Compressor::new(1.0),
+      threshold: f32::NAN,
+      ratio: f32::NAN,
+      attack: f32::NAN,
+      release: f32::NAN,
+      makeup: f32::NAN,
+    }
+  }
+
+  /// Current gain reduction in decibels, as a positive value.
+  pub fn reduction(&self) -> f32 {
+    self.compressor.reduction()
+  }
+}
+
+impl Default for CompressorProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for CompressorProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![
+            AudioDescriptor::new(Self::AUDIO_IN_NAME, 2),
+            AudioDescriptor::new(Self::SIDECHAIN_IN_NAME, 2),
+          ])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("threshold")
+          .initial(Compressor::<f32>::DEFAULT_THRESHOLD as f32)
+          .min(-60.0)
+          .max(0.0)
+          .unit(ParamUnit::Decibels)
+          .precision(1),
+        ParamDescriptor::new("ratio")
+          .initial(Compressor::<f32>::DEFAULT_RATIO as f32)
+          .min(1.0)
+          .max(20.0)
+          .scale(ParamScale::Logarithmic)
+          .precision(1),
+        ParamDescriptor::new("attack")
+          .initial(Compressor::<f32>::DEFAULT_ATTACK as f32)
+          .max(0.5)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("release")
+          .initial(Compressor::<f32>::DEFAULT_RELEASE as f32)
+          .max(5.0)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("makeup")
+          .max(24.0)
+          .unit(ParamUnit::Decibels)
+          .precision(1),
+        ParamDescriptor::new("sidechain").values(vec!["off", "on"]),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.compressor = Compressor::new(sample_rate as f32);
+    self.threshold = f32::NAN;
+    self.ratio = f32::NAN;
+    self.attack = f32::NAN;
+    self.release = f32::NAN;
+    self.makeup = f32::NAN;
+  }
+
+  fn reset(&mut self) {
+    self.compressor.reset();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let threshold = context.parameter(Self::THRESHOLD_INDEX).get();
+    if threshold != self.threshold {
+      self.threshold = threshold;
+      self.compressor.set_threshold(threshold);
+    }
+
+    let ratio = context.parameter(Self::RATIO_INDEX).get();
+    if ratio != self.ratio {
+      self.ratio = ratio;
+      self.compressor.set_ratio(ratio);
+    }
+
+    let attack = context.parameter(Self::ATTACK_INDEX).get();
+    if attack != self.attack {
+      self.attack = attack;
+      self.compressor.set_attack(attack);
+    }
+
+    let release = context.parameter(Self::RELEASE_INDEX).get();
+    if release != self.release {
+      self.release = release;
+      self.compressor.set_release(release);
+    }
+
+    let makeup = context.parameter(Self::MAKEUP_INDEX).get();
+    if makeup != self.makeup {
+      self.makeup = makeup;
+      self.compressor.set_makeup(makeup);
+    }
+
+    let sidechain = context.parameter(Self::SIDECHAIN_INDEX).get() >= 0.5;
+    let detector_index = if sidechain {
+      Self::SIDECHAIN_IN_INDEX
+    } else {
+      Self::AUDIO_IN_INDEX
+    };
+    let detector = context.audio_input(detector_index);
+    let (detector_left, detector_right) = (detector.channel(0), detector.channel(1));
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let outputs = output_left.iter_mut().zip(output_right.iter_mut());
+    let inputs = input_left.iter().zip(input_right.iter());
+    let detectors = detector_left.iter().zip(detector_right.iter());
+    for ((out_left, out_right), ((left, right), (key_left, key_right))) in
+      outputs.zip(inputs.zip(detectors))
+    {
+      let gain = self.compressor.process(key_left.abs().max(key_right.abs()));
+      *out_left = left * gain;
+      *out_right = right * gain;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::ProcessorHarness;
+
+  #[test]
+  fn duck_the_input_from_the_sidechain() {
+    let mut harness = ProcessorHarness::new(CompressorProcessor::new()).with_block_size(100);
+    harness
+      .feed_audio(CompressorProcessor::AUDIO_IN_INDEX, 0, &[0.1; 200])
+      .feed_audio(CompressorProcessor::SIDECHAIN_IN_INDEX, 1, &[1.0; 200])
+      .set_parameter(0, CompressorProcessor::ATTACK_INDEX, 0.0);
+
+    // the input is below the threshold
+    let output = harness.render(1);
+    assert!(output
+      .channel(CompressorProcessor::AUDIO_OUT_INDEX, 0)
+      .iter()
+      .all(|sample| (sample - 0.1).abs() < 1e-6));
+
+    // but the sidechain is 18 dB above it
+    harness.set_parameter(1, CompressorProcessor::SIDECHAIN_INDEX, 1.0);
+    let output = harness.render(1);
+    let left = output.channel(CompressorProcessor::AUDIO_OUT_INDEX, 0);
+    assert!((harness.processor().reduction() - 13.5).abs() < 1e-3);
+    assert!((left[99] - 0.1 * 10.0f32.powf(-13.5 / 20.0)).abs() < 1e-6);
+  }
+}
@@ -0,0 +1,318 @@
+use kiro_dsp::filters::va_one_pole::VAOnePoleFilter;
+use kiro_dsp::smoother::{LinearSteps, LinearStepsSmoother};
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamScale, ParamUnit, Processor};
+
+/// Stereo delay with a low-pass filter in its feedback loop, mixed with the dry signal.
+///
+/// The delay time is set in seconds, or as a note length when synced to the tempo of the transport.
+/// Changes of the time glide along some milliseconds, as a tape delay would, to avoid clicks.
+pub struct DelayProcessor {
+  sample_rate: f32,
+  lines: [Vec<f32>; 2],
+  /// Position of the lines where the next sample is written
+  head: usize,
+  filters: [VAOnePoleFilter<f32>; 2],
+  /// Delay in samples
+  delay: LinearStepsSmoother<f32>,
+  /// Whether the delay already reached its time since the last reset, to start there without gliding
+  settled: bool,
+  cutoff: f32,
+}
+
+impl DelayProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const TIME_INDEX: usize = 0;
+  pub const FEEDBACK_INDEX: usize = 1;
+  pub const FILTER_INDEX: usize = 2;
+  pub const MIX_INDEX: usize = 3;
+  pub const SYNC_INDEX: usize = 4;
+  pub const DIVISION_INDEX: usize = 5;
+
+  /// Note lengths of the delay when synced to the tempo, together with their duration in beats
+  pub const DIVISIONS: [(&'static str, f64); 8] = [
+    ("1/16", 0.25),
+    ("1/8t", 1.0 / 3.0),
+    ("1/8", 0.5),
+    ("1/8.", 0.75),
+    ("1/4", 1.0),
+    ("1/4.", 1.5),
+    ("1/2", 2.0),
+    ("1/1", 4.0),
+  ];
+
+  pub const MAX_TIME: f32 = 4.0;
+  const DEFAULT_TIME: f32 = 0.3;
+  const DEFAULT_CUTOFF: f32 = 6000.0;
+  const GLIDE_TIME: f32 = 0.05;
+
+  pub fn new() -> Self {
+    Self {
+      sample_rate: 0.0,
+      lines: [Vec::new(), Vec::new()],
+      head: 0,
+      filters: [
+        VAOnePoleFilter::new(1.0, Self::DEFAULT_CUTOFF),
+        VAOnePoleFilter::new(1.0, Self::DEFAULT_CUTOFF),
+      ],
+      delay: LinearStepsSmoother::new(1.0, LinearSteps::new(1)),
+      settled: false,
+      cutoff: Self::DEFAULT_CUTOFF,
+    }
+  }
+
+  /// Delay in seconds for the time and sync parameters.
+  fn time(context: &ProcessorContext) -> f32 {
+    if context.parameter(Self::SYNC_INDEX).get() >= 0.5 {
+      let division = context
+        .parameter(Self::DIVISION_INDEX)
+        .get()
+        .round()
+        .max(0.0) as usize;
+      let (_, beats) = Self::DIVISIONS[division.min(Self::DIVISIONS.len() - 1)];
+      let seconds_per_beat = 60.0 / f64::from(context.transport().tempo());
+      (beats * seconds_per_beat) as f32
+    } else {
+      context.parameter(Self::TIME_INDEX).get()
+    }
+  }
+
+  /// Delay in samples for a time in seconds, within the length of the lines.
+  fn delay_samples(&self, time: f32) -> f32 {
+    let max_delay = self.lines[0].len().saturating_sub(2).max(1) as f32;
+    (time * self.sample_rate).max(1.0).min(max_delay)
+  }
+}
+
+impl Default for DelayProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for DelayProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 2)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("time")
+          .initial(Self::DEFAULT_TIME)
+          .min(0.001)
+          .max(Self::MAX_TIME)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("feedback")
+          .initial(0.4)
+          .max(0.95)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("filter")
+          .initial(Self::DEFAULT_CUTOFF)
+          .min(200.0)
+          .max(18000.0)
+          .unit(ParamUnit::Hertz)
+          .scale(ParamScale::Logarithmic)
+          .precision(0),
+        ParamDescriptor::new("mix")
+          .initial(0.3)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("sync").values(vec!["off", "on"]),
+        ParamDescriptor::new("division")
+          .values(
+            Self::DIVISIONS
+              .iter()
+              .map(|(label, _)| *label)
+              .collect::<Vec<_>>(),
+          )
+          .initial(3.0),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    let sample_rate = sample_rate as f32;
+    self.sample_rate = sample_rate;
+    let length = (Self::MAX_TIME * sample_rate).ceil() as usize + 2;
+    self.lines = [vec![0.0; length], vec![0.0; length]];
+    self.head = 0;
+    self.filters = [
+      VAOnePoleFilter::new(sample_rate, self.cutoff),
+      VAOnePoleFilter::new(sample_rate, self.cutoff),
+    ];
+    let delay = self.delay_samples(Self::DEFAULT_TIME);
+    self.delay =
+      LinearStepsSmoother::new(delay, LinearSteps::from_time(sample_rate, Self::GLIDE_TIME));
+  }
+
+  fn reset(&mut self) {
+    for line in self.lines.iter_mut() {
+      line.fill(0.0);
+    }
+    self.head = 0;
+    for filter in self.filters.iter_mut() {
+      filter.reset();
+    }
+    self.settled = false;
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let time = Self::time(context);
+    let delay = self.delay_samples(time);
+    if self.settled {
+      self.delay.set_target(delay);
+    } else {
+      self.delay.reset(delay);
+      self.settled = true;
+    }
+
+    // below the nyquist frequency, where the filter would be unstable
+    let cutoff = context
+      .parameter(Self::FILTER_INDEX)
+      .get()
+      .min(self.sample_rate * 0.45);
+    if cutoff != self.cutoff {
+      self.cutoff = cutoff;
+      for filter in self.filters.iter_mut() {
+        filter.set_frequency(cutoff);
+      }
+    }
+    let feedback = context.parameter(Self::FEEDBACK_INDEX).get();
+    let mix = context.parameter(Self::MIX_INDEX).get();
+
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let length = self.lines[0].len();
+    if length == 0 {
+      // not prepared yet, so the output keeps nothing from previous blocks
+      for channel in 0..output.len() {
+        output.channel_mut(channel).fill(0.0);
+      }
+      return;
+    }
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let inputs = [input_left.as_slice(), input_right.as_slice()];
+    let outputs = [output_left.as_mut_slice(), output_right.as_mut_slice()];
+    let num_samples = outputs[0].len();
+    for index in 0..num_samples {
+      let delay = self.delay.next_value();
+      let position = (self.head + length) as f32 - delay;
+      let (before, fraction) = (position.floor() as usize, position.fract());
+      for channel in 0..2 {
+        let line = &mut self.lines[channel];
+        let previous = line[before % length];
+        let next = line[(before + 1) % length];
+        let wet = previous + (next - previous) * fraction;
+        let dry = inputs[channel][index];
+        line[self.head] = dry + self.filters[channel].process(wet) * feedback;
+        outputs[channel][index] = dry * (1.0 - mix) + wet * mix;
+      }
+      self.head = (self.head + 1) % length;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_time::Tempo;
+
+  use super::*;
+  use crate::testing::TestContext;
+  use crate::{Transport, TransportMessage};
+
+  fn delay(sample_rate: SampleRate, block_size: usize) -> (DelayProcessor, TestContext) {
+    let mut delay = DelayProcessor::new();
+    delay.prepare(sample_rate, block_size);
+    delay.reset();
+    let mut context = TestContext::new(&delay.descriptor(), block_size);
+    context.set_parameter(DelayProcessor::FEEDBACK_INDEX, 0.0);
+    context.set_parameter(DelayProcessor::FILTER_INDEX, 18000.0);
+    context.set_parameter(DelayProcessor::MIX_INDEX, 1.0);
+    (delay, context)
+  }
+
+  fn impulse(length: usize) -> Vec<f32> {
+    let mut samples = vec![0.0; length];
+    samples[0] = 1.0;
+    samples
+  }
+
+  fn position_of_peak(samples: &[f32]) -> usize {
+    samples
+      .iter()
+      .enumerate()
+      .fold((0, 0.0), |(position, peak), (index, sample)| {
+        if sample.abs() > peak {
+          (index, sample.abs())
+        } else {
+          (position, peak)
+        }
+      })
+      .0
+  }
+
+  #[test]
+  fn delay_both_channels() {
+    let (mut processor, mut context) = delay(1000, 400);
+    context.set_input(0, 0, &impulse(400));
+    context.set_input(0, 1, &impulse(400));
+    context.render(&mut processor);
+    assert_eq!(position_of_peak(&context.output(0, 0)), 300);
+    assert_eq!(position_of_peak(&context.output(0, 1)), 300);
+    assert_eq!(context.output(0, 0)[0], 0.0);
+  }
+
+  #[test]
+  fn feed_back_the_delayed_signal() {
+    let (mut processor, mut context) = delay(48_000, 1200);
+    context.set_parameter(DelayProcessor::TIME_INDEX, 0.01);
+    context.set_parameter(DelayProcessor::FEEDBACK_INDEX, 0.5);
+    let mut input = vec![0.0; 1200];
+    input[0..100].fill(1.0);
+    context.set_input(0, 0, &input);
+    context.render(&mut processor);
+    // every repetition 480 samples after the previous one, with half its level
+    let output = context.output(0, 0);
+    assert!((output[480 + 50] - 1.0).abs() < 0.01);
+    assert!((output[960 + 50] - 0.5).abs() < 0.01);
+    assert!(output[960 + 150].abs() < 0.01);
+  }
+
+  #[test]
+  fn silence_the_output_until_prepared() {
+    let (mut processor, mut context) = delay(1000, 400);
+    context.set_parameter(DelayProcessor::MIX_INDEX, 0.0);
+    context.set_input(0, 0, &impulse(400));
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0)[0], 1.0);
+
+    context.render(&mut DelayProcessor::new());
+    assert!(context.output(0, 0).iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn sync_to_the_tempo() {
+    let (mut processor, mut context) = delay(1000, 400);
+    let mut transport = Transport::new();
+    transport.apply(&TransportMessage::Tempo(Tempo::new(240)));
+    context.set_transport(transport);
+    context.set_parameter(DelayProcessor::SYNC_INDEX, 1.0);
+    // an eighth note of a quarter of a second beat
+    context.set_parameter(DelayProcessor::DIVISION_INDEX, 2.0);
+    context.set_input(0, 0, &impulse(400));
+    context.render(&mut processor);
+    assert_eq!(position_of_peak(&context.output(0, 0)), 125);
+  }
+}
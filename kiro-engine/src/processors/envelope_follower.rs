@@ -0,0 +1,113 @@
+use kiro_dsp::meters::EnvelopeFollower;
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Follows the envelope of its audio input into its output, to modulate the parameters of other nodes
+/// through [`crate::ProcessorNode::modulate_parameter`], so any signal in the graph can act as a sidechain.
+pub struct EnvelopeFollowerProcessor {
+  follower: EnvelopeFollower<f32>,
+  attack: f32,
+  release: f32,
+}
+
+impl EnvelopeFollowerProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const ATTACK_INDEX: usize = 0;
+  pub const RELEASE_INDEX: usize = 1;
+
+  const DEFAULT_ATTACK: f32 = 0.005;
+  const DEFAULT_RELEASE: f32 = 0.1;
+
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      follower: EnvelopeFollower::new(sample_rate, Self::DEFAULT_ATTACK, Self::DEFAULT_RELEASE),
+      attack: Self::DEFAULT_ATTACK,
+      release: Self::DEFAULT_RELEASE,
+    }
+  }
+
+  pub fn envelope(&self) -> f32 {
+    self.follower.get()
+  }
+}
+
+impl Processor for EnvelopeFollowerProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 1)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 1)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("attack")
+          .initial(Self::DEFAULT_ATTACK)
+          .max(1.0)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("release")
+          .initial(Self::DEFAULT_RELEASE)
+          .max(5.0)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.follower = EnvelopeFollower::new(sample_rate as f32, self.attack, self.release);
+  }
+
+  fn reset(&mut self) {
+    self.follower.reset();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let attack = context.parameter(Self::ATTACK_INDEX).get();
+    if attack != self.attack {
+      self.attack = attack;
+      self.follower.set_attack(attack);
+    }
+
+    let release = context.parameter(Self::RELEASE_INDEX).get();
+    if release != self.release {
+      self.release = release;
+      self.follower.set_release(release);
+    }
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX).channel(0);
+    let mut output = context.audio_output(Self::AUDIO_OUT_INDEX).channel_mut(0);
+    for (output, input) in output.iter_mut().zip(input.iter()) {
+      *output = self.follower.process(*input);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+
+  #[test]
+  fn follow_the_envelope_into_the_output() {
+    let mut follower = EnvelopeFollowerProcessor::new(1_000.0);
+    let mut context = TestContext::new(&follower.descriptor(), 4);
+    context.set_parameter(EnvelopeFollowerProcessor::ATTACK_INDEX, 0.0);
+    context.set_input(0, 0, &[0.5, -1.0, 0.0, 0.0]);
+    context.render(&mut follower);
+
+    let output = context.output(0, 0);
+    assert_eq!(&output[0..2], &[0.5, 1.0]);
+    assert!(output[2] < 1.0 && output[3] < output[2]);
+    assert_eq!(follower.envelope(), output[3]);
+  }
+}
@@ -0,0 +1,369 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ringbuf::{Consumer, Producer, RingBuffer};
+
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::processors::audio_file::{AudioFileReader, Result};
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Interleaved samples decoded by the reader thread
+const CHUNK_SAMPLES: usize = 1024;
+/// Number of chunks decoded in advance
+const CHUNKS_CAPACITY: usize = 32;
+/// Time the reader thread waits when there is nothing to decode
+const READER_IDLE: Duration = Duration::from_millis(2);
+
+/// Plays a wav or flac file, decoded by a reader thread and streamed into the audio thread.
+///
+/// Changing the position parameter seeks the file, the loop parameter restarts it
+/// from the beginning when it reaches the end, and the speed parameter changes the playback rate.
+/// It starts playing as soon as there are decoded samples available.
+pub struct FilePlayerProcessor {
+  channels: usize,
+  file_sample_rate: SampleRate,
+  rate: f64,
+  shared: Arc<Shared>,
+  chunks: Consumer<Chunk>,
+  chunk: Option<Chunk>,
+  chunk_position: usize,
+  epoch: usize,
+  position: f32,
+  primed: bool,
+  /// Interpolation between the current and next frames of the file
+  current: Vec<f32>,
+  next: Vec<f32>,
+  fraction: f64,
+  /// Interleaved frames of the block being rendered
+  block: Vec<f32>,
+}
+
+/// State shared with the reader thread
+struct Shared {
+  epoch: AtomicUsize,
+  position: AtomicU64,
+  looping: AtomicBool,
+  stopped: AtomicBool,
+}
+
+struct Chunk {
+  epoch: usize,
+  len: usize,
+  samples: [f32; CHUNK_SAMPLES],
+}
+
+impl FilePlayerProcessor {
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const POSITION_INDEX: usize = 0;
+  pub const LOOP_INDEX: usize = 1;
+  pub const SPEED_INDEX: usize = 2;
+
+  const MAX_POSITION: f32 = 24.0 * 60.0 * 60.0;
+  const MAX_SPEED: f32 = 4.0;
+
+  /// Open the file and start its reader thread.
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let reader = AudioFileReader::open(path)?;
+    let channels = reader.channels();
+    let file_sample_rate = reader.sample_rate();
+    let shared = Arc::new(Shared {
+      epoch: AtomicUsize::new(0),
+      position: AtomicU64::new(0),
+      looping: AtomicBool::new(false),
+      stopped: AtomicBool::new(false),
+    });
+    let (producer, chunks) = RingBuffer::new(CHUNKS_CAPACITY).split();
+
+    let reader_shared = shared.clone();
+    thread::Builder::new()
+      .name("kiro-file-player".to_string())
+      .spawn(move || read_chunks(reader, producer, reader_shared))?;
+
+    Ok(Self {
+      channels,
+      file_sample_rate,
+      rate: 1.0,
+      shared,
+      chunks,
+      chunk: None,
+      chunk_position: 0,
+      epoch: 0,
+      position: 0.0,
+      primed: false,
+      current: vec![0.0; channels],
+      next: vec![0.0; channels],
+      fraction: 0.0,
+      block: Vec::new(),
+    })
+  }
+
+  pub fn channels(&self) -> usize {
+    self.channels
+  }
+
+  fn descriptor_with_channels(channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, channels)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("position")
+          .max(Self::MAX_POSITION)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("loop")
+          .steps(2)
+          .values(vec!["off", "on"]),
+        ParamDescriptor::new("speed")
+          .initial(1.0)
+          .max(Self::MAX_SPEED)
+          .center(1.0),
+      ])
+  }
+
+  /// Ask the reader thread to continue from another position, discarding the chunks already decoded.
+  fn seek(&mut self, position: f32) {
+    self.position = position;
+    let frame = (f64::from(position) * f64::from(self.file_sample_rate)) as u64;
+    self.shared.position.store(frame, Ordering::Release);
+    self.epoch = self.epoch.wrapping_add(1);
+    self.shared.epoch.store(self.epoch, Ordering::Release);
+    self.chunk = None;
+    self.primed = false;
+  }
+
+  /// Move to the next frame of the file, returning false if the reader thread didn't decode it yet.
+  fn advance(&mut self) -> bool {
+    std::mem::swap(&mut self.current, &mut self.next);
+    loop {
+      if let Some(chunk) = self.chunk.as_ref() {
+        if self.chunk_position < chunk.len {
+          let end = self.chunk_position + self.channels;
+          self
+            .next
+            .copy_from_slice(&chunk.samples[self.chunk_position..end]);
+          self.chunk_position = end;
+          return true;
+        }
+      }
+
+      match self.chunks.pop() {
+        Some(chunk) if chunk.epoch == self.epoch => {
+          self.chunk = Some(chunk);
+          self.chunk_position = 0;
+        }
+        Some(_stale_chunk) => {}
+        None => {
+          self.chunk = None;
+          self.next.fill(0.0);
+          return false;
+        }
+      }
+    }
+  }
+
+  /// Load the first two frames to interpolate between them, once they are available.
+  fn prime(&mut self) -> bool {
+    if !self.primed && self.advance() {
+      self.primed = self.advance();
+      self.fraction = 0.0;
+    }
+    self.primed
+  }
+}
+
+impl Processor for FilePlayerProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(self.channels)
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, max_block: usize) {
+    self.rate = f64::from(self.file_sample_rate) / f64::from(sample_rate);
+    self.block = vec![0.0; max_block * self.channels];
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let position = context.parameter(Self::POSITION_INDEX).get();
+    if position != self.position {
+      self.seek(position);
+    }
+    let looping = context.parameter(Self::LOOP_INDEX).get() >= 0.5;
+    self.shared.looping.store(looping, Ordering::Relaxed);
+    let speed = f64::from(context.parameter(Self::SPEED_INDEX).get());
+
+    let num_samples = context
+      .num_samples()
+      .min(self.block.len() / self.channels.max(1));
+    let channels = self.channels;
+    let mut block = std::mem::take(&mut self.block);
+    block.fill(0.0);
+    if self.prime() {
+      for frame in block.chunks_exact_mut(channels).take(num_samples) {
+        let fraction = self.fraction as f32;
+        for ((sample, current), next) in frame.iter_mut().zip(&self.current).zip(&self.next) {
+          *sample = current + (next - current) * fraction;
+        }
+        self.fraction += self.rate * speed;
+        while self.fraction >= 1.0 {
+          self.fraction -= 1.0;
+          self.advance();
+        }
+      }
+    }
+
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    for channel in 0..output.len().min(channels) {
+      let mut output = output.channel_mut(channel);
+      let samples = block.iter().skip(channel).step_by(channels);
+      for (out, sample) in output.iter_mut().zip(samples.take(num_samples)) {
+        *out = *sample;
+      }
+    }
+    self.block = block;
+  }
+}
+
+impl Drop for FilePlayerProcessor {
+  fn drop(&mut self) {
+    self.shared.stopped.store(true, Ordering::Relaxed);
+  }
+}
+
+/// Decode chunks of the file until the player is dropped.
+fn read_chunks(mut reader: AudioFileReader, mut producer: Producer<Chunk>, shared: Arc<Shared>) {
+  let chunk_len = CHUNK_SAMPLES - CHUNK_SAMPLES % reader.channels();
+  let mut epoch = 0;
+  let mut at_end = false;
+
+  while !shared.stopped.load(Ordering::Relaxed) {
+    let current_epoch = shared.epoch.load(Ordering::Acquire);
+    if current_epoch != epoch {
+      epoch = current_epoch;
+      at_end = reader
+        .seek(shared.position.load(Ordering::Acquire))
+        .is_err();
+    }
+    if at_end && shared.looping.load(Ordering::Relaxed) {
+      at_end = reader.seek(0).is_err();
+    }
+    if at_end || producer.is_full() {
+      thread::sleep(READER_IDLE);
+      continue;
+    }
+
+    let mut chunk = Chunk {
+      epoch,
+      len: 0,
+      samples: [0.0; CHUNK_SAMPLES],
+    };
+    match reader.read(&mut chunk.samples[..chunk_len]) {
+      Ok(len) if len > 0 => {
+        chunk.len = len;
+        producer.push(chunk).ok();
+      }
+      _ => at_end = true,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use super::*;
+  use crate::processors::audio_file::testing::write_wav;
+  use crate::testing::TestContext;
+
+  /// Wait until the reader thread decodes some chunks
+  fn wait_for_chunks(player: &FilePlayerProcessor) {
+    let start = Instant::now();
+    while player.chunks.is_empty() && start.elapsed() < Duration::from_secs(5) {
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  fn assert_samples(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected) {
+      assert!((actual - expected).abs() < 0.001, "{:?}", actual);
+    }
+  }
+
+  fn player(name: &str, samples: &[f32]) -> (FilePlayerProcessor, TestContext) {
+    let path = write_wav(name, 1, 1_000, samples);
+    let mut player = FilePlayerProcessor::open(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    player.prepare(1_000, 4);
+    let context = TestContext::new(&player.descriptor(), 4);
+    (player, context)
+  }
+
+  #[test]
+  fn play_and_seek() {
+    let samples = (0..6).map(|i| i as f32 / 8.0).collect::<Vec<_>>();
+    let (mut player, mut context) = player("play", &samples);
+    assert_eq!(player.channels(), 1);
+
+    wait_for_chunks(&player);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &samples[0..4]);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &[0.5, 0.625, 0.0, 0.0]);
+
+    context.set_parameter(FilePlayerProcessor::POSITION_INDEX, 0.002);
+    player.seek(0.002);
+    wait_for_chunks(&player);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &samples[2..6]);
+  }
+
+  #[test]
+  fn looping() {
+    let samples = (0..6).map(|i| i as f32 / 8.0).collect::<Vec<_>>();
+    let (mut player, mut context) = player("loop", &samples);
+
+    context.set_parameter(FilePlayerProcessor::LOOP_INDEX, 1.0);
+    player.shared.looping.store(true, Ordering::Relaxed);
+    let start = Instant::now();
+    while !player.chunks.is_full() && start.elapsed() < Duration::from_secs(5) {
+      thread::sleep(Duration::from_millis(1));
+    }
+
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &samples[0..4]);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &[0.5, 0.625, 0.0, 0.125]);
+  }
+
+  #[test]
+  fn speed() {
+    let samples = (0..16).map(|i| i as f32 / 16.0).collect::<Vec<_>>();
+    let (mut player, mut context) = player("speed", &samples);
+
+    context.set_parameter(FilePlayerProcessor::SPEED_INDEX, 0.5);
+    wait_for_chunks(&player);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &[0.0, 0.03125, 0.0625, 0.09375]);
+
+    context.set_parameter(FilePlayerProcessor::SPEED_INDEX, 2.0);
+    context.render(&mut player);
+    assert_samples(&context.output(0, 0), &[0.125, 0.25, 0.375, 0.5]);
+  }
+}
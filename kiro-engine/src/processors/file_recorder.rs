@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ringbuf::{Consumer, Producer, RingBuffer};
+
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::processors::audio_file::Result;
+use crate::{
+  AudioDescriptor, EventData, EventsDescriptor, NodeDescriptor, ParamDescriptor, Processor,
+  TransportMessage,
+};
+
+/// Interleaved samples sent to the writer thread
+const CHUNK_SAMPLES: usize = 1024;
+/// Number of chunks pending to be written
+const CHUNKS_CAPACITY: usize = 64;
+/// Time the writer thread waits when there is nothing to write
+const WRITER_IDLE: Duration = Duration::from_millis(5);
+
+/// Records its audio input into a wav file with 32 bits float samples,
+/// streaming them to a writer thread.
+///
+/// The recording is controlled by the record parameter, and by the start, continue and stop
+/// transport messages of its events input. The file is finished when the processor is dropped.
+/// Samples not fitting into the ring buffer of the writer thread are lost.
+pub struct FileRecorderProcessor {
+  channels: usize,
+  shared: Arc<Shared>,
+  chunks: Producer<Chunk>,
+  chunk: Chunk,
+  record: f32,
+  recording: bool,
+}
+
+/// State shared with the writer thread
+struct Shared {
+  stopped: AtomicBool,
+}
+
+struct Chunk {
+  len: usize,
+  samples: [f32; CHUNK_SAMPLES],
+}
+
+impl Chunk {
+  fn new() -> Self {
+    Self {
+      len: 0,
+      samples: [0.0; CHUNK_SAMPLES],
+    }
+  }
+}
+
+impl FileRecorderProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const EVENTS_IN_INDEX: usize = 0;
+
+  pub const RECORD_INDEX: usize = 0;
+
+  /// Create the file and start its writer thread.
+  pub fn create<P: AsRef<Path>>(path: P, channels: usize, sample_rate: SampleRate) -> Result<Self> {
+    let spec = hound::WavSpec {
+      channels: channels as u16,
+      sample_rate,
+      bits_per_sample: 32,
+      sample_format: hound::SampleFormat::Float,
+    };
+    let writer = hound::WavWriter::create(path, spec)?;
+    let shared = Arc::new(Shared {
+      stopped: AtomicBool::new(false),
+    });
+    let (chunks, consumer) = RingBuffer::new(CHUNKS_CAPACITY).split();
+
+    let writer_shared = shared.clone();
+    thread::Builder::new()
+      .name("kiro-file-recorder".to_string())
+      .spawn(move || write_chunks(writer, consumer, writer_shared))?;
+
+    Ok(Self {
+      channels,
+      shared,
+      chunks,
+      chunk: Chunk::new(),
+      record: 0.0,
+      recording: false,
+    })
+  }
+
+  pub fn is_recording(&self) -> bool {
+    self.recording
+  }
+
+  fn descriptor_with_channels(channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, channels)])
+      })
+      .with_events_ports(|ports| {
+        ports.static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+      })
+      .with_parameters(vec![ParamDescriptor::new("record")
+        .steps(2)
+        .values(vec!["off", "on"])])
+  }
+
+  /// Send the current chunk to the writer thread, dropping it if the ring buffer is full.
+  fn send_chunk(&mut self) {
+    let chunk = std::mem::replace(&mut self.chunk, Chunk::new());
+    if let Err(chunk) = self.chunks.push(chunk) {
+      self.chunk = chunk;
+    }
+    self.chunk.len = 0;
+  }
+}
+
+impl Processor for FileRecorderProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(self.channels)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let was_recording = self.recording;
+    let record = context.parameter(Self::RECORD_INDEX).get();
+    if record != self.record {
+      self.record = record;
+      self.recording = record >= 0.5;
+    }
+    for event in context.events_input(Self::EVENTS_IN_INDEX).iter() {
+      match event.data {
+        EventData::Transport(TransportMessage::Start | TransportMessage::Continue) => {
+          self.recording = true
+        }
+        EventData::Transport(TransportMessage::Stop) => self.recording = false,
+        _ => {}
+      }
+    }
+    if !self.recording {
+      if was_recording && self.chunk.len > 0 {
+        self.send_chunk();
+      }
+      return;
+    }
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let num_samples = context.num_samples();
+    let chunk_len = CHUNK_SAMPLES - CHUNK_SAMPLES % self.channels;
+    let mut position = 0;
+    while position < num_samples {
+      let frames = ((chunk_len - self.chunk.len) / self.channels).min(num_samples - position);
+      for channel in 0..self.channels {
+        let samples = self.chunk.samples[self.chunk.len + channel..]
+          .iter_mut()
+          .step_by(self.channels)
+          .take(frames);
+        if channel < input.len() {
+          let input = input.channel(channel);
+          for (sample, value) in samples.zip(&input.as_slice()[position..position + frames]) {
+            *sample = *value;
+          }
+        } else {
+          samples.for_each(|sample| *sample = 0.0);
+        }
+      }
+      self.chunk.len += frames * self.channels;
+      position += frames;
+      if self.chunk.len == chunk_len {
+        self.send_chunk();
+      }
+    }
+  }
+}
+
+impl Drop for FileRecorderProcessor {
+  fn drop(&mut self) {
+    if self.chunk.len > 0 {
+      self.send_chunk();
+    }
+    self.shared.stopped.store(true, Ordering::Release);
+  }
+}
+
+/// Write the chunks into the file until the recorder is dropped, and then finish it.
+fn write_chunks(
+  mut writer: hound::WavWriter<BufWriter<File>>,
+  mut chunks: Consumer<Chunk>,
+  shared: Arc<Shared>,
+) {
+  loop {
+    let stopped = shared.stopped.load(Ordering::Acquire);
+    let mut written = false;
+    while let Some(chunk) = chunks.pop() {
+      for sample in &chunk.samples[..chunk.len] {
+        if writer.write_sample(*sample).is_err() {
+          return;
+        }
+      }
+      written = true;
+    }
+    if stopped {
+      break;
+    }
+    if written {
+      // keep the header updated, so the file is valid even if it is not finished
+      writer.flush().ok();
+    } else {
+      thread::sleep(WRITER_IDLE);
+    }
+  }
+  writer.finalize().ok();
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::time::Instant;
+
+  use super::*;
+  use crate::testing::TestContext;
+  use crate::Event;
+
+  /// Wait until the writer thread finishes the file, and read its samples.
+  fn read_wav(path: &PathBuf, expected_len: usize) -> Vec<f32> {
+    let start = Instant::now();
+    loop {
+      if let Ok(mut reader) = hound::WavReader::open(path) {
+        if reader.len() as usize == expected_len || start.elapsed() > Duration::from_secs(5) {
+          return reader
+            .samples::<f32>()
+            .map(|sample| sample.unwrap())
+            .collect();
+        }
+      }
+      thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  #[test]
+  fn record() {
+    let path = std::env::temp_dir().join(format!("kiro-engine-record-{}.wav", std::process::id()));
+    let mut recorder = FileRecorderProcessor::create(&path, 2, 1_000).unwrap();
+    let mut context = TestContext::new(&recorder.descriptor(), 4);
+    context.set_input(0, 0, &[0.1, 0.2, 0.3, 0.4]);
+    context.set_input(0, 1, &[-0.1, -0.2, -0.3, -0.4]);
+
+    context.render(&mut recorder);
+    assert!(!recorder.is_recording());
+
+    context.set_parameter(FileRecorderProcessor::RECORD_INDEX, 1.0);
+    context.render(&mut recorder);
+    assert!(recorder.is_recording());
+
+    context.set_input(0, 0, &[0.5, 0.6, 0.7, 0.8]);
+    context.render(&mut recorder);
+
+    context.set_parameter(FileRecorderProcessor::RECORD_INDEX, 0.0);
+    context.render(&mut recorder);
+    drop(recorder);
+
+    let samples = read_wav(&path, 16);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(
+      samples,
+      vec![0.1, -0.1, 0.2, -0.2, 0.3, -0.3, 0.4, -0.4, 0.5, -0.1, 0.6, -0.2, 0.7, -0.3, 0.8, -0.4]
+    );
+  }
+
+  #[test]
+  fn record_with_transport_events() {
+    let path = std::env::temp_dir().join(format!(
+      "kiro-engine-record-events-{}.wav",
+      std::process::id()
+    ));
+    let mut recorder = FileRecorderProcessor::create(&path, 1, 1_000).unwrap();
+    let mut context = TestContext::new(&recorder.descriptor(), 2);
+    context.set_input(0, 0, &[0.1, 0.2]);
+
+    let transport = |message| Event {
+      timestamp: 0,
+      data: EventData::Transport(message),
+    };
+    context.set_events(0, &[transport(TransportMessage::Start)]);
+    context.render(&mut recorder);
+    assert!(recorder.is_recording());
+
+    context.set_events(0, &[transport(TransportMessage::Stop)]);
+    context.render(&mut recorder);
+    assert!(!recorder.is_recording());
+    drop(recorder);
+
+    let samples = read_wav(&path, 2);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(samples, vec![0.1, 0.2]);
+  }
+}
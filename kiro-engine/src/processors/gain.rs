@@ -0,0 +1,130 @@
+use kiro_dsp::gain::Gain;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Applies a gain in decibels to all the channels of its input.
+pub struct GainProcessor {
+  channels: usize,
+  gain: CachedGain,
+}
+
+impl GainProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const GAIN_INDEX: usize = 0;
+
+  pub fn new(channels: usize) -> Self {
+    Self {
+      channels,
+      gain: CachedGain::default(),
+    }
+  }
+
+  fn descriptor_with_channels(channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, channels)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, channels)])
+      })
+      .with_parameters(vec![gain_descriptor("gain")])
+  }
+}
+
+impl Processor for GainProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(self.channels)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let gains = context.parameter_buffer(Self::GAIN_INDEX);
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    for channel in 0..input.len().min(output.len()) {
+      let input = input.channel(channel);
+      let mut output = output.channel_mut(channel);
+      for ((out, sample), gain) in output.iter_mut().zip(input.iter()).zip(gains.iter()) {
+        *out = sample * self.gain.amplitude(*gain);
+      }
+    }
+  }
+}
+
+/// Descriptor for a gain in decibels, where the minimum means silence.
+pub(crate) fn gain_descriptor(id: &str) -> ParamDescriptor {
+  ParamDescriptor::new(id)
+    .min(CachedGain::MIN_DECIBELS)
+    .max(CachedGain::MAX_DECIBELS)
+    .unit(ParamUnit::Decibels)
+    .precision(1)
+}
+
+/// Conversion from decibels to amplitude with [`Gain`], only recalculated when the decibels change.
+///
+/// The parameters are already smoothed, so the smoothing of [`Gain`] is not needed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CachedGain {
+  decibels: f32,
+  amplitude: f32,
+}
+
+impl CachedGain {
+  pub const MIN_DECIBELS: f32 = Gain::<f32>::MIN_DECIBELS as f32;
+  pub const MAX_DECIBELS: f32 = 24.0;
+
+  pub fn amplitude(&mut self, decibels: f32) -> f32 {
+    if decibels != self.decibels {
+      self.decibels = decibels;
+      self.amplitude = Gain::to_amplitude(decibels);
+    }
+    self.amplitude
+  }
+}
+
+impl Default for CachedGain {
+  fn default() -> Self {
+    Self {
+      decibels: 0.0,
+      amplitude: 1.0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+  use kiro_dsp::funcs::decibels::db_to_lin;
+
+  #[test]
+  fn apply_gain() {
+    let mut processor = GainProcessor::new(2);
+    let mut context = TestContext::new(&processor.descriptor(), 2);
+    context.set_input(0, 0, &[1.0, -0.5]);
+    context.set_input(0, 1, &[0.25, 0.0]);
+    context.set_parameter(GainProcessor::GAIN_INDEX, 6.0);
+
+    context.render(&mut processor);
+    let expected = db_to_lin(6.0);
+    assert_eq!(context.output(0, 0), vec![expected, -0.5 * expected]);
+    assert_eq!(context.output(0, 1), vec![0.25 * expected, 0.0]);
+
+    context.set_parameter(GainProcessor::GAIN_INDEX, CachedGain::MIN_DECIBELS);
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0), vec![0.0, 0.0]);
+  }
+}
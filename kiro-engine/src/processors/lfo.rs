@@ -0,0 +1,252 @@
+use kiro_dsp::oscillators::lfo::{Lfo, Rate, Shape};
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamScale, ParamUnit, Processor};
+
+/// Waveforms of an [`LfoProcessor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+  Sine,
+  Triangle,
+  Saw,
+  Square,
+  /// A random value held along every cycle
+  SampleAndHold,
+  /// A random value every cycle, with smooth transitions between them
+  SmoothRandom,
+}
+
+impl LfoShape {
+  pub const ALL: [LfoShape; 6] = [
+    LfoShape::Sine,
+    LfoShape::Triangle,
+    LfoShape::Saw,
+    LfoShape::Square,
+    LfoShape::SampleAndHold,
+    LfoShape::SmoothRandom,
+  ];
+
+  pub fn from_value(value: f32) -> Self {
+    let index = value.round().max(0.0) as usize;
+    Self::ALL[index.min(Self::ALL.len() - 1)]
+  }
+
+  pub fn value(&self) -> f32 {
+    Self::ALL
+      .iter()
+      .position(|shape| shape == self)
+      .unwrap_or(0) as f32
+  }
+
+  pub fn label(&self) -> &'static str {
+    match self {
+      LfoShape::Sine => "sine",
+      LfoShape::Triangle => "triangle",
+      LfoShape::Saw => "saw",
+      LfoShape::Square => "square",
+      LfoShape::SampleAndHold => "s&h",
+      LfoShape::SmoothRandom => "smooth",
+    }
+  }
+
+  fn shape(&self) -> Shape {
+    match self {
+      LfoShape::Sine => Shape::Sine,
+      LfoShape::Triangle => Shape::Triangle,
+      LfoShape::Saw => Shape::Saw,
+      LfoShape::Square => Shape::Square,
+      LfoShape::SampleAndHold => Shape::SampleAndHold,
+      LfoShape::SmoothRandom => Shape::SmoothRandom,
+    }
+  }
+}
+
+/// Low frequency oscillator producing a bipolar signal scaled by its depth,
+/// to modulate the parameters of other nodes through [`crate::ProcessorNode::modulate_parameter`].
+///
+/// The rate is set in hertz, or as a note length when synced to the tempo of the transport.
+pub struct LfoProcessor {
+  lfo: Lfo<f32>,
+}
+
+impl LfoProcessor {
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const SHAPE_INDEX: usize = 0;
+  pub const RATE_INDEX: usize = 1;
+  pub const DEPTH_INDEX: usize = 2;
+  pub const SYNC_INDEX: usize = 3;
+  pub const DIVISION_INDEX: usize = 4;
+
+  /// Note lengths of a cycle when synced to the tempo, together with their duration in beats
+  pub const DIVISIONS: [(&'static str, f64); 7] = [
+    ("1/16", 0.25),
+    ("1/8", 0.5),
+    ("1/4", 1.0),
+    ("1/2", 2.0),
+    ("1/1", 4.0),
+    ("2/1", 8.0),
+    ("4/1", 16.0),
+  ];
+
+  pub fn new() -> Self {
+    Self { lfo: Lfo::new(1.0) }
+  }
+
+  /// Rate for the rate and sync parameters.
+  fn rate(&self, context: &ProcessorContext) -> Rate<f32> {
+    if context.parameter(Self::SYNC_INDEX).get() >= 0.5 {
+      let division = context
+        .parameter(Self::DIVISION_INDEX)
+        .get()
+        .round()
+        .max(0.0) as usize;
+      let (_, beats) = Self::DIVISIONS[division.min(Self::DIVISIONS.len() - 1)];
+      Rate::Synced(beats as f32)
+    } else {
+      Rate::Free(context.parameter(Self::RATE_INDEX).get())
+    }
+  }
+}
+
+impl Default for LfoProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for LfoProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 1)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("shape").values(
+          LfoShape::ALL
+            .iter()
+            .map(|shape| shape.label())
+            .collect::<Vec<_>>(),
+        ),
+        ParamDescriptor::new("rate")
+          .initial(1.0)
+          .min(0.01)
+          .max(50.0)
+          .unit(ParamUnit::Hertz)
+          .scale(ParamScale::Logarithmic),
+        ParamDescriptor::new("depth")
+          .initial(1.0)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("sync").values(vec!["off", "on"]),
+        ParamDescriptor::new("division")
+          .values(
+            Self::DIVISIONS
+              .iter()
+              .map(|(label, _)| *label)
+              .collect::<Vec<_>>(),
+          )
+          .initial(2.0),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.lfo.set_sample_rate(sample_rate as f32);
+  }
+
+  fn reset(&mut self) {
+    self.lfo.reset();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let shape = LfoShape::from_value(context.parameter(Self::SHAPE_INDEX).get());
+    self.lfo.set_shape(shape.shape());
+    self
+      .lfo
+      .set_depth(context.parameter(Self::DEPTH_INDEX).get());
+    self.lfo.set_tempo(context.transport().tempo());
+    let rate = self.rate(context);
+    self.lfo.set_rate(rate);
+
+    let mut output = context.audio_output(Self::AUDIO_OUT_INDEX).channel_mut(0);
+    for sample in output.iter_mut() {
+      *sample = self.lfo.generate();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_time::Tempo;
+
+  use super::*;
+  use crate::testing::TestContext;
+  use crate::{Transport, TransportMessage};
+
+  fn lfo(shape: LfoShape) -> (LfoProcessor, TestContext) {
+    let mut lfo = LfoProcessor::new();
+    lfo.prepare(8, 8);
+    lfo.reset();
+    let mut context = TestContext::new(&lfo.descriptor(), 8);
+    context.set_parameter(LfoProcessor::SHAPE_INDEX, shape.value());
+    (lfo, context)
+  }
+
+  #[test]
+  fn generate_shapes() {
+    let (mut processor, mut context) = lfo(LfoShape::Square);
+    context.set_parameter(LfoProcessor::RATE_INDEX, 2.0);
+    context.set_parameter(LfoProcessor::DEPTH_INDEX, 0.5);
+    context.render(&mut processor);
+    assert_eq!(
+      context.output(0, 0),
+      vec![0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, -0.5]
+    );
+
+    let (mut processor, mut context) = lfo(LfoShape::Saw);
+    context.render(&mut processor);
+    assert_eq!(
+      context.output(0, 0),
+      vec![-1.0, -0.75, -0.5, -0.25, 0.0, 0.25, 0.5, 0.75]
+    );
+
+    let (mut processor, mut context) = lfo(LfoShape::Triangle);
+    context.render(&mut processor);
+    assert_eq!(
+      context.output(0, 0),
+      vec![-1.0, -0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5]
+    );
+  }
+
+  #[test]
+  fn hold_random_values_along_cycles() {
+    let (mut processor, mut context) = lfo(LfoShape::SampleAndHold);
+    context.set_parameter(LfoProcessor::RATE_INDEX, 2.0);
+    context.render(&mut processor);
+    let output = context.output(0, 0);
+    assert!(output[0..4].iter().all(|value| *value == output[0]));
+    assert!(output[4..8].iter().all(|value| *value == output[4]));
+    assert_ne!(output[0], output[4]);
+    assert!(output.iter().all(|value| (-1.0..=1.0).contains(value)));
+  }
+
+  #[test]
+  fn sync_to_the_tempo() {
+    let (mut processor, mut context) = lfo(LfoShape::Square);
+    let mut transport = Transport::new();
+    transport.apply(&TransportMessage::Tempo(Tempo::new(240)));
+    context.set_transport(transport);
+    context.set_parameter(LfoProcessor::SYNC_INDEX, 1.0);
+    // a cycle every beat of a quarter of a second
+    context.set_parameter(LfoProcessor::DIVISION_INDEX, 2.0);
+    context.render(&mut processor);
+    assert_eq!(
+      context.output(0, 0),
+      vec![1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0]
+    );
+  }
+}
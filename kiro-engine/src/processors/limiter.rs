@@ -0,0 +1,149 @@
+use kiro_dsp::dynamics::Limiter;
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Stereo brickwall limiter with lookahead, to keep a bus such as the master one from clipping.
+///
+/// The output is delayed by the lookahead, which is reported as the latency of its descriptor
+/// for the sample rate it was created with.
+pub struct LimiterProcessor {
+  limiter: Limiter<f32>,
+  ceiling: f32,
+  release: f32,
+}
+
+impl LimiterProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const CEILING_INDEX: usize = 0;
+  pub const RELEASE_INDEX: usize = 1;
+
+  const DEFAULT_CEILING: f32 = -0.3;
+
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      limiter: Self::limiter(sample_rate),
+      ceiling: f32::NAN,
+      release: f32::NAN,
+    }
+  }
+
+  /// Samples the output is delayed from the input.
+  pub fn latency(&self) -> usize {
+    self.limiter.latency()
+  }
+
+  /// Current gain reduction in decibels, as a positive value.
+  pub fn reduction(&self) -> f32 {
+    self.limiter.reduction()
+  }
+
+  fn limiter(sample_rate: f32) -> Limiter<f32> {
+    Limiter::new(sample_rate, 2, Limiter::<f32>::DEFAULT_LOOKAHEAD as f32)
+  }
+}
+
+impl Processor for LimiterProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 2)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("ceiling")
+          .initial(Self::DEFAULT_CEILING)
+          .min(-24.0)
+          .max(0.0)
+          .unit(ParamUnit::Decibels)
+          .precision(1),
+        ParamDescriptor::new("release")
+          .initial(Limiter::<f32>::DEFAULT_RELEASE as f32)
+          .min(0.001)
+          .max(1.0)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+      ])
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::static_descriptor().with_latency(self.latency())
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.limiter = Self::limiter(sample_rate as f32);
+    self.ceiling = f32::NAN;
+    self.release = f32::NAN;
+  }
+
+  fn reset(&mut self) {
+    self.limiter.reset();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let ceiling = context.parameter(Self::CEILING_INDEX).get();
+    if ceiling != self.ceiling {
+      self.ceiling = ceiling;
+      self.limiter.set_ceiling(ceiling);
+    }
+
+    let release = context.parameter(Self::RELEASE_INDEX).get();
+    if release != self.release {
+      self.release = release;
+      self.limiter.set_release(release);
+    }
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let outputs = output_left.iter_mut().zip(output_right.iter_mut());
+    let inputs = input_left.iter().zip(input_right.iter());
+    for ((out_left, out_right), (left, right)) in outputs.zip(inputs) {
+      let mut frame = [*left, *right];
+      self.limiter.process(&mut frame);
+      *out_left = frame[0];
+      *out_right = frame[1];
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::ProcessorHarness;
+
+  #[test]
+  fn limit_the_output_with_latency() {
+    let limiter = LimiterProcessor::new(44_100.0);
+    assert_eq!(limiter.latency(), 221);
+    assert_eq!(limiter.descriptor().latency, 221);
+
+    let input = (0..1024)
+      .map(|index| 2.0 * (index as f32 * 0.1).sin())
+      .collect::<Vec<_>>();
+    let mut harness = ProcessorHarness::new(limiter).with_block_size(256);
+    harness
+      .feed_audio(LimiterProcessor::AUDIO_IN_INDEX, 0, &input)
+      .set_parameter(0, LimiterProcessor::CEILING_INDEX, -6.0);
+    let output = harness.render(4);
+    let left = output.channel(LimiterProcessor::AUDIO_OUT_INDEX, 0);
+    assert!(left[..221].iter().all(|sample| *sample == 0.0));
+    assert!(left[221..].iter().any(|sample| *sample != 0.0));
+    let ceiling = 10.0f32.powf(-6.0 / 20.0);
+    assert!(left.iter().all(|sample| sample.abs() <= ceiling + 1e-6));
+    assert!(harness.processor().reduction() > 6.0);
+  }
+}
@@ -0,0 +1,95 @@
+use crate::processor::ProcessorContext;
+use crate::processors::gain::{gain_descriptor, CachedGain};
+use crate::{AudioDescriptor, NodeDescriptor, Processor};
+
+/// Sums a number of inputs into a single output, each one with its own level in decibels.
+pub struct MixerProcessor {
+  channels: usize,
+  levels: Vec<CachedGain>,
+}
+
+impl MixerProcessor {
+  /// Prefix of the inputs, as in `audio-in-0`, `audio-in-1`, ...
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub fn new(inputs: usize, channels: usize) -> Self {
+    Self {
+      channels,
+      levels: vec![CachedGain::default(); inputs],
+    }
+  }
+
+  fn descriptor_with(inputs: usize, channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs_cardinality(inputs, AudioDescriptor::new(Self::AUDIO_IN_NAME, channels))
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, channels)])
+      })
+      .with_parameters(
+        (0..inputs)
+          .map(|index| gain_descriptor(&format!("level-{}", index)))
+          .collect(),
+      )
+  }
+}
+
+impl Processor for MixerProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(2, 2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(self.levels.len(), self.channels)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    for channel in 0..output.len() {
+      output.channel_mut(channel).fill(0.0);
+    }
+
+    let num_inputs = context.num_audio_inputs().min(self.levels.len());
+    for (index, level) in self.levels.iter_mut().take(num_inputs).enumerate() {
+      let levels = context.parameter_buffer(index);
+      let input = context.audio_input(index);
+      for channel in 0..input.len().min(output.len()) {
+        let input = input.channel(channel);
+        let mut output = output.channel_mut(channel);
+        for ((out, sample), decibels) in output.iter_mut().zip(input.iter()).zip(levels.iter()) {
+          *out += sample * level.amplitude(*decibels);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+
+  #[test]
+  fn mix_inputs() {
+    let mut processor = MixerProcessor::new(3, 1);
+    let descriptor = processor.descriptor();
+    assert_eq!(descriptor.parameters[2].id, "level-2");
+
+    let mut context = TestContext::new(&descriptor, 2);
+    context.set_input(0, 0, &[1.0, 0.5]);
+    context.set_input(1, 0, &[0.25, 0.25]);
+    context.set_input(2, 0, &[4.0, 4.0]);
+    context.set_parameter(2, CachedGain::MIN_DECIBELS);
+
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0), vec![1.25, 0.75]);
+  }
+}
@@ -0,0 +1,46 @@
+pub mod analyzer;
+pub mod arpeggiator;
+pub mod audio_file;
+pub mod builtin;
+pub mod chorus;
+pub mod compressor;
+pub mod delay;
+pub mod envelope_follower;
+// the disk is streamed from threads, which are not available in the web
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_player;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_recorder;
+pub mod gain;
+pub mod lfo;
+pub mod limiter;
+pub mod mixer;
+pub mod null;
+pub mod pan;
+pub mod resampled;
+pub mod reverb;
+pub mod splitter;
+pub mod voice_allocator;
+
+pub use analyzer::{Analysis, AnalysisReader, AnalyzerProcessor};
+pub use arpeggiator::{ArpeggiatorMode, ArpeggiatorProcessor};
+pub use audio_file::{read_mono_samples, read_samples, AudioFileError};
+pub use builtin::{builtin_processor, BUILTIN_PROCESSORS};
+pub use chorus::ChorusProcessor;
+pub use compressor::CompressorProcessor;
+pub use delay::DelayProcessor;
+pub use envelope_follower::EnvelopeFollowerProcessor;
+#[cfg(not(target_arch = "wasm32"))]
+pub use file_player::FilePlayerProcessor;
+#[cfg(not(target_arch = "wasm32"))]
+pub use file_recorder::FileRecorderProcessor;
+pub use gain::GainProcessor;
+pub use lfo::{LfoProcessor, LfoShape};
+pub use limiter::LimiterProcessor;
+pub use mixer::MixerProcessor;
+pub use null::NullProcessor;
+pub use pan::StereoPannerProcessor;
+pub use resampled::ResampledProcessor;
+pub use reverb::ReverbProcessor;
+pub use splitter::SplitterProcessor;
+pub use voice_allocator::{NotePriority, VoiceAllocatorProcessor, VoiceStealing};
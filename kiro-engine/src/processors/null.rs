@@ -0,0 +1,39 @@
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, Processor};
+
+/// Sink that discards its input, to mute a signal or terminate a branch of the graph.
+pub struct NullProcessor {
+  channels: usize,
+}
+
+impl NullProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+
+  pub fn new(channels: usize) -> Self {
+    Self { channels }
+  }
+
+  fn descriptor_with_channels(channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new().with_audio_ports(|ports| {
+      ports.static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, channels)])
+    })
+  }
+}
+
+impl Processor for NullProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with_channels(self.channels)
+  }
+
+  fn render(&mut self, _context: &mut ProcessorContext) {}
+}
@@ -0,0 +1,102 @@
+use kiro_dsp::funcs::pan_law;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, Processor};
+
+/// Places a mono input in the stereo field using a constant power pan law,
+/// so the perceived loudness doesn't change while panning.
+pub struct StereoPannerProcessor {
+  pan: f32,
+  left: f32,
+  right: f32,
+}
+
+impl StereoPannerProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  /// From `-1` (left) to `1` (right)
+  pub const PAN_INDEX: usize = 0;
+
+  pub fn new() -> Self {
+    let mut processor = Self {
+      pan: 0.0,
+      left: 0.0,
+      right: 0.0,
+    };
+    processor.update_pan(0.0);
+    processor
+  }
+
+  fn update_pan(&mut self, pan: f32) {
+    self.pan = pan;
+    let (left, right) = pan_law::equal_power(pan);
+    self.left = left;
+    self.right = right;
+  }
+}
+
+impl Default for StereoPannerProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for StereoPannerProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 1)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![ParamDescriptor::new("pan").min(-1.0).max(1.0)])
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let pans = context.parameter_buffer(Self::PAN_INDEX);
+    let input = context.audio_input(Self::AUDIO_IN_INDEX).channel(0);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let mut left = output.channel_mut(0);
+    let mut right = output.channel_mut(1);
+    let samples = left
+      .iter_mut()
+      .zip(right.iter_mut())
+      .zip(input.iter().zip(pans.iter()));
+    for ((left, right), (sample, pan)) in samples {
+      if *pan != self.pan {
+        self.update_pan(*pan);
+      }
+      *left = sample * self.left;
+      *right = sample * self.right;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+
+  #[test]
+  fn constant_power() {
+    let mut processor = StereoPannerProcessor::new();
+    let mut context = TestContext::new(&StereoPannerProcessor::static_descriptor(), 1);
+    context.set_input(0, 0, &[1.0]);
+
+    context.render(&mut processor);
+    let (left, right) = (context.output(0, 0)[0], context.output(0, 1)[0]);
+    assert!((left - right).abs() < 1e-6);
+    assert!((left * left + right * right - 1.0).abs() < 1e-6);
+
+    context.set_parameter(StereoPannerProcessor::PAN_INDEX, -1.0);
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0), vec![1.0]);
+    assert!(context.output(0, 1)[0].abs() < 1e-6);
+  }
+}
@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use kiro_dsp::resampler::Resampler;
+use kiro_time::SampleRate;
+
+use crate::processor::ports::audio::AudioPort;
+use crate::processor::ports::param::{ParamData, ParamRenderPort};
+use crate::processor::ports::{Input, Output};
+use crate::processor::{ProcessorContext, SharedState, StateError};
+use crate::rendering::buffers::audio::AudioBuffer;
+use crate::rendering::owned_data::{OwnedData, Ref};
+use crate::{NodeDescriptor, ParamValue, Processor};
+
+/// Runs a processor at its own sample rate inside a graph rendering at another one,
+/// such as a hosted plugin or an impulse response that only work at some rate.
+///
+/// The audio inputs are resampled into the rate of the processor, and its outputs back into the rate
+/// of the graph, which delays them by the latency reported in the descriptor.
+/// The engine doesn't compensate it, so it is up to the application to align the outputs with other paths.
+/// The buffers of the parameters are held into the samples of the processor, keeping their automation and modulation,
+/// and the events keep their timestamps, so they land on the right sample of the blocks of the processor.
+///
+/// The engine creates it through [`crate::Module::create_resampled_processor`].
+pub struct ResampledProcessor<P> {
+  processor: P,
+  descriptor: NodeDescriptor,
+  sample_rate: SampleRate,
+  graph_rate: SampleRate,
+  /// Samples of the processor left to render, as the blocks don't match exactly between both rates
+  phase: f64,
+  max_block: usize,
+  parameters: Vec<Arc<ParamValue>>,
+  parameter_buffers: Vec<Ref<AudioBuffer>>,
+  parameter_ports: Vec<ParamRenderPort>,
+  input_buffers: Vec<Ref<AudioBuffer>>,
+  output_buffers: Vec<Ref<AudioBuffer>>,
+  audio_inputs: Vec<AudioPort<Input>>,
+  audio_outputs: Vec<AudioPort<Output>>,
+  input_resamplers: Vec<Resampler<f32>>,
+  output_resamplers: Vec<Resampler<f32>>,
+  /// Resampled samples by channel waiting to be rendered, or to be sent to the graph
+  input_pending: Vec<VecDeque<f32>>,
+  output_pending: Vec<VecDeque<f32>>,
+  scratch: Vec<f32>,
+}
+
+impl<P: Processor> ResampledProcessor<P> {
+  /// Wrap a processor to run at `sample_rate` inside a graph running at `graph_rate`.
+  pub fn new(processor: P, sample_rate: SampleRate, graph_rate: SampleRate) -> Self {
+    let descriptor = processor.descriptor();
+    let latency = Self::latency(descriptor.latency, sample_rate, graph_rate);
+    let descriptor = descriptor.with_latency(latency);
+    Self {
+      processor,
+      descriptor,
+      sample_rate,
+      graph_rate,
+      phase: 0.0,
+      max_block: 0,
+      parameters: Vec::new(),
+      parameter_buffers: Vec::new(),
+      parameter_ports: Vec::new(),
+      input_buffers: Vec::new(),
+      output_buffers: Vec::new(),
+      audio_inputs: Vec::new(),
+      audio_outputs: Vec::new(),
+      input_resamplers: Vec::new(),
+      output_resamplers: Vec::new(),
+      input_pending: Vec::new(),
+      output_pending: Vec::new(),
+      scratch: Vec::new(),
+    }
+  }
+
+  pub fn processor(&self) -> &P {
+    &self.processor
+  }
+
+  pub fn sample_rate(&self) -> SampleRate {
+    self.sample_rate
+  }
+
+  /// Samples of a rate needed in advance to never run out of them
+  /// when the blocks are resampled from another rate.
+  fn headroom(rate: SampleRate, other_rate: SampleRate) -> usize {
+    (f64::from(rate) / f64::from(other_rate)).ceil() as usize + 2
+  }
+
+  /// Delay of the outputs in samples of the graph, through the resamplers,
+  /// the headroom at both sides, and the processor itself.
+  fn latency(processor_latency: usize, sample_rate: SampleRate, graph_rate: SampleRate) -> usize {
+    let inner =
+      Self::headroom(sample_rate, graph_rate) + processor_latency + Resampler::<f32>::LATENCY;
+    let inner = (inner as f64 * f64::from(graph_rate) / f64::from(sample_rate)).ceil() as usize;
+    Resampler::<f32>::LATENCY + inner + Self::headroom(graph_rate, sample_rate)
+  }
+
+  /// Hold the samples of a block of the graph into the samples of a block of the processor.
+  fn hold(outer: &[f32], inner: &mut [f32]) {
+    if let Some(last) = outer.len().checked_sub(1) {
+      let step = outer.len() as f64 / inner.len() as f64;
+      for (index, sample) in inner.iter_mut().enumerate() {
+        *sample = outer[((index as f64 * step) as usize).min(last)];
+      }
+    }
+  }
+
+  fn prime(pending: &mut [VecDeque<f32>], headroom: usize) {
+    for channel in pending.iter_mut() {
+      channel.clear();
+      channel.resize(headroom, 0.0);
+    }
+  }
+}
+
+impl<P: Processor> Processor for ResampledProcessor<P> {
+  fn descriptor(&self) -> NodeDescriptor {
+    self.descriptor.clone()
+  }
+
+  fn prepare(&mut self, graph_rate: SampleRate, max_block: usize) {
+    self.graph_rate = graph_rate;
+    let (rate, graph) = (self.sample_rate as f32, graph_rate as f32);
+    self.max_block =
+      (max_block as f64 * f64::from(self.sample_rate) / f64::from(graph_rate)).ceil() as usize + 1;
+
+    let mut buffers = OwnedData::new();
+    let max_len = self.max_block;
+    let mut new_buffer = || {
+      let key = buffers.add(AudioBuffer::with_capacity(max_len));
+      buffers.get(key).unwrap()
+    };
+
+    self.parameters = self
+      .descriptor
+      .parameters
+      .iter()
+      .map(|param| Arc::new(ParamValue::new(param.initial)))
+      .collect();
+    self.parameter_buffers = self.parameters.iter().map(|_| new_buffer()).collect();
+    self.parameter_ports = self
+      .parameter_buffers
+      .iter()
+      .map(|buffer| ParamRenderPort::new(ParamData::from_output(buffer.clone())))
+      .collect();
+
+    let ports = &self.descriptor.ports.audio;
+    let input_channels = ports.static_inputs.iter().map(|port| port.channels());
+    let output_channels = ports.static_outputs.iter().map(|port| port.channels());
+    let input_buffers = input_channels
+      .map(|channels| (0..channels).map(|_| new_buffer()).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+    let output_buffers = output_channels
+      .map(|channels| (0..channels).map(|_| new_buffer()).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+    self.audio_inputs = input_buffers
+      .iter()
+      .map(|channels| AudioPort::new(channels.clone()))
+      .collect();
+    self.audio_outputs = output_buffers
+      .iter()
+      .map(|channels| AudioPort::new(channels.clone()))
+      .collect();
+    self.input_buffers = input_buffers.into_iter().flatten().collect();
+    self.output_buffers = output_buffers.into_iter().flatten().collect();
+
+    self.input_resamplers = self
+      .input_buffers
+      .iter()
+      .map(|_| Resampler::new(graph, rate))
+      .collect();
+    self.output_resamplers = self
+      .output_buffers
+      .iter()
+      .map(|_| Resampler::new(rate, graph))
+      .collect();
+
+    let scratch_len = self
+      .input_resamplers
+      .iter()
+      .map(|resampler| resampler.max_output_len(max_block))
+      .chain(
+        self
+          .output_resamplers
+          .iter()
+          .map(|resampler| resampler.max_output_len(self.max_block)),
+      )
+      .max()
+      .unwrap_or(0);
+    self.scratch = vec![0.0; scratch_len];
+    // the headroom plus the samples of a block, with room to spare for the ones left from the previous
+    let pending_capacity = 2 * (scratch_len + self.max_block.max(max_block));
+    self.input_pending = vec![VecDeque::with_capacity(pending_capacity); self.input_buffers.len()];
+    self.output_pending =
+      vec![VecDeque::with_capacity(pending_capacity); self.output_buffers.len()];
+
+    self.processor.prepare(self.sample_rate, self.max_block);
+    let latency = Self::latency(
+      self.processor.descriptor().latency,
+      self.sample_rate,
+      graph_rate,
+    );
+    self.descriptor.latency = latency;
+  }
+
+  fn reset(&mut self) {
+    self.phase = 0.0;
+    for resampler in self
+      .input_resamplers
+      .iter_mut()
+      .chain(self.output_resamplers.iter_mut())
+    {
+      resampler.reset();
+    }
+    Self::prime(
+      &mut self.input_pending,
+      Self::headroom(self.sample_rate, self.graph_rate),
+    );
+    Self::prime(
+      &mut self.output_pending,
+      Self::headroom(self.graph_rate, self.sample_rate),
+    );
+    self.processor.reset();
+  }
+
+  fn state(&self) -> Option<SharedState> {
+    self.processor.state()
+  }
+
+  fn set_state(&mut self, state: &mut SharedState) -> Result<(), StateError> {
+    self.processor.set_state(state)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let num_samples = context.num_samples();
+    self.phase += num_samples as f64 * f64::from(self.sample_rate) / f64::from(self.graph_rate);
+    let inner_samples = (self.phase.floor() as usize).min(self.max_block);
+    self.phase -= inner_samples as f64;
+
+    for (value, outer) in self.parameters.iter().zip(context.parameters()) {
+      value.set(outer.get());
+    }
+    let num_parameters = context.parameters().len().min(self.parameter_buffers.len());
+    for (index, buffer) in self.parameter_buffers[0..num_parameters].iter().enumerate() {
+      let inner = &mut buffer.get_mut().as_mut_slice()[0..inner_samples];
+      Self::hold(context.parameter_buffer(index), inner);
+    }
+    for port in self.parameter_ports.iter_mut() {
+      port.set_num_samples(inner_samples);
+    }
+
+    let mut channel_index = 0;
+    for port in 0..context.num_audio_inputs().min(self.audio_inputs.len()) {
+      let input = context.audio_input(port);
+      for channel in 0..input.len() {
+        let pending = &mut self.input_pending[channel_index];
+        let (_, written) = self.input_resamplers[channel_index]
+          .process_slice(input.channel(channel).as_slice(), &mut self.scratch);
+        pending.extend(self.scratch[0..written].iter());
+        let buffer = self.input_buffers[channel_index].get_mut().as_mut_slice();
+        for sample in buffer.iter_mut().take(inner_samples) {
+          *sample = pending.pop_front().unwrap_or(0.0);
+        }
+        channel_index += 1;
+      }
+    }
+    for port in self.audio_inputs.iter_mut() {
+      port.set_num_samples(inner_samples);
+    }
+    for port in self.audio_outputs.iter_mut() {
+      port.set_num_samples(inner_samples);
+    }
+
+    let mut inner = ProcessorContext::new(
+      inner_samples,
+      self.parameters.as_slice(),
+      self.parameter_ports.as_slice(),
+      self.audio_inputs.as_slice(),
+      self.audio_outputs.as_slice(),
+      context.events_inputs(),
+      context.events_outputs(),
+    )
+    .with_timing(context.block_timestamp(), self.sample_rate)
+    .with_transport(*context.transport());
+    self.processor.render(&mut inner);
+
+    let mut channel_index = 0;
+    for port in 0..context.num_audio_outputs().min(self.audio_outputs.len()) {
+      let output = context.audio_output(port);
+      for channel in 0..output.len() {
+        let pending = &mut self.output_pending[channel_index];
+        let rendered = &self.output_buffers[channel_index].as_slice()[0..inner_samples];
+        let (_, written) =
+          self.output_resamplers[channel_index].process_slice(rendered, &mut self.scratch);
+        pending.extend(self.scratch[0..written].iter());
+        for sample in output.channel_mut(channel).iter_mut() {
+          *sample = pending.pop_front().unwrap_or(0.0);
+        }
+        channel_index += 1;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::ProcessorHarness;
+  use crate::{AudioDescriptor, EventData, EventsDescriptor};
+
+  /// Counts the samples it renders, and outputs the ones of its input plus the value of a parameter.
+  struct CountingProcessor {
+    rendered: usize,
+    sample_rate: SampleRate,
+    events: usize,
+    offsets: Vec<f32>,
+  }
+
+  impl Processor for CountingProcessor {
+    fn static_descriptor() -> NodeDescriptor
+    where
+      Self: Sized,
+    {
+      NodeDescriptor::new()
+        .with_audio_ports(|ports| {
+          ports
+            .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+            .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+        })
+        .with_events_ports(|ports| ports.static_inputs(vec![EventsDescriptor::new("events-in")]))
+        .with_parameters(vec![crate::ParamDescriptor::new("offset")])
+    }
+
+    fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+      self.sample_rate = sample_rate;
+    }
+
+    fn render(&mut self, context: &mut ProcessorContext) {
+      self.rendered += context.num_samples();
+      self.events += context.events_input(0).iter().count();
+      let offsets = context.parameter_buffer(0);
+      self.offsets.extend_from_slice(offsets);
+      let input = context.audio_input(0).channel(0);
+      let mut output = context.audio_output(0).channel_mut(0);
+      for ((output, input), offset) in output.iter_mut().zip(input.iter()).zip(offsets) {
+        *output = *input + offset;
+      }
+    }
+  }
+
+  fn resampled(sample_rate: SampleRate) -> ProcessorHarness<ResampledProcessor<CountingProcessor>> {
+    let processor = CountingProcessor {
+      rendered: 0,
+      sample_rate: 0,
+      events: 0,
+      offsets: Vec::new(),
+    };
+    ProcessorHarness::new(ResampledProcessor::new(processor, sample_rate, 48_000))
+      .with_sample_rate(48_000)
+      .with_block_size(64)
+  }
+
+  #[test]
+  fn render_at_the_rate_of_the_processor() {
+    let mut harness = resampled(24_000);
+    harness.send_event(
+      0,
+      crate::Event {
+        timestamp: 0,
+        data: EventData::Transport(crate::TransportMessage::Start),
+      },
+    );
+    harness.render(100);
+    let processor = harness.processor().processor();
+    assert_eq!(processor.sample_rate, 24_000);
+    assert_eq!(processor.rendered, 3200);
+    assert_eq!(processor.events, 1);
+  }
+
+  #[test]
+  fn delay_the_outputs_by_the_latency() {
+    let mut harness = resampled(32_000);
+    let latency = harness.processor().descriptor().latency;
+    harness.feed_audio(0, 0, &[0.25; 64 * 20]);
+    let output = harness.render(20).channel(0, 0).to_vec();
+    // the step of the input reaches half way around the latency, as the resamplers interpolate it
+    assert!(output[0..latency - 4]
+      .iter()
+      .all(|sample| sample.abs() < 1e-2));
+    assert!(output[latency + 2] > 0.125);
+    // and the constant input passes through untouched once they settle
+    assert!(output[latency + 64..]
+      .iter()
+      .all(|sample| (sample - 0.25).abs() < 1e-3));
+  }
+
+  #[test]
+  fn follow_the_parameters() {
+    let mut harness = resampled(96_000);
+    harness.set_parameter(0, 0, 0.5).set_parameter(10, 0, -0.5);
+    let output = harness.render(20).channel(0, 0).to_vec();
+    assert!((output[64 * 9] - 0.5).abs() < 1e-3);
+    assert!((output[64 * 20 - 1] + 0.5).abs() < 1e-3);
+  }
+
+  #[test]
+  fn hold_the_automation_of_the_parameters() {
+    let mut harness = resampled(24_000);
+    let timestamp = harness.sample_timestamp(63);
+    harness.send_event(
+      0,
+      crate::Event {
+        timestamp,
+        data: EventData::Automation(crate::AutomationData::new(
+          crate::ParamRef(0),
+          1.0,
+          crate::AutomationCurve::Linear,
+        )),
+      },
+    );
+    harness.render(2);
+    let offsets = &harness.processor().processor().offsets;
+    assert_eq!(offsets.len(), 64);
+    // the ramp along the first block reaches the processor at half the samples
+    assert_eq!(offsets[0], 0.0);
+    assert!((offsets[16] - 0.5).abs() < 0.02);
+    assert!(offsets[0..32].windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(offsets[32..].iter().all(|offset| *offset == 1.0));
+  }
+
+  #[test]
+  fn update_the_latency_when_prepared_at_another_rate() {
+    let mut harness = resampled(32_000).with_sample_rate(96_000);
+    harness.render(1);
+    let latency = ResampledProcessor::<CountingProcessor>::latency(0, 32_000, 96_000);
+    assert_ne!(
+      latency,
+      ResampledProcessor::<CountingProcessor>::latency(0, 32_000, 48_000)
+    );
+    assert_eq!(harness.processor().descriptor().latency, latency);
+  }
+}
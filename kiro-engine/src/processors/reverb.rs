@@ -0,0 +1,142 @@
+use kiro_dsp::effects::reverb::FdnReverb;
+use kiro_time::SampleRate;
+
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Stereo algorithmic reverb, built on a feedback delay network.
+///
+/// It is fully wet by default, to be used in a send bus, but it can also be inserted in a chain
+/// by lowering its mix.
+pub struct ReverbProcessor {
+  reverb: FdnReverb<f32>,
+  decay: f32,
+  damping: f32,
+  size: f32,
+}
+
+impl ReverbProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const DECAY_INDEX: usize = 0;
+  pub const DAMPING_INDEX: usize = 1;
+  pub const SIZE_INDEX: usize = 2;
+  pub const MIX_INDEX: usize = 3;
+
+  pub fn new() -> Self {
+    Self {
+      reverb: FdnReverb::new(1.0),
+      decay: f32::NAN,
+      damping: f32::NAN,
+      size: f32::NAN,
+    }
+  }
+}
+
+impl Default for ReverbProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Processor for ReverbProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 2)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("decay")
+          .initial(2.0)
+          .min(0.1)
+          .max(20.0)
+          .unit(ParamUnit::Seconds),
+        ParamDescriptor::new("damping")
+          .initial(0.5)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("size")
+          .initial(0.7)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("mix")
+          .initial(1.0)
+          .unit(ParamUnit::Percent),
+      ])
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.reverb = FdnReverb::new(sample_rate as f32);
+    self.decay = f32::NAN;
+    self.damping = f32::NAN;
+    self.size = f32::NAN;
+  }
+
+  fn reset(&mut self) {
+    self.reverb.reset();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let decay = context.parameter(Self::DECAY_INDEX).get();
+    if decay != self.decay {
+      self.decay = decay;
+      self.reverb.set_decay_time_sec(decay);
+    }
+
+    let damping = context.parameter(Self::DAMPING_INDEX).get();
+    if damping != self.damping {
+      self.damping = damping;
+      self.reverb.set_damping(damping);
+    }
+
+    let size = context.parameter(Self::SIZE_INDEX).get();
+    if size != self.size {
+      self.size = size;
+      self.reverb.set_size(size);
+    }
+
+    let mix = context.parameter(Self::MIX_INDEX).get();
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let outputs = output_left.iter_mut().zip(output_right.iter_mut());
+    let inputs = input_left.iter().zip(input_right.iter());
+    for ((out_left, out_right), (left, right)) in outputs.zip(inputs) {
+      let (wet_left, wet_right) = self.reverb.process(*left, *right);
+      *out_left = left * (1.0 - mix) + wet_left * mix;
+      *out_right = right * (1.0 - mix) + wet_right * mix;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::ProcessorHarness;
+
+  #[test]
+  fn reverberate_an_impulse() {
+    let mut harness = ProcessorHarness::new(ReverbProcessor::new()).with_block_size(1000);
+    harness
+      .feed_audio(ReverbProcessor::AUDIO_IN_INDEX, 0, &[1.0])
+      .set_parameter(0, ReverbProcessor::DECAY_INDEX, 1.0);
+    let output = harness.render(10);
+    let left = output.channel(ReverbProcessor::AUDIO_OUT_INDEX, 0);
+    let right = output.channel(ReverbProcessor::AUDIO_OUT_INDEX, 1);
+    // fully wet, with the tail reaching both channels
+    assert_eq!(left[0], 0.0);
+    assert!(left.iter().any(|sample| *sample != 0.0));
+    assert!(right.iter().any(|sample| *sample != 0.0));
+    let rms = |samples: &[f32]| {
+      (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+    };
+    assert!(rms(&left[0..5000]) > rms(&left[5000..10000]));
+  }
+}
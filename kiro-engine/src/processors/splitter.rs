@@ -0,0 +1,59 @@
+use crate::processor::ProcessorContext;
+use crate::{AudioDescriptor, NodeDescriptor, Processor};
+
+/// Copies its input into a number of outputs.
+pub struct SplitterProcessor {
+  outputs: usize,
+  channels: usize,
+}
+
+impl SplitterProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  /// Prefix of the outputs, as in `audio-out-0`, `audio-out-1`, ...
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+
+  pub fn new(outputs: usize, channels: usize) -> Self {
+    Self { outputs, channels }
+  }
+
+  fn descriptor_with(outputs: usize, channels: usize) -> NodeDescriptor {
+    NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, channels)])
+        .static_outputs_cardinality(
+          outputs,
+          AudioDescriptor::new(Self::AUDIO_OUT_NAME, channels),
+        )
+    })
+  }
+}
+
+impl Processor for SplitterProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(2, 2)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(self.outputs, self.channels)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    for index in 0..context.num_audio_outputs() {
+      let output = context.audio_output(index);
+      for channel in 0..input.len().min(output.len()) {
+        output
+          .channel_mut(channel)
+          .as_mut_slice()
+          .copy_from_slice(input.channel(channel).as_slice());
+      }
+    }
+  }
+}
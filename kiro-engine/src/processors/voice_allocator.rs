@@ -0,0 +1,908 @@
+use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+use kiro_midi::messages::{Message, MessageType};
+use kiro_midi::TimestampNanos;
+
+use crate::processor::ProcessorContext;
+use crate::{
+  Event, EventData, EventsDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor,
+};
+
+/// Which voice plays a new note when all of them are busy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealing {
+  /// The voice that started playing first
+  Oldest,
+  /// The voice with the lowest level, from the velocity of its note and how far it is into its release
+  Quietest,
+}
+
+impl VoiceStealing {
+  pub fn from_value(value: f32) -> Self {
+    if value >= 0.5 {
+      VoiceStealing::Quietest
+    } else {
+      VoiceStealing::Oldest
+    }
+  }
+
+  pub fn value(&self) -> f32 {
+    match self {
+      VoiceStealing::Oldest => 0.0,
+      VoiceStealing::Quietest => 1.0,
+    }
+  }
+}
+
+/// Which of the held notes plays in the monophonic mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotePriority {
+  /// The last note played
+  Last,
+  /// The lowest note
+  Low,
+  /// The highest note
+  High,
+}
+
+impl NotePriority {
+  pub const ALL: [NotePriority; 3] = [NotePriority::Last, NotePriority::Low, NotePriority::High];
+
+  pub fn from_value(value: f32) -> Self {
+    let index = value.round().max(0.0) as usize;
+    Self::ALL[index.min(Self::ALL.len() - 1)]
+  }
+
+  pub fn value(&self) -> f32 {
+    Self::ALL
+      .iter()
+      .position(|priority| priority == self)
+      .unwrap_or(0) as f32
+  }
+}
+
+/// Distributes the notes of its events input among a number of voices, one per events output.
+///
+/// Every note on goes to a free voice, or steals a busy one according to the `stealing` parameter,
+/// preferring the voices sounding the release tail of their notes over the ones still held.
+/// A note played again retriggers the voice that was playing it, unless `retrigger` is off.
+/// Note offs and per-note messages follow the voice playing their note,
+/// while the rest of the events are sent to every voice.
+///
+/// The voices are considered to sound for `release-tail` seconds after their note off,
+/// which should match the release of their amplitude envelope.
+///
+/// With `mpe` on, the pitch bend, channel pressure and timbre (CC 74) messages
+/// of the member channels of MPE are converted into the per-note pitch bend, poly pressure
+/// and per-note timbre controller of the voices playing a note on those channels,
+/// while the ones of the manager channels (the first and the last) are sent to every voice.
+///
+/// Only the first `max-voices` voices are given new notes, while the ones above it play out
+/// the notes they already have, which lets the polyphony be reduced without cutting any note.
+///
+/// In the `mono` mode, only the first voice plays, the held note chosen by the `priority` parameter.
+/// When that note changes while others are held, the voice receives the note on of the new one
+/// without any note off in between, so it can slide into it without restarting its envelope.
+pub struct VoiceAllocatorProcessor {
+  voices: Vec<Voice>,
+  /// Notes held in the monophonic mode, in the order they were played, with their velocity
+  held_notes: Vec<(NoteId, u16)>,
+  /// Number of notes allocated so far, to know which voice started playing first
+  counter: u64,
+  /// Whether the previous block was in the monophonic mode, to release the voices when switching to it
+  mono: bool,
+}
+
+/// Group, channel and number of a note
+type NoteId = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+  note: Option<NoteId>,
+  /// Whether the note is still held, rather than sounding its release tail
+  held: bool,
+  velocity: u16,
+  started: u64,
+  released: TimestampNanos,
+}
+
+impl Voice {
+  fn is_free(&self, timestamp: TimestampNanos, release_tail: TimestampNanos) -> bool {
+    self.note.is_none() || (!self.held && timestamp >= self.released.saturating_add(release_tail))
+  }
+
+  /// Estimated level, fading out linearly along the release tail.
+  fn level(&self, timestamp: TimestampNanos, release_tail: TimestampNanos) -> f32 {
+    let velocity = f32::from(self.velocity) / f32::from(u16::MAX);
+    if self.held {
+      velocity
+    } else if release_tail == 0 {
+      0.0
+    } else {
+      let elapsed = timestamp.saturating_sub(self.released) as f32 / release_tail as f32;
+      velocity * (1.0 - elapsed).max(0.0)
+    }
+  }
+}
+
+/// Allocation settings read from the parameters at every block
+struct Settings {
+  mpe: bool,
+  mono: bool,
+  priority: NotePriority,
+  stealing: VoiceStealing,
+  retrigger: bool,
+  release_tail: TimestampNanos,
+  max_voices: usize,
+}
+
+enum Route {
+  NoteOn(NoteId, u16),
+  NoteOff(NoteId),
+  PerNote(NoteId),
+  All,
+}
+
+impl VoiceAllocatorProcessor {
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const EVENTS_IN_INDEX: usize = 0;
+  /// Prefix of the outputs, as in `events-out-0`, `events-out-1`, ...
+  pub const EVENTS_OUT_NAME: &'static str = "events-out";
+
+  pub const STEALING_INDEX: usize = 0;
+  pub const RETRIGGER_INDEX: usize = 1;
+  pub const RELEASE_TAIL_INDEX: usize = 2;
+  pub const MODE_INDEX: usize = 3;
+  pub const PRIORITY_INDEX: usize = 4;
+  pub const MPE_INDEX: usize = 5;
+  pub const MAX_VOICES_INDEX: usize = 6;
+
+  /// Index of the registered per-note controller, and of the control change of MPE, for the timbre
+  pub const TIMBRE_CONTROLLER: u8 = 74;
+  /// Channels of the lower and upper zones of MPE carrying the messages for all the notes
+  const MPE_MANAGER_CHANNELS: [u8; 2] = [0, 15];
+
+  const MAX_RELEASE_TAIL: f32 = 20.0;
+  const MAX_HELD_NOTES: usize = 128;
+
+  pub fn new(voices: usize) -> Self {
+    Self {
+      voices: vec![Voice::default(); voices],
+      held_notes: Vec::with_capacity(Self::MAX_HELD_NOTES),
+      counter: 0,
+      mono: false,
+    }
+  }
+
+  pub fn num_voices(&self) -> usize {
+    self.voices.len()
+  }
+
+  /// Number of voices playing a held note.
+  pub fn active_voices(&self) -> usize {
+    self.voices.iter().filter(|voice| voice.held).count()
+  }
+
+  fn descriptor_with(voices: usize) -> NodeDescriptor {
+    NodeDescriptor::new()
+      .with_events_ports(|ports| {
+        ports
+          .static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+          .static_outputs_cardinality(voices, EventsDescriptor::new(Self::EVENTS_OUT_NAME))
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("stealing").values(vec!["oldest", "quietest"]),
+        ParamDescriptor::new("retrigger")
+          .values(vec!["off", "on"])
+          .initial(1.0),
+        ParamDescriptor::new("release-tail")
+          .max(Self::MAX_RELEASE_TAIL)
+          .unit(ParamUnit::Seconds)
+          .precision(3),
+        ParamDescriptor::new("mode").values(vec!["poly", "mono"]),
+        ParamDescriptor::new("priority").values(vec!["last", "low", "high"]),
+        ParamDescriptor::new("mpe").values(vec!["off", "on"]),
+        ParamDescriptor::new("max-voices")
+          .min(1.0)
+          .max(voices as f32)
+          .initial(voices as f32)
+          .steps(voices.saturating_sub(1))
+          .precision(0),
+      ])
+  }
+
+  fn settings(&self, context: &ProcessorContext) -> Settings {
+    let release_tail = context
+      .parameter(Self::RELEASE_TAIL_INDEX)
+      .get()
+      .clamp(0.0, Self::MAX_RELEASE_TAIL);
+    Settings {
+      mpe: context.parameter(Self::MPE_INDEX).get() >= 0.5,
+      mono: context.parameter(Self::MODE_INDEX).get() >= 0.5,
+      priority: NotePriority::from_value(context.parameter(Self::PRIORITY_INDEX).get()),
+      stealing: VoiceStealing::from_value(context.parameter(Self::STEALING_INDEX).get()),
+      retrigger: context.parameter(Self::RETRIGGER_INDEX).get() >= 0.5,
+      release_tail: (f64::from(release_tail) * 1e9) as TimestampNanos,
+      max_voices: (context
+        .parameter(Self::MAX_VOICES_INDEX)
+        .get()
+        .round()
+        .max(1.0) as usize)
+        .min(self.voices.len()),
+    }
+  }
+
+  fn route(event: &Event) -> Route {
+    match event.data {
+      EventData::Midi(Message {
+        group,
+        mtype: MessageType::ChannelVoice(ChannelVoice { channel, message }),
+      }) => match message {
+        ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+          Route::NoteOn((group, channel, note), velocity)
+        }
+        ChannelVoiceMessage::NoteOff { note, .. } => Route::NoteOff((group, channel, note)),
+        ChannelVoiceMessage::PolyPressure { note, .. }
+        | ChannelVoiceMessage::RegisteredPerNoteController { note, .. }
+        | ChannelVoiceMessage::AssignablePerNoteController { note, .. }
+        | ChannelVoiceMessage::PerNoteManagement { note, .. }
+        | ChannelVoiceMessage::PerNotePitchBend { note, .. } => {
+          Route::PerNote((group, channel, note))
+        }
+        _ => Route::All,
+      },
+      _ => Route::All,
+    }
+  }
+
+  fn find(&self, note: NoteId) -> Option<usize> {
+    self
+      .voices
+      .iter()
+      .position(|voice| voice.note == Some(note))
+  }
+
+  fn find_held(&self, note: NoteId) -> Option<usize> {
+    self
+      .voices
+      .iter()
+      .position(|voice| voice.held && voice.note == Some(note))
+  }
+
+  /// Find a voice to steal among the ones below the maximum accepted by `filter`.
+  fn steal<F>(&self, timestamp: TimestampNanos, settings: &Settings, filter: F) -> Option<usize>
+  where
+    F: Fn(&Voice) -> bool,
+  {
+    let candidates = self
+      .voices
+      .iter()
+      .take(settings.max_voices)
+      .enumerate()
+      .filter(|(_, voice)| filter(voice));
+    match settings.stealing {
+      VoiceStealing::Oldest => candidates.min_by_key(|(_, voice)| voice.started),
+      VoiceStealing::Quietest => candidates.min_by(|(_, a), (_, b)| {
+        let a = a.level(timestamp, settings.release_tail);
+        let b = b.level(timestamp, settings.release_tail);
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+      }),
+    }
+    .map(|(index, _)| index)
+  }
+
+  /// Find a voice for a new note, which is the one already playing it when retriggering, a free one,
+  /// or a stolen one, preferring the ones sounding their release tail.
+  fn allocate(
+    &mut self,
+    note: NoteId,
+    velocity: u16,
+    timestamp: TimestampNanos,
+    settings: &Settings,
+  ) -> Option<usize> {
+    let same_note = if settings.retrigger {
+      self.find(note)
+    } else {
+      None
+    };
+    let index = same_note
+      .or_else(|| {
+        self
+          .voices
+          .iter()
+          .take(settings.max_voices)
+          .position(|voice| voice.is_free(timestamp, settings.release_tail))
+      })
+      .or_else(|| self.steal(timestamp, settings, |voice| !voice.held))
+      .or_else(|| self.steal(timestamp, settings, |_| true))?;
+    self.counter += 1;
+    self.voices[index] = Voice {
+      note: Some(note),
+      held: true,
+      velocity,
+      started: self.counter,
+      released: 0,
+    };
+    Some(index)
+  }
+
+  /// Group and channel of a message for the note of an MPE member channel.
+  fn mpe_member_channel(event: &Event) -> Option<(u8, u8)> {
+    match event.data {
+      EventData::Midi(Message {
+        group,
+        mtype: MessageType::ChannelVoice(ChannelVoice { channel, message }),
+      }) if !Self::MPE_MANAGER_CHANNELS.contains(&channel) => match message {
+        ChannelVoiceMessage::PitchBend { .. } | ChannelVoiceMessage::ChannelPressure { .. } => {
+          Some((group, channel))
+        }
+        ChannelVoiceMessage::ControlChange { index, .. } if index == Self::TIMBRE_CONTROLLER => {
+          Some((group, channel))
+        }
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// Convert a message of an MPE member channel into its per-note version for a note.
+  fn mpe_per_note(event: &Event, note: NoteId) -> Option<Event> {
+    let (group, channel, note) = note;
+    let message = match event.data {
+      EventData::Midi(Message {
+        mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+        ..
+      }) => match message {
+        ChannelVoiceMessage::PitchBend { data } => {
+          ChannelVoiceMessage::PerNotePitchBend { note, data }
+        }
+        ChannelVoiceMessage::ChannelPressure { pressure } => {
+          ChannelVoiceMessage::PolyPressure { note, pressure }
+        }
+        ChannelVoiceMessage::ControlChange { index, data } => {
+          ChannelVoiceMessage::RegisteredPerNoteController { note, index, data }
+        }
+        _ => return None,
+      },
+      _ => return None,
+    };
+    Some(Event {
+      timestamp: event.timestamp,
+      data: EventData::Midi(Message::channel_voice(group, channel, message)),
+    })
+  }
+
+  /// Release all the held voices, sending them their note off.
+  fn release_all(&mut self, timestamp: TimestampNanos, context: &ProcessorContext) {
+    let num_outputs = context.num_events_outputs();
+    for (index, voice) in self.voices.iter_mut().enumerate() {
+      if let Some((group, channel, note)) = voice.note.filter(|_| voice.held) {
+        voice.held = false;
+        voice.released = timestamp;
+        if index < num_outputs {
+          let message = ChannelVoiceMessage::NoteOff {
+            note,
+            velocity: 0,
+            attr_type: 0,
+            attr_data: 0,
+          };
+          let event = Event {
+            timestamp,
+            data: EventData::Midi(Message::channel_voice(group, channel, message)),
+          };
+          context.events_output(index).push(event).ok();
+        }
+      }
+    }
+  }
+
+  /// The held note to play in the monophonic mode.
+  fn prioritized(&self, priority: NotePriority) -> Option<(NoteId, u16)> {
+    let notes = self.held_notes.iter().cloned();
+    match priority {
+      NotePriority::Last => self.held_notes.last().cloned(),
+      NotePriority::Low => notes.min_by_key(|((_, _, note), _)| *note),
+      NotePriority::High => notes.max_by_key(|((_, _, note), _)| *note),
+    }
+  }
+
+  /// Follow the held notes in the monophonic mode, returning the event for the first voice, if any.
+  fn play_mono(&mut self, event: &Event, settings: &Settings) -> Option<Event> {
+    let playing = self.voices[0].note.filter(|_| self.voices[0].held);
+    match Self::route(event) {
+      Route::NoteOn(note, velocity) => {
+        self.held_notes.retain(|(held, _)| *held != note);
+        if self.held_notes.len() < Self::MAX_HELD_NOTES {
+          self.held_notes.push((note, velocity));
+        }
+      }
+      Route::NoteOff(note) => self.held_notes.retain(|(held, _)| *held != note),
+      Route::PerNote(note) => return Some(*event).filter(|_| playing == Some(note)),
+      Route::All => return Some(*event),
+    }
+
+    let message = match (playing, self.prioritized(settings.priority)) {
+      (Some(current), Some((note, _))) if current == note => return None,
+      (_, Some((note, velocity))) => {
+        self.counter += 1;
+        self.voices[0] = Voice {
+          note: Some(note),
+          held: true,
+          velocity,
+          started: self.counter,
+          released: 0,
+        };
+        let (group, channel, note) = note;
+        let message = ChannelVoiceMessage::NoteOn {
+          note,
+          velocity,
+          attr_type: 0,
+          attr_data: 0,
+        };
+        (group, channel, message)
+      }
+      (Some((group, channel, note)), None) => {
+        let voice = &mut self.voices[0];
+        voice.held = false;
+        voice.released = event.timestamp;
+        let message = ChannelVoiceMessage::NoteOff {
+          note,
+          velocity: 0,
+          attr_type: 0,
+          attr_data: 0,
+        };
+        (group, channel, message)
+      }
+      (None, None) => return None,
+    };
+
+    let (group, channel, message) = message;
+    Some(Event {
+      timestamp: event.timestamp,
+      data: EventData::Midi(Message::channel_voice(group, channel, message)),
+    })
+  }
+}
+
+impl Processor for VoiceAllocatorProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(8)
+  }
+
+  fn descriptor(&self) -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    Self::descriptor_with(self.voices.len())
+  }
+
+  fn reset(&mut self) {
+    self.voices.fill(Voice::default());
+    self.held_notes.clear();
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let num_voices = self.voices.len().min(context.num_events_outputs());
+    let settings = self.settings(context);
+    if !settings.mono {
+      self.held_notes.clear();
+    } else if !self.mono {
+      // the notes of the polyphonic mode would keep sounding in the other voices otherwise
+      self.release_all(context.block_timestamp(), context);
+    }
+    self.mono = settings.mono;
+    for event in context.events_input(Self::EVENTS_IN_INDEX).iter() {
+      if let Some((group, channel)) = Self::mpe_member_channel(event).filter(|_| settings.mpe) {
+        for (index, voice) in self.voices.iter().enumerate().take(num_voices) {
+          let note = voice.note.filter(|(note_group, note_channel, _)| {
+            (*note_group, *note_channel) == (group, channel)
+          });
+          if let Some(event) = note.and_then(|note| Self::mpe_per_note(event, note)) {
+            context.events_output(index).push(event).ok();
+          }
+        }
+        continue;
+      }
+      if settings.mono && num_voices > 0 {
+        let event = self.play_mono(event, &settings);
+        if let Some(event) = event {
+          context.events_output(0).push(event).ok();
+        }
+        continue;
+      }
+      let voice = match Self::route(event) {
+        Route::NoteOn(note, velocity) => self.allocate(note, velocity, event.timestamp, &settings),
+        Route::NoteOff(note) => {
+          let index = self.find_held(note);
+          if let Some(index) = index {
+            let voice = &mut self.voices[index];
+            voice.held = false;
+            voice.released = event.timestamp;
+          }
+          index
+        }
+        Route::PerNote(note) => self.find(note),
+        Route::All => {
+          for index in 0..num_voices {
+            context.events_output(index).push(*event).ok();
+          }
+          None
+        }
+      };
+      if let Some(index) = voice.filter(|index| *index < num_voices) {
+        context.events_output(index).push(*event).ok();
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TestContext;
+  use crate::TransportMessage;
+
+  fn note(note: u8, on: bool) -> Event {
+    note_at(0, note, u16::MAX, on)
+  }
+
+  fn note_at(timestamp: u64, note: u8, velocity: u16, on: bool) -> Event {
+    let message = if on {
+      ChannelVoiceMessage::NoteOn {
+        note,
+        velocity,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    } else {
+      ChannelVoiceMessage::NoteOff {
+        note,
+        velocity: 0,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    };
+    Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(0, 0, message)),
+    }
+  }
+
+  #[test]
+  fn allocate_voices() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+
+    context.set_events(0, &[note(60, true), note(64, true)]);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(0), vec![note(60, true)]);
+    assert_eq!(context.events_output(1), vec![note(64, true)]);
+    assert_eq!(allocator.active_voices(), 2);
+
+    context.set_events(0, &[note(60, false), note(67, true)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(60, false), note(67, true)]
+    );
+    assert!(context.events_output(1).is_empty());
+  }
+
+  #[test]
+  fn steal_the_oldest_voice() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+
+    context.set_events(0, &[note(60, true), note(64, true), note(67, true)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(60, true), note(67, true)]
+    );
+
+    context.set_events(0, &[note(60, false)]);
+    context.render(&mut allocator);
+    assert!(context.events_output(0).is_empty());
+    assert_eq!(allocator.active_voices(), 2);
+  }
+
+  #[test]
+  fn prefer_voices_in_their_release_tail() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    context.set_parameter(VoiceAllocatorProcessor::RELEASE_TAIL_INDEX, 1.0);
+
+    let events = [
+      note(60, true),
+      note(64, true),
+      note(64, false),
+      note(67, true),
+    ];
+    context.set_events(0, &events);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(0), vec![note(60, true)]);
+    assert_eq!(
+      context.events_output(1),
+      vec![note(64, true), note(64, false), note(67, true)]
+    );
+
+    // free again once the release tail is over
+    let events = [
+      note_at(0, 67, 0, false),
+      note_at(2_000_000_000, 72, 1, true),
+    ];
+    context.set_events(0, &events);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(1), events.to_vec());
+    assert_eq!(allocator.active_voices(), 2);
+  }
+
+  #[test]
+  fn steal_the_quietest_voice() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    context.set_parameter(
+      VoiceAllocatorProcessor::STEALING_INDEX,
+      VoiceStealing::Quietest.value(),
+    );
+    context.set_parameter(VoiceAllocatorProcessor::RELEASE_TAIL_INDEX, 1.0);
+
+    let soft = note_at(0, 64, u16::MAX / 4, true);
+    context.set_events(0, &[note(60, true), soft, note(67, true)]);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(0), vec![note(60, true)]);
+    assert_eq!(context.events_output(1), vec![soft, note(67, true)]);
+
+    // the voice released earlier is quieter along the release tail
+    let events = [
+      note_at(100_000_000, 67, 0, false),
+      note_at(500_000_000, 60, 0, false),
+      note_at(600_000_000, 72, u16::MAX, true),
+    ];
+    context.set_events(0, &events);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(1), vec![events[0], events[2]]);
+  }
+
+  #[test]
+  fn retrigger_the_same_note() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    context.set_parameter(VoiceAllocatorProcessor::RELEASE_TAIL_INDEX, 1.0);
+
+    context.set_events(0, &[note(60, true), note(60, false), note(60, true)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(60, true), note(60, false), note(60, true)]
+    );
+    assert!(context.events_output(1).is_empty());
+
+    context.set_parameter(VoiceAllocatorProcessor::RETRIGGER_INDEX, 0.0);
+    context.set_events(0, &[note(60, false), note(60, true)]);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(0), vec![note(60, false)]);
+    assert_eq!(context.events_output(1), vec![note(60, true)]);
+  }
+
+  #[test]
+  fn limit_the_active_voices() {
+    let mut allocator = VoiceAllocatorProcessor::new(3);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+
+    context.set_events(0, &[note(60, true), note(64, true), note(67, true)]);
+    context.render(&mut allocator);
+    assert_eq!(allocator.active_voices(), 3);
+
+    // the third voice plays out its note, but doesn't get new ones
+    context.set_parameter(VoiceAllocatorProcessor::MAX_VOICES_INDEX, 2.0);
+    context.set_events(0, &[note(60, false), note(72, true), note(76, true)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(60, false), note(72, true)]
+    );
+    // stealing the oldest voice below the maximum
+    assert_eq!(context.events_output(1), vec![note(76, true)]);
+    assert!(context.events_output(2).is_empty());
+
+    context.set_events(0, &[note(67, false)]);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(2), vec![note(67, false)]);
+    assert_eq!(allocator.active_voices(), 2);
+  }
+
+  #[test]
+  fn play_a_single_voice_in_mono() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    context.set_parameter(VoiceAllocatorProcessor::MODE_INDEX, 1.0);
+
+    context.set_events(
+      0,
+      &[
+        note(60, true),
+        note(64, true),
+        note(64, false),
+        note(60, false),
+      ],
+    );
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![
+        note(60, true),
+        note(64, true),
+        note(60, true),
+        note(60, false)
+      ]
+    );
+    assert!(context.events_output(1).is_empty());
+    assert_eq!(allocator.active_voices(), 0);
+
+    context.set_parameter(
+      VoiceAllocatorProcessor::PRIORITY_INDEX,
+      NotePriority::Low.value(),
+    );
+    context.set_events(
+      0,
+      &[
+        note(60, true),
+        note(64, true),
+        note(55, true),
+        note(55, false),
+        note(60, false),
+      ],
+    );
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![
+        note(60, true),
+        note(55, true),
+        note(60, true),
+        note(64, true)
+      ]
+    );
+    assert_eq!(allocator.active_voices(), 1);
+
+    context.set_parameter(
+      VoiceAllocatorProcessor::PRIORITY_INDEX,
+      NotePriority::High.value(),
+    );
+    context.set_events(0, &[note(67, true), note(62, true), note(67, false)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(67, true), note(64, true)]
+    );
+  }
+
+  #[test]
+  fn release_all_the_voices_when_switching_to_mono() {
+    let mut allocator = VoiceAllocatorProcessor::new(3);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+
+    context.set_events(0, &[note(60, true), note(64, true), note(67, true)]);
+    context.render(&mut allocator);
+    assert_eq!(allocator.active_voices(), 3);
+
+    context.set_parameter(VoiceAllocatorProcessor::MODE_INDEX, 1.0);
+    context.set_events(0, &[note(72, true)]);
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![note(60, false), note(72, true)]
+    );
+    assert_eq!(context.events_output(1), vec![note(64, false)]);
+    assert_eq!(context.events_output(2), vec![note(67, false)]);
+    assert_eq!(allocator.active_voices(), 1);
+
+    // already in mono, nothing else is released
+    context.set_events(0, &[]);
+    context.render(&mut allocator);
+    assert!(context.events_output(0).is_empty());
+    assert_eq!(allocator.active_voices(), 1);
+  }
+
+  #[test]
+  fn convert_mpe_member_channels_into_per_note_messages() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    context.set_parameter(VoiceAllocatorProcessor::MPE_INDEX, 1.0);
+    let message = |channel, message| Event {
+      timestamp: 0,
+      data: EventData::Midi(Message::channel_voice(0, channel, message)),
+    };
+    let note_on = |channel, note| {
+      message(
+        channel,
+        ChannelVoiceMessage::NoteOn {
+          note,
+          velocity: u16::MAX,
+          attr_type: 0,
+          attr_data: 0,
+        },
+      )
+    };
+
+    let bend = message(2, ChannelVoiceMessage::PitchBend { data: 7 });
+    let pressure = message(1, ChannelVoiceMessage::ChannelPressure { pressure: 8 });
+    let timbre = message(
+      2,
+      ChannelVoiceMessage::ControlChange {
+        index: VoiceAllocatorProcessor::TIMBRE_CONTROLLER,
+        data: 9,
+      },
+    );
+    let manager_bend = message(0, ChannelVoiceMessage::PitchBend { data: 10 });
+    let per_note_bend = message(
+      1,
+      ChannelVoiceMessage::PerNotePitchBend { note: 60, data: 11 },
+    );
+    context.set_events(
+      0,
+      &[
+        note_on(1, 60),
+        note_on(2, 64),
+        bend,
+        pressure,
+        timbre,
+        manager_bend,
+        per_note_bend,
+      ],
+    );
+    context.render(&mut allocator);
+    assert_eq!(
+      context.events_output(0),
+      vec![
+        note_on(1, 60),
+        message(
+          1,
+          ChannelVoiceMessage::PolyPressure {
+            note: 60,
+            pressure: 8
+          }
+        ),
+        manager_bend,
+        per_note_bend,
+      ]
+    );
+    assert_eq!(
+      context.events_output(1),
+      vec![
+        note_on(2, 64),
+        message(
+          2,
+          ChannelVoiceMessage::PerNotePitchBend { note: 64, data: 7 }
+        ),
+        message(
+          2,
+          ChannelVoiceMessage::RegisteredPerNoteController {
+            note: 64,
+            index: VoiceAllocatorProcessor::TIMBRE_CONTROLLER,
+            data: 9
+          }
+        ),
+        manager_bend,
+      ]
+    );
+  }
+
+  #[test]
+  fn broadcast_other_events() {
+    let mut allocator = VoiceAllocatorProcessor::new(2);
+    let mut context = TestContext::new(&allocator.descriptor(), 4);
+    let start = Event {
+      timestamp: 0,
+      data: EventData::Transport(TransportMessage::Start),
+    };
+
+    context.set_events(0, &[start]);
+    context.render(&mut allocator);
+    assert_eq!(context.events_output(0), vec![start]);
+    assert_eq!(context.events_output(1), vec![start]);
+  }
+}
@@ -1,5 +1,5 @@
 use kiro_midi as midi;
-use kiro_time::{BarsTime, ClockTime, Signature, Tempo, TicksTime};
+use kiro_time::{BarsTime, ClockTime, SampleRate, Signature, Tempo, TicksTime};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Event {
@@ -7,11 +7,55 @@ pub struct Event {
   pub data: EventData,
 }
 
+impl Event {
+  /// Offset in samples of this event from the beginning of a block starting at `block_timestamp`.
+  ///
+  /// Events from before the beginning of the block happen at its first sample.
+  pub fn sample_offset(
+    &self,
+    block_timestamp: midi::TimestampNanos,
+    sample_rate: SampleRate,
+  ) -> usize {
+    let nanos = self.timestamp.saturating_sub(block_timestamp);
+    (nanos as u128 * u128::from(sample_rate) / 1_000_000_000) as usize
+  }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EventData {
   Transport(TransportMessage),
   Midi(midi::messages::Message),
-  Automation(), // TODO
+  Automation(AutomationData),
+}
+
+/// Reference to one of the parameters of the processor receiving the event, by its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParamRef(pub usize);
+
+/// How a parameter reaches the value of an automation event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomationCurve {
+  /// Jump to the value at the sample of the event.
+  Step,
+  /// Ramp linearly from the previous value to reach the value at the sample of the event.
+  Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutomationData {
+  pub param_ref: ParamRef,
+  pub value: f32,
+  pub curve: AutomationCurve,
+}
+
+impl AutomationData {
+  pub fn new(param_ref: ParamRef, value: f32, curve: AutomationCurve) -> Self {
+    Self {
+      param_ref,
+      value,
+      curve,
+    }
+  }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -29,6 +73,10 @@ pub enum TransportMessage {
   },
 }
 
+/// Events ordered by timestamp, as processors expect them.
+///
+/// Pushing events out of order is allowed, but then the buffer is sorted by the renderer
+/// before it reaches a processor.
 pub struct EventsBuffer {
   data: Vec<Event>,
   sorted: bool,
@@ -76,6 +124,46 @@ impl EventsBuffer {
     }
   }
 
+  /// Insert an event after the ones with the same or an earlier timestamp,
+  /// so the buffer keeps sorted if it already was.
+  pub fn insert_sorted(&mut self, event: Event) -> Result<(), Event> {
+    if self.data.len() < self.data.capacity() {
+      let index = self
+        .data
+        .partition_point(|other| other.timestamp <= event.timestamp);
+      self.data.insert(index, event);
+      Ok(())
+    } else {
+      Err(event)
+    }
+  }
+
+  /// Sort the events by timestamp, keeping the order of the ones with the same timestamp.
+  ///
+  /// It doesn't allocate, so it is safe to call from the audio thread.
+  pub fn sort(&mut self) {
+    if self.sorted {
+      return;
+    }
+    for index in 1..self.data.len() {
+      let mut position = index;
+      while position > 0 && self.data[position - 1].timestamp > self.data[position].timestamp {
+        self.data.swap(position - 1, position);
+        position -= 1;
+      }
+    }
+    self.sorted = true;
+  }
+
+  /// Move the events earlier than `timestamp` into another buffer, keeping the ones not fitting into it.
+  ///
+  /// It doesn't allocate, so it is safe to call from the audio thread.
+  pub fn move_earlier(&mut self, timestamp: midi::TimestampNanos, target: &mut EventsBuffer) {
+    self
+      .data
+      .retain(|event| event.timestamp >= timestamp || target.push(*event).is_err());
+  }
+
   pub fn iter(&self) -> Iter<'_> {
     Iter(self.data.iter())
   }
@@ -90,3 +178,59 @@ impl<'a> Iterator for Iter<'a> {
     self.0.next()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn event(timestamp: midi::TimestampNanos, message: TransportMessage) -> Event {
+    Event {
+      timestamp,
+      data: EventData::Transport(message),
+    }
+  }
+
+  #[test]
+  fn move_earlier_events() {
+    let mut buffer = EventsBuffer::with_capacity(4);
+    buffer.push(event(30, TransportMessage::Stop)).unwrap();
+    buffer.push(event(10, TransportMessage::Start)).unwrap();
+    buffer.push(event(20, TransportMessage::Continue)).unwrap();
+    let mut target = EventsBuffer::with_capacity(1);
+
+    buffer.move_earlier(25, &mut target);
+    assert_eq!(
+      target.iter().cloned().collect::<Vec<_>>(),
+      vec![event(10, TransportMessage::Start)]
+    );
+    assert_eq!(
+      buffer.iter().cloned().collect::<Vec<_>>(),
+      vec![
+        event(30, TransportMessage::Stop),
+        event(20, TransportMessage::Continue)
+      ]
+    );
+  }
+
+  #[test]
+  fn sort_keeping_the_order_of_simultaneous_events() {
+    let mut buffer = EventsBuffer::with_capacity(4);
+    buffer.push(event(20, TransportMessage::Stop)).unwrap();
+    buffer.push(event(10, TransportMessage::Start)).unwrap();
+    buffer.push(event(20, TransportMessage::Continue)).unwrap();
+    buffer.push(event(5, TransportMessage::Loop)).unwrap();
+    assert!(!buffer.is_sorted());
+
+    buffer.sort();
+    assert!(buffer.is_sorted());
+    assert_eq!(
+      buffer.iter().cloned().collect::<Vec<_>>(),
+      vec![
+        event(5, TransportMessage::Loop),
+        event(10, TransportMessage::Start),
+        event(20, TransportMessage::Stop),
+        event(20, TransportMessage::Continue),
+      ]
+    );
+  }
+}
@@ -1,23 +1,71 @@
 use ringbuf::{Consumer, Producer};
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+use kiro_time::SampleRate;
+
+use crate::graph::connection::NodeAudioOut;
+use crate::graph::NodeKey;
 use crate::key_gen::Key;
 use crate::key_store::KeyStore;
 use crate::processor::ports::audio::AudioPort;
 use crate::processor::ports::events::EventsPort;
+use crate::processor::ports::param::{ParamData, ParamModulation, ParamRenderPort};
 use crate::processor::ports::{Input, Output};
-use crate::processor::BoxedProcessor;
+use crate::processor::{BoxedProcessor, SharedState, StateError};
 use crate::rendering::buffers::audio::AudioBuffer;
-use crate::rendering::buffers::events::EventsBuffer;
-use crate::rendering::messages::Message;
+use crate::rendering::buffers::events::{Event, EventsBuffer};
+use crate::rendering::controller_plan::{GraphPlan, PlanMix, PlanModulation, PlanRequiredInput};
+use crate::rendering::messages::{Garbage, Message};
+use crate::rendering::meters::{meter, MeterKey, MeterLevel, MeterReader, MeterTap, MeterWriter};
 use crate::rendering::owned_data::{OwnedData, Ref};
-use crate::rendering::renderer_plan::{RenderNode, RenderPlan};
-use crate::{EngineConfig, ParamValue, PlanNode, Processor};
+use crate::rendering::renderer_plan::{AudioMix, Bypass, EventsMerge, RenderNode, RenderPlan};
+use crate::{EngineConfig, ParamValue, Processor};
 
 pub type ProcessorKey = Key<BoxedProcessor>;
 pub type ParamKey = Key<Arc<ParamValue>>;
+
+/// Event sent by the renderer from one of the events outputs of the graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputEvent {
+  /// Index of the events output of the graph
+  pub port: usize,
+  pub event: Event,
+}
+/// A processor panicked while rendering, so it has been bypassed.
+///
+/// It stays bypassed until it is replaced, or the bypass is explicitly disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorFault {
+  pub processor: ProcessorKey,
+}
+
+/// State loaded into a processor by the renderer, see [`Controller::subscribe_states`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateLoaded {
+  pub processor: ProcessorKey,
+  pub result: core::result::Result<(), StateError>,
+}
+
+/// The user started or finished touching a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GestureKind {
+  Begin,
+  End,
+}
+
+/// Gesture on a parameter, so hosts can group the changes in between into a single undo step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamGesture {
+  pub param: ParamKey,
+  pub kind: GestureKind,
+  /// Value of the parameter when the gesture happened
+  pub value: f32,
+}
+
 pub type AudioBufferKey = Key<AudioBuffer>;
 pub type EventsBufferKey = Key<EventsBuffer>;
 
@@ -42,14 +90,32 @@ pub enum Error {
   #[error("Processor with key {0:?} not found")]
   ProcessorNotFound(ProcessorKey),
 
+  #[error("State of the processor with key {0:?}: {1}")]
+  State(ProcessorKey, StateError),
+
   #[error("Parameter value with key {0:?} not found")]
   ParamValueNotFound(ParamKey),
 
+  #[error("Meter with key {0:?} not found")]
+  MeterNotFound(MeterKey),
+
   #[error("Audio buffer with key {0:?} not found")]
   AudioBufferNotFound(AudioBufferKey),
 
   #[error("Event buffer with key {0:?} not found")]
   EventsBufferNotFound(EventsBufferKey),
+
+  #[error("Required input '{1}' of {0} is not connected")]
+  RequiredInputNotConnected(String, String),
+
+  #[error("Nodes {0} and {1} write into the same buffer without an order between them")]
+  SharedOutputBuffer(String, String),
+
+  #[error("Node {0} can never render, as it depends on nodes that are missing or depend on it")]
+  UnreachableNode(String),
+
+  #[error("Events buffer missing for {0}")]
+  EventsBufferMissing(String),
   // #[error("Parameter slice buffer not found for port {0:?}")]
   // SliceBufferNotFound(Key<ParamPort>),
 
@@ -65,25 +131,68 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub struct Controller {
   tx: Producer<Message>,
   rx: Consumer<Message>,
+  garbage: Consumer<Garbage>,
 
   config: EngineConfig,
 
   processors: OwnedData<BoxedProcessor>,
+  prepared_processors: HashSet<ProcessorKey>,
+  planned_processors: HashSet<ProcessorKey>,
+  bypassed_processors: HashSet<ProcessorKey>,
+  faulted_processors: HashSet<ProcessorKey>,
+  /// Current state of the processors that have one, see [`Processor::state`]
+  states: HashMap<ProcessorKey, SharedState>,
   parameters: KeyStore<Arc<ParamValue>>,
+  param_slice_buffers: HashMap<ParamKey, AudioBufferKey>,
   audio_buffers: OwnedData<AudioBuffer>,
   event_buffers: OwnedData<EventsBuffer>,
+  scheduled_buffers: OwnedData<EventsBuffer>,
+  scheduled_buffer_keys: HashMap<(ProcessorKey, usize), EventsBufferKey>,
+  events_subscribers: Vec<Sender<OutputEvent>>,
+  gesture_subscribers: Vec<Sender<ParamGesture>>,
+  fault_subscribers: Vec<Sender<ProcessorFault>>,
+  state_subscribers: Vec<Sender<StateLoaded>>,
+  meter_writers: OwnedData<MeterWriter>,
+  meters: HashMap<NodeAudioOut, Meter>,
+}
+
+/// Meter of an audio output of a node, kept while disabled so its writer can be reused.
+struct Meter {
+  key: MeterKey,
+  reader: MeterReader,
+  enabled: bool,
 }
 
 impl Controller {
-  pub fn new(tx: Producer<Message>, rx: Consumer<Message>, config: EngineConfig) -> Self {
+  pub fn new(
+    tx: Producer<Message>,
+    rx: Consumer<Message>,
+    garbage: Consumer<Garbage>,
+    config: EngineConfig,
+  ) -> Self {
     Self {
       tx,
       rx,
+      garbage,
       config,
       parameters: KeyStore::new(),
+      param_slice_buffers: HashMap::new(),
       processors: OwnedData::new(),
+      prepared_processors: HashSet::new(),
+      planned_processors: HashSet::new(),
+      bypassed_processors: HashSet::new(),
+      faulted_processors: HashSet::new(),
+      states: HashMap::new(),
       audio_buffers: OwnedData::new(),
       event_buffers: OwnedData::new(),
+      scheduled_buffers: OwnedData::new(),
+      scheduled_buffer_keys: HashMap::new(),
+      events_subscribers: Vec::new(),
+      gesture_subscribers: Vec::new(),
+      fault_subscribers: Vec::new(),
+      state_subscribers: Vec::new(),
+      meter_writers: OwnedData::new(),
+      meters: HashMap::new(),
     }
   }
 
@@ -91,7 +200,129 @@ impl Controller {
   where
     P: Processor + 'static,
   {
-    self.processors.add(Box::new(processor))
+    self.add_boxed_processor(Box::new(processor))
+  }
+
+  pub fn add_boxed_processor(&mut self, processor: BoxedProcessor) -> ProcessorKey {
+    let state = processor.state();
+    let key = self.processors.add(processor);
+    if let Some(state) = state {
+      self.states.insert(key, state);
+    }
+    key
+  }
+
+  /// Remove a processor, which is dropped once the render plan using it is retired by the renderer.
+  pub fn remove_processor(&mut self, key: ProcessorKey) -> Result<()> {
+    if !self.processors.remove(key) {
+      return Err(Error::ProcessorNotFound(key));
+    }
+    self.prepared_processors.remove(&key);
+    self.planned_processors.remove(&key);
+    self.bypassed_processors.remove(&key);
+    self.faulted_processors.remove(&key);
+    self.states.remove(&key);
+    let scheduled_buffers = &mut self.scheduled_buffers;
+    self
+      .scheduled_buffer_keys
+      .retain(|(processor, _), buffer_key| {
+        if *processor == key {
+          scheduled_buffers.remove(*buffer_key);
+          false
+        } else {
+          true
+        }
+      });
+    Ok(())
+  }
+
+  /// Encode the state of a processor, or none if it doesn't have one, see [`Processor::state`].
+  ///
+  /// The controller keeps the current state of every processor, so the renderer is not involved.
+  pub fn save_processor_state(&self, key: ProcessorKey) -> Result<Option<Vec<u8>>> {
+    self.get_processor_ref(key)?;
+    self
+      .states
+      .get(&key)
+      .map(|state| state.encode())
+      .transpose()
+      .map_err(|err| Error::State(key, err))
+  }
+
+  /// Decode the state of a processor from the current one and load it, see [`Processor::set_state`].
+  ///
+  /// The processors in a render plan swap it from the renderer between two blocks,
+  /// and the result is delivered by [`Controller::process_messages`], otherwise it is delivered right away.
+  /// See [`Controller::subscribe_states`]. The processors without state ignore it.
+  pub fn load_processor_state(&mut self, key: ProcessorKey, state: &[u8]) -> Result<()> {
+    let processor = self.get_processor_ref(key)?;
+    let current = match self.states.get(&key) {
+      Some(current) => current,
+      None => return Ok(()),
+    };
+    let mut state = current
+      .decode(state)
+      .map_err(|err| Error::State(key, err))?;
+    self.states.insert(key, state.clone());
+    if self.prepared_processors.contains(&key) {
+      self
+        .tx
+        .push(Message::LoadProcessorState(key, state))
+        .map_err(|_| Error::SendFailure)
+    } else {
+      let result = processor.get_mut().set_state(&mut state);
+      Self::notify_state(&mut self.state_subscribers, key, result);
+      Ok(())
+    }
+  }
+
+  /// Receive the results of loading the states with [`Controller::load_processor_state`].
+  pub fn subscribe_states(&mut self) -> Receiver<StateLoaded> {
+    let (tx, rx) = channel();
+    self.state_subscribers.push(tx);
+    rx
+  }
+
+  fn notify_state(
+    subscribers: &mut Vec<Sender<StateLoaded>>,
+    processor: ProcessorKey,
+    result: core::result::Result<(), StateError>,
+  ) {
+    let loaded = StateLoaded { processor, result };
+    subscribers.retain(|subscriber| subscriber.send(loaded.clone()).is_ok());
+  }
+
+  /// Replace the outputs of a processor by its inputs, crossfading between them to avoid clicks.
+  ///
+  /// Every audio or events output takes the input with the same index, if any.
+  pub fn set_bypass(&mut self, key: ProcessorKey, enabled: bool) -> Result<()> {
+    self.get_processor_ref(key)?;
+    if enabled {
+      self.bypassed_processors.insert(key);
+    } else {
+      self.bypassed_processors.remove(&key);
+      self.faulted_processors.remove(&key);
+    }
+    self
+      .tx
+      .push(Message::SetBypass(key, enabled))
+      .map_err(|_| Error::SendFailure)
+  }
+
+  pub fn is_bypassed(&self, key: ProcessorKey) -> bool {
+    self.bypassed_processors.contains(&key)
+  }
+
+  /// Whether the processor panicked while rendering.
+  pub fn is_faulted(&self, key: ProcessorKey) -> bool {
+    self.faulted_processors.contains(&key)
+  }
+
+  /// Receive the processors that panicked while rendering.
+  pub fn subscribe_faults(&mut self) -> Receiver<ProcessorFault> {
+    let (tx, rx) = channel();
+    self.fault_subscribers.push(tx);
+    rx
   }
 
   fn get_processor_ref(&self, key: ProcessorKey) -> Result<Ref<BoxedProcessor>> {
@@ -101,6 +332,44 @@ impl Controller {
       .ok_or(Error::ProcessorNotFound(key))
   }
 
+  pub fn sample_rate(&self) -> SampleRate {
+    self.config.sample_rate
+  }
+
+  pub fn max_block(&self) -> usize {
+    self.config.audio_buffer_size
+  }
+
+  /// Change the sample rate for all the processors.
+  ///
+  /// The processors in the last render plan are prepared again by the renderer,
+  /// while the rest will be prepared once they are placed into a plan.
+  pub fn set_sample_rate(&mut self, sample_rate: SampleRate) -> Result<()> {
+    self.config.sample_rate = sample_rate;
+    let planned_processors = &self.planned_processors;
+    self
+      .prepared_processors
+      .retain(|key| planned_processors.contains(key));
+
+    self
+      .tx
+      .push(Message::SetSampleRate(sample_rate))
+      .map_err(|_| Error::SendFailure)
+  }
+
+  /// Prepare and reset a processor that has never been placed into a render plan before.
+  fn prepare_processor(&mut self, key: ProcessorKey) -> Result<()> {
+    if !self.prepared_processors.contains(&key) {
+      let processor = self.get_processor_ref(key)?;
+      // the processor is not in any render plan yet, so the renderer can't be using it
+      let processor = processor.get_mut();
+      processor.prepare(self.config.sample_rate, self.config.audio_buffer_size);
+      processor.reset();
+      self.prepared_processors.insert(key);
+    }
+    Ok(())
+  }
+
   pub fn add_parameters(&mut self, initial_values: &[f32]) -> Vec<ParamKey> {
     initial_values
       .iter()
@@ -109,7 +378,17 @@ impl Controller {
       .collect()
   }
 
-  fn get_parameter_value(&self, param_key: ParamKey) -> Result<Arc<ParamValue>> {
+  /// Remove parameters, together with the buffers where their values are rendered.
+  pub fn remove_parameters(&mut self, keys: &[ParamKey]) {
+    for key in keys {
+      self.parameters.remove(*key);
+      if let Some(buffer_key) = self.param_slice_buffers.remove(key) {
+        self.audio_buffers.remove(buffer_key);
+      }
+    }
+  }
+
+  pub(crate) fn get_parameter_value(&self, param_key: ParamKey) -> Result<Arc<ParamValue>> {
     self
       .parameters
       .get(param_key)
@@ -123,6 +402,48 @@ impl Controller {
     Ok(())
   }
 
+  /// Set the values of many parameters, which the renderer applies all at once before rendering the next block.
+  pub fn set_parameter_values(&mut self, values: &[(ParamKey, f32)]) -> Result<()> {
+    let values = values
+      .iter()
+      .map(|(key, value)| Ok((self.get_parameter_value(*key)?, *value)))
+      .collect::<Result<Vec<(Arc<ParamValue>, f32)>>>()?;
+    self
+      .tx
+      .push(Message::SetParameterValues(values))
+      .map_err(|_| Error::SendFailure)
+  }
+
+  /// The user started touching a parameter.
+  pub fn begin_gesture(&mut self, key: ParamKey) -> Result<()> {
+    let param_value = self.get_parameter_value(key)?;
+    param_value.begin_gesture();
+    self.notify_gesture(key, GestureKind::Begin, param_value.get());
+    Ok(())
+  }
+
+  /// The user released a parameter.
+  pub fn end_gesture(&mut self, key: ParamKey) -> Result<()> {
+    let param_value = self.get_parameter_value(key)?;
+    param_value.end_gesture();
+    self.notify_gesture(key, GestureKind::End, param_value.get());
+    Ok(())
+  }
+
+  /// Receive the gestures on the parameters, until the receiver is dropped.
+  pub fn subscribe_gestures(&mut self) -> Receiver<ParamGesture> {
+    let (tx, rx) = channel();
+    self.gesture_subscribers.push(tx);
+    rx
+  }
+
+  fn notify_gesture(&mut self, param: ParamKey, kind: GestureKind, value: f32) {
+    let gesture = ParamGesture { param, kind, value };
+    self
+      .gesture_subscribers
+      .retain(|subscriber| subscriber.send(gesture).is_ok());
+  }
+
   pub fn add_audio_buffer(&mut self) -> AudioBufferKey {
     self
       .audio_buffers
@@ -149,60 +470,212 @@ impl Controller {
       .ok_or(Error::EventsBufferNotFound(key))
   }
 
-  pub fn send_render_plan(
-    &mut self,
-    plan_nodes: Vec<PlanNode>,
-    audio_inputs: Vec<AudioBufferKey>,
-    audio_outputs: Vec<AudioBufferKey>,
-    events_inputs: Vec<EventsBufferKey>,
-    events_outputs: Vec<EventsBufferKey>,
-  ) -> Result<()> {
-    let render_plan = self.build_render_plan(
-      plan_nodes,
-      audio_inputs,
-      audio_outputs,
-      events_inputs,
-      events_outputs,
-    )?;
+  /// Send a new render plan to the renderer.
+  ///
+  /// The buffers not used by the plan are released, although the renderer keeps the ones
+  /// used by its current plan alive until it retires the plan.
+  pub fn send_render_plan(&mut self, plan: GraphPlan) -> Result<()> {
+    let planned_processors = plan
+      .nodes
+      .iter()
+      .map(|node| node.processor)
+      .collect::<HashSet<ProcessorKey>>();
+    let audio_buffer_keys = plan.audio_buffer_keys();
+    let events_buffer_keys = plan.events_buffer_keys();
+
+    let render_plan = self.build_render_plan(plan)?;
 
     self
       .tx
       .push(Message::MoveRenderPlan(Box::new(render_plan)))
-      .map_err(|_| Error::SendFailure)
+      .map_err(|_| Error::SendFailure)?;
+
+    self.planned_processors = planned_processors;
+
+    let param_slice_buffers = self
+      .param_slice_buffers
+      .values()
+      .cloned()
+      .collect::<HashSet<AudioBufferKey>>();
+    self
+      .audio_buffers
+      .retain(|key| audio_buffer_keys.contains(&key) || param_slice_buffers.contains(&key));
+    self
+      .event_buffers
+      .retain(|key| events_buffer_keys.contains(&key));
+    Ok(())
   }
 
-  fn build_render_plan(
-    &mut self,
-    plan_nodes: Vec<PlanNode>,
-    audio_inputs: Vec<AudioBufferKey>,
-    audio_outputs: Vec<AudioBufferKey>,
-    events_inputs: Vec<EventsBufferKey>,
-    events_outputs: Vec<EventsBufferKey>,
-  ) -> Result<RenderPlan> {
+  /// Check that a plan can be rendered properly, rather than producing silently broken render plans.
+  fn validate_plan(&self, plan: &GraphPlan) -> Result<()> {
+    let indices = plan
+      .nodes
+      .iter()
+      .enumerate()
+      .map(|(index, node)| (node.processor, index))
+      .collect::<HashMap<ProcessorKey, usize>>();
+
+    // render the nodes in the same way the renderer does, to find the ones that never get ready
+    let mut pending = Vec::with_capacity(plan.nodes.len());
+    let mut triggers = vec![Vec::new(); plan.nodes.len()];
+    for (index, node) in plan.nodes.iter().enumerate() {
+      for dependency in node.dependencies.iter() {
+        let dependency = indices
+          .get(dependency)
+          .ok_or_else(|| Error::UnreachableNode(node.path.clone()))?;
+        triggers[*dependency].push(index);
+      }
+      pending.push(node.dependencies.len());
+    }
+    let mut ready = (0..plan.nodes.len())
+      .filter(|index| pending[*index] == 0)
+      .collect::<VecDeque<usize>>();
+    let mut order = Vec::with_capacity(plan.nodes.len());
+    let mut ancestors = vec![HashSet::<usize>::new(); plan.nodes.len()];
+    while let Some(index) = ready.pop_front() {
+      order.push(index);
+      for trigger in triggers[index].iter().cloned() {
+        let inherited = ancestors[index].clone();
+        ancestors[trigger].extend(inherited);
+        ancestors[trigger].insert(index);
+        pending[trigger] -= 1;
+        if pending[trigger] == 0 {
+          ready.push_back(trigger);
+        }
+      }
+    }
+    if let Some(index) = pending.iter().position(|count| *count > 0) {
+      return Err(Error::UnreachableNode(plan.nodes[index].path.clone()));
+    }
+
+    for node in plan.nodes.iter() {
+      for input in node.required_inputs.iter() {
+        let (connected, id) = match input {
+          PlanRequiredInput::Audio(index, id) => {
+            let connected = node
+              .audio_input_buffers
+              .get(*index)
+              .filter(|buffers| buffers.iter().any(|buffer| Some(*buffer) != plan.silence))
+              .is_some();
+            (connected, id)
+          }
+          PlanRequiredInput::Events(index, id) => {
+            let connected = node
+              .events_input_buffers
+              .get(*index)
+              .filter(|buffer| Some(**buffer) != plan.no_events)
+              .is_some();
+            (connected, id)
+          }
+        };
+        if !connected {
+          return Err(Error::RequiredInputNotConnected(
+            node.path.clone(),
+            id.clone(),
+          ));
+        }
+      }
+
+      let mut events_buffers = node
+        .events_input_buffers
+        .iter()
+        .chain(node.events_output_buffers.iter())
+        .chain(
+          node
+            .events_merges
+            .iter()
+            .flat_map(|merge| merge.sources.iter()),
+        );
+      if events_buffers.any(|key| self.event_buffers.get(*key).is_none()) {
+        return Err(Error::EventsBufferMissing(node.path.clone()));
+      }
+    }
+
+    // buffers are reused by later nodes, but only after the previous writer and its readers have rendered
+    let mut audio_writers = HashMap::<AudioBufferKey, usize>::new();
+    let mut events_writers = HashMap::<EventsBufferKey, usize>::new();
+    for index in order {
+      let node = &plan.nodes[index];
+      let audio_buffers = node
+        .audio_output_buffers
+        .iter()
+        .flatten()
+        .chain(node.audio_mixes.iter().map(|mix| &mix.output));
+      let events_buffers = node
+        .events_output_buffers
+        .iter()
+        .chain(node.events_merges.iter().map(|merge| &merge.output));
+      let previous_writers = audio_buffers
+        .filter_map(|key| audio_writers.insert(*key, index))
+        .chain(events_buffers.filter_map(|key| events_writers.insert(*key, index)))
+        .collect::<Vec<usize>>();
+      if let Some(writer) = previous_writers
+        .into_iter()
+        .find(|writer| !ancestors[index].contains(writer))
+      {
+        return Err(Error::SharedOutputBuffer(
+          plan.nodes[writer].path.clone(),
+          node.path.clone(),
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  fn build_render_plan(&mut self, plan: GraphPlan) -> Result<RenderPlan> {
+    self.validate_plan(&plan)?;
+    let GraphPlan {
+      nodes: plan_nodes,
+      audio_inputs,
+      audio_outputs,
+      events_inputs,
+      events_outputs,
+      audio_output_mixes,
+      events_output_merges,
+      ..
+    } = plan;
+
     let mut nodes = Vec::<RenderNode>::new();
     let mut nodes_by_key = HashMap::<ProcessorKey, usize>::new();
     let mut dependencies = vec![0; plan_nodes.len()];
     let mut triggers = HashMap::<ProcessorKey, HashSet<usize>>::new();
 
     for (index, node) in plan_nodes.into_iter().enumerate() {
+      self.prepare_processor(node.processor)?;
       let processor = self.get_processor_ref(node.processor)?;
 
+      let parameter_ports = self.build_parameter_ports(&node.parameters, node.modulations)?;
       let parameters = self.build_parameters(node.parameters)?;
 
+      let audio_mixes = self.build_audio_mixes(node.audio_mixes)?;
+      let events_merges = self.build_events_merges(node.events_merges)?;
+
       let audio_input_ports = self.build_audio_input_ports(node.audio_input_buffers)?;
       let audio_output_ports = self.build_audio_output_ports(node.audio_output_buffers)?;
 
       let events_input_ports = self.build_events_input_ports(node.events_input_buffers)?;
-      let events_output_ports = self.build_events_output_ports(node.events_output_buffers)?;
+      let events_output_ports =
+        self.build_events_output_ports(node.processor, node.events_output_buffers)?;
+
+      let meters = self.build_meters(node.meters)?;
 
       let render_node = RenderNode {
+        processor_key: node.processor,
         processor,
+        bypass: Bypass::new(self.bypassed_processors.contains(&node.processor)),
+        faulted: self.faulted_processors.contains(&node.processor),
+        dry_wet: node.dry_wet,
         parameters,
+        parameter_ports,
+        audio_mixes,
+        events_merges,
         audio_input_ports,
         audio_output_ports,
         events_input_ports,
         events_output_ports,
+        meters,
         triggers: Vec::new(),
+        render_time: Duration::ZERO,
       };
 
       dependencies[index] = node.dependencies.len();
@@ -239,6 +712,8 @@ impl Controller {
     let audio_outputs = self.build_audio_buffers(audio_outputs)?;
     let events_inputs = self.build_events_buffers(events_inputs)?;
     let events_outputs = self.build_events_buffers(events_outputs)?;
+    let audio_output_mixes = self.build_audio_mixes(audio_output_mixes)?;
+    let events_output_merges = self.build_events_merges(events_output_merges)?;
 
     Ok(RenderPlan {
       nodes,
@@ -246,6 +721,8 @@ impl Controller {
       audio_outputs,
       events_inputs,
       events_outputs,
+      audio_output_mixes,
+      events_output_merges,
       dependencies,
       completed,
       initial_ready,
@@ -253,6 +730,19 @@ impl Controller {
     })
   }
 
+  fn build_meters(&self, meters: Vec<(usize, MeterKey)>) -> Result<Vec<MeterTap>> {
+    meters
+      .into_iter()
+      .map(|(port, key)| {
+        let writer = self
+          .meter_writers
+          .get(key)
+          .ok_or(Error::MeterNotFound(key))?;
+        Ok(MeterTap { port, writer })
+      })
+      .collect()
+  }
+
   fn build_parameters(&mut self, keys: Vec<ParamKey>) -> Result<Vec<Arc<ParamValue>>> {
     keys
       .into_iter()
@@ -263,6 +753,48 @@ impl Controller {
       })
   }
 
+  fn build_parameter_ports(
+    &mut self,
+    keys: &[ParamKey],
+    modulations: Vec<PlanModulation>,
+  ) -> Result<Vec<ParamRenderPort>> {
+    let mut modulations = modulations
+      .into_iter()
+      .map(|modulation| (modulation.parameter, modulation))
+      .collect::<HashMap<usize, PlanModulation>>();
+
+    keys
+      .iter()
+      .enumerate()
+      .try_fold(Vec::new(), |mut ports, (index, key)| {
+        let value = self.get_parameter_value(*key)?;
+        // the slice buffers are only used by the processor owning the parameter, so they can be kept between plans
+        let slice_key = match self.param_slice_buffers.get(key) {
+          Some(slice_key) => *slice_key,
+          None => {
+            let slice_key = self.add_audio_buffer();
+            self.param_slice_buffers.insert(*key, slice_key);
+            slice_key
+          }
+        };
+        let slice_buffer = self.get_audio_buffer_ref(slice_key)?;
+        let data = match modulations.remove(&index) {
+          Some(modulation) => {
+            let modulation = ParamModulation {
+              buffer: self.get_audio_buffer_ref(modulation.buffer)?,
+              depth: modulation.depth,
+              min: modulation.min,
+              max: modulation.max,
+            };
+            ParamData::modulated(value, modulation, slice_buffer)
+          }
+          None => ParamData::from_value(value, slice_buffer),
+        };
+        ports.push(ParamRenderPort::new(data));
+        Ok(ports)
+      })
+  }
+
   fn build_audio_input_ports(
     &mut self,
     keys: Vec<Vec<AudioBufferKey>>,
@@ -296,6 +828,30 @@ impl Controller {
     })
   }
 
+  fn build_audio_mixes(&self, mixes: Vec<PlanMix<AudioBufferKey>>) -> Result<Vec<AudioMix>> {
+    mixes
+      .into_iter()
+      .try_fold(Vec::new(), |mut audio_mixes, mix| {
+        audio_mixes.push(AudioMix {
+          sources: self.build_audio_buffers(mix.sources)?,
+          output: self.get_audio_buffer_ref(mix.output)?,
+        });
+        Ok(audio_mixes)
+      })
+  }
+
+  fn build_events_merges(&self, merges: Vec<PlanMix<EventsBufferKey>>) -> Result<Vec<EventsMerge>> {
+    merges
+      .into_iter()
+      .try_fold(Vec::new(), |mut events_merges, merge| {
+        events_merges.push(EventsMerge {
+          sources: self.build_events_buffers(merge.sources)?,
+          output: self.get_event_buffer_ref(merge.output)?,
+        });
+        Ok(events_merges)
+      })
+  }
+
   fn build_events_input_ports(
     &mut self,
     keys: Vec<EventsBufferKey>,
@@ -306,10 +862,35 @@ impl Controller {
 
   fn build_events_output_ports(
     &mut self,
+    processor: ProcessorKey,
     keys: Vec<EventsBufferKey>,
   ) -> Result<Vec<EventsPort<Output>>> {
     let buffers = self.build_events_buffers(keys)?;
-    Ok(buffers.into_iter().map(EventsPort::new).collect())
+    let mut ports = Vec::with_capacity(buffers.len());
+    for (index, buffer) in buffers.into_iter().enumerate() {
+      let scheduled = self.get_scheduled_buffer_ref(processor, index)?;
+      ports.push(EventsPort::new(buffer).with_scheduled(scheduled));
+    }
+    Ok(ports)
+  }
+
+  /// Buffer holding the events scheduled from an events output of a processor,
+  /// which is kept between render plans so no event gets lost.
+  fn get_scheduled_buffer_ref(
+    &mut self,
+    processor: ProcessorKey,
+    index: usize,
+  ) -> Result<Ref<EventsBuffer>> {
+    let scheduled_buffers = &mut self.scheduled_buffers;
+    let event_buffer_size = self.config.event_buffer_size;
+    let key = *self
+      .scheduled_buffer_keys
+      .entry((processor, index))
+      .or_insert_with(|| scheduled_buffers.add(EventsBuffer::with_capacity(event_buffer_size)));
+    self
+      .scheduled_buffers
+      .get(key)
+      .ok_or(Error::EventsBufferNotFound(key))
   }
 
   fn build_events_buffers(&self, keys: Vec<EventsBufferKey>) -> Result<Vec<Ref<EventsBuffer>>> {
@@ -320,12 +901,129 @@ impl Controller {
     })
   }
 
+  /// Start metering an audio output of a node from the next render plan.
+  pub fn enable_meter(&mut self, node_out: NodeAudioOut, channels: usize) {
+    let meter_writers = &mut self.meter_writers;
+    self
+      .meters
+      .entry(node_out)
+      .or_insert_with(|| {
+        let (writer, reader) = meter(channels);
+        Meter {
+          key: meter_writers.add(writer),
+          reader,
+          enabled: true,
+        }
+      })
+      .enabled = true;
+  }
+
+  /// Remove the meters of all the audio outputs of a node.
+  pub fn remove_meters(&mut self, node_key: NodeKey) {
+    let meter_writers = &mut self.meter_writers;
+    self.meters.retain(|node_out, meter| {
+      let keep = node_out.node_key() != node_key;
+      if !keep {
+        meter_writers.remove(meter.key);
+      }
+      keep
+    });
+  }
+
+  /// Stop metering an audio output of a node from the next render plan.
+  pub fn disable_meter(&mut self, node_out: NodeAudioOut) {
+    if let Some(meter) = self.meters.get_mut(&node_out) {
+      meter.enabled = false;
+    }
+  }
+
+  pub(crate) fn meter_key(&self, node_out: NodeAudioOut) -> Option<MeterKey> {
+    self
+      .meters
+      .get(&node_out)
+      .filter(|meter| meter.enabled)
+      .map(|meter| meter.key)
+  }
+
+  /// Latest levels of every channel of a metered audio output.
+  pub fn meter_levels(&mut self, node_out: NodeAudioOut) -> Option<&[MeterLevel]> {
+    self
+      .meters
+      .get_mut(&node_out)
+      .filter(|meter| meter.enabled)
+      .map(|meter| meter.reader.levels())
+  }
+
+  /// Receive the events sent by the processors to the events outputs of the graph.
+  ///
+  /// They are delivered by [`Controller::process_messages`],
+  /// and the subscription finishes when the receiver is dropped.
+  pub fn subscribe_events(&mut self) -> Receiver<OutputEvent> {
+    let (tx, rx) = channel();
+    self.events_subscribers.push(tx);
+    rx
+  }
+
   pub fn process_messages(&mut self) {
+    self.collect_garbage();
+
+    let events_subscribers = &mut self.events_subscribers;
+    let fault_subscribers = &mut self.fault_subscribers;
+    let bypassed_processors = &mut self.bypassed_processors;
+    let faulted_processors = &mut self.faulted_processors;
     self.rx.pop_each(
-      move |message| {
+      |message| {
         match message {
-          Message::MoveRenderPlan(plan) => {
-            drop(plan);
+          Message::MoveRenderPlan(_)
+          | Message::SetSampleRate(_)
+          | Message::SetBypass(..)
+          | Message::SetParameterValues(_)
+          | Message::LoadProcessorState(..) => {}
+          Message::OutputEvent(port, event) => {
+            let output_event = OutputEvent { port, event };
+            events_subscribers.retain(|subscriber| subscriber.send(output_event).is_ok());
+          }
+          Message::ProcessorFaulted(processor) => {
+            bypassed_processors.insert(processor);
+            faulted_processors.insert(processor);
+            let fault = ProcessorFault { processor };
+            fault_subscribers.retain(|subscriber| subscriber.send(fault).is_ok());
+          }
+        }
+        true
+      },
+      None,
+    );
+  }
+
+  /// Deallocate the data retired by the renderer.
+  fn collect_garbage(&mut self) {
+    let processors = &self.processors;
+    let planned_processors = &self.planned_processors;
+    let state_subscribers = &mut self.state_subscribers;
+    let tx = &mut self.tx;
+    self.garbage.pop_each(
+      |garbage| {
+        match garbage {
+          Garbage::RenderPlan(plan) => drop(plan),
+          Garbage::ParameterValues(values) => drop(values),
+          Garbage::ProcessorState(processor, mut state, result) => {
+            let result = match result {
+              Some(result) => Some(result),
+              // the processor was placed into a newer plan, so the renderer has to load it
+              // the queue is never full while the renderer keeps receiving messages
+              None if planned_processors.contains(&processor) => {
+                drop(tx.push(Message::LoadProcessorState(processor, state)));
+                None
+              }
+              // the renderer is not using the processors out of its plan
+              None => processors
+                .get(processor)
+                .map(|p| p.get_mut().set_state(&mut state)),
+            };
+            if let Some(result) = result {
+              Self::notify_state(state_subscribers, processor, result);
+            }
           }
         }
         true
@@ -341,7 +1039,163 @@ mod tests {
 
   use super::*;
   use crate::processor::ProcessorContext;
-  use crate::Processor;
+  use crate::{PlanNode, Processor};
+  use kiro_time::SampleRate;
+  use std::sync::Mutex;
+
+  #[derive(Debug, PartialEq)]
+  enum Lifecycle {
+    Prepare(SampleRate, usize),
+    Reset,
+  }
+
+  struct LifecycleProcessor(Arc<Mutex<Vec<Lifecycle>>>);
+
+  impl Processor for LifecycleProcessor {
+    fn prepare(&mut self, sample_rate: SampleRate, max_block: usize) {
+      self
+        .0
+        .lock()
+        .unwrap()
+        .push(Lifecycle::Prepare(sample_rate, max_block));
+    }
+
+    fn reset(&mut self) {
+      self.0.lock().unwrap().push(Lifecycle::Reset);
+    }
+
+    fn render(&mut self, _context: &mut ProcessorContext) {}
+  }
+
+  #[test]
+  fn prepare_processors_before_placing_them_into_a_plan() {
+    let (tx, _rx) = RingBuffer::new(4).split();
+    let (_tx, rx) = RingBuffer::new(4).split();
+    let (_garbage_tx, garbage_rx) = RingBuffer::new(4).split();
+    let config = EngineConfig {
+      sample_rate: 48_000,
+      audio_buffer_size: 128,
+      ..EngineConfig::default()
+    };
+    let mut controller = Controller::new(tx, rx, garbage_rx, config);
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let processor = controller.add_processor(LifecycleProcessor(calls.clone()));
+    assert!(calls.lock().unwrap().is_empty());
+
+    for _ in 0..2 {
+      controller
+        .send_render_plan(GraphPlan {
+          nodes: vec![PlanNode::new(processor)],
+          ..GraphPlan::default()
+        })
+        .unwrap();
+    }
+
+    // only prepared the first time it was placed into a plan
+    assert_eq!(
+      *calls.lock().unwrap(),
+      vec![Lifecycle::Prepare(48_000, 128), Lifecycle::Reset]
+    );
+  }
+
+  #[test]
+  fn validate_render_plans() {
+    let (tx, _rx) = RingBuffer::new(4).split();
+    let (_tx, rx) = RingBuffer::new(4).split();
+    let (_garbage_tx, garbage_rx) = RingBuffer::new(4).split();
+    let mut controller = Controller::new(tx, rx, garbage_rx, EngineConfig::default());
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let first = controller.add_processor(LifecycleProcessor(calls.clone()));
+    let second = controller.add_processor(LifecycleProcessor(calls));
+    let node = |processor, path| PlanNode::new(processor).with_path(path);
+
+    let plan = GraphPlan {
+      nodes: vec![node(first, "/root/first").with_dependency(second)],
+      ..GraphPlan::default()
+    };
+    assert_eq!(
+      controller.send_render_plan(plan),
+      Err(Error::UnreachableNode("/root/first".to_string()))
+    );
+
+    let silence = controller.add_audio_buffer();
+    let plan = GraphPlan {
+      nodes: vec![node(first, "/root/first")
+        .with_audio_input_port(vec![silence])
+        .with_required_input(PlanRequiredInput::Audio(0, "audio-in".to_string()))],
+      silence: Some(silence),
+      ..GraphPlan::default()
+    };
+    assert_eq!(
+      controller.send_render_plan(plan),
+      Err(Error::RequiredInputNotConnected(
+        "/root/first".to_string(),
+        "audio-in".to_string()
+      ))
+    );
+
+    let (other_tx, _rx) = RingBuffer::new(4).split();
+    let (_tx, other_rx) = RingBuffer::new(4).split();
+    let (_garbage_tx, other_garbage_rx) = RingBuffer::new(4).split();
+    let mut other = Controller::new(
+      other_tx,
+      other_rx,
+      other_garbage_rx,
+      EngineConfig::default(),
+    );
+    let missing = other.add_event_buffer();
+    let plan = GraphPlan {
+      nodes: vec![node(first, "/root/first").with_event_input(missing)],
+      ..GraphPlan::default()
+    };
+    assert_eq!(
+      controller.send_render_plan(plan),
+      Err(Error::EventsBufferMissing("/root/first".to_string()))
+    );
+
+    let buffer = controller.add_audio_buffer();
+    let plan = |dependencies: Vec<ProcessorKey>| GraphPlan {
+      nodes: vec![
+        node(first, "/root/first").with_audio_output_port(vec![buffer]),
+        node(second, "/root/second")
+          .with_audio_output_port(vec![buffer])
+          .with_dependencies(dependencies),
+      ],
+      ..GraphPlan::default()
+    };
+    assert_eq!(
+      controller.send_render_plan(plan(vec![])),
+      Err(Error::SharedOutputBuffer(
+        "/root/first".to_string(),
+        "/root/second".to_string()
+      ))
+    );
+    // the buffer can be reused once the first node has rendered
+    assert!(controller.send_render_plan(plan(vec![first])).is_ok());
+  }
+
+  #[test]
+  fn release_the_buffers_not_used_by_the_new_plan() {
+    let (tx, _rx) = RingBuffer::new(4).split();
+    let (_tx, rx) = RingBuffer::new(4).split();
+    let (_garbage_tx, garbage_rx) = RingBuffer::new(4).split();
+    let mut controller = Controller::new(tx, rx, garbage_rx, EngineConfig::default());
+
+    for _ in 0..3 {
+      let audio_buffer = controller.add_audio_buffer();
+      let events_buffer = controller.add_event_buffer();
+      controller
+        .send_render_plan(GraphPlan {
+          audio_outputs: vec![audio_buffer],
+          events_outputs: vec![events_buffer],
+          ..GraphPlan::default()
+        })
+        .unwrap();
+      assert_eq!(controller.audio_buffers.len(), 1);
+      assert_eq!(controller.event_buffers.len(), 1);
+    }
+  }
 
   // struct TestProcessor(NodeDescriptor);
   //
@@ -1,29 +1,108 @@
-use crate::rendering::controller::{AudioBufferKey, EventsBufferKey, ParamKey, ProcessorKey};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::graph::connection::{NodeAudioOut, NodeEventsOut, NodeOut};
+use crate::graph::port::{ChannelLayout, InputPort, NodeLike};
+use crate::graph::{self, Graph, NodeKey};
+use crate::key_store::HasId;
+use crate::rendering::controller::{
+  AudioBufferKey, Controller, EventsBufferKey, ParamKey, ProcessorKey,
+};
+use crate::rendering::meters::MeterKey;
+use crate::{AudioDescriptor, EventsDescriptor};
+
+/// Audio buffer modulating a parameter, as in `base + depth * amount` clamped to `min..=max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanModulation {
+  pub(crate) parameter: usize,
+  pub(crate) buffer: AudioBufferKey,
+  pub(crate) depth: f32,
+  pub(crate) min: f32,
+  pub(crate) max: f32,
+}
+
+impl PlanModulation {
+  pub fn new(parameter: usize, buffer: AudioBufferKey, depth: f32, min: f32, max: f32) -> Self {
+    Self {
+      parameter,
+      buffer,
+      depth,
+      min,
+      max,
+    }
+  }
+}
+
+/// Buffers summed (or merged for events) into an output buffer, for inputs with many sources.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanMix<K> {
+  pub(crate) sources: Vec<K>,
+  pub(crate) output: K,
+}
+
+impl<K> PlanMix<K> {
+  pub fn new(sources: Vec<K>, output: K) -> Self {
+    Self { sources, output }
+  }
+}
+
+/// Input of a node that must have a source, by its index and id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanRequiredInput {
+  Audio(usize, String),
+  Events(usize, String),
+}
 
 #[derive(Debug)]
 pub struct PlanNode {
   pub(crate) processor: ProcessorKey,
+  /// Path of the node, to describe the problems found when validating the plan
+  pub(crate) path: String,
   pub(crate) parameters: Vec<ParamKey>,
+  pub(crate) modulations: Vec<PlanModulation>,
+  pub(crate) audio_mixes: Vec<PlanMix<AudioBufferKey>>,
+  pub(crate) events_merges: Vec<PlanMix<EventsBufferKey>>,
   pub(crate) audio_input_buffers: Vec<Vec<AudioBufferKey>>,
   pub(crate) audio_output_buffers: Vec<Vec<AudioBufferKey>>,
   pub(crate) events_input_buffers: Vec<EventsBufferKey>,
   pub(crate) events_output_buffers: Vec<EventsBufferKey>,
+  /// Meters by audio output index
+  pub(crate) meters: Vec<(usize, MeterKey)>,
   pub(crate) dependencies: Vec<ProcessorKey>,
+  /// Index of the dry/wet parameter
+  pub(crate) dry_wet: Option<usize>,
+  pub(crate) required_inputs: Vec<PlanRequiredInput>,
 }
 
 impl PlanNode {
   pub fn new(processor: ProcessorKey) -> Self {
     Self {
       processor,
+      path: String::new(),
       parameters: Vec::new(),
+      modulations: Vec::new(),
+      audio_mixes: Vec::new(),
+      events_merges: Vec::new(),
       audio_input_buffers: Vec::new(),
       audio_output_buffers: Vec::new(),
       events_input_buffers: Vec::new(),
       events_output_buffers: Vec::new(),
+      meters: Vec::new(),
       dependencies: Vec::new(),
+      dry_wet: None,
+      required_inputs: Vec::new(),
     }
   }
 
+  pub fn with_path<S: Into<String>>(mut self, path: S) -> Self {
+    self.path = path.into();
+    self
+  }
+
+  pub fn with_required_input(mut self, input: PlanRequiredInput) -> Self {
+    self.required_inputs.push(input);
+    self
+  }
+
   pub fn with_parameters(mut self, parameter_keys: Vec<ParamKey>) -> Self {
     self.parameters.extend(parameter_keys);
     self
@@ -34,6 +113,26 @@ impl PlanNode {
     self
   }
 
+  pub fn with_dry_wet(mut self, dry_wet: Option<usize>) -> Self {
+    self.dry_wet = dry_wet;
+    self
+  }
+
+  pub fn with_modulation(mut self, modulation: PlanModulation) -> Self {
+    self.modulations.push(modulation);
+    self
+  }
+
+  pub fn with_audio_mix(mut self, mix: PlanMix<AudioBufferKey>) -> Self {
+    self.audio_mixes.push(mix);
+    self
+  }
+
+  pub fn with_events_merge(mut self, merge: PlanMix<EventsBufferKey>) -> Self {
+    self.events_merges.push(merge);
+    self
+  }
+
   pub fn with_audio_input_port(mut self, audio_buffer_keys: Vec<AudioBufferKey>) -> Self {
     self.audio_input_buffers.push(audio_buffer_keys);
     self
@@ -64,6 +163,11 @@ impl PlanNode {
     self
   }
 
+  pub fn with_meter(mut self, port: usize, meter: MeterKey) -> Self {
+    self.meters.push((port, meter));
+    self
+  }
+
   pub fn with_dependencies(mut self, processor_keys: Vec<ProcessorKey>) -> Self {
     self.dependencies.extend(processor_keys);
     self
@@ -74,3 +178,810 @@ impl PlanNode {
     self
   }
 }
+
+/// Processor and parameters rendering a node of the graph.
+#[derive(Debug, Clone)]
+pub struct NodeProcessor {
+  pub processor: ProcessorKey,
+  pub parameters: Vec<ParamKey>,
+}
+
+/// Nodes and engine buffers to build a render plan from.
+#[derive(Debug, Default)]
+pub struct GraphPlan {
+  pub nodes: Vec<PlanNode>,
+  pub audio_inputs: Vec<AudioBufferKey>,
+  pub audio_outputs: Vec<AudioBufferKey>,
+  pub events_inputs: Vec<EventsBufferKey>,
+  pub events_outputs: Vec<EventsBufferKey>,
+  pub audio_output_mixes: Vec<PlanMix<AudioBufferKey>>,
+  pub events_output_merges: Vec<PlanMix<EventsBufferKey>>,
+  /// Buffer read by the audio inputs without sources
+  pub silence: Option<AudioBufferKey>,
+  /// Buffer read by the events inputs without sources
+  pub no_events: Option<EventsBufferKey>,
+}
+
+impl GraphPlan {
+  /// Keys of all the audio buffers used by the plan.
+  pub(crate) fn audio_buffer_keys(&self) -> HashSet<AudioBufferKey> {
+    let mut keys = HashSet::new();
+    keys.extend(self.audio_inputs.iter().cloned());
+    keys.extend(self.audio_outputs.iter().cloned());
+    for mix in self.audio_output_mixes.iter() {
+      keys.extend(mix.sources.iter().cloned());
+      keys.insert(mix.output);
+    }
+    for node in self.nodes.iter() {
+      keys.extend(node.modulations.iter().map(|modulation| modulation.buffer));
+      for mix in node.audio_mixes.iter() {
+        keys.extend(mix.sources.iter().cloned());
+        keys.insert(mix.output);
+      }
+      keys.extend(node.audio_input_buffers.iter().flatten().cloned());
+      keys.extend(node.audio_output_buffers.iter().flatten().cloned());
+    }
+    keys
+  }
+
+  /// Keys of all the events buffers used by the plan.
+  pub(crate) fn events_buffer_keys(&self) -> HashSet<EventsBufferKey> {
+    let mut keys = HashSet::new();
+    keys.extend(self.events_inputs.iter().cloned());
+    keys.extend(self.events_outputs.iter().cloned());
+    for merge in self.events_output_merges.iter() {
+      keys.extend(merge.sources.iter().cloned());
+      keys.insert(merge.output);
+    }
+    for node in self.nodes.iter() {
+      for merge in node.events_merges.iter() {
+        keys.extend(merge.sources.iter().cloned());
+        keys.insert(merge.output);
+      }
+      keys.extend(node.events_input_buffers.iter().cloned());
+      keys.extend(node.events_output_buffers.iter().cloned());
+    }
+    keys
+  }
+}
+
+/// Audio buffer that can be reused once all the processors that read it have rendered.
+struct FreeAudioBuffer {
+  key: AudioBufferKey,
+  readers: Vec<ProcessorKey>,
+}
+
+/// Builds the plan nodes for a graph.
+///
+/// The module bindings are resolved into connections between nodes, which are sorted topologically.
+/// Every node output gets its own buffers, but the audio buffers are reused by later nodes
+/// once all their readers have rendered. In that case the later node depends on those readers,
+/// so the renderer can't overwrite a buffer that is still needed.
+/// Inputs with many sources read a buffer where all of them are summed right before rendering.
+pub struct RenderPlanner<'a> {
+  graph: &'a Graph,
+  processors: &'a HashMap<NodeKey, NodeProcessor>,
+  audio_outputs: HashMap<NodeAudioOut, Vec<AudioBufferKey>>,
+  events_outputs: HashMap<NodeEventsOut, EventsBufferKey>,
+  pending_readers: HashMap<NodeAudioOut, usize>,
+  readers: HashMap<NodeAudioOut, Vec<ProcessorKey>>,
+  free_audio_buffers: Vec<FreeAudioBuffer>,
+  silence: Option<AudioBufferKey>,
+  no_events: Option<EventsBufferKey>,
+}
+
+impl<'a> RenderPlanner<'a> {
+  pub fn new(graph: &'a Graph, processors: &'a HashMap<NodeKey, NodeProcessor>) -> Self {
+    Self {
+      graph,
+      processors,
+      audio_outputs: HashMap::new(),
+      events_outputs: HashMap::new(),
+      pending_readers: HashMap::new(),
+      readers: HashMap::new(),
+      free_audio_buffers: Vec::new(),
+      silence: None,
+      no_events: None,
+    }
+  }
+
+  pub fn plan(mut self, controller: &mut Controller) -> graph::error::Result<GraphPlan> {
+    let mut plan = GraphPlan::default();
+
+    let nodes = self.sorted_nodes()?;
+
+    let inputs_node = self.graph.get_node(self.graph.get_inputs_node())?;
+    for (port_key, port) in inputs_node.ports.audio_output_ports.ordered_iter() {
+      let buffers = (0..port.descriptor.channels())
+        .map(|_| controller.add_audio_buffer())
+        .collect::<Vec<AudioBufferKey>>();
+      plan.audio_inputs.extend(buffers.iter().cloned());
+      let node_out = NodeOut(self.graph.get_inputs_node(), port_key);
+      // the buffers written by the audio driver are never reused
+      self.pending_readers.remove(&node_out);
+      self.audio_outputs.insert(node_out, buffers);
+    }
+    for (port_key, _) in inputs_node.ports.events_output_ports.ordered_iter() {
+      let buffer = controller.add_event_buffer();
+      plan.events_inputs.push(buffer);
+      let node_out = NodeOut(self.graph.get_inputs_node(), port_key);
+      self.events_outputs.insert(node_out, buffer);
+    }
+
+    for node_key in nodes {
+      if let Some(node_processor) = self.processors.get(&node_key) {
+        let plan_node = self.plan_node(controller, node_key, node_processor)?;
+        plan.nodes.push(plan_node);
+      }
+    }
+
+    let outputs_node = self.graph.get_node(self.graph.get_outputs_node())?;
+    // all the nodes have rendered by now, so any free buffer can be used for mixing
+    let mut dependencies = HashSet::new();
+    for (_, port) in outputs_node.ports.audio_input_ports.ordered_iter() {
+      let (buffers, _) = self.audio_input_buffers(
+        controller,
+        port,
+        &mut dependencies,
+        &mut plan.audio_output_mixes,
+      )?;
+      plan.audio_outputs.extend(buffers);
+    }
+    for (_, port) in outputs_node.ports.events_input_ports.ordered_iter() {
+      let (buffer, _) =
+        self.events_input_buffer(controller, port, &mut plan.events_output_merges)?;
+      plan.events_outputs.push(buffer);
+    }
+
+    plan.silence = self.silence;
+    plan.no_events = self.no_events;
+    Ok(plan)
+  }
+
+  /// Sort the nodes so that every node comes after the nodes connected to its inputs.
+  fn sorted_nodes(&mut self) -> graph::error::Result<Vec<NodeKey>> {
+    let mut node_keys = self
+      .graph
+      .get_nodes()
+      .map(|(node_key, _)| node_key)
+      .collect::<Vec<NodeKey>>();
+    node_keys.sort_unstable();
+
+    let mut num_sources = HashMap::<NodeKey, usize>::new();
+    let mut destinations = HashMap::<NodeKey, Vec<NodeKey>>::new();
+    for node_key in node_keys.iter().cloned() {
+      let node = self.graph.get_node(node_key)?;
+      let mut sources = HashSet::<NodeKey>::new();
+      for port in node.ports.audio_input_ports.values() {
+        for node_out in self.graph.input_port_sources(port)? {
+          *self.pending_readers.entry(node_out).or_default() += 1;
+          sources.insert(node_out.node_key());
+        }
+      }
+      for port in node.ports.events_input_ports.values() {
+        for node_out in self.graph.input_port_sources(port)? {
+          sources.insert(node_out.node_key());
+        }
+      }
+      for modulation in node.param_modulations.values() {
+        *self.pending_readers.entry(modulation.source).or_default() += 1;
+        sources.insert(modulation.source.node_key());
+      }
+      num_sources.insert(node_key, sources.len());
+      for source in sources {
+        destinations.entry(source).or_default().push(node_key);
+      }
+    }
+
+    let mut ready = node_keys
+      .iter()
+      .filter(|node_key| num_sources[node_key] == 0)
+      .cloned()
+      .collect::<VecDeque<NodeKey>>();
+
+    let mut sorted = Vec::with_capacity(node_keys.len());
+    while let Some(node_key) = ready.pop_front() {
+      sorted.push(node_key);
+      if let Some(destinations) = destinations.get_mut(&node_key) {
+        destinations.sort_unstable();
+        for destination in destinations.iter() {
+          let count = num_sources.get_mut(destination).unwrap();
+          *count -= 1;
+          if *count == 0 {
+            ready.push_back(*destination);
+          }
+        }
+      }
+    }
+
+    if sorted.len() < node_keys.len() {
+      let cycle = node_keys
+        .iter()
+        .filter(|node_key| num_sources[node_key] > 0)
+        .map(|node_key| self.graph.get_node(*node_key).map(|node| node.full_name()))
+        .collect::<graph::error::Result<Vec<String>>>()?;
+      return Err(graph::Error::CycleDetected(cycle.join(", ")));
+    }
+
+    Ok(sorted)
+  }
+
+  fn plan_node(
+    &mut self,
+    controller: &mut Controller,
+    node_key: NodeKey,
+    node_processor: &NodeProcessor,
+  ) -> graph::error::Result<PlanNode> {
+    let node = self.graph.get_node(node_key)?;
+    let mut plan_node = PlanNode::new(node_processor.processor)
+      .with_path(node.full_name())
+      .with_parameters(node_processor.parameters.clone())
+      .with_dry_wet(node.descriptor.dry_wet);
+    let mut dependencies = HashSet::<ProcessorKey>::new();
+
+    // the outputs are allocated before releasing the inputs, so they never share buffers
+    let mut unread_outputs = Vec::new();
+    for (index, (port_key, port)) in node.ports.audio_output_ports.ordered_iter().enumerate() {
+      let buffers = (0..port.descriptor.channels())
+        .map(|_| self.allocate_audio_buffer(controller, &mut dependencies))
+        .collect::<Vec<AudioBufferKey>>();
+      plan_node = plan_node.with_audio_output_port(buffers.clone());
+      let node_out = NodeOut(node_key, port_key);
+      if let Some(meter) = controller.meter_key(node_out) {
+        plan_node = plan_node.with_meter(index, meter);
+      }
+      if !self.pending_readers.contains_key(&node_out) {
+        unread_outputs.push(node_out);
+      }
+      self.audio_outputs.insert(node_out, buffers);
+    }
+
+    for (port_key, _) in node.ports.events_output_ports.ordered_iter() {
+      let buffer = controller.add_event_buffer();
+      plan_node = plan_node.with_event_output(buffer);
+      let node_out = NodeOut(node_key, port_key);
+      self.events_outputs.insert(node_out, buffer);
+    }
+
+    let mut read_outputs = Vec::new();
+    let mut audio_mixes = Vec::new();
+    for (index, (_, port)) in node.ports.audio_input_ports.ordered_iter().enumerate() {
+      let (buffers, node_outs) =
+        self.audio_input_buffers(controller, port, &mut dependencies, &mut audio_mixes)?;
+      plan_node = plan_node.with_audio_input_port(buffers);
+      if port.descriptor.is_required() {
+        let input = PlanRequiredInput::Audio(index, port.descriptor.id().to_string());
+        plan_node = plan_node.with_required_input(input);
+      }
+      for node_out in node_outs {
+        self.add_source_dependency(node_out.node_key(), &mut dependencies);
+        self
+          .readers
+          .entry(node_out)
+          .or_default()
+          .push(node_processor.processor);
+        read_outputs.push(node_out);
+      }
+    }
+
+    let mut events_merges = Vec::new();
+    for (index, (_, port)) in node.ports.events_input_ports.ordered_iter().enumerate() {
+      let (buffer, node_outs) = self.events_input_buffer(controller, port, &mut events_merges)?;
+      plan_node = plan_node.with_event_input(buffer);
+      if port.descriptor.is_required() {
+        let input = PlanRequiredInput::Events(index, port.descriptor.id().to_string());
+        plan_node = plan_node.with_required_input(input);
+      }
+      for node_out in node_outs {
+        self.add_source_dependency(node_out.node_key(), &mut dependencies);
+      }
+    }
+
+    let mut modulations = node.param_modulations.iter().collect::<Vec<_>>();
+    modulations.sort_unstable_by_key(|(index, _)| **index);
+    for (index, modulation) in modulations {
+      let param = &node.descriptor.parameters[*index];
+      let buffer = match self
+        .audio_outputs
+        .get(&modulation.source)
+        .and_then(|buffers| buffers.first())
+      {
+        Some(buffer) => *buffer,
+        None => *self
+          .silence
+          .get_or_insert_with(|| controller.add_audio_buffer()),
+      };
+      plan_node = plan_node.with_modulation(PlanModulation::new(
+        *index,
+        buffer,
+        modulation.depth,
+        param.min,
+        param.max,
+      ));
+      self.add_source_dependency(modulation.source.node_key(), &mut dependencies);
+      self
+        .readers
+        .entry(modulation.source)
+        .or_default()
+        .push(node_processor.processor);
+      read_outputs.push(modulation.source);
+    }
+
+    for node_out in read_outputs {
+      if let Some(count) = self.pending_readers.get_mut(&node_out) {
+        *count -= 1;
+        if *count == 0 {
+          self.pending_readers.remove(&node_out);
+          let readers = self.readers.remove(&node_out).unwrap_or_default();
+          self.release_audio_buffers(node_out, readers);
+        }
+      }
+    }
+
+    for node_out in unread_outputs {
+      self.release_audio_buffers(node_out, vec![node_processor.processor]);
+    }
+
+    for mix in audio_mixes.iter() {
+      self.free_audio_buffers.push(FreeAudioBuffer {
+        key: mix.output,
+        readers: vec![node_processor.processor],
+      });
+    }
+    for mix in audio_mixes {
+      plan_node = plan_node.with_audio_mix(mix);
+    }
+    for merge in events_merges {
+      plan_node = plan_node.with_events_merge(merge);
+    }
+
+    dependencies.remove(&node_processor.processor);
+    let mut dependencies = dependencies.into_iter().collect::<Vec<ProcessorKey>>();
+    dependencies.sort_unstable();
+
+    Ok(plan_node.with_dependencies(dependencies))
+  }
+
+  fn add_source_dependency(&self, source: NodeKey, dependencies: &mut HashSet<ProcessorKey>) {
+    if let Some(source_processor) = self.processors.get(&source) {
+      dependencies.insert(source_processor.processor);
+    }
+  }
+
+  /// Buffers for every channel of an input port, and the node outputs connected to it.
+  ///
+  /// The channels without a source, or with more channels than the sources have, read silence.
+  /// The channels with many sources read a new buffer where they are mixed.
+  /// Mono sources are read by every channel of stereo inputs.
+  fn audio_input_buffers(
+    &mut self,
+    controller: &mut Controller,
+    port: &InputPort<AudioDescriptor>,
+    dependencies: &mut HashSet<ProcessorKey>,
+    mixes: &mut Vec<PlanMix<AudioBufferKey>>,
+  ) -> graph::error::Result<(Vec<AudioBufferKey>, Vec<NodeAudioOut>)> {
+    let node_outs = self.graph.input_port_sources(port)?;
+    let layout = port.descriptor.layout();
+    let source_layouts = node_outs
+      .iter()
+      .map(|node_out| {
+        let node = self.graph.get_node(node_out.node_key())?;
+        let source_port = node.get_output_port(node_out.output_port_key())?;
+        Ok(source_port.descriptor.layout())
+      })
+      .collect::<graph::error::Result<Vec<&ChannelLayout>>>()?;
+    let mut buffers = Vec::with_capacity(port.descriptor.channels());
+    for channel in 0..port.descriptor.channels() {
+      let sources = node_outs
+        .iter()
+        .zip(source_layouts.iter())
+        .filter_map(|(node_out, source_layout)| {
+          let source_channel = layout.source_channel(source_layout, channel);
+          self
+            .audio_outputs
+            .get(node_out)
+            .and_then(|source_buffers| source_buffers.get(source_channel).cloned())
+        })
+        .collect::<Vec<AudioBufferKey>>();
+      let buffer = match sources.as_slice() {
+        [] => *self
+          .silence
+          .get_or_insert_with(|| controller.add_audio_buffer()),
+        [source] => *source,
+        _ => {
+          let output = self.allocate_audio_buffer(controller, dependencies);
+          mixes.push(PlanMix::new(sources, output));
+          output
+        }
+      };
+      buffers.push(buffer);
+    }
+    Ok((buffers, node_outs))
+  }
+
+  /// Buffer for an events input port, and the node outputs connected to it.
+  ///
+  /// When there are many sources, the buffer is a new one where their events are merged.
+  fn events_input_buffer(
+    &mut self,
+    controller: &mut Controller,
+    port: &InputPort<EventsDescriptor>,
+    merges: &mut Vec<PlanMix<EventsBufferKey>>,
+  ) -> graph::error::Result<(EventsBufferKey, Vec<NodeEventsOut>)> {
+    let node_outs = self.graph.input_port_sources(port)?;
+    let sources = node_outs
+      .iter()
+      .filter_map(|node_out| self.events_outputs.get(node_out).cloned())
+      .collect::<Vec<EventsBufferKey>>();
+    let buffer = match sources.as_slice() {
+      [] => *self
+        .no_events
+        .get_or_insert_with(|| controller.add_event_buffer()),
+      [source] => *source,
+      _ => {
+        let output = controller.add_event_buffer();
+        merges.push(PlanMix::new(sources, output));
+        output
+      }
+    };
+    Ok((buffer, node_outs))
+  }
+
+  fn allocate_audio_buffer(
+    &mut self,
+    controller: &mut Controller,
+    dependencies: &mut HashSet<ProcessorKey>,
+  ) -> AudioBufferKey {
+    match self.free_audio_buffers.pop() {
+      Some(free_buffer) => {
+        dependencies.extend(free_buffer.readers);
+        free_buffer.key
+      }
+      None => controller.add_audio_buffer(),
+    }
+  }
+
+  fn release_audio_buffers(&mut self, node_out: NodeAudioOut, readers: Vec<ProcessorKey>) {
+    if let Some(buffers) = self.audio_outputs.get(&node_out) {
+      for key in buffers.iter().rev().cloned() {
+        self.free_audio_buffers.push(FreeAudioBuffer {
+          key,
+          readers: readers.clone(),
+        });
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ringbuf::RingBuffer;
+
+  use super::*;
+  use crate::graph::{ModuleDescriptor, NodeDescriptor};
+  use crate::{EngineConfig, ParamDescriptor, Processor, ProcessorContext};
+
+  struct TestProcessor;
+
+  impl Processor for TestProcessor {
+    fn render(&mut self, _context: &mut ProcessorContext) {}
+  }
+
+  fn controller() -> Controller {
+    let (tx, _) = RingBuffer::new(1).split();
+    let (_, rx) = RingBuffer::new(1).split();
+    let (_, garbage) = RingBuffer::new(1).split();
+    Controller::new(tx, rx, garbage, EngineConfig::default())
+  }
+
+  fn mono_descriptor() -> NodeDescriptor {
+    NodeDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    })
+  }
+
+  fn create_node(
+    g: &mut Graph,
+    controller: &mut Controller,
+    processors: &mut HashMap<NodeKey, NodeProcessor>,
+    parent: graph::ModuleKey,
+    name: &str,
+  ) -> NodeKey {
+    let node_key = g.create_node(parent, name, mono_descriptor()).unwrap();
+    let processor = controller.add_processor(TestProcessor);
+    processors.insert(
+      node_key,
+      NodeProcessor {
+        processor,
+        parameters: Vec::new(),
+      },
+    );
+    node_key
+  }
+
+  fn plan_node(
+    plan: &GraphPlan,
+    processors: &HashMap<NodeKey, NodeProcessor>,
+    node_key: NodeKey,
+  ) -> usize {
+    let processor = processors[&node_key].processor;
+    plan
+      .nodes
+      .iter()
+      .position(|plan_node| plan_node.processor == processor)
+      .unwrap()
+  }
+
+  #[test]
+  fn resolve_bindings_through_nested_modules() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+
+    let module_descriptor = ModuleDescriptor::new().with_audio_ports(|ports| {
+      ports
+        .static_inputs(vec![AudioDescriptor::new("audio-in", 1)])
+        .static_outputs(vec![AudioDescriptor::new("audio-out", 1)])
+    });
+    let m1 = g
+      .create_module(g.get_root_module(), "m1", module_descriptor.clone())
+      .unwrap();
+    let m2 = g.create_module(m1, "m2", module_descriptor).unwrap();
+    // created in reverse order to check that the topological order doesn't depend on the keys
+    let n2 = create_node(&mut g, &mut controller, &mut processors, m2, "n2");
+    let n1 = create_node(&mut g, &mut controller, &mut processors, m1, "n1");
+
+    let inputs = g.node_audio_outputs(g.get_inputs_node()).unwrap();
+    let outputs = g.node_audio_inputs(g.get_outputs_node()).unwrap();
+    let m1_in = g.module_audio_inputs(m1).unwrap();
+    let m1_out = g.module_audio_outputs(m1).unwrap();
+    let m2_in = g.module_audio_inputs(m2).unwrap();
+    let m2_out = g.module_audio_outputs(m2).unwrap();
+    let n1_in = g.node_audio_inputs(n1).unwrap();
+    let n1_out = g.node_audio_outputs(n1).unwrap();
+    let n2_in = g.node_audio_inputs(n2).unwrap();
+    let n2_out = g.node_audio_outputs(n2).unwrap();
+
+    g.connect_audio(inputs[0].to(m1_in[0])).unwrap();
+    g.connect_audio(m1_in[0].bind(n1_in[0])).unwrap();
+    g.connect_audio(n1_out[0].to(m2_in[0])).unwrap();
+    g.connect_audio(m2_in[0].bind(n2_in[0])).unwrap();
+    g.connect_audio(n2_out[0].bind(m2_out[0])).unwrap();
+    g.connect_audio(m2_out[0].bind(m1_out[0])).unwrap();
+    g.connect_audio(m1_out[0].to(outputs[0])).unwrap();
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    assert_eq!(plan.nodes.len(), 2);
+    let p1 = &plan.nodes[plan_node(&plan, &processors, n1)];
+    let p2 = &plan.nodes[plan_node(&plan, &processors, n2)];
+    assert!(plan_node(&plan, &processors, n1) < plan_node(&plan, &processors, n2));
+
+    assert_eq!(p1.audio_input_buffers, vec![plan.audio_inputs.clone()]);
+    assert_eq!(p2.audio_input_buffers, p1.audio_output_buffers);
+    assert_eq!(p2.audio_output_buffers, vec![plan.audio_outputs.clone()]);
+    assert!(p1.dependencies.is_empty());
+    assert_eq!(p2.dependencies, vec![p1.processor]);
+  }
+
+  #[test]
+  fn reuse_audio_buffers() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let n1 = create_node(&mut g, &mut controller, &mut processors, root, "n1");
+    let n2 = create_node(&mut g, &mut controller, &mut processors, root, "n2");
+    let n3 = create_node(&mut g, &mut controller, &mut processors, root, "n3");
+
+    let outputs = g.node_audio_inputs(g.get_outputs_node()).unwrap();
+    let n1_out = g.node_audio_outputs(n1).unwrap();
+    let n2_in = g.node_audio_inputs(n2).unwrap();
+    let n2_out = g.node_audio_outputs(n2).unwrap();
+    let n3_in = g.node_audio_inputs(n3).unwrap();
+    let n3_out = g.node_audio_outputs(n3).unwrap();
+
+    g.connect_audio(n1_out[0].to(n2_in[0])).unwrap();
+    g.connect_audio(n2_out[0].to(n3_in[0])).unwrap();
+    g.connect_audio(n3_out[0].to(outputs[0])).unwrap();
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    let p1 = &plan.nodes[plan_node(&plan, &processors, n1)];
+    let p2 = &plan.nodes[plan_node(&plan, &processors, n2)];
+    let p3 = &plan.nodes[plan_node(&plan, &processors, n3)];
+
+    // the output of n1 is not needed anymore once n2 has rendered
+    assert_eq!(p3.audio_output_buffers, p1.audio_output_buffers);
+    assert_ne!(p2.audio_output_buffers, p1.audio_output_buffers);
+    assert_eq!(p3.dependencies, vec![p2.processor]);
+    assert_eq!(plan.audio_outputs, p3.audio_output_buffers[0]);
+  }
+
+  #[test]
+  fn depend_on_the_readers_of_reused_buffers() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let n1 = create_node(&mut g, &mut controller, &mut processors, root, "n1");
+    let n2 = create_node(&mut g, &mut controller, &mut processors, root, "n2");
+    let n3 = create_node(&mut g, &mut controller, &mut processors, root, "n3");
+
+    let outputs = g.node_audio_inputs(g.get_outputs_node()).unwrap();
+    let n1_out = g.node_audio_outputs(n1).unwrap();
+    let n2_in = g.node_audio_inputs(n2).unwrap();
+    let n3_in = g.node_audio_inputs(n3).unwrap();
+    let n3_out = g.node_audio_outputs(n3).unwrap();
+
+    // the output of n2 is never read, so its buffer is reused by n3
+    g.connect_audio(n1_out[0].to(n2_in[0])).unwrap();
+    g.connect_audio(n1_out[0].to(n3_in[0])).unwrap();
+    g.connect_audio(n3_out[0].to(outputs[0])).unwrap();
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    let p1 = &plan.nodes[plan_node(&plan, &processors, n1)];
+    let p2 = &plan.nodes[plan_node(&plan, &processors, n2)];
+    let p3 = &plan.nodes[plan_node(&plan, &processors, n3)];
+
+    assert_eq!(p3.audio_output_buffers, p2.audio_output_buffers);
+    let mut expected_dependencies = vec![p1.processor, p2.processor];
+    expected_dependencies.sort_unstable();
+    assert_eq!(p3.dependencies, expected_dependencies);
+  }
+
+  #[test]
+  fn unconnected_inputs_read_silence() {
+    let mut g = Graph::new(2, 2);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let n1 = create_node(&mut g, &mut controller, &mut processors, root, "n1");
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    let p1 = &plan.nodes[plan_node(&plan, &processors, n1)];
+    let silence = p1.audio_input_buffers[0][0];
+    assert_eq!(plan.audio_inputs.len(), 2);
+    assert_eq!(plan.audio_outputs, vec![silence, silence]);
+    assert_eq!(plan.events_inputs.len(), 1);
+    assert_eq!(plan.events_outputs.len(), 1);
+  }
+
+  #[test]
+  fn modulations_read_the_source_buffer() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let lfo = create_node(&mut g, &mut controller, &mut processors, root, "lfo");
+    let osc = g
+      .create_node(
+        root,
+        "osc",
+        mono_descriptor().with_parameters(vec![ParamDescriptor::new("freq").min(-1.0)]),
+      )
+      .unwrap();
+    let processor = controller.add_processor(TestProcessor);
+    processors.insert(
+      osc,
+      NodeProcessor {
+        processor,
+        parameters: Vec::new(),
+      },
+    );
+
+    let lfo_out = g.node_audio_outputs(lfo).unwrap();
+    g.modulate_param(lfo_out[0], osc, 0, 0.5).unwrap();
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    assert!(plan_node(&plan, &processors, lfo) < plan_node(&plan, &processors, osc));
+    let p1 = &plan.nodes[plan_node(&plan, &processors, lfo)];
+    let p2 = &plan.nodes[plan_node(&plan, &processors, osc)];
+    assert_eq!(p2.dependencies, vec![p1.processor]);
+    assert_eq!(
+      p2.modulations,
+      vec![PlanModulation::new(
+        0,
+        p1.audio_output_buffers[0][0],
+        0.5,
+        -1.0,
+        1.0
+      )]
+    );
+  }
+
+  #[test]
+  fn sum_many_sources() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let n1 = create_node(&mut g, &mut controller, &mut processors, root, "n1");
+    let n2 = create_node(&mut g, &mut controller, &mut processors, root, "n2");
+    let n3 = create_node(&mut g, &mut controller, &mut processors, root, "n3");
+    let n4 = create_node(&mut g, &mut controller, &mut processors, root, "n4");
+
+    let outputs = g.node_audio_inputs(g.get_outputs_node()).unwrap();
+    let n1_out = g.node_audio_outputs(n1).unwrap();
+    let n2_out = g.node_audio_outputs(n2).unwrap();
+    let n3_in = g.node_audio_inputs(n3).unwrap();
+    let n3_out = g.node_audio_outputs(n3).unwrap();
+    let n4_out = g.node_audio_outputs(n4).unwrap();
+
+    g.add_audio(n1_out[0].to(n3_in[0])).unwrap();
+    g.add_audio(n2_out[0].to(n3_in[0])).unwrap();
+    g.add_audio(n3_out[0].to(outputs[0])).unwrap();
+    g.add_audio(n4_out[0].to(outputs[0])).unwrap();
+
+    let plan = RenderPlanner::new(&g, &processors)
+      .plan(&mut controller)
+      .unwrap();
+
+    let p1 = &plan.nodes[plan_node(&plan, &processors, n1)];
+    let p2 = &plan.nodes[plan_node(&plan, &processors, n2)];
+    let p3 = &plan.nodes[plan_node(&plan, &processors, n3)];
+    let p4 = &plan.nodes[plan_node(&plan, &processors, n4)];
+
+    let mut expected_dependencies = vec![p1.processor, p2.processor];
+    expected_dependencies.sort_unstable();
+    assert_eq!(p3.dependencies, expected_dependencies);
+    assert_eq!(
+      p3.audio_mixes,
+      vec![PlanMix::new(
+        vec![p1.audio_output_buffers[0][0], p2.audio_output_buffers[0][0]],
+        p3.audio_input_buffers[0][0]
+      )]
+    );
+    assert_ne!(p3.audio_input_buffers[0][0], p3.audio_output_buffers[0][0]);
+
+    assert_eq!(
+      plan.audio_output_mixes,
+      vec![PlanMix::new(
+        vec![p3.audio_output_buffers[0][0], p4.audio_output_buffers[0][0]],
+        plan.audio_outputs[0]
+      )]
+    );
+  }
+
+  #[test]
+  fn cycles_are_detected() {
+    let mut g = Graph::new(1, 1);
+    let mut controller = controller();
+    let mut processors = HashMap::new();
+    let root = g.get_root_module();
+
+    let n1 = create_node(&mut g, &mut controller, &mut processors, root, "n1");
+    let n2 = create_node(&mut g, &mut controller, &mut processors, root, "n2");
+
+    let n1_in = g.node_audio_inputs(n1).unwrap();
+    let n1_out = g.node_audio_outputs(n1).unwrap();
+    let n2_in = g.node_audio_inputs(n2).unwrap();
+    let n2_out = g.node_audio_outputs(n2).unwrap();
+
+    g.connect_audio(n1_out[0].to(n2_in[0])).unwrap();
+    g.connect_audio(n2_out[0].to(n1_in[0])).unwrap();
+
+    let result = RenderPlanner::new(&g, &processors).plan(&mut controller);
+    assert!(matches!(result, Err(graph::Error::CycleDetected(_))));
+  }
+}
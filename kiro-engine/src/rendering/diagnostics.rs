@@ -0,0 +1,273 @@
+use ringbuf::{Consumer, Producer, RingBuffer};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Compact snapshot of the renderer state captured when a dropout happens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropoutSnapshot {
+  pub cause: DropoutCause,
+  pub num_samples: usize,
+  pub render_time: Duration,
+  pub render_budget: Option<Duration>,
+  pub plan_nodes: usize,
+  /// Time that every node of the render plan took to render the last block, by node index,
+  /// which might not include all the nodes of a plan that grew since the previous dropout
+  pub node_times: Vec<(usize, Duration)>,
+  pub slowest_node: Option<(usize, Duration)>,
+  pub pending_messages: usize,
+  /// Last messages processed by the renderer, the oldest first
+  pub last_messages: Vec<MessageKind>,
+}
+
+impl DropoutSnapshot {
+  fn with_capacity(nodes: usize) -> Self {
+    Self {
+      cause: DropoutCause::Xrun,
+      num_samples: 0,
+      render_time: Duration::ZERO,
+      render_budget: None,
+      plan_nodes: 0,
+      node_times: Vec::with_capacity(nodes),
+      slowest_node: None,
+      pending_messages: 0,
+      last_messages: Vec::with_capacity(RecentMessages::CAPACITY),
+    }
+  }
+}
+
+/// Kind of a message from the controller processed by the renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+  MoveRenderPlan,
+  SetSampleRate,
+  /// Number of parameters set at once
+  SetParameterValues(usize),
+  SetBypass,
+  LoadProcessorState,
+}
+
+/// Ring with the kinds of the last messages processed by the renderer, preallocated to be used in the audio thread.
+#[derive(Debug)]
+pub(crate) struct RecentMessages {
+  messages: [Option<MessageKind>; Self::CAPACITY],
+  position: usize,
+}
+
+impl RecentMessages {
+  pub const CAPACITY: usize = 16;
+
+  pub fn push(&mut self, kind: MessageKind) {
+    self.messages[self.position] = Some(kind);
+    self.position = (self.position + 1) % Self::CAPACITY;
+  }
+
+  /// Copy the messages into the output, the oldest first, without growing it.
+  pub fn copy_into(&self, output: &mut Vec<MessageKind>) {
+    let (newest, oldest) = self.messages.split_at(self.position);
+    let capacity = output.capacity() - output.len();
+    let messages = oldest.iter().chain(newest.iter()).flatten();
+    output.extend(messages.take(capacity));
+  }
+}
+
+impl Default for RecentMessages {
+  fn default() -> Self {
+    Self {
+      messages: [None; Self::CAPACITY],
+      position: 0,
+    }
+  }
+}
+
+/// Reports the dropouts from the renderer into the snapshots preallocated by the [`Diagnostics`],
+/// so nothing is allocated nor deallocated in the audio thread.
+pub struct DropoutReporter {
+  tx: Producer<DropoutSnapshot>,
+  spare: Consumer<DropoutSnapshot>,
+}
+
+impl DropoutReporter {
+  /// Fill a spare snapshot and send it to the diagnostics,
+  /// or drop the report if all of them are waiting to be read.
+  pub(crate) fn report<F>(&mut self, fill: F)
+  where
+    F: FnOnce(&mut DropoutSnapshot),
+  {
+    if let Some(mut snapshot) = self.spare.pop() {
+      fill(&mut snapshot);
+      // there are never more snapshots than room in the ring buffer, so it doesn't fail
+      self.tx.push(snapshot).ok();
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropoutCause {
+  /// The audio driver reported an under-run or over-run
+  Xrun,
+  /// The rendering took longer than the time available for the block
+  Watchdog,
+}
+
+/// Keeps the last dropout snapshots reported by the renderer.
+pub struct Diagnostics {
+  rx: Consumer<DropoutSnapshot>,
+  spare: Producer<DropoutSnapshot>,
+  capacity: usize,
+  dropouts: VecDeque<DropoutSnapshot>,
+}
+
+impl Diagnostics {
+  /// Nodes with room for their times in the snapshots, until a dropout reports a bigger plan
+  const INITIAL_NODES: usize = 64;
+
+  pub(crate) fn new(capacity: usize) -> (Self, DropoutReporter) {
+    let capacity = capacity.max(1);
+    let (tx, rx) = RingBuffer::new(capacity).split();
+    let (mut spare, spare_rx) = RingBuffer::new(capacity).split();
+    for _ in 0..capacity {
+      spare
+        .push(DropoutSnapshot::with_capacity(Self::INITIAL_NODES))
+        .ok();
+    }
+    let diagnostics = Self {
+      rx,
+      spare,
+      capacity,
+      dropouts: VecDeque::with_capacity(capacity),
+    };
+    let reporter = DropoutReporter {
+      tx,
+      spare: spare_rx,
+    };
+    (diagnostics, reporter)
+  }
+
+  pub fn dropouts(&mut self) -> impl Iterator<Item = &DropoutSnapshot> {
+    self.update();
+    self.dropouts.iter()
+  }
+
+  pub fn clear(&mut self) {
+    self.update();
+    self.dropouts.clear();
+  }
+
+  fn update(&mut self) {
+    while let Some(mut snapshot) = self.rx.pop() {
+      if self.dropouts.len() == self.capacity {
+        self.dropouts.pop_front();
+      }
+      self.dropouts.push_back(snapshot.clone());
+
+      // give the snapshot back with room for the times of all the nodes of the plan
+      snapshot.node_times.clear();
+      snapshot.node_times.reserve(snapshot.plan_nodes);
+      snapshot.last_messages.clear();
+      self.spare.push(snapshot).ok();
+    }
+  }
+}
+
+/// Smoothed ratio between the time spent rendering a block and its duration,
+/// updated by the renderer when the watchdog is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct RenderLoad(Arc<AtomicU32>);
+
+impl RenderLoad {
+  const SMOOTHING: f32 = 0.1;
+
+  pub fn get(&self) -> f32 {
+    f32::from_bits(self.0.load(Ordering::Relaxed))
+  }
+
+  pub(crate) fn update(&self, load: f32) {
+    let smoothed = self.get() + (load - self.get()) * Self::SMOOTHING;
+    self.0.store(smoothed.to_bits(), Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn report(reporter: &mut DropoutReporter, num_samples: usize, plan_nodes: usize) {
+    reporter.report(|snapshot| {
+      snapshot.num_samples = num_samples;
+      snapshot.plan_nodes = plan_nodes;
+      let capacity = snapshot.node_times.capacity();
+      let node_times = (0..plan_nodes).map(|index| (index, Duration::from_micros(index as u64)));
+      snapshot.node_times.extend(node_times.take(capacity));
+    });
+  }
+
+  #[test]
+  fn dropouts_keeps_the_last_snapshots() {
+    let (mut diagnostics, mut reporter) = Diagnostics::new(2);
+
+    report(&mut reporter, 1, 0);
+    report(&mut reporter, 2, 0);
+    // there is no spare snapshot left until the diagnostics are read
+    report(&mut reporter, 3, 0);
+    let dropouts = diagnostics.dropouts().map(|snapshot| snapshot.num_samples);
+    assert_eq!(dropouts.collect::<Vec<_>>(), vec![1, 2]);
+
+    report(&mut reporter, 4, 0);
+    let dropouts = diagnostics.dropouts().map(|snapshot| snapshot.num_samples);
+    assert_eq!(dropouts.collect::<Vec<_>>(), vec![2, 4]);
+
+    diagnostics.clear();
+    assert_eq!(diagnostics.dropouts().count(), 0);
+  }
+
+  #[test]
+  fn snapshots_grow_to_the_size_of_the_plan() {
+    let (mut diagnostics, mut reporter) = Diagnostics::new(1);
+    let plan_nodes = Diagnostics::INITIAL_NODES + 10;
+
+    report(&mut reporter, 1, plan_nodes);
+    let snapshot = diagnostics.dropouts().last().unwrap();
+    assert_eq!(snapshot.node_times.len(), Diagnostics::INITIAL_NODES);
+
+    report(&mut reporter, 2, plan_nodes);
+    let snapshot = diagnostics.dropouts().last().unwrap();
+    assert_eq!(snapshot.node_times.len(), plan_nodes);
+    assert_eq!(snapshot.node_times[70], (70, Duration::from_micros(70)));
+  }
+
+  #[test]
+  fn recent_messages_keep_the_last_ones() {
+    let mut messages = RecentMessages::default();
+    let mut output = Vec::with_capacity(RecentMessages::CAPACITY);
+    messages.copy_into(&mut output);
+    assert!(output.is_empty());
+
+    messages.push(MessageKind::MoveRenderPlan);
+    for count in 0..RecentMessages::CAPACITY {
+      messages.push(MessageKind::SetParameterValues(count));
+    }
+    messages.copy_into(&mut output);
+    assert_eq!(output.len(), RecentMessages::CAPACITY);
+    assert_eq!(output[0], MessageKind::SetParameterValues(0));
+    assert_eq!(
+      output.last(),
+      Some(&MessageKind::SetParameterValues(
+        RecentMessages::CAPACITY - 1
+      ))
+    );
+  }
+
+  #[test]
+  fn render_load_is_smoothed() {
+    let load = RenderLoad::default();
+    assert_eq!(load.get(), 0.0);
+    for _ in 0..100 {
+      load.update(0.5);
+    }
+    assert!((load.get() - 0.5).abs() < 1e-3);
+    load.update(1.0);
+    assert!(load.get() < 0.6);
+  }
+}
@@ -1,6 +1,49 @@
+use std::sync::Arc;
+
+use kiro_time::SampleRate;
+
+use crate::processor::{SharedState, StateError};
+use crate::rendering::buffers::events::Event;
+use crate::rendering::controller::ProcessorKey;
+use crate::rendering::diagnostics::MessageKind;
 use crate::rendering::renderer_plan::RenderPlan;
+use crate::ParamValue;
 
 // #[derive(Debug, Clone)]
 pub enum Message {
   MoveRenderPlan(Box<RenderPlan>),
+  SetSampleRate(SampleRate),
+  /// Values for many parameters, set at once before rendering the next block
+  SetParameterValues(Vec<(Arc<ParamValue>, f32)>),
+  /// Replace the outputs of a processor by its inputs, or go back to process them
+  SetBypass(ProcessorKey, bool),
+  /// Swap the state of a processor between two blocks, by a state already decoded by the controller
+  LoadProcessorState(ProcessorKey, SharedState),
+  /// Event that reached one of the events outputs of the graph, by output index
+  OutputEvent(usize, Event),
+  /// A processor panicked while rendering, so it has been bypassed
+  ProcessorFaulted(ProcessorKey),
+}
+
+impl Message {
+  /// Kind of a message sent to the renderer, or none if it is sent back to the controller.
+  pub fn kind(&self) -> Option<MessageKind> {
+    match self {
+      Message::MoveRenderPlan(_) => Some(MessageKind::MoveRenderPlan),
+      Message::SetSampleRate(_) => Some(MessageKind::SetSampleRate),
+      Message::SetParameterValues(values) => Some(MessageKind::SetParameterValues(values.len())),
+      Message::SetBypass(..) => Some(MessageKind::SetBypass),
+      Message::LoadProcessorState(..) => Some(MessageKind::LoadProcessorState),
+      Message::OutputEvent(..) | Message::ProcessorFaulted(_) => None,
+    }
+  }
+}
+
+/// Data retired by the renderer, sent back to the controller to be deallocated outside of the audio thread.
+pub enum Garbage {
+  RenderPlan(Box<RenderPlan>),
+  ParameterValues(Vec<(Arc<ParamValue>, f32)>),
+  /// State swapped out of a processor, and the result of swapping it,
+  /// or the state to load and none if the processor is not in the render plan
+  ProcessorState(ProcessorKey, SharedState, Option<Result<(), StateError>>),
 }
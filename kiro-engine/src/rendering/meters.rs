@@ -0,0 +1,107 @@
+use crate::key_gen::Key;
+use crate::processor::ports::audio::AudioPort;
+use crate::processor::ports::Output;
+use crate::rendering::owned_data::Ref;
+use crate::rendering::triple_buffer::{triple_buffer, TripleBufferReader, TripleBufferWriter};
+
+pub type MeterKey = Key<MeterWriter>;
+
+/// Levels of a channel for the last rendered block.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeterLevel {
+  pub peak: f32,
+  pub rms: f32,
+}
+
+/// Publishes the levels of every channel of an audio output from the renderer.
+pub struct MeterWriter(TripleBufferWriter<Vec<MeterLevel>>);
+
+impl MeterWriter {
+  pub fn update(&mut self, port: &AudioPort<Output>) {
+    let levels = self.0.write();
+    for (channel, level) in levels.iter_mut().enumerate().take(port.len()) {
+      let buffer = port.channel_mut(channel);
+      let samples = buffer.as_slice();
+      let mut peak = 0.0f32;
+      let mut sum = 0.0f32;
+      for sample in samples.iter() {
+        peak = peak.max(sample.abs());
+        sum += sample * sample;
+      }
+      level.peak = peak;
+      level.rms = if samples.is_empty() {
+        0.0
+      } else {
+        (sum / samples.len() as f32).sqrt()
+      };
+    }
+    self.0.publish();
+  }
+}
+
+/// Reads the latest levels published by a [`MeterWriter`].
+pub struct MeterReader(TripleBufferReader<Vec<MeterLevel>>);
+
+impl MeterReader {
+  pub fn levels(&mut self) -> &[MeterLevel] {
+    self.0.read().as_slice()
+  }
+}
+
+pub fn meter(channels: usize) -> (MeterWriter, MeterReader) {
+  let (writer, reader) = triple_buffer(vec![MeterLevel::default(); channels]);
+  (MeterWriter(writer), MeterReader(reader))
+}
+
+/// Meter updated by the renderer right after the node renders,
+/// as the buffers of the output can be reused by later nodes.
+#[derive(Debug, Clone)]
+pub struct MeterTap {
+  /// Index of the audio output of the node
+  pub port: usize,
+  pub writer: Ref<MeterWriter>,
+}
+
+impl MeterTap {
+  pub fn update(&self, ports: &[AudioPort<Output>]) {
+    if let Some(port) = ports.get(self.port) {
+      self.writer.get_mut().update(port);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rendering::buffers::audio::AudioBuffer;
+  use crate::rendering::owned_data::OwnedData;
+
+  #[test]
+  fn peak_and_rms() {
+    let mut buffers = OwnedData::new();
+    let mut left = AudioBuffer::with_capacity(4);
+    left.as_mut_slice().copy_from_slice(&[0.5, -1.0, 0.5, -1.0]);
+    let left = buffers.add(left);
+    let right = buffers.add(AudioBuffer::with_capacity(4));
+    let mut port = AudioPort::new(vec![
+      buffers.get(left).unwrap(),
+      buffers.get(right).unwrap(),
+    ]);
+    port.set_num_samples(4);
+
+    let (mut writer, mut reader) = meter(2);
+    writer.update(&port);
+
+    let expected_rms = (0.625f32).sqrt();
+    assert_eq!(
+      reader.levels(),
+      &[
+        MeterLevel {
+          peak: 1.0,
+          rms: expected_rms
+        },
+        MeterLevel::default()
+      ]
+    );
+  }
+}
@@ -1,8 +1,13 @@
 pub(crate) mod buffers;
 pub(crate) mod controller;
 pub(crate) mod controller_plan;
+pub mod diagnostics;
 mod messages;
+pub(crate) mod meters;
 pub mod owned_data;
 pub mod param_value;
 pub(crate) mod renderer;
 pub mod renderer_plan;
+mod stopwatch;
+pub(crate) mod transport;
+pub(crate) mod triple_buffer;
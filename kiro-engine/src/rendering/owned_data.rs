@@ -78,6 +78,24 @@ impl<T> OwnedData<T> {
     key
   }
 
+  /// Stop owning some data, returning whether it existed.
+  ///
+  /// The data is dropped once there are no references left to it.
+  pub fn remove<DK>(&mut self, key: DK) -> bool
+  where
+    DK: Into<Key<T>>,
+  {
+    self.data.remove(&key.into()).is_some()
+  }
+
+  /// Keep owning only the data which key satisfies the predicate.
+  pub fn retain<F>(&mut self, mut f: F)
+  where
+    F: FnMut(Key<T>) -> bool,
+  {
+    self.data.retain(|key, _| f(*key));
+  }
+
   pub fn get<DK>(&self, key: DK) -> Option<Ref<T>>
   where
     DK: Into<Key<T>>,
@@ -8,19 +8,61 @@ use std::sync::atomic::{AtomicU32, Ordering};
 /// Designed for the common case of sharing parameters between
 /// multiple threads when no synchronization or change notification
 /// is needed.
-pub struct ParamValue(AtomicU32);
+///
+/// It also tracks whether the user is touching the parameter, so automation recording
+/// can distinguish the user changes from the playback.
+///
+/// The renderer publishes the effective value at the end of every block, including the audio rate modulation,
+/// so user interfaces can follow what is actually being rendered.
+pub struct ParamValue {
+  value: AtomicU32,
+  current: AtomicU32,
+  gestures: AtomicU32,
+}
 
 impl ParamValue {
   pub fn new(value: f32) -> Self {
-    Self(AtomicU32::new(value.to_bits()))
+    Self {
+      value: AtomicU32::new(value.to_bits()),
+      current: AtomicU32::new(value.to_bits()),
+      gestures: AtomicU32::new(0),
+    }
   }
 
   pub fn get(&self) -> f32 {
-    f32::from_bits(self.0.load(Ordering::Relaxed))
+    f32::from_bits(self.value.load(Ordering::Relaxed))
   }
 
   pub fn set(&self, value: f32) {
-    self.0.store(value.to_bits(), Ordering::Relaxed)
+    self.value.store(value.to_bits(), Ordering::Relaxed)
+  }
+
+  /// Effective value at the end of the last rendered block.
+  pub fn current(&self) -> f32 {
+    f32::from_bits(self.current.load(Ordering::Relaxed))
+  }
+
+  pub(crate) fn set_current(&self, value: f32) {
+    self.current.store(value.to_bits(), Ordering::Relaxed)
+  }
+
+  /// The user started touching the parameter. Gestures can be nested, as for multiple controls.
+  pub fn begin_gesture(&self) {
+    self.gestures.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// The user released the parameter. Unbalanced releases are ignored.
+  pub fn end_gesture(&self) {
+    self
+      .gestures
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |gestures| {
+        gestures.checked_sub(1)
+      })
+      .ok();
+  }
+
+  pub fn is_in_gesture(&self) -> bool {
+    self.gestures.load(Ordering::Relaxed) > 0
   }
 }
 
@@ -35,3 +77,23 @@ impl Debug for ParamValue {
     f.write_fmt(format_args!("{}", self.get()))
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nested_gestures() {
+    let value = ParamValue::new(0.0);
+    value.begin_gesture();
+    value.begin_gesture();
+    value.end_gesture();
+    assert!(value.is_in_gesture());
+    value.end_gesture();
+    assert!(!value.is_in_gesture());
+    value.end_gesture();
+    assert!(!value.is_in_gesture());
+    value.begin_gesture();
+    assert!(value.is_in_gesture());
+  }
+}
@@ -1,27 +1,105 @@
 use ringbuf::{Consumer, Producer};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use kiro_midi::TimestampNanos;
+use kiro_time::SampleRate;
 
 use crate::processor::context::ProcessorContext;
+use crate::processor::{SharedState, StateError};
 use crate::rendering::buffers::audio::AudioBuffer;
-use crate::rendering::buffers::events::EventsBuffer;
-use crate::rendering::messages::Message;
+use crate::rendering::buffers::events::{EventData, EventsBuffer};
+use crate::rendering::controller::ProcessorKey;
+use crate::rendering::diagnostics::{DropoutCause, DropoutReporter, RecentMessages, RenderLoad};
+use crate::rendering::messages::{Garbage, Message};
 use crate::rendering::owned_data::Ref;
-use crate::rendering::renderer_plan::RenderPlan;
+use crate::rendering::renderer_plan::{Bypass, RenderPlan};
+use crate::rendering::stopwatch::Stopwatch;
+use crate::rendering::transport::Transport;
 use crate::EngineConfig;
 
 pub struct Renderer {
   tx: Producer<Message>,
   rx: Consumer<Message>,
+  garbage: Producer<Garbage>,
+  /// Retired data waiting for room in the garbage ring buffer
+  pending_garbage: Option<Garbage>,
 
   plan: Box<RenderPlan>,
+  max_block: usize,
+  sample_rate: SampleRate,
+  block_timestamp: TimestampNanos,
+  transport: Transport,
+
+  diagnostics: DropoutReporter,
+  load: RenderLoad,
+  watchdog_sample_rate: Option<SampleRate>,
+  stats: RenderStats,
+}
+
+#[derive(Debug, Default)]
+struct RenderStats {
+  num_samples: usize,
+  render_time: Duration,
+  slowest_node: Option<(usize, Duration)>,
+  last_messages: RecentMessages,
 }
 
 unsafe impl Send for Renderer {}
 
 impl Renderer {
-  pub fn new(tx: Producer<Message>, rx: Consumer<Message>, _config: EngineConfig) -> Self {
+  pub fn new(
+    tx: Producer<Message>,
+    rx: Consumer<Message>,
+    garbage: Producer<Garbage>,
+    diagnostics: DropoutReporter,
+    load: RenderLoad,
+    config: EngineConfig,
+  ) -> Self {
     let plan = Box::new(RenderPlan::default());
 
-    Self { tx, rx, plan }
+    Self {
+      tx,
+      rx,
+      garbage,
+      pending_garbage: None,
+      plan,
+      max_block: config.audio_buffer_size,
+      sample_rate: config.sample_rate,
+      block_timestamp: 0,
+      transport: Transport::new(),
+      diagnostics,
+      load,
+      watchdog_sample_rate: None,
+      stats: RenderStats::default(),
+    }
+  }
+
+  /// Enables reporting a dropout whenever rendering a block takes longer than its duration,
+  /// and measuring the render load.
+  pub fn enable_watchdog(&mut self, sample_rate: SampleRate) {
+    self.watchdog_sample_rate = Some(sample_rate);
+  }
+
+  pub fn disable_watchdog(&mut self) {
+    self.watchdog_sample_rate = None;
+  }
+
+  /// To be called from the audio callback when the driver reports an under-run or over-run,
+  /// capturing the state of the last rendered block.
+  pub fn report_xrun(&mut self) {
+    self.report_dropout(DropoutCause::Xrun);
+  }
+
+  /// Sets the timestamp of the first sample of the next block, to place the events within it.
+  ///
+  /// Otherwise the timestamp is advanced by the duration of every rendered block.
+  pub fn set_block_timestamp(&mut self, timestamp: TimestampNanos) {
+    self.block_timestamp = timestamp;
+  }
+
+  pub fn block_timestamp(&self) -> TimestampNanos {
+    self.block_timestamp
   }
 
   pub fn get_audio_inputs(&mut self) -> &[Ref<AudioBuffer>] {
@@ -41,26 +119,212 @@ impl Renderer {
   }
 
   pub fn render(&mut self, num_samples: usize) {
+    let start = Stopwatch::start();
+    self.stats.num_samples = num_samples;
+    self.stats.slowest_node = None;
     self.process_messages();
+    self.follow_transport();
     self.render_plan(num_samples);
+    self.transport.advance(num_samples, self.sample_rate);
+    self.send_output_events();
+    self.stats.render_time = start.elapsed();
+    self.block_timestamp +=
+      (num_samples as u128 * 1_000_000_000 / u128::from(self.sample_rate)) as TimestampNanos;
+
+    if let Some(render_budget) = self.render_budget() {
+      if !render_budget.is_zero() {
+        let load = self.stats.render_time.as_secs_f32() / render_budget.as_secs_f32();
+        self.load.update(load);
+      }
+      if self.stats.render_time > render_budget {
+        self.report_dropout(DropoutCause::Watchdog);
+      }
+    }
   }
 
-  fn process_messages(&mut self) {
-    while let Some(message) = self.rx.pop() {
+  fn render_budget(&self) -> Option<Duration> {
+    self.watchdog_sample_rate.map(|sample_rate| {
+      Duration::from_secs_f64(self.stats.num_samples as f64 / f64::from(sample_rate))
+    })
+  }
+
+  fn report_dropout(&mut self, cause: DropoutCause) {
+    let render_budget = self.render_budget();
+    let (stats, plan, pending_messages) = (&self.stats, &self.plan, self.rx.len());
+    self.diagnostics.report(|snapshot| {
+      snapshot.cause = cause;
+      snapshot.num_samples = stats.num_samples;
+      snapshot.render_time = stats.render_time;
+      snapshot.render_budget = render_budget;
+      snapshot.plan_nodes = plan.nodes.len();
+      snapshot.node_times.clear();
+      // the snapshot has room for the times of all the nodes unless the plan grew since the last dropout
+      let capacity = snapshot.node_times.capacity();
+      let node_times = plan.nodes.iter().map(|node| node.render_time).enumerate();
+      snapshot.node_times.extend(node_times.take(capacity));
+      snapshot.slowest_node = stats.slowest_node;
+      snapshot.pending_messages = pending_messages;
+      snapshot.last_messages.clear();
+      stats.last_messages.copy_into(&mut snapshot.last_messages);
+    });
+  }
+
+  /// Process the messages from the controller.
+  ///
+  /// While some retired data doesn't fit into the garbage ring buffer, the messages are kept
+  /// in the ring buffer, so nothing is deallocated in the audio thread.
+  fn process_messages(&mut self) -> usize {
+    let mut count = 0;
+    while self.flush_garbage() {
+      let message = match self.rx.pop() {
+        Some(message) => message,
+        None => break,
+      };
+      count += 1;
+      if let Some(kind) = message.kind() {
+        self.stats.last_messages.push(kind);
+      }
       match message {
-        Message::MoveRenderPlan(plan) => {
+        Message::MoveRenderPlan(mut plan) => {
+          Self::keep_bypass_fades(&self.plan, &mut plan);
           let prev_plan = std::mem::replace(&mut self.plan, plan);
-          self.tx.push(Message::MoveRenderPlan(prev_plan)).ok(); // FIXME this will deallocate if failure
+          self.pending_garbage = Some(Garbage::RenderPlan(prev_plan));
+        }
+        Message::SetSampleRate(sample_rate) => self.set_sample_rate(sample_rate),
+        Message::SetParameterValues(values) => {
+          for (param_value, value) in values.iter() {
+            param_value.set(*value);
+          }
+          self.pending_garbage = Some(Garbage::ParameterValues(values));
+        }
+        Message::SetBypass(processor_key, enabled) => self.set_bypass(processor_key, enabled),
+        Message::LoadProcessorState(processor_key, mut state) => {
+          let result = self.swap_state(processor_key, &mut state);
+          self.pending_garbage = Some(Garbage::ProcessorState(processor_key, state, result));
+        }
+        Message::OutputEvent(..) | Message::ProcessorFaulted(_) => {}
+      }
+    }
+    count
+  }
+
+  /// Send the pending garbage back to the controller, returning whether it succeeded.
+  fn flush_garbage(&mut self) -> bool {
+    match self.pending_garbage.take() {
+      Some(garbage) => match self.garbage.push(garbage) {
+        Ok(()) => true,
+        Err(garbage) => {
+          self.pending_garbage = Some(garbage);
+          false
+        }
+      },
+      None => true,
+    }
+  }
+
+  /// Apply the transport events received through the events inputs of the graph,
+  /// which take effect from the beginning of the block.
+  fn follow_transport(&mut self) {
+    for buffer in self.plan.events_inputs.iter() {
+      for event in buffer.iter() {
+        if let EventData::Transport(message) = &event.data {
+          self.transport.apply(message);
+        }
+      }
+    }
+  }
+
+  /// Send the events from the outputs of the graph to the controller.
+  ///
+  /// Events not fitting into the ring buffer are dropped.
+  fn send_output_events(&mut self) {
+    for (index, buffer) in self.plan.events_outputs.iter().enumerate() {
+      for event in buffer.iter() {
+        if self.tx.push(Message::OutputEvent(index, *event)).is_err() {
+          return;
+        }
+      }
+    }
+  }
+
+  /// The audio device has renegotiated its sample rate, so the audio is not running in real-time.
+  fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+    self.sample_rate = sample_rate;
+    if self.watchdog_sample_rate.is_some() {
+      self.watchdog_sample_rate = Some(sample_rate);
+    }
+    for node in self.plan.nodes.iter_mut() {
+      node.processor.prepare(sample_rate, self.max_block);
+      node.processor.reset();
+    }
+  }
+
+  /// Continue the crossfades of the bypassed nodes that are also in the new plan,
+  /// and keep the faulted ones bypassed even if the controller didn't know about them yet.
+  fn keep_bypass_fades(prev_plan: &RenderPlan, plan: &mut RenderPlan) {
+    for (index, node) in plan.nodes.iter_mut().enumerate() {
+      // nodes tend to keep their position between plans
+      let prev_node = prev_plan
+        .nodes
+        .get(index)
+        .filter(|prev_node| prev_node.processor_key == node.processor_key)
+        .or_else(|| {
+          prev_plan
+            .nodes
+            .iter()
+            .find(|prev_node| prev_node.processor_key == node.processor_key)
+        });
+      if let Some(prev_node) = prev_node {
+        node.bypass.mix = prev_node.bypass.mix;
+        if prev_node.faulted {
+          node.faulted = true;
+          node.bypass = Bypass::new(true);
         }
       }
     }
   }
 
+  fn set_bypass(&mut self, processor_key: ProcessorKey, enabled: bool) {
+    if let Some(node) = self
+      .plan
+      .nodes
+      .iter_mut()
+      .find(|node| node.processor_key == processor_key)
+    {
+      node.bypass.enabled = enabled;
+      node.faulted &= enabled;
+    }
+  }
+
+  /// Swap the state of a processor in the render plan between two blocks, so it keeps rendering,
+  /// returning none if it is not in the plan.
+  fn swap_state(
+    &mut self,
+    processor_key: ProcessorKey,
+    state: &mut SharedState,
+  ) -> Option<Result<(), StateError>> {
+    let node = self
+      .plan
+      .nodes
+      .iter()
+      .find(|node| node.processor_key == processor_key)?;
+    let processor = node.processor.get_mut();
+    // a panicking processor is not allowed to take down the audio thread
+    let result = panic::catch_unwind(AssertUnwindSafe(|| processor.set_state(state)));
+    Some(result.unwrap_or(Err(StateError::Panicked)))
+  }
+
   fn render_plan(&mut self, num_samples: usize) {
     self.plan.ready.clear();
     self.plan.ready.extend(self.plan.initial_ready.iter());
     // println!(">>{:?}", self.plan.ready);
 
+    let block_timestamp = self.block_timestamp;
+    let sample_rate = self.sample_rate;
+    let transport = self.transport;
+    let block_end = block_timestamp
+      + (num_samples as u128 * 1_000_000_000 / u128::from(sample_rate)) as TimestampNanos;
+    let bypass_step = 1.0 / (Bypass::FADE_SECONDS * sample_rate as f32).max(1.0);
     let dependencies = &mut self.plan.dependencies;
     let completed = &mut self.plan.completed;
     completed.fill(0);
@@ -80,16 +344,65 @@ impl Renderer {
           .iter_mut()
           .for_each(|port| port.set_num_samples(num_samples));
 
+        node
+          .parameter_ports
+          .iter_mut()
+          .for_each(|port| port.set_num_samples(num_samples));
+
+        node
+          .events_output_ports
+          .iter_mut()
+          .for_each(|port| port.buffer_mut().clear());
+
+        node.mix_inputs(num_samples);
+        node.events_input_ports.iter().for_each(|port| port.sort());
+        node.apply_automation(block_timestamp, sample_rate);
+
         let mut context = ProcessorContext::new(
           num_samples,
           &node.parameters,
+          &node.parameter_ports,
           &node.audio_input_ports,
           &node.audio_output_ports,
           &node.events_input_ports,
           &node.events_output_ports,
-        );
+        )
+        .with_timing(block_timestamp, sample_rate)
+        .with_transport(transport);
 
-        processor.render(&mut context);
+        let start = Stopwatch::start();
+        if !node.bypass.is_bypassed() {
+          // a panicking processor is bypassed rather than taking down the audio thread
+          let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+            #[cfg(not(feature = "alloc-audit"))]
+            processor.render(&mut context);
+            #[cfg(feature = "alloc-audit")]
+            crate::alloc_audit::audit(node_index, || processor.render(&mut context));
+          }));
+          if rendered.is_ok() {
+            node.apply_dry_wet();
+          } else {
+            node.faulted = true;
+            node.bypass = Bypass::new(true);
+            self
+              .tx
+              .push(Message::ProcessorFaulted(node.processor_key))
+              .ok();
+          }
+        }
+        let node_time = start.elapsed();
+        node.render_time = node_time;
+        node.deliver_scheduled_events(block_end);
+        node.apply_bypass(num_samples, bypass_step);
+        node.publish_parameters();
+
+        for meter in node.meters.iter() {
+          meter.update(&node.audio_output_ports);
+        }
+        match self.stats.slowest_node {
+          Some((_, slowest_time)) if slowest_time >= node_time => {}
+          _ => self.stats.slowest_node = Some((node_index, node_time)),
+        }
 
         for index in node.triggers.iter().cloned() {
           completed[index] += 1;
@@ -99,5 +412,68 @@ impl Renderer {
         }
       }
     }
+
+    for mix in self.plan.audio_output_mixes.iter() {
+      mix.render(num_samples);
+    }
+    for merge in self.plan.events_output_merges.iter() {
+      merge.render();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ringbuf::RingBuffer;
+
+  use super::*;
+  use crate::rendering::diagnostics::Diagnostics;
+
+  fn plan(marker: usize) -> Box<RenderPlan> {
+    Box::new(RenderPlan {
+      dependencies: vec![marker],
+      ..RenderPlan::default()
+    })
+  }
+
+  fn garbage_marker(garbage: Garbage) -> usize {
+    match garbage {
+      Garbage::RenderPlan(plan) => plan.dependencies[0],
+      Garbage::ParameterValues(_) | Garbage::ProcessorState(..) => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn retired_plans_wait_for_room_in_the_garbage_ring_buffer() {
+    let (mut forward_tx, forward_rx) = RingBuffer::new(4).split();
+    let (backward_tx, _backward_rx) = RingBuffer::new(4).split();
+    let (garbage_tx, mut garbage_rx) = RingBuffer::new(1).split();
+    let (_diagnostics, dropout_reporter) = Diagnostics::new(1);
+    let mut renderer = Renderer::new(
+      backward_tx,
+      forward_rx,
+      garbage_tx,
+      dropout_reporter,
+      RenderLoad::default(),
+      EngineConfig::default(),
+    );
+    renderer.plan = plan(0);
+
+    for marker in 1..=3 {
+      forward_tx.push(Message::MoveRenderPlan(plan(marker))).ok();
+    }
+
+    assert_eq!(renderer.process_messages(), 2);
+    assert_eq!(renderer.plan.dependencies, vec![2]);
+    assert_eq!(garbage_rx.pop().map(garbage_marker), Some(0));
+    assert_eq!(garbage_rx.pop().map(garbage_marker), None);
+
+    assert_eq!(renderer.process_messages(), 1);
+    assert_eq!(renderer.plan.dependencies, vec![3]);
+    assert_eq!(garbage_rx.pop().map(garbage_marker), Some(1));
+
+    assert_eq!(renderer.process_messages(), 0);
+    assert_eq!(garbage_rx.pop().map(garbage_marker), Some(2));
+    assert!(renderer.pending_garbage.is_none());
   }
 }
@@ -1,24 +1,263 @@
 use crate::rendering::buffers::audio::AudioBuffer;
 use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::Duration;
+
+use kiro_midi::TimestampNanos;
+use kiro_time::SampleRate;
 
 use crate::processor::ports::audio::AudioPort;
 use crate::processor::ports::events::EventsPort;
+use crate::processor::ports::param::ParamRenderPort;
 use crate::processor::ports::{Input, Output};
 use crate::processor::BoxedProcessor;
-use crate::rendering::buffers::events::EventsBuffer;
+use crate::rendering::buffers::events::{EventData, EventsBuffer};
+use crate::rendering::controller::ProcessorKey;
+use crate::rendering::meters::MeterTap;
 use crate::rendering::owned_data::Ref;
 use crate::ParamValue;
 
+/// Sums audio buffers into another one, for inputs with many sources.
+#[derive(Debug)]
+pub struct AudioMix {
+  pub sources: Vec<Ref<AudioBuffer>>,
+  pub output: Ref<AudioBuffer>,
+}
+
+impl AudioMix {
+  pub fn render(&self, num_samples: usize) {
+    let output = self.output.get_mut();
+    output.fill_first(num_samples, 0.0);
+    for source in self.sources.iter() {
+      for (sample, source_sample) in output.iter_mut().zip(source.iter()).take(num_samples) {
+        *sample += *source_sample;
+      }
+    }
+  }
+}
+
+/// Merges the events from many buffers into another one sorted by timestamp.
+///
+/// Events exceeding the capacity of the output buffer are dropped.
+#[derive(Debug)]
+pub struct EventsMerge {
+  pub sources: Vec<Ref<EventsBuffer>>,
+  pub output: Ref<EventsBuffer>,
+}
+
+impl EventsMerge {
+  pub fn render(&self) {
+    let output = self.output.get_mut();
+    output.clear();
+    for source in self.sources.iter() {
+      for event in source.iter() {
+        if output.insert_sorted(*event).is_err() {
+          return;
+        }
+      }
+    }
+  }
+}
+
+/// Crossfade between the outputs of a processor and its inputs, so it can be bypassed without clicks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bypass {
+  pub enabled: bool,
+  /// Amount of the inputs in the outputs, from 0 when processing to 1 when bypassed
+  pub mix: f32,
+}
+
+impl Bypass {
+  /// Time to crossfade between the outputs and the inputs
+  pub const FADE_SECONDS: f32 = 0.01;
+
+  pub fn new(enabled: bool) -> Self {
+    Self {
+      enabled,
+      mix: if enabled { 1.0 } else { 0.0 },
+    }
+  }
+
+  /// Whether the processor doesn't need to render, as its outputs are replaced by the inputs.
+  pub fn is_bypassed(&self) -> bool {
+    self.enabled && self.mix >= 1.0
+  }
+
+  /// Whether the outputs of the processor are used as they are.
+  pub fn is_processing(&self) -> bool {
+    !self.enabled && self.mix <= 0.0
+  }
+
+  fn target(&self) -> f32 {
+    if self.enabled {
+      1.0
+    } else {
+      0.0
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct RenderNode {
+  pub processor_key: ProcessorKey,
   pub processor: Ref<BoxedProcessor>,
+  pub bypass: Bypass,
+  /// The processor panicked while rendering, so it is not rendered anymore
+  pub faulted: bool,
+  /// Index of the dry/wet parameter
+  pub dry_wet: Option<usize>,
   pub parameters: Vec<Arc<ParamValue>>,
+  pub parameter_ports: Vec<ParamRenderPort>,
+  pub audio_mixes: Vec<AudioMix>,
+  pub events_merges: Vec<EventsMerge>,
   pub audio_input_ports: Vec<AudioPort<Input>>,
   pub audio_output_ports: Vec<AudioPort<Output>>,
   pub events_input_ports: Vec<EventsPort<Input>>,
   pub events_output_ports: Vec<EventsPort<Output>>,
+  pub meters: Vec<MeterTap>,
   pub triggers: Vec<usize>,
+  /// Time it took to render the last block
+  pub render_time: Duration,
+}
+
+impl RenderNode {
+  /// Mix the inputs with many sources.
+  pub fn mix_inputs(&self, num_samples: usize) {
+    for mix in self.audio_mixes.iter() {
+      mix.render(num_samples);
+    }
+    for merge in self.events_merges.iter() {
+      merge.render();
+    }
+  }
+
+  /// Crossfade every output with the input with the same index, which is the dry signal
+  /// as the processors don't modify their inputs, according to the dry/wet parameter.
+  pub fn apply_dry_wet(&self) {
+    let wet = match self
+      .dry_wet
+      .and_then(|index| self.parameter_ports.get(index))
+    {
+      Some(port) => port.as_slice(),
+      None => return,
+    };
+    if wet.iter().all(|wet| *wet >= 1.0) {
+      return;
+    }
+
+    for (index, output) in self.audio_output_ports.iter().enumerate() {
+      let input = self.audio_input_ports.get(index);
+      for channel in 0..output.len() {
+        let mut output = output.channel_mut(channel);
+        match input.filter(|input| channel < input.len()) {
+          Some(input) => {
+            let input = input.channel(channel);
+            for ((out, dry), wet) in output.iter_mut().zip(input.iter()).zip(wet) {
+              *out = *out * wet + *dry * (1.0 - wet);
+            }
+          }
+          None => {
+            for (out, wet) in output.iter_mut().zip(wet) {
+              *out *= wet;
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Replace the outputs by the inputs with the same index, crossfading between them
+  /// while the bypass changes, and advance the crossfade by `step` every sample.
+  ///
+  /// The outputs are not read while fully bypassed, so the processor doesn't need to render.
+  /// Move the events scheduled by the processor for before `timestamp` into its events outputs.
+  pub fn deliver_scheduled_events(&self, timestamp: TimestampNanos) {
+    for port in self.events_output_ports.iter() {
+      port.deliver_scheduled(timestamp);
+    }
+  }
+
+  pub fn apply_bypass(&mut self, num_samples: usize, step: f32) {
+    if self.bypass.is_processing() {
+      return;
+    }
+
+    let bypassed = self.bypass.is_bypassed();
+    let start = self.bypass.mix;
+    let target = self.bypass.target();
+    let mix_at = |index: usize| {
+      let mix = start + (target - start).signum() * step * (index + 1) as f32;
+      mix.clamp(start.min(target), start.max(target))
+    };
+
+    for (index, output) in self.audio_output_ports.iter().enumerate() {
+      let input = self.audio_input_ports.get(index);
+      for channel in 0..output.len() {
+        let mut output = output.channel_mut(channel);
+        match input.filter(|input| channel < input.len()) {
+          Some(input) if bypassed => output
+            .as_mut_slice()
+            .copy_from_slice(input.channel(channel).as_slice()),
+          Some(input) => {
+            let input = input.channel(channel);
+            for (index, (out, sample)) in output.iter_mut().zip(input.iter()).enumerate() {
+              let mix = mix_at(index);
+              *out = *out * (1.0 - mix) + *sample * mix;
+            }
+          }
+          None if bypassed => output.fill(0.0),
+          None => {
+            for (index, out) in output.iter_mut().enumerate() {
+              *out *= 1.0 - mix_at(index);
+            }
+          }
+        }
+      }
+    }
+
+    if self.bypass.enabled {
+      for (index, output) in self.events_output_ports.iter_mut().enumerate() {
+        let output = output.buffer_mut();
+        output.clear();
+        if let Some(input) = self.events_input_ports.get(index) {
+          for event in input.iter() {
+            if output.push(*event).is_err() {
+              break;
+            }
+          }
+        }
+      }
+    }
+
+    if num_samples > 0 {
+      self.bypass.mix = mix_at(num_samples - 1);
+    }
+  }
+
+  /// Publish the effective values of the parameters at the end of the block.
+  pub fn publish_parameters(&self) {
+    for (parameter, port) in self.parameters.iter().zip(self.parameter_ports.iter()) {
+      if let Some(value) = port.last_value() {
+        parameter.set_current(value);
+      }
+    }
+  }
+
+  /// Schedule the automation events received through the events inputs into the parameter ports.
+  ///
+  /// Events for parameters that don't exist, or exceeding the capacity of the ports, are dropped.
+  pub fn apply_automation(&mut self, block_timestamp: TimestampNanos, sample_rate: SampleRate) {
+    for port in self.events_input_ports.iter() {
+      for event in port.iter() {
+        if let EventData::Automation(automation) = event.data {
+          if let Some(param_port) = self.parameter_ports.get_mut(automation.param_ref.0) {
+            let offset = event.sample_offset(block_timestamp, sample_rate);
+            param_port.automate(offset, automation.value, automation.curve);
+          }
+        }
+      }
+    }
+  }
 }
 
 #[derive(Debug, Default)]
@@ -28,8 +267,66 @@ pub struct RenderPlan {
   pub audio_outputs: Vec<Ref<AudioBuffer>>,
   pub events_inputs: Vec<Ref<EventsBuffer>>,
   pub events_outputs: Vec<Ref<EventsBuffer>>,
+  pub audio_output_mixes: Vec<AudioMix>,
+  pub events_output_merges: Vec<EventsMerge>,
   pub dependencies: Vec<usize>,
   pub completed: Vec<usize>,
   pub initial_ready: Vec<usize>,
   pub ready: VecDeque<usize>,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rendering::buffers::events::{Event, TransportMessage};
+  use crate::rendering::owned_data::OwnedData;
+
+  #[test]
+  fn mix_audio_and_merge_events() {
+    let mut audio_buffers = OwnedData::new();
+    let mut audio_keys = Vec::new();
+    for value in [0.25, 0.5, 0.0] {
+      let mut buffer = AudioBuffer::with_capacity(4);
+      buffer.fill(value);
+      audio_keys.push(audio_buffers.add(buffer));
+    }
+    let mix = AudioMix {
+      sources: vec![
+        audio_buffers.get(audio_keys[0]).unwrap(),
+        audio_buffers.get(audio_keys[1]).unwrap(),
+      ],
+      output: audio_buffers.get(audio_keys[2]).unwrap(),
+    };
+    mix.render(3);
+    assert_eq!(mix.output.as_slice(), &[0.75, 0.75, 0.75, 0.0]);
+
+    let event = |timestamp| Event {
+      timestamp,
+      data: EventData::Transport(TransportMessage::Start),
+    };
+    let mut events_buffers = OwnedData::new();
+    let mut events_keys = Vec::new();
+    for timestamps in [vec![10, 30], vec![20, 30], vec![]] {
+      let mut buffer = EventsBuffer::with_capacity(8);
+      for timestamp in timestamps {
+        buffer.push(event(timestamp)).unwrap();
+      }
+      events_keys.push(events_buffers.add(buffer));
+    }
+    let merge = EventsMerge {
+      sources: vec![
+        events_buffers.get(events_keys[0]).unwrap(),
+        events_buffers.get(events_keys[1]).unwrap(),
+      ],
+      output: events_buffers.get(events_keys[2]).unwrap(),
+    };
+    merge.render();
+    let timestamps = merge
+      .output
+      .iter()
+      .map(|event| event.timestamp)
+      .collect::<Vec<_>>();
+    assert_eq!(timestamps, vec![10, 20, 30, 30]);
+    assert!(merge.output.is_sorted());
+  }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+/// Measures elapsed time in the rendering thread.
+///
+/// `std::time::Instant` is not available in `wasm32-unknown-unknown`,
+/// so there it always reports zero and the watchdog never fires.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+  #[cfg(not(target_arch = "wasm32"))]
+  start: std::time::Instant,
+}
+
+impl Stopwatch {
+  pub fn start() -> Self {
+    Self {
+      #[cfg(not(target_arch = "wasm32"))]
+      start: std::time::Instant::now(),
+    }
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn elapsed(&self) -> Duration {
+    self.start.elapsed()
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  pub fn elapsed(&self) -> Duration {
+    Duration::ZERO
+  }
+}
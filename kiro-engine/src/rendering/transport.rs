@@ -0,0 +1,147 @@
+use kiro_time::{BarsTime, ClockTime, SampleRate, Signature, Tempo, TicksTime};
+
+use crate::TransportMessage;
+
+/// Tempo, time signature, playhead position and play state at the beginning of a block.
+///
+/// The renderer follows the transport events received through the events inputs of the graph,
+/// and advances the playhead while playing, so processors don't need to parse them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transport {
+  playing: bool,
+  tempo: Tempo,
+  signature: Signature,
+  /// Musical position when the tempo or signature last changed
+  anchor_ticks: TicksTime,
+  /// Time played since the tempo or signature last changed
+  anchor_elapsed: ClockTime,
+  clock: ClockTime,
+}
+
+impl Transport {
+  pub const DEFAULT_TEMPO: u16 = 120;
+
+  pub fn new() -> Self {
+    Self {
+      playing: false,
+      tempo: Tempo::new(Self::DEFAULT_TEMPO),
+      signature: Signature::new(4, 4),
+      anchor_ticks: TicksTime::zero(),
+      anchor_elapsed: ClockTime::zero(),
+      clock: ClockTime::zero(),
+    }
+  }
+
+  pub fn is_playing(&self) -> bool {
+    self.playing
+  }
+
+  pub fn tempo(&self) -> Tempo {
+    self.tempo
+  }
+
+  pub fn signature(&self) -> Signature {
+    self.signature
+  }
+
+  /// Musical position of the playhead.
+  pub fn ticks(&self) -> TicksTime {
+    self.anchor_ticks + self.anchor_elapsed.to_ticks(self.signature, self.tempo)
+  }
+
+  /// Musical position of the playhead in bars, beats and sixteenths.
+  pub fn bars(&self) -> BarsTime {
+    BarsTime::from_ticks(self.ticks(), self.signature)
+  }
+
+  /// Time played since the beginning of the song.
+  pub fn clock(&self) -> ClockTime {
+    self.clock
+  }
+
+  /// Duration of a beat in samples, as for tempo synced LFOs and delays.
+  pub fn samples_per_beat(&self, sample_rate: SampleRate) -> f64 {
+    f64::from(sample_rate) * 60.0 / f64::from(self.tempo)
+  }
+
+  /// Follow a transport event.
+  pub fn apply(&mut self, message: &TransportMessage) {
+    match *message {
+      TransportMessage::Start => {
+        self.playing = true;
+        self.set_position(TicksTime::zero(), ClockTime::zero());
+      }
+      TransportMessage::Stop => self.playing = false,
+      TransportMessage::Continue => self.playing = true,
+      // the position after looping is sent along with it
+      TransportMessage::Loop => {}
+      TransportMessage::Tempo(tempo) => {
+        self.set_position(self.ticks(), self.clock);
+        self.tempo = tempo;
+      }
+      TransportMessage::Signature(signature) => {
+        self.set_position(self.ticks(), self.clock);
+        self.signature = signature;
+      }
+      TransportMessage::Position { ticks, clock, .. } => self.set_position(ticks, clock),
+    }
+  }
+
+  /// Move the playhead forward by the duration of a block, if playing.
+  pub fn advance(&mut self, num_samples: usize, sample_rate: SampleRate) {
+    if self.playing && sample_rate > 0 {
+      let elapsed = ClockTime::from_samples(num_samples as u32, sample_rate);
+      self.anchor_elapsed += elapsed;
+      self.clock += elapsed;
+    }
+  }
+
+  fn set_position(&mut self, ticks: TicksTime, clock: ClockTime) {
+    self.anchor_ticks = ticks;
+    self.anchor_elapsed = ClockTime::zero();
+    self.clock = clock;
+  }
+}
+
+impl Default for Transport {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn follow_transport_events() {
+    let mut transport = Transport::new();
+    transport.advance(44_100, 44_100);
+    assert!(!transport.is_playing());
+    assert_eq!(transport.clock(), ClockTime::zero());
+
+    transport.apply(&TransportMessage::Start);
+    transport.advance(22_050, 44_100);
+    assert!(transport.is_playing());
+    assert_eq!(transport.clock(), ClockTime::from_millis(500));
+    // one beat at 120 bpm
+    assert_eq!(transport.bars(), BarsTime::new(0, 1, 0, 0));
+
+    transport.apply(&TransportMessage::Tempo(Tempo::new(60)));
+    transport.advance(44_100, 44_100);
+    assert_eq!(transport.bars(), BarsTime::new(0, 2, 0, 0));
+    assert_eq!(transport.samples_per_beat(44_100), 44_100.0);
+
+    transport.apply(&TransportMessage::Stop);
+    transport.advance(44_100, 44_100);
+    assert_eq!(transport.clock(), ClockTime::from_millis(1500));
+
+    transport.apply(&TransportMessage::Position {
+      bars: BarsTime::from_bars(2),
+      ticks: BarsTime::from_bars(2).to_ticks(transport.signature()),
+      clock: ClockTime::from_seconds(8.0),
+    });
+    assert_eq!(transport.bars(), BarsTime::from_bars(2));
+    assert_eq!(transport.clock(), ClockTime::from_seconds(8.0));
+  }
+}
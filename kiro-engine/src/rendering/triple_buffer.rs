@@ -0,0 +1,97 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+const INDEX_MASK: u8 = 0b011;
+const UPDATED: u8 = 0b100;
+
+/// Three slots shared by a writer and a reader, so the writer always has a slot to write into,
+/// and the reader always gets the latest published one, without locks nor allocations.
+struct Slots<T> {
+  slots: [UnsafeCell<T>; 3],
+  /// Index of the slot that is not owned by the writer nor the reader,
+  /// flagged when it was published and not read yet.
+  back: AtomicU8,
+}
+
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleBufferWriter<T>, TripleBufferReader<T>) {
+  let slots = Arc::new(Slots {
+    slots: [
+      UnsafeCell::new(initial.clone()),
+      UnsafeCell::new(initial.clone()),
+      UnsafeCell::new(initial),
+    ],
+    back: AtomicU8::new(1),
+  });
+  let writer = TripleBufferWriter {
+    slots: slots.clone(),
+    index: 0,
+  };
+  let reader = TripleBufferReader { slots, index: 2 };
+  (writer, reader)
+}
+
+pub struct TripleBufferWriter<T> {
+  slots: Arc<Slots<T>>,
+  index: usize,
+}
+
+unsafe impl<T: Send> Send for TripleBufferWriter<T> {}
+
+impl<T> TripleBufferWriter<T> {
+  /// The slot owned by the writer. It contains stale data, so it needs to be written completely.
+  pub fn write(&mut self) -> &mut T {
+    unsafe { &mut *self.slots.slots[self.index].get() }
+  }
+
+  /// Make the written slot available to the reader.
+  pub fn publish(&mut self) {
+    let previous = self
+      .slots
+      .back
+      .swap(self.index as u8 | UPDATED, Ordering::AcqRel);
+    self.index = (previous & INDEX_MASK) as usize;
+  }
+}
+
+pub struct TripleBufferReader<T> {
+  slots: Arc<Slots<T>>,
+  index: usize,
+}
+
+unsafe impl<T: Send> Send for TripleBufferReader<T> {}
+
+impl<T> TripleBufferReader<T> {
+  /// The latest published value.
+  pub fn read(&mut self) -> &T {
+    if self.slots.back.load(Ordering::Relaxed) & UPDATED != 0 {
+      let previous = self.slots.back.swap(self.index as u8, Ordering::AcqRel);
+      self.index = (previous & INDEX_MASK) as usize;
+    }
+    unsafe { &*self.slots.slots[self.index].get() }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn read_latest() {
+    let (mut writer, mut reader) = triple_buffer(0);
+    assert_eq!(*reader.read(), 0);
+
+    *writer.write() = 1;
+    writer.publish();
+    *writer.write() = 2;
+    assert_eq!(*reader.read(), 1);
+
+    writer.publish();
+    *writer.write() = 3;
+    writer.publish();
+    assert_eq!(*reader.read(), 3);
+    assert_eq!(*reader.read(), 3);
+  }
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::document::GraphDocument;
+
+/// Graph and parameter values of an engine at some point in time,
+/// so they can be restored later to compare different settings or to recover from a crash.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+  /// Modules, nodes and connections, including the parameter values of every node
+  pub graph: GraphDocument,
+}
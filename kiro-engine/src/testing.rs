@@ -0,0 +1,535 @@
+//! Rendering of processors outside of an engine, so DSP nodes can have regression tests.
+//!
+//! The [`ProcessorHarness`] feeds a processor with scripted parameter changes, events and audio,
+//! renders a number of blocks offline, and returns what it produced,
+//! which can be compared against a golden file with [`assert_golden`].
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use kiro_midi::TimestampNanos;
+use kiro_time::SampleRate;
+
+use crate::processor::ports::audio::AudioPort;
+use crate::processor::ports::events::EventsPort;
+use crate::processor::ports::param::{ParamData, ParamRenderPort};
+use crate::processor::ports::{Input, Output};
+use crate::rendering::buffers::audio::AudioBuffer;
+use crate::rendering::buffers::events::{Event, EventData, EventsBuffer};
+use crate::rendering::owned_data::{OwnedData, Ref};
+use crate::{NodeDescriptor, ParamValue, Processor, ProcessorContext, Transport};
+
+/// Environment variable to rewrite the golden files rather than comparing against them.
+pub const UPDATE_GOLDEN_VAR: &str = "KIRO_UPDATE_GOLDEN";
+
+/// Ports and parameters to render a processor outside of an engine.
+pub struct TestContext {
+  num_samples: usize,
+  block_timestamp: TimestampNanos,
+  sample_rate: SampleRate,
+  transport: Transport,
+  parameters: Vec<Arc<ParamValue>>,
+  parameter_ports: Vec<ParamRenderPort>,
+  audio_inputs: Vec<AudioPort<Input>>,
+  input_buffers: Vec<Vec<Ref<AudioBuffer>>>,
+  audio_outputs: Vec<AudioPort<Output>>,
+  output_buffers: Vec<Vec<Ref<AudioBuffer>>>,
+  events_inputs: Vec<EventsPort<Input>>,
+  events_input_buffers: Vec<Ref<EventsBuffer>>,
+  events_outputs: Vec<EventsPort<Output>>,
+}
+
+impl TestContext {
+  const EVENTS_CAPACITY: usize = 64;
+
+  pub fn new(descriptor: &NodeDescriptor, num_samples: usize) -> Self {
+    let mut buffers = OwnedData::new();
+    let mut new_buffer = || {
+      let key = buffers.add(AudioBuffer::with_capacity(num_samples));
+      buffers.get(key).unwrap()
+    };
+
+    let parameters = descriptor
+      .parameters
+      .iter()
+      .map(|param| Arc::new(ParamValue::new(param.initial)))
+      .collect::<Vec<_>>();
+    let parameter_ports = parameters
+      .iter()
+      .map(|value| {
+        let mut port = ParamRenderPort::new(ParamData::from_value(value.clone(), new_buffer()));
+        port.set_num_samples(num_samples);
+        port
+      })
+      .collect();
+
+    let input_buffers = descriptor
+      .ports
+      .audio
+      .static_inputs
+      .iter()
+      .map(|port| (0..port.channels()).map(|_| new_buffer()).collect())
+      .collect::<Vec<Vec<_>>>();
+    let output_buffers = descriptor
+      .ports
+      .audio
+      .static_outputs
+      .iter()
+      .map(|port| (0..port.channels()).map(|_| new_buffer()).collect())
+      .collect::<Vec<Vec<_>>>();
+
+    let audio_inputs = input_buffers
+      .iter()
+      .map(|channels| {
+        let mut port = AudioPort::new(channels.clone());
+        port.set_num_samples(num_samples);
+        port
+      })
+      .collect();
+    let audio_outputs = output_buffers
+      .iter()
+      .map(|channels| {
+        let mut port = AudioPort::new(channels.clone());
+        port.set_num_samples(num_samples);
+        port
+      })
+      .collect();
+
+    let mut events_buffers = OwnedData::new();
+    let mut new_events_buffer = || {
+      let key = events_buffers.add(EventsBuffer::with_capacity(Self::EVENTS_CAPACITY));
+      events_buffers.get(key).unwrap()
+    };
+    let events_input_buffers = descriptor
+      .ports
+      .events
+      .static_inputs
+      .iter()
+      .map(|_| new_events_buffer())
+      .collect::<Vec<_>>();
+    let events_inputs = events_input_buffers
+      .iter()
+      .map(|buffer| EventsPort::new(buffer.clone()))
+      .collect();
+    let events_outputs = descriptor
+      .ports
+      .events
+      .static_outputs
+      .iter()
+      .map(|_| EventsPort::new(new_events_buffer()).with_scheduled(new_events_buffer()))
+      .collect();
+
+    Self {
+      num_samples,
+      block_timestamp: 0,
+      sample_rate: SampleRate::default(),
+      transport: Transport::new(),
+      parameters,
+      parameter_ports,
+      audio_inputs,
+      input_buffers,
+      audio_outputs,
+      output_buffers,
+      events_inputs,
+      events_input_buffers,
+      events_outputs,
+    }
+  }
+
+  /// Set the timestamp of the first sample of the next render, and the sample rate.
+  pub fn set_timing(&mut self, block_timestamp: TimestampNanos, sample_rate: SampleRate) {
+    self.block_timestamp = block_timestamp;
+    self.sample_rate = sample_rate;
+  }
+
+  pub fn set_transport(&mut self, transport: Transport) {
+    self.transport = transport;
+  }
+
+  pub fn set_parameter(&mut self, index: usize, value: f32) {
+    self.parameters[index].set(value);
+  }
+
+  pub fn set_input(&mut self, port: usize, channel: usize, samples: &[f32]) {
+    let buffer = self.input_buffers[port][channel].get_mut();
+    buffer.as_mut_slice()[0..samples.len()].copy_from_slice(samples);
+  }
+
+  /// Replace the events of an events input for the next render.
+  pub fn set_events(&mut self, port: usize, events: &[Event]) {
+    let buffer = self.events_input_buffers[port].get_mut();
+    buffer.clear();
+    for event in events {
+      buffer.push(*event).unwrap();
+    }
+  }
+
+  pub fn output(&self, port: usize, channel: usize) -> Vec<f32> {
+    self.output_buffers[port][channel].as_slice()[0..self.num_samples].to_vec()
+  }
+
+  /// Events sent by the processor to an events output during the last render.
+  pub fn events_output(&self, port: usize) -> Vec<Event> {
+    self.events_outputs[port].iter().cloned().collect()
+  }
+
+  /// Render a block the way the renderer does,
+  /// sorting the input events and applying the automation events to the parameters.
+  pub fn render<P: Processor>(&mut self, processor: &mut P) {
+    for port in self.parameter_ports.iter_mut() {
+      port.set_num_samples(self.num_samples);
+    }
+    for port in self.events_outputs.iter_mut() {
+      port.buffer_mut().clear();
+    }
+    for port in self.events_inputs.iter() {
+      port.sort();
+      for event in port.iter() {
+        if let EventData::Automation(automation) = event.data {
+          if let Some(param_port) = self.parameter_ports.get_mut(automation.param_ref.0) {
+            let offset = event.sample_offset(self.block_timestamp, self.sample_rate);
+            param_port.automate(offset, automation.value, automation.curve);
+          }
+        }
+      }
+    }
+
+    let mut context = ProcessorContext::new(
+      self.num_samples,
+      self.parameters.as_slice(),
+      self.parameter_ports.as_slice(),
+      self.audio_inputs.as_slice(),
+      self.audio_outputs.as_slice(),
+      self.events_inputs.as_slice(),
+      self.events_outputs.as_slice(),
+    )
+    .with_timing(self.block_timestamp, self.sample_rate)
+    .with_transport(self.transport);
+    processor.render(&mut context);
+
+    let block_end = match self.sample_rate {
+      0 => TimestampNanos::MAX,
+      sample_rate => self.block_timestamp + samples_to_nanos(self.num_samples, sample_rate),
+    };
+    for port in self.events_outputs.iter() {
+      port.deliver_scheduled(block_end);
+    }
+  }
+}
+
+/// Audio and events produced by a processor along all the rendered blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HarnessOutput {
+  /// Samples by audio output and channel
+  pub audio: Vec<Vec<Vec<f32>>>,
+  /// Events by events output
+  pub events: Vec<Vec<Event>>,
+}
+
+impl HarnessOutput {
+  pub fn channel(&self, port: usize, channel: usize) -> &[f32] {
+    self.audio[port][channel].as_slice()
+  }
+}
+
+/// Renders a processor offline, block by block, from scripted parameter changes, events and audio.
+///
+/// The processor is prepared and reset before rendering the first block.
+/// Timestamps are relative to the beginning of the first block.
+pub struct ProcessorHarness<P: Processor> {
+  processor: P,
+  descriptor: NodeDescriptor,
+  sample_rate: SampleRate,
+  block_size: usize,
+  transport: Transport,
+  context: Option<TestContext>,
+  num_blocks: usize,
+  /// Parameter changes as block, parameter index and value
+  parameter_changes: Vec<(usize, usize, f32)>,
+  /// Events by events input
+  events: Vec<Vec<Event>>,
+  /// Samples by audio input and channel
+  audio: Vec<Vec<Vec<f32>>>,
+}
+
+impl<P: Processor> ProcessorHarness<P> {
+  pub const DEFAULT_SAMPLE_RATE: SampleRate = 44_100;
+  pub const DEFAULT_BLOCK_SIZE: usize = 64;
+
+  pub fn new(processor: P) -> Self {
+    let descriptor = processor.descriptor();
+    let events = vec![Vec::new(); descriptor.ports.events.static_inputs.len()];
+    let audio = descriptor
+      .ports
+      .audio
+      .static_inputs
+      .iter()
+      .map(|port| vec![Vec::new(); port.channels()])
+      .collect();
+    Self {
+      processor,
+      descriptor,
+      sample_rate: Self::DEFAULT_SAMPLE_RATE,
+      block_size: Self::DEFAULT_BLOCK_SIZE,
+      transport: Transport::new(),
+      context: None,
+      num_blocks: 0,
+      parameter_changes: Vec::new(),
+      events,
+      audio,
+    }
+  }
+
+  #[must_use]
+  pub fn with_sample_rate(mut self, sample_rate: SampleRate) -> Self {
+    self.sample_rate = sample_rate;
+    self
+  }
+
+  #[must_use]
+  pub fn with_block_size(mut self, block_size: usize) -> Self {
+    self.block_size = block_size;
+    self
+  }
+
+  /// Set the state of the transport at the beginning of the first block.
+  /// It advances along the blocks while playing.
+  #[must_use]
+  pub fn with_transport(mut self, transport: Transport) -> Self {
+    self.transport = transport;
+    self
+  }
+
+  pub fn processor(&self) -> &P {
+    &self.processor
+  }
+
+  pub fn processor_mut(&mut self) -> &mut P {
+    &mut self.processor
+  }
+
+  /// Timestamp of a sample, counting from the beginning of the first block.
+  pub fn sample_timestamp(&self, sample: usize) -> TimestampNanos {
+    samples_to_nanos(sample, self.sample_rate)
+  }
+
+  /// Timestamp of the first sample of a block.
+  pub fn block_timestamp(&self, block: usize) -> TimestampNanos {
+    self.sample_timestamp(block * self.block_size)
+  }
+
+  /// Change the value of a parameter at the beginning of a block.
+  pub fn set_parameter(&mut self, block: usize, index: usize, value: f32) -> &mut Self {
+    self.parameter_changes.push((block, index, value));
+    self
+  }
+
+  /// Send an event to an events input, to be received within the block including its timestamp.
+  pub fn send_event(&mut self, port: usize, event: Event) -> &mut Self {
+    self.events[port].push(event);
+    self
+  }
+
+  /// Feed a channel of an audio input with samples starting from the first block, followed by silence.
+  pub fn feed_audio(&mut self, port: usize, channel: usize, samples: &[f32]) -> &mut Self {
+    self.audio[port][channel] = samples.to_vec();
+    self
+  }
+
+  /// Render a number of blocks following the ones already rendered, returning what the processor produced.
+  pub fn render(&mut self, num_blocks: usize) -> HarnessOutput {
+    let (sample_rate, block_size) = (self.sample_rate, self.block_size);
+    if self.context.is_none() {
+      self.processor.prepare(sample_rate, block_size);
+      self.processor.reset();
+      self.context = Some(TestContext::new(&self.descriptor, block_size));
+    }
+
+    let ports = &self.descriptor.ports;
+    let mut output = HarnessOutput {
+      audio: ports
+        .audio
+        .static_outputs
+        .iter()
+        .map(|port| vec![Vec::new(); port.channels()])
+        .collect(),
+      events: vec![Vec::new(); ports.events.static_outputs.len()],
+    };
+
+    for _ in 0..num_blocks {
+      let block = self.num_blocks;
+      let block_start = self.block_timestamp(block);
+      let block_end = self.block_timestamp(block + 1);
+      let context = self.context.as_mut().unwrap();
+
+      for (_, index, value) in self
+        .parameter_changes
+        .iter()
+        .filter(|(change_block, _, _)| *change_block == block)
+      {
+        context.set_parameter(*index, *value);
+      }
+
+      for (port, channels) in self.audio.iter().enumerate() {
+        for (channel, samples) in channels.iter().enumerate() {
+          let mut block_samples = vec![0.0; block_size];
+          for (target, sample) in block_samples
+            .iter_mut()
+            .zip(samples.iter().skip(block * block_size))
+          {
+            *target = *sample;
+          }
+          context.set_input(port, channel, &block_samples);
+        }
+      }
+
+      for (port, events) in self.events.iter().enumerate() {
+        let block_events = events
+          .iter()
+          .filter(|event| block_start <= event.timestamp && event.timestamp < block_end)
+          .cloned()
+          .collect::<Vec<_>>();
+        context.set_events(port, &block_events);
+      }
+
+      context.set_timing(block_start, sample_rate);
+      context.set_transport(self.transport);
+      context.render(&mut self.processor);
+
+      for (port, channels) in output.audio.iter_mut().enumerate() {
+        for (channel, samples) in channels.iter_mut().enumerate() {
+          samples.extend(context.output(port, channel));
+        }
+      }
+      for (port, events) in output.events.iter_mut().enumerate() {
+        events.extend(context.events_output(port));
+      }
+
+      self.transport.advance(block_size, sample_rate);
+      self.num_blocks += 1;
+    }
+    output
+  }
+}
+
+fn samples_to_nanos(num_samples: usize, sample_rate: SampleRate) -> TimestampNanos {
+  (num_samples as u128 * 1_000_000_000 / u128::from(sample_rate)) as TimestampNanos
+}
+
+/// Compare samples against the ones in a golden file within a tolerance, panicking on the first difference.
+///
+/// The golden file is written instead when the [`UPDATE_GOLDEN_VAR`] environment variable is set,
+/// so it can be reviewed and committed along with the test. Otherwise a missing golden file fails,
+/// rather than passing silently where it was not committed.
+pub fn assert_golden<T: AsRef<Path>>(path: T, samples: &[f32], tolerance: f32) {
+  let path = path.as_ref();
+  if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+    write_golden(path, samples);
+    return;
+  }
+  assert!(
+    path.exists(),
+    "{}: missing golden file, set {} to write it",
+    path.display(),
+    UPDATE_GOLDEN_VAR
+  );
+
+  let expected = read_golden(path);
+  assert_eq!(
+    expected.len(),
+    samples.len(),
+    "{}: expected {} samples rather than {}",
+    path.display(),
+    expected.len(),
+    samples.len()
+  );
+  for (index, (expected, sample)) in expected.iter().zip(samples.iter()).enumerate() {
+    assert!(
+      (expected - sample).abs() <= tolerance,
+      "{}: expected {} at sample {} rather than {}",
+      path.display(),
+      expected,
+      index,
+      sample
+    );
+  }
+}
+
+/// Write samples into a golden file as text, one per line, so the differences can be reviewed.
+pub fn write_golden<T: AsRef<Path>>(path: T, samples: &[f32]) {
+  let path = path.as_ref();
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).unwrap_or_else(|err| panic!("{}: {}", parent.display(), err));
+  }
+  let text = samples
+    .iter()
+    .map(|sample| format!("{:e}\n", sample))
+    .collect::<String>();
+  fs::write(path, text).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+}
+
+pub fn read_golden<T: AsRef<Path>>(path: T) -> Vec<f32> {
+  let path = path.as_ref();
+  fs::read_to_string(path)
+    .unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    .lines()
+    .map(|line| {
+      line
+        .parse()
+        .unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::processors::gain::CachedGain;
+  use crate::processors::GainProcessor;
+
+  #[test]
+  fn render_scripted_blocks() {
+    let mut harness = ProcessorHarness::new(GainProcessor::new(1))
+      .with_sample_rate(1000)
+      .with_block_size(4);
+    harness
+      .feed_audio(0, 0, &[1.0; 10])
+      .set_parameter(1, GainProcessor::GAIN_INDEX, CachedGain::MIN_DECIBELS)
+      .set_parameter(2, GainProcessor::GAIN_INDEX, 0.0);
+
+    let output = harness.render(2);
+    assert_eq!(
+      output.channel(0, 0),
+      &[1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0]
+    );
+
+    let output = harness.render(2);
+    assert_eq!(
+      output.channel(0, 0),
+      &[1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+    );
+  }
+
+  #[test]
+  fn compare_against_golden_files() {
+    // the golden files are written rather than compared while updating them
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+      return;
+    }
+    let path = std::env::temp_dir().join(format!("kiro-golden-{}.txt", std::process::id()));
+    write_golden(&path, &[0.0, 0.5, -1.0]);
+    assert_eq!(read_golden(&path), vec![0.0, 0.5, -1.0]);
+    assert_golden(&path, &[0.0, 0.50001, -1.0], 0.001);
+
+    let different = std::panic::catch_unwind(|| assert_golden(&path, &[0.0, 0.6, -1.0], 0.001));
+    let shorter = std::panic::catch_unwind(|| assert_golden(&path, &[0.0, 0.5], 0.001));
+    fs::remove_file(&path).unwrap();
+    assert!(different.is_err());
+    assert!(shorter.is_err());
+
+    let missing = std::panic::catch_unwind(|| assert_golden(&path, &[0.0], 0.001));
+    assert!(missing.is_err());
+    assert!(!path.exists());
+  }
+}
@@ -1,3 +1,4 @@
+#[cfg(target_os = "macos")]
 use core_foundation::runloop::CFRunLoop;
 use kiro_midi::{self as midi, drivers::DriverSpec, Filter, InputConfig, SourceMatch};
 
@@ -22,23 +23,34 @@ fn main() {
   print_endpoints(&driver);
 
   std::thread::spawn(move || loop {
-    loop {
-      let mut input_line = String::new();
-      std::io::stdin()
-        .read_line(&mut input_line)
-        .expect("Failed to read line");
-
-      print_endpoints(&driver);
-    }
+    let mut input_line = String::new();
+    std::io::stdin()
+      .read_line(&mut input_line)
+      .expect("Failed to read line");
+
+    print_endpoints(&driver);
   });
 
   println!("=== Press Ctrl-C to stop ===");
   println!("=== Press Enter to list endpoints ===");
 
-  // This is required in MacOS to be able to handle notifications whenever devices are plugged/unplugged
+  run_until_stopped();
+}
+
+// This is required in MacOS to be able to handle notifications whenever devices are plugged/unplugged
+#[cfg(target_os = "macos")]
+fn run_until_stopped() {
   CFRunLoop::run_current();
 }
 
+// Elsewhere there are no notifications, so just wait for Ctrl-C
+#[cfg(not(target_os = "macos"))]
+fn run_until_stopped() {
+  loop {
+    std::thread::park();
+  }
+}
+
 fn print_endpoints(driver: &midi::drivers::Driver) {
   println!("===================================================================================");
   println!("Sources:");
@@ -0,0 +1,339 @@
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::endpoints::EndpointId;
+use crate::event::{Event, JrTimestamp, TimestampNanos};
+use crate::filter::Filter;
+use crate::input_handler::InputHandler;
+use crate::protocol::codec::Decoder;
+
+/// Magic bytes at the beginning of every capture file, including the format version.
+pub const CAPTURE_MAGIC: &[u8; 8] = b"KMIDCAP1";
+
+const JR_TIMESTAMP_FLAG: u8 = 0x01;
+const JR_SENDER_CLOCK_FLAG: u8 = 0x02;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+  #[error("IO error: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Not a capture file")]
+  InvalidHeader,
+
+  #[error("Invalid message with {0} words")]
+  InvalidMessage(usize),
+}
+
+/// Writes events into a capture stream.
+///
+/// A capture is a header with [`CAPTURE_MAGIC`] followed by one record per event, all of them in little endian:
+///
+/// | Field         | Size                                          |
+/// |---------------|-----------------------------------------------|
+/// | timestamp     | u64 (nanoseconds)                             |
+/// | endpoint      | u64                                           |
+/// | flags         | u8 (bit 0: JR timestamp, bit 1: sender clock) |
+/// | jr timestamp  | u16                                           |
+/// | sender clock  | u16                                           |
+/// | length        | u8 (number of words)                          |
+/// | words         | u32 * length (Universal MIDI Packet)          |
+pub struct CaptureWriter<W: Write> {
+  writer: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+  pub fn new(mut writer: W) -> Result<Self, CaptureError> {
+    writer.write_all(CAPTURE_MAGIC)?;
+    Ok(Self { writer })
+  }
+
+  pub fn write_event(&mut self, event: &Event) -> Result<(), CaptureError> {
+    let (ump, len) = event.message.encode();
+
+    let mut flags = 0u8;
+    let mut jr_timestamp = 0u16;
+    let mut sender_clock = 0u16;
+    if let Some(jr) = event.jr_timestamp {
+      flags |= JR_TIMESTAMP_FLAG;
+      jr_timestamp = jr.timestamp;
+      if let Some(clock) = jr.sender_clock {
+        flags |= JR_SENDER_CLOCK_FLAG;
+        sender_clock = clock;
+      }
+    }
+
+    self.writer.write_all(&event.timestamp.to_le_bytes())?;
+    self.writer.write_all(&event.endpoint.to_le_bytes())?;
+    self.writer.write_all(&[flags])?;
+    self.writer.write_all(&jr_timestamp.to_le_bytes())?;
+    self.writer.write_all(&sender_clock.to_le_bytes())?;
+    self.writer.write_all(&[len as u8])?;
+    for word in &ump[0..len] {
+      self.writer.write_all(&word.to_le_bytes())?;
+    }
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> Result<(), CaptureError> {
+    self.writer.flush().map_err(CaptureError::from)
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+/// Reads the events from a capture stream written by [`CaptureWriter`].
+pub struct CaptureReader<R: Read> {
+  reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+  pub fn new(mut reader: R) -> Result<Self, CaptureError> {
+    let mut magic = [0u8; 8];
+    reader
+      .read_exact(&mut magic)
+      .map_err(|_| CaptureError::InvalidHeader)?;
+    if &magic == CAPTURE_MAGIC {
+      Ok(Self { reader })
+    } else {
+      Err(CaptureError::InvalidHeader)
+    }
+  }
+
+  /// Read the next event, or `None` when the end of the capture has been reached.
+  pub fn read_event(&mut self) -> Result<Option<Event>, CaptureError> {
+    let mut timestamp = [0u8; 8];
+    match self.reader.read_exact(&mut timestamp) {
+      Ok(()) => {}
+      Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+      Err(err) => return Err(err.into()),
+    }
+    let timestamp = TimestampNanos::from_le_bytes(timestamp);
+
+    let mut header = [0u8; 14];
+    self.reader.read_exact(&mut header)?;
+    let endpoint = EndpointId::from_le_bytes(header[0..8].try_into().unwrap());
+    let flags = header[8];
+    let jr_timestamp = u16::from_le_bytes([header[9], header[10]]);
+    let sender_clock = u16::from_le_bytes([header[11], header[12]]);
+    let len = header[13] as usize;
+
+    if len == 0 || len > 4 {
+      return Err(CaptureError::InvalidMessage(len));
+    }
+
+    let filter = Filter::new();
+    let mut decoder = Decoder::default();
+    let mut message = None;
+    for _ in 0..len {
+      let mut word = [0u8; 4];
+      self.reader.read_exact(&mut word)?;
+      message = decoder
+        .next(u32::from_le_bytes(word), &filter)
+        .map_err(|_| CaptureError::InvalidMessage(len))?;
+    }
+    let message = message.ok_or(CaptureError::InvalidMessage(len))?;
+
+    let jr_timestamp = if flags & JR_TIMESTAMP_FLAG != 0 {
+      let sender_clock = if flags & JR_SENDER_CLOCK_FLAG != 0 {
+        Some(sender_clock)
+      } else {
+        None
+      };
+      Some(JrTimestamp::new(jr_timestamp, sender_clock))
+    } else {
+      None
+    };
+
+    Ok(Some(Event {
+      timestamp,
+      endpoint,
+      message,
+      jr_timestamp,
+    }))
+  }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+  type Item = Result<Event, CaptureError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.read_event().transpose()
+  }
+}
+
+/// How to pace the events when replaying a capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+  /// Deliver the events as fast as possible.
+  Immediate,
+  /// Wait between events according to their timestamps, scaled by a speed factor.
+  Realtime { speed: f64 },
+}
+
+/// Replay the events of a capture through an [`InputHandler`], returning how many were delivered.
+///
+/// The events keep their original timestamps, so that timing issues can be reproduced offline.
+pub fn replay<R, H>(
+  reader: CaptureReader<R>,
+  handler: H,
+  pacing: Pacing,
+) -> Result<usize, CaptureError>
+where
+  R: Read,
+  H: Into<InputHandler>,
+{
+  let mut handler = handler.into();
+  let start = Instant::now();
+  let mut first_timestamp = None;
+  let mut count = 0;
+
+  for event in reader {
+    let event = event?;
+    if let Pacing::Realtime { speed } = pacing {
+      let first_timestamp = *first_timestamp.get_or_insert(event.timestamp);
+      let offset = event.timestamp.saturating_sub(first_timestamp) as f64 / speed.max(f64::EPSILON);
+      let deadline = start + Duration::from_nanos(offset as u64);
+      let now = Instant::now();
+      if deadline > now {
+        std::thread::sleep(deadline - now);
+      }
+    }
+    handler.call(event);
+    count += 1;
+  }
+
+  Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+  use crate::messages::channel_voice::ChannelVoiceMessage;
+  use crate::messages::system_common::SystemCommon;
+  use crate::messages::system_exclusive::{Payload, SystemExclusive};
+  use crate::messages::{Message, MessageType};
+
+  fn events() -> Vec<Event> {
+    vec![
+      Event {
+        timestamp: 1_000,
+        endpoint: 0x1234,
+        message: Message::channel_voice(
+          1,
+          2,
+          ChannelVoiceMessage::NoteOn {
+            note: 60,
+            velocity: 0xabcd,
+            attr_type: 0,
+            attr_data: 0,
+          },
+        ),
+        jr_timestamp: Some(JrTimestamp::new(110, Some(100))),
+      },
+      Event {
+        timestamp: 2_000,
+        endpoint: 0x1234,
+        message: Message::new(0, MessageType::SystemCommon(SystemCommon::TimingClock)),
+        jr_timestamp: Some(JrTimestamp::new(120, None)),
+      },
+      Event {
+        timestamp: 3_000,
+        endpoint: 0x5678,
+        message: Message::new(
+          3,
+          MessageType::SystemExclusive(SystemExclusive::Complete(
+            Payload::new(&[0x7e, 0x7f, 0x06, 0x01]).unwrap(),
+          )),
+        ),
+        jr_timestamp: None,
+      },
+    ]
+  }
+
+  fn capture(events: &[Event]) -> Vec<u8> {
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    for event in events {
+      writer.write_event(event).unwrap();
+    }
+    writer.into_inner()
+  }
+
+  #[test]
+  fn write_and_read() {
+    let events = events();
+    let data = capture(&events);
+
+    let reader = CaptureReader::new(data.as_slice()).unwrap();
+    let read_events = reader
+      .collect::<Result<Vec<Event>, CaptureError>>()
+      .unwrap();
+
+    assert_eq!(read_events, events);
+  }
+
+  #[test]
+  fn invalid_header() {
+    assert!(matches!(
+      CaptureReader::new(&b"MThd\0\0\0\x06"[..]),
+      Err(CaptureError::InvalidHeader)
+    ));
+    assert!(matches!(
+      CaptureReader::new(&b"KMID"[..]),
+      Err(CaptureError::InvalidHeader)
+    ));
+  }
+
+  #[test]
+  fn truncated_record() {
+    let mut data = capture(&events());
+    data.truncate(data.len() - 2);
+
+    let reader = CaptureReader::new(data.as_slice()).unwrap();
+    let result = reader.collect::<Result<Vec<Event>, CaptureError>>();
+
+    assert!(matches!(result, Err(CaptureError::Io(_))));
+  }
+
+  #[test]
+  fn replay_through_input_handler() {
+    let events = events();
+    let data = capture(&events);
+    let replayed = Arc::new(Mutex::new(Vec::new()));
+    let replayed_clone = replayed.clone();
+
+    let reader = CaptureReader::new(data.as_slice()).unwrap();
+    let count = replay(
+      reader,
+      move |event: Event| replayed_clone.lock().unwrap().push(event),
+      Pacing::Immediate,
+    )
+    .unwrap();
+
+    assert_eq!(count, 3);
+    assert_eq!(*replayed.lock().unwrap(), events);
+  }
+
+  #[test]
+  fn replay_in_realtime() {
+    let data = capture(&events());
+
+    let start = Instant::now();
+    let reader = CaptureReader::new(data.as_slice()).unwrap();
+    let count = replay(
+      reader,
+      |_event: Event| {},
+      Pacing::Realtime { speed: 0.001 },
+    )
+    .unwrap();
+
+    assert_eq!(count, 3);
+    assert!(start.elapsed() >= Duration::from_millis(2));
+  }
+}
@@ -2,27 +2,38 @@ use arc_swap::ArcSwap;
 use core_foundation_sys::base::OSStatus;
 use coremidi::{
   Client, EventList, InputPortWithContext, Notification, NotifyCallback, Object, ObjectType,
-  Protocol, Source,
+  Properties, Protocol, Source,
 };
 use parking_lot::Mutex;
+use ringbuf::{Consumer, Producer, RingBuffer};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use thiserror::Error;
 
 use crate::drivers;
 use crate::drivers::coremidi::endpoints::Endpoints;
 use crate::drivers::coremidi::timestamp::coremidi_timestamp_to_nanos;
-use crate::endpoints::{DestinationInfo, EndpointId, SourceId, SourceInfo};
+use crate::drivers::{DecodeReason, ErrorSink, InputError, InputErrorRecord, InputErrorReporter};
+use crate::endpoints::{DestinationInfo, EndpointId, EndpointProperties, SourceId, SourceInfo};
 use crate::event::Event;
 use crate::filter::Filter;
 use crate::input_config::InputConfig;
 use crate::input_handler::InputHandler;
 use crate::input_info::InputInfo;
+use crate::messages::ump_stream::UmpStream;
+use crate::messages::{Message, MessageType};
 use crate::protocol::codec::Decoder;
+use crate::protocol::translate::Translator;
 use crate::source_match::SourceMatches;
 
 type InputName = String;
 
+/// UMP Stream messages received by the inputs, forwarded to update the endpoints.
+type UmpStreams = Arc<Mutex<Vec<Consumer<(SourceId, UmpStream)>>>>;
+
 #[derive(Error, Debug)]
 pub enum CoreMidiError {
   #[error("Error creating a new client: {0}")]
@@ -44,18 +55,50 @@ pub enum CoreMidiError {
   ConnectSource(OSStatus, InputName, SourceId),
 }
 
+/// Context of a source connected to an input, with the translators of its groups,
+/// created when it connects so the callback of the input only has to index into them.
+struct SourceContext {
+  id: SourceId,
+  translators: Vec<Translator>,
+}
+
+impl SourceContext {
+  fn new(id: SourceId, config: &InputConfig) -> Self {
+    Self {
+      id,
+      translators: config.translators(),
+    }
+  }
+}
+
 struct Input {
   name: InputName,
   sources: SourceMatches,
+  high_resolution_cc: bool,
+  ump_capacity: Option<usize>,
   connected: HashSet<SourceId>,
   filters: Arc<ArcSwap<HashMap<SourceId, Filter>>>,
-  port: coremidi::InputPortWithContext<SourceId>,
+  port: coremidi::InputPortWithContext<SourceContext>,
+}
+
+impl Input {
+  fn config(&self) -> InputConfig {
+    InputConfig {
+      name: self.name.clone(),
+      sources: self.sources.clone(),
+      high_resolution_cc: self.high_resolution_cc,
+      ump_capacity: self.ump_capacity,
+    }
+  }
 }
 
 pub struct CoreMidiDriver {
   client: Client,
   endpoints: Arc<Mutex<Endpoints>>,
   inputs: Arc<Mutex<HashMap<String, Input>>>,
+  errors: ErrorSink,
+  ump_streams: UmpStreams,
+  monitoring: Arc<AtomicBool>,
 }
 
 impl drivers::DriverSpec for CoreMidiDriver {
@@ -66,7 +109,13 @@ impl drivers::DriverSpec for CoreMidiDriver {
     if self.inputs.lock().contains_key(config.name.as_str()) {
       Err(CoreMidiError::InputAlreadyExists(config).into())
     } else {
-      let InputConfig { name, sources } = config;
+      let port_config = config.clone();
+      let InputConfig {
+        name,
+        sources,
+        high_resolution_cc,
+        ump_capacity,
+      } = config;
 
       let filters = self
         .endpoints
@@ -75,14 +124,19 @@ impl drivers::DriverSpec for CoreMidiDriver {
         .into_iter()
         .filter_map(|connected_source| {
           sources
-            .match_filter(connected_source.id, connected_source.name.as_str())
+            .match_filter(
+              connected_source.id,
+              connected_source.name.as_str(),
+              &connected_source.properties,
+            )
             .map(|filter| (connected_source.id, filter))
         })
         .collect::<HashMap<SourceId, Filter>>();
 
       let filters = Arc::new(ArcSwap::new(Arc::new(filters)));
 
-      let mut port = self.create_input_port(name.clone(), handler.into(), filters.clone())?;
+      let mut port =
+        self.create_input_port(port_config.clone(), handler.into(), filters.clone())?;
 
       let endpoints = self.endpoints.lock();
 
@@ -90,8 +144,13 @@ impl drivers::DriverSpec for CoreMidiDriver {
 
       for source_id in filters.load().keys().cloned() {
         if let Some(source) = endpoints.get_source(source_id) {
-          if let Ok(()) = port.connect_source(source, source_id) {
-            connected.insert(source_id);
+          match port.connect_source(source, SourceContext::new(source_id, &port_config)) {
+            Ok(()) => {
+              connected.insert(source_id);
+            }
+            Err(status) => self
+              .errors
+              .report(Self::connect_error(&name, source_id, status)),
           }
         }
       }
@@ -99,6 +158,8 @@ impl drivers::DriverSpec for CoreMidiDriver {
       let input = Input {
         name: name.clone(),
         sources,
+        high_resolution_cc,
+        ump_capacity,
         connected,
         filters,
         port,
@@ -129,7 +190,13 @@ impl drivers::DriverSpec for CoreMidiDriver {
           .get(&connected_source.id)
           .map(|inputs| inputs.iter().cloned().collect::<Vec<String>>())
           .unwrap_or_default();
-        SourceInfo::new(connected_source.id, connected_source.name.clone(), inputs)
+        SourceInfo::new(
+          connected_source.id,
+          connected_source.name.clone(),
+          connected_source.properties.clone(),
+          connected_source.ump_endpoint.clone(),
+          inputs,
+        )
       })
       .collect()
   }
@@ -160,10 +227,7 @@ impl drivers::DriverSpec for CoreMidiDriver {
   }
 
   fn get_input_config(&self, name: &str) -> Option<InputConfig> {
-    self.inputs.lock().get(name).map(|input| InputConfig {
-      name: input.name.clone(),
-      sources: input.sources.clone(),
-    })
+    self.inputs.lock().get(name).map(Input::config)
   }
 
   fn set_input_sources(&self, name: &str, sources: SourceMatches) -> Result<(), drivers::Error> {
@@ -180,19 +244,32 @@ impl drivers::DriverSpec for CoreMidiDriver {
       .into_iter()
       .filter_map(|connected_source| {
         sources
-          .match_filter(connected_source.id, connected_source.name.as_str())
+          .match_filter(
+            connected_source.id,
+            connected_source.name.as_str(),
+            &connected_source.properties,
+          )
           .map(|filter| (connected_source.id, filter, &connected_source.source))
       })
       .collect::<Vec<(SourceId, Filter, &Source)>>();
 
     let mut filters = HashMap::<SourceId, Filter>::with_capacity(connected_sources.len());
     let mut disconnected = input.connected.clone();
+    let config = input.config();
 
     for (source_id, filter, source) in connected_sources {
       filters.insert(source_id, filter);
       if !input.connected.contains(&source_id) {
-        if let Ok(()) = input.port.connect_source(source, source_id) {
-          input.connected.insert(source_id);
+        match input
+          .port
+          .connect_source(source, SourceContext::new(source_id, &config))
+        {
+          Ok(()) => {
+            input.connected.insert(source_id);
+          }
+          Err(status) => self
+            .errors
+            .report(Self::connect_error(&input.name, source_id, status)),
         }
       } else {
         disconnected.remove(&source_id);
@@ -210,77 +287,176 @@ impl drivers::DriverSpec for CoreMidiDriver {
 
     Ok(())
   }
+
+  fn set_error_handler<F>(&mut self, handler: F)
+  where
+    F: Fn(InputError) + Send + Sync + 'static,
+  {
+    self.errors.set_handler(handler);
+  }
 }
 
 impl CoreMidiDriver {
+  /// Period of the thread that forwards the errors and UMP Stream messages queued by the callbacks of the inputs.
+  const MONITOR_PERIOD: Duration = Duration::from_millis(20);
+
+  /// Number of UMP Stream messages that an input can queue until they are forwarded.
+  const UMP_STREAM_CAPACITY: usize = 64;
+
   pub fn new(name: &str) -> Result<Self, drivers::Error> {
     let endpoints = Arc::new(Mutex::new(Endpoints::new()));
     let inputs = Arc::new(Mutex::new(HashMap::new()));
-    let callback = Self::notifications_callback(endpoints.clone(), inputs.clone());
+    let errors = ErrorSink::default();
+    let callback = Self::notifications_callback(endpoints.clone(), inputs.clone(), errors.clone());
     let client =
       Client::new_with_notifications(name, callback).map_err(CoreMidiError::ClientCreate)?;
     Self::initialize_endpoints(endpoints.clone());
+    let ump_streams = UmpStreams::default();
+    let monitoring = Arc::new(AtomicBool::new(true));
+    Self::spawn_monitor(
+      monitoring.clone(),
+      errors.clone(),
+      endpoints.clone(),
+      ump_streams.clone(),
+    );
 
     Ok(Self {
       client,
       endpoints,
       inputs,
+      errors,
+      ump_streams,
+      monitoring,
     })
   }
 
+  fn spawn_monitor(
+    monitoring: Arc<AtomicBool>,
+    errors: ErrorSink,
+    endpoints: Arc<Mutex<Endpoints>>,
+    ump_streams: UmpStreams,
+  ) {
+    thread::spawn(move || {
+      while monitoring.load(Ordering::Relaxed) {
+        errors.drain();
+        for consumer in ump_streams.lock().iter_mut() {
+          while let Some((source_id, ump_stream)) = consumer.pop() {
+            endpoints.lock().update_ump_endpoint(source_id, &ump_stream);
+          }
+        }
+        thread::sleep(Self::MONITOR_PERIOD);
+      }
+    });
+  }
+
   fn create_input_port(
     &self,
-    name: String,
+    config: InputConfig,
     mut handler: InputHandler,
     filters: Arc<ArcSwap<HashMap<SourceId, Filter>>>,
-  ) -> Result<InputPortWithContext<SourceId>, CoreMidiError> {
+  ) -> Result<InputPortWithContext<SourceContext>, CoreMidiError> {
     let default_filter = Filter::new();
     let mut decoder = Decoder::default();
+    let (mut ump_streams, ump_streams_consumer) =
+      RingBuffer::new(Self::UMP_STREAM_CAPACITY).split();
+    self.ump_streams.lock().push(ump_streams_consumer);
+    let mut errors = self.errors.input_reporter(Arc::from(config.name.as_str()));
+    // the MIDI 1.0 messages are translated by the input rather than by CoreMIDI when it needs to pair them
+    let protocol = if config.translates_midi1() {
+      Protocol::Midi10
+    } else {
+      Protocol::Midi20
+    };
     self
       .client
       .input_port_with_protocol(
-        name.clone().as_str(),
-        Protocol::Midi20,
-        move |events, source_id: &mut SourceId| {
+        config.name.clone().as_str(),
+        protocol,
+        move |events, source: &mut SourceContext| {
           Self::handle_input(
-            name.as_str(),
+            &mut ump_streams,
+            &mut errors,
             &filters,
             &default_filter,
             &mut decoder,
             &mut handler,
             events,
-            *source_id,
+            source,
           );
         },
       )
       .map_err(CoreMidiError::PortCreate)
   }
 
+  #[allow(clippy::too_many_arguments)]
   fn handle_input(
-    _name: &str,
+    ump_streams: &mut Producer<(SourceId, UmpStream)>,
+    errors: &mut InputErrorReporter,
     filters: &ArcSwap<HashMap<SourceId, Filter>>,
     default_filter: &Filter,
     decoder: &mut Decoder,
     handler: &mut InputHandler,
     events: &EventList,
-    source_id: SourceId,
+    source: &mut SourceContext,
   ) {
+    let source_id = source.id;
     let filters = filters.load();
     let filter = filters.get(&source_id).unwrap_or(default_filter);
     // println!("filter: {:#?}", filter);
-    // println!("\n==> [{}:{:08x}:{}] {:?}", name, source_id, source_id, events);
+    // println!("\n==> [{:08x}:{}] {:?}", source_id, source_id, events);
 
     for event in events.iter() {
       decoder.reset();
       let timestamp = coremidi_timestamp_to_nanos(event.timestamp());
+      let mut decode = |word: Result<u32, DecodeReason>| {
+        let decoded = word.and_then(|word| {
+          decoder.next(word, filter).map_err(|err| {
+            decoder.reset();
+            DecodeReason::Codec(err)
+          })
+        });
+        match decoded {
+          Ok(Some(message)) => {
+            if let Message {
+              mtype: MessageType::UmpStream(ump_stream),
+              ..
+            } = &message
+            {
+              ump_streams.push((source_id, *ump_stream)).ok();
+            }
+            let event = Event {
+              timestamp,
+              endpoint: source_id,
+              message,
+              jr_timestamp: decoder.jr_timestamp(),
+            };
+            if !handler.call(event) {
+              errors.report(InputErrorRecord::EventDropped { source: source_id });
+            }
+          }
+          Ok(None) => {}
+          Err(reason) => errors.report(InputErrorRecord::Decode {
+            source: source_id,
+            reason,
+          }),
+        }
+      };
+
       for word in event.data() {
-        if let Ok(Some(message)) = decoder.next(*word, filter) {
-          let event = Event {
-            timestamp,
-            endpoint: source_id,
-            message,
-          };
-          handler.call(event);
+        // MIDI 1.0 Channel Voice messages, only received when the input translates them
+        let translator = match word >> 28 {
+          0x2 => source.translators.get_mut(((word >> 24) & 0x0f) as usize),
+          _ => None,
+        };
+        match translator {
+          Some(translator) => match translator.push_midi1_ump(*word, filter) {
+            Ok(_) => translator.drain().map(Ok).for_each(&mut decode),
+            Err(err) => {
+              translator.drain().for_each(drop);
+              decode(Err(DecodeReason::Translate(err)));
+            }
+          },
+          None => decode(Ok(*word)),
         }
       }
     }
@@ -289,10 +465,13 @@ impl CoreMidiDriver {
   fn notifications_callback(
     endpoints: Arc<Mutex<Endpoints>>,
     mut inputs: Arc<Mutex<HashMap<InputName, Input>>>,
+    errors: ErrorSink,
   ) -> NotifyCallback {
     NotifyCallback::by_ownership(move |notification: Notification| match notification {
       Notification::ObjectAdded(info) => match info.child_type {
-        ObjectType::Source => Self::handle_source_connected(&endpoints, &mut inputs, info.child),
+        ObjectType::Source => {
+          Self::handle_source_connected(&endpoints, &mut inputs, &errors, info.child)
+        }
         ObjectType::Destination => Self::handle_destination_connected(&endpoints, info.child),
         _ => {}
       },
@@ -308,31 +487,51 @@ impl CoreMidiDriver {
   fn handle_source_connected(
     endpoints: &Arc<Mutex<Endpoints>>,
     inputs: &mut Arc<Mutex<HashMap<InputName, Input>>>,
+    errors: &ErrorSink,
     object: Object,
   ) {
     if let Some((source_id, name)) = Self::object_info(&object) {
+      let properties = Self::object_properties(&object);
       let mut endpoints = endpoints.lock();
-      endpoints.add_source(source_id, name.clone(), object.into());
+      endpoints.add_source(source_id, name.clone(), properties.clone(), object.into());
       if let Some(source) = endpoints.get_source(source_id) {
-        Self::connect_source(&mut inputs.lock(), source_id, name, source);
+        Self::connect_source(
+          &mut inputs.lock(),
+          errors,
+          source_id,
+          name,
+          &properties,
+          source,
+        );
       }
     }
   }
 
   fn connect_source(
     inputs: &mut HashMap<InputName, Input>,
+    errors: &ErrorSink,
     source_id: SourceId,
     source_name: String,
+    source_properties: &EndpointProperties,
     source: &Source,
   ) {
     for input in inputs.values_mut() {
       if !input.connected.contains(&source_id) {
-        if let Some(filter) = input.sources.match_filter(source_id, source_name.as_str()) {
-          let mut filters = input.filters.load().as_ref().clone();
-          filters.insert(source_id, filter);
-          input.filters.swap(Arc::new(filters));
-          input.port.connect_source(source, source_id).ok();
-          input.connected.insert(source_id);
+        if let Some(filter) =
+          input
+            .sources
+            .match_filter(source_id, source_name.as_str(), source_properties)
+        {
+          let context = SourceContext::new(source_id, &input.config());
+          match input.port.connect_source(source, context) {
+            Ok(()) => {
+              let mut filters = input.filters.load().as_ref().clone();
+              filters.insert(source_id, filter);
+              input.filters.swap(Arc::new(filters));
+              input.connected.insert(source_id);
+            }
+            Err(status) => errors.report(Self::connect_error(&input.name, source_id, status)),
+          }
         }
       }
     }
@@ -348,6 +547,7 @@ impl CoreMidiDriver {
         &mut inputs.lock(),
         connected_source.id,
         connected_source.name,
+        &connected_source.properties,
         connected_source.source,
       );
     }
@@ -357,12 +557,13 @@ impl CoreMidiDriver {
     inputs: &mut HashMap<InputName, Input>,
     source_id: SourceId,
     source_name: String,
+    source_properties: &EndpointProperties,
     source: Source,
   ) {
     for input in inputs.values_mut() {
       if input
         .sources
-        .match_index(source_id, source_name.as_str())
+        .match_index(source_id, source_name.as_str(), source_properties)
         .is_some()
       {
         let mut filters = input.filters.load().as_ref().clone();
@@ -384,17 +585,34 @@ impl CoreMidiDriver {
     endpoints.lock().remove_destination(object.into());
   }
 
+  fn connect_error(input: &str, source: SourceId, status: OSStatus) -> InputError {
+    InputError::ConnectSource {
+      input: input.to_string(),
+      source,
+      reason: format!("OSStatus {}", status),
+    }
+  }
+
   fn object_info(object: &coremidi::Object) -> Option<(EndpointId, String)> {
     let maybe_id = object.unique_id().map(|id| id as u64);
     let maybe_name = object.display_name();
     maybe_id.zip(maybe_name)
   }
 
+  fn object_properties(object: &coremidi::Object) -> EndpointProperties {
+    EndpointProperties {
+      manufacturer: object.get_property(&Properties::manufacturer()).ok(),
+      model: object.get_property(&Properties::model()).ok(),
+      driver_owner: object.get_property(&Properties::driver_owner()).ok(),
+    }
+  }
+
   fn initialize_endpoints(endpoints: Arc<Mutex<Endpoints>>) {
     let mut endpoints = endpoints.lock();
     for source in coremidi::Sources {
       if let Some((id, name)) = Self::object_info(&source) {
-        endpoints.add_source(id, name, source);
+        let properties = Self::object_properties(&source);
+        endpoints.add_source(id, name, properties, source);
       }
     }
     for destination in coremidi::Destinations {
@@ -404,3 +622,9 @@ impl CoreMidiDriver {
     }
   }
 }
+
+impl Drop for CoreMidiDriver {
+  fn drop(&mut self) {
+    self.monitoring.store(false, Ordering::Relaxed);
+  }
+}
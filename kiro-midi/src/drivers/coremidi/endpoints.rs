@@ -1,11 +1,14 @@
 use std::collections::hash_map;
 use std::collections::HashMap;
 
-use crate::endpoints::{DestinationId, SourceId};
+use crate::endpoints::{DestinationId, EndpointProperties, SourceId, UmpEndpoint};
+use crate::messages::ump_stream::UmpStream;
 
 pub struct ConnectedSource {
   pub id: SourceId,
   pub name: String,
+  pub properties: EndpointProperties,
+  pub ump_endpoint: Option<UmpEndpoint>,
   pub source: coremidi::Source,
 }
 
@@ -61,10 +64,22 @@ impl Endpoints {
     destinations
   }
 
-  pub fn add_source(&mut self, id: SourceId, name: String, source: coremidi::Source) {
+  pub fn add_source(
+    &mut self,
+    id: SourceId,
+    name: String,
+    properties: EndpointProperties,
+    source: coremidi::Source,
+  ) {
     if let hash_map::Entry::Vacant(connected_source) = self.connected_sources.entry(id) {
       self.disconnected_sources.remove(&id);
-      connected_source.insert(ConnectedSource { id, name, source });
+      connected_source.insert(ConnectedSource {
+        id,
+        name,
+        properties,
+        ump_endpoint: None,
+        source,
+      });
     }
   }
 
@@ -95,6 +110,15 @@ impl Endpoints {
       .map(|connected_source| &connected_source.source)
   }
 
+  pub fn update_ump_endpoint(&mut self, source_id: SourceId, message: &UmpStream) {
+    if let Some(connected_source) = self.connected_sources.get_mut(&source_id) {
+      let mut ump_endpoint = connected_source.ump_endpoint.clone().unwrap_or_default();
+      if ump_endpoint.update(message) {
+        connected_source.ump_endpoint = Some(ump_endpoint);
+      }
+    }
+  }
+
   pub fn add_destination(
     &mut self,
     id: DestinationId,
@@ -0,0 +1,223 @@
+// only the CoreMIDI driver reports errors from its callbacks
+#![cfg_attr(not(target_os = "macos"), allow(dead_code))]
+
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+use ringbuf::{Consumer, Producer, RingBuffer};
+
+use crate::endpoints::SourceId;
+use crate::protocol::{codec, translate};
+
+/// Problems found by a driver while receiving events, which can not be returned to the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputError {
+  /// The data received from a source could not be decoded
+  Decode {
+    input: String,
+    source: SourceId,
+    reason: String,
+  },
+  /// An event was dropped because the input handler could not accept it (ex. full ring buffer)
+  EventDropped { input: String, source: SourceId },
+  /// A source could not be connected to an input
+  ConnectSource {
+    input: String,
+    source: SourceId,
+    reason: String,
+  },
+}
+
+/// Reason why the data received from a source could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DecodeReason {
+  Codec(codec::Error),
+  Translate(translate::Error),
+}
+
+impl Display for DecodeReason {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Codec(err) => err.fmt(f),
+      Self::Translate(err) => err.fmt(f),
+    }
+  }
+}
+
+/// Error found by the callback of an input, as it is queued until it is formatted into an [`InputError`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum InputErrorRecord {
+  Decode {
+    source: SourceId,
+    reason: DecodeReason,
+  },
+  EventDropped {
+    source: SourceId,
+  },
+}
+
+impl InputErrorRecord {
+  fn into_error(self, input: &str) -> InputError {
+    match self {
+      Self::Decode { source, reason } => InputError::Decode {
+        input: input.to_string(),
+        source,
+        reason: reason.to_string(),
+      },
+      Self::EventDropped { source } => InputError::EventDropped {
+        input: input.to_string(),
+        source,
+      },
+    }
+  }
+}
+
+/// Reports the errors of the callback of an input without locking nor allocating,
+/// dropping them while its queue is full.
+pub(crate) struct InputErrorReporter {
+  producer: Producer<InputErrorRecord>,
+}
+
+impl InputErrorReporter {
+  pub fn report(&mut self, record: InputErrorRecord) {
+    self.producer.push(record).ok();
+  }
+}
+
+type ErrorHandler = Box<dyn Fn(InputError) + Send + Sync + 'static>;
+
+#[derive(Default)]
+struct Sinks {
+  handler: Option<ErrorHandler>,
+  inputs: Vec<(Arc<str>, Consumer<InputErrorRecord>)>,
+}
+
+/// Shared place where the driver reports its errors,
+/// forwarding them to the handler set by the application, if any.
+///
+/// The callbacks of the inputs queue them through an [`InputErrorReporter`],
+/// and they are forwarded by [`ErrorSink::drain`] from another thread.
+#[derive(Clone, Default)]
+pub(crate) struct ErrorSink {
+  sinks: Arc<Mutex<Sinks>>,
+}
+
+impl ErrorSink {
+  /// Number of errors that an input can queue until they are drained.
+  pub const INPUT_CAPACITY: usize = 64;
+
+  pub fn set_handler<F>(&self, handler: F)
+  where
+    F: Fn(InputError) + Send + Sync + 'static,
+  {
+    if let Ok(mut sinks) = self.sinks.lock() {
+      sinks.handler = Some(Box::new(handler));
+    }
+  }
+
+  /// Report an error right away, not to be used from the callbacks of the inputs.
+  pub fn report(&self, error: InputError) {
+    if let Ok(sinks) = self.sinks.lock() {
+      if let Some(handler) = sinks.handler.as_ref() {
+        handler(error);
+      }
+    }
+  }
+
+  /// Create the queue for the errors of the callback of an input.
+  pub fn input_reporter(&self, input: Arc<str>) -> InputErrorReporter {
+    let (producer, consumer) = RingBuffer::new(Self::INPUT_CAPACITY).split();
+    if let Ok(mut sinks) = self.sinks.lock() {
+      sinks.inputs.push((input, consumer));
+    }
+    InputErrorReporter { producer }
+  }
+
+  /// Forward the errors queued by the inputs to the handler.
+  pub fn drain(&self) {
+    if let Ok(mut sinks) = self.sinks.lock() {
+      let Sinks { handler, inputs } = &mut *sinks;
+      for (input, consumer) in inputs.iter_mut() {
+        while let Some(record) = consumer.pop() {
+          if let Some(handler) = handler.as_ref() {
+            handler(record.into_error(input));
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn report_without_handler() {
+    ErrorSink::default().report(InputError::EventDropped {
+      input: "input".to_string(),
+      source: 1,
+    });
+  }
+
+  #[test]
+  fn report_to_handler() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_clone = errors.clone();
+    let sink = ErrorSink::default();
+    let callback_sink = sink.clone();
+
+    sink.set_handler(move |error| errors_clone.lock().unwrap().push(error));
+    callback_sink.report(InputError::ConnectSource {
+      input: "input".to_string(),
+      source: 1,
+      reason: "-50".to_string(),
+    });
+
+    assert_eq!(
+      *errors.lock().unwrap(),
+      vec![InputError::ConnectSource {
+        input: "input".to_string(),
+        source: 1,
+        reason: "-50".to_string(),
+      }]
+    );
+  }
+
+  #[test]
+  fn drain_the_errors_of_the_inputs() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_clone = errors.clone();
+    let sink = ErrorSink::default();
+    sink.set_handler(move |error| errors_clone.lock().unwrap().push(error));
+
+    let mut reporter = sink.input_reporter(Arc::from("input"));
+    reporter.report(InputErrorRecord::EventDropped { source: 1 });
+    for _ in 0..ErrorSink::INPUT_CAPACITY {
+      reporter.report(InputErrorRecord::Decode {
+        source: 2,
+        reason: DecodeReason::Codec(codec::Error::Reserved),
+      });
+    }
+    assert!(errors.lock().unwrap().is_empty());
+
+    sink.drain();
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), ErrorSink::INPUT_CAPACITY);
+    assert_eq!(
+      errors[0],
+      InputError::EventDropped {
+        input: "input".to_string(),
+        source: 1,
+      }
+    );
+    assert_eq!(
+      errors[1],
+      InputError::Decode {
+        input: "input".to_string(),
+        source: 2,
+        reason: "Found reserved encoding".to_string(),
+      }
+    );
+  }
+}
@@ -1,16 +1,28 @@
 #[cfg(target_os = "macos")]
 mod coremidi;
+mod error_sink;
+#[cfg(not(target_os = "macos"))]
+mod null;
 
 #[cfg(target_os = "macos")]
 use crate::drivers::coremidi::{CoreMidiDriver, CoreMidiError};
+#[cfg(not(target_os = "macos"))]
+pub use crate::drivers::null::NullDriver;
 
 use thiserror::Error;
 
+pub use error_sink::InputError;
+#[cfg(target_os = "macos")]
+pub(crate) use error_sink::{DecodeReason, ErrorSink, InputErrorRecord, InputErrorReporter};
+
 #[derive(Error, Debug)]
 pub enum Error {
   #[cfg(target_os = "macos")]
   #[error("CoreMidi: {0}")]
   CoreMidi(#[from] CoreMidiError),
+
+  #[error("MIDI is not supported in this platform")]
+  Unsupported,
 }
 
 use enum_dispatch::enum_dispatch;
@@ -18,6 +30,9 @@ use enum_dispatch::enum_dispatch;
 use crate::endpoints::{DestinationInfo, SourceInfo};
 use crate::{InputConfig, InputHandler, InputInfo, SourceMatches};
 
+#[cfg(feature = "async")]
+use crate::InputStream;
+
 #[enum_dispatch(Driver)]
 pub trait DriverSpec {
   fn create_input<H>(&mut self, config: InputConfig, handler: H) -> Result<String, Error>
@@ -28,15 +43,52 @@ pub trait DriverSpec {
   fn inputs(&self) -> Vec<InputInfo>;
   fn get_input_config(&self, name: &str) -> Option<InputConfig>;
   fn set_input_sources(&self, name: &str, sources: SourceMatches) -> Result<(), Error>;
+
+  /// Set the handler for the errors found while receiving events,
+  /// such as decoding errors, dropped events or sources that failed to connect.
+  ///
+  /// It is called from a thread of the driver rather than from the callbacks of the inputs,
+  /// but it should still return quickly.
+  fn set_error_handler<F>(&mut self, handler: F)
+  where
+    F: Fn(InputError) + Send + Sync + 'static;
 }
 
 #[enum_dispatch]
 pub enum Driver {
   #[cfg(target_os = "macos")]
   CoreMidiDriver,
+  #[cfg(not(target_os = "macos"))]
+  NullDriver,
+}
+
+#[cfg(feature = "async")]
+impl Driver {
+  /// Creates an input whose events are consumed asynchronously through a [`Stream`](futures_core::Stream).
+  ///
+  /// The stream is bounded to [`InputStream::DEFAULT_CAPACITY`] events,
+  /// and new events are dropped while it is full.
+  pub fn create_input_stream(&mut self, config: InputConfig) -> Result<InputStream, Error> {
+    self.create_input_stream_with_capacity(config, InputStream::DEFAULT_CAPACITY)
+  }
+
+  pub fn create_input_stream_with_capacity(
+    &mut self,
+    config: InputConfig,
+    capacity: usize,
+  ) -> Result<InputStream, Error> {
+    let (handler, stream) = InputStream::channel(config.name.clone(), capacity);
+    self.create_input(config, handler)?;
+    Ok(stream)
+  }
 }
 
 #[cfg(target_os = "macos")]
 pub fn create(name: &str) -> Result<Driver, Error> {
   CoreMidiDriver::new(name).map(Into::into)
 }
+
+#[cfg(not(target_os = "macos"))]
+pub fn create(_name: &str) -> Result<Driver, Error> {
+  Ok(NullDriver.into())
+}
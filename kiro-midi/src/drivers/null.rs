@@ -0,0 +1,58 @@
+use crate::drivers::{DriverSpec, Error, InputError};
+use crate::endpoints::{DestinationInfo, SourceInfo};
+use crate::{InputConfig, InputHandler, InputInfo, SourceMatches};
+
+/// Driver for the platforms without MIDI support, such as the web.
+///
+/// It has no sources nor destinations, and it fails to create inputs.
+#[derive(Debug, Default)]
+pub struct NullDriver;
+
+impl DriverSpec for NullDriver {
+  fn create_input<H>(&mut self, _config: InputConfig, _handler: H) -> Result<String, Error>
+  where
+    H: Into<InputHandler>,
+  {
+    Err(Error::Unsupported)
+  }
+
+  fn sources(&self) -> Vec<SourceInfo> {
+    Vec::new()
+  }
+
+  fn destinations(&self) -> Vec<DestinationInfo> {
+    Vec::new()
+  }
+
+  fn inputs(&self) -> Vec<InputInfo> {
+    Vec::new()
+  }
+
+  fn get_input_config(&self, _name: &str) -> Option<InputConfig> {
+    None
+  }
+
+  fn set_input_sources(&self, _name: &str, _sources: SourceMatches) -> Result<(), Error> {
+    Err(Error::Unsupported)
+  }
+
+  fn set_error_handler<F>(&mut self, _handler: F)
+  where
+    F: Fn(InputError) + Send + Sync + 'static,
+  {
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fail_to_create_inputs() {
+    let mut driver = NullDriver;
+    let result = driver.create_input(InputConfig::new("input"), |_event| {});
+    assert!(matches!(result, Err(Error::Unsupported)));
+    assert!(driver.sources().is_empty());
+    assert!(driver.inputs().is_empty());
+  }
+}
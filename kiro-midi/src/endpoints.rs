@@ -1,19 +1,95 @@
+use crate::messages::ump_stream::{EndpointInfo, Form, FunctionBlockInfo, UmpStream};
+
 pub type EndpointId = u64;
 pub type SourceId = EndpointId;
 pub type DestinationId = EndpointId;
 
+/// Properties describing the device behind an endpoint, when available.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointProperties {
+  pub manufacturer: Option<String>,
+  pub model: Option<String>,
+  pub driver_owner: Option<String>,
+}
+
+/// Identity and capabilities announced by a MIDI 2.0 endpoint through UMP Stream messages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UmpEndpoint {
+  pub info: Option<EndpointInfo>,
+  pub name: Option<String>,
+  pub function_blocks: Vec<FunctionBlockInfo>,
+  partial_name: Vec<u8>,
+}
+
+impl UmpEndpoint {
+  /// Update it with a notification received from the endpoint.
+  /// Returns whether the message was a notification relevant for the endpoint.
+  pub fn update(&mut self, message: &UmpStream) -> bool {
+    match message {
+      UmpStream::EndpointInfo(info) => {
+        self.info = Some(*info);
+        true
+      }
+      UmpStream::EndpointName { form, name } => {
+        if matches!(form, Form::Complete | Form::Start) {
+          self.partial_name.clear();
+        }
+        self.partial_name.extend_from_slice(name.as_slice());
+        if matches!(form, Form::Complete | Form::End) {
+          self.name = Some(String::from_utf8_lossy(&self.partial_name).into_owned());
+          self.partial_name.clear();
+        }
+        true
+      }
+      UmpStream::FunctionBlockInfo(block_info) => {
+        match self
+          .function_blocks
+          .iter_mut()
+          .find(|current| current.block == block_info.block)
+        {
+          Some(current) => *current = *block_info,
+          None => {
+            self.function_blocks.push(*block_info);
+            self.function_blocks.sort_by_key(|current| current.block);
+          }
+        }
+        true
+      }
+      UmpStream::EndpointDiscovery { .. } | UmpStream::FunctionBlockDiscovery { .. } => false,
+    }
+  }
+
+  pub fn supports_midi2(&self) -> bool {
+    matches!(self.info, Some(info) if info.midi2_protocol)
+  }
+
+  pub fn supports_midi1(&self) -> bool {
+    matches!(self.info, Some(info) if info.midi1_protocol)
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct SourceInfo {
   pub id: SourceId,
   pub name: String,
+  pub properties: EndpointProperties,
+  pub ump_endpoint: Option<UmpEndpoint>,
   pub connected_inputs: Vec<String>,
 }
 
 impl SourceInfo {
-  pub fn new(id: SourceId, name: String, connected_inputs: Vec<String>) -> Self {
+  pub fn new(
+    id: SourceId,
+    name: String,
+    properties: EndpointProperties,
+    ump_endpoint: Option<UmpEndpoint>,
+    connected_inputs: Vec<String>,
+  ) -> Self {
     Self {
       id,
       name,
+      properties,
+      ump_endpoint,
       connected_inputs,
     }
   }
@@ -30,3 +106,62 @@ impl DestinationInfo {
     Self { id, name }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::messages::ump_stream::{EndpointNamePayload, FunctionBlockDirection, UmpVersion};
+
+  fn function_block(block: u8, active: bool) -> FunctionBlockInfo {
+    FunctionBlockInfo {
+      active,
+      block,
+      ui_hint: 0,
+      midi1: 0,
+      direction: FunctionBlockDirection::Bidirectional,
+      first_group: block,
+      num_groups: 1,
+      midi_ci_version: 0,
+      max_sysex8_streams: 0,
+    }
+  }
+
+  #[test]
+  fn ump_endpoint_update() {
+    let mut endpoint = UmpEndpoint::default();
+    assert!(!endpoint.supports_midi2());
+
+    assert!(endpoint.update(&UmpStream::EndpointInfo(EndpointInfo {
+      ump_version: UmpVersion::V1_1,
+      static_function_blocks: true,
+      num_function_blocks: 2,
+      midi2_protocol: true,
+      midi1_protocol: false,
+      rx_jr_timestamp: false,
+      tx_jr_timestamp: false,
+    })));
+    assert!(endpoint.supports_midi2());
+    assert!(!endpoint.supports_midi1());
+
+    endpoint.update(&UmpStream::EndpointName {
+      form: Form::Start,
+      name: EndpointNamePayload::new(b"Kiro Studio Sy").unwrap(),
+    });
+    assert_eq!(endpoint.name, None);
+    endpoint.update(&UmpStream::EndpointName {
+      form: Form::End,
+      name: EndpointNamePayload::new(b"nth").unwrap(),
+    });
+    assert_eq!(endpoint.name.as_deref(), Some("Kiro Studio Synth"));
+
+    endpoint.update(&UmpStream::FunctionBlockInfo(function_block(1, true)));
+    endpoint.update(&UmpStream::FunctionBlockInfo(function_block(0, true)));
+    endpoint.update(&UmpStream::FunctionBlockInfo(function_block(1, false)));
+    assert_eq!(
+      endpoint.function_blocks,
+      vec![function_block(0, true), function_block(1, false)]
+    );
+
+    assert!(!endpoint.update(&UmpStream::discover_endpoint()));
+  }
+}
@@ -9,6 +9,8 @@ pub struct Event {
   pub timestamp: TimestampNanos,
   pub endpoint: EndpointId,
   pub message: Message,
+  /// Jitter Reduction timestamp sent along with the message, if any
+  pub jr_timestamp: Option<JrTimestamp>,
 }
 
 impl std::fmt::Debug for Event {
@@ -17,6 +19,73 @@ impl std::fmt::Debug for Event {
       f,
       "[{:08x}] {:016} {:?}",
       self.endpoint, self.timestamp, self.message
-    )
+    )?;
+    if let Some(jr_timestamp) = self.jr_timestamp {
+      write!(f, " {:?}", jr_timestamp)?;
+    }
+    Ok(())
+  }
+}
+
+/// Jitter Reduction timestamp in units of 1/31250 seconds,
+/// along with the last JR Clock received from the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JrTimestamp {
+  pub timestamp: u16,
+  pub sender_clock: Option<u16>,
+}
+
+impl JrTimestamp {
+  pub const TICK_NANOS: u64 = 32_000;
+
+  pub fn new(timestamp: u16, sender_clock: Option<u16>) -> Self {
+    Self {
+      timestamp,
+      sender_clock,
+    }
+  }
+
+  /// Time of the sender clock in nanoseconds (it wraps around every ~2.1 seconds).
+  pub fn nanos(&self) -> TimestampNanos {
+    self.timestamp as TimestampNanos * Self::TICK_NANOS
+  }
+
+  /// Offset in nanoseconds between the timestamp and the last JR Clock from the sender,
+  /// taking into account the wrap around of the 16 bits clock.
+  ///
+  /// Adding it to the time when the JR Clock was received gives the time of the message
+  /// free from the jitter introduced by the transport.
+  pub fn clock_offset_nanos(&self) -> Option<i64> {
+    self.sender_clock.map(|sender_clock| {
+      let ticks = self.timestamp.wrapping_sub(sender_clock) as i16;
+      ticks as i64 * Self::TICK_NANOS as i64
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn jr_timestamp_nanos() {
+    assert_eq!(JrTimestamp::new(31250, None).nanos(), 1_000_000_000);
+  }
+
+  #[test]
+  fn jr_timestamp_clock_offset() {
+    assert_eq!(JrTimestamp::new(100, None).clock_offset_nanos(), None);
+    assert_eq!(
+      JrTimestamp::new(110, Some(100)).clock_offset_nanos(),
+      Some(320_000)
+    );
+    assert_eq!(
+      JrTimestamp::new(90, Some(100)).clock_offset_nanos(),
+      Some(-320_000)
+    );
+    assert_eq!(
+      JrTimestamp::new(2, Some(0xfffe)).clock_offset_nanos(),
+      Some(128_000)
+    );
   }
 }
@@ -1,10 +1,16 @@
 use crate::filter::Filter;
+use crate::protocol::translate::Translator;
 use crate::source_match::{SourceMatch, SourceMatches};
 
 #[derive(Debug, Clone)]
 pub struct InputConfig {
   pub name: String,
   pub sources: SourceMatches,
+  /// Combine the MSB/LSB pairs of controllers 1 to 31 into high resolution Control Changes,
+  /// which requires receiving the sources as MIDI 1.0 and translating them
+  pub high_resolution_cc: bool,
+  /// Maximum number of UMP words queued while translating the MIDI 1.0 messages of a source
+  pub ump_capacity: Option<usize>,
 }
 
 impl InputConfig {
@@ -15,6 +21,8 @@ impl InputConfig {
     Self {
       name: name.into(),
       sources: SourceMatches::default(),
+      high_resolution_cc: false,
+      ump_capacity: None,
     }
   }
 
@@ -26,6 +34,46 @@ impl InputConfig {
     self
   }
 
+  #[must_use]
+  pub fn with_high_resolution_cc(mut self, enabled: bool) -> Self {
+    self.high_resolution_cc = enabled;
+    self
+  }
+
+  #[must_use]
+  pub fn with_ump_capacity(mut self, capacity: usize) -> Self {
+    self.ump_capacity = Some(capacity);
+    self
+  }
+
+  /// Whether the sources are received as MIDI 1.0 and translated with [`InputConfig::translator`].
+  // only the CoreMIDI driver receives MIDI 1.0 so far
+  #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+  pub(crate) fn translates_midi1(&self) -> bool {
+    self.high_resolution_cc
+  }
+
+  /// Translator for the MIDI 1.0 messages of a group of a source, following this configuration.
+  #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+  pub(crate) fn translator(&self, group: u8) -> Translator {
+    let translator = Translator::new(group).with_high_resolution_cc(self.high_resolution_cc);
+    match self.ump_capacity {
+      Some(capacity) => translator.with_ump_capacity(capacity),
+      None => translator,
+    }
+  }
+
+  /// Translators for the 16 groups of a source, indexed by group,
+  /// or none when the sources are not translated.
+  #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+  pub(crate) fn translators(&self) -> Vec<Translator> {
+    if self.translates_midi1() {
+      (0..16).map(|group| self.translator(group)).collect()
+    } else {
+      Vec::new()
+    }
+  }
+
   pub fn with_all_sources(mut self, filter: Filter) -> Self {
     self
       .sources
@@ -33,3 +81,26 @@ impl InputConfig {
     self
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn translator_follows_the_config() {
+    let config = InputConfig::new("input");
+    assert!(!config.translates_midi1());
+
+    let config = config.with_high_resolution_cc(true).with_ump_capacity(1024);
+    assert!(config.translates_midi1());
+    let mut translator = config.translator(0);
+    let filter = Filter::new();
+    for word in [0x20b9077f, 0x20b9277f] {
+      assert!(translator.push_midi1_ump(word, &filter).unwrap());
+    }
+    assert_eq!(
+      translator.drain().collect::<Vec<u32>>(),
+      vec![0x40b90700, 0xffffffff, 0x40b90700, 0xffffffff]
+    );
+  }
+}
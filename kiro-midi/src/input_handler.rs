@@ -9,13 +9,15 @@ pub enum InputHandler {
 }
 
 impl InputHandler {
-  pub(crate) fn call(&mut self, event: Event) {
+  /// Deliver the event, returning whether it was accepted.
+  pub(crate) fn call(&mut self, event: Event) -> bool {
     match self {
-      InputHandler::Callback(ref mut callback) => (callback)(event),
-      InputHandler::RingBuffer(ref mut producer) => {
-        producer.push(event).ok();
+      InputHandler::Callback(ref mut callback) => {
+        (callback)(event);
+        true
       }
-    };
+      InputHandler::RingBuffer(ref mut producer) => producer.push(event).is_ok(),
+    }
   }
 }
 
@@ -70,6 +72,7 @@ mod tests {
         group: 8,
         mtype: MessageType::Utility(Utility::Noop),
       },
+      jr_timestamp: None,
     });
 
     assert_eq!(state.load(Ordering::Relaxed), 8);
@@ -85,12 +88,15 @@ mod tests {
         group: 8,
         mtype: MessageType::Utility(Utility::Noop),
       },
+      jr_timestamp: None,
     };
 
     let mut handler = InputHandler::from(producer);
 
-    handler.call(event.clone());
+    assert!(handler.call(event.clone()));
+    assert!(!handler.call(event.clone()));
 
     assert_eq!(consumer.pop(), Some(event));
+    assert_eq!(consumer.pop(), None);
   }
 }
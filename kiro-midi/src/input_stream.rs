@@ -0,0 +1,93 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_channel::mpsc;
+use futures_core::Stream;
+
+use crate::event::Event;
+use crate::input_handler::InputHandler;
+
+/// Stream of events received by a driver input.
+///
+/// It is backed by a bounded channel, so the driver never blocks when the consumer
+/// is not keeping up with the incoming events, they are dropped instead.
+pub struct InputStream {
+  name: String,
+  receiver: mpsc::Receiver<Event>,
+}
+
+impl InputStream {
+  pub const DEFAULT_CAPACITY: usize = 1024;
+
+  pub(crate) fn channel(name: String, capacity: usize) -> (InputHandler, Self) {
+    let (mut sender, receiver) = mpsc::channel(capacity);
+    let handler = InputHandler::from(move |event: Event| {
+      sender.try_send(event).ok();
+    });
+    (handler, Self { name, receiver })
+  }
+
+  pub fn name(&self) -> &str {
+    self.name.as_str()
+  }
+}
+
+impl Stream for InputStream {
+  type Item = Event;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    Pin::new(&mut self.receiver).poll_next(cx)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.receiver.size_hint()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_executor::block_on_stream;
+
+  use crate::messages::Message;
+  use crate::protocol::messages::utility::Utility;
+  use crate::protocol::messages::MessageType;
+
+  use super::*;
+
+  fn event(group: u8) -> Event {
+    Event {
+      timestamp: 0,
+      endpoint: 0,
+      message: Message {
+        group,
+        mtype: MessageType::Utility(Utility::Noop),
+      },
+      jr_timestamp: None,
+    }
+  }
+
+  #[test]
+  fn channel() {
+    let (mut handler, stream) = InputStream::channel("input".to_string(), 4);
+    assert_eq!(stream.name(), "input");
+
+    handler.call(event(1));
+    handler.call(event(2));
+    drop(handler);
+
+    let events = block_on_stream(stream).collect::<Vec<Event>>();
+    assert_eq!(events, vec![event(1), event(2)]);
+  }
+
+  #[test]
+  fn channel_drops_when_full() {
+    let (mut handler, stream) = InputStream::channel("input".to_string(), 0);
+
+    handler.call(event(1));
+    handler.call(event(2));
+    drop(handler);
+
+    let events = block_on_stream(stream).collect::<Vec<Event>>();
+    assert_eq!(events, vec![event(1)]);
+  }
+}
@@ -1,3 +1,4 @@
+pub mod capture;
 pub mod drivers;
 pub mod endpoints;
 pub(crate) mod event;
@@ -5,15 +6,22 @@ pub(crate) mod filter;
 pub(crate) mod input_config;
 pub(crate) mod input_handler;
 pub(crate) mod input_info;
+#[cfg(feature = "async")]
+pub(crate) mod input_stream;
 pub mod note_freq;
+pub mod note_name;
 pub(crate) mod protocol;
+pub mod scala;
 pub(crate) mod source_match;
 
 pub use drivers::{Driver, DriverSpec};
-pub use event::{Event, TimestampNanos};
+pub use event::{Event, JrTimestamp, TimestampNanos};
 pub use filter::Filter;
 pub use input_config::InputConfig;
 pub use input_handler::InputHandler;
 pub use input_info::InputInfo;
+#[cfg(feature = "async")]
+pub use input_stream::InputStream;
+pub use note_name::NoteName;
 pub use protocol::messages;
 pub use source_match::{SourceMatch, SourceMatches};
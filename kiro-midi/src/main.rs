@@ -1,62 +1,263 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{ArgEnum, Parser, Subcommand};
+#[cfg(target_os = "macos")]
 use core_foundation::runloop::CFRunLoop;
-use kiro_midi::{self as midi, drivers::DriverSpec};
+use kiro_midi::capture::{self, CaptureReader, CaptureWriter, Pacing};
+use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+use kiro_midi::messages::MessageType;
+use kiro_midi::{self as midi, drivers::DriverSpec, Filter, InputConfig, NoteName, SourceMatch};
 
-fn main() {
-  let mut driver = midi::drivers::create("test").unwrap();
+/// Monitor the MIDI events received from the connected sources
+#[derive(Debug, Parser)]
+#[clap(name = "midimon", version)]
+struct Args {
+  /// Regex for the names of the sources to monitor (all of them by default)
+  #[clap(short, long = "source", value_name = "REGEX")]
+  sources: Vec<String>,
 
-  let input_config1 = midi::InputConfig::new("novation").with_source(
-    midi::SourceMatch::regex("Novation SL MkIII.*").unwrap(),
-    midi::Filter::default(),
-  );
+  /// Groups to monitor, from 1 to 16 (all of them by default)
+  #[clap(short, long = "group", value_name = "GROUP")]
+  groups: Vec<u8>,
 
-  let input_config2 = midi::InputConfig::new("arturia").with_source(
-    midi::SourceMatch::regex("Midi.*").unwrap(),
-    midi::Filter::default().with_channels(1, &[1, 2]),
-  );
+  /// Channels to monitor, from 1 to 16 (all of them by default)
+  #[clap(short, long = "channel", value_name = "CHANNEL")]
+  channels: Vec<u8>,
 
-  driver
-    .create_input(input_config1, |event| println!(">> {:?}", event))
-    .unwrap();
+  /// How to print the messages
+  #[clap(short, long, arg_enum, default_value = "decoded")]
+  format: Format,
 
-  driver
-    .create_input(input_config2, |event| println!(">> {:?}", event))
-    .unwrap();
+  /// How to print the timestamps
+  #[clap(short, long, arg_enum, default_value = "none")]
+  timestamp: Timestamp,
 
-  print_endpoints(&driver);
+  /// List the sources and destinations, and exit
+  #[clap(short, long)]
+  list: bool,
 
-  let _join = std::thread::spawn(move || loop {
-    loop {
-      let mut input_line = String::new();
-      std::io::stdin()
-        .read_line(&mut input_line)
-        .expect("Failed to read line");
-
-      print_endpoints(&driver);
-
-      if let Some(mut arturia_config) = driver.get_input_config("arturia") {
-        arturia_config.sources.add_source(
-          midi::SourceMatch::regex("Midi.*").unwrap(),
-          midi::Filter::default().with_channels(1, &[1]),
-        );
-
-        driver
-          .set_input_sources(
-            "arturia",
-            midi::SourceMatches::default().with_source(
-              midi::SourceMatch::regex("IAC.*").unwrap(),
-              midi::Filter::default().with_channels(1, &[1]),
-            ),
-          )
-          .ok();
-      }
+  #[clap(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+  /// Record the events from the selected sources into a capture file
+  Capture {
+    /// Path of the capture file
+    file: PathBuf,
+  },
+  /// Print the events from a capture file
+  Replay {
+    /// Path of the capture file
+    file: PathBuf,
+
+    /// Wait between events as they were received, with a speed factor
+    #[clap(short, long, value_name = "SPEED")]
+    realtime: Option<f64>,
+  },
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum Format {
+  /// Decoded messages, with note names
+  Decoded,
+  /// Decoded messages with the values in hexadecimal
+  Hex,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum Timestamp {
+  None,
+  /// Nanoseconds as reported by the driver
+  Absolute,
+  /// Nanoseconds since the previous event
+  Delta,
+}
+
+fn main() -> anyhow::Result<()> {
+  let args = Args::parse();
+
+  if let Some(Command::Replay { file, realtime }) = &args.command {
+    return replay(
+      file,
+      realtime.map(|speed| Pacing::Realtime { speed }),
+      &args,
+    );
+  }
+
+  let mut driver = midi::drivers::create("midimon")?;
+
+  if args.list {
+    print_endpoints(&driver);
+    return Ok(());
+  }
+
+  let filter = create_filter(&args);
+  let mut input_config = InputConfig::new("midimon");
+  if args.sources.is_empty() {
+    input_config = input_config.with_all_sources(filter);
+  } else {
+    for source in args.sources.iter() {
+      input_config = input_config.with_source(SourceMatch::regex(source)?, filter);
     }
+  }
+
+  if let Some(Command::Capture { file }) = &args.command {
+    start_capture(&mut driver, input_config, file)?;
+  } else {
+    let format = args.format;
+    let timestamp = args.timestamp;
+    let last_timestamp = Arc::new(AtomicU64::new(0));
+    driver.create_input(input_config, move |event: midi::Event| {
+      let last = last_timestamp.swap(event.timestamp, Ordering::Relaxed);
+      print_event(&event, format, timestamp, last);
+    })?;
+  }
+
+  print_endpoints(&driver);
+
+  std::thread::spawn(move || loop {
+    let mut input_line = String::new();
+    std::io::stdin()
+      .read_line(&mut input_line)
+      .expect("Failed to read line");
+
+    print_endpoints(&driver);
   });
 
   println!("=== Press Ctrl-C to stop ===");
   println!("=== Press Enter to list endpoints ===");
+
+  run_until_stopped();
+
+  Ok(())
+}
+
+// This is required in MacOS to be able to handle notifications whenever devices are plugged/unplugged
+#[cfg(target_os = "macos")]
+fn run_until_stopped() {
   CFRunLoop::run_current();
 }
 
+// Elsewhere there are no notifications, so just wait for Ctrl-C
+#[cfg(not(target_os = "macos"))]
+fn run_until_stopped() {
+  loop {
+    std::thread::park();
+  }
+}
+
+fn start_capture(
+  driver: &mut midi::drivers::Driver,
+  input_config: InputConfig,
+  path: &PathBuf,
+) -> anyhow::Result<()> {
+  let mut writer = CaptureWriter::new(BufWriter::new(File::create(path)?))?;
+  let (producer, mut consumer) = ringbuf::RingBuffer::<midi::Event>::new(4096).split();
+  driver.create_input(input_config, producer)?;
+
+  // The events are written from a different thread to keep the driver callback free from IO
+  std::thread::spawn(move || loop {
+    let mut count = 0;
+    while let Some(event) = consumer.pop() {
+      if let Err(err) = writer.write_event(&event) {
+        eprintln!("Failed to write the event: {}", err);
+      }
+      count += 1;
+    }
+    if count > 0 {
+      writer.flush().ok();
+    }
+    std::thread::sleep(Duration::from_millis(10));
+  });
+
+  println!("=== Capturing into {} ===", path.display());
+  Ok(())
+}
+
+fn replay(path: &PathBuf, pacing: Option<Pacing>, args: &Args) -> anyhow::Result<()> {
+  let reader = CaptureReader::new(BufReader::new(File::open(path)?))?;
+  let filter = create_filter(args);
+  let format = args.format;
+  let timestamp = args.timestamp;
+  let mut last_timestamp = 0;
+  let count = capture::replay(
+    reader,
+    move |event: midi::Event| {
+      if matches_filter(&filter, &event) {
+        print_event(&event, format, timestamp, last_timestamp);
+      }
+      last_timestamp = event.timestamp;
+    },
+    pacing.unwrap_or(Pacing::Immediate),
+  )?;
+  println!("=== Replayed {} events ===", count);
+  Ok(())
+}
+
+fn matches_filter(filter: &Filter, event: &midi::Event) -> bool {
+  let group = event.message.group;
+  match event.message.mtype {
+    MessageType::ChannelVoice(ChannelVoice { channel, .. }) => filter.channel(group, channel),
+    MessageType::UmpStream(_) => true,
+    _ => filter.group(group),
+  }
+}
+
+fn create_filter(args: &Args) -> Filter {
+  let mut filter = Filter::default();
+  if !args.groups.is_empty() {
+    filter = filter.with_groups(&args.groups);
+  }
+  if !args.channels.is_empty() {
+    for group in 1..=16 {
+      filter = filter.with_channels(group, &args.channels);
+    }
+  }
+  filter
+}
+
+fn print_event(event: &midi::Event, format: Format, timestamp: Timestamp, last_timestamp: u64) {
+  let timestamp = match timestamp {
+    Timestamp::None => String::new(),
+    Timestamp::Absolute => format!("{:016} ", event.timestamp),
+    Timestamp::Delta => {
+      let delta = event.timestamp.saturating_sub(last_timestamp);
+      format!("{:+12} ", if last_timestamp == 0 { 0 } else { delta })
+    }
+  };
+
+  let message = match format {
+    Format::Decoded => format!("{:?}", event.message),
+    Format::Hex => format!("{:x?}", event.message),
+  };
+
+  let note_name = match (format, event.message.mtype) {
+    (
+      Format::Decoded,
+      MessageType::ChannelVoice(ChannelVoice {
+        message:
+          ChannelVoiceMessage::NoteOn { note, .. } | ChannelVoiceMessage::NoteOff { note, .. },
+        ..
+      }),
+    ) => NoteName::new(note)
+      .map(|name| format!(" [{}]", name))
+      .unwrap_or_default(),
+    _ => String::new(),
+  };
+
+  println!(
+    "{}[{:08x}] {}{}",
+    timestamp, event.endpoint, message, note_name
+  );
+}
+
 fn print_endpoints(driver: &midi::drivers::Driver) {
   println!("===================================================================================");
   println!("Sources:");
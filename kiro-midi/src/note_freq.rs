@@ -1,6 +1,10 @@
 #![allow(unused_attributes)]
 #![allow(clippy::all)]
 
+use std::path::Path;
+
+use crate::scala::{KeyboardMapping, ScalaError, Scale};
+
 pub const KEY_FREQ: [f32; 128] = [
   8.176, 8.662, 9.177, 9.723, 10.301, 10.913, 11.562, 12.250, 12.978, 13.750, 14.568, 15.434,
   16.352, 17.324, 18.354, 19.445, 20.602, 21.827, 23.125, 24.5, 25.957, 27.500, 29.135, 30.868,
@@ -16,3 +20,193 @@ pub const KEY_FREQ: [f32; 128] = [
   5919.911, 6271.927, 6644.875, 7040.000, 7458.620, 7902.133, 8372.018, 8869.844, 9397.273,
   9956.063, 10548.082, 11175.303, 11839.822, 12543.854,
 ];
+
+/// Tuning used to convert MIDI notes into frequencies.
+///
+/// It is 12-EDO relative to a reference frequency for A4 (note 69),
+/// with optional offsets in cents for each of the 12 pitch classes (starting from C),
+/// or any other tuning described by a [`Scale`] and a [`KeyboardMapping`] (see [`Tuning::scala`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+  reference_freq: f32,
+  offsets: [f32; 12],
+  scala: Option<(Scale, KeyboardMapping)>,
+  freqs: [f32; 128],
+}
+
+impl Tuning {
+  pub const A4_NOTE: u8 = 69;
+  pub const DEFAULT_REFERENCE_FREQ: f32 = 440.0;
+
+  pub fn new(reference_freq: f32, offsets: [f32; 12]) -> Self {
+    Self::build(reference_freq, offsets, None)
+  }
+
+  /// 12-EDO tuning with the given frequency for A4.
+  pub fn equal_temperament(reference_freq: f32) -> Self {
+    Self::new(reference_freq, [0.0; 12])
+  }
+
+  /// Tuning from a scale and a keyboard mapping as found in Scala files,
+  /// where the reference frequency is the one of the reference key of the mapping.
+  ///
+  /// The keys left out of the mapping are tuned in 12-EDO from the reference key.
+  pub fn scala(scale: Scale, mapping: KeyboardMapping) -> Self {
+    Self::build(mapping.reference_freq, [0.0; 12], Some((scale, mapping)))
+  }
+
+  /// Load a tuning from a `.scl` file, and optionally a `.kbm` file,
+  /// mapping the keys linearly with A4 at the default reference frequency when missing.
+  pub fn load_scala<P: AsRef<Path>>(
+    scale_path: P,
+    mapping_path: Option<P>,
+  ) -> Result<Self, ScalaError> {
+    let scale = Scale::load(scale_path)?;
+    let mapping = match mapping_path {
+      Some(path) => KeyboardMapping::load(path)?,
+      None => KeyboardMapping::linear(Self::DEFAULT_REFERENCE_FREQ),
+    };
+    Ok(Self::scala(scale, mapping))
+  }
+
+  fn build(
+    reference_freq: f32,
+    offsets: [f32; 12],
+    scala: Option<(Scale, KeyboardMapping)>,
+  ) -> Self {
+    let reference_note = match &scala {
+      Some((_, mapping)) => mapping.reference_key,
+      None => Self::A4_NOTE,
+    };
+    let mut freqs = [0.0; 128];
+    for (note, freq) in freqs.iter_mut().enumerate() {
+      let equal_cents = (note as f32 - reference_note as f32) * 100.0;
+      let cents = match &scala {
+        Some((scale, mapping)) => match Self::scala_cents(scale, mapping, note as u8) {
+          Some(cents) => cents,
+          None => equal_cents,
+        },
+        None => equal_cents,
+      };
+      *freq = reference_freq * ((cents + offsets[note % 12]) / 1200.0).exp2();
+    }
+    Self {
+      reference_freq,
+      offsets,
+      scala,
+      freqs,
+    }
+  }
+
+  /// Cents of a key from the reference one of the mapping, or `None` when it is not mapped.
+  fn scala_cents(scale: &Scale, mapping: &KeyboardMapping, note: u8) -> Option<f32> {
+    let degree = mapping.degree(note, scale)?;
+    // an unmapped reference key is taken as the first degree of the scale
+    let reference = mapping.degree(mapping.reference_key, scale).unwrap_or(0);
+    let cents = scale.degree_cents(degree) - scale.degree_cents(reference);
+    Some(cents as f32)
+  }
+
+  #[must_use]
+  pub fn with_reference_freq(self, reference_freq: f32) -> Self {
+    let scala = self.scala.map(|(scale, mut mapping)| {
+      mapping.reference_freq = reference_freq;
+      (scale, mapping)
+    });
+    Self::build(reference_freq, self.offsets, scala)
+  }
+
+  #[must_use]
+  pub fn with_offsets(self, offsets: [f32; 12]) -> Self {
+    Self::build(self.reference_freq, offsets, self.scala)
+  }
+
+  pub fn reference_freq(&self) -> f32 {
+    self.reference_freq
+  }
+
+  pub fn offsets(&self) -> &[f32; 12] {
+    &self.offsets
+  }
+
+  pub fn scale(&self) -> Option<&Scale> {
+    self.scala.as_ref().map(|(scale, _)| scale)
+  }
+
+  pub fn keyboard_mapping(&self) -> Option<&KeyboardMapping> {
+    self.scala.as_ref().map(|(_, mapping)| mapping)
+  }
+
+  /// Frequency for a MIDI note (notes above 127 are clamped).
+  pub fn freq(&self, note: u8) -> f32 {
+    self.freqs[note.min(127) as usize]
+  }
+}
+
+impl Default for Tuning {
+  fn default() -> Self {
+    Self::equal_temperament(Self::DEFAULT_REFERENCE_FREQ)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+  }
+
+  #[test]
+  fn default_matches_key_freq() {
+    let tuning = Tuning::default();
+    for note in 0..128u8 {
+      assert_close(tuning.freq(note), KEY_FREQ[note as usize]);
+    }
+  }
+
+  #[test]
+  fn reference_freq() {
+    let tuning = Tuning::equal_temperament(432.0);
+    assert_close(tuning.freq(69), 432.0);
+    assert_close(tuning.freq(81), 864.0);
+  }
+
+  #[test]
+  fn offsets() {
+    let mut offsets = [0.0; 12];
+    offsets[9] = 100.0;
+    let tuning = Tuning::default().with_offsets(offsets);
+    assert_close(tuning.freq(69), KEY_FREQ[70]);
+    assert_close(tuning.freq(60), KEY_FREQ[60]);
+  }
+
+  #[test]
+  fn scala_equal_division() {
+    let tuning = Tuning::scala(Scale::equal_division(12), KeyboardMapping::default());
+    for note in 0..128u8 {
+      assert_close(tuning.freq(note), KEY_FREQ[note as usize]);
+    }
+
+    let tuning = Tuning::scala(Scale::equal_division(24), KeyboardMapping::default());
+    assert_close(tuning.freq(69), 440.0);
+    assert_close(tuning.freq(70), KEY_FREQ[69] * (1.0f32 / 24.0).exp2());
+    assert_close(tuning.freq(93), 880.0);
+    let tuning = tuning.with_reference_freq(432.0);
+    assert_close(tuning.freq(69), 432.0);
+    assert_eq!(tuning.keyboard_mapping().unwrap().reference_freq, 432.0);
+  }
+
+  #[test]
+  fn scala_unmapped_keys() {
+    let mapping = KeyboardMapping {
+      first_key: 60,
+      last_key: 72,
+      ..KeyboardMapping::default()
+    };
+    let tuning = Tuning::scala(Scale::equal_division(19), mapping);
+    assert_close(tuning.freq(59), KEY_FREQ[59]);
+    assert_close(tuning.freq(69), 440.0);
+    assert_close(tuning.freq(72), 440.0 * (3.0f32 / 19.0).exp2());
+  }
+}
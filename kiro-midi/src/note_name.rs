@@ -0,0 +1,190 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseNoteNameError {
+  #[error("Invalid note letter: {0:?}")]
+  InvalidLetter(Option<char>),
+
+  #[error("Invalid octave: {0:?}")]
+  InvalidOctave(String),
+
+  #[error("Note out of the MIDI range: {0}")]
+  OutOfRange(i32),
+}
+
+/// MIDI note with a human friendly representation,
+/// where middle C (note 60) is `C4` and the lowest note is `C-1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NoteName(u8);
+
+impl NoteName {
+  const NAMES: [&'static str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+  ];
+
+  pub fn new(note: u8) -> Option<Self> {
+    if note < 128 {
+      Some(Self(note))
+    } else {
+      None
+    }
+  }
+
+  pub fn note(&self) -> u8 {
+    self.0
+  }
+
+  /// Pitch class from 0 (C) to 11 (B)
+  pub fn pitch_class(&self) -> u8 {
+    self.0 % 12
+  }
+
+  pub fn octave(&self) -> i8 {
+    (self.0 / 12) as i8 - 1
+  }
+}
+
+impl From<NoteName> for u8 {
+  fn from(name: NoteName) -> Self {
+    name.0
+  }
+}
+
+impl TryFrom<u8> for NoteName {
+  type Error = ParseNoteNameError;
+
+  fn try_from(note: u8) -> Result<Self, Self::Error> {
+    Self::new(note).ok_or(ParseNoteNameError::OutOfRange(note as i32))
+  }
+}
+
+impl Display for NoteName {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}{}",
+      Self::NAMES[self.pitch_class() as usize],
+      self.octave()
+    )
+  }
+}
+
+impl FromStr for NoteName {
+  type Err = ParseNoteNameError;
+
+  /// Parses names like `C4`, `c#4`, `Eb-1` or `F##3`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut chars = s.trim().chars().peekable();
+
+    let letter = chars.next();
+    let pitch_class: i32 = match letter.map(|letter| letter.to_ascii_uppercase()) {
+      Some('C') => 0,
+      Some('D') => 2,
+      Some('E') => 4,
+      Some('F') => 5,
+      Some('G') => 7,
+      Some('A') => 9,
+      Some('B') => 11,
+      _ => return Err(ParseNoteNameError::InvalidLetter(letter)),
+    };
+
+    let mut accidentals = 0;
+    while let Some(accidental) = chars.peek() {
+      match accidental {
+        '#' => accidentals += 1,
+        'b' => accidentals -= 1,
+        _ => break,
+      }
+      chars.next();
+    }
+
+    let octave = chars.collect::<String>();
+    let octave_number = octave
+      .parse::<i32>()
+      .map_err(|_| ParseNoteNameError::InvalidOctave(octave))?;
+
+    // the octave may be too far from the MIDI range for the note to fit in an i32
+    let note = octave_number
+      .checked_add(1)
+      .and_then(|octave| octave.checked_mul(12))
+      .and_then(|note| note.checked_add(pitch_class + accidentals))
+      .ok_or(ParseNoteNameError::OutOfRange(if octave_number < 0 {
+        i32::MIN
+      } else {
+        i32::MAX
+      }))?;
+    if (0..128).contains(&note) {
+      Ok(Self(note as u8))
+    } else {
+      Err(ParseNoteNameError::OutOfRange(note))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse() {
+    assert_eq!("C4".parse::<NoteName>().map(u8::from), Ok(60));
+    assert_eq!("c#4".parse::<NoteName>().map(u8::from), Ok(61));
+    assert_eq!("Db4".parse::<NoteName>().map(u8::from), Ok(61));
+    assert_eq!("A4".parse::<NoteName>().map(u8::from), Ok(69));
+    assert_eq!("C-1".parse::<NoteName>().map(u8::from), Ok(0));
+    assert_eq!("G9".parse::<NoteName>().map(u8::from), Ok(127));
+    assert_eq!("B#3".parse::<NoteName>().map(u8::from), Ok(60));
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert_eq!(
+      "H4".parse::<NoteName>(),
+      Err(ParseNoteNameError::InvalidLetter(Some('H')))
+    );
+    assert_eq!(
+      "".parse::<NoteName>(),
+      Err(ParseNoteNameError::InvalidLetter(None))
+    );
+    assert_eq!(
+      "C".parse::<NoteName>(),
+      Err(ParseNoteNameError::InvalidOctave("".to_string()))
+    );
+    assert_eq!(
+      "G#9".parse::<NoteName>(),
+      Err(ParseNoteNameError::OutOfRange(128))
+    );
+    assert_eq!(
+      "Cb-1".parse::<NoteName>(),
+      Err(ParseNoteNameError::OutOfRange(-1))
+    );
+    assert_eq!(
+      "C999999999".parse::<NoteName>(),
+      Err(ParseNoteNameError::OutOfRange(i32::MAX))
+    );
+    assert_eq!(
+      "C-999999999".parse::<NoteName>(),
+      Err(ParseNoteNameError::OutOfRange(i32::MIN))
+    );
+  }
+
+  #[test]
+  fn display() {
+    assert_eq!(NoteName::new(60).unwrap().to_string(), "C4");
+    assert_eq!(NoteName::new(61).unwrap().to_string(), "C#4");
+    assert_eq!(NoteName::new(0).unwrap().to_string(), "C-1");
+    assert_eq!(NoteName::new(127).unwrap().to_string(), "G9");
+    assert_eq!(NoteName::new(128), None);
+  }
+
+  #[test]
+  fn round_trip() {
+    for note in 0..128u8 {
+      let name = NoteName::new(note).unwrap();
+      assert_eq!(name.to_string().parse::<NoteName>(), Ok(name));
+    }
+  }
+}
@@ -163,6 +163,109 @@ pub fn decode_channel_voice(ump: &[u32]) -> Option<ChannelVoice> {
   }
 }
 
+pub fn encode_channel_voice(group: u8, channel_voice: &ChannelVoice) -> [u32; 2] {
+  let (status, data0, data1) = match channel_voice.message {
+    ChannelVoiceMessage::NoteOff {
+      note,
+      velocity,
+      attr_type,
+      attr_data,
+    } => (
+      0b1000,
+      (note as u32 & 0x7f) << 8 | attr_type as u32,
+      (velocity as u32) << 16 | attr_data as u32,
+    ),
+    ChannelVoiceMessage::NoteOn {
+      note,
+      velocity,
+      attr_type,
+      attr_data,
+    } => (
+      0b1001,
+      (note as u32 & 0x7f) << 8 | attr_type as u32,
+      (velocity as u32) << 16 | attr_data as u32,
+    ),
+    ChannelVoiceMessage::PolyPressure { note, pressure } => {
+      (0b1010, (note as u32 & 0x7f) << 8, pressure)
+    }
+    ChannelVoiceMessage::RegisteredPerNoteController { note, index, data } => {
+      (0b0000, (note as u32 & 0x7f) << 8 | index as u32, data)
+    }
+    ChannelVoiceMessage::AssignablePerNoteController { note, index, data } => {
+      (0b0001, (note as u32 & 0x7f) << 8 | index as u32, data)
+    }
+    ChannelVoiceMessage::PerNoteManagement {
+      note,
+      detach,
+      reset,
+    } => (
+      0b1111,
+      (note as u32 & 0x7f) << 8 | (detach as u32) << 1 | reset as u32,
+      0,
+    ),
+    ChannelVoiceMessage::ControlChange { index, data } => {
+      (0b1011, (index as u32 & 0x7f) << 8, data)
+    }
+    ChannelVoiceMessage::RegisteredController { bank, index, data } => (
+      0b0010,
+      (bank as u32 & 0x7f) << 8 | (index as u32 & 0x7f),
+      data,
+    ),
+    ChannelVoiceMessage::AssignableController { bank, index, data } => (
+      0b0011,
+      (bank as u32 & 0x7f) << 8 | (index as u32 & 0x7f),
+      data,
+    ),
+    ChannelVoiceMessage::RelativeRegisteredController { bank, index, data } => (
+      0b0100,
+      (bank as u32 & 0x7f) << 8 | (index as u32 & 0x7f),
+      data as u32,
+    ),
+    ChannelVoiceMessage::RelativeAssignableController { bank, index, data } => (
+      0b0101,
+      (bank as u32 & 0x7f) << 8 | (index as u32 & 0x7f),
+      data as u32,
+    ),
+    ChannelVoiceMessage::ProgramChange { program, bank } => {
+      let bank_data = bank
+        .map(|bank| (bank as u32) << 1 & 0x7f00 | bank as u32 & 0x7f)
+        .unwrap_or_default();
+      (
+        0b1100,
+        bank.is_some() as u32,
+        (program as u32 & 0x7f) << 24 | bank_data,
+      )
+    }
+    ChannelVoiceMessage::ChannelPressure { pressure } => (0b1101, 0, pressure),
+    ChannelVoiceMessage::PitchBend { data } => (0b1110, 0, data),
+    ChannelVoiceMessage::PerNotePitchBend { note, data } => {
+      (0b0110, (note as u32 & 0x7f) << 8, data)
+    }
+    ChannelVoiceMessage::ChannelMode(mode) => {
+      let (index, data) = match mode {
+        ChannelMode::AllSoundOff => (120, 0),
+        ChannelMode::ResetAllControllers => (121, 0),
+        ChannelMode::LocalControl(on) => (122, if on { 127 } else { 0 }),
+        ChannelMode::AllNotesOff => (123, 0),
+        ChannelMode::OmniMode(on) => (if on { 125 } else { 124 }, 0),
+        ChannelMode::MonoModeOnForNumberOfChannels(channels) => (126, channels as u32),
+        ChannelMode::MonoModeOnForNumberOfVoices => (126, 0),
+        ChannelMode::PolyModeOn => (127, 0),
+      };
+      (0b1011, index << 8, data)
+    }
+  };
+
+  [
+    0x40000000
+      | (group as u32 & 0x0f) << 24
+      | status << 20
+      | (channel_voice.channel as u32 & 0x0f) << 16
+      | data0,
+    data1,
+  ]
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -472,4 +575,87 @@ mod tests {
       })
     );
   }
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let messages = [
+      ChannelVoiceMessage::NoteOff {
+        note: 0x3c,
+        velocity: 0xabcd,
+        attr_type: 3,
+        attr_data: 0x1234,
+      },
+      ChannelVoiceMessage::NoteOn {
+        note: 0x3c,
+        velocity: 0xabcd,
+        attr_type: 3,
+        attr_data: 0x1234,
+      },
+      ChannelVoiceMessage::PolyPressure {
+        note: 1,
+        pressure: 0x12345678,
+      },
+      ChannelVoiceMessage::RegisteredPerNoteController {
+        note: 1,
+        index: 2,
+        data: 3,
+      },
+      ChannelVoiceMessage::AssignablePerNoteController {
+        note: 1,
+        index: 2,
+        data: 3,
+      },
+      ChannelVoiceMessage::PerNoteManagement {
+        note: 1,
+        detach: true,
+        reset: false,
+      },
+      ChannelVoiceMessage::ControlChange {
+        index: 7,
+        data: 0x80000000,
+      },
+      ChannelVoiceMessage::RegisteredController {
+        bank: 1,
+        index: 2,
+        data: 3,
+      },
+      ChannelVoiceMessage::AssignableController {
+        bank: 1,
+        index: 2,
+        data: 3,
+      },
+      ChannelVoiceMessage::RelativeRegisteredController {
+        bank: 1,
+        index: 2,
+        data: -3,
+      },
+      ChannelVoiceMessage::RelativeAssignableController {
+        bank: 1,
+        index: 2,
+        data: -3,
+      },
+      ChannelVoiceMessage::ProgramChange {
+        program: 5,
+        bank: Some(0x1234),
+      },
+      ChannelVoiceMessage::ProgramChange {
+        program: 5,
+        bank: None,
+      },
+      ChannelVoiceMessage::ChannelPressure { pressure: 9 },
+      ChannelVoiceMessage::PitchBend { data: 0x80000000 },
+      ChannelVoiceMessage::PerNotePitchBend { note: 1, data: 2 },
+      ChannelVoiceMessage::ChannelMode(ChannelMode::LocalControl(true)),
+      ChannelVoiceMessage::ChannelMode(ChannelMode::OmniMode(true)),
+      ChannelVoiceMessage::ChannelMode(ChannelMode::MonoModeOnForNumberOfChannels(4)),
+      ChannelVoiceMessage::ChannelMode(ChannelMode::PolyModeOn),
+    ];
+
+    for message in messages {
+      let channel_voice = ChannelVoice::new(5, message);
+      let ump = encode_channel_voice(3, &channel_voice);
+      assert_eq!((ump[0] >> 24) & 0x0f, 3);
+      assert_eq!(decode_channel_voice(&ump), Some(channel_voice));
+    }
+  }
 }
@@ -0,0 +1,242 @@
+use crate::messages::flex_data::{Address, FlexData, FlexDataMessage, Form, Text, TextPayload};
+
+const SETUP_AND_PERFORMANCE_BANK: u8 = 0x00;
+const METADATA_TEXT_BANK: u8 = 0x01;
+const PERFORMANCE_TEXT_BANK: u8 = 0x02;
+
+const SET_TEMPO_STATUS: u8 = 0x00;
+const SET_TIME_SIGNATURE_STATUS: u8 = 0x01;
+const SET_KEY_SIGNATURE_STATUS: u8 = 0x05;
+
+pub fn decode_flex_data(ump: &[u32]) -> Option<FlexData> {
+  if ump.len() == 4 {
+    let form = match (ump[0] >> 22) & 0x03 {
+      0 => Form::Complete,
+      1 => Form::Start,
+      2 => Form::Continue,
+      _ => Form::End,
+    };
+    let address = match (ump[0] >> 20) & 0x03 {
+      0 => Address::Channel(((ump[0] >> 16) & 0x0f) as u8),
+      1 => Address::Group,
+      _ => return None,
+    };
+    let status_bank = ((ump[0] >> 8) & 0xff) as u8;
+    let status = (ump[0] & 0xff) as u8;
+    let message = match (status_bank, status) {
+      (SETUP_AND_PERFORMANCE_BANK, SET_TEMPO_STATUS) => FlexDataMessage::SetTempo(ump[1]),
+      (SETUP_AND_PERFORMANCE_BANK, SET_TIME_SIGNATURE_STATUS) => {
+        FlexDataMessage::SetTimeSignature {
+          numerator: (ump[1] >> 24) as u8,
+          denominator: ((ump[1] >> 16) & 0xff) as u8,
+          num_32nd_notes: ((ump[1] >> 8) & 0xff) as u8,
+        }
+      }
+      (SETUP_AND_PERFORMANCE_BANK, SET_KEY_SIGNATURE_STATUS) => {
+        let sharps_flats = ((ump[1] >> 28) & 0x0f) as i8;
+        FlexDataMessage::SetKeySignature {
+          sharps_flats: (sharps_flats << 4) >> 4,
+          tonic_note: ((ump[1] >> 24) & 0x0f) as u8,
+        }
+      }
+      (METADATA_TEXT_BANK, _) => FlexDataMessage::MetadataText(decode_text(form, status, ump)?),
+      (PERFORMANCE_TEXT_BANK, _) => {
+        FlexDataMessage::PerformanceText(decode_text(form, status, ump)?)
+      }
+      _ => return None,
+    };
+    Some(FlexData { address, message })
+  } else {
+    None
+  }
+}
+
+fn decode_text(form: Form, status: u8, ump: &[u32]) -> Option<Text> {
+  let mut text = TextPayload::default();
+  for byte in ump[1..4].iter().flat_map(|word| word.to_be_bytes()) {
+    if byte != 0 {
+      text.push(byte).ok()?;
+    }
+  }
+  Some(Text { form, status, text })
+}
+
+pub fn encode_flex_data(group: u8, flex_data: &FlexData) -> [u32; 4] {
+  let (form, status_bank, status) = match &flex_data.message {
+    FlexDataMessage::SetTempo(_) => (Form::Complete, SETUP_AND_PERFORMANCE_BANK, SET_TEMPO_STATUS),
+    FlexDataMessage::SetTimeSignature { .. } => (
+      Form::Complete,
+      SETUP_AND_PERFORMANCE_BANK,
+      SET_TIME_SIGNATURE_STATUS,
+    ),
+    FlexDataMessage::SetKeySignature { .. } => (
+      Form::Complete,
+      SETUP_AND_PERFORMANCE_BANK,
+      SET_KEY_SIGNATURE_STATUS,
+    ),
+    FlexDataMessage::MetadataText(text) => (text.form, METADATA_TEXT_BANK, text.status),
+    FlexDataMessage::PerformanceText(text) => (text.form, PERFORMANCE_TEXT_BANK, text.status),
+  };
+
+  let form = match form {
+    Form::Complete => 0u32,
+    Form::Start => 1,
+    Form::Continue => 2,
+    Form::End => 3,
+  };
+
+  let (address, channel) = match flex_data.address {
+    Address::Channel(channel) => (0u32, channel as u32 & 0x0f),
+    Address::Group => (1, 0),
+  };
+
+  let mut ump = [0u32; 4];
+  ump[0] = 0xd0000000
+    | (group as u32 & 0x0f) << 24
+    | form << 22
+    | address << 20
+    | channel << 16
+    | (status_bank as u32) << 8
+    | status as u32;
+
+  match &flex_data.message {
+    FlexDataMessage::SetTempo(units) => ump[1] = *units,
+    FlexDataMessage::SetTimeSignature {
+      numerator,
+      denominator,
+      num_32nd_notes,
+    } => {
+      ump[1] =
+        (*numerator as u32) << 24 | (*denominator as u32) << 16 | (*num_32nd_notes as u32) << 8
+    }
+    FlexDataMessage::SetKeySignature {
+      sharps_flats,
+      tonic_note,
+    } => ump[1] = (*sharps_flats as u32 & 0x0f) << 28 | (*tonic_note as u32 & 0x0f) << 24,
+    FlexDataMessage::MetadataText(text) | FlexDataMessage::PerformanceText(text) => {
+      let mut bytes = [0u8; 12];
+      bytes[0..text.text.len()].copy_from_slice(text.text.as_slice());
+      for (word, chunk) in ump[1..4].iter_mut().zip(bytes.chunks(4)) {
+        *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+      }
+    }
+  }
+
+  ump
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::messages::flex_data::{Address, FlexData, FlexDataMessage, Form, Text, TextPayload};
+  use crate::protocol::codec::flex_data::decode_flex_data;
+
+  #[test]
+  fn decode_set_tempo() {
+    assert_eq!(
+      decode_flex_data(&[0xd0100000, 0x02faf080, 0x00000000, 0x00000000]),
+      Some(FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::SetTempo(50_000_000),
+      })
+    );
+  }
+
+  #[test]
+  fn decode_set_time_signature() {
+    assert_eq!(
+      decode_flex_data(&[0xd0100001, 0x06030800, 0x00000000, 0x00000000]),
+      Some(FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::SetTimeSignature {
+          numerator: 6,
+          denominator: 3,
+          num_32nd_notes: 8,
+        },
+      })
+    );
+  }
+
+  #[test]
+  fn decode_set_key_signature() {
+    assert_eq!(
+      decode_flex_data(&[0xd0050005, 0xd6000000, 0x00000000, 0x00000000]),
+      Some(FlexData {
+        address: Address::Channel(5),
+        message: FlexDataMessage::SetKeySignature {
+          sharps_flats: -3,
+          tonic_note: 6,
+        },
+      })
+    );
+  }
+
+  #[test]
+  fn decode_lyrics() {
+    assert_eq!(
+      decode_flex_data(&[0xd0500201, 0x48656c6c, 0x6f000000, 0x00000000]),
+      Some(FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::PerformanceText(Text {
+          form: Form::Start,
+          status: 0x01,
+          text: TextPayload::new(b"Hello").unwrap(),
+        }),
+      })
+    );
+  }
+
+  #[test]
+  fn decode_reserved_address() {
+    assert_eq!(
+      decode_flex_data(&[0xd0200000, 0x00000000, 0x00000000, 0x00000000]),
+      None
+    );
+  }
+
+  #[test]
+  fn decode_unknown_status() {
+    assert_eq!(
+      decode_flex_data(&[0xd0107f00, 0x00000000, 0x00000000, 0x00000000]),
+      None
+    );
+  }
+
+  #[test]
+  fn encode_decode() {
+    let messages = vec![
+      FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::SetTempo(50_000_000),
+      },
+      FlexData {
+        address: Address::Channel(3),
+        message: FlexDataMessage::SetTimeSignature {
+          numerator: 3,
+          denominator: 2,
+          num_32nd_notes: 8,
+        },
+      },
+      FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::SetKeySignature {
+          sharps_flats: -2,
+          tonic_note: 2,
+        },
+      },
+      FlexData {
+        address: Address::Group,
+        message: FlexDataMessage::MetadataText(Text {
+          form: Form::Complete,
+          status: 0x05,
+          text: TextPayload::new(b"J.S. Bach").unwrap(),
+        }),
+      },
+    ];
+
+    for message in messages {
+      let ump = message.encode(2);
+      assert_eq!((ump[0] >> 24) & 0x0f, 2);
+      assert_eq!(decode_flex_data(&ump), Some(message));
+    }
+  }
+}
@@ -1,17 +1,25 @@
-mod channel_voice;
-mod system_common;
-mod system_exclusive;
-mod utility;
+pub mod channel_voice;
+pub mod flex_data;
+pub mod system_common;
+pub mod system_exclusive;
+pub mod ump_stream;
+pub mod utility;
 
 use thiserror::Error;
 
+use crate::event::JrTimestamp;
 use crate::filter::Filter;
 use crate::protocol::codec::channel_voice::decode_channel_voice;
+use crate::protocol::codec::flex_data::decode_flex_data;
 use crate::protocol::codec::system_common::decode_system_common;
+use crate::protocol::codec::system_exclusive::decode_system_exclusive;
+use crate::protocol::codec::ump_stream::decode_ump_stream;
 use crate::protocol::codec::utility::decode_utility;
+use crate::protocol::messages::flex_data::Address;
+use crate::protocol::messages::utility::Utility;
 use crate::protocol::messages::{Message, MessageType};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
 pub enum Error {
   #[error("Found reserved encoding")]
   Reserved,
@@ -22,6 +30,9 @@ pub struct Decoder {
   ump: [u32; 4],
   index: usize,
   len: usize,
+  jr_clock: Option<u16>,
+  pending_jr_timestamp: Option<u16>,
+  jr_timestamp: Option<JrTimestamp>,
 }
 
 impl Decoder {
@@ -33,11 +44,14 @@ impl Decoder {
 
     let next_message = if self.is_complete() {
       let (mtype, group) = self.extract_mtype_and_group();
-      let message = if filter.mtype(mtype) && filter.group(group) {
+      // UMP Stream messages are addressed to the whole endpoint rather than to a group
+      let matches_group = mtype == 0x0f || filter.group(group);
+      let message = if filter.mtype(mtype) && matches_group {
         self.decode(mtype, group, filter)
       } else {
         None
       };
+      self.update_jr_timestamp(mtype, &message);
       self.reset();
       message
     } else {
@@ -56,6 +70,8 @@ impl Decoder {
       0x03 => 2,
       0x04 => 2,
       0x05 => 4,
+      0x0d => 4,
+      0x0f => 4,
       _ => 1,
     };
   }
@@ -87,6 +103,11 @@ impl Decoder {
         group,
         mtype: MessageType::SystemCommon(system_common),
       }),
+      // System Exclusive (7-bit)
+      0x03 => decode_system_exclusive(&self.ump[0..2]).map(|system_exclusive| Message {
+        group,
+        mtype: MessageType::SystemExclusive(system_exclusive),
+      }),
       // Channel Voice
       0x04 => decode_channel_voice(&self.ump[0..2]).and_then(|channel_voice| {
         filter
@@ -96,10 +117,54 @@ impl Decoder {
             mtype: MessageType::ChannelVoice(channel_voice),
           })
       }),
+      // Flex Data
+      0x0d => decode_flex_data(&self.ump[0..4]).and_then(|flex_data| {
+        let matches_filter = match flex_data.address {
+          Address::Channel(channel) => filter.channel(group, channel),
+          Address::Group => true,
+        };
+        if matches_filter {
+          Some(Message {
+            group,
+            mtype: MessageType::FlexData(flex_data),
+          })
+        } else {
+          None
+        }
+      }),
+      // UMP Stream
+      0x0f => decode_ump_stream(&self.ump[0..4]).map(|ump_stream| Message {
+        group: 0,
+        mtype: MessageType::UmpStream(ump_stream),
+      }),
       _ => None,
     }
   }
 
+  fn update_jr_timestamp(&mut self, mtype: u8, message: &Option<Message>) {
+    if mtype == 0x00 {
+      self.jr_timestamp = None;
+      // JR messages are tracked even when the filter discards them
+      match decode_utility(&self.ump[0..1]) {
+        Some(Utility::JrClock(sender_clock)) => self.jr_clock = Some(sender_clock),
+        Some(Utility::JrTimestamp(timestamp)) => self.pending_jr_timestamp = Some(timestamp),
+        _ => {}
+      }
+    } else if message.is_some() {
+      self.jr_timestamp = self
+        .pending_jr_timestamp
+        .take()
+        .map(|timestamp| JrTimestamp::new(timestamp, self.jr_clock));
+    }
+  }
+
+  /// JR Timestamp that preceded the last decoded message, if any.
+  // only the CoreMIDI driver receives JR Timestamps
+  #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+  pub fn jr_timestamp(&self) -> Option<JrTimestamp> {
+    self.jr_timestamp
+  }
+
   pub fn reset(&mut self) {
     self.index = 0;
     self.len = 0;
@@ -222,6 +287,90 @@ mod tests {
     )
   }
 
+  #[test]
+  fn decode_system_exclusive() {
+    let filter = Filter::new();
+    let mut decoder = Decoder::default();
+
+    assert!(matches!(decoder.next(0x32037e7f, &filter), Ok(None)));
+    let result = decoder.next(0x06000000, &filter);
+    assert!(
+      matches!(
+        result,
+        Ok(Some(Message {
+          group: 2,
+          mtype: MessageType::SystemExclusive(_)
+        }))
+      ),
+      "Unexpected result: {:?}",
+      result
+    )
+  }
+
+  #[test]
+  fn decode_flex_data() {
+    let filter = Filter::new();
+    let mut decoder = Decoder::default();
+
+    assert!(matches!(decoder.next(0xd1100000, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0x02faf080, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0x00000000, &filter), Ok(None)));
+    let result = decoder.next(0x00000000, &filter);
+    assert!(
+      matches!(
+        result,
+        Ok(Some(Message {
+          group: 1,
+          mtype: MessageType::FlexData(_)
+        }))
+      ),
+      "Unexpected result: {:?}",
+      result
+    )
+  }
+
+  #[test]
+  fn decode_ump_stream() {
+    let filter = Filter::new().with_groups(&[2]);
+    let mut decoder = Decoder::default();
+
+    assert!(matches!(decoder.next(0xf0010101, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0x82000301, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0x00000000, &filter), Ok(None)));
+    let result = decoder.next(0x00000000, &filter);
+    assert!(
+      matches!(
+        result,
+        Ok(Some(Message {
+          group: 0,
+          mtype: MessageType::UmpStream(_)
+        }))
+      ),
+      "Unexpected result: {:?}",
+      result
+    )
+  }
+
+  #[test]
+  fn jr_timestamp_applies_to_next_message() {
+    let filter = Filter::new();
+    let mut decoder = Decoder::default();
+
+    assert!(matches!(decoder.next(0x00100064, &filter), Ok(Some(_))));
+    assert!(matches!(decoder.next(0x0020006e, &filter), Ok(Some(_))));
+    assert_eq!(decoder.jr_timestamp(), None);
+    assert!(matches!(decoder.next(0x40903c00, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0xabcd0000, &filter), Ok(Some(_))));
+    assert_eq!(
+      decoder.jr_timestamp(),
+      Some(JrTimestamp::new(110, Some(100)))
+    );
+
+    assert!(matches!(decoder.next(0x40903c00, &filter), Ok(None)));
+    assert!(matches!(decoder.next(0xabcd0000, &filter), Ok(Some(_))));
+    assert_eq!(decoder.jr_timestamp(), None);
+  }
+
   #[test]
   fn decode_channel_voice() {
     let filter = Filter::new();
@@ -54,10 +54,41 @@ pub fn decode_system_common(ump: &[u32]) -> Option<SystemCommon> {
   }
 }
 
+pub fn encode_system_common(group: u8, system_common: &SystemCommon) -> [u32; 1] {
+  let (status, data) = match *system_common {
+    SystemCommon::MidiTimeCode(code) => {
+      let (message_type, value) = match code {
+        MidiTimeCode::FrameLessSignificantNibble(value) => (0, value),
+        MidiTimeCode::FrameMostSignificantNibble(value) => (1, value),
+        MidiTimeCode::SecondsLessSignificantNibble(value) => (2, value),
+        MidiTimeCode::SecondsMostSignificantNibble(value) => (3, value),
+        MidiTimeCode::MinutesLessSignificantNibble(value) => (4, value),
+        MidiTimeCode::MinutesMostSignificantNibble(value) => (5, value),
+        MidiTimeCode::HoursLessSignificantNibble(value) => (6, value),
+        MidiTimeCode::HoursMostSignificantNibble(value) => (7, value),
+      };
+      (0xf1, (message_type << 12) | (value as u32 & 0x0f) << 8)
+    }
+    SystemCommon::SongPositionPointer(value) => {
+      let value = value as u32;
+      (0xf2, (value & 0x7f) << 8 | (value >> 7) & 0x7f)
+    }
+    SystemCommon::SongSelect(value) => (0xf3, (value as u32 & 0x7f) << 8),
+    SystemCommon::TuneRequest => (0xf6, 0),
+    SystemCommon::TimingClock => (0xf8, 0),
+    SystemCommon::Start => (0xfa, 0),
+    SystemCommon::Continue => (0xfb, 0),
+    SystemCommon::Stop => (0xfc, 0),
+    SystemCommon::ActiveSensing => (0xfe, 0),
+    SystemCommon::Reset => (0xff, 0),
+  };
+  [0x10000000 | (group as u32 & 0x0f) << 24 | status << 16 | data]
+}
+
 #[cfg(test)]
 mod tests {
   use crate::messages::system_common::{MidiTimeCode, SystemCommon};
-  use crate::protocol::codec::system_common::decode_system_common;
+  use crate::protocol::codec::system_common::{decode_system_common, encode_system_common};
 
   #[test]
   fn decode_midi_time_code() {
@@ -188,4 +219,27 @@ mod tests {
       Some(SystemCommon::Reset),
     )
   }
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let messages = [
+      SystemCommon::MidiTimeCode(MidiTimeCode::MinutesMostSignificantNibble(5)),
+      SystemCommon::SongPositionPointer(0x1234),
+      SystemCommon::SongSelect(9),
+      SystemCommon::TuneRequest,
+      SystemCommon::TimingClock,
+      SystemCommon::Start,
+      SystemCommon::Continue,
+      SystemCommon::Stop,
+      SystemCommon::ActiveSensing,
+      SystemCommon::Reset,
+    ];
+
+    for message in messages {
+      assert_eq!(
+        decode_system_common(&encode_system_common(2, &message)),
+        Some(message)
+      );
+    }
+  }
 }
@@ -2,7 +2,7 @@ use crate::messages::system_exclusive::{Payload, SystemExclusive};
 
 pub fn decode_system_exclusive(ump: &[u32]) -> Option<SystemExclusive> {
   if ump.len() == 2 {
-    let status = (ump[0] >> 20) & 0x7f;
+    let status = (ump[0] >> 20) & 0x0f;
     let len = (ump[0] >> 16) & 0x0f;
     let mut payload = Payload::default();
     if len > 0 {
@@ -27,10 +27,32 @@ pub fn decode_system_exclusive(ump: &[u32]) -> Option<SystemExclusive> {
   }
 }
 
+pub fn encode_system_exclusive(group: u8, system_exclusive: &SystemExclusive) -> [u32; 2] {
+  let (status, payload) = match system_exclusive {
+    SystemExclusive::Complete(payload) => (0x00u32, payload),
+    SystemExclusive::Start(payload) => (0x01, payload),
+    SystemExclusive::Continue(payload) => (0x02, payload),
+    SystemExclusive::End(payload) => (0x03, payload),
+  };
+  let mut data = [0u8; 6];
+  data[0..payload.len()].copy_from_slice(payload.as_slice());
+  [
+    0x30000000
+      | (group as u32 & 0x0f) << 24
+      | status << 20
+      | (payload.len() as u32) << 16
+      | (data[0] as u32 & 0x7f) << 8
+      | data[1] as u32 & 0x7f,
+    u32::from_be_bytes([data[2], data[3], data[4], data[5]]) & 0x7f7f7f7f,
+  ]
+}
+
 #[cfg(test)]
 mod tests {
   use crate::messages::system_exclusive::{Payload, SystemExclusive};
-  use crate::protocol::codec::system_exclusive::decode_system_exclusive;
+  use crate::protocol::codec::system_exclusive::{
+    decode_system_exclusive, encode_system_exclusive,
+  };
 
   #[test]
   fn payload_empty() {
@@ -83,4 +105,21 @@ mod tests {
       Some(SystemExclusive::End(Payload::default()))
     );
   }
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let messages = [
+      SystemExclusive::Complete(Payload::default()),
+      SystemExclusive::Start(Payload::new(&[0x7e, 0x7f, 0x06]).unwrap()),
+      SystemExclusive::Continue(Payload::from([1u8, 2, 3, 4, 5, 6])),
+      SystemExclusive::End(Payload::new(&[0x10]).unwrap()),
+    ];
+
+    for message in messages {
+      assert_eq!(
+        decode_system_exclusive(&encode_system_exclusive(1, &message)),
+        Some(message)
+      );
+    }
+  }
 }
@@ -0,0 +1,254 @@
+use crate::messages::ump_stream::{
+  EndpointInfo, EndpointNamePayload, Form, FunctionBlockDirection, FunctionBlockInfo, UmpStream,
+  UmpVersion,
+};
+
+const ENDPOINT_DISCOVERY_STATUS: u16 = 0x00;
+const ENDPOINT_INFO_STATUS: u16 = 0x01;
+const ENDPOINT_NAME_STATUS: u16 = 0x03;
+const FUNCTION_BLOCK_DISCOVERY_STATUS: u16 = 0x10;
+const FUNCTION_BLOCK_INFO_STATUS: u16 = 0x11;
+
+pub fn decode_ump_stream(ump: &[u32]) -> Option<UmpStream> {
+  if ump.len() == 4 {
+    let form = match (ump[0] >> 26) & 0x03 {
+      0 => Form::Complete,
+      1 => Form::Start,
+      2 => Form::Continue,
+      _ => Form::End,
+    };
+    let status = ((ump[0] >> 16) & 0x03ff) as u16;
+    match status {
+      ENDPOINT_DISCOVERY_STATUS => Some(UmpStream::EndpointDiscovery {
+        ump_version: decode_ump_version(ump[0]),
+        filter: (ump[1] & 0xff) as u8,
+      }),
+      ENDPOINT_INFO_STATUS => Some(UmpStream::EndpointInfo(EndpointInfo {
+        ump_version: decode_ump_version(ump[0]),
+        static_function_blocks: (ump[1] & 0x80000000) != 0,
+        num_function_blocks: ((ump[1] >> 24) & 0x7f) as u8,
+        midi2_protocol: (ump[1] & 0x0200) != 0,
+        midi1_protocol: (ump[1] & 0x0100) != 0,
+        rx_jr_timestamp: (ump[1] & 0x02) != 0,
+        tx_jr_timestamp: (ump[1] & 0x01) != 0,
+      })),
+      ENDPOINT_NAME_STATUS => {
+        let mut name = EndpointNamePayload::default();
+        let first = ump[0].to_be_bytes();
+        let rest = ump[1..4].iter().flat_map(|word| word.to_be_bytes());
+        for byte in first[2..4].iter().cloned().chain(rest) {
+          if byte != 0 {
+            name.push(byte).ok()?;
+          }
+        }
+        Some(UmpStream::EndpointName { form, name })
+      }
+      FUNCTION_BLOCK_DISCOVERY_STATUS => Some(UmpStream::FunctionBlockDiscovery {
+        block: ((ump[0] >> 8) & 0xff) as u8,
+        filter: (ump[0] & 0xff) as u8,
+      }),
+      FUNCTION_BLOCK_INFO_STATUS => Some(UmpStream::FunctionBlockInfo(FunctionBlockInfo {
+        active: (ump[0] & 0x8000) != 0,
+        block: ((ump[0] >> 8) & 0x7f) as u8,
+        ui_hint: ((ump[0] >> 4) & 0x03) as u8,
+        midi1: ((ump[0] >> 2) & 0x03) as u8,
+        direction: match ump[0] & 0x03 {
+          1 => FunctionBlockDirection::Input,
+          2 => FunctionBlockDirection::Output,
+          3 => FunctionBlockDirection::Bidirectional,
+          _ => FunctionBlockDirection::Reserved,
+        },
+        first_group: (ump[1] >> 24) as u8,
+        num_groups: ((ump[1] >> 16) & 0xff) as u8,
+        midi_ci_version: ((ump[1] >> 8) & 0xff) as u8,
+        max_sysex8_streams: (ump[1] & 0xff) as u8,
+      })),
+      _ => None,
+    }
+  } else {
+    None
+  }
+}
+
+fn decode_ump_version(word: u32) -> UmpVersion {
+  UmpVersion {
+    major: ((word >> 8) & 0xff) as u8,
+    minor: (word & 0xff) as u8,
+  }
+}
+
+pub fn encode_ump_stream(ump_stream: &UmpStream) -> [u32; 4] {
+  let (form, status) = match ump_stream {
+    UmpStream::EndpointDiscovery { .. } => (Form::Complete, ENDPOINT_DISCOVERY_STATUS),
+    UmpStream::EndpointInfo(_) => (Form::Complete, ENDPOINT_INFO_STATUS),
+    UmpStream::EndpointName { form, .. } => (*form, ENDPOINT_NAME_STATUS),
+    UmpStream::FunctionBlockDiscovery { .. } => (Form::Complete, FUNCTION_BLOCK_DISCOVERY_STATUS),
+    UmpStream::FunctionBlockInfo(_) => (Form::Complete, FUNCTION_BLOCK_INFO_STATUS),
+  };
+
+  let form = match form {
+    Form::Complete => 0u32,
+    Form::Start => 1,
+    Form::Continue => 2,
+    Form::End => 3,
+  };
+
+  let mut ump = [0u32; 4];
+  ump[0] = 0xf0000000 | form << 26 | (status as u32) << 16;
+
+  match ump_stream {
+    UmpStream::EndpointDiscovery {
+      ump_version,
+      filter,
+    } => {
+      ump[0] |= encode_ump_version(ump_version);
+      ump[1] = *filter as u32;
+    }
+    UmpStream::EndpointInfo(info) => {
+      ump[0] |= encode_ump_version(&info.ump_version);
+      ump[1] = (info.static_function_blocks as u32) << 31
+        | (info.num_function_blocks as u32 & 0x7f) << 24
+        | (info.midi2_protocol as u32) << 9
+        | (info.midi1_protocol as u32) << 8
+        | (info.rx_jr_timestamp as u32) << 1
+        | info.tx_jr_timestamp as u32;
+    }
+    UmpStream::EndpointName { name, .. } => {
+      let mut bytes = [0u8; 14];
+      bytes[0..name.len()].copy_from_slice(name.as_slice());
+      ump[0] |= (bytes[0] as u32) << 8 | bytes[1] as u32;
+      for (word, chunk) in ump[1..4].iter_mut().zip(bytes[2..].chunks(4)) {
+        *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+      }
+    }
+    UmpStream::FunctionBlockDiscovery { block, filter } => {
+      ump[0] |= (*block as u32) << 8 | *filter as u32;
+    }
+    UmpStream::FunctionBlockInfo(info) => {
+      let direction = match info.direction {
+        FunctionBlockDirection::Reserved => 0u32,
+        FunctionBlockDirection::Input => 1,
+        FunctionBlockDirection::Output => 2,
+        FunctionBlockDirection::Bidirectional => 3,
+      };
+      ump[0] |= (info.active as u32) << 15
+        | (info.block as u32 & 0x7f) << 8
+        | (info.ui_hint as u32 & 0x03) << 4
+        | (info.midi1 as u32 & 0x03) << 2
+        | direction;
+      ump[1] = (info.first_group as u32) << 24
+        | (info.num_groups as u32) << 16
+        | (info.midi_ci_version as u32) << 8
+        | info.max_sysex8_streams as u32;
+    }
+  }
+
+  ump
+}
+
+fn encode_ump_version(ump_version: &UmpVersion) -> u32 {
+  (ump_version.major as u32) << 8 | ump_version.minor as u32
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::messages::ump_stream::{
+    EndpointInfo, EndpointNamePayload, Form, FunctionBlockDirection, FunctionBlockInfo, UmpStream,
+    UmpVersion,
+  };
+  use crate::protocol::codec::ump_stream::{decode_ump_stream, encode_ump_stream};
+
+  #[test]
+  fn decode_endpoint_info() {
+    assert_eq!(
+      decode_ump_stream(&[0xf0010101, 0x82000301, 0x00000000, 0x00000000]),
+      Some(UmpStream::EndpointInfo(EndpointInfo {
+        ump_version: UmpVersion::V1_1,
+        static_function_blocks: true,
+        num_function_blocks: 2,
+        midi2_protocol: true,
+        midi1_protocol: true,
+        rx_jr_timestamp: false,
+        tx_jr_timestamp: true,
+      }))
+    );
+  }
+
+  #[test]
+  fn decode_endpoint_name() {
+    assert_eq!(
+      decode_ump_stream(&[0xf4034b69, 0x726f2053, 0x74756469, 0x6f000000]),
+      Some(UmpStream::EndpointName {
+        form: Form::Start,
+        name: EndpointNamePayload::new(b"Kiro Studio").unwrap(),
+      })
+    );
+  }
+
+  #[test]
+  fn decode_function_block_info() {
+    assert_eq!(
+      decode_ump_stream(&[0xf0118132, 0x02040110, 0x00000000, 0x00000000]),
+      Some(UmpStream::FunctionBlockInfo(FunctionBlockInfo {
+        active: true,
+        block: 1,
+        ui_hint: 3,
+        midi1: 0,
+        direction: FunctionBlockDirection::Output,
+        first_group: 2,
+        num_groups: 4,
+        midi_ci_version: 1,
+        max_sysex8_streams: 0x10,
+      }))
+    );
+  }
+
+  #[test]
+  fn decode_unknown_status() {
+    assert_eq!(
+      decode_ump_stream(&[0xf3ff0000, 0x00000000, 0x00000000, 0x00000000]),
+      None
+    );
+  }
+
+  #[test]
+  fn encode_discovery() {
+    assert_eq!(
+      encode_ump_stream(&UmpStream::discover_endpoint()),
+      [0xf0000101, 0x0000001f, 0x00000000, 0x00000000]
+    );
+    assert_eq!(
+      encode_ump_stream(&UmpStream::discover_function_blocks()),
+      [0xf0107f01, 0x00000000, 0x00000000, 0x00000000]
+    );
+  }
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let messages = [
+      UmpStream::discover_endpoint(),
+      UmpStream::EndpointName {
+        form: Form::End,
+        name: EndpointNamePayload::new(b"Synth").unwrap(),
+      },
+      UmpStream::FunctionBlockInfo(FunctionBlockInfo {
+        active: false,
+        block: 5,
+        ui_hint: 1,
+        midi1: 2,
+        direction: FunctionBlockDirection::Input,
+        first_group: 0,
+        num_groups: 1,
+        midi_ci_version: 0,
+        max_sysex8_streams: 0,
+      }),
+    ];
+
+    for message in messages {
+      assert_eq!(
+        decode_ump_stream(&encode_ump_stream(&message)),
+        Some(message)
+      );
+    }
+  }
+}
@@ -1,14 +1,69 @@
 use crate::messages::utility::Utility;
 
 pub fn decode_utility(ump: &[u32]) -> Option<Utility> {
-  (ump.len() == 1).then(|| {
+  if ump.len() == 1 {
     let status = ((ump[0] >> 20) & 0x0f) as u8;
+    let data = (ump[0] & 0xffff) as u16;
     match status {
-      0b0000 => Utility::Noop,
-      _ => unimplemented!(),
+      0b0000 => Some(Utility::Noop),
+      0b0001 => Some(Utility::JrClock(data)),
+      0b0010 => Some(Utility::JrTimestamp(data)),
+      _ => None,
     }
-  })
+  } else {
+    None
+  }
+}
+
+pub fn encode_utility(group: u8, utility: &Utility) -> [u32; 1] {
+  let (status, data) = match *utility {
+    Utility::Noop => (0b0000u32, 0),
+    Utility::JrClock(sender_clock) => (0b0001, sender_clock as u32),
+    Utility::JrTimestamp(timestamp) => (0b0010, timestamp as u32),
+  };
+  [(group as u32 & 0x0f) << 24 | status << 20 | data]
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+  use crate::messages::utility::Utility;
+  use crate::protocol::codec::utility::{decode_utility, encode_utility};
+
+  #[test]
+  fn decode_noop() {
+    assert_eq!(decode_utility(&[0x00000000]), Some(Utility::Noop));
+  }
+
+  #[test]
+  fn decode_jr_clock() {
+    assert_eq!(
+      decode_utility(&[0x00101234]),
+      Some(Utility::JrClock(0x1234))
+    );
+  }
+
+  #[test]
+  fn decode_jr_timestamp() {
+    assert_eq!(
+      decode_utility(&[0x0020abcd]),
+      Some(Utility::JrTimestamp(0xabcd))
+    );
+  }
+
+  #[test]
+  fn decode_unknown_status() {
+    assert_eq!(decode_utility(&[0x00f00000]), None);
+  }
+
+  #[test]
+  fn encode_jr_timestamp() {
+    assert_eq!(
+      encode_utility(0, &Utility::JrTimestamp(0xabcd)),
+      [0x0020abcd]
+    );
+    assert_eq!(
+      decode_utility(&encode_utility(0, &Utility::JrClock(0x1234))),
+      Some(Utility::JrClock(0x1234))
+    );
+  }
+}
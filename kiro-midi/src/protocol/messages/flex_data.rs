@@ -0,0 +1,99 @@
+use crate::messages::system_exclusive::Payload;
+use crate::protocol::codec::flex_data::encode_flex_data;
+
+pub type TextPayload = Payload<12>;
+
+/// Flex Data Type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlexData {
+  pub address: Address,
+  pub message: FlexDataMessage,
+}
+
+/// Destination of a Flex Data message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Address {
+  Channel(u8),
+  Group,
+}
+
+/// Position of a message in a sequence of Flex Data messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Form {
+  Complete,
+  Start,
+  Continue,
+  End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexDataMessage {
+  // Setup and Performance
+  /// Set Tempo (number of 10 nanosecond units per quarter note)
+  SetTempo(u32),
+
+  /// Set Time Signature
+  SetTimeSignature {
+    numerator: u8,
+    /// Denominator as a negative power of 2 (2 means a quarter note)
+    denominator: u8,
+    num_32nd_notes: u8,
+  },
+
+  /// Set Key Signature
+  SetKeySignature {
+    /// Number of sharps (positive) or flats (negative)
+    sharps_flats: i8,
+    /// Tonic note (0: unknown, 1: A, ..., 7: G)
+    tonic_note: u8,
+  },
+
+  // Text
+  /// Metadata Text (project name, composer, copyright, ...)
+  MetadataText(Text),
+
+  /// Performance Text (lyrics, ruby, ...)
+  PerformanceText(Text),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Text {
+  pub form: Form,
+  pub status: u8,
+  pub text: TextPayload,
+}
+
+impl FlexData {
+  /// Encode it as an Universal MIDI Packet for the given group.
+  pub fn encode(&self, group: u8) -> [u32; 4] {
+    encode_flex_data(group, self)
+  }
+}
+
+impl FlexDataMessage {
+  const TEMPO_UNITS_PER_MINUTE: f64 = 60.0 * 100_000_000.0;
+
+  pub fn set_tempo_from_bpm(bpm: f64) -> Self {
+    Self::SetTempo((Self::TEMPO_UNITS_PER_MINUTE / bpm).round() as u32)
+  }
+
+  /// Returns the tempo in beats per minute for a Set Tempo message.
+  pub fn tempo_bpm(&self) -> Option<f64> {
+    match self {
+      Self::SetTempo(units) if *units > 0 => Some(Self::TEMPO_UNITS_PER_MINUTE / f64::from(*units)),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tempo_bpm() {
+    let message = FlexDataMessage::set_tempo_from_bpm(120.0);
+    assert_eq!(message, FlexDataMessage::SetTempo(50_000_000));
+    assert_eq!(message.tempo_bpm(), Some(120.0));
+  }
+}
@@ -1,11 +1,22 @@
 pub mod channel_voice;
+pub mod flex_data;
+pub mod panic;
 pub mod system_common;
 pub mod system_exclusive;
+pub mod ump_stream;
 pub mod utility;
 
 use crate::messages::channel_voice::ChannelVoiceMessage;
+use crate::messages::flex_data::FlexData;
 use crate::messages::system_common::SystemCommon;
 use crate::messages::system_exclusive::SystemExclusive;
+use crate::messages::ump_stream::UmpStream;
+use crate::protocol::codec::channel_voice::encode_channel_voice;
+use crate::protocol::codec::flex_data::encode_flex_data;
+use crate::protocol::codec::system_common::encode_system_common;
+use crate::protocol::codec::system_exclusive::encode_system_exclusive;
+use crate::protocol::codec::ump_stream::encode_ump_stream;
+use crate::protocol::codec::utility::encode_utility;
 use crate::protocol::messages::channel_voice::ChannelVoice;
 use crate::protocol::messages::utility::Utility;
 
@@ -26,6 +37,36 @@ impl Message {
       mtype: MessageType::ChannelVoice(ChannelVoice { channel, message }),
     }
   }
+
+  /// Encode it as an Universal MIDI Packet.
+  ///
+  /// Returns the packet words and how many of them are used by the message.
+  pub fn encode(&self) -> ([u32; 4], usize) {
+    let mut ump = [0u32; 4];
+    let len = match &self.mtype {
+      MessageType::Utility(utility) => copy_words(&mut ump, &encode_utility(self.group, utility)),
+      MessageType::SystemCommon(system_common) => {
+        copy_words(&mut ump, &encode_system_common(self.group, system_common))
+      }
+      MessageType::SystemExclusive(system_exclusive) => copy_words(
+        &mut ump,
+        &encode_system_exclusive(self.group, system_exclusive),
+      ),
+      MessageType::ChannelVoice(channel_voice) => {
+        copy_words(&mut ump, &encode_channel_voice(self.group, channel_voice))
+      }
+      MessageType::FlexData(flex_data) => {
+        copy_words(&mut ump, &encode_flex_data(self.group, flex_data))
+      }
+      MessageType::UmpStream(ump_stream) => copy_words(&mut ump, &encode_ump_stream(ump_stream)),
+    };
+    (ump, len)
+  }
+}
+
+fn copy_words(ump: &mut [u32; 4], words: &[u32]) -> usize {
+  ump[0..words.len()].copy_from_slice(words);
+  words.len()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -35,4 +76,6 @@ pub enum MessageType {
   SystemExclusive(SystemExclusive),
   ChannelVoice(ChannelVoice),
   // Data(Data)
+  FlexData(FlexData),
+  UmpStream(UmpStream),
 }
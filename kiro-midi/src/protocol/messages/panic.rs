@@ -0,0 +1,78 @@
+use crate::messages::channel_voice::{ChannelMode, ChannelVoiceMessage};
+use crate::messages::Message;
+
+const SUSTAIN_CC: u8 = 64;
+
+/// Messages to silence a group and recover from stuck notes, as sent by a panic button.
+///
+/// For every channel it sends All Sound Off, All Notes Off and Sustain Off,
+/// followed by a Per-Note Management reset of the per-note controllers for every note.
+pub fn panic_messages(group: u8) -> impl Iterator<Item = Message> {
+  (0..16u8).flat_map(move |channel| {
+    let channel_messages = [
+      ChannelVoiceMessage::ChannelMode(ChannelMode::AllSoundOff),
+      ChannelVoiceMessage::ChannelMode(ChannelMode::AllNotesOff),
+      ChannelVoiceMessage::ControlChange {
+        index: SUSTAIN_CC,
+        data: 0,
+      },
+    ];
+    let per_note_messages = (0..128u8).map(|note| ChannelVoiceMessage::PerNoteManagement {
+      note,
+      detach: false,
+      reset: true,
+    });
+    channel_messages
+      .into_iter()
+      .chain(per_note_messages)
+      .map(move |message| Message::channel_voice(group, channel, message))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::messages::channel_voice::ChannelVoice;
+  use crate::messages::MessageType;
+
+  #[test]
+  fn panic_covers_all_channels() {
+    let messages = panic_messages(3).collect::<Vec<Message>>();
+
+    assert_eq!(messages.len(), 16 * (3 + 128));
+    assert!(messages.iter().all(|message| message.group == 3));
+
+    for channel in 0..16u8 {
+      let channel_messages = messages
+        .iter()
+        .filter_map(|message| match message.mtype {
+          MessageType::ChannelVoice(ChannelVoice {
+            channel: msg_channel,
+            message,
+          }) if msg_channel == channel => Some(message),
+          _ => None,
+        })
+        .collect::<Vec<ChannelVoiceMessage>>();
+
+      assert_eq!(
+        channel_messages[0..3],
+        [
+          ChannelVoiceMessage::ChannelMode(ChannelMode::AllSoundOff),
+          ChannelVoiceMessage::ChannelMode(ChannelMode::AllNotesOff),
+          ChannelVoiceMessage::ControlChange {
+            index: SUSTAIN_CC,
+            data: 0
+          },
+        ]
+      );
+      assert_eq!(
+        channel_messages[130],
+        ChannelVoiceMessage::PerNoteManagement {
+          note: 127,
+          detach: false,
+          reset: true
+        }
+      );
+    }
+  }
+}
@@ -0,0 +1,125 @@
+use crate::messages::system_exclusive::Payload;
+use crate::protocol::codec::ump_stream::encode_ump_stream;
+
+pub type EndpointNamePayload = Payload<14>;
+
+/// UMP Stream message, used to discover and negotiate the capabilities of an endpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UmpStream {
+  /// Endpoint Discovery
+  EndpointDiscovery {
+    ump_version: UmpVersion,
+    /// Bitmap with the notifications requested (see `DiscoveryFilter`)
+    filter: u8,
+  },
+
+  /// Endpoint Info Notification
+  EndpointInfo(EndpointInfo),
+
+  /// Endpoint Name Notification
+  EndpointName {
+    form: Form,
+    name: EndpointNamePayload,
+  },
+
+  /// Function Block Discovery
+  FunctionBlockDiscovery {
+    /// Function Block number (0x7f means all of them)
+    block: u8,
+    /// Bitmap with the notifications requested (see `DiscoveryFilter`)
+    filter: u8,
+  },
+
+  /// Function Block Info Notification
+  FunctionBlockInfo(FunctionBlockInfo),
+}
+
+/// Position of a message in a sequence of UMP Stream messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Form {
+  Complete,
+  Start,
+  Continue,
+  End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UmpVersion {
+  pub major: u8,
+  pub minor: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointInfo {
+  pub ump_version: UmpVersion,
+  pub static_function_blocks: bool,
+  pub num_function_blocks: u8,
+  pub midi2_protocol: bool,
+  pub midi1_protocol: bool,
+  pub rx_jr_timestamp: bool,
+  pub tx_jr_timestamp: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionBlockDirection {
+  Reserved,
+  Input,
+  Output,
+  Bidirectional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionBlockInfo {
+  pub active: bool,
+  pub block: u8,
+  pub ui_hint: u8,
+  /// 0: not MIDI 1.0, 1: MIDI 1.0, 2: MIDI 1.0 restricted to 31.25kb/s
+  pub midi1: u8,
+  pub direction: FunctionBlockDirection,
+  pub first_group: u8,
+  pub num_groups: u8,
+  pub midi_ci_version: u8,
+  pub max_sysex8_streams: u8,
+}
+
+/// Bits of the filter bitmap used by the discovery messages
+pub struct DiscoveryFilter;
+
+impl DiscoveryFilter {
+  pub const ENDPOINT_INFO: u8 = 0x01;
+  pub const DEVICE_IDENTITY: u8 = 0x02;
+  pub const ENDPOINT_NAME: u8 = 0x04;
+  pub const PRODUCT_INSTANCE_ID: u8 = 0x08;
+  pub const STREAM_CONFIGURATION: u8 = 0x10;
+  pub const ALL: u8 = 0x1f;
+
+  pub const FUNCTION_BLOCK_INFO: u8 = 0x01;
+  pub const FUNCTION_BLOCK_NAME: u8 = 0x02;
+}
+
+impl UmpVersion {
+  pub const V1_1: UmpVersion = UmpVersion { major: 1, minor: 1 };
+}
+
+impl UmpStream {
+  /// Endpoint Discovery asking for all the endpoint notifications.
+  pub fn discover_endpoint() -> Self {
+    Self::EndpointDiscovery {
+      ump_version: UmpVersion::V1_1,
+      filter: DiscoveryFilter::ALL,
+    }
+  }
+
+  /// Function Block Discovery asking for the info of all the blocks.
+  pub fn discover_function_blocks() -> Self {
+    Self::FunctionBlockDiscovery {
+      block: 0x7f,
+      filter: DiscoveryFilter::FUNCTION_BLOCK_INFO,
+    }
+  }
+
+  /// Encode it as an Universal MIDI Packet.
+  pub fn encode(&self) -> [u32; 4] {
+    encode_ump_stream(self)
+  }
+}
@@ -1,5 +1,8 @@
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Utility {
   Noop,
-  // TODO ...
+  /// Jitter Reduction Clock with the current time of the sender (in units of 1/31250 seconds)
+  JrClock(u16),
+  /// Jitter Reduction Timestamp for the messages that follow (in units of 1/31250 seconds)
+  JrTimestamp(u16),
 }
@@ -7,8 +7,9 @@ const NULL_STATUS: u8 = 0;
 const SYSEX_START_STATUS: u8 = 0xf0;
 const SYSEX_END_STATUS: u8 = 0xf7;
 const SYSEX_MAX_LEN: usize = 6;
+const LSB_CC_OFFSET: u8 = 32;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
 pub enum Error {
   #[error("Data buffer overflow")]
   DataOverflow,
@@ -147,6 +148,10 @@ pub struct Translator {
   banks: [Data14; 16],
   controllers: [ControllerState; 16],
   sysex: SysexStatus,
+  high_resolution_cc: bool,
+  cc_msb: [[Option<u8>; 32]; 16],
+  ump_capacity: usize,
+  sysex_chunk_len: usize,
 }
 
 impl Translator {
@@ -163,9 +168,40 @@ impl Translator {
       banks: [Data14::default(); 16],
       controllers: [ControllerState::new(); 16],
       sysex: SysexStatus::Start,
+      high_resolution_cc: false,
+      cc_msb: [[None; 32]; 16],
+      ump_capacity: Self::UMP_CAPACITY,
+      sysex_chunk_len: SYSEX_MAX_LEN,
     }
   }
 
+  /// Maximum number of UMP words that can be queued before [`Error::UmpOverflow`] is returned.
+  ///
+  /// With the default capacity the words have to be popped after every pushed byte,
+  /// a bigger capacity allows to push whole buffers (ex. firmware dumps) before draining them.
+  #[must_use]
+  pub fn with_ump_capacity(mut self, capacity: usize) -> Self {
+    self.ump_capacity = capacity.max(Self::UMP_CAPACITY);
+    self.ump.reserve(self.ump_capacity);
+    self
+  }
+
+  /// Number of SysEx data bytes sent in every UMP packet, from 1 to 6.
+  #[must_use]
+  pub fn with_sysex_chunk_len(mut self, len: usize) -> Self {
+    self.sysex_chunk_len = len.clamp(1, SYSEX_MAX_LEN);
+    self
+  }
+
+  /// Combine the MSB/LSB pairs of controllers 1 to 31 (CC n and CC n+32) into high resolution Control Changes.
+  ///
+  /// The MSB is emitted as soon as it arrives, and every LSB that follows updates it with the full 14 bits resolution.
+  #[must_use]
+  pub fn with_high_resolution_cc(mut self, enabled: bool) -> Self {
+    self.high_resolution_cc = enabled;
+    self
+  }
+
   pub fn push(&mut self, byte: u8, filter: &Filter) -> Result<(), Error> {
     if Self::is_status(byte) {
       if Self::is_real_time(byte) {
@@ -178,10 +214,35 @@ impl Translator {
     }
   }
 
+  /// Translate a MIDI 1.0 Channel Voice message packed into an UMP (message type 2),
+  /// as delivered by the drivers receiving MIDI 1.0, returning whether the word was one of them.
+  pub fn push_midi1_ump(&mut self, word: u32, filter: &Filter) -> Result<bool, Error> {
+    if word >> 28 != 0x2 {
+      return Ok(false);
+    }
+    let status = (word >> 16) as u8;
+    self.push(status, filter)?;
+    let data = [(word >> 8) as u8 & 0x7f, word as u8 & 0x7f];
+    for byte in data.iter().take(Self::expected_len(status)) {
+      self.push(*byte, filter)?;
+    }
+    Ok(true)
+  }
+
   pub fn pop(&mut self) -> Option<u32> {
     self.ump.pop_front()
   }
 
+  /// Remove all the queued UMP words.
+  pub fn drain(&mut self) -> impl Iterator<Item = u32> + '_ {
+    self.ump.drain(..)
+  }
+
+  /// Number of queued UMP words.
+  pub fn pending(&self) -> usize {
+    self.ump.len()
+  }
+
   fn handle_real_time(&mut self, status: u8, filter: &Filter) -> Result<(), Error> {
     let result = self.emit_system_common(status, 0x00, 0x00);
     if status == 0xff {
@@ -233,7 +294,7 @@ impl Translator {
       Ok(())
     } else if self.status == SYSEX_START_STATUS {
       self.data.push(data);
-      if self.data.len() == SYSEX_MAX_LEN {
+      if self.data.len() == self.sysex_chunk_len {
         self.handle_sysex(false)
       } else {
         Ok(())
@@ -370,12 +431,33 @@ impl Translator {
             self.controllers[channel as usize].set_param_msb(ControllerKind::Registered, data7);
             Ok(())
           }
+          // High resolution MSB
+          1..=31 if self.high_resolution_cc => {
+            self.cc_msb[channel as usize][index as usize] = Some(data7);
+            let data = convert7to32(data7);
+            self.emit_channel_voice(self.status, (index as u16) << 8, data)
+          }
+          // High resolution LSB
+          33..=63 if self.high_resolution_cc => {
+            let msb_index = index - LSB_CC_OFFSET;
+            match self.cc_msb[channel as usize][msb_index as usize] {
+              Some(msb) => {
+                let data = convert14to32((msb as u16) << 7 | data7 as u16);
+                self.emit_channel_voice(self.status, (msb_index as u16) << 8, data)
+              }
+              None => {
+                let data = convert7to32(data7);
+                self.emit_channel_voice(self.status, (index as u16) << 8, data)
+              }
+            }
+          }
           _ => {
             let data = convert7to32(self.data[1] & 0x7f);
             let result = self.emit_channel_voice(self.status, (index as u16) << 8, data);
             if index == 121 {
               self.banks[channel as usize].reset();
               self.controllers[channel as usize].reset();
+              self.cc_msb[channel as usize] = [None; 32];
             }
             result
           }
@@ -457,6 +539,7 @@ impl Translator {
       .controllers
       .iter_mut()
       .for_each(|controller| controller.reset());
+    self.cc_msb = [[None; 32]; 16];
   }
 
   fn emit_system_common(&mut self, status: u8, data0: u8, data1: u8) -> Result<(), Error> {
@@ -474,7 +557,7 @@ impl Translator {
   }
 
   fn emit(&mut self, ump: &[u32]) -> Result<(), Error> {
-    if self.ump.len() + ump.len() <= Self::UMP_CAPACITY {
+    if self.ump.len() + ump.len() <= self.ump_capacity {
       self.ump.extend(ump.iter());
       Ok(())
     } else {
@@ -642,6 +725,65 @@ mod tests {
     );
   }
 
+  #[test]
+  fn system_exclusive_chunk_len() {
+    assert_decodes_with(
+      Translator::new(0).with_sysex_chunk_len(4),
+      vec![0xf0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xf7],
+      vec![0x30140102, 0x03040000, 0x30320506, 0x00000000],
+    );
+  }
+
+  #[test]
+  fn system_exclusive_large_dump() {
+    let mut translator = Translator::new(0).with_ump_capacity(1024);
+    let filter = Filter::new();
+    let dump = (0..600u32).map(|i| (i % 0x80) as u8).collect::<Vec<u8>>();
+
+    translator.push(0xf0, &filter).unwrap();
+    for byte in dump.iter() {
+      translator.push(*byte, &filter).unwrap();
+    }
+    translator.push(0xf7, &filter).unwrap();
+
+    assert_eq!(translator.pending(), 2 * 101);
+    let ump = translator.drain().collect::<Vec<u32>>();
+    assert_eq!(translator.pending(), 0);
+    assert_eq!(ump[0], 0x30160001);
+    assert_eq!(ump[2], 0x30260607);
+    assert_eq!(ump[200], 0x30300000);
+
+    let data = ump
+      .chunks(2)
+      .flat_map(|packet| {
+        let len = ((packet[0] >> 16) & 0x0f) as usize;
+        let bytes = [
+          (packet[0] >> 8) as u8,
+          packet[0] as u8,
+          (packet[1] >> 24) as u8,
+          (packet[1] >> 16) as u8,
+          (packet[1] >> 8) as u8,
+          packet[1] as u8,
+        ];
+        bytes.into_iter().take(len)
+      })
+      .collect::<Vec<u8>>();
+    assert_eq!(data, dump);
+  }
+
+  #[test]
+  fn ump_capacity_is_bounded() {
+    let mut translator = Translator::new(0).with_ump_capacity(6);
+    let filter = Filter::new();
+    for byte in [0x89, 0x40, 0x7f, 0x41, 0x40, 0x42, 0x40, 0x43] {
+      assert!(matches!(translator.push(byte, &filter), Ok(())));
+    }
+    assert!(matches!(
+      translator.push(0x40, &filter),
+      Err(Error::UmpOverflow)
+    ));
+  }
+
   #[test]
   fn channel_voice_note_off() {
     assert_decodes(
@@ -682,6 +824,67 @@ mod tests {
     );
   }
 
+  #[test]
+  fn channel_voice_control_change_high_resolution() {
+    let bytes = vec![
+      0xb9, 0x07, 0x7f, // volume MSB on channel 9
+      0xb9, 0x27, 0x7f, // volume LSB on channel 9
+      0xb2, 0x27, 0x40, // volume LSB without MSB on channel 2
+      0xb9, 0x06, 0x40, // data entry MSB is not paired
+      0xb9, 0x20, 0x01, // bank LSB is not paired
+    ];
+
+    assert_decodes_with(
+      Translator::new(0).with_high_resolution_cc(true),
+      bytes.clone(),
+      vec![
+        0x40b90700, 0xffffffff, 0x40b90700, 0xffffffff, 0x40b22700, 0x80000000,
+      ],
+    );
+
+    assert_decodes(
+      bytes,
+      vec![
+        0x40b90700, 0xffffffff, 0x40b92700, 0xffffffff, 0x40b22700, 0x80000000,
+      ],
+    );
+  }
+
+  #[test]
+  fn midi1_ump_control_change_high_resolution() {
+    let mut translator = Translator::new(3)
+      .with_high_resolution_cc(true)
+      .with_ump_capacity(8);
+    let filter = Filter::new();
+    // volume MSB and LSB on channel 9 of group 3
+    assert!(translator.push_midi1_ump(0x23b9077f, &filter).unwrap());
+    assert!(translator.push_midi1_ump(0x23b9277f, &filter).unwrap());
+    // program change without the second data byte
+    assert!(translator.push_midi1_ump(0x23c90500, &filter).unwrap());
+    assert!(!translator.push_midi1_ump(0x43b90700, &filter).unwrap());
+    assert_eq!(
+      translator.drain().collect::<Vec<u32>>(),
+      vec![0x43b90700, 0xffffffff, 0x43b90700, 0xffffffff, 0x43c90000, 0x05000000]
+    );
+  }
+
+  #[test]
+  fn channel_voice_control_change_high_resolution_fine() {
+    let translator = assert_decodes_with(
+      Translator::new(0).with_high_resolution_cc(true),
+      vec![
+        0xb1, 0x01, 0x40, // modulation MSB on channel 1
+        0xb1, 0x21, 0x00, // modulation LSB on channel 1
+        0xb1, 0x21, 0x01, // modulation LSB on channel 1
+      ],
+      vec![
+        0x40b10100, 0x80000000, 0x40b10100, 0x80000000, 0x40b10100, 0x80040020,
+      ],
+    );
+
+    assert_eq!(translator.cc_msb[1][1], Some(0x40));
+  }
+
   #[test]
   fn channel_voice_controllers() {
     assert_decodes(
@@ -791,7 +994,14 @@ mod tests {
   }
 
   fn assert_decodes(bytes: Vec<u8>, expected: Vec<u32>) -> Translator {
-    let mut translator = Translator::new(0);
+    assert_decodes_with(Translator::new(0), bytes, expected)
+  }
+
+  fn assert_decodes_with(
+    mut translator: Translator,
+    bytes: Vec<u8>,
+    expected: Vec<u32>,
+  ) -> Translator {
     let filter = Filter::new();
     let mut bytes = bytes.into_iter();
     let mut ump = Vec::new();
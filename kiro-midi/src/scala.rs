@@ -0,0 +1,381 @@
+//! Scales and keyboard mappings in the formats of the [Scala] program (`.scl` and `.kbm` files),
+//! the usual way of sharing tunings outside of 12-EDO, see [`crate::note_freq::Tuning::scala`].
+//!
+//! [Scala]: https://www.huygens-fokker.org/scala/scl_format.html
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScalaError {
+  #[error("IO error: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Missing {0}")]
+  Missing(&'static str),
+
+  #[error("Invalid {0}: {1:?}")]
+  Invalid(&'static str, String),
+}
+
+/// Interval of a scale from its first degree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalePitch {
+  Cents(f64),
+  /// Numerator and denominator of a frequency ratio
+  Ratio(u32, u32),
+}
+
+impl ScalePitch {
+  pub fn cents(&self) -> f64 {
+    match *self {
+      ScalePitch::Cents(cents) => cents,
+      ScalePitch::Ratio(numerator, denominator) => {
+        1200.0 * (f64::from(numerator) / f64::from(denominator)).log2()
+      }
+    }
+  }
+}
+
+impl Display for ScalePitch {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      // the dot is what tells cents apart from ratios
+      ScalePitch::Cents(cents) if cents.fract() == 0.0 => write!(f, "{:.1}", cents),
+      ScalePitch::Cents(cents) => write!(f, "{}", cents),
+      ScalePitch::Ratio(numerator, denominator) => write!(f, "{}/{}", numerator, denominator),
+    }
+  }
+}
+
+impl FromStr for ScalePitch {
+  type Err = ScalaError;
+
+  /// Parses cents like `701.955`, ratios like `3/2`, or whole numbers like `2`,
+  /// ignoring anything after the value.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let value = s.split_whitespace().next().unwrap_or("");
+    let invalid = || ScalaError::Invalid("pitch", value.to_string());
+    if value.contains('.') {
+      value
+        .parse::<f64>()
+        .ok()
+        .filter(|cents| cents.is_finite())
+        .map(ScalePitch::Cents)
+        .ok_or_else(invalid)
+    } else {
+      let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+      match (numerator.parse::<u32>(), denominator.parse::<u32>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0 && denominator > 0 => {
+          Ok(ScalePitch::Ratio(numerator, denominator))
+        }
+        _ => Err(invalid()),
+      }
+    }
+  }
+}
+
+/// Scale from a `.scl` file, as the intervals of its degrees from the first one,
+/// where the last one is the period at which it repeats (usually the octave).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+  description: String,
+  pitches: Vec<ScalePitch>,
+}
+
+impl Scale {
+  pub fn new<S: Into<String>>(description: S, pitches: Vec<ScalePitch>) -> Self {
+    Self {
+      description: description.into(),
+      pitches,
+    }
+  }
+
+  /// Equal division of the octave into some number of steps.
+  pub fn equal_division(steps: usize) -> Self {
+    let pitches = (1..=steps)
+      .map(|step| ScalePitch::Cents(1200.0 * step as f64 / steps as f64))
+      .collect();
+    Self::new(format!("{}-EDO", steps), pitches)
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ScalaError> {
+    std::fs::read_to_string(path)?.parse()
+  }
+
+  pub fn description(&self) -> &str {
+    &self.description
+  }
+
+  pub fn pitches(&self) -> &[ScalePitch] {
+    &self.pitches
+  }
+
+  /// Number of degrees before the scale repeats.
+  pub fn len(&self) -> usize {
+    self.pitches.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.pitches.is_empty()
+  }
+
+  /// Cents from the first degree of the scale to any degree, repeating it along its period.
+  pub fn degree_cents(&self, degree: i32) -> f64 {
+    let len = self.pitches.len() as i32;
+    if len == 0 {
+      return 0.0;
+    }
+    let (periods, step) = (degree.div_euclid(len), degree.rem_euclid(len));
+    let period = self.pitches[self.pitches.len() - 1].cents();
+    let cents = match step {
+      0 => 0.0,
+      step => self.pitches[step as usize - 1].cents(),
+    };
+    f64::from(periods) * period + cents
+  }
+}
+
+impl Display for Scale {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "{}", self.description)?;
+    writeln!(f, "{}", self.pitches.len())?;
+    for pitch in self.pitches.iter() {
+      writeln!(f, "{}", pitch)?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for Scale {
+  type Err = ScalaError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    // the description is the first line that is not a comment, even if it is empty
+    let mut lines = s.lines().filter(|line| !line.starts_with('!'));
+    let description = lines
+      .next()
+      .ok_or(ScalaError::Missing("description"))?
+      .trim()
+      .to_string();
+    let mut values = lines.map(str::trim).filter(|line| !line.is_empty());
+    let count = parse_value::<usize>(values.next(), "number of notes")?;
+    if count == 0 {
+      return Err(ScalaError::Invalid("number of notes", count.to_string()));
+    }
+    let pitches = (0..count)
+      .map(|_| values.next().ok_or(ScalaError::Missing("pitch"))?.parse())
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(Self::new(description, pitches))
+  }
+}
+
+/// How the keys of a keyboard are mapped into the degrees of a [`Scale`], from a `.kbm` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+  /// First and last keys to retune, the rest are tuned in 12-EDO from the reference key
+  pub first_key: u8,
+  pub last_key: u8,
+  /// Key mapped into the first degree of the scale
+  pub middle_key: u8,
+  /// Key with a known frequency, which fixes the frequencies of the rest
+  pub reference_key: u8,
+  pub reference_freq: f32,
+  /// Degree of the scale where the mapping repeats
+  pub octave_degree: i32,
+  /// Degree of the scale for the keys from the middle one, repeating every `mapping.len()` keys,
+  /// with `None` for the keys left unmapped. When empty all the keys follow the scale in order.
+  pub mapping: Vec<Option<i32>>,
+}
+
+impl KeyboardMapping {
+  /// Map the keys linearly into the degrees of the scale, with the first one in middle C
+  /// and A4 tuned to a frequency.
+  pub fn linear(reference_freq: f32) -> Self {
+    Self {
+      first_key: 0,
+      last_key: 127,
+      middle_key: 60,
+      reference_key: 69,
+      reference_freq,
+      octave_degree: 0,
+      mapping: Vec::new(),
+    }
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ScalaError> {
+    std::fs::read_to_string(path)?.parse()
+  }
+
+  /// Degree of the scale for a key, or `None` when it is not mapped.
+  pub fn degree(&self, key: u8, scale: &Scale) -> Option<i32> {
+    if key < self.first_key || key > self.last_key {
+      return None;
+    }
+    let offset = i32::from(key) - i32::from(self.middle_key);
+    if self.mapping.is_empty() {
+      return Some(offset);
+    }
+    let len = self.mapping.len() as i32;
+    let octave_degree = if self.octave_degree > 0 {
+      self.octave_degree
+    } else {
+      scale.len() as i32
+    };
+    let (repeats, index) = (offset.div_euclid(len), offset.rem_euclid(len));
+    self.mapping[index as usize].map(|degree| degree + repeats * octave_degree)
+  }
+}
+
+impl Default for KeyboardMapping {
+  fn default() -> Self {
+    Self::linear(440.0)
+  }
+}
+
+impl Display for KeyboardMapping {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "{}", self.mapping.len())?;
+    writeln!(f, "{}", self.first_key)?;
+    writeln!(f, "{}", self.last_key)?;
+    writeln!(f, "{}", self.middle_key)?;
+    writeln!(f, "{}", self.reference_key)?;
+    writeln!(f, "{}", self.reference_freq)?;
+    writeln!(f, "{}", self.octave_degree)?;
+    for degree in self.mapping.iter() {
+      match degree {
+        Some(degree) => writeln!(f, "{}", degree)?,
+        None => writeln!(f, "x")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for KeyboardMapping {
+  type Err = ScalaError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut values = s
+      .lines()
+      .filter(|line| !line.starts_with('!'))
+      .filter_map(|line| line.split_whitespace().next());
+    let size = parse_value::<usize>(values.next(), "size of the mapping")?;
+    let first_key = parse_value(values.next(), "first key")?;
+    let last_key = parse_value(values.next(), "last key")?;
+    let middle_key = parse_value(values.next(), "middle key")?;
+    let reference_key = parse_value(values.next(), "reference key")?;
+    let reference_freq = parse_value(values.next(), "reference frequency")?;
+    let octave_degree = parse_value(values.next(), "octave degree")?;
+    // the last keys of the mapping can be left out, meaning they are not mapped
+    let mapping = (0..size)
+      .map(|_| match values.next() {
+        None | Some("x") | Some("X") => Ok(None),
+        value => parse_value(value, "mapped degree").map(Some),
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(Self {
+      first_key,
+      last_key,
+      middle_key,
+      reference_key,
+      reference_freq,
+      octave_degree,
+      mapping,
+    })
+  }
+}
+
+fn parse_value<T: FromStr>(value: Option<&str>, name: &'static str) -> Result<T, ScalaError> {
+  let value = value.ok_or(ScalaError::Missing(name))?;
+  value
+    .parse()
+    .map_err(|_| ScalaError::Invalid(name, value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MEANTONE: &str = "! meanjust.scl
+!
+Meantone with just fifths
+ 3
+!
+ 200.0 whole tone
+ 3/2
+ 2
+";
+
+  #[test]
+  fn parse_a_scale() {
+    let scale = Scale::from_str(MEANTONE).unwrap();
+    assert_eq!(scale.description(), "Meantone with just fifths");
+    assert_eq!(
+      scale.pitches(),
+      &[
+        ScalePitch::Cents(200.0),
+        ScalePitch::Ratio(3, 2),
+        ScalePitch::Ratio(2, 1)
+      ]
+    );
+    assert!((scale.degree_cents(2) - 701.955).abs() < 1e-3);
+    assert!((scale.degree_cents(4) - 1400.0).abs() < 1e-6);
+    assert!((scale.degree_cents(-1) - -498.045).abs() < 1e-3);
+    assert_eq!(Scale::from_str(&scale.to_string()).unwrap(), scale);
+  }
+
+  #[test]
+  fn fail_to_parse_an_invalid_scale() {
+    assert!(matches!(
+      Scale::from_str("! empty\n"),
+      Err(ScalaError::Missing("description"))
+    ));
+    assert!(matches!(
+      Scale::from_str("Short\n2\n100.0\n"),
+      Err(ScalaError::Missing("pitch"))
+    ));
+    assert!(matches!(
+      Scale::from_str("Ratio\n1\n0/2\n"),
+      Err(ScalaError::Invalid("pitch", _))
+    ));
+  }
+
+  #[test]
+  fn map_the_keys_into_degrees() {
+    let scale = Scale::equal_division(12);
+    let mapping = KeyboardMapping::from_str(
+      "! white keys only
+7
+21
+108
+60
+69
+440.0
+12
+0
+x
+2
+x
+4
+5
+x
+",
+    )
+    .unwrap();
+    assert_eq!(mapping.mapping.len(), 7);
+    assert_eq!(mapping.degree(60, &scale), Some(0));
+    assert_eq!(mapping.degree(61, &scale), None);
+    assert_eq!(mapping.degree(67, &scale), Some(12));
+    assert_eq!(mapping.degree(59, &scale), None);
+    assert_eq!(mapping.degree(58, &scale), Some(-7));
+    assert_eq!(mapping.degree(20, &scale), None);
+    assert_eq!(
+      KeyboardMapping::from_str(&mapping.to_string()).unwrap(),
+      mapping
+    );
+  }
+}
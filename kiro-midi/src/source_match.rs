@@ -1,6 +1,6 @@
 use regex::Regex;
 
-use crate::endpoints::SourceId;
+use crate::endpoints::{EndpointProperties, SourceId};
 use crate::filter::Filter;
 
 #[derive(Debug, Clone)]
@@ -8,6 +8,9 @@ pub enum SourceMatch {
   Id(SourceId),
   Name(String),
   Regex(Regex),
+  Manufacturer(String),
+  Model(String),
+  DriverOwner(String),
 }
 
 impl SourceMatch {
@@ -15,11 +18,35 @@ impl SourceMatch {
     Regex::new(regex).map(Self::Regex)
   }
 
-  pub(crate) fn matches(&self, source_id: SourceId, source_name: &str) -> bool {
+  pub fn manufacturer(manufacturer: &str) -> Self {
+    Self::Manufacturer(manufacturer.to_string())
+  }
+
+  pub fn model(model: &str) -> Self {
+    Self::Model(model.to_string())
+  }
+
+  pub fn driver_owner(driver_owner: &str) -> Self {
+    Self::DriverOwner(driver_owner.to_string())
+  }
+
+  pub(crate) fn matches(
+    &self,
+    source_id: SourceId,
+    source_name: &str,
+    source_properties: &EndpointProperties,
+  ) -> bool {
     match self {
       Self::Id(id) => source_id == *id,
       Self::Name(name) => source_name == name.as_str(),
       Self::Regex(regex) => regex.is_match(source_name),
+      Self::Manufacturer(manufacturer) => {
+        source_properties.manufacturer.as_deref() == Some(manufacturer.as_str())
+      }
+      Self::Model(model) => source_properties.model.as_deref() == Some(model.as_str()),
+      Self::DriverOwner(driver_owner) => {
+        source_properties.driver_owner.as_deref() == Some(driver_owner.as_str())
+      }
     }
   }
 }
@@ -60,17 +87,74 @@ impl SourceMatches {
     self.0.push((source_match.into(), filter));
   }
 
-  pub fn match_filter(&self, id: SourceId, name: &str) -> Option<Filter> {
-    self
-      .0
-      .iter()
-      .find_map(|(source_match, filter)| source_match.matches(id, name).then(|| *filter))
+  pub fn match_filter(
+    &self,
+    id: SourceId,
+    name: &str,
+    properties: &EndpointProperties,
+  ) -> Option<Filter> {
+    self.0.iter().find_map(|(source_match, filter)| {
+      if source_match.matches(id, name, properties) {
+        Some(*filter)
+      } else {
+        None
+      }
+    })
   }
 
-  pub fn match_index(&self, id: SourceId, name: &str) -> Option<usize> {
+  pub fn match_index(
+    &self,
+    id: SourceId,
+    name: &str,
+    properties: &EndpointProperties,
+  ) -> Option<usize> {
     self
       .0
       .iter()
-      .position(|(source_match, _)| source_match.matches(id, name))
+      .position(|(source_match, _)| source_match.matches(id, name, properties))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn properties() -> EndpointProperties {
+    EndpointProperties {
+      manufacturer: Some("Arturia".to_string()),
+      model: Some("KeyStep 37".to_string()),
+      driver_owner: None,
+    }
+  }
+
+  #[test]
+  fn matches_properties() {
+    let properties = properties();
+    assert!(SourceMatch::manufacturer("Arturia").matches(1, "MIDI IN 2", &properties));
+    assert!(SourceMatch::model("KeyStep 37").matches(1, "MIDI IN 2", &properties));
+    assert!(!SourceMatch::model("KeyStep").matches(1, "MIDI IN 2", &properties));
+    assert!(
+      !SourceMatch::driver_owner("com.apple.AppleMIDIUSBDriver").matches(
+        1,
+        "MIDI IN 2",
+        &properties
+      )
+    );
+  }
+
+  #[test]
+  fn match_filter() {
+    let filter = Filter::new().with_groups(&[1]);
+    let matches = SourceMatches::default()
+      .with_source("Other", Filter::new())
+      .with_source(SourceMatch::manufacturer("Arturia"), filter);
+
+    let matched = matches.match_filter(1, "MIDI IN 2", &properties());
+    assert!(matches!(matched, Some(filter) if filter.group(0) && !filter.group(1)));
+    assert_eq!(matches.match_index(1, "MIDI IN 2", &properties()), Some(1));
+    assert_eq!(
+      matches.match_index(1, "MIDI IN 2", &EndpointProperties::default()),
+      None
+    );
   }
 }
@@ -0,0 +1,435 @@
+use std::collections::{HashMap, VecDeque};
+
+use thiserror::Error;
+
+use crate::devices::DeviceAction;
+
+/// Names of the built-in actions.
+pub mod names {
+  pub const TRANSPORT_PLAY: &str = "transport.play";
+  pub const TRANSPORT_STOP: &str = "transport.stop";
+  pub const TRANSPORT_RECORD: &str = "transport.record";
+  pub const TRACK_ADD: &str = "track.add";
+  pub const TRACK_RECORD_ARM: &str = "track.record-arm";
+  pub const EDIT_QUANTIZE: &str = "edit.quantize";
+  pub const DEVICE_TOGGLE: &str = "device.toggle";
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ActionError {
+  #[error("Unknown action: {0}")]
+  Unknown(String),
+
+  #[error("Action already registered: {0}")]
+  AlreadyRegistered(String),
+
+  #[error("Action {name} failed: {reason}")]
+  Failed { name: String, reason: String },
+}
+
+/// Control path an action was dispatched from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionSource {
+  KeyBinding,
+  ControlSurface,
+}
+
+/// Record of a dispatched action kept for auditing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionRecord {
+  pub name: String,
+  pub value: Option<f64>,
+  pub source: ActionSource,
+  pub succeeded: bool,
+}
+
+type ActionHandler<C> = Box<dyn FnMut(&mut C, Option<f64>) -> Result<(), String> + Send>;
+
+struct ActionEntry<C> {
+  description: String,
+  handler: ActionHandler<C>,
+}
+
+/// Registry of named actions, so that every control path goes through the same dispatch.
+///
+/// The handlers receive a mutable reference to the context `C` they act upon,
+/// and an optional normalized value (for faders, knobs, ...).
+pub struct ActionRegistry<C> {
+  actions: HashMap<String, ActionEntry<C>>,
+  history: VecDeque<ActionRecord>,
+  history_capacity: usize,
+}
+
+impl<C> ActionRegistry<C> {
+  pub const DEFAULT_HISTORY_CAPACITY: usize = 256;
+
+  pub fn new() -> Self {
+    Self {
+      actions: HashMap::new(),
+      history: VecDeque::new(),
+      history_capacity: Self::DEFAULT_HISTORY_CAPACITY,
+    }
+  }
+
+  #[must_use]
+  pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+    self.history_capacity = capacity;
+    self.history.truncate(capacity);
+    self
+  }
+
+  pub fn register<F>(
+    &mut self,
+    name: &str,
+    description: &str,
+    handler: F,
+  ) -> Result<(), ActionError>
+  where
+    F: FnMut(&mut C, Option<f64>) -> Result<(), String> + Send + 'static,
+  {
+    if self.actions.contains_key(name) {
+      Err(ActionError::AlreadyRegistered(name.to_string()))
+    } else {
+      self.actions.insert(
+        name.to_string(),
+        ActionEntry {
+          description: description.to_string(),
+          handler: Box::new(handler),
+        },
+      );
+      Ok(())
+    }
+  }
+
+  pub fn unregister(&mut self, name: &str) -> bool {
+    self.actions.remove(name).is_some()
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.actions.contains_key(name)
+  }
+
+  /// Names of the registered actions sorted alphabetically, along with their descriptions.
+  pub fn actions(&self) -> Vec<(&str, &str)> {
+    let mut actions = self
+      .actions
+      .iter()
+      .map(|(name, entry)| (name.as_str(), entry.description.as_str()))
+      .collect::<Vec<(&str, &str)>>();
+    actions.sort_unstable();
+    actions
+  }
+
+  pub fn dispatch(
+    &mut self,
+    context: &mut C,
+    name: &str,
+    value: Option<f64>,
+    source: ActionSource,
+  ) -> Result<(), ActionError> {
+    let result = match self.actions.get_mut(name) {
+      Some(entry) => (entry.handler)(context, value).map_err(|reason| ActionError::Failed {
+        name: name.to_string(),
+        reason,
+      }),
+      None => Err(ActionError::Unknown(name.to_string())),
+    };
+
+    self.record(ActionRecord {
+      name: name.to_string(),
+      value,
+      source,
+      succeeded: result.is_ok(),
+    });
+
+    result
+  }
+
+  /// Dispatch an action requested by a control surface script.
+  pub fn dispatch_device_action(
+    &mut self,
+    context: &mut C,
+    action: &DeviceAction,
+  ) -> Result<(), ActionError> {
+    match action {
+      DeviceAction::Trigger(name) => {
+        self.dispatch(context, name, None, ActionSource::ControlSurface)
+      }
+      DeviceAction::SetValue { name, value } => {
+        self.dispatch(context, name, Some(*value), ActionSource::ControlSurface)
+      }
+    }
+  }
+
+  /// Most recent dispatched actions, from the oldest to the newest.
+  pub fn history(&self) -> impl Iterator<Item = &ActionRecord> {
+    self.history.iter()
+  }
+
+  fn record(&mut self, record: ActionRecord) {
+    if self.history_capacity > 0 {
+      if self.history.len() == self.history_capacity {
+        self.history.pop_front();
+      }
+      self.history.push_back(record);
+    }
+  }
+}
+
+impl<C> Default for ActionRegistry<C> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Key bindings, from key combinations (ex. `ctrl+shift+r`) to action names.
+///
+/// The key combinations are normalized, so the order of the modifiers and the case don't matter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyBindings {
+  bindings: HashMap<String, String>,
+}
+
+impl KeyBindings {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[must_use]
+  pub fn with_binding(mut self, keys: &str, action: &str) -> Self {
+    self.bind(keys, action);
+    self
+  }
+
+  pub fn bind(&mut self, keys: &str, action: &str) {
+    self
+      .bindings
+      .insert(Self::normalize(keys), action.to_string());
+  }
+
+  pub fn unbind(&mut self, keys: &str) -> Option<String> {
+    self.bindings.remove(&Self::normalize(keys))
+  }
+
+  pub fn action(&self, keys: &str) -> Option<&str> {
+    self
+      .bindings
+      .get(&Self::normalize(keys))
+      .map(String::as_str)
+  }
+
+  /// Dispatch the action bound to the key combination, returning whether there was any.
+  pub fn dispatch<C>(
+    &self,
+    registry: &mut ActionRegistry<C>,
+    context: &mut C,
+    keys: &str,
+  ) -> Result<bool, ActionError> {
+    match self.action(keys) {
+      Some(action) => registry
+        .dispatch(context, action, None, ActionSource::KeyBinding)
+        .map(|_| true),
+      None => Ok(false),
+    }
+  }
+
+  fn normalize(keys: &str) -> String {
+    let mut parts = keys
+      .split('+')
+      .map(|part| part.trim().to_lowercase())
+      .filter(|part| !part.is_empty())
+      .collect::<Vec<String>>();
+    // keep the main key at the end and sort the modifiers
+    let key = parts.pop();
+    parts.sort_unstable();
+    parts.dedup();
+    parts.extend(key);
+    parts.join("+")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct Context {
+    playing: bool,
+    volume: f64,
+  }
+
+  fn registry() -> ActionRegistry<Context> {
+    let mut registry = ActionRegistry::new();
+    registry
+      .register(
+        names::TRANSPORT_PLAY,
+        "Start playing",
+        |ctx: &mut Context, _| {
+          ctx.playing = true;
+          Ok(())
+        },
+      )
+      .unwrap();
+    registry
+      .register(
+        "mixer.volume",
+        "Master volume",
+        |ctx: &mut Context, value| {
+          ctx.volume = value.ok_or_else(|| "Missing value".to_string())?;
+          Ok(())
+        },
+      )
+      .unwrap();
+    registry
+  }
+
+  #[test]
+  fn register_and_dispatch() {
+    let mut registry = registry();
+    let mut context = Context::default();
+
+    assert!(registry.contains(names::TRANSPORT_PLAY));
+    assert_eq!(
+      registry.register(names::TRANSPORT_PLAY, "", |_, _| Ok(())),
+      Err(ActionError::AlreadyRegistered(
+        names::TRANSPORT_PLAY.to_string()
+      ))
+    );
+    assert_eq!(
+      registry.actions(),
+      vec![
+        ("mixer.volume", "Master volume"),
+        (names::TRANSPORT_PLAY, "Start playing")
+      ]
+    );
+
+    registry
+      .dispatch(
+        &mut context,
+        names::TRANSPORT_PLAY,
+        None,
+        ActionSource::KeyBinding,
+      )
+      .unwrap();
+    assert!(context.playing);
+
+    assert_eq!(
+      registry.dispatch(
+        &mut context,
+        "transport.rewind",
+        None,
+        ActionSource::KeyBinding
+      ),
+      Err(ActionError::Unknown("transport.rewind".to_string()))
+    );
+    assert_eq!(
+      registry.dispatch(
+        &mut context,
+        "mixer.volume",
+        None,
+        ActionSource::ControlSurface
+      ),
+      Err(ActionError::Failed {
+        name: "mixer.volume".to_string(),
+        reason: "Missing value".to_string()
+      })
+    );
+  }
+
+  #[test]
+  fn dispatch_device_actions() {
+    let mut registry = registry();
+    let mut context = Context::default();
+
+    let actions = [
+      DeviceAction::Trigger(names::TRANSPORT_PLAY.to_string()),
+      DeviceAction::SetValue {
+        name: "mixer.volume".to_string(),
+        value: 0.5,
+      },
+    ];
+    for action in actions.iter() {
+      registry
+        .dispatch_device_action(&mut context, action)
+        .unwrap();
+    }
+
+    assert!(context.playing);
+    assert_eq!(context.volume, 0.5);
+  }
+
+  #[test]
+  fn history_is_bounded() {
+    let mut registry = registry().with_history_capacity(2);
+    let mut context = Context::default();
+
+    registry
+      .dispatch(
+        &mut context,
+        names::TRANSPORT_PLAY,
+        None,
+        ActionSource::KeyBinding,
+      )
+      .unwrap();
+    registry
+      .dispatch(
+        &mut context,
+        "mixer.volume",
+        Some(0.1),
+        ActionSource::ControlSurface,
+      )
+      .unwrap();
+    registry
+      .dispatch(&mut context, "unknown", None, ActionSource::KeyBinding)
+      .ok();
+
+    let history = registry.history().cloned().collect::<Vec<ActionRecord>>();
+    assert_eq!(
+      history,
+      vec![
+        ActionRecord {
+          name: "mixer.volume".to_string(),
+          value: Some(0.1),
+          source: ActionSource::ControlSurface,
+          succeeded: true,
+        },
+        ActionRecord {
+          name: "unknown".to_string(),
+          value: None,
+          source: ActionSource::KeyBinding,
+          succeeded: false,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn key_bindings() {
+    let mut registry = registry();
+    let mut context = Context::default();
+    let bindings = KeyBindings::new().with_binding("Shift+Ctrl+Space", names::TRANSPORT_PLAY);
+
+    assert_eq!(
+      bindings.action("ctrl + shift + space"),
+      Some(names::TRANSPORT_PLAY)
+    );
+    assert_eq!(bindings.action("space"), None);
+
+    assert_eq!(
+      bindings.dispatch(&mut registry, &mut context, "space"),
+      Ok(false)
+    );
+    assert_eq!(
+      bindings.dispatch(&mut registry, &mut context, "ctrl+shift+space"),
+      Ok(true)
+    );
+    assert!(context.playing);
+    assert_eq!(
+      registry
+        .history()
+        .last()
+        .map(|record| record.source.clone()),
+      Some(ActionSource::KeyBinding)
+    );
+  }
+}
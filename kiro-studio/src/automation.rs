@@ -0,0 +1,238 @@
+use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+use kiro_midi::messages::{Message, MessageType};
+use kiro_midi::{Event, TimestampNanos};
+
+/// MIDI 2.0 controller an automation lane is exchanged with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerAddress {
+  Registered { bank: u8, index: u8 },
+  Assignable { bank: u8, index: u8 },
+}
+
+impl ControllerAddress {
+  fn message(&self, data: u32) -> ChannelVoiceMessage {
+    match *self {
+      Self::Registered { bank, index } => {
+        ChannelVoiceMessage::RegisteredController { bank, index, data }
+      }
+      Self::Assignable { bank, index } => {
+        ChannelVoiceMessage::AssignableController { bank, index, data }
+      }
+    }
+  }
+
+  fn data(&self, message: &ChannelVoiceMessage) -> Option<u32> {
+    match (*self, *message) {
+      (
+        Self::Registered { bank, index },
+        ChannelVoiceMessage::RegisteredController {
+          bank: msg_bank,
+          index: msg_index,
+          data,
+        },
+      )
+      | (
+        Self::Assignable { bank, index },
+        ChannelVoiceMessage::AssignableController {
+          bank: msg_bank,
+          index: msg_index,
+          data,
+        },
+      ) if bank == msg_bank && index == msg_index => Some(data),
+      _ => None,
+    }
+  }
+}
+
+/// Automation point with a normalized value between 0.0 and 1.0.
+///
+/// The value is kept with enough precision to round-trip the 32 bits of controller data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutomationPoint {
+  pub timestamp: TimestampNanos,
+  pub value: f64,
+}
+
+impl AutomationPoint {
+  pub fn new(timestamp: TimestampNanos, value: f64) -> Self {
+    Self { timestamp, value }
+  }
+
+  pub fn from_data(timestamp: TimestampNanos, data: u32) -> Self {
+    Self::new(timestamp, f64::from(data) / f64::from(u32::MAX))
+  }
+
+  pub fn data(&self) -> u32 {
+    (self.value.clamp(0.0, 1.0) * f64::from(u32::MAX)).round() as u32
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutomationLane {
+  pub group: u8,
+  pub channel: u8,
+  pub controller: ControllerAddress,
+  points: Vec<AutomationPoint>,
+}
+
+impl AutomationLane {
+  pub fn new(group: u8, channel: u8, controller: ControllerAddress) -> Self {
+    Self {
+      group,
+      channel,
+      controller,
+      points: Vec::new(),
+    }
+  }
+
+  /// Build a lane from the controller messages found in a recorded stream of events.
+  pub fn from_events<'a, I>(
+    group: u8,
+    channel: u8,
+    controller: ControllerAddress,
+    events: I,
+  ) -> Self
+  where
+    I: IntoIterator<Item = &'a Event>,
+  {
+    let mut lane = Self::new(group, channel, controller);
+    for event in events {
+      lane.record(event);
+    }
+    lane
+  }
+
+  pub fn points(&self) -> &[AutomationPoint] {
+    self.points.as_slice()
+  }
+
+  /// Add a point keeping them sorted by timestamp.
+  pub fn add_point(&mut self, point: AutomationPoint) {
+    let index = self
+      .points
+      .partition_point(|other| other.timestamp <= point.timestamp);
+    self.points.insert(index, point);
+  }
+
+  /// Record the event as a new point if it is a message for the controller of this lane.
+  /// Returns whether it was recorded or not.
+  pub fn record(&mut self, event: &Event) -> bool {
+    match event.message {
+      Message {
+        group,
+        mtype: MessageType::ChannelVoice(ChannelVoice { channel, message }),
+      } if group == self.group && channel == self.channel => match self.controller.data(&message) {
+        Some(data) => {
+          self.add_point(AutomationPoint::from_data(event.timestamp, data));
+          true
+        }
+        None => false,
+      },
+      _ => false,
+    }
+  }
+
+  /// Convert the points into controller messages.
+  pub fn to_messages(&self) -> Vec<(TimestampNanos, Message)> {
+    self
+      .points
+      .iter()
+      .map(|point| {
+        let message = self.controller.message(point.data());
+        let message = Message::channel_voice(self.group, self.channel, message);
+        (point.timestamp, message)
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CONTROLLER: ControllerAddress = ControllerAddress::Assignable { bank: 1, index: 7 };
+
+  fn event(timestamp: TimestampNanos, channel: u8, message: ChannelVoiceMessage) -> Event {
+    Event {
+      timestamp,
+      endpoint: 0,
+      message: Message::channel_voice(0, channel, message),
+      jr_timestamp: None,
+    }
+  }
+
+  #[test]
+  fn point_data_round_trip() {
+    for data in [0, 1, 0x7fffffff, 0x80000000, 0xfffffffe, u32::MAX] {
+      assert_eq!(AutomationPoint::from_data(0, data).data(), data);
+    }
+  }
+
+  #[test]
+  fn add_point_keeps_order() {
+    let mut lane = AutomationLane::new(0, 0, CONTROLLER);
+    lane.add_point(AutomationPoint::new(20, 0.2));
+    lane.add_point(AutomationPoint::new(10, 0.1));
+    lane.add_point(AutomationPoint::new(30, 0.3));
+    let timestamps = lane
+      .points()
+      .iter()
+      .map(|point| point.timestamp)
+      .collect::<Vec<_>>();
+    assert_eq!(timestamps, vec![10, 20, 30]);
+  }
+
+  #[test]
+  fn from_events_and_back() {
+    let events = [
+      event(
+        10,
+        2,
+        ChannelVoiceMessage::AssignableController {
+          bank: 1,
+          index: 7,
+          data: 0x12345678,
+        },
+      ),
+      event(
+        20,
+        2,
+        ChannelVoiceMessage::RegisteredController {
+          bank: 1,
+          index: 7,
+          data: 0x1,
+        },
+      ),
+      event(
+        30,
+        3,
+        ChannelVoiceMessage::AssignableController {
+          bank: 1,
+          index: 7,
+          data: 0x1,
+        },
+      ),
+      event(
+        40,
+        2,
+        ChannelVoiceMessage::AssignableController {
+          bank: 1,
+          index: 7,
+          data: 0xffffffff,
+        },
+      ),
+    ];
+
+    let lane = AutomationLane::from_events(0, 2, CONTROLLER, events.iter());
+    assert_eq!(lane.points().len(), 2);
+
+    let messages = lane.to_messages();
+    assert_eq!(
+      messages,
+      vec![
+        (events[0].timestamp, events[0].message),
+        (events[3].timestamp, events[3].message),
+      ]
+    );
+  }
+}
@@ -1,8 +1,15 @@
 pub mod midi;
 
+use std::path::PathBuf;
+
 use crate::config::midi::MidiConfig;
 
 #[derive(Debug, Clone, Default)]
 pub struct Config {
   pub midi: MidiConfig,
+  /// Device scripts to load, which need the `rhai` feature
+  pub device_scripts: Vec<PathBuf>,
+  /// Start without loading the device scripts,
+  /// so the studio can still be opened when one of them fails or misbehaves.
+  pub safe_mode: bool,
 }
@@ -0,0 +1,201 @@
+#[cfg(feature = "rhai")]
+mod rhai_script;
+
+use std::collections::HashMap;
+
+use kiro_midi::endpoints::{SourceId, SourceInfo};
+use kiro_midi::messages::Message;
+use kiro_midi::Event;
+
+#[cfg(feature = "rhai")]
+pub use rhai_script::RhaiDeviceScript;
+
+/// Action requested to the studio by a device script
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceAction {
+  /// Trigger a named action (ex. `transport.play`)
+  Trigger(String),
+  /// Set a named value (ex. `mixer.volume`) with a normalized value between 0.0 and 1.0
+  SetValue { name: String, value: f64 },
+}
+
+/// State of the studio sent back to the devices (LEDs, displays, motorized faders, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feedback {
+  Value { name: String, value: f64 },
+  Text { name: String, text: String },
+}
+
+/// Integration for a specific controller,
+/// translating its vocabulary into studio actions and the studio state into feedback messages.
+pub trait DeviceScript: Send {
+  fn name(&self) -> &str;
+
+  /// Whether this script handles the given source.
+  fn matches(&self, source: &SourceInfo) -> bool;
+
+  fn handle_message(&mut self, message: &Message) -> Vec<DeviceAction>;
+
+  fn feedback(&mut self, _feedback: &Feedback) -> Vec<Message> {
+    Vec::new()
+  }
+}
+
+/// Registry of device scripts bound to the sources they match.
+#[derive(Default)]
+pub struct DeviceScripts {
+  scripts: Vec<Box<dyn DeviceScript>>,
+  bindings: HashMap<SourceId, Vec<usize>>,
+}
+
+impl DeviceScripts {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn register<S>(&mut self, script: S)
+  where
+    S: DeviceScript + 'static,
+  {
+    self.scripts.push(Box::new(script));
+  }
+
+  pub fn names(&self) -> Vec<&str> {
+    self.scripts.iter().map(|script| script.name()).collect()
+  }
+
+  /// Bind the scripts to the sources currently available.
+  pub fn update_sources(&mut self, sources: &[SourceInfo]) {
+    self.bindings = sources
+      .iter()
+      .filter_map(|source| {
+        let indices = self
+          .scripts
+          .iter()
+          .enumerate()
+          .filter(|(_, script)| script.matches(source))
+          .map(|(index, _)| index)
+          .collect::<Vec<usize>>();
+        if indices.is_empty() {
+          None
+        } else {
+          Some((source.id, indices))
+        }
+      })
+      .collect();
+  }
+
+  pub fn handle_event(&mut self, event: &Event) -> Vec<DeviceAction> {
+    match self.bindings.get(&event.endpoint) {
+      Some(indices) => indices
+        .iter()
+        .flat_map(|index| self.scripts[*index].handle_message(&event.message))
+        .collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// Collect the feedback messages for the devices of all the bound scripts.
+  pub fn feedback(&mut self, feedback: &Feedback) -> Vec<(SourceId, Message)> {
+    let mut messages = Vec::new();
+    for (source_id, indices) in self.bindings.iter() {
+      for index in indices {
+        for message in self.scripts[*index].feedback(feedback) {
+          messages.push((*source_id, message));
+        }
+      }
+    }
+    messages
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use kiro_midi::endpoints::EndpointProperties;
+  use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+  use kiro_midi::messages::MessageType;
+
+  struct TransportScript;
+
+  impl DeviceScript for TransportScript {
+    fn name(&self) -> &str {
+      "transport"
+    }
+
+    fn matches(&self, source: &SourceInfo) -> bool {
+      source.properties.manufacturer.as_deref() == Some("Kiro")
+    }
+
+    fn handle_message(&mut self, message: &Message) -> Vec<DeviceAction> {
+      match message.mtype {
+        MessageType::ChannelVoice(channel_voice) => match channel_voice.message {
+          ChannelVoiceMessage::ControlChange { index: 0x10, data } if data > 0 => {
+            vec![DeviceAction::Trigger("transport.play".to_string())]
+          }
+          _ => Vec::new(),
+        },
+        _ => Vec::new(),
+      }
+    }
+
+    fn feedback(&mut self, feedback: &Feedback) -> Vec<Message> {
+      match feedback {
+        Feedback::Value { name, value } if name == "transport.playing" => {
+          let data = if *value > 0.0 { u32::MAX } else { 0 };
+          vec![Message::channel_voice(
+            0,
+            0,
+            ChannelVoiceMessage::ControlChange { index: 0x10, data },
+          )]
+        }
+        _ => Vec::new(),
+      }
+    }
+  }
+
+  fn source(id: SourceId, manufacturer: &str) -> SourceInfo {
+    let properties = EndpointProperties {
+      manufacturer: Some(manufacturer.to_string()),
+      ..EndpointProperties::default()
+    };
+    SourceInfo::new(id, format!("source-{}", id), properties, None, Vec::new())
+  }
+
+  fn control_change(endpoint: SourceId, index: u8, data: u32) -> Event {
+    Event {
+      timestamp: 0,
+      endpoint,
+      message: Message::channel_voice(0, 0, ChannelVoiceMessage::ControlChange { index, data }),
+      jr_timestamp: None,
+    }
+  }
+
+  #[test]
+  fn actions_from_bound_sources() {
+    let mut scripts = DeviceScripts::new();
+    scripts.register(TransportScript);
+    scripts.update_sources(&[source(1, "Kiro"), source(2, "Other")]);
+
+    assert_eq!(
+      scripts.handle_event(&control_change(1, 0x10, 1)),
+      vec![DeviceAction::Trigger("transport.play".to_string())]
+    );
+    assert!(scripts.handle_event(&control_change(1, 0x11, 1)).is_empty());
+    assert!(scripts.handle_event(&control_change(2, 0x10, 1)).is_empty());
+  }
+
+  #[test]
+  fn feedback_to_bound_sources() {
+    let mut scripts = DeviceScripts::new();
+    scripts.register(TransportScript);
+    scripts.update_sources(&[source(1, "Kiro"), source(2, "Other")]);
+
+    let messages = scripts.feedback(&Feedback::Value {
+      name: "transport.playing".to_string(),
+      value: 1.0,
+    });
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0, 1);
+  }
+}
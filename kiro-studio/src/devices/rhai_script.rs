@@ -0,0 +1,232 @@
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+
+use kiro_midi::endpoints::SourceInfo;
+use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+use kiro_midi::messages::{Message, MessageType};
+
+use crate::devices::{DeviceAction, DeviceScript, Feedback};
+use crate::errors::{Error, Result};
+
+/// Device script written in [rhai](https://rhai.rs).
+///
+/// The script can define any of the following functions:
+///
+/// - `matches(name, manufacturer, model)`: whether the script handles a source.
+/// - `on_cc(group, channel, index, value)`: returns an array of actions for a control change.
+/// - `on_note(group, channel, note, velocity)`: returns an array of actions for a note on.
+/// - `on_value(name, value)` and `on_text(name, text)`: return an array of feedback messages.
+///
+/// Actions are created with `trigger(name)` and `set_value(name, value)`,
+/// and feedback messages with `cc(group, channel, index, value)`.
+/// Values are always normalized between 0.0 and 1.0.
+pub struct RhaiDeviceScript {
+  name: String,
+  engine: Engine,
+  ast: AST,
+  last_error: Option<String>,
+}
+
+impl RhaiDeviceScript {
+  pub fn new(name: &str, script: &str) -> Result<Self> {
+    let engine = Self::create_engine();
+    let ast = engine
+      .compile(script)
+      .map_err(|err| Error::Script(err.to_string()))?;
+
+    Ok(Self {
+      name: name.to_string(),
+      engine,
+      ast,
+      last_error: None,
+    })
+  }
+
+  /// Last error found while running the script, if any.
+  pub fn last_error(&self) -> Option<&str> {
+    self.last_error.as_deref()
+  }
+
+  fn create_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+      .register_type_with_name::<DeviceAction>("DeviceAction")
+      .register_fn("trigger", |name: &str| {
+        DeviceAction::Trigger(name.to_string())
+      })
+      .register_fn("set_value", |name: &str, value: f64| {
+        DeviceAction::SetValue {
+          name: name.to_string(),
+          value,
+        }
+      })
+      .register_type_with_name::<Message>("Message")
+      .register_fn("cc", |group: i64, channel: i64, index: i64, value: f64| {
+        Message::channel_voice(
+          group as u8,
+          channel as u8,
+          ChannelVoiceMessage::ControlChange {
+            index: index as u8,
+            data: (value.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32,
+          },
+        )
+      });
+    engine
+  }
+
+  fn has_function(&self, name: &str) -> bool {
+    self
+      .ast
+      .iter_functions()
+      .any(|function| function.name == name)
+  }
+
+  fn call<T, A>(&mut self, name: &str, args: A) -> Option<T>
+  where
+    T: Clone + Send + Sync + 'static,
+    A: rhai::FuncArgs,
+  {
+    if self.has_function(name) {
+      match self
+        .engine
+        .call_fn::<T>(&mut Scope::new(), &self.ast, name, args)
+      {
+        Ok(result) => Some(result),
+        Err(err) => {
+          self.last_error = Some(err.to_string());
+          None
+        }
+      }
+    } else {
+      None
+    }
+  }
+
+  fn collect<T: Clone + 'static>(array: Option<Array>) -> Vec<T> {
+    array
+      .unwrap_or_default()
+      .into_iter()
+      .filter_map(Dynamic::try_cast::<T>)
+      .collect()
+  }
+}
+
+impl DeviceScript for RhaiDeviceScript {
+  fn name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn matches(&self, source: &SourceInfo) -> bool {
+    let properties = &source.properties;
+    let args = (
+      source.name.clone(),
+      properties.manufacturer.clone().unwrap_or_default(),
+      properties.model.clone().unwrap_or_default(),
+    );
+    self.has_function("matches")
+      && self
+        .engine
+        .call_fn::<bool>(&mut Scope::new(), &self.ast, "matches", args)
+        .unwrap_or(false)
+  }
+
+  fn handle_message(&mut self, message: &Message) -> Vec<DeviceAction> {
+    let group = message.group as i64;
+    let actions = match message.mtype {
+      MessageType::ChannelVoice(channel_voice) => {
+        let channel = channel_voice.channel as i64;
+        match channel_voice.message {
+          ChannelVoiceMessage::ControlChange { index, data } => {
+            let value = data as f64 / u32::MAX as f64;
+            self.call::<Array, _>("on_cc", (group, channel, index as i64, value))
+          }
+          ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+            let velocity = velocity as f64 / u16::MAX as f64;
+            self.call::<Array, _>("on_note", (group, channel, note as i64, velocity))
+          }
+          _ => None,
+        }
+      }
+      _ => None,
+    };
+    Self::collect(actions)
+  }
+
+  fn feedback(&mut self, feedback: &Feedback) -> Vec<Message> {
+    let messages = match feedback {
+      Feedback::Value { name, value } => self.call::<Array, _>("on_value", (name.clone(), *value)),
+      Feedback::Text { name, text } => {
+        self.call::<Array, _>("on_text", (name.clone(), text.clone()))
+      }
+    };
+    Self::collect(messages)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use kiro_midi::endpoints::EndpointProperties;
+
+  const SCRIPT: &str = r#"
+    fn matches(name, manufacturer, model) {
+      manufacturer == "Kiro" && model == "K1"
+    }
+
+    fn on_cc(group, channel, index, value) {
+      if index == 7 {
+        [set_value("mixer.volume", value)]
+      } else if index == 16 && value > 0.5 {
+        [trigger("transport.play")]
+      } else {
+        []
+      }
+    }
+
+    fn on_value(name, value) {
+      if name == "transport.playing" { [cc(0, 0, 16, value)] } else { [] }
+    }
+  "#;
+
+  #[test]
+  fn matches_source() {
+    let script = RhaiDeviceScript::new("k1", SCRIPT).unwrap();
+    let properties = EndpointProperties {
+      manufacturer: Some("Kiro".to_string()),
+      model: Some("K1".to_string()),
+      driver_owner: None,
+    };
+    let source = SourceInfo::new(1, "K1".to_string(), properties, None, Vec::new());
+    assert!(script.matches(&source));
+    let source = SourceInfo::new(2, "X".to_string(), Default::default(), None, Vec::new());
+    assert!(!script.matches(&source));
+  }
+
+  #[test]
+  fn actions_and_feedback() {
+    let mut script = RhaiDeviceScript::new("k1", SCRIPT).unwrap();
+    let message = Message::channel_voice(
+      0,
+      0,
+      ChannelVoiceMessage::ControlChange {
+        index: 16,
+        data: u32::MAX,
+      },
+    );
+    assert_eq!(
+      script.handle_message(&message),
+      vec![DeviceAction::Trigger("transport.play".to_string())]
+    );
+
+    let messages = script.feedback(&Feedback::Value {
+      name: "transport.playing".to_string(),
+      value: 1.0,
+    });
+    assert_eq!(messages, vec![message]);
+    assert_eq!(script.last_error(), None);
+  }
+
+  #[test]
+  fn invalid_script() {
+    assert!(RhaiDeviceScript::new("broken", "fn on_cc(").is_err());
+  }
+}
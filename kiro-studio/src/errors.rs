@@ -10,6 +10,10 @@ pub enum Error {
 
   #[error("Audio: {0}")]
   Audio(#[from] audio::AudioError),
+
+  #[cfg(feature = "rhai")]
+  #[error("Script: {0}")]
+  Script(String),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
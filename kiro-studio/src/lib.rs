@@ -1,4 +1,8 @@
+pub mod actions;
+pub mod automation;
 pub mod config;
+pub mod devices;
 pub mod errors;
 pub mod platform;
+pub mod project;
 pub mod studio;
@@ -0,0 +1,309 @@
+use std::fmt::{Display, Formatter};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// Color used by the frontends to present tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+}
+
+impl Color {
+  pub fn new(r: u8, g: u8, b: u8) -> Self {
+    Self { r, g, b }
+  }
+
+  /// Parse a color in the `#rrggbb` form (the `#` is optional).
+  pub fn from_hex(hex: &str) -> Option<Self> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() == 6 && hex.is_ascii() {
+      let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+      Some(Self::new(component(0)?, component(2)?, component(4)?))
+    } else {
+      None
+    }
+  }
+}
+
+impl Display for Color {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackInfo {
+  pub name: String,
+  pub color: Option<Color>,
+  /// Name of the icon, interpreted by the frontend
+  pub icon: Option<String>,
+  pub notes: String,
+  /// Record the inputs of the track when recording
+  pub armed: bool,
+}
+
+impl TrackInfo {
+  pub fn new(name: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      color: None,
+      icon: None,
+      notes: String::new(),
+      armed: false,
+    }
+  }
+
+  #[must_use]
+  pub fn with_color(mut self, color: Color) -> Self {
+    self.color = Some(color);
+    self
+  }
+
+  #[must_use]
+  pub fn with_icon(mut self, icon: &str) -> Self {
+    self.icon = Some(icon.to_string());
+    self
+  }
+
+  #[must_use]
+  pub fn with_notes(mut self, notes: &str) -> Self {
+    self.notes = notes.to_string();
+    self
+  }
+}
+
+/// User facing information about a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+  pub title: String,
+  pub author: String,
+  pub notes: String,
+  tags: Vec<String>,
+  created: SystemTime,
+  modified: SystemTime,
+}
+
+impl SessionInfo {
+  pub fn new(title: &str) -> Self {
+    let now = SystemTime::now();
+    Self {
+      title: title.to_string(),
+      author: String::new(),
+      notes: String::new(),
+      tags: Vec::new(),
+      created: now,
+      modified: now,
+    }
+  }
+
+  #[must_use]
+  pub fn with_author(mut self, author: &str) -> Self {
+    self.author = author.to_string();
+    self
+  }
+
+  pub fn created(&self) -> SystemTime {
+    self.created
+  }
+
+  pub fn modified(&self) -> SystemTime {
+    self.modified
+  }
+
+  pub fn tags(&self) -> &[String] {
+    self.tags.as_slice()
+  }
+
+  pub fn has_tag(&self, tag: &str) -> bool {
+    self.tags.iter().any(|t| t == tag)
+  }
+
+  /// Add a tag if not already there, returning whether it was added.
+  pub fn add_tag(&mut self, tag: &str) -> bool {
+    if self.has_tag(tag) {
+      false
+    } else {
+      self.tags.push(tag.to_string());
+      true
+    }
+  }
+
+  /// Remove a tag, returning whether it was there.
+  pub fn remove_tag(&mut self, tag: &str) -> bool {
+    let len = self.tags.len();
+    self.tags.retain(|t| t != tag);
+    self.tags.len() != len
+  }
+}
+
+/// Project document with the session and tracks metadata.
+///
+/// Every change made through it updates the modification time of the session.
+/// It serializes with serde, so the frontends can read the metadata from the document itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+  info: SessionInfo,
+  tracks: Vec<TrackInfo>,
+}
+
+impl Project {
+  pub fn new(info: SessionInfo) -> Self {
+    Self {
+      info,
+      tracks: Vec::new(),
+    }
+  }
+
+  pub fn info(&self) -> &SessionInfo {
+    &self.info
+  }
+
+  pub fn update_info<F>(&mut self, f: F)
+  where
+    F: FnOnce(&mut SessionInfo),
+  {
+    f(&mut self.info);
+    self.touch();
+  }
+
+  pub fn tracks(&self) -> &[TrackInfo] {
+    self.tracks.as_slice()
+  }
+
+  pub fn track(&self, index: usize) -> Option<&TrackInfo> {
+    self.tracks.get(index)
+  }
+
+  /// Add a track returning its index.
+  pub fn add_track(&mut self, track: TrackInfo) -> usize {
+    self.tracks.push(track);
+    self.touch();
+    self.tracks.len() - 1
+  }
+
+  pub fn remove_track(&mut self, index: usize) -> Option<TrackInfo> {
+    if index < self.tracks.len() {
+      self.touch();
+      Some(self.tracks.remove(index))
+    } else {
+      None
+    }
+  }
+
+  /// Update a track returning whether it exists.
+  pub fn update_track<F>(&mut self, index: usize, f: F) -> bool
+  where
+    F: FnOnce(&mut TrackInfo),
+  {
+    match self.tracks.get_mut(index) {
+      Some(track) => {
+        f(track);
+        self.touch();
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn touch(&mut self) {
+    self.info.modified = SystemTime::now().max(self.info.modified);
+  }
+}
+
+impl Default for Project {
+  fn default() -> Self {
+    Self::new(SessionInfo::new("Untitled"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn color_hex() {
+    assert_eq!(Color::from_hex("#ff8000"), Some(Color::new(255, 128, 0)));
+    assert_eq!(Color::from_hex("0a0B0c"), Some(Color::new(10, 11, 12)));
+    assert_eq!(Color::from_hex("#ff80"), None);
+    assert_eq!(Color::from_hex("#gg8000"), None);
+    assert_eq!(Color::new(255, 128, 0).to_string(), "#ff8000");
+  }
+
+  #[test]
+  fn session_tags() {
+    let mut info = SessionInfo::new("Demo").with_author("Christian");
+    assert!(info.add_tag("ambient"));
+    assert!(!info.add_tag("ambient"));
+    assert!(info.add_tag("live"));
+    assert_eq!(info.tags(), &["ambient".to_string(), "live".to_string()]);
+    assert!(info.remove_tag("ambient"));
+    assert!(!info.remove_tag("ambient"));
+    assert!(!info.has_tag("ambient"));
+    assert_eq!(info.author, "Christian");
+  }
+
+  #[test]
+  fn tracks_metadata() {
+    let mut project = Project::default();
+    let index = project.add_track(
+      TrackInfo::new("Drums")
+        .with_color(Color::new(200, 0, 0))
+        .with_icon("drums"),
+    );
+
+    assert!(project.update_track(index, |track| track.notes = "Needs a new kick".to_string()));
+    assert!(!project.update_track(index + 1, |_| {}));
+
+    let track = project.track(index).unwrap();
+    assert_eq!(track.color, Some(Color::new(200, 0, 0)));
+    assert_eq!(track.icon.as_deref(), Some("drums"));
+    assert_eq!(track.notes, "Needs a new kick");
+
+    assert_eq!(
+      project.remove_track(index).map(|track| track.name),
+      Some("Drums".to_string())
+    );
+    assert_eq!(project.remove_track(index), None);
+  }
+
+  #[test]
+  fn changes_update_modification_time() {
+    let mut project = Project::default();
+    let created = project.info().created();
+    project.update_info(|info| info.notes = "Second take".to_string());
+    assert!(project.info().modified() >= created);
+    assert_eq!(project.info().created(), created);
+    assert_eq!(project.info().notes, "Second take");
+  }
+
+  #[test]
+  fn serialize_the_metadata() {
+    let mut project = Project::new(SessionInfo::new("Demo").with_author("Christian"));
+    project.update_info(|info| {
+      info.add_tag("live");
+    });
+    project.add_track(
+      TrackInfo::new("Bass")
+        .with_color(Color::new(0, 64, 255))
+        .with_notes("Sidechained to the kick"),
+    );
+    project.add_track(TrackInfo::new("Keys").with_icon("piano"));
+
+    let json = serde_json::to_string(&project).unwrap();
+    let loaded: Project = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, project);
+    assert_eq!(loaded.info().created(), project.info().created());
+    assert_eq!(loaded.info().tags(), &["live".to_string()]);
+    assert_eq!(loaded.track(1).unwrap().icon.as_deref(), Some("piano"));
+  }
+
+  #[test]
+  fn serialize_a_track() {
+    let track = TrackInfo::new("Drums").with_color(Color::new(200, 0, 0));
+    let json = serde_json::to_string(&track).unwrap();
+    assert_eq!(serde_json::from_str::<TrackInfo>(&json).unwrap(), track);
+  }
+}
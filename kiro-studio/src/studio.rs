@@ -1,21 +1,269 @@
-use ringbuf::Consumer;
+use ringbuf::{Consumer, Producer};
 
 use kiro_audio as audio;
-use kiro_engine::{Engine, EngineConfig, Event, EventData, Renderer};
+use kiro_engine::{Engine, EngineConfig, Event, EventData, Renderer, TransportMessage};
+use kiro_midi::endpoints::{SourceId, SourceInfo};
+use kiro_midi::messages::flex_data::{FlexData, FlexDataMessage};
+use kiro_midi::messages::{Message, MessageType};
 use kiro_midi::{self as midi, Driver, DriverSpec};
+use kiro_time::{Signature, Tempo};
 
+use crate::actions::{names, ActionError, ActionRegistry, ActionSource, KeyBindings};
 use crate::config::Config;
+use crate::devices::DeviceScripts;
+#[cfg(feature = "rhai")]
+use crate::devices::RhaiDeviceScript;
+#[cfg(feature = "rhai")]
+use crate::errors::Error;
 use crate::errors::Result;
+use crate::project::{Project, TrackInfo};
+
+/// Capacity of the queue of transport messages sent to the audio thread
+const TRANSPORT_QUEUE_SIZE: usize = 64;
+
+/// State of the studio that the actions act upon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StudioState {
+  pub project: Project,
+  pub playing: bool,
+  pub recording: bool,
+  /// Track the track actions apply to
+  pub selected_track: Option<usize>,
+  /// Quantize the recorded notes to the grid
+  pub quantize: bool,
+  /// Handle the events from the control surfaces
+  pub devices_enabled: bool,
+  /// Transport messages waiting to be sent to the renderer
+  transport: Vec<TransportMessage>,
+}
+
+impl StudioState {
+  pub fn new(project: Project) -> Self {
+    Self {
+      project,
+      playing: false,
+      recording: false,
+      selected_track: None,
+      quantize: false,
+      devices_enabled: true,
+      transport: Vec::new(),
+    }
+  }
+
+  /// Take the transport messages produced by the actions since the last call.
+  pub fn take_transport_messages(&mut self) -> Vec<TransportMessage> {
+    std::mem::take(&mut self.transport)
+  }
+
+  fn play(&mut self) {
+    if !self.playing {
+      self.playing = true;
+      self.transport.push(TransportMessage::Start);
+    }
+  }
+
+  fn stop(&mut self) {
+    if self.playing {
+      self.playing = false;
+      self.transport.push(TransportMessage::Stop);
+    }
+    self.recording = false;
+  }
+}
+
+impl Default for StudioState {
+  fn default() -> Self {
+    Self::new(Project::default())
+  }
+}
+
+/// Toggle a flag, or set it from a normalized value when there is one (ex. a button of a control surface).
+fn toggle(flag: &mut bool, value: Option<f64>) {
+  *flag = match value {
+    Some(value) => value >= 0.5,
+    None => !*flag,
+  };
+}
+
+/// Transport message for the tempo and time signature of the Flex Data messages (ex. from a MIDI 2.0 sequencer).
+fn transport_message(message: &Message) -> Option<TransportMessage> {
+  let flex_data = match message.mtype {
+    MessageType::FlexData(FlexData { message, .. }) => message,
+    _ => return None,
+  };
+  match flex_data {
+    FlexDataMessage::SetTempo(_) => flex_data
+      .tempo_bpm()
+      .map(|bpm| TransportMessage::Tempo(Tempo::new(bpm.round().min(f64::from(u16::MAX)) as u16))),
+    // the signatures support up to sixteenth notes
+    FlexDataMessage::SetTimeSignature {
+      numerator,
+      denominator,
+      ..
+    } if denominator <= 4 => Some(TransportMessage::Signature(Signature::new(
+      numerator,
+      1 << denominator,
+    ))),
+    _ => None,
+  }
+}
+
+/// Register the built-in actions of the studio.
+pub fn register_actions(
+  registry: &mut ActionRegistry<StudioState>,
+) -> core::result::Result<(), ActionError> {
+  registry.register(names::TRANSPORT_PLAY, "Start playing", |state, _| {
+    state.play();
+    Ok(())
+  })?;
+  registry.register(
+    names::TRANSPORT_STOP,
+    "Stop playing and recording",
+    |state, _| {
+      state.stop();
+      Ok(())
+    },
+  )?;
+  registry.register(
+    names::TRANSPORT_RECORD,
+    "Toggle recording",
+    |state, value| {
+      toggle(&mut state.recording, value);
+      if state.recording {
+        state.play();
+      }
+      Ok(())
+    },
+  )?;
+  registry.register(names::TRACK_ADD, "Add a track and select it", |state, _| {
+    let name = format!("Track {}", state.project.tracks().len() + 1);
+    state.selected_track = Some(state.project.add_track(TrackInfo::new(&name)));
+    Ok(())
+  })?;
+  registry.register(
+    names::TRACK_RECORD_ARM,
+    "Toggle the record arm of the selected track",
+    |state, value| {
+      let index = state
+        .selected_track
+        .ok_or_else(|| "No track selected".to_string())?;
+      if state
+        .project
+        .update_track(index, |track| toggle(&mut track.armed, value))
+      {
+        Ok(())
+      } else {
+        Err(format!("Track {} not found", index))
+      }
+    },
+  )?;
+  registry.register(
+    names::EDIT_QUANTIZE,
+    "Toggle the quantization of the recorded notes",
+    |state, value| {
+      toggle(&mut state.quantize, value);
+      Ok(())
+    },
+  )?;
+  registry.register(
+    names::DEVICE_TOGGLE,
+    "Toggle the control surfaces",
+    |state, value| {
+      toggle(&mut state.devices_enabled, value);
+      Ok(())
+    },
+  )
+}
+
+/// Default key bindings of the built-in actions.
+pub fn default_key_bindings() -> KeyBindings {
+  KeyBindings::new()
+    .with_binding("space", names::TRANSPORT_PLAY)
+    .with_binding("shift+space", names::TRANSPORT_STOP)
+    .with_binding("r", names::TRANSPORT_RECORD)
+    .with_binding("ctrl+t", names::TRACK_ADD)
+    .with_binding("shift+r", names::TRACK_RECORD_ARM)
+    .with_binding("q", names::EDIT_QUANTIZE)
+    .with_binding("ctrl+d", names::DEVICE_TOGGLE)
+}
+
+/// Load the device scripts from the configuration, unless in safe mode.
+fn load_device_scripts(config: &Config) -> Result<DeviceScripts> {
+  #[allow(unused_mut)]
+  let mut scripts = DeviceScripts::new();
+  if config.safe_mode {
+    return Ok(scripts);
+  }
+  #[cfg(feature = "rhai")]
+  for path in config.device_scripts.iter() {
+    let source = std::fs::read_to_string(path)
+      .map_err(|err| Error::Script(format!("{}: {}", path.display(), err)))?;
+    let name = path
+      .file_stem()
+      .map(|name| name.to_string_lossy())
+      .unwrap_or_default();
+    scripts.register(RhaiDeviceScript::new(&name, &source)?);
+  }
+  Ok(scripts)
+}
+
+/// Dispatch the actions requested by the device scripts for an event of a control surface,
+/// returning the first error while still dispatching the rest of them.
+fn dispatch_device_event(
+  devices: &mut DeviceScripts,
+  actions: &mut ActionRegistry<StudioState>,
+  state: &mut StudioState,
+  event: &midi::Event,
+) -> core::result::Result<(), ActionError> {
+  if !state.devices_enabled {
+    return Ok(());
+  }
+  let mut result = Ok(());
+  for action in devices.handle_event(event) {
+    let dispatched = actions.dispatch_device_action(state, &action);
+    result = result.and(dispatched);
+  }
+  result
+}
+
+/// Bind the device scripts to the sources, unless they are the same as the last time they were bound,
+/// returning whether they were bound again.
+fn bind_device_sources(
+  devices: &mut DeviceScripts,
+  bound_sources: &mut Vec<SourceId>,
+  sources: &[SourceInfo],
+) -> bool {
+  let mut source_ids = sources.iter().map(|source| source.id).collect::<Vec<_>>();
+  source_ids.sort_unstable();
+  if source_ids == *bound_sources {
+    false
+  } else {
+    devices.update_sources(sources);
+    *bound_sources = source_ids;
+    true
+  }
+}
 
 pub struct Studio {
   config: Config,
-  _midi_driver: Driver,
+  midi_driver: Driver,
   _audio_driver: audio::AudioDriver,
   engine: Engine,
+  state: StudioState,
+  actions: ActionRegistry<StudioState>,
+  key_bindings: KeyBindings,
+  devices: DeviceScripts,
+  /// Sources the device scripts are bound to
+  device_sources: Vec<SourceId>,
+  /// Events from the control surfaces, handled outside of the audio thread
+  device_consumer: Consumer<midi::Event>,
+  transport_producer: Producer<TransportMessage>,
 }
 
 impl Studio {
   pub fn new(config: Config) -> Result<Self> {
+    let mut devices = load_device_scripts(&config)?;
+
     let mut midi_driver = midi::drivers::create("kiro-studio")?;
 
     let (midi_track_producer, midi_track_consumer) =
@@ -25,34 +273,146 @@ impl Studio {
       midi_track_producer,
     )?;
 
+    let (device_producer, device_consumer) =
+      ringbuf::RingBuffer::new(config.midi.ringbuf_size).split();
+    midi_driver.create_input(
+      midi::InputConfig::new("devices").with_all_sources(midi::Filter::default()),
+      device_producer,
+    )?;
+    let mut device_sources = Vec::new();
+    bind_device_sources(&mut devices, &mut device_sources, &midi_driver.sources());
+
     let audio_config = audio::AudioConfig::default();
     let sample_rate = audio_config.sample_rate as f32;
 
-    let mut engine_config = EngineConfig::default();
-    engine_config.audio_buffer_size = audio_config.buffer_size;
+    let engine_config = EngineConfig {
+      sample_rate: audio_config.sample_rate,
+      audio_buffer_size: audio_config.buffer_size,
+      ..EngineConfig::default()
+    };
 
     let mut engine = Engine::new(engine_config);
     // the renderer will always be available just after creating the engine so it is safe to unwrap
-    let renderer = engine.take_renderer().unwrap();
+    let mut renderer = engine.take_renderer().unwrap();
+    renderer.enable_watchdog(audio_config.sample_rate);
+
+    let (transport_producer, transport_consumer) =
+      ringbuf::RingBuffer::new(TRANSPORT_QUEUE_SIZE).split();
 
     let studio_callack = StudioCallback {
       midi_consumer: midi_track_consumer,
+      transport_consumer,
       renderer,
     };
 
     let audio_driver = audio::AudioDriver::new(audio_config, studio_callack)?;
 
+    let mut actions = ActionRegistry::new();
+    // the registry is empty so the built-in actions can not clash
+    register_actions(&mut actions).unwrap();
+
     Ok(Self {
       config,
-      _midi_driver: midi_driver,
+      midi_driver,
       _audio_driver: audio_driver,
       engine,
+      state: StudioState::default(),
+      actions,
+      key_bindings: default_key_bindings(),
+      devices,
+      device_sources,
+      device_consumer,
+      transport_producer,
     })
   }
+
+  pub fn is_safe_mode(&self) -> bool {
+    self.config.safe_mode
+  }
+
+  pub fn project(&self) -> &Project {
+    &self.state.project
+  }
+
+  pub fn project_mut(&mut self) -> &mut Project {
+    &mut self.state.project
+  }
+
+  pub fn state(&self) -> &StudioState {
+    &self.state
+  }
+
+  /// Registry with the built-in actions, where more of them can be registered.
+  pub fn actions_mut(&mut self) -> &mut ActionRegistry<StudioState> {
+    &mut self.actions
+  }
+
+  pub fn key_bindings_mut(&mut self) -> &mut KeyBindings {
+    &mut self.key_bindings
+  }
+
+  pub fn devices_mut(&mut self) -> &mut DeviceScripts {
+    &mut self.devices
+  }
+
+  pub fn dispatch(
+    &mut self,
+    name: &str,
+    value: Option<f64>,
+    source: ActionSource,
+  ) -> core::result::Result<(), ActionError> {
+    let result = self.actions.dispatch(&mut self.state, name, value, source);
+    self.send_transport_messages();
+    result
+  }
+
+  /// Dispatch the action bound to a key combination, returning whether there was any.
+  pub fn handle_keys(&mut self, keys: &str) -> core::result::Result<bool, ActionError> {
+    let result = self
+      .key_bindings
+      .dispatch(&mut self.actions, &mut self.state, keys);
+    self.send_transport_messages();
+    result
+  }
+
+  /// Dispatch the actions requested by the device scripts for an event of a control surface.
+  pub fn handle_device_event(
+    &mut self,
+    event: &midi::Event,
+  ) -> core::result::Result<(), ActionError> {
+    let result =
+      dispatch_device_event(&mut self.devices, &mut self.actions, &mut self.state, event);
+    self.send_transport_messages();
+    result
+  }
+
+  /// Dispatch the actions for the events received from the control surfaces since the last call,
+  /// binding the device scripts to the sources connected or disconnected since then.
+  ///
+  /// It is meant to be called periodically from outside of the audio thread (ex. from the UI loop),
+  /// and returns the first error while still handling the rest of the events.
+  pub fn process_device_events(&mut self) -> core::result::Result<(), ActionError> {
+    let sources = self.midi_driver.sources();
+    bind_device_sources(&mut self.devices, &mut self.device_sources, &sources);
+    let mut result = Ok(());
+    while let Some(event) = self.device_consumer.pop() {
+      let handled = self.handle_device_event(&event);
+      result = result.and(handled);
+    }
+    result
+  }
+
+  fn send_transport_messages(&mut self) {
+    for message in self.state.take_transport_messages() {
+      // the audio thread drains the queue on every block, so it is only full if it is not running
+      self.transport_producer.push(message).ok();
+    }
+  }
 }
 
 struct StudioCallback {
   midi_consumer: Consumer<midi::Event>,
+  transport_consumer: Consumer<TransportMessage>,
   renderer: Renderer,
 }
 
@@ -79,10 +439,23 @@ impl StudioCallback {
     if let Some(buffer) = self.renderer.get_events_inputs().get(0) {
       let buffer = buffer.get_mut();
       buffer.clear();
+      // the transport messages are timestamped at the beginning of the block
+      while let Some(message) = self.transport_consumer.pop() {
+        let event = Event {
+          timestamp: 0,
+          data: EventData::Transport(message),
+        };
+        buffer.push(event).ok();
+      }
+      // the tempo and time signature of the Flex Data messages drive the transport
       for midi_event in self.midi_consumer.iter() {
+        let data = match transport_message(&midi_event.message) {
+          Some(message) => EventData::Transport(message),
+          None => EventData::Midi(midi_event.message),
+        };
         let event = Event {
           timestamp: midi_event.timestamp,
-          data: EventData::Midi(midi_event.message),
+          data,
         };
         buffer.push(event).ok();
       }
@@ -101,4 +474,243 @@ impl audio::AudioHandler for StudioCallback {
 
     self.process_audio_output(output, channels, num_samples);
   }
+
+  fn xrun(&mut self) {
+    self.renderer.report_xrun();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use kiro_midi::endpoints::EndpointProperties;
+  use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+  use kiro_midi::messages::flex_data::Address;
+
+  use crate::devices::{DeviceAction, DeviceScript};
+
+  fn actions() -> ActionRegistry<StudioState> {
+    let mut actions = ActionRegistry::new();
+    register_actions(&mut actions).unwrap();
+    actions
+  }
+
+  #[test]
+  fn builtin_actions() {
+    let mut actions = actions();
+    let mut state = StudioState::default();
+    let mut dispatch = |state: &mut StudioState, name: &str, value: Option<f64>| {
+      actions.dispatch(state, name, value, ActionSource::KeyBinding)
+    };
+
+    assert_eq!(
+      dispatch(&mut state, names::TRACK_RECORD_ARM, None),
+      Err(ActionError::Failed {
+        name: names::TRACK_RECORD_ARM.to_string(),
+        reason: "No track selected".to_string()
+      })
+    );
+    dispatch(&mut state, names::TRACK_ADD, None).unwrap();
+    dispatch(&mut state, names::TRACK_ADD, None).unwrap();
+    assert_eq!(state.selected_track, Some(1));
+    assert_eq!(state.project.tracks()[1].name, "Track 2");
+    dispatch(&mut state, names::TRACK_RECORD_ARM, None).unwrap();
+    assert!(state.project.tracks()[1].armed);
+    assert!(!state.project.tracks()[0].armed);
+
+    dispatch(&mut state, names::TRANSPORT_RECORD, None).unwrap();
+    assert!(state.playing && state.recording);
+    dispatch(&mut state, names::TRANSPORT_PLAY, None).unwrap();
+    dispatch(&mut state, names::TRANSPORT_STOP, None).unwrap();
+    assert!(!state.playing && !state.recording);
+    assert_eq!(
+      state.take_transport_messages(),
+      vec![TransportMessage::Start, TransportMessage::Stop]
+    );
+
+    dispatch(&mut state, names::EDIT_QUANTIZE, Some(1.0)).unwrap();
+    dispatch(&mut state, names::EDIT_QUANTIZE, Some(1.0)).unwrap();
+    assert!(state.quantize);
+    dispatch(&mut state, names::DEVICE_TOGGLE, None).unwrap();
+    assert!(!state.devices_enabled);
+  }
+
+  #[test]
+  fn key_bindings_dispatch_the_builtin_actions() {
+    let mut actions = actions();
+    let mut state = StudioState::default();
+    let bindings = default_key_bindings();
+
+    assert_eq!(
+      bindings.dispatch(&mut actions, &mut state, "Space"),
+      Ok(true)
+    );
+    assert!(state.playing);
+    assert_eq!(
+      bindings.dispatch(&mut actions, &mut state, "Ctrl+T"),
+      Ok(true)
+    );
+    assert_eq!(state.project.tracks().len(), 1);
+  }
+
+  #[test]
+  fn safe_mode_skips_the_device_scripts() {
+    let config = Config {
+      device_scripts: vec!["missing.rhai".into()],
+      safe_mode: true,
+      ..Config::default()
+    };
+    let scripts = load_device_scripts(&config).unwrap();
+    assert!(scripts.names().is_empty());
+
+    #[cfg(feature = "rhai")]
+    {
+      let config = Config {
+        safe_mode: false,
+        ..config
+      };
+      assert!(load_device_scripts(&config).is_err());
+    }
+  }
+
+  struct PlayButton;
+
+  impl DeviceScript for PlayButton {
+    fn name(&self) -> &str {
+      "play-button"
+    }
+
+    fn matches(&self, _source: &SourceInfo) -> bool {
+      true
+    }
+
+    fn handle_message(&mut self, _message: &Message) -> Vec<DeviceAction> {
+      vec![
+        DeviceAction::Trigger("transport.rewind".to_string()),
+        DeviceAction::Trigger(names::TRANSPORT_PLAY.to_string()),
+      ]
+    }
+  }
+
+  fn source(id: SourceId) -> SourceInfo {
+    SourceInfo::new(
+      id,
+      "button".to_string(),
+      EndpointProperties::default(),
+      None,
+      Vec::new(),
+    )
+  }
+
+  #[test]
+  fn device_events_dispatch_the_builtin_actions() {
+    let mut actions = actions();
+    let mut state = StudioState::default();
+    let mut devices = DeviceScripts::new();
+    devices.register(PlayButton);
+    devices.update_sources(&[source(1)]);
+    let event = midi::Event {
+      timestamp: 0,
+      endpoint: 1,
+      message: Message::channel_voice(
+        0,
+        0,
+        ChannelVoiceMessage::ControlChange { index: 0, data: 0 },
+      ),
+      jr_timestamp: None,
+    };
+
+    // the unknown action does not prevent the rest from being dispatched
+    assert_eq!(
+      dispatch_device_event(&mut devices, &mut actions, &mut state, &event),
+      Err(ActionError::Unknown("transport.rewind".to_string()))
+    );
+    assert!(state.playing);
+    assert_eq!(
+      actions.history().last().map(|record| record.source.clone()),
+      Some(ActionSource::ControlSurface)
+    );
+
+    state.devices_enabled = false;
+    state.playing = false;
+    assert_eq!(
+      dispatch_device_event(&mut devices, &mut actions, &mut state, &event),
+      Ok(())
+    );
+    assert!(!state.playing);
+  }
+
+  #[test]
+  fn bind_the_device_scripts_to_the_plugged_sources() {
+    let mut devices = DeviceScripts::new();
+    devices.register(PlayButton);
+    let mut bound_sources = Vec::new();
+    let event = |endpoint| midi::Event {
+      timestamp: 0,
+      endpoint,
+      message: Message::channel_voice(
+        0,
+        0,
+        ChannelVoiceMessage::ControlChange { index: 0, data: 0 },
+      ),
+      jr_timestamp: None,
+    };
+
+    assert!(!bind_device_sources(&mut devices, &mut bound_sources, &[]));
+    assert!(devices.handle_event(&event(1)).is_empty());
+
+    assert!(bind_device_sources(
+      &mut devices,
+      &mut bound_sources,
+      &[source(2), source(1)]
+    ));
+    assert!(!bind_device_sources(
+      &mut devices,
+      &mut bound_sources,
+      &[source(1), source(2)]
+    ));
+    assert_eq!(devices.handle_event(&event(1)).len(), 2);
+
+    assert!(bind_device_sources(
+      &mut devices,
+      &mut bound_sources,
+      &[source(2)]
+    ));
+    assert!(devices.handle_event(&event(1)).is_empty());
+    assert_eq!(devices.handle_event(&event(2)).len(), 2);
+  }
+
+  #[test]
+  fn follow_the_tempo_and_signature_of_the_flex_data() {
+    let flex_data = |message| Message {
+      group: 0,
+      mtype: MessageType::FlexData(FlexData {
+        address: Address::Group,
+        message,
+      }),
+    };
+    assert_eq!(
+      transport_message(&flex_data(FlexDataMessage::set_tempo_from_bpm(96.0))),
+      Some(TransportMessage::Tempo(Tempo::new(96)))
+    );
+    assert_eq!(
+      transport_message(&flex_data(FlexDataMessage::SetTimeSignature {
+        numerator: 6,
+        denominator: 3,
+        num_32nd_notes: 8,
+      })),
+      Some(TransportMessage::Signature(Signature::new(6, 8)))
+    );
+    assert_eq!(
+      transport_message(&flex_data(FlexDataMessage::SetTempo(0))),
+      None
+    );
+    assert_eq!(
+      transport_message(&flex_data(FlexDataMessage::SetKeySignature {
+        sharps_flats: -2,
+        tonic_note: 2,
+      })),
+      None
+    );
+  }
 }
@@ -0,0 +1,176 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use kiro_engine::processors::{builtin_processor, read_samples, AudioFileError};
+use kiro_engine::{Engine, EngineConfig, GraphDocument};
+
+use crate::render;
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error("I/O: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Effect chain: {0}")]
+  Chain(#[from] serde_json::Error),
+
+  #[error("Engine: {0}")]
+  Engine(#[from] kiro_engine::Error),
+
+  #[error("Audio file: {0}")]
+  AudioFile(#[from] AudioFileError),
+
+  #[error("{0}")]
+  Render(#[from] render::Error),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Extensions of the audio files processed by [`process_directory`].
+pub const EXTENSIONS: [&str; 3] = ["wav", "wave", "flac"];
+
+/// Load an effect chain from a graph document, as exported with [`Engine::export_graph`].
+///
+/// The nodes are created with the built-in processors of the engine, chosen by their names,
+/// see [`builtin_processor`]. The chain is fed from the audio inputs of the graph,
+/// and sends its result to the audio outputs.
+pub fn load_chain<P: AsRef<Path>>(path: P) -> Result<GraphDocument> {
+  let reader = BufReader::new(File::open(path)?);
+  Ok(serde_json::from_reader(reader)?)
+}
+
+/// Process an audio file offline through an effect chain, and write the result into a wav file
+/// of 32 bits floats with the same sample rate, extended by some seconds of tail for the effects to decay.
+///
+/// Mono files are sent to both channels of the chain.
+pub fn process_file<P, Q>(chain: &GraphDocument, input: P, output: Q, tail: f64) -> Result<()>
+where
+  P: AsRef<Path>,
+  Q: AsRef<Path>,
+{
+  let (mut channels, sample_rate) = read_samples(input)?;
+  if channels.len() == 1 {
+    channels.push(channels[0].clone());
+  }
+  let mut engine = Engine::new(EngineConfig {
+    sample_rate,
+    ..EngineConfig::default()
+  });
+  engine.import_graph(chain, |node| {
+    builtin_processor(&node.name, &node.descriptor, sample_rate)
+  })?;
+  let num_samples = channels.first().map_or(0, Vec::len)
+    + (tail.max(0.0) * f64::from(sample_rate)).round() as usize;
+  let processed = engine.render_offline_with_inputs(&channels, num_samples, &[])?;
+  render::write_wav(output, sample_rate, &processed)?;
+  Ok(())
+}
+
+/// Process every audio file of a directory through an effect chain with [`process_file`],
+/// writing the results as wav files with the same names into the output directory,
+/// which is created if needed. It returns the paths of the files written, sorted by name.
+pub fn process_directory<P, Q>(
+  chain: &GraphDocument,
+  input_dir: P,
+  output_dir: Q,
+  tail: f64,
+) -> Result<Vec<PathBuf>>
+where
+  P: AsRef<Path>,
+  Q: AsRef<Path>,
+{
+  let mut inputs = fs::read_dir(input_dir)?
+    .map(|entry| entry.map(|entry| entry.path()))
+    .collect::<std::io::Result<Vec<_>>>()?;
+  inputs.retain(|path| path.is_file() && is_audio_file(path));
+  inputs.sort();
+
+  let output_dir = output_dir.as_ref();
+  fs::create_dir_all(output_dir)?;
+  let mut outputs = Vec::with_capacity(inputs.len());
+  for input in inputs {
+    let mut output = output_dir.join(input.file_name().unwrap_or_default());
+    output.set_extension("wav");
+    process_file(chain, &input, &output, tail)?;
+    outputs.push(output);
+  }
+  Ok(outputs)
+}
+
+fn is_audio_file(path: &Path) -> bool {
+  match path.extension().and_then(|extension| extension.to_str()) {
+    Some(extension) => EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+    None => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::processors::GainProcessor;
+
+  use super::*;
+
+  fn gain_chain(decibels: f32) -> GraphDocument {
+    let mut engine = Engine::default();
+    let gain = engine
+      .create_processor("gain-1", GainProcessor::new(2))
+      .unwrap();
+    gain
+      .set_parameter(GainProcessor::GAIN_INDEX, decibels)
+      .unwrap();
+    engine
+      .audio_inputs()
+      .unwrap()
+      .connect(&gain.audio_input("audio-in").unwrap())
+      .unwrap();
+    gain
+      .audio_output("audio-out")
+      .unwrap()
+      .connect(&engine.audio_outputs().unwrap())
+      .unwrap();
+    engine.export_graph().unwrap()
+  }
+
+  #[test]
+  fn process_a_directory() {
+    let root = std::env::temp_dir().join("kiro-synth-batch-test");
+    let (input_dir, output_dir) = (root.join("in"), root.join("out"));
+    fs::create_dir_all(&input_dir).unwrap();
+    let spec = hound::WavSpec {
+      channels: 1,
+      sample_rate: 8_000,
+      bits_per_sample: 32,
+      sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(input_dir.join("a.wav"), spec).unwrap();
+    for sample in [0.5, -0.25, 1.0] {
+      writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+    fs::write(input_dir.join("notes.txt"), "not audio").unwrap();
+
+    let chain_path = root.join("chain.json");
+    fs::write(&chain_path, serde_json::to_vec(&gain_chain(-6.0)).unwrap()).unwrap();
+    let chain = load_chain(&chain_path).unwrap();
+
+    let outputs = process_directory(&chain, &input_dir, &output_dir, 0.001).unwrap();
+    assert_eq!(outputs, vec![output_dir.join("a.wav")]);
+    let mut reader = hound::WavReader::open(&outputs[0]).unwrap();
+    assert_eq!(reader.spec().channels, 2);
+    assert_eq!(reader.spec().sample_rate, 8_000);
+    let samples = reader
+      .samples::<f32>()
+      .collect::<core::result::Result<Vec<_>, _>>()
+      .unwrap();
+    // three samples followed by a tail of eight
+    assert_eq!(samples.len(), 2 * 11);
+    let gain = 10.0f32.powf(-6.0 / 20.0);
+    assert!((samples[0] - 0.5 * gain).abs() < 1e-6);
+    assert!((samples[3] - -0.25 * gain).abs() < 1e-6);
+    assert_eq!(samples[21], 0.0);
+    fs::remove_dir_all(root).unwrap();
+  }
+}
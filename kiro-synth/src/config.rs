@@ -1,14 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
 use kiro_audio::AudioConfig;
+use kiro_midi::note_freq::Tuning;
+use kiro_midi::SourceMatch;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Error)]
+pub enum ConfigError {
+  #[error("Failed to read {0}: {1}")]
+  Io(PathBuf, std::io::Error),
+
+  #[error("{0}")]
+  Parse(#[from] toml::de::Error),
+
+  #[error("Invalid value for key `{key}`: {reason}")]
+  Invalid { key: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone)]
 pub struct Config {
   pub midi: MidiConfig,
   pub audio: AudioConfig,
+  pub num_voices: usize,
+  pub voice_scaling: VoiceScalingConfig,
+  pub tuning: Tuning,
+  /// Whether to follow the channel per note convention of MPE controllers
+  pub mpe: bool,
+  /// Preset to load once the synth is built
+  pub preset: Option<PathBuf>,
+}
+
+impl Config {
+  pub const DEFAULT_NUM_VOICES: usize = 8;
+
+  /// Path of the config file read by [`Config::load_default`], `~/.config/kiro/synth.toml`
+  pub fn default_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("kiro").join("synth.toml"))
+  }
+
+  /// Read the config from the default path, or use the defaults if there is no file.
+  pub fn load_default() -> Result<Self, ConfigError> {
+    match Self::default_path() {
+      Some(path) if path.exists() => Self::load(path),
+      _ => Ok(Self::default()),
+    }
+  }
+
+  /// Read the config from a TOML file, taking the defaults for the missing keys.
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+    let path = path.as_ref();
+    let text =
+      std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+    Self::from_toml(&text)
+  }
+
+  /// Parse the config from the contents of a TOML file, taking the defaults for the missing keys.
+  pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+    let file: ConfigFile = toml::from_str(text)?;
+    let mut config = Self::default();
+    if let Some(num_voices) = file.num_voices {
+      validate(
+        "num_voices",
+        num_voices > 0,
+        "there must be at least one voice",
+      )?;
+      config.num_voices = num_voices;
+    }
+    config.preset = file.preset.or(config.preset);
+    config.mpe = file.mpe.unwrap_or(config.mpe);
+
+    let audio = file.audio.unwrap_or_default();
+    config.audio.device = audio.device.or(config.audio.device);
+    if let Some(sample_rate) = audio.sample_rate {
+      validate("audio.sample_rate", sample_rate > 0, "it must be positive")?;
+      config.audio.sample_rate = sample_rate;
+    }
+    if let Some(buffer_size) = audio.buffer_size {
+      validate("audio.buffer_size", buffer_size > 0, "it must be positive")?;
+      config.audio.buffer_size = buffer_size;
+    }
+
+    let midi = file.midi.unwrap_or_default();
+    if let Some(sources) = midi.sources {
+      for source in sources.iter() {
+        SourceMatch::regex(source).map_err(|err| ConfigError::Invalid {
+          key: "midi.sources",
+          reason: err.to_string(),
+        })?;
+      }
+      config.midi.sources = sources;
+    }
+    if let Some(ringbuf_size) = midi.ringbuf_size {
+      validate("midi.ringbuf_size", ringbuf_size > 0, "it must be positive")?;
+      config.midi.ringbuf_size = ringbuf_size;
+    }
+    Ok(config)
+  }
+}
+
+fn validate(key: &'static str, valid: bool, reason: &str) -> Result<(), ConfigError> {
+  if valid {
+    Ok(())
+  } else {
+    Err(ConfigError::Invalid {
+      key,
+      reason: reason.to_string(),
+    })
+  }
+}
+
+/// Contents of a config file, where every key is optional and the unknown ones are rejected.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+  num_voices: Option<usize>,
+  preset: Option<PathBuf>,
+  mpe: Option<bool>,
+  audio: Option<AudioConfigFile>,
+  midi: Option<MidiConfigFile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AudioConfigFile {
+  device: Option<String>,
+  sample_rate: Option<u32>,
+  buffer_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MidiConfigFile {
+  sources: Option<Vec<String>>,
+  ringbuf_size: Option<usize>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      midi: MidiConfig::default(),
+      audio: AudioConfig::default(),
+      num_voices: Config::DEFAULT_NUM_VOICES,
+      voice_scaling: VoiceScalingConfig::default(),
+      tuning: Tuning::default(),
+      mpe: false,
+      preset: None,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct MidiConfig {
   pub endpoints: Vec<EndpointConfig>,
+  /// Regexes for the names of the sources to play from, or all of them when empty
+  pub sources: Vec<String>,
   pub ringbuf_size: usize,
 }
 
@@ -16,6 +163,7 @@ impl Default for MidiConfig {
   fn default() -> Self {
     Self {
       endpoints: Default::default(),
+      sources: Vec::new(),
       ringbuf_size: 4096,
     }
   }
@@ -23,3 +171,90 @@ impl Default for MidiConfig {
 
 #[derive(Debug, Clone, Default)]
 pub struct EndpointConfig {}
+
+/// Adaptive limit of active voices based on the render load.
+#[derive(Debug, Clone)]
+pub struct VoiceScalingConfig {
+  pub enabled: bool,
+  pub min_voices: usize,
+  pub max_voices: usize,
+  /// Number of voices added or removed at a time
+  pub step: usize,
+  /// Load under which voices are added back
+  pub low_load: f32,
+  /// Load over which voices are removed
+  pub high_load: f32,
+  /// Consecutive updates out of the band required to change the limit
+  pub hold_updates: usize,
+}
+
+impl Default for VoiceScalingConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      min_voices: 4,
+      max_voices: 32,
+      step: 2,
+      low_load: 0.5,
+      high_load: 0.8,
+      hold_updates: 4,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn read_the_config_from_toml() {
+    let config = Config::from_toml(
+      r#"
+      num_voices = 16
+      mpe = true
+
+      [audio]
+      device = "Speakers"
+      buffer_size = 128
+
+      [midi]
+      sources = ["Keystep.*"]
+      "#,
+    )
+    .unwrap();
+    assert_eq!(config.num_voices, 16);
+    assert!(config.mpe);
+    assert_eq!(config.audio.device.as_deref(), Some("Speakers"));
+    assert_eq!(config.audio.buffer_size, 128);
+    assert_eq!(config.audio.sample_rate, AudioConfig::DEFAULT_SAMPLE_RATE);
+    assert_eq!(config.midi.sources, vec!["Keystep.*".to_string()]);
+    assert_eq!(config.midi.ringbuf_size, MidiConfig::default().ringbuf_size);
+  }
+
+  #[test]
+  fn point_at_the_invalid_keys() {
+    let error = Config::from_toml("[audio]\nbuffer_size = \"large\"").unwrap_err();
+    assert!(error.to_string().contains("audio.buffer_size"), "{}", error);
+
+    let error = Config::from_toml("[audio]\nbuffer_sizes = 128").unwrap_err();
+    assert!(error.to_string().contains("buffer_sizes"), "{}", error);
+
+    let error = Config::from_toml("[midi]\nringbuf_size = 0").unwrap_err();
+    assert!(matches!(
+      error,
+      ConfigError::Invalid {
+        key: "midi.ringbuf_size",
+        ..
+      }
+    ));
+
+    let error = Config::from_toml("[midi]\nsources = [\"(\"]").unwrap_err();
+    assert!(matches!(
+      error,
+      ConfigError::Invalid {
+        key: "midi.sources",
+        ..
+      }
+    ));
+  }
+}
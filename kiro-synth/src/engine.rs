@@ -1,18 +1,22 @@
-use ringbuf::Consumer;
+use ringbuf::{Consumer, Producer};
 use thiserror::Error;
 
 use kiro_audio as audio;
-use kiro_engine::{Engine, EngineConfig, Event, EventData, Renderer};
+use kiro_engine::{Engine, EngineConfig, Event, EventData, RenderLoad, Renderer};
 use kiro_midi::{self as midi, Driver, DriverSpec};
 use kiro_time::SampleRate;
 
 use crate::config::Config;
+use crate::midi_map::MidiController;
 
 #[derive(Debug, Error)]
 pub enum Error {
   #[error("Midi: {0}")]
   Midi(#[from] midi::drivers::Error),
 
+  #[error("Midi source: {0}")]
+  MidiSource(String),
+
   #[error("Audio: {0}")]
   Audio(#[from] audio::AudioError),
 }
@@ -24,6 +28,7 @@ pub struct SynthEngine {
   _midi_driver: Driver,
   audio_driver: audio::AudioDriver,
   engine: Engine,
+  controllers_consumer: Consumer<midi::Event>,
 }
 
 impl SynthEngine {
@@ -32,23 +37,38 @@ impl SynthEngine {
 
     let (midi_track_producer, midi_track_consumer) =
       ringbuf::RingBuffer::new(config.midi.ringbuf_size).split();
-    midi_driver.create_input(
-      midi::InputConfig::new("track").with_all_sources(midi::Filter::default()),
-      midi_track_producer,
-    )?;
+    let mut input_config = midi::InputConfig::new("track");
+    if config.midi.sources.is_empty() {
+      input_config = input_config.with_all_sources(midi::Filter::default());
+    } else {
+      for source in config.midi.sources.iter() {
+        let source_match =
+          midi::SourceMatch::regex(source).map_err(|err| Error::MidiSource(err.to_string()))?;
+        input_config = input_config.with_source(source_match, midi::Filter::default());
+      }
+    }
+    midi_driver.create_input(input_config, midi_track_producer)?;
 
     let audio_output_config = audio::AudioDriver::output_config(&config.audio)?;
 
-    let mut engine_config = EngineConfig::default();
-    engine_config.audio_buffer_size = audio_output_config.buffer_size;
-    engine_config.audio_output_channels = audio_output_config.channels;
+    let engine_config = EngineConfig {
+      sample_rate: config.audio.sample_rate,
+      audio_buffer_size: audio_output_config.buffer_size,
+      audio_output_channels: audio_output_config.channels,
+      ..EngineConfig::default()
+    };
 
     let mut engine = Engine::new(engine_config);
     // the renderer will always be available just after creating the engine so it is safe to unwrap
-    let renderer = engine.take_renderer().unwrap();
+    let mut renderer = engine.take_renderer().unwrap();
+    renderer.enable_watchdog(config.audio.sample_rate);
+
+    let (controllers_producer, controllers_consumer) =
+      ringbuf::RingBuffer::new(config.midi.ringbuf_size).split();
 
     let studio_callack = StudioCallback {
       midi_consumer: midi_track_consumer,
+      controllers_producer,
       renderer,
     };
 
@@ -59,6 +79,7 @@ impl SynthEngine {
       _midi_driver: midi_driver,
       audio_driver,
       engine,
+      controllers_consumer,
     })
   }
 
@@ -74,6 +95,14 @@ impl SynthEngine {
     self.audio_driver.num_output_channels()
   }
 
+  pub fn config(&self) -> &Config {
+    &self.config
+  }
+
+  pub fn render_load(&self) -> RenderLoad {
+    self.engine.render_load()
+  }
+
   pub fn engine(&self) -> &Engine {
     &self.engine
   }
@@ -82,6 +111,11 @@ impl SynthEngine {
     &mut self.engine
   }
 
+  /// Controller messages received since the last call, to be followed by a [`crate::midi_map::MidiMap`].
+  pub fn controller_events(&mut self) -> impl Iterator<Item = midi::Event> + '_ {
+    std::iter::from_fn(move || self.controllers_consumer.pop())
+  }
+
   pub fn start(&self) -> Result<()> {
     self.audio_driver.start().map_err(Error::Audio)
   }
@@ -89,6 +123,7 @@ impl SynthEngine {
 
 struct StudioCallback {
   midi_consumer: Consumer<midi::Event>,
+  controllers_producer: Producer<midi::Event>,
   renderer: Renderer,
 }
 
@@ -116,6 +151,10 @@ impl StudioCallback {
       let buffer = buffer.get_mut();
       buffer.clear();
       for midi_event in self.midi_consumer.iter() {
+        // the mappings of the controllers are applied from the control thread
+        if MidiController::from_message(&midi_event.message).is_some() {
+          self.controllers_producer.push(midi_event.clone()).ok();
+        }
         let event = Event {
           timestamp: midi_event.timestamp,
           data: EventData::Midi(midi_event.message),
@@ -137,4 +176,8 @@ impl audio::AudioHandler for StudioCallback {
 
     self.process_audio_output(output, channels, num_samples);
   }
+
+  fn xrun(&mut self) {
+    self.renderer.report_xrun();
+  }
 }
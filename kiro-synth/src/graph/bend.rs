@@ -0,0 +1,109 @@
+use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+
+use crate::graph::voice::bipolar;
+
+/// Pitch bend of a voice, from the pitch bend of the channel within its range,
+/// as set by the pitch bend sensitivity RPN, and the per-note pitch bend sent by MPE controllers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct PitchBend {
+  /// Pitch bend of the channel, in the `-1..1` range
+  bend: f32,
+  /// Semitones of pitch bend of the channel at its ends
+  range: f32,
+  /// Per-note pitch bend of the note being played, in the `-1..1` range
+  note_bend: f32,
+}
+
+impl PitchBend {
+  pub const DEFAULT_RANGE: f32 = 2.0;
+  /// Semitones of per-note pitch bend at its ends, the default of MPE for the member channels
+  pub const PER_NOTE_RANGE: f32 = 48.0;
+  /// Bank and index of the registered controller for the pitch bend sensitivity (RPN 0)
+  const SENSITIVITY: (u8, u8) = (0, 0);
+
+  pub fn reset(&mut self) {
+    *self = Self::default();
+  }
+
+  /// A new note starts without the per-note pitch bend of the previous one.
+  pub fn start_note(&mut self) {
+    self.note_bend = 0.0;
+  }
+
+  /// Follow the pitch bend messages, returning whether the message was one of them.
+  pub fn handle_message(&mut self, message: &ChannelVoiceMessage) -> bool {
+    match *message {
+      ChannelVoiceMessage::PitchBend { data } => self.bend = bipolar(data),
+      ChannelVoiceMessage::PerNotePitchBend { data, .. } => self.note_bend = bipolar(data),
+      ChannelVoiceMessage::RegisteredController { bank, index, data }
+        if (bank, index) == Self::SENSITIVITY =>
+      {
+        // semitones and cents in the two upper 7 bits groups, as the MSB and LSB of MIDI 1.0
+        let semitones = (data >> 25) as f32;
+        let cents = ((data >> 18) & 0x7f) as f32;
+        self.range = semitones + cents / 100.0;
+      }
+      _ => return false,
+    }
+    true
+  }
+
+  /// Semitones to bend the pitch, with an offset to the pitch bend of the channel (ex. from a parameter).
+  pub fn semitones(&self, offset: f32) -> f32 {
+    (self.bend + offset).clamp(-1.0, 1.0) * self.range + self.note_bend * Self::PER_NOTE_RANGE
+  }
+}
+
+impl Default for PitchBend {
+  fn default() -> Self {
+    Self {
+      bend: 0.0,
+      range: Self::DEFAULT_RANGE,
+      note_bend: 0.0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sensitivity(semitones: u32, cents: u32) -> ChannelVoiceMessage {
+    ChannelVoiceMessage::RegisteredController {
+      bank: 0,
+      index: 0,
+      data: semitones << 25 | cents << 18,
+    }
+  }
+
+  #[test]
+  fn bend_within_the_range() {
+    let mut bend = PitchBend::default();
+    assert!(bend.handle_message(&ChannelVoiceMessage::PitchBend { data: u32::MAX }));
+    assert!((bend.semitones(0.0) - 2.0).abs() < 1e-4);
+    assert!((bend.semitones(-0.5) - 1.0).abs() < 1e-4);
+
+    assert!(bend.handle_message(&sensitivity(12, 50)));
+    assert!((bend.semitones(0.0) - 12.5).abs() < 1e-4);
+    assert!((bend.semitones(1.0) - 12.5).abs() < 1e-4);
+
+    assert!(
+      !bend.handle_message(&ChannelVoiceMessage::RegisteredController {
+        bank: 0,
+        index: 1,
+        data: 0,
+      })
+    );
+    bend.reset();
+    assert_eq!(bend, PitchBend::default());
+  }
+
+  #[test]
+  fn bend_the_notes() {
+    let mut bend = PitchBend::default();
+    bend.handle_message(&ChannelVoiceMessage::PerNotePitchBend { note: 60, data: 0 });
+    assert!((bend.semitones(0.0) + PitchBend::PER_NOTE_RANGE).abs() < 1e-4);
+    bend.start_note();
+    assert_eq!(bend.semitones(0.0), 0.0);
+  }
+}
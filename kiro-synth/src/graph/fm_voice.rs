@@ -0,0 +1,499 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use kiro_dsp::envgen::adsr::EnvGen;
+use kiro_engine::processor::ProcessorContext;
+use kiro_engine::{
+  swap_state, AudioDescriptor, Event, EventData, EventsDescriptor, Module, NodeDescriptor,
+  ParamDescriptor, ParamUnit, PolyVoice, Processor, SharedState, StateError,
+};
+use kiro_midi::{
+  self as midi,
+  messages::{
+    channel_voice::{ChannelVoice, ChannelVoiceMessage},
+    MessageType,
+  },
+  note_freq::Tuning,
+};
+use kiro_time::SampleRate;
+
+use crate::graph::bend::PitchBend;
+use crate::graph::tuning::SharedTuning;
+use crate::graph::velocity::VelocityResponse;
+use crate::graph::voice::{update_envelope, VoiceNode, VoiceProcessor};
+
+/// How the operators of an [`FmVoiceProcessor`] modulate each other,
+/// where the operators with a higher number always modulate the lower ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FmAlgorithm {
+  /// 4 → 3 → 2 → 1
+  Stack,
+  /// 2 → 1 and 4 → 3
+  TwoStacks,
+  /// 2, 3 and 4 → 1
+  Branch,
+  /// All the operators are carriers
+  Additive,
+}
+
+impl FmAlgorithm {
+  pub const ALL: [FmAlgorithm; 4] = [
+    FmAlgorithm::Stack,
+    FmAlgorithm::TwoStacks,
+    FmAlgorithm::Branch,
+    FmAlgorithm::Additive,
+  ];
+
+  pub fn from_value(value: f32) -> Self {
+    let index = value.round().max(0.0) as usize;
+    Self::ALL[index.min(Self::ALL.len() - 1)]
+  }
+
+  pub fn value(&self) -> f32 {
+    Self::ALL
+      .iter()
+      .position(|algorithm| algorithm == self)
+      .unwrap_or(0) as f32
+  }
+
+  pub fn label(&self) -> &'static str {
+    match self {
+      FmAlgorithm::Stack => "stack",
+      FmAlgorithm::TwoStacks => "two-stacks",
+      FmAlgorithm::Branch => "branch",
+      FmAlgorithm::Additive => "additive",
+    }
+  }
+
+  /// Operators modulating an operator, counting from zero.
+  pub fn modulators(&self, operator: usize) -> &'static [usize] {
+    match (self, operator) {
+      (FmAlgorithm::Stack, 0) => &[1],
+      (FmAlgorithm::Stack, 1) => &[2],
+      (FmAlgorithm::Stack, 2) => &[3],
+      (FmAlgorithm::TwoStacks, 0) => &[1],
+      (FmAlgorithm::TwoStacks, 2) => &[3],
+      (FmAlgorithm::Branch, 0) => &[1, 2, 3],
+      _ => &[],
+    }
+  }
+
+  /// Whether an operator is heard, instead of modulating other ones.
+  pub fn is_carrier(&self, operator: usize) -> bool {
+    match self {
+      FmAlgorithm::Stack | FmAlgorithm::Branch => operator == 0,
+      FmAlgorithm::TwoStacks => operator == 0 || operator == 2,
+      FmAlgorithm::Additive => true,
+    }
+  }
+}
+
+pub struct FmVoiceNode;
+
+impl FmVoiceNode {
+  /// Build an FM voice inside one of the modules of the voices of the synth,
+  /// with the same name as the subtractive ones.
+  pub fn build(
+    module: &mut Module,
+    sample_rate: SampleRate,
+    tuning: Tuning,
+  ) -> Result<PolyVoice, kiro_engine::Error> {
+    let processor = FmVoiceProcessor::new(sample_rate as f32).with_tuning(tuning);
+    let node = module.create_processor(VoiceNode::NAME, processor)?;
+    Ok(PolyVoice {
+      events_in: node.events_input(FmVoiceProcessor::EVENTS_IN_NAME)?,
+      audio_out: node.audio_output(FmVoiceProcessor::AUDIO_OUT_NAME)?,
+    })
+  }
+}
+
+/// Sine oscillator of an FM voice, modulated in phase by other operators or by itself.
+#[derive(Debug, Clone, Copy, Default)]
+struct Operator {
+  /// Position along the cycle, in the `0..1` range
+  phase: f32,
+  phase_inc: f32,
+  /// Last two outputs, averaged for the feedback to keep it stable
+  outputs: [f32; 2],
+}
+
+impl Operator {
+  fn generate(&mut self, modulation: f32, feedback: f32) -> f32 {
+    let feedback = (self.outputs[0] + self.outputs[1]) * 0.5 * feedback;
+    let output = (2.0 * PI * (self.phase + modulation + feedback)).sin();
+    self.outputs = [output, self.outputs[0]];
+    self.phase = (self.phase + self.phase_inc).fract();
+    output
+  }
+
+  fn reset(&mut self) {
+    *self = Self::default();
+  }
+}
+
+/// Voice made of four sine operators modulating each other in phase along an [`FmAlgorithm`],
+/// with an envelope shaping the carriers, as an alternative to the subtractive [`VoiceProcessor`].
+///
+/// The output has as many channels as the subtractive voices, with the same signal on both,
+/// so they can be built into the same voices of the synth.
+pub struct FmVoiceProcessor {
+  sample_rate: f32,
+  operators: [Operator; Self::NUM_OPERATORS],
+  envelope: EnvGen<f32>,
+  /// Attack, decay, sustain and release last set into the envelope
+  envelope_params: [f32; 4],
+  velocity: f32,
+  note_freq: f32,
+  /// Pitch bend received through MIDI, with its range as set by the pitch bend sensitivity RPN
+  bend: PitchBend,
+  tuning: Arc<SharedTuning>,
+}
+
+impl FmVoiceProcessor {
+  pub const NUM_OPERATORS: usize = 4;
+  pub const CHANNELS: usize = VoiceProcessor::CHANNELS;
+
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const EVENTS_IN_INDEX: usize = 0;
+
+  pub const ALGORITHM_INDEX: usize = 0;
+  pub const AMPLITUDE_INDEX: usize = 1;
+  pub const ATTACK_INDEX: usize = 2;
+  pub const DECAY_INDEX: usize = 3;
+  pub const SUSTAIN_INDEX: usize = 4;
+  pub const RELEASE_INDEX: usize = 5;
+  pub const LEGATO_INDEX: usize = 6;
+  /// The ratio, level and feedback of every operator follow, see [`FmVoiceProcessor::operator_index`]
+  const FIRST_OPERATOR_INDEX: usize = 7;
+
+  /// Frequency of an operator relative to the note
+  pub const RATIO: usize = 0;
+  /// Output of an operator, as its modulation index for the modulators
+  pub const LEVEL: usize = 1;
+  pub const FEEDBACK: usize = 2;
+  const OPERATOR_PARAMS: usize = 3;
+  /// First of the parameters of the [`VelocityResponse`], after the ones of the operators
+  pub const VELOCITY_INDEX: usize =
+    Self::FIRST_OPERATOR_INDEX + Self::NUM_OPERATORS * Self::OPERATOR_PARAMS;
+
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      sample_rate,
+      operators: [Operator::default(); Self::NUM_OPERATORS],
+      envelope: EnvGen::new(sample_rate),
+      envelope_params: [f32::NAN; 4],
+      velocity: 0.0,
+      note_freq: 0.0,
+      bend: PitchBend::default(),
+      tuning: Arc::default(),
+    }
+  }
+
+  /// Index of a parameter of an operator, counting the operators from zero,
+  /// for one of [`FmVoiceProcessor::RATIO`], [`FmVoiceProcessor::LEVEL`]
+  /// or [`FmVoiceProcessor::FEEDBACK`].
+  pub fn operator_index(operator: usize, param: usize) -> usize {
+    Self::FIRST_OPERATOR_INDEX + operator * Self::OPERATOR_PARAMS + param
+  }
+
+  #[must_use]
+  pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+    self.tuning = SharedTuning::new(tuning);
+    self
+  }
+
+  /// Follow an event from the voice allocator, such as the notes or their pitch bend.
+  fn handle_event(&mut self, event: &Event, legato: bool, velocity_response: VelocityResponse) {
+    if let EventData::Midi(midi::messages::Message {
+      mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+      ..
+    }) = event.data
+    {
+      match message {
+        ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+          self.note_freq = self.tuning.freq(note);
+          if !(legato && self.envelope.is_active()) {
+            self.velocity = velocity_response.level(velocity);
+            self.bend.start_note();
+          }
+          self.envelope.start();
+        }
+        ChannelVoiceMessage::NoteOff { .. } => {
+          self.envelope.note_off();
+        }
+        message => {
+          self.bend.handle_message(&message);
+        }
+      }
+    }
+  }
+
+  /// Set the phase increment of the operators, at their ratio of the bent frequency of the note.
+  fn update_frequency(&mut self, ratios: &[f32; Self::NUM_OPERATORS]) {
+    let semitones = self.bend.semitones(0.0);
+    let freq = self.note_freq * (semitones / 12.0).exp2();
+    for (operator, ratio) in self.operators.iter_mut().zip(ratios) {
+      operator.phase_inc = freq * ratio / self.sample_rate;
+    }
+  }
+}
+
+impl Processor for FmVoiceProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    let mut parameters = vec![
+      ParamDescriptor::new("algorithm").values(
+        FmAlgorithm::ALL
+          .iter()
+          .map(|algorithm| algorithm.label())
+          .collect::<Vec<_>>(),
+      ),
+      ParamDescriptor::new("amplitude").initial(1.0).max(1.0),
+      ParamDescriptor::new("attack")
+        .initial(0.005)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("decay")
+        .initial(0.2)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("sustain")
+        .initial(0.8)
+        .max(1.0)
+        .unit(ParamUnit::Percent),
+      ParamDescriptor::new("release")
+        .initial(0.3)
+        .max(20.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("legato").values(vec!["off", "on"]),
+    ];
+    for operator in 0..Self::NUM_OPERATORS {
+      let number = operator + 1;
+      // only the first operator is heard until the others are raised
+      let level = if operator == 0 { 1.0 } else { 0.0 };
+      parameters.extend([
+        ParamDescriptor::new(format!("op{}-ratio", number))
+          .initial(1.0)
+          .min(0.5)
+          .max(16.0)
+          .precision(2),
+        ParamDescriptor::new(format!("op{}-level", number))
+          .initial(level)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new(format!("op{}-feedback", number)).unit(ParamUnit::Percent),
+      ]);
+    }
+    parameters.extend(VelocityResponse::parameters());
+
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_outputs(vec![AudioDescriptor::new(
+          Self::AUDIO_OUT_NAME,
+          Self::CHANNELS,
+        )])
+      })
+      .with_events_ports(|ports| {
+        ports.static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+      })
+      .with_parameters(parameters)
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.sample_rate = sample_rate as f32;
+    self.envelope = EnvGen::new(self.sample_rate);
+    self.envelope_params = [f32::NAN; 4];
+  }
+
+  fn reset(&mut self) {
+    for operator in self.operators.iter_mut() {
+      operator.reset();
+    }
+    self.envelope.reset();
+    self.velocity = 0.0;
+    self.bend.reset();
+  }
+
+  fn state(&self) -> Option<SharedState> {
+    Some(self.tuning.clone())
+  }
+
+  fn set_state(&mut self, state: &mut SharedState) -> Result<(), StateError> {
+    swap_state(&mut self.tuning, state)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let algorithm = FmAlgorithm::from_value(context.parameter(Self::ALGORITHM_INDEX).get());
+    let amplitude = context.parameter(Self::AMPLITUDE_INDEX).get();
+    update_envelope(
+      &mut self.envelope,
+      &mut self.envelope_params,
+      context,
+      Self::ATTACK_INDEX,
+    );
+    let legato = context.parameter(Self::LEGATO_INDEX).get() >= 0.5;
+    let velocity_response = VelocityResponse::from_context(context, Self::VELOCITY_INDEX);
+    self.envelope.set_legato(legato);
+
+    let mut ratios = [0.0; Self::NUM_OPERATORS];
+    let mut levels = [0.0; Self::NUM_OPERATORS];
+    let mut feedbacks = [0.0; Self::NUM_OPERATORS];
+    for operator in 0..Self::NUM_OPERATORS {
+      ratios[operator] = context
+        .parameter(Self::operator_index(operator, Self::RATIO))
+        .get();
+      levels[operator] = context
+        .parameter(Self::operator_index(operator, Self::LEVEL))
+        .get();
+      feedbacks[operator] = context
+        .parameter(Self::operator_index(operator, Self::FEEDBACK))
+        .get();
+    }
+
+    self.update_frequency(&ratios);
+    let mut events = context
+      .events_input_with_offsets(Self::EVENTS_IN_INDEX)
+      .peekable();
+
+    let num_carriers = (0..Self::NUM_OPERATORS)
+      .filter(|operator| algorithm.is_carrier(*operator))
+      .count();
+    let carrier_gain = (num_carriers as f32).recip();
+
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let mut left = output.channel_mut(0);
+    let mut right = output.channel_mut(1);
+    let samples = left.iter_mut().zip(right.iter_mut()).enumerate();
+    for (index, (left, right)) in samples {
+      // the events change the voice at the sample where they happen, as the gate of the envelope
+      while let Some((_, event)) = events.next_if(|(offset, _)| *offset <= index) {
+        self.handle_event(event, legato, velocity_response);
+        self.update_frequency(&ratios);
+      }
+
+      let envelope = self.envelope.generate();
+      if self.envelope.is_off() {
+        *left = 0.0;
+        *right = 0.0;
+        continue;
+      }
+
+      // the modulators have a higher number, so they are ready before the operators they modulate
+      let mut outputs = [0.0; Self::NUM_OPERATORS];
+      let mut mix = 0.0;
+      for operator in (0..Self::NUM_OPERATORS).rev() {
+        let modulation = algorithm
+          .modulators(operator)
+          .iter()
+          .map(|modulator| outputs[*modulator])
+          .sum::<f32>();
+        let signal = self.operators[operator].generate(modulation, feedbacks[operator]);
+        outputs[operator] = signal * levels[operator];
+        if algorithm.is_carrier(operator) {
+          mix += outputs[operator];
+        }
+      }
+
+      let sample = mix * carrier_gain * envelope * self.velocity * amplitude;
+      *left = sample;
+      *right = sample;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::testing::ProcessorHarness;
+  use kiro_engine::Event;
+  use kiro_midi::messages::Message;
+
+  use super::*;
+
+  fn render(parameters: &[(usize, f32)]) -> Vec<f32> {
+    let mut harness = ProcessorHarness::new(FmVoiceProcessor::new(48_000.0))
+      .with_sample_rate(48_000)
+      .with_block_size(480);
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note: 69,
+      velocity: u16::MAX,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    harness
+      .set_parameter(0, FmVoiceProcessor::ATTACK_INDEX, 0.0)
+      .set_parameter(0, FmVoiceProcessor::SUSTAIN_INDEX, 1.0)
+      .send_event(
+        FmVoiceProcessor::EVENTS_IN_INDEX,
+        Event {
+          timestamp: 0,
+          data: EventData::Midi(Message::channel_voice(0, 0, note_on)),
+        },
+      );
+    for (index, value) in parameters.iter().cloned() {
+      harness.set_parameter(0, index, value);
+    }
+    harness.render(1);
+    harness.render(10).channel(0, 0).to_vec()
+  }
+
+  fn zero_crossings(samples: &[f32]) -> usize {
+    samples
+      .windows(2)
+      .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+      .count()
+  }
+
+  #[test]
+  fn play_a_sine_with_the_first_operator() {
+    // two crossings every cycle along a tenth of a second
+    let samples = render(&[]);
+    assert!((zero_crossings(&samples) as i32 - 88).abs() <= 1);
+    let peak = samples
+      .iter()
+      .fold(0.0f32, |peak, sample| peak.max(*sample));
+    assert!((peak - 1.0).abs() < 0.01);
+  }
+
+  #[test]
+  fn modulate_the_carriers() {
+    let sine = render(&[]);
+    let level = |operator| FmVoiceProcessor::operator_index(operator, FmVoiceProcessor::LEVEL);
+    let ratio = |operator| FmVoiceProcessor::operator_index(operator, FmVoiceProcessor::RATIO);
+
+    // richer in harmonics, which cross zero more often
+    let modulated = render(&[(level(1), 1.0), (ratio(1), 3.0)]);
+    assert!(zero_crossings(&modulated) > zero_crossings(&sine));
+
+    // a modulator that is not heard by itself
+    let branch = render(&[
+      (
+        FmVoiceProcessor::ALGORITHM_INDEX,
+        FmAlgorithm::Branch.value(),
+      ),
+      (level(0), 0.0),
+      (level(3), 1.0),
+    ]);
+    assert!(branch.iter().all(|sample| *sample == 0.0));
+
+    let additive = render(&[
+      (
+        FmVoiceProcessor::ALGORITHM_INDEX,
+        FmAlgorithm::Additive.value(),
+      ),
+      (level(3), 1.0),
+      (ratio(3), 2.0),
+    ]);
+    assert_ne!(additive, sine);
+    assert!(additive.iter().all(|sample| sample.abs() <= 1.0));
+  }
+
+  #[test]
+  fn feed_back_an_operator() {
+    let feedback = FmVoiceProcessor::operator_index(0, FmVoiceProcessor::FEEDBACK);
+    assert_ne!(render(&[(feedback, 0.5)]), render(&[]));
+  }
+}
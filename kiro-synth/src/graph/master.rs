@@ -0,0 +1,185 @@
+use kiro_dsp::funcs::pan_law;
+use kiro_dsp::gain::Gain;
+use kiro_engine::processor::ProcessorContext;
+use kiro_engine::{AudioDescriptor, NodeDescriptor, ParamDescriptor, ParamUnit, Processor};
+
+/// Output stage of the synth, with its gain, the balance and width of the stereo image,
+/// and a soft limiter to keep the sum of the voices below full scale.
+pub struct MasterProcessor {
+  decibels: f32,
+  amplitude: f32,
+}
+
+impl MasterProcessor {
+  pub const AUDIO_IN_NAME: &'static str = "audio-in";
+  pub const AUDIO_IN_INDEX: usize = 0;
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const GAIN_INDEX: usize = 0;
+  /// From `-1` (left) to `1` (right)
+  pub const PAN_INDEX: usize = 1;
+  /// From `0` (mono) to `2` (twice as wide)
+  pub const WIDTH_INDEX: usize = 2;
+  pub const LIMITER_INDEX: usize = 3;
+
+  /// Amplitude above which the limiter starts to bend the signal towards full scale
+  pub const LIMITER_KNEE: f32 = 0.8;
+
+  const MIN_DECIBELS: f32 = Gain::<f32>::MIN_DECIBELS as f32;
+
+  pub fn new() -> Self {
+    Self {
+      decibels: 0.0,
+      amplitude: 1.0,
+    }
+  }
+
+  fn amplitude(&mut self, decibels: f32) -> f32 {
+    if decibels != self.decibels {
+      self.decibels = decibels;
+      self.amplitude = Gain::to_amplitude(decibels);
+    }
+    self.amplitude
+  }
+}
+
+impl Default for MasterProcessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Gains of the channels following the equal power pan law,
+/// compensated so both channels are left untouched when centered, and never boosted.
+fn balance(pan: f32) -> (f32, f32) {
+  let (center, _) = pan_law::equal_power(0.0_f32);
+  let (left, right) = pan_law::equal_power(pan);
+  ((left / center).min(1.0), (right / center).min(1.0))
+}
+
+/// Leave the signal untouched below the knee, and bend it smoothly above,
+/// so it approaches full scale without ever going beyond it.
+fn soft_limit(sample: f32) -> f32 {
+  let knee = MasterProcessor::LIMITER_KNEE;
+  let magnitude = sample.abs();
+  if magnitude <= knee {
+    sample
+  } else {
+    let headroom = 1.0 - knee;
+    let limited = knee + headroom * ((magnitude - knee) / headroom).tanh();
+    limited.copysign(sample)
+  }
+}
+
+impl Processor for MasterProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports
+          .static_inputs(vec![AudioDescriptor::new(Self::AUDIO_IN_NAME, 2)])
+          .static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 2)])
+      })
+      .with_parameters(vec![
+        ParamDescriptor::new("gain")
+          .min(Self::MIN_DECIBELS)
+          .max(12.0)
+          .unit(ParamUnit::Decibels)
+          .precision(1),
+        ParamDescriptor::new("pan").min(-1.0).max(1.0),
+        ParamDescriptor::new("width")
+          .initial(1.0)
+          .max(2.0)
+          .unit(ParamUnit::Percent),
+        ParamDescriptor::new("limiter")
+          .values(vec!["off", "on"])
+          .initial(1.0),
+      ])
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let gains = context.parameter_buffer(Self::GAIN_INDEX);
+    let pan = context.parameter(Self::PAN_INDEX).get().clamp(-1.0, 1.0);
+    let width = context.parameter(Self::WIDTH_INDEX).get();
+    let limiter = context.parameter(Self::LIMITER_INDEX).get() >= 0.5;
+    let (left_gain, right_gain) = balance(pan);
+
+    let input = context.audio_input(Self::AUDIO_IN_INDEX);
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let (input_left, input_right) = (input.channel(0), input.channel(1));
+    let (mut output_left, mut output_right) = (output.channel_mut(0), output.channel_mut(1));
+    let outputs = output_left.iter_mut().zip(output_right.iter_mut());
+    let inputs = input_left.iter().zip(input_right.iter()).zip(gains.iter());
+    for ((out_left, out_right), ((left, right), gain)) in outputs.zip(inputs) {
+      let amplitude = self.amplitude(*gain);
+      let mid = (left + right) * 0.5;
+      let side = (left - right) * 0.5 * width;
+      let left = (mid + side) * left_gain * amplitude;
+      let right = (mid - side) * right_gain * amplitude;
+      if limiter {
+        *out_left = soft_limit(left);
+        *out_right = soft_limit(right);
+      } else {
+        *out_left = left;
+        *out_right = right;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::testing::TestContext;
+
+  use super::*;
+
+  fn master(left: &[f32], right: &[f32]) -> (MasterProcessor, TestContext) {
+    let processor = MasterProcessor::new();
+    let mut context = TestContext::new(&processor.descriptor(), left.len());
+    context.set_input(0, 0, left);
+    context.set_input(0, 1, right);
+    (processor, context)
+  }
+
+  #[test]
+  fn limit_the_peaks_softly() {
+    let (mut processor, mut context) = master(&[0.5, 1.0, 1.2, -20.0], &[0.0; 4]);
+    context.render(&mut processor);
+    let output = context.output(0, 0);
+    assert_eq!(output[0], 0.5);
+    assert!(output[1] > MasterProcessor::LIMITER_KNEE && output[1] < 1.0);
+    assert!(output[2] > output[1] && output[2] < 1.0);
+    assert!(output[3] < -MasterProcessor::LIMITER_KNEE && output[3] >= -1.0);
+
+    context.set_parameter(MasterProcessor::LIMITER_INDEX, 0.0);
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0), vec![0.5, 1.0, 1.2, -20.0]);
+  }
+
+  #[test]
+  fn shape_the_stereo_image() {
+    let (mut processor, mut context) = master(&[0.4], &[0.0]);
+    context.set_parameter(MasterProcessor::GAIN_INDEX, -6.0);
+    context.set_parameter(MasterProcessor::WIDTH_INDEX, 0.0);
+    context.render(&mut processor);
+    let (left, right) = (context.output(0, 0)[0], context.output(0, 1)[0]);
+    assert!((left - 0.1).abs() < 1e-3);
+    assert_eq!(left, right);
+
+    context.set_parameter(MasterProcessor::GAIN_INDEX, 0.0);
+    context.set_parameter(MasterProcessor::WIDTH_INDEX, 1.0);
+    context.set_parameter(MasterProcessor::PAN_INDEX, 0.5);
+    context.render(&mut processor);
+    let (left_gain, _) = pan_law::equal_power(0.5_f32);
+    let (center, _) = pan_law::equal_power(0.0_f32);
+    assert!((context.output(0, 0)[0] - 0.4 * left_gain / center).abs() < 1e-6);
+    assert_eq!(context.output(0, 1), vec![0.0]);
+
+    context.set_parameter(MasterProcessor::PAN_INDEX, 0.0);
+    context.render(&mut processor);
+    assert_eq!(context.output(0, 0), vec![0.4]);
+  }
+}
@@ -1,33 +1,762 @@
+mod bend;
+mod fm_voice;
+mod master;
+mod params;
+mod sampler_voice;
+mod tuning;
+mod velocity;
 mod voice;
 
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
 use thiserror::Error;
 
-use kiro_engine::Engine;
+use kiro_dsp::waveforms::wavetable::{Wavetable, WavetableError};
+use kiro_engine::processor::{decode_state, encode_state};
+use kiro_engine::processors::{
+  read_mono_samples, AnalysisReader, AnalyzerProcessor, ArpeggiatorProcessor, AudioFileError,
+  ChorusProcessor, DelayProcessor, GainProcessor, NotePriority, ReverbProcessor,
+  VoiceAllocatorProcessor,
+};
+use kiro_engine::{
+  Engine, EngineSnapshot, GraphEvent, ObserverId, PolyModule, Processor, ProcessorNode, StateError,
+};
+use kiro_midi::note_freq::Tuning;
 
+use crate::graph::fm_voice::FmVoiceNode;
+use crate::graph::sampler_voice::SamplerVoiceNode;
 use crate::graph::voice::VoiceNode;
 
+pub use crate::graph::fm_voice::{FmAlgorithm, FmVoiceProcessor};
+pub use crate::graph::master::MasterProcessor;
+pub use crate::graph::params::{ParamTarget, ParameterChange, SynthParameter};
+pub use crate::graph::sampler_voice::{Sample, SamplerVoiceProcessor};
+pub use crate::graph::velocity::{VelocityCurve, VelocityResponse};
+pub use crate::graph::voice::VoiceProcessor;
+
 #[derive(Debug, Error)]
 pub enum Error {
   #[error("Engine: {0}")]
   Engine(#[from] kiro_engine::Error),
+
+  #[error("Audio file: {0}")]
+  AudioFile(#[from] AudioFileError),
+
+  #[error("Wavetable: {0}")]
+  Wavetable(#[from] WavetableError),
+
+  #[error("State: {0}")]
+  State(#[from] StateError),
+
+  #[error("Parameter not found: {0}")]
+  ParameterNotFound(String),
+
+  #[error("Preset: {0}")]
+  Preset(#[from] std::io::Error),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// How the notes are played by the voices of the synth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceMode {
+  Poly,
+  /// A single voice playing one of the held notes,
+  /// which slides into the next note without restarting its envelope when legato.
+  Mono {
+    priority: NotePriority,
+    legato: bool,
+  },
+}
+
+/// Implementation of the voices of the synth.
+pub enum VoiceKind {
+  /// Oscillators with analytic and wavetable shapes through a filter, see [`VoiceProcessor`]
+  Subtractive { wavetable: Wavetable<f32> },
+  /// Four sine operators modulating each other, see [`FmVoiceProcessor`]
+  Fm,
+  /// A recording transposed to the notes, see [`SamplerVoiceProcessor`]
+  Sampler { sample: Sample },
+}
+
+/// Voices of the synth, fed by the events of the engine through an arpeggiator,
+/// followed by the chain of effects (chorus and delay), mixed together
+/// with a reverb fed by a send bus from the end of the chain into the master section,
+/// which is sent to the outputs of the engine.
 pub struct SynthGraph {
-  voices: Vec<VoiceNode>,
+  arpeggiator: ProcessorNode,
+  voices: PolyModule,
+  chorus: ProcessorNode,
+  delay: ProcessorNode,
+  reverb_send: ProcessorNode,
+  reverb_send_level: f32,
+  reverb: ProcessorNode,
+  master: ProcessorNode,
+  analyzer: ProcessorNode,
+  analysis: Option<AnalysisReader>,
+  legato: usize,
+  voice_mode: VoiceMode,
+  velocity_params: [usize; VelocityResponse::NUM_PARAMETERS],
+  velocity_response: VelocityResponse,
+  tuning: Tuning,
+  max_active_voices: usize,
 }
 
 impl SynthGraph {
-  pub fn try_new(engine: &mut Engine, sample_rate: u32, num_voices: usize) -> Result<Self> {
-    let mut voices = Vec::new();
+  pub const ARPEGGIATOR_NAME: &'static str = "arpeggiator";
+  pub const CHORUS_NAME: &'static str = "chorus";
+  pub const DELAY_NAME: &'static str = "delay";
+  pub const REVERB_SEND_NAME: &'static str = "reverb-send";
+  pub const REVERB_NAME: &'static str = "reverb";
+  pub const MASTER_NAME: &'static str = "master";
+  pub const ANALYZER_NAME: &'static str = "analyzer";
+
+  /// Decibels of the signal sent to the reverb
+  const DEFAULT_REVERB_SEND: f32 = -12.0;
+
+  pub fn try_new(
+    engine: &mut Engine,
+    sample_rate: u32,
+    num_voices: usize,
+    tuning: &Tuning,
+  ) -> Result<Self> {
+    let wavetable = Wavetable::basic_shapes(Wavetable::<f32>::DEFAULT_FRAME_SIZE)?;
+    let kind = VoiceKind::Subtractive { wavetable };
+    Self::try_with_voices(engine, sample_rate, num_voices, tuning, kind)
+  }
+
+  /// Build the synth with some kind of voices,
+  /// such as subtractive ones with a wavetable loaded with [`load_wavetable`].
+  pub fn try_with_voices(
+    engine: &mut Engine,
+    sample_rate: u32,
+    num_voices: usize,
+    tuning: &Tuning,
+    kind: VoiceKind,
+  ) -> Result<Self> {
+    let (mut voices, release, legato_index, velocity_index) = match kind {
+      VoiceKind::Subtractive { wavetable } => {
+        let wavetable = Arc::new(wavetable);
+        let voices = engine.create_poly_module(
+          "voices",
+          num_voices,
+          VoiceProcessor::CHANNELS,
+          |module, _index| VoiceNode::build(module, sample_rate, tuning.clone(), wavetable.clone()),
+        )?;
+        let release =
+          VoiceProcessor::static_descriptor().parameters[VoiceProcessor::RELEASE_INDEX].initial;
+        (
+          voices,
+          release,
+          VoiceProcessor::LEGATO_INDEX,
+          VoiceProcessor::VELOCITY_INDEX,
+        )
+      }
+      VoiceKind::Fm => {
+        let voices = engine.create_poly_module(
+          "voices",
+          num_voices,
+          FmVoiceProcessor::CHANNELS,
+          |module, _index| FmVoiceNode::build(module, sample_rate, tuning.clone()),
+        )?;
+        let release =
+          FmVoiceProcessor::static_descriptor().parameters[FmVoiceProcessor::RELEASE_INDEX].initial;
+        (
+          voices,
+          release,
+          FmVoiceProcessor::LEGATO_INDEX,
+          FmVoiceProcessor::VELOCITY_INDEX,
+        )
+      }
+      VoiceKind::Sampler { sample } => {
+        let sample = Arc::new(sample);
+        let voices = engine.create_poly_module(
+          "voices",
+          num_voices,
+          SamplerVoiceProcessor::CHANNELS,
+          |module, _index| {
+            SamplerVoiceNode::build(module, sample_rate, tuning.clone(), sample.clone())
+          },
+        )?;
+        let release = SamplerVoiceProcessor::static_descriptor().parameters
+          [SamplerVoiceProcessor::RELEASE_INDEX]
+          .initial;
+        (
+          voices,
+          release,
+          SamplerVoiceProcessor::LEGATO_INDEX,
+          SamplerVoiceProcessor::VELOCITY_INDEX,
+        )
+      }
+    };
 
-    for index in 0..num_voices {
-      let name = format!("voice-{index}");
-      let voice = VoiceNode::try_new(engine, name.as_str(), sample_rate)?;
-      voices.push(voice);
+    // the voices are not reused until the release of their envelopes is over
+    voices
+      .allocator()
+      .set_parameter(VoiceAllocatorProcessor::RELEASE_TAIL_INDEX, release)?;
+
+    let legato = voices.share_parameter("legato", VoiceNode::NAME, legato_index)?;
+    let mut velocity_params = [0; VelocityResponse::NUM_PARAMETERS];
+    for (param, (index, descriptor)) in velocity_params
+      .iter_mut()
+      .zip(VelocityResponse::parameters().iter().enumerate())
+    {
+      *param = voices.share_parameter(&descriptor.id, VoiceNode::NAME, velocity_index + index)?;
     }
 
-    Ok(Self { voices })
+    // the notes reach the voices through the arpeggiator, which lets them through until enabled
+    let arpeggiator =
+      engine.create_processor(Self::ARPEGGIATOR_NAME, ArpeggiatorProcessor::new())?;
+    arpeggiator.set_bypass(true)?;
+    engine
+      .events_inputs()?
+      .connect(&arpeggiator.events_input(ArpeggiatorProcessor::EVENTS_IN_NAME)?)?;
+    arpeggiator
+      .events_output(ArpeggiatorProcessor::EVENTS_OUT_NAME)?
+      .connect_module(&voices.module().events_input(PolyModule::EVENTS_IN_NAME)?)?;
+
+    // the chorus is left out of the chain until enabled by the patch
+    let chorus = engine.create_processor(Self::CHORUS_NAME, ChorusProcessor::new())?;
+    chorus.set_bypass(true)?;
+    let voices_out = voices.module().audio_output(PolyModule::AUDIO_OUT_NAME)?;
+    voices_out.connect(&chorus.audio_input(ChorusProcessor::AUDIO_IN_NAME)?)?;
+    let chorus_out = chorus.audio_output(ChorusProcessor::AUDIO_OUT_NAME)?;
+
+    let delay = engine.create_processor(Self::DELAY_NAME, DelayProcessor::new())?;
+    chorus_out.connect(&delay.audio_input(DelayProcessor::AUDIO_IN_NAME)?)?;
+    let delay_out = delay.audio_output(DelayProcessor::AUDIO_OUT_NAME)?;
+
+    let master = engine.create_processor(Self::MASTER_NAME, MasterProcessor::new())?;
+    let master_in = master.audio_input(MasterProcessor::AUDIO_IN_NAME)?;
+    delay_out.connect(&master_in)?;
+    let master_out = master.audio_output(MasterProcessor::AUDIO_OUT_NAME)?;
+    master_out.connect(&engine.audio_outputs()?)?;
+
+    // the analyzer taps the output for the user interfaces to show it
+    let (analyzer, analysis) = AnalyzerProcessor::new(2, AnalyzerProcessor::DEFAULT_SIZE);
+    let analyzer = engine.create_processor(Self::ANALYZER_NAME, analyzer)?;
+    master_out.connect(&analyzer.audio_input(AnalyzerProcessor::AUDIO_IN_NAME)?)?;
+
+    let reverb_send = engine.create_processor(Self::REVERB_SEND_NAME, GainProcessor::new(2))?;
+    reverb_send.set_parameter(GainProcessor::GAIN_INDEX, Self::DEFAULT_REVERB_SEND)?;
+    let reverb = engine.create_processor(Self::REVERB_NAME, ReverbProcessor::new())?;
+    delay_out.connect(&reverb_send.audio_input(GainProcessor::AUDIO_IN_NAME)?)?;
+    let reverb_send_out = reverb_send.audio_output(GainProcessor::AUDIO_OUT_NAME)?;
+    reverb_send_out.connect(&reverb.audio_input(ReverbProcessor::AUDIO_IN_NAME)?)?;
+    let reverb_out = reverb.audio_output(ReverbProcessor::AUDIO_OUT_NAME)?;
+    reverb_out.add(&master_in)?;
+
+    Ok(Self {
+      arpeggiator,
+      voices,
+      chorus,
+      delay,
+      reverb_send,
+      reverb_send_level: Self::DEFAULT_REVERB_SEND,
+      reverb,
+      master,
+      analyzer,
+      analysis: Some(analysis),
+      legato,
+      voice_mode: VoiceMode::Poly,
+      velocity_params,
+      velocity_response: VelocityResponse::default(),
+      tuning: tuning.clone(),
+      max_active_voices: num_voices,
+    })
+  }
+
+  pub fn num_voices(&self) -> usize {
+    self.voices.num_voices()
+  }
+
+  pub fn max_active_voices(&self) -> usize {
+    self.max_active_voices
+  }
+
+  pub fn voice_mode(&self) -> VoiceMode {
+    self.voice_mode
+  }
+
+  pub fn set_voice_mode(&mut self, voice_mode: VoiceMode) -> Result<()> {
+    let (mono, priority, legato) = match voice_mode {
+      VoiceMode::Poly => (false, NotePriority::Last, false),
+      VoiceMode::Mono { priority, legato } => (true, priority, legato),
+    };
+    let allocator = self.voices.allocator();
+    allocator.set_parameter(
+      VoiceAllocatorProcessor::MODE_INDEX,
+      f32::from(u8::from(mono)),
+    )?;
+    allocator.set_parameter(VoiceAllocatorProcessor::PRIORITY_INDEX, priority.value())?;
+    self
+      .voices
+      .set_parameter(self.legato, f32::from(u8::from(legato)))?;
+    self.voice_mode = voice_mode;
+    Ok(())
+  }
+
+  pub fn velocity_response(&self) -> VelocityResponse {
+    self.velocity_response
+  }
+
+  /// Change how the velocity of the notes is turned into the level of the voices.
+  pub fn set_velocity_response(&mut self, response: VelocityResponse) -> Result<()> {
+    for (param, value) in self.velocity_params.iter().zip(response.values()) {
+      self.voices.set_parameter(*param, value)?;
+    }
+    self.velocity_response = response;
+    Ok(())
+  }
+
+  pub fn tuning(&self) -> &Tuning {
+    &self.tuning
+  }
+
+  /// Retune the voices, such as when loading a preset with its own tuning,
+  /// or a Scala file with [`Tuning::load_scala`].
+  pub fn set_tuning(&mut self, engine: &mut Engine, tuning: Tuning) -> Result<()> {
+    let state = tuning::save_tuning(&tuning)?;
+    for index in 0..self.voices.num_voices() {
+      let voice = self
+        .voices
+        .voice(index)
+        .and_then(|voice| voice.find_node(VoiceNode::NAME));
+      if let Some(node) = voice {
+        engine.load_processor_state(&node, state.clone())?;
+      }
+    }
+    self.tuning = tuning;
+    Ok(())
+  }
+
+  /// Nodes of the synth with some name, or all the voice nodes for [`VoiceNode::NAME`],
+  /// to reach their parameters from outside, as from a [`crate::midi_map::MidiMap`].
+  pub fn parameter_nodes(&self, engine: &Engine, name: &str) -> Vec<ProcessorNode> {
+    if name == VoiceNode::NAME {
+      return (0..self.voices.num_voices())
+        .filter_map(|index| self.voices.voice(index))
+        .filter_map(|voice| voice.find_node(VoiceNode::NAME))
+        .collect();
+    }
+    let nodes = [
+      &self.arpeggiator,
+      &self.chorus,
+      &self.delay,
+      &self.reverb_send,
+      &self.reverb,
+      &self.master,
+    ];
+    nodes
+      .iter()
+      .filter(|node| matches!(node.name(), Ok(node_name) if node_name == name))
+      .filter_map(|node| node.path().ok())
+      .filter_map(|path| engine.find_node(&format!("{}/{}", path, name)))
+      .collect()
+  }
+
+  /// Names of the nodes with parameters that can be reached with [`SynthGraph::parameter_nodes`].
+  pub fn parameter_node_names() -> [&'static str; 7] {
+    [
+      VoiceNode::NAME,
+      Self::ARPEGGIATOR_NAME,
+      Self::CHORUS_NAME,
+      Self::DELAY_NAME,
+      Self::REVERB_SEND_NAME,
+      Self::REVERB_NAME,
+      Self::MASTER_NAME,
+    ]
+  }
+
+  /// All the parameters of the synth, with the values last set for them,
+  /// taking the ones of the first voice for the parameters of the voices.
+  pub fn parameters(&self, engine: &Engine) -> Result<Vec<SynthParameter>> {
+    let mut parameters = Vec::new();
+    for name in Self::parameter_node_names() {
+      if let Some(node) = self.parameter_nodes(engine, name).first() {
+        for (index, descriptor) in node.descriptor()?.parameters.into_iter().enumerate() {
+          parameters.push(SynthParameter {
+            target: ParamTarget::new(name, index),
+            path: format!("{}/{}", name, descriptor.id),
+            value: node.parameter(index)?,
+            descriptor,
+          });
+        }
+      }
+    }
+    Ok(parameters)
+  }
+
+  /// Call `observer` for every change made to the parameters of the synth through the engine,
+  /// following the first voice for the parameters of the voices.
+  ///
+  /// The observer can be removed with [`Engine::unsubscribe`].
+  pub fn subscribe_parameters<F>(&self, engine: &mut Engine, mut observer: F) -> Result<ObserverId>
+  where
+    F: FnMut(&ParameterChange) + 'static,
+  {
+    let mut names = HashMap::new();
+    for name in Self::parameter_node_names() {
+      if let Some(node) = self.parameter_nodes(engine, name).first() {
+        names.insert(format!("{}/{}", node.path()?, node.name()?), name);
+      }
+    }
+    let id = engine.subscribe(move |event| {
+      if let GraphEvent::ParamChanged { node, index, value } = event {
+        if let Some(name) = names.get(node) {
+          observer(&ParameterChange {
+            target: ParamTarget::new(name, *index),
+            value: *value,
+          });
+        }
+      }
+    });
+    Ok(id)
+  }
+
+  /// Save the parameter values of the synth into a preset file.
+  pub fn save_preset<P: AsRef<Path>>(&self, engine: &Engine, path: P) -> Result<()> {
+    let snapshot = engine.snapshot()?;
+    std::fs::write(path, encode_state(&snapshot)?)?;
+    Ok(())
+  }
+
+  /// Load the parameter values saved with [`SynthGraph::save_preset`],
+  /// for a synth built with the same kind and number of voices.
+  pub fn load_preset<P: AsRef<Path>>(&self, engine: &mut Engine, path: P) -> Result<()> {
+    let snapshot: EngineSnapshot = decode_state(&std::fs::read(path)?)?;
+    engine.restore(&snapshot)?;
+    Ok(())
+  }
+
+  /// Whether to follow the channel per note convention of MPE,
+  /// rather than applying the pitch bend and pressure of any channel to all the notes.
+  pub fn set_mpe(&self, mpe: bool) -> Result<()> {
+    let value = f32::from(u8::from(mpe));
+    self
+      .voices
+      .allocator()
+      .set_parameter(VoiceAllocatorProcessor::MPE_INDEX, value)?;
+    Ok(())
+  }
+
+  pub fn arpeggiator(&self) -> &ProcessorNode {
+    &self.arpeggiator
+  }
+
+  pub fn is_arpeggiator_enabled(&self) -> bool {
+    !self.arpeggiator.is_bypassed()
+  }
+
+  /// Play the held notes as an arpeggio, or let them through to the voices.
+  pub fn set_arpeggiator_enabled(&self, enabled: bool) -> Result<()> {
+    self.arpeggiator.set_bypass(!enabled)?;
+    Ok(())
+  }
+
+  pub fn chorus(&self) -> &ProcessorNode {
+    &self.chorus
+  }
+
+  pub fn is_chorus_enabled(&self) -> bool {
+    !self.chorus.is_bypassed()
+  }
+
+  /// Turn the chorus on or off, bypassing it without clicks.
+  pub fn set_chorus_enabled(&self, enabled: bool) -> Result<()> {
+    self.chorus.set_bypass(!enabled)?;
+    Ok(())
+  }
+
+  pub fn delay(&self) -> &ProcessorNode {
+    &self.delay
+  }
+
+  pub fn is_delay_enabled(&self) -> bool {
+    !self.delay.is_bypassed()
+  }
+
+  /// Turn the delay on or off, bypassing it without clicks.
+  pub fn set_delay_enabled(&self, enabled: bool) -> Result<()> {
+    self.delay.set_bypass(!enabled)?;
+    Ok(())
+  }
+
+  pub fn reverb(&self) -> &ProcessorNode {
+    &self.reverb
+  }
+
+  /// Level in decibels of the signal sent to the reverb.
+  pub fn reverb_send(&self) -> f32 {
+    self.reverb_send_level
+  }
+
+  pub fn set_reverb_send(&mut self, decibels: f32) -> Result<()> {
+    self
+      .reverb_send
+      .set_parameter(GainProcessor::GAIN_INDEX, decibels)?;
+    self.reverb_send_level = decibels;
+    Ok(())
+  }
+
+  pub fn master(&self) -> &ProcessorNode {
+    &self.master
+  }
+
+  pub fn analyzer(&self) -> &ProcessorNode {
+    &self.analyzer
+  }
+
+  /// Take the reader of the latest samples and spectrum of the output,
+  /// to be moved into the user interface. There is only one reader, so later calls return `None`.
+  pub fn take_analysis(&mut self) -> Option<AnalysisReader> {
+    self.analysis.take()
+  }
+
+  /// Limit the number of voices that can be active at the same time.
+  /// The voices above the limit play out their notes, but are not given new ones.
+  pub fn set_max_active_voices(&mut self, max_active_voices: usize) -> Result<()> {
+    let max_active_voices = max_active_voices.clamp(1, self.voices.num_voices());
+    self.voices.allocator().set_parameter(
+      VoiceAllocatorProcessor::MAX_VOICES_INDEX,
+      max_active_voices as f32,
+    )?;
+    self.max_active_voices = max_active_voices;
+    Ok(())
+  }
+}
+
+/// Load a wavetable from an audio file with its frames one after the other,
+/// mixing down its channels.
+pub fn load_wavetable<P: AsRef<Path>>(path: P, frame_size: usize) -> Result<Wavetable<f32>> {
+  let (samples, _) = read_mono_samples(path)?;
+  Ok(Wavetable::from_samples(&samples, frame_size)?)
+}
+
+/// Load a sample for the sampler voices from an audio file, mixing down its channels,
+/// to be played at its original pitch by the root note.
+pub fn load_sample<P: AsRef<Path>>(path: P, root_note: u8) -> Result<Sample> {
+  let (samples, sample_rate) = read_mono_samples(path)?;
+  Ok(Sample::new(samples, sample_rate).with_root_note(root_note))
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::{EngineConfig, Event, EventData};
+  use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+  use kiro_midi::messages::Message;
+  use kiro_midi::scala::{KeyboardMapping, Scale};
+
+  use super::*;
+
+  #[test]
+  fn chain_the_effects_after_the_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    assert!(engine.find_node("/root/chorus").is_some());
+    assert!(engine.find_node("/root/delay").is_some());
+    assert!(engine.find_node("/root/reverb").is_some());
+    assert!(engine.find_node("/root/master").is_some());
+    engine.update_render_plan().unwrap();
+
+    assert!(!graph.is_chorus_enabled());
+    graph.set_chorus_enabled(true).unwrap();
+    assert!(graph.is_chorus_enabled());
+
+    assert!(graph.is_delay_enabled());
+    graph.set_delay_enabled(false).unwrap();
+    assert!(!graph.is_delay_enabled());
+
+    assert_eq!(graph.reverb_send(), -12.0);
+    graph.set_reverb_send(-6.0).unwrap();
+    assert_eq!(graph.reverb_send(), -6.0);
+  }
+
+  #[test]
+  fn limit_the_active_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 4, &Tuning::default()).unwrap();
+    engine.update_render_plan().unwrap();
+    assert_eq!(graph.max_active_voices(), 4);
+
+    graph.set_max_active_voices(2).unwrap();
+    assert_eq!(graph.max_active_voices(), 2);
+    graph.set_max_active_voices(8).unwrap();
+    assert_eq!(graph.max_active_voices(), 4);
+    graph.set_max_active_voices(0).unwrap();
+    assert_eq!(graph.max_active_voices(), 1);
+  }
+
+  #[test]
+  fn play_the_notes_through_the_arpeggiator() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    assert!(!graph.is_arpeggiator_enabled());
+    graph.set_arpeggiator_enabled(true).unwrap();
+    assert!(graph.is_arpeggiator_enabled());
+
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note: 69,
+      velocity: u16::MAX,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    let events = [Event {
+      timestamp: 0,
+      data: EventData::Midi(Message::channel_voice(0, 0, note_on)),
+    }];
+    let outputs = engine.render_offline(4096, &events).unwrap();
+    assert!(outputs[0].iter().any(|sample| *sample != 0.0));
+  }
+
+  #[test]
+  fn share_the_velocity_response() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    engine.update_render_plan().unwrap();
+    assert_eq!(graph.velocity_response(), VelocityResponse::default());
+
+    let response = VelocityResponse {
+      curve: VelocityCurve::Logarithmic,
+      sensitivity: 0.5,
+      fixed: true,
+    };
+    graph.set_velocity_response(response).unwrap();
+    assert_eq!(graph.velocity_response(), response);
+  }
+
+  #[test]
+  fn retune_the_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    engine.update_render_plan().unwrap();
+
+    let tuning = Tuning::scala(Scale::equal_division(19), KeyboardMapping::default());
+    graph.set_tuning(&mut engine, tuning.clone()).unwrap();
+    assert_eq!(graph.tuning(), &tuning);
+    let node = engine.find_node("/root/voices/voice-1/voice").unwrap();
+    let state = engine.save_processor_state(&node).unwrap().unwrap();
+    assert_eq!(tuning::load_tuning(&state).unwrap(), tuning);
+  }
+
+  #[test]
+  fn list_and_follow_the_parameters() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    engine.update_render_plan().unwrap();
+
+    let parameters = graph.parameters(&engine).unwrap();
+    let gain = parameters
+      .iter()
+      .find(|param| param.path == "master/gain")
+      .unwrap();
+    assert_eq!(
+      gain.target,
+      ParamTarget::new(SynthGraph::MASTER_NAME, MasterProcessor::GAIN_INDEX)
+    );
+    assert_eq!(gain.value, gain.descriptor.initial);
+    assert!(parameters
+      .iter()
+      .any(|param| param.target.node == VoiceNode::NAME));
+
+    let changes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorded = changes.clone();
+    graph
+      .subscribe_parameters(&mut engine, move |change| {
+        recorded.borrow_mut().push(change.clone())
+      })
+      .unwrap();
+    graph.set_reverb_send(-6.0).unwrap();
+    graph
+      .set_velocity_response(VelocityResponse::default())
+      .unwrap();
+    let changes = changes.borrow();
+    assert_eq!(
+      changes[0],
+      ParameterChange {
+        target: ParamTarget::new(SynthGraph::REVERB_SEND_NAME, GainProcessor::GAIN_INDEX),
+        value: -6.0,
+      }
+    );
+    // the changes of the voices are only reported once
+    assert_eq!(changes.len(), 1 + VelocityResponse::NUM_PARAMETERS);
+  }
+
+  #[test]
+  fn build_fm_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let graph =
+      SynthGraph::try_with_voices(&mut engine, 44_100, 2, &Tuning::default(), VoiceKind::Fm)
+        .unwrap();
+    assert_eq!(graph.num_voices(), 2);
+    assert!(engine.find_node("/root/voices/voice-1/voice").is_some());
+    engine.update_render_plan().unwrap();
+  }
+
+  #[test]
+  fn build_sampler_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let sample = Sample::new(vec![0.0; 100], 44_100);
+    let kind = VoiceKind::Sampler { sample };
+    let graph =
+      SynthGraph::try_with_voices(&mut engine, 44_100, 2, &Tuning::default(), kind).unwrap();
+    assert_eq!(graph.num_voices(), 2);
+    assert!(engine.find_node("/root/voices/voice-1/voice").is_some());
+    engine.update_render_plan().unwrap();
+  }
+
+  #[test]
+  fn save_and_load_presets() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    let path = std::env::temp_dir().join("kiro-synth-preset-test.json");
+    graph.set_reverb_send(-6.0).unwrap();
+    graph.save_preset(&engine, &path).unwrap();
+
+    graph.set_reverb_send(-24.0).unwrap();
+    graph.load_preset(&mut engine, &path).unwrap();
+    let gain = graph
+      .parameters(&engine)
+      .unwrap()
+      .into_iter()
+      .find(|param| param.path == "reverb-send/gain")
+      .unwrap();
+    assert_eq!(gain.value, -6.0);
+    std::fs::remove_file(&path).unwrap();
+
+    let result = graph.load_preset(&mut engine, "missing-preset.json");
+    assert!(matches!(result, Err(Error::Preset(_))));
+  }
+
+  #[test]
+  fn analyze_the_output() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let mut graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    assert!(engine.find_node("/root/analyzer").is_some());
+    let mut analysis = graph.take_analysis().unwrap();
+    assert!(graph.take_analysis().is_none());
+
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note: 69,
+      velocity: u16::MAX,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    let events = [Event {
+      timestamp: 0,
+      data: EventData::Midi(Message::channel_voice(0, 0, note_on)),
+    }];
+    engine.render_offline(4096, &events).unwrap();
+    let analysis = analysis.analysis();
+    assert!(analysis.samples.iter().any(|sample| *sample != 0.0));
+    assert!(analysis.spectrum.iter().any(|magnitude| *magnitude > 0.0));
+  }
+
+  #[test]
+  fn fail_to_load_a_missing_wavetable() {
+    let result = load_wavetable("missing-wavetable.wav", 2048);
+    assert!(matches!(result, Err(Error::AudioFile(_))));
   }
 }
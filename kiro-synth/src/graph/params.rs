@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use kiro_engine::ParamDescriptor;
+
+/// Parameter of one of the nodes of the synth, by the name of the node,
+/// as the [`super::SynthGraph::MASTER_NAME`] or the voice, which stands for all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ParamTarget {
+  pub node: String,
+  pub index: usize,
+}
+
+impl ParamTarget {
+  pub fn new(node: &str, index: usize) -> Self {
+    Self {
+      node: node.to_string(),
+      index,
+    }
+  }
+}
+
+/// Parameter of the synth as listed by [`super::SynthGraph::parameters`],
+/// with everything needed to bind a control to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynthParameter {
+  pub target: ParamTarget,
+  /// Name of the node followed by the id of the parameter, as `master/gain`
+  pub path: String,
+  pub descriptor: ParamDescriptor,
+  pub value: f32,
+}
+
+/// Change of a parameter of the synth, see [`super::SynthGraph::subscribe_parameters`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterChange {
+  pub target: ParamTarget,
+  pub value: f32,
+}
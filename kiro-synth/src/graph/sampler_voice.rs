@@ -0,0 +1,493 @@
+use std::sync::Arc;
+
+use kiro_dsp::envgen::adsr::EnvGen;
+use kiro_dsp::funcs::interpolation::cubic_hermite_interpolation;
+use kiro_engine::processor::ProcessorContext;
+use kiro_engine::{
+  swap_state, AudioDescriptor, Event, EventData, EventsDescriptor, Module, NodeDescriptor,
+  ParamDescriptor, ParamUnit, PolyVoice, Processor, SharedState, StateError,
+};
+use kiro_midi::{
+  self as midi,
+  messages::{
+    channel_voice::{ChannelVoice, ChannelVoiceMessage},
+    MessageType,
+  },
+  note_freq::Tuning,
+};
+use kiro_time::SampleRate;
+
+use crate::graph::bend::PitchBend;
+use crate::graph::tuning::SharedTuning;
+use crate::graph::velocity::VelocityResponse;
+use crate::graph::voice::{update_envelope, VoiceNode, VoiceProcessor};
+
+/// Mono recording played by the sampler voices, mapped across the keyboard from its root note.
+#[derive(Debug, Clone)]
+pub struct Sample {
+  samples: Vec<f32>,
+  sample_rate: SampleRate,
+  root_note: u8,
+}
+
+impl Sample {
+  pub const DEFAULT_ROOT_NOTE: u8 = 60;
+
+  pub fn new(samples: Vec<f32>, sample_rate: SampleRate) -> Self {
+    Self {
+      samples,
+      sample_rate,
+      root_note: Self::DEFAULT_ROOT_NOTE,
+    }
+  }
+
+  /// Set the note played at the original pitch of the recording
+  #[must_use]
+  pub fn with_root_note(mut self, root_note: u8) -> Self {
+    self.root_note = root_note;
+    self
+  }
+
+  pub fn samples(&self) -> &[f32] {
+    &self.samples
+  }
+
+  pub fn sample_rate(&self) -> SampleRate {
+    self.sample_rate
+  }
+
+  pub fn root_note(&self) -> u8 {
+    self.root_note
+  }
+
+  pub fn len(&self) -> usize {
+    self.samples.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.samples.is_empty()
+  }
+}
+
+pub struct SamplerVoiceNode;
+
+impl SamplerVoiceNode {
+  /// Build a sampler voice inside one of the modules of the voices of the synth,
+  /// with the same name as the subtractive ones.
+  pub fn build(
+    module: &mut Module,
+    sample_rate: SampleRate,
+    tuning: Tuning,
+    sample: Arc<Sample>,
+  ) -> Result<PolyVoice, kiro_engine::Error> {
+    let processor = SamplerVoiceProcessor::new(sample_rate as f32, sample).with_tuning(tuning);
+    let node = module.create_processor(VoiceNode::NAME, processor)?;
+    Ok(PolyVoice {
+      events_in: node.events_input(SamplerVoiceProcessor::EVENTS_IN_NAME)?,
+      audio_out: node.audio_output(SamplerVoiceProcessor::AUDIO_OUT_NAME)?,
+    })
+  }
+}
+
+/// Voice playing a [`Sample`] transposed to the notes, with an amplitude envelope,
+/// and optionally looping a region of it while the note is held.
+///
+/// The output has as many channels as the subtractive voices, with the same signal on both,
+/// so they can be built into the same voices of the synth.
+pub struct SamplerVoiceProcessor {
+  sample_rate: f32,
+  sample: Arc<Sample>,
+  /// Position being played, in samples of the recording
+  position: f64,
+  /// Samples of the recording to advance for every sample of the output
+  step: f64,
+  playing: bool,
+  envelope: EnvGen<f32>,
+  /// Attack, decay, sustain and release last set into the envelope
+  envelope_params: [f32; 4],
+  velocity: f32,
+  note: u8,
+  /// Pitch bend received through MIDI, with its range as set by the pitch bend sensitivity RPN
+  bend: PitchBend,
+  tuning: Arc<SharedTuning>,
+}
+
+impl SamplerVoiceProcessor {
+  pub const CHANNELS: usize = VoiceProcessor::CHANNELS;
+
+  pub const AUDIO_OUT_NAME: &'static str = "audio-out";
+  pub const AUDIO_OUT_INDEX: usize = 0;
+
+  pub const EVENTS_IN_NAME: &'static str = "events-in";
+  pub const EVENTS_IN_INDEX: usize = 0;
+
+  pub const AMPLITUDE_INDEX: usize = 0;
+  pub const ATTACK_INDEX: usize = 1;
+  pub const DECAY_INDEX: usize = 2;
+  pub const SUSTAIN_INDEX: usize = 3;
+  pub const RELEASE_INDEX: usize = 4;
+  pub const LEGATO_INDEX: usize = 5;
+  pub const LOOP_INDEX: usize = 6;
+  /// Start of the loop, as a fraction of the length of the sample
+  pub const LOOP_START_INDEX: usize = 7;
+  /// End of the loop, as a fraction of the length of the sample
+  pub const LOOP_END_INDEX: usize = 8;
+  /// First of the parameters of the [`VelocityResponse`]
+  pub const VELOCITY_INDEX: usize = 9;
+
+  pub fn new(sample_rate: f32, sample: Arc<Sample>) -> Self {
+    Self {
+      sample_rate,
+      sample,
+      position: 0.0,
+      step: 1.0,
+      playing: false,
+      envelope: EnvGen::new(sample_rate),
+      envelope_params: [f32::NAN; 4],
+      velocity: 0.0,
+      note: Sample::DEFAULT_ROOT_NOTE,
+      bend: PitchBend::default(),
+      tuning: Arc::default(),
+    }
+  }
+
+  #[must_use]
+  pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+    self.tuning = SharedTuning::new(tuning);
+    self
+  }
+
+  /// Follow an event from the voice allocator, such as the notes or their pitch bend.
+  fn handle_event(&mut self, event: &Event, legato: bool, velocity_response: VelocityResponse) {
+    if let EventData::Midi(midi::messages::Message {
+      mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+      ..
+    }) = event.data
+    {
+      match message {
+        ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+          self.note = note;
+          if !(legato && self.envelope.is_active()) {
+            self.velocity = velocity_response.level(velocity);
+            self.bend.start_note();
+            self.position = 0.0;
+          }
+          self.playing = true;
+          self.envelope.start();
+        }
+        ChannelVoiceMessage::NoteOff { .. } => {
+          self.envelope.note_off();
+        }
+        message => {
+          self.bend.handle_message(&message);
+        }
+      }
+    }
+  }
+
+  /// Samples of the recording to advance for every sample of the output, to play the note.
+  fn step(&self) -> f64 {
+    let ratio = self.tuning.freq(self.note) / self.tuning.freq(self.sample.root_note);
+    let semitones = self.bend.semitones(0.0);
+    let bend = (semitones / 12.0).exp2();
+    let rates = self.sample.sample_rate as f32 / self.sample_rate;
+    f64::from(ratio * bend * rates)
+  }
+
+  /// Sample of the recording at an index, wrapped inside the loop when looping.
+  fn sample_at(&self, index: isize, looping: Option<(usize, usize)>) -> f32 {
+    let samples = self.sample.samples();
+    let index = match looping {
+      Some((start, end)) if index >= end as isize => {
+        start as isize + (index - end as isize) % (end - start) as isize
+      }
+      _ => index,
+    };
+    if index < 0 || index as usize >= samples.len() {
+      0.0
+    } else {
+      samples[index as usize]
+    }
+  }
+}
+
+impl Processor for SamplerVoiceProcessor {
+  fn static_descriptor() -> NodeDescriptor
+  where
+    Self: Sized,
+  {
+    let mut parameters = vec![
+      ParamDescriptor::new("amplitude").initial(1.0).max(1.0),
+      ParamDescriptor::new("attack")
+        .initial(0.001)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("decay")
+        .initial(0.2)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("sustain")
+        .initial(1.0)
+        .max(1.0)
+        .unit(ParamUnit::Percent),
+      ParamDescriptor::new("release")
+        .initial(0.3)
+        .max(20.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("legato").values(vec!["off", "on"]),
+      ParamDescriptor::new("loop").values(vec!["off", "on"]),
+      ParamDescriptor::new("loop-start").unit(ParamUnit::Percent),
+      ParamDescriptor::new("loop-end")
+        .initial(1.0)
+        .unit(ParamUnit::Percent),
+    ];
+    parameters.extend(VelocityResponse::parameters());
+
+    NodeDescriptor::new()
+      .with_audio_ports(|ports| {
+        ports.static_outputs(vec![AudioDescriptor::new(
+          Self::AUDIO_OUT_NAME,
+          Self::CHANNELS,
+        )])
+      })
+      .with_events_ports(|ports| {
+        ports.static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
+      })
+      .with_parameters(parameters)
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    self.sample_rate = sample_rate as f32;
+    self.envelope = EnvGen::new(self.sample_rate);
+    self.envelope_params = [f32::NAN; 4];
+  }
+
+  fn reset(&mut self) {
+    self.position = 0.0;
+    self.playing = false;
+    self.envelope.reset();
+    self.velocity = 0.0;
+    self.bend.reset();
+  }
+
+  fn state(&self) -> Option<SharedState> {
+    Some(self.tuning.clone())
+  }
+
+  fn set_state(&mut self, state: &mut SharedState) -> Result<(), StateError> {
+    swap_state(&mut self.tuning, state)
+  }
+
+  fn render(&mut self, context: &mut ProcessorContext) {
+    let amplitude = context.parameter(Self::AMPLITUDE_INDEX).get();
+    update_envelope(
+      &mut self.envelope,
+      &mut self.envelope_params,
+      context,
+      Self::ATTACK_INDEX,
+    );
+    let legato = context.parameter(Self::LEGATO_INDEX).get() >= 0.5;
+    let velocity_response = VelocityResponse::from_context(context, Self::VELOCITY_INDEX);
+    self.envelope.set_legato(legato);
+
+    // the loop needs at least a sample, and is only kept while the note is held
+    let length = self.sample.len();
+    let loop_start = (context.parameter(Self::LOOP_START_INDEX).get() * length as f32) as usize;
+    let loop_end = (context.parameter(Self::LOOP_END_INDEX).get() * length as f32) as usize;
+    let looping = if context.parameter(Self::LOOP_INDEX).get() >= 0.5 && loop_start < loop_end {
+      Some((loop_start, loop_end.min(length)))
+    } else {
+      None
+    };
+
+    self.step = self.step();
+    let mut events = context
+      .events_input_with_offsets(Self::EVENTS_IN_INDEX)
+      .peekable();
+
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let mut left = output.channel_mut(0);
+    let mut right = output.channel_mut(1);
+    let samples = left.iter_mut().zip(right.iter_mut()).enumerate();
+    for (index, (left, right)) in samples {
+      // the events change the voice at the sample where they happen, as the gate of the envelope
+      while let Some((_, event)) = events.next_if(|(offset, _)| *offset <= index) {
+        self.handle_event(event, legato, velocity_response);
+        self.step = self.step();
+      }
+
+      let envelope = self.envelope.generate();
+      if self.envelope.is_off() || !self.playing {
+        *left = 0.0;
+        *right = 0.0;
+        continue;
+      }
+
+      let index = self.position.floor();
+      let fraction = (self.position - index) as f32;
+      let index = index as isize;
+      let value = cubic_hermite_interpolation(
+        self.sample_at(index - 1, looping),
+        self.sample_at(index, looping),
+        self.sample_at(index + 1, looping),
+        self.sample_at(index + 2, looping),
+        fraction,
+      );
+      let sample = value * envelope * self.velocity * amplitude;
+      *left = sample;
+      *right = sample;
+
+      self.position += self.step;
+      match looping {
+        Some((start, end)) if self.position >= end as f64 => {
+          self.position -= (end - start) as f64;
+        }
+        _ if self.position >= length as f64 => {
+          self.playing = false;
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::testing::ProcessorHarness;
+  use kiro_engine::Event;
+  use kiro_midi::messages::Message;
+
+  use super::*;
+
+  /// A ramp from zero to almost one, a hundred samples long.
+  fn ramp() -> Arc<Sample> {
+    let samples = (0..100).map(|index| index as f32 / 100.0).collect();
+    Arc::new(Sample::new(samples, 1_000).with_root_note(60))
+  }
+
+  fn render(note: u8, parameters: &[(usize, f32)]) -> Vec<f32> {
+    let voice = SamplerVoiceProcessor::new(1_000.0, ramp());
+    let mut harness = ProcessorHarness::new(voice)
+      .with_sample_rate(1_000)
+      .with_block_size(100);
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note,
+      velocity: u16::MAX,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    harness
+      .set_parameter(0, SamplerVoiceProcessor::ATTACK_INDEX, 0.0)
+      .send_event(
+        SamplerVoiceProcessor::EVENTS_IN_INDEX,
+        Event {
+          timestamp: 0,
+          data: EventData::Midi(Message::channel_voice(0, 0, note_on)),
+        },
+      );
+    for (index, value) in parameters.iter().cloned() {
+      harness.set_parameter(0, index, value);
+    }
+    harness.render(3).channel(0, 0).to_vec()
+  }
+
+  #[test]
+  fn play_the_sample_at_the_root_note() {
+    let output = render(60, &[]);
+    assert!((output[50] - 0.5).abs() < 1e-3);
+    assert!((output[99] - 0.99).abs() < 1e-3);
+    assert!(output[100..].iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn transpose_the_sample_to_the_notes() {
+    // an octave above plays twice as fast
+    let output = render(72, &[]);
+    assert!((output[25] - 0.5).abs() < 1e-3);
+    assert!(output[50..].iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn bend_the_note() {
+    let voice = SamplerVoiceProcessor::new(1_000.0, ramp());
+    let mut harness = ProcessorHarness::new(voice)
+      .with_sample_rate(1_000)
+      .with_block_size(100);
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note: 60,
+      velocity: u16::MAX,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    // an octave up, as a quarter of the per-note bend range
+    let bend = ChannelVoiceMessage::PerNotePitchBend {
+      note: 60,
+      data: 0xa000_0000,
+    };
+    harness.set_parameter(0, SamplerVoiceProcessor::ATTACK_INDEX, 0.0);
+    for message in [note_on, bend] {
+      harness.send_event(
+        SamplerVoiceProcessor::EVENTS_IN_INDEX,
+        Event {
+          timestamp: 0,
+          data: EventData::Midi(Message::channel_voice(0, 0, message)),
+        },
+      );
+    }
+    let output = harness.render(1).channel(0, 0).to_vec();
+    assert!((output[25] - 0.5).abs() < 1e-3);
+    assert!(output[50..].iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn follow_the_pitch_bend_sensitivity() {
+    let voice = SamplerVoiceProcessor::new(1_000.0, ramp());
+    let mut harness = ProcessorHarness::new(voice)
+      .with_sample_rate(1_000)
+      .with_block_size(100);
+    // an octave up, as the full bend with a range of 12 semitones
+    let messages = [
+      ChannelVoiceMessage::RegisteredController {
+        bank: 0,
+        index: 0,
+        data: 12 << 25,
+      },
+      ChannelVoiceMessage::NoteOn {
+        note: 60,
+        velocity: u16::MAX,
+        attr_type: 0,
+        attr_data: 0,
+      },
+      ChannelVoiceMessage::PitchBend { data: u32::MAX },
+    ];
+    harness.set_parameter(0, SamplerVoiceProcessor::ATTACK_INDEX, 0.0);
+    for message in messages {
+      harness.send_event(
+        SamplerVoiceProcessor::EVENTS_IN_INDEX,
+        Event {
+          timestamp: 0,
+          data: EventData::Midi(Message::channel_voice(0, 0, message)),
+        },
+      );
+    }
+    let output = harness.render(1).channel(0, 0).to_vec();
+    assert!((output[25] - 0.5).abs() < 1e-3);
+    assert!(output[50..].iter().all(|sample| *sample == 0.0));
+  }
+
+  #[test]
+  fn loop_a_region_of_the_sample() {
+    let output = render(
+      60,
+      &[
+        (SamplerVoiceProcessor::LOOP_INDEX, 1.0),
+        (SamplerVoiceProcessor::LOOP_START_INDEX, 0.5),
+        (SamplerVoiceProcessor::LOOP_END_INDEX, 0.75),
+      ],
+    );
+    // back to the start of the loop every 25 samples
+    assert!((output[60] - 0.6).abs() < 1e-3);
+    assert!((output[85] - 0.6).abs() < 1e-3);
+    assert!((output[210] - 0.6).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,100 @@
+use std::any::Any;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use kiro_engine::processor::{decode_state, encode_state};
+use kiro_engine::{SharedState, StateError, StateSnapshot};
+use kiro_midi::note_freq::Tuning;
+use kiro_midi::scala::{KeyboardMapping, Scale};
+
+/// Tuning of the voices as it is kept in their state, so every preset can choose its own,
+/// with the scale and keyboard mapping written as the contents of their Scala files.
+#[derive(Debug, Serialize, Deserialize)]
+struct TuningState {
+  reference_freq: f32,
+  offsets: [f32; 12],
+  scale: Option<String>,
+  keyboard_mapping: Option<String>,
+}
+
+pub(super) fn save_tuning(tuning: &Tuning) -> Result<Vec<u8>, StateError> {
+  let state = TuningState {
+    reference_freq: tuning.reference_freq(),
+    offsets: *tuning.offsets(),
+    scale: tuning.scale().map(Scale::to_string),
+    keyboard_mapping: tuning.keyboard_mapping().map(KeyboardMapping::to_string),
+  };
+  encode_state(&state)
+}
+
+pub(super) fn load_tuning(state: &[u8]) -> Result<Tuning, StateError> {
+  let state: TuningState = decode_state(state)?;
+  let invalid = |err: kiro_midi::scala::ScalaError| StateError::Invalid(err.to_string());
+  let tuning = match (state.scale, state.keyboard_mapping) {
+    (Some(scale), mapping) => {
+      let scale = scale.parse::<Scale>().map_err(invalid)?;
+      let mapping = match mapping {
+        Some(mapping) => mapping.parse::<KeyboardMapping>().map_err(invalid)?,
+        None => KeyboardMapping::linear(state.reference_freq),
+      };
+      Tuning::scala(scale, mapping)
+    }
+    (None, _) => Tuning::new(state.reference_freq, [0.0; 12]),
+  };
+  Ok(tuning.with_offsets(state.offsets))
+}
+
+/// Tuning of the voices, shared with the controller as their state.
+#[derive(Debug, Default)]
+pub(super) struct SharedTuning(Tuning);
+
+impl SharedTuning {
+  pub(super) fn new(tuning: Tuning) -> Arc<Self> {
+    Arc::new(Self(tuning))
+  }
+}
+
+impl Deref for SharedTuning {
+  type Target = Tuning;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl StateSnapshot for SharedTuning {
+  fn encode(&self) -> Result<Vec<u8>, StateError> {
+    save_tuning(&self.0)
+  }
+
+  fn decode(&self, state: &[u8]) -> Result<SharedState, StateError> {
+    load_tuning(state).map(|tuning| Self::new(tuning) as SharedState)
+  }
+
+  fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keep_the_tuning_in_the_state() {
+    let tuning = Tuning::equal_temperament(432.0).with_offsets([5.0; 12]);
+    let state = save_tuning(&tuning).unwrap();
+    assert_eq!(load_tuning(&state).unwrap(), tuning);
+
+    let tuning = Tuning::scala(Scale::equal_division(19), KeyboardMapping::linear(440.0));
+    let state = save_tuning(&tuning).unwrap();
+    assert_eq!(load_tuning(&state).unwrap(), tuning);
+
+    assert!(matches!(
+      load_tuning(br#"{"reference_freq":440.0,"offsets":[0,0,0,0,0,0,0,0,0,0,0,0],"scale":"x","keyboard_mapping":null}"#),
+      Err(StateError::Invalid(_))
+    ));
+  }
+}
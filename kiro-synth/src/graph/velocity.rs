@@ -0,0 +1,164 @@
+use kiro_engine::processor::ProcessorContext;
+use kiro_engine::{ParamDescriptor, ParamUnit};
+
+/// Shape of the response of the voices to the velocity of the notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityCurve {
+  Linear,
+  /// Softer for most of the range, so the loud notes need to be played hard
+  Exponential,
+  /// Louder for most of the range, so the soft notes are easier to hear
+  Logarithmic,
+}
+
+impl VelocityCurve {
+  pub const ALL: [VelocityCurve; 3] = [
+    VelocityCurve::Linear,
+    VelocityCurve::Exponential,
+    VelocityCurve::Logarithmic,
+  ];
+
+  /// How far the exponential and logarithmic curves bend away from the linear one
+  const STEEPNESS: f32 = 4.0;
+
+  pub fn from_value(value: f32) -> Self {
+    let index = (value.max(0.0) as usize).min(Self::ALL.len() - 1);
+    Self::ALL[index]
+  }
+
+  pub fn value(&self) -> f32 {
+    match self {
+      VelocityCurve::Linear => 0.0,
+      VelocityCurve::Exponential => 1.0,
+      VelocityCurve::Logarithmic => 2.0,
+    }
+  }
+
+  pub fn label(&self) -> &'static str {
+    match self {
+      VelocityCurve::Linear => "linear",
+      VelocityCurve::Exponential => "exp",
+      VelocityCurve::Logarithmic => "log",
+    }
+  }
+
+  /// Map a velocity in the `0..1` range into a level in the same range.
+  pub fn apply(&self, velocity: f32) -> f32 {
+    let velocity = velocity.clamp(0.0, 1.0);
+    let range = Self::STEEPNESS.exp() - 1.0;
+    match self {
+      VelocityCurve::Linear => velocity,
+      VelocityCurve::Exponential => ((Self::STEEPNESS * velocity).exp() - 1.0) / range,
+      VelocityCurve::Logarithmic => (1.0 + range * velocity).ln() / Self::STEEPNESS,
+    }
+  }
+}
+
+/// How the velocity of the notes is turned into the level used by the voices,
+/// before it reaches the amplitude or any other modulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VelocityResponse {
+  pub curve: VelocityCurve,
+  /// How much the velocity changes the level, from `0` (none) to `1` (the whole range)
+  pub sensitivity: f32,
+  /// Play all the notes as if they were played at the full velocity
+  pub fixed: bool,
+}
+
+impl VelocityResponse {
+  /// Number of parameters used by the voices for the response, see [`VelocityResponse::parameters`]
+  pub const NUM_PARAMETERS: usize = 3;
+
+  const CURVE: usize = 0;
+  const SENSITIVITY: usize = 1;
+  const FIXED: usize = 2;
+
+  /// Descriptors of the parameters for the response, to be added one after the other to a voice.
+  pub fn parameters() -> [ParamDescriptor; Self::NUM_PARAMETERS] {
+    let curves = VelocityCurve::ALL
+      .iter()
+      .map(VelocityCurve::label)
+      .collect::<Vec<_>>();
+    [
+      ParamDescriptor::new("velocity-curve").values(curves),
+      ParamDescriptor::new("velocity-sensitivity")
+        .initial(1.0)
+        .unit(ParamUnit::Percent),
+      ParamDescriptor::new("fixed-velocity").values(vec!["off", "on"]),
+    ]
+  }
+
+  /// Read the response from the parameters of a voice, starting at the index of the first one.
+  pub fn from_context(context: &ProcessorContext, first_index: usize) -> Self {
+    let parameter = |param: usize| context.parameter(first_index + param).get();
+    Self {
+      curve: VelocityCurve::from_value(parameter(Self::CURVE)),
+      sensitivity: parameter(Self::SENSITIVITY).clamp(0.0, 1.0),
+      fixed: parameter(Self::FIXED) >= 0.5,
+    }
+  }
+
+  /// Values for the parameters of the response, in the same order as their descriptors.
+  pub fn values(&self) -> [f32; Self::NUM_PARAMETERS] {
+    [
+      self.curve.value(),
+      self.sensitivity,
+      f32::from(u8::from(self.fixed)),
+    ]
+  }
+
+  /// Level in the `0..1` range for the velocity of a MIDI 2.0 note.
+  pub fn level(&self, velocity: u16) -> f32 {
+    if self.fixed {
+      return 1.0;
+    }
+    let velocity = f32::from(velocity) / f32::from(u16::MAX);
+    1.0 - self.sensitivity * (1.0 - self.curve.apply(velocity))
+  }
+}
+
+impl Default for VelocityResponse {
+  fn default() -> Self {
+    Self {
+      curve: VelocityCurve::Linear,
+      sensitivity: 1.0,
+      fixed: false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bend_the_curves_around_the_linear_one() {
+    for curve in VelocityCurve::ALL {
+      assert!(curve.apply(0.0).abs() < 1e-6);
+      assert!((curve.apply(1.0) - 1.0).abs() < 1e-6);
+      assert_eq!(VelocityCurve::from_value(curve.value()), curve);
+    }
+    assert!(VelocityCurve::Exponential.apply(0.5) < 0.5);
+    assert!(VelocityCurve::Logarithmic.apply(0.5) > 0.5);
+  }
+
+  #[test]
+  fn scale_the_level_by_the_sensitivity() {
+    let half = u16::MAX / 2;
+    let response = VelocityResponse::default();
+    assert!((response.level(half) - 0.5).abs() < 1e-3);
+
+    let response = VelocityResponse {
+      sensitivity: 0.5,
+      ..VelocityResponse::default()
+    };
+    assert!((response.level(0) - 0.5).abs() < 1e-6);
+    assert!((response.level(half) - 0.75).abs() < 1e-3);
+
+    let response = VelocityResponse {
+      fixed: true,
+      ..VelocityResponse::default()
+    };
+    assert_eq!(response.level(0), 1.0);
+  }
+}
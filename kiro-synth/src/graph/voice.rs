@@ -1,13 +1,23 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use kiro_dsp::envgen::adsr::EnvGen;
+use kiro_dsp::filters::freq_control::FreqControl;
+use kiro_dsp::filters::q_control::QControl;
+use kiro_dsp::filters::svf::StateVariableFilter;
 use kiro_dsp::oscillators::osc_waveform::OscWaveform;
 use kiro_dsp::oscillators::pitched_oscillator::PitchedOscillator;
 use kiro_dsp::smoother::{LinearSteps, LinearStepsSmoother};
+use kiro_dsp::waveforms::pulse_blep::PulseBlep;
 use kiro_dsp::waveforms::saw_blep::{self, SawBlep};
 use kiro_dsp::waveforms::sine_parabolic::SineParabolic;
 use kiro_dsp::waveforms::triangle_dpw2x::TriangleDpw2x;
+use kiro_dsp::waveforms::wavetable::{Wavetable, WavetableWaveform};
 use kiro_engine::processor::ProcessorContext;
+use kiro_engine::processors::{LfoProcessor, VoiceAllocatorProcessor};
 use kiro_engine::{
-  AudioDescriptor, AudioNodeOut, Engine, EventData, EventsDescriptor, Module, ModuleDescriptor,
-  NodeDescriptor, ParamDescriptor, Processor, ProcessorNode,
+  swap_state, AudioDescriptor, Event, EventData, EventsDescriptor, Module, NodeDescriptor,
+  ParamDescriptor, ParamScale, ParamUnit, PolyVoice, Processor, SharedState, StateError,
 };
 use kiro_midi::{
   self as midi,
@@ -15,38 +25,124 @@ use kiro_midi::{
     channel_voice::{ChannelVoice, ChannelVoiceMessage},
     MessageType,
   },
+  note_freq::Tuning,
 };
 use kiro_time::SampleRate;
 
-use crate::graph::Error;
+use crate::graph::bend::PitchBend;
+use crate::graph::tuning::SharedTuning;
+use crate::graph::velocity::VelocityResponse;
 
-pub struct VoiceNode {
-  node: ProcessorNode,
-  audio_out: AudioNodeOut,
-}
+pub struct VoiceNode;
 
 impl VoiceNode {
-  pub fn try_new(engine: &mut Engine, name: &str, sample_rate: SampleRate) -> Result<Self, Error> {
-    let node = engine.create_processor(name, VoiceProcessor::new(sample_rate as f32))?;
-    let events_in = node.events_input(VoiceProcessor::EVENTS_IN_NAME)?;
-    let audio_out = node.audio_output(VoiceProcessor::AUDIO_OUT_NAME)?;
-    Ok(Self { node, audio_out })
+  pub const NAME: &'static str = "voice";
+  pub const LFO_PITCH_NAME: &'static str = "lfo-pitch";
+  pub const LFO_AMPLITUDE_NAME: &'static str = "lfo-amplitude";
+  pub const LFO_CUTOFF_NAME: &'static str = "lfo-cutoff";
+
+  /// Semitones of pitch modulation at the full depth of its LFO
+  const LFO_PITCH_RANGE: f32 = 12.0;
+  /// Amplitude modulation at the full depth of its LFO, as for a tremolo
+  const LFO_AMPLITUDE_RANGE: f32 = 0.5;
+  /// Semitones of cutoff modulation at the full depth of its LFO
+  const LFO_CUTOFF_RANGE: f32 = 48.0;
+
+  /// Build a voice inside one of the modules of the voices of the synth,
+  /// together with the LFOs modulating its pitch, amplitude and filter cutoff,
+  /// which start with no depth.
+  pub fn build(
+    module: &mut Module,
+    sample_rate: SampleRate,
+    tuning: Tuning,
+    wavetable: Arc<Wavetable<f32>>,
+  ) -> Result<PolyVoice, kiro_engine::Error> {
+    let processor = VoiceProcessor::new(sample_rate as f32)
+      .with_tuning(tuning)
+      .with_wavetable(wavetable);
+    let mut node = module.create_processor(Self::NAME, processor)?;
+
+    let routes = [
+      (
+        Self::LFO_PITCH_NAME,
+        VoiceProcessor::SEMITONES_INDEX,
+        Self::LFO_PITCH_RANGE,
+      ),
+      (
+        Self::LFO_AMPLITUDE_NAME,
+        VoiceProcessor::AMPLITUDE_INDEX,
+        Self::LFO_AMPLITUDE_RANGE,
+      ),
+      (
+        Self::LFO_CUTOFF_NAME,
+        VoiceProcessor::CUTOFF_MOD_INDEX,
+        Self::LFO_CUTOFF_RANGE,
+      ),
+    ];
+    for (name, index, range) in routes {
+      let lfo = module.create_processor(name, LfoProcessor::new())?;
+      lfo.set_parameter(LfoProcessor::DEPTH_INDEX, 0.0)?;
+      let source = lfo.audio_output(LfoProcessor::AUDIO_OUT_NAME)?;
+      node.modulate_parameter(index, source, range)?;
+    }
+
+    Ok(PolyVoice {
+      events_in: node.events_input(VoiceProcessor::EVENTS_IN_NAME)?,
+      audio_out: node.audio_output(VoiceProcessor::AUDIO_OUT_NAME)?,
+    })
   }
 }
 
 pub struct VoiceProcessor {
-  waveforms: [OscWaveform<f32>; 3],
+  waveforms: [OscWaveform<f32>; Self::NUM_SHAPES],
   waveform_index: usize,
-  osc: PitchedOscillator<f32>,
+  /// Oscillators of the unison, all of them kept up to date although only the first ones sound
+  oscs: Vec<PitchedOscillator<f32>>,
+  /// Second oscillator of every oscillator of the unison, optionally hard synced to it
+  oscs2: Vec<PitchedOscillator<f32>>,
+  waveform2_index: usize,
+  /// Semitones from the note to the pitch of the second oscillators
+  osc2_transpose: f32,
+  note_freq: f32,
+  unison: usize,
+  detune: f32,
+  spread: f32,
+  /// Left and right gains of every oscillator of the unison
+  gains: [[f32; 2]; Self::MAX_UNISON],
   shape: LinearStepsSmoother<f32>,
   semitones: LinearStepsSmoother<f32>,
   cents: LinearStepsSmoother<f32>,
   pitch_bend: LinearStepsSmoother<f32>,
   amplitude: LinearStepsSmoother<f32>,
+  envelope: EnvGen<f32>,
+  /// Attack, decay, sustain and release last set into the envelope
+  envelope_params: [f32; 4],
+  /// A filter for every channel of the output
+  filters: [StateVariableFilter<f32>; 2],
+  cutoff: LinearStepsSmoother<f32>,
+  velocity: f32,
+  /// Pitch bend received through MIDI, with its range as set by the pitch bend sensitivity RPN
+  bend: PitchBend,
+  /// Per-note pressure and timbre of the note being played, as sent by MPE controllers
+  pressure: f32,
+  timbre: f32,
+  /// Pressure of the whole channel, applied to the note together with its own pressure
+  channel_pressure: f32,
+  /// Phase of the vibrato brought by the pressure, in the `0..1` range
+  vibrato_phase: f32,
+  /// Semitones from the middle C to the note being played, for the key tracking of the filter
+  key_offset: f32,
+  tuning: Arc<SharedTuning>,
 }
 
 impl VoiceProcessor {
-  pub const NUM_SHAPES: usize = 3;
+  pub const NUM_SHAPES: usize = 5;
+  /// Shape reading the wavetable of the voice
+  pub const WAVETABLE_SHAPE: usize = 3;
+  /// Pulse shape, with its width following the pulse width parameter
+  pub const PULSE_SHAPE: usize = 4;
+  pub const MAX_UNISON: usize = 7;
+  pub const CHANNELS: usize = 2;
 
   pub const AUDIO_OUT_NAME: &'static str = "audio-out";
   pub const AUDIO_OUT_INDEX: usize = 0;
@@ -57,8 +153,51 @@ impl VoiceProcessor {
   pub const SHAPE_INDEX: usize = 0;
   pub const SEMITONES_INDEX: usize = 1;
   pub const CENTS_INDEX: usize = 2;
+  /// Pitch bend as a fraction of the bend range, added to the one received through MIDI
   pub const PITCH_BEND_INDEX: usize = 3;
   pub const AMPLITUDE_INDEX: usize = 4;
+  pub const ATTACK_INDEX: usize = 5;
+  pub const DECAY_INDEX: usize = 6;
+  pub const SUSTAIN_INDEX: usize = 7;
+  pub const RELEASE_INDEX: usize = 8;
+  pub const CUTOFF_INDEX: usize = 9;
+  pub const RESONANCE_INDEX: usize = 10;
+  pub const KEY_TRACKING_INDEX: usize = 11;
+  pub const ENV_AMOUNT_INDEX: usize = 12;
+  /// Semitones added to the cutoff, meant to be modulated
+  pub const CUTOFF_MOD_INDEX: usize = 13;
+  pub const UNISON_INDEX: usize = 14;
+  /// Cents between the most detuned oscillators of the unison and the note
+  pub const DETUNE_INDEX: usize = 15;
+  pub const STEREO_SPREAD_INDEX: usize = 16;
+  /// Notes played while another one is held only change the pitch
+  pub const LEGATO_INDEX: usize = 17;
+  /// Semitones of cutoff modulation at the ends of the per-note timbre
+  pub const TIMBRE_AMOUNT_INDEX: usize = 18;
+  /// Gain added at the full pressure
+  pub const PRESSURE_AMOUNT_INDEX: usize = 19;
+  pub const OSC2_SHAPE_INDEX: usize = 20;
+  pub const OSC2_SEMITONES_INDEX: usize = 21;
+  pub const OSC2_CENTS_INDEX: usize = 22;
+  pub const OSC2_LEVEL_INDEX: usize = 23;
+  /// The second oscillator restarts its cycle with every cycle of the first one
+  pub const OSC2_SYNC_INDEX: usize = 24;
+  /// Position between the first and last frames of the wavetable
+  pub const WAVETABLE_POSITION_INDEX: usize = 25;
+  /// First of the parameters of the [`VelocityResponse`]
+  pub const VELOCITY_INDEX: usize = 26;
+  /// Semitones of vibrato depth at the full pressure
+  pub const PRESSURE_VIBRATO_INDEX: usize = Self::VELOCITY_INDEX + VelocityResponse::NUM_PARAMETERS;
+  /// Semitones of cutoff modulation at the full pressure
+  pub const PRESSURE_CUTOFF_INDEX: usize = Self::PRESSURE_VIBRATO_INDEX + 1;
+  /// Width of the pulse shape, a half being a square
+  pub const PULSE_WIDTH_INDEX: usize = Self::PRESSURE_CUTOFF_INDEX + 1;
+
+  const KEY_TRACKING_CENTER: u8 = 60;
+
+  const CENTERED_TIMBRE: f32 = 0.5;
+  /// Rate in Hz of the vibrato brought by the pressure
+  const PRESSURE_VIBRATO_RATE: f32 = 5.5;
 
   pub fn new(sample_rate: f32) -> Self {
     let waveforms: [OscWaveform<f32>; Self::NUM_SHAPES] = [
@@ -69,36 +208,157 @@ impl VoiceProcessor {
           .with_mode(saw_blep::Mode::Bipolar)
           .with_correction(saw_blep::Correction::EightPointBlepWithInterpolation),
       ),
+      Self::wavetable_waveform(Arc::new(
+        Wavetable::basic_shapes(Wavetable::<f32>::DEFAULT_FRAME_SIZE).expect("valid frame size"),
+      )),
+      OscWaveform::PulseBlep(PulseBlep::default()),
     ];
-    let params = Self::static_descriptor().parameters;
-    let osc = PitchedOscillator::new(sample_rate, waveforms[0].clone(), 80.0);
-    let smoothing_strategy = LinearSteps::from_time(sample_rate, 0.0005);
-    Self {
+    let new_oscs = || {
+      (0..Self::MAX_UNISON)
+        .map(|_| PitchedOscillator::new(sample_rate, waveforms[0].clone(), 80.0))
+        .collect::<Vec<_>>()
+    };
+    let (oscs, oscs2) = (new_oscs(), new_oscs());
+    let mut processor = Self {
       waveforms,
       waveform_index: 0,
-      osc,
-      shape: LinearStepsSmoother::new(
-        params[Self::SHAPE_INDEX].initial,
-        smoothing_strategy.clone(),
-      ),
-      semitones: LinearStepsSmoother::new(
-        params[Self::SEMITONES_INDEX].initial,
-        smoothing_strategy.clone(),
-      ),
-      cents: LinearStepsSmoother::new(
-        params[Self::CENTS_INDEX].initial,
-        smoothing_strategy.clone(),
-      ),
-      pitch_bend: LinearStepsSmoother::new(
-        params[Self::PITCH_BEND_INDEX].initial,
-        smoothing_strategy.clone(),
-      ),
-      amplitude: LinearStepsSmoother::new(
-        params[Self::AMPLITUDE_INDEX].initial,
-        smoothing_strategy.clone(),
-      ),
+      oscs,
+      oscs2,
+      waveform2_index: 0,
+      osc2_transpose: 0.0,
+      note_freq: 80.0,
+      unison: 1,
+      detune: 0.0,
+      spread: 0.0,
+      gains: [[0.0; 2]; Self::MAX_UNISON],
+      shape: Self::smoother(Self::SHAPE_INDEX, sample_rate),
+      semitones: Self::smoother(Self::SEMITONES_INDEX, sample_rate),
+      cents: Self::smoother(Self::CENTS_INDEX, sample_rate),
+      pitch_bend: Self::smoother(Self::PITCH_BEND_INDEX, sample_rate),
+      amplitude: Self::smoother(Self::AMPLITUDE_INDEX, sample_rate),
+      envelope: EnvGen::new(sample_rate),
+      envelope_params: [f32::NAN; 4],
+      filters: [Self::filter(sample_rate), Self::filter(sample_rate)],
+      cutoff: Self::smoother(Self::CUTOFF_INDEX, sample_rate),
+      velocity: 0.0,
+      bend: PitchBend::default(),
+      pressure: 0.0,
+      timbre: Self::CENTERED_TIMBRE,
+      channel_pressure: 0.0,
+      vibrato_phase: 0.0,
+      key_offset: 0.0,
+      tuning: Arc::default(),
+    };
+    processor.update_unison(1, 0.0, 0.0);
+    processor
+  }
+
+  fn smoother(param_index: usize, sample_rate: f32) -> LinearStepsSmoother<f32> {
+    let params = Self::static_descriptor().parameters;
+    LinearStepsSmoother::new(
+      params[param_index].initial,
+      LinearSteps::from_time(sample_rate, 0.0005),
+    )
+  }
+
+  fn filter(sample_rate: f32) -> StateVariableFilter<f32> {
+    let params = Self::static_descriptor().parameters;
+    StateVariableFilter::new(
+      sample_rate,
+      params[Self::CUTOFF_INDEX].initial,
+      QControl::default_q(),
+    )
+  }
+
+  /// Spread the oscillators of the unison evenly along the detune and the stereo field,
+  /// keeping the loudness when adding more of them.
+  fn update_unison(&mut self, unison: usize, detune: f32, spread: f32) {
+    self.unison = unison;
+    self.detune = detune;
+    self.spread = spread;
+    let gain = (unison as f32).sqrt().recip();
+    let oscs = self.oscs.iter_mut().zip(self.oscs2.iter_mut()).enumerate();
+    for (index, (osc, osc2)) in oscs {
+      let position = if unison > 1 && index < unison {
+        2.0 * index as f32 / (unison - 1) as f32 - 1.0
+      } else {
+        0.0
+      };
+      osc.set_frequency_modulation(position * detune / 100.0);
+      osc2.set_frequency_modulation(position * detune / 100.0);
+      let pan = position * spread;
+      self.gains[index] = [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)];
     }
   }
+
+  /// Follow an event from the voice allocator, such as the notes or their expression.
+  fn handle_event(&mut self, event: &Event, legato: bool, velocity_response: VelocityResponse) {
+    if let EventData::Midi(midi::messages::Message {
+      mtype: MessageType::ChannelVoice(ChannelVoice { message, .. }),
+      ..
+    }) = event.data
+    {
+      match message {
+        ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
+          self.note_freq = self.tuning.freq(note);
+          for osc in self.oscs.iter_mut() {
+            osc.set_pitch_frequency(self.note_freq);
+          }
+          let freq2 = self.osc2_frequency();
+          for osc in self.oscs2.iter_mut() {
+            osc.set_pitch_frequency(freq2);
+          }
+          self.key_offset = f32::from(note) - f32::from(Self::KEY_TRACKING_CENTER);
+          if !(legato && self.envelope.is_active()) {
+            self.velocity = velocity_response.level(velocity);
+            self.bend.start_note();
+            self.pressure = 0.0;
+            self.timbre = Self::CENTERED_TIMBRE;
+          }
+          self.envelope.start();
+        }
+        ChannelVoiceMessage::NoteOff { .. } => {
+          self.envelope.note_off();
+        }
+        ChannelVoiceMessage::PolyPressure { pressure, .. } => {
+          self.pressure = pressure as f32 / u32::MAX as f32;
+        }
+        ChannelVoiceMessage::ChannelPressure { pressure } => {
+          self.channel_pressure = pressure as f32 / u32::MAX as f32;
+        }
+        ChannelVoiceMessage::RegisteredPerNoteController { index, data, .. }
+          if index == VoiceAllocatorProcessor::TIMBRE_CONTROLLER =>
+        {
+          self.timbre = data as f32 / u32::MAX as f32;
+        }
+        message => {
+          self.bend.handle_message(&message);
+        }
+      }
+    }
+  }
+
+  /// Frequency of the second oscillators for the note being played.
+  fn osc2_frequency(&self) -> f32 {
+    self.note_freq * (self.osc2_transpose / 12.0).exp2()
+  }
+
+  fn wavetable_waveform(wavetable: Arc<Wavetable<f32>>) -> OscWaveform<f32> {
+    OscWaveform::Wavetable(WavetableWaveform::new(wavetable))
+  }
+
+  #[must_use]
+  pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+    self.tuning = SharedTuning::new(tuning);
+    self
+  }
+
+  /// Use a wavetable for the wavetable shape, instead of the built-in basic shapes.
+  #[must_use]
+  pub fn with_wavetable(mut self, wavetable: Arc<Wavetable<f32>>) -> Self {
+    self.waveforms[Self::WAVETABLE_SHAPE] = Self::wavetable_waveform(wavetable);
+    self
+  }
 }
 
 impl Processor for VoiceProcessor {
@@ -106,24 +366,148 @@ impl Processor for VoiceProcessor {
   where
     Self: Sized,
   {
+    let mut parameters = vec![
+      ParamDescriptor::new("shape")
+        .initial(2.0)
+        .max(Self::NUM_SHAPES as f32),
+      ParamDescriptor::new("semitones")
+        .min(-12.0 * 4.0)
+        .max(12.0 * 4.0),
+      ParamDescriptor::new("cents").min(-100.0).max(100.0),
+      ParamDescriptor::new("pitch-bend").min(-1.0).max(1.0),
+      ParamDescriptor::new("amplitude").initial(1.0).max(1.0),
+      ParamDescriptor::new("attack")
+        .initial(0.005)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("decay")
+        .initial(0.2)
+        .max(10.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("sustain")
+        .initial(0.8)
+        .max(1.0)
+        .unit(ParamUnit::Percent),
+      ParamDescriptor::new("release")
+        .initial(0.3)
+        .max(20.0)
+        .unit(ParamUnit::Seconds),
+      ParamDescriptor::new("cutoff")
+        .initial(FreqControl::max_frequency())
+        .min(FreqControl::min_frequency())
+        .max(FreqControl::max_frequency())
+        .unit(ParamUnit::Hertz)
+        .scale(ParamScale::Logarithmic)
+        .precision(0),
+      ParamDescriptor::new("resonance").unit(ParamUnit::Percent),
+      ParamDescriptor::new("key-tracking").unit(ParamUnit::Percent),
+      ParamDescriptor::new("env-amount")
+        .min(-12.0 * 4.0)
+        .max(12.0 * 4.0)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("cutoff-mod")
+        .min(-12.0 * 4.0)
+        .max(12.0 * 4.0)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("unison")
+        .initial(1.0)
+        .min(1.0)
+        .max(Self::MAX_UNISON as f32)
+        .steps(Self::MAX_UNISON - 1),
+      ParamDescriptor::new("detune")
+        .max(100.0)
+        .unit(ParamUnit::Cents),
+      ParamDescriptor::new("stereo-spread").unit(ParamUnit::Percent),
+      ParamDescriptor::new("legato").values(vec!["off", "on"]),
+      ParamDescriptor::new("timbre-amount")
+        .initial(24.0)
+        .min(-12.0 * 4.0)
+        .max(12.0 * 4.0)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("pressure-amount")
+        .initial(0.5)
+        .unit(ParamUnit::Percent),
+      ParamDescriptor::new("osc2-shape")
+        .initial(2.0)
+        .max(Self::NUM_SHAPES as f32),
+      ParamDescriptor::new("osc2-semitones")
+        .min(-24.0)
+        .max(24.0)
+        .steps(48)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("osc2-cents")
+        .min(-100.0)
+        .max(100.0)
+        .unit(ParamUnit::Cents),
+      ParamDescriptor::new("osc2-level").unit(ParamUnit::Percent),
+      ParamDescriptor::new("osc2-sync").values(vec!["off", "on"]),
+      ParamDescriptor::new("wavetable-position").unit(ParamUnit::Percent),
+    ];
+    parameters.extend(VelocityResponse::parameters());
+    parameters.extend([
+      ParamDescriptor::new("pressure-vibrato")
+        .max(2.0)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("pressure-cutoff")
+        .min(-48.0)
+        .max(48.0)
+        .unit(ParamUnit::Semitones),
+      ParamDescriptor::new("pulse-width")
+        .initial(0.5)
+        .unit(ParamUnit::Percent),
+    ]);
+
     NodeDescriptor::new()
       .with_audio_ports(|ports| {
-        ports.static_outputs(vec![AudioDescriptor::new(Self::AUDIO_OUT_NAME, 1)])
+        ports.static_outputs(vec![AudioDescriptor::new(
+          Self::AUDIO_OUT_NAME,
+          Self::CHANNELS,
+        )])
       })
       .with_events_ports(|ports| {
         ports.static_inputs(vec![EventsDescriptor::new(Self::EVENTS_IN_NAME)])
       })
-      .with_parameters(vec![
-        ParamDescriptor::new("shape")
-          .initial(2.0)
-          .max(Self::NUM_SHAPES as f32),
-        ParamDescriptor::new("semitones")
-          .min(-12.0 * 4.0)
-          .max(12.0 * 4.0),
-        ParamDescriptor::new("cents").min(-100.0).max(100.0),
-        ParamDescriptor::new("pitch-bend").min(-1.0).max(1.0),
-        ParamDescriptor::new("amplitude").initial(1.0).max(1.0),
-      ])
+      .with_parameters(parameters)
+  }
+
+  fn prepare(&mut self, sample_rate: SampleRate, _max_block: usize) {
+    let sample_rate = sample_rate as f32;
+    for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+      osc.set_sample_rate(sample_rate);
+    }
+    self.shape = Self::smoother(Self::SHAPE_INDEX, sample_rate);
+    self.semitones = Self::smoother(Self::SEMITONES_INDEX, sample_rate);
+    self.cents = Self::smoother(Self::CENTS_INDEX, sample_rate);
+    self.pitch_bend = Self::smoother(Self::PITCH_BEND_INDEX, sample_rate);
+    self.amplitude = Self::smoother(Self::AMPLITUDE_INDEX, sample_rate);
+    self.envelope = EnvGen::new(sample_rate);
+    self.envelope_params = [f32::NAN; 4];
+    self.filters = [Self::filter(sample_rate), Self::filter(sample_rate)];
+    self.cutoff = Self::smoother(Self::CUTOFF_INDEX, sample_rate);
+  }
+
+  fn reset(&mut self) {
+    for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+      osc.reset();
+    }
+    self.envelope.reset();
+    for filter in self.filters.iter_mut() {
+      filter.reset();
+    }
+    self.velocity = 0.0;
+    self.bend.reset();
+    self.pressure = 0.0;
+    self.timbre = Self::CENTERED_TIMBRE;
+    self.channel_pressure = 0.0;
+    self.vibrato_phase = 0.0;
+  }
+
+  fn state(&self) -> Option<SharedState> {
+    Some(self.tuning.clone())
+  }
+
+  fn set_state(&mut self, state: &mut SharedState) -> Result<(), StateError> {
+    swap_state(&mut self.tuning, state)
   }
 
   fn render(&mut self, context: &mut ProcessorContext) {
@@ -132,67 +516,686 @@ impl Processor for VoiceProcessor {
     if waveform_index != self.waveform_index && waveform_index < self.waveforms.len() {
       self.waveform_index = waveform_index;
       let waveform = &self.waveforms[waveform_index];
-      self.osc.set_waveform(waveform.clone())
+      for osc in self.oscs.iter_mut() {
+        osc.set_waveform(waveform.clone());
+      }
+    }
+
+    let shape2 = context.parameter(Self::OSC2_SHAPE_INDEX).get();
+    let waveform2_index = shape2.round().max(0.0) as usize;
+    if waveform2_index != self.waveform2_index && waveform2_index < self.waveforms.len() {
+      self.waveform2_index = waveform2_index;
+      let waveform = &self.waveforms[waveform2_index];
+      for osc in self.oscs2.iter_mut() {
+        osc.set_waveform(waveform.clone());
+      }
+    }
+    let osc2_transpose = context.parameter(Self::OSC2_SEMITONES_INDEX).get()
+      + context.parameter(Self::OSC2_CENTS_INDEX).get() / 100.0;
+    if osc2_transpose != self.osc2_transpose {
+      self.osc2_transpose = osc2_transpose;
+      let freq = self.osc2_frequency();
+      for osc in self.oscs2.iter_mut() {
+        osc.set_pitch_frequency(freq);
+      }
+    }
+    let position = context.parameter(Self::WAVETABLE_POSITION_INDEX).get();
+    let pulse_width = context.parameter(Self::PULSE_WIDTH_INDEX).get();
+    for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+      match osc.waveform_mut() {
+        OscWaveform::Wavetable(waveform) => waveform.set_position(position),
+        OscWaveform::PulseBlep(waveform) => waveform.set_pulse_width(pulse_width),
+        _ => {}
+      }
+    }
+    let osc2_level = context.parameter(Self::OSC2_LEVEL_INDEX).get();
+    let osc2_sync = context.parameter(Self::OSC2_SYNC_INDEX).get() >= 0.5;
+
+    let unison = context.parameter(Self::UNISON_INDEX).get().round().max(1.0) as usize;
+    let unison = unison.min(Self::MAX_UNISON);
+    let detune = context.parameter(Self::DETUNE_INDEX).get();
+    let spread = context.parameter(Self::STEREO_SPREAD_INDEX).get();
+    if unison != self.unison || detune != self.detune || spread != self.spread {
+      self.update_unison(unison, detune, spread);
     }
 
-    self
-      .semitones
-      .set_target(context.parameter(Self::SEMITONES_INDEX).get());
     self
       .cents
       .set_target(context.parameter(Self::CENTS_INDEX).get());
+    update_envelope(
+      &mut self.envelope,
+      &mut self.envelope_params,
+      context,
+      Self::ATTACK_INDEX,
+    );
     self
-      .pitch_bend
-      .set_target(context.parameter(Self::PITCH_BEND_INDEX).get());
-    self
-      .amplitude
-      .set_target(context.parameter(Self::AMPLITUDE_INDEX).get());
-
-    let events = context.events_input(Self::EVENTS_IN_INDEX);
-    for event in events.iter() {
-      match event.data {
-        EventData::Midi(midi::messages::Message {
-          group: _,
-          mtype:
-            MessageType::ChannelVoice(ChannelVoice {
-              channel: _,
-              message,
-            }),
-        }) => match message {
-          ChannelVoiceMessage::NoteOn { note, velocity, .. } => {
-            self
-              .osc
-              .set_pitch_frequency(midi::note_freq::KEY_FREQ[note as usize]);
-            self.osc.set_amplitude(velocity as f32 / u16::MAX as f32);
-          }
-          ChannelVoiceMessage::NoteOff { .. } => {
-            self.osc.set_amplitude(0.0);
-          }
-          _ => {}
-        },
-        _ => {}
-      }
+      .cutoff
+      .set_target(context.parameter(Self::CUTOFF_INDEX).get());
+    let resonance = context.parameter(Self::RESONANCE_INDEX).get();
+    for filter in self.filters.iter_mut() {
+      filter.set_q(resonance);
     }
+    let key_tracking = context.parameter(Self::KEY_TRACKING_INDEX).get();
+    let legato = context.parameter(Self::LEGATO_INDEX).get() >= 0.5;
+    let velocity_response = VelocityResponse::from_context(context, Self::VELOCITY_INDEX);
+    self.envelope.set_legato(legato);
+    let env_amount = context.parameter(Self::ENV_AMOUNT_INDEX).get();
+    let timbre_amount = context.parameter(Self::TIMBRE_AMOUNT_INDEX).get();
+    let pressure_amount = context.parameter(Self::PRESSURE_AMOUNT_INDEX).get();
+    let pressure_vibrato = context.parameter(Self::PRESSURE_VIBRATO_INDEX).get();
+    let pressure_cutoff = context.parameter(Self::PRESSURE_CUTOFF_INDEX).get();
+    let vibrato_inc = Self::PRESSURE_VIBRATO_RATE / context.sample_rate() as f32;
+
+    // parameters that can be modulated at audio rate
+    let semitones = context.parameter_buffer(Self::SEMITONES_INDEX);
+    let amplitudes = context.parameter_buffer(Self::AMPLITUDE_INDEX);
+    let cutoff_mods = context.parameter_buffer(Self::CUTOFF_MOD_INDEX);
+    let (mut last_semitones, mut last_amplitude) = (f32::NAN, f32::NAN);
+
+    let bend = context.parameter(Self::PITCH_BEND_INDEX).get();
+    let mut last_bend = f32::NAN;
+    let mut events = context
+      .events_input_with_offsets(Self::EVENTS_IN_INDEX)
+      .peekable();
+
+    let output = context.audio_output(Self::AUDIO_OUT_INDEX);
+    let mut left = output.channel_mut(0);
+    let mut right = output.channel_mut(1);
+    let samples = left.iter_mut().zip(right.iter_mut()).enumerate();
+    for (index, (left, right)) in samples {
+      // the events change the voice at the sample where they happen, as the gate of the envelope
+      while let Some((_, event)) = events.next_if(|(offset, _)| *offset <= index) {
+        self.handle_event(event, legato, velocity_response);
+      }
+      follow(
+        &mut self.pitch_bend,
+        &mut last_bend,
+        self.bend.semitones(bend),
+      );
+      let timbre_mod = (self.timbre - Self::CENTERED_TIMBRE) * 2.0 * timbre_amount;
+      let pressure = self.pressure.max(self.channel_pressure);
+      let vibrato_depth = pressure * pressure_vibrato;
+
+      self.vibrato_phase = (self.vibrato_phase + vibrato_inc).fract();
+      let vibrato = if vibrato_depth != 0.0 {
+        (2.0 * PI * self.vibrato_phase).sin() * vibrato_depth
+      } else {
+        0.0
+      };
+      follow(
+        &mut self.semitones,
+        &mut last_semitones,
+        semitones[index] + vibrato,
+      );
+      follow(&mut self.amplitude, &mut last_amplitude, amplitudes[index]);
 
-    let mut output = context.audio_output(Self::AUDIO_OUT_INDEX).channel_mut(0);
-    for sample in output.as_mut_slice().iter_mut() {
       self.semitones.next_value_with(|semitones| {
-        self.osc.set_semitones(semitones);
+        for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+          osc.set_semitones(semitones);
+        }
       });
 
       self.cents.next_value_with(|cents| {
-        self.osc.set_cents(cents);
+        for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+          osc.set_cents(cents);
+        }
       });
 
       self.pitch_bend.next_value_with(|pitch_bend| {
-        self.osc.set_pitch_bend(pitch_bend);
+        for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+          osc.set_pitch_bend(pitch_bend);
+        }
       });
 
       self.amplitude.next_value_with(|amplitude| {
-        self.osc.set_amplitude(amplitude);
+        for osc in self.oscs.iter_mut().chain(self.oscs2.iter_mut()) {
+          osc.set_amplitude(amplitude);
+        }
       });
 
-      *sample = self.osc.generate();
+      self.cutoff.next_value_with(|cutoff| {
+        for filter in self.filters.iter_mut() {
+          filter.set_frequency(cutoff);
+        }
+      });
+
+      let envelope = self.envelope.generate();
+      if self.envelope.is_off() {
+        // silent voices don't keep the filters ringing into denormals
+        for filter in self.filters.iter_mut() {
+          filter.reset();
+        }
+        *left = 0.0;
+        *right = 0.0;
+        continue;
+      }
+      let modulation = self.key_offset * key_tracking
+        + envelope * env_amount
+        + cutoff_mods[index]
+        + timbre_mod
+        + pressure * pressure_cutoff;
+
+      let mut mix = [0.0; 2];
+      let oscs = self
+        .oscs
+        .iter_mut()
+        .zip(self.oscs2.iter_mut())
+        .zip(self.gains.iter())
+        .take(self.unison);
+      for ((osc, osc2), [left_gain, right_gain]) in oscs {
+        let mut signal = osc.generate();
+        if osc2_level > 0.0 {
+          if osc2_sync && osc.has_wrapped() {
+            osc2.restart_cycle();
+          }
+          signal += osc2.generate() * osc2_level;
+        }
+        mix[0] += signal * left_gain;
+        mix[1] += signal * right_gain;
+      }
+
+      let gain = self.velocity * envelope * (1.0 + pressure * pressure_amount);
+      for ((sample, filter), value) in [left, right]
+        .into_iter()
+        .zip(self.filters.iter_mut())
+        .zip(mix)
+      {
+        filter.set_frequency_modulation(modulation);
+        *sample = filter.process(value * gain);
+      }
+    }
+  }
+}
+
+/// Update an envelope from its attack, decay, sustain and release parameters,
+/// found one after the other from the attack, only for the ones that changed since the last block.
+pub(super) fn update_envelope(
+  envelope: &mut EnvGen<f32>,
+  last_params: &mut [f32; 4],
+  context: &ProcessorContext,
+  attack_index: usize,
+) {
+  let params = [
+    context.parameter(attack_index).get(),
+    context.parameter(attack_index + 1).get(),
+    context.parameter(attack_index + 2).get(),
+    context.parameter(attack_index + 3).get(),
+  ];
+  let [attack, decay, sustain, release] = params;
+  let [prev_attack, prev_decay, prev_sustain, prev_release] = *last_params;
+  if attack != prev_attack {
+    envelope.set_attack_time_sec(attack);
+  }
+  if decay != prev_decay {
+    envelope.set_decay_time_sec(decay);
+  }
+  if sustain != prev_sustain {
+    envelope.set_sustain_level(sustain);
+  }
+  if release != prev_release {
+    envelope.set_release_time_sec(release);
+  }
+  *last_params = params;
+}
+
+/// Convert an unsigned bipolar value centered at `0x80000000` into the `-1..1` range.
+pub(super) fn bipolar(data: u32) -> f32 {
+  const CENTER: u32 = 0x8000_0000;
+  if data >= CENTER {
+    (data - CENTER) as f32 / (u32::MAX - CENTER) as f32
+  } else {
+    -((CENTER - data) as f32 / CENTER as f32)
+  }
+}
+
+/// Smooth towards the value of a parameter for a sample, restarting the ramp only when it changes.
+fn follow(smoother: &mut LinearStepsSmoother<f32>, last: &mut f32, value: f32) {
+  if value != *last {
+    *last = value;
+    smoother.set_target(value);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::testing::ProcessorHarness;
+  use kiro_engine::{Engine, EngineConfig, Event};
+  use kiro_midi::messages::Message;
+
+  use super::*;
+
+  fn note(timestamp: u64, on: bool) -> Event {
+    let message = if on {
+      ChannelVoiceMessage::NoteOn {
+        note: 69,
+        velocity: u16::MAX,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    } else {
+      ChannelVoiceMessage::NoteOff {
+        note: 69,
+        velocity: 0,
+        attr_type: 0,
+        attr_data: 0,
+      }
+    };
+    Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(0, 0, message)),
+    }
+  }
+
+  fn peak(samples: &[f32]) -> f32 {
+    samples
+      .iter()
+      .fold(0.0, |peak, sample| sample.abs().max(peak))
+  }
+
+  #[test]
+  fn shape_the_amplitude_with_an_envelope() {
+    let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+    let (note_on, note_off) = (harness.block_timestamp(1), harness.block_timestamp(5));
+    harness
+      .set_parameter(0, VoiceProcessor::SHAPE_INDEX, 0.0)
+      .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.01)
+      .set_parameter(0, VoiceProcessor::DECAY_INDEX, 0.01)
+      .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 0.5)
+      .set_parameter(0, VoiceProcessor::RELEASE_INDEX, 0.01)
+      .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(note_on, true))
+      .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(note_off, false));
+
+    let output = harness.render(1);
+    assert_eq!(peak(output.channel(0, 0)), 0.0);
+
+    // attack without clicks, up to the peak and then down to the sustain level
+    let output = harness.render(4);
+    let samples = output.channel(0, 0);
+    assert!(peak(&samples[0..10]) < 0.05);
+    assert!(peak(&samples[0..960]) > 0.9);
+    assert!(peak(&samples[1440..]) <= 0.5);
+    assert!(peak(&samples[1440..]) > 0.45);
+
+    // release
+    let output = harness.render(4);
+    let samples = output.channel(0, 0);
+    assert!(peak(&samples[0..10]) <= 0.5);
+    assert_eq!(peak(&samples[960..]), 0.0);
+  }
+
+  #[test]
+  fn start_the_notes_at_the_sample_of_their_events() {
+    let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+    let (note_on, note_off) = (harness.sample_timestamp(200), harness.sample_timestamp(680));
+    harness
+      .set_parameter(0, VoiceProcessor::SHAPE_INDEX, 0.0)
+      .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+      .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+      .set_parameter(0, VoiceProcessor::RELEASE_INDEX, 0.0)
+      .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(note_on, true))
+      .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(note_off, false));
+
+    let output = harness.render(2);
+    let samples = output.channel(0, 0);
+    assert_eq!(peak(&samples[..200]), 0.0);
+    assert!(peak(&samples[200..680]) > 0.5);
+    assert!(peak(&samples[700..]) < 1e-3);
+  }
+
+  #[test]
+  fn slide_into_overlapping_notes_with_legato() {
+    let render = |legato: f32| {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      let overlapping = harness.block_timestamp(5);
+      harness
+        .set_parameter(0, VoiceProcessor::SHAPE_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.01)
+        .set_parameter(0, VoiceProcessor::DECAY_INDEX, 0.01)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 0.5)
+        .set_parameter(0, VoiceProcessor::LEGATO_INDEX, legato)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true))
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(overlapping, true));
+      harness.render(5);
+      peak(harness.render(2).channel(0, 0))
+    };
+
+    assert!(render(0.0) > 0.9);
+    assert!(render(1.0) <= 0.5);
+  }
+
+  fn rms(samples: &[f32]) -> f32 {
+    let sum = samples.iter().map(|sample| sample * sample).sum::<f32>();
+    (sum / samples.len() as f32).sqrt()
+  }
+
+  /// Render the sustain of a saw note with some parameters.
+  fn render_sustain(parameters: &[(usize, f32)]) -> f32 {
+    let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+    harness
+      .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+      .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+      .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true));
+    for (index, value) in parameters.iter().cloned() {
+      harness.set_parameter(0, index, value);
+    }
+    harness.render(1);
+    rms(harness.render(4).channel(0, 0))
+  }
+
+  #[test]
+  fn filter_the_oscillator() {
+    let open = render_sustain(&[]);
+    let closed = render_sustain(&[(VoiceProcessor::CUTOFF_INDEX, 100.0)]);
+    assert!(closed < open * 0.5);
+
+    let tracked = render_sustain(&[
+      (VoiceProcessor::CUTOFF_INDEX, 100.0),
+      (VoiceProcessor::KEY_TRACKING_INDEX, 1.0),
+    ]);
+    assert!(tracked > closed);
+
+    let enveloped = render_sustain(&[
+      (VoiceProcessor::CUTOFF_INDEX, 100.0),
+      (VoiceProcessor::ENV_AMOUNT_INDEX, 36.0),
+    ]);
+    assert!(enveloped > closed * 1.5);
+
+    let resonant = render_sustain(&[
+      (VoiceProcessor::CUTOFF_INDEX, 440.0),
+      (VoiceProcessor::RESONANCE_INDEX, 1.0),
+    ]);
+    let flat = render_sustain(&[(VoiceProcessor::CUTOFF_INDEX, 440.0)]);
+    assert!(resonant > flat);
+
+    let modulated = render_sustain(&[
+      (VoiceProcessor::CUTOFF_INDEX, 100.0),
+      (VoiceProcessor::CUTOFF_MOD_INDEX, 36.0),
+    ]);
+    assert!(modulated > closed * 1.5);
+  }
+
+  #[test]
+  fn spread_the_unison() {
+    let render = |parameters: &[(usize, f32)]| {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      harness
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true));
+      for (index, value) in parameters.iter().cloned() {
+        harness.set_parameter(0, index, value);
+      }
+      let output = harness.render(4);
+      (output.channel(0, 0).to_vec(), output.channel(0, 1).to_vec())
+    };
+
+    let (left, right) = render(&[(VoiceProcessor::STEREO_SPREAD_INDEX, 1.0)]);
+    assert_eq!(left, right);
+
+    let (left, right) = render(&[
+      (VoiceProcessor::UNISON_INDEX, 1.0),
+      (VoiceProcessor::DETUNE_INDEX, 50.0),
+    ]);
+    let (unison_left, unison_right) = render(&[
+      (VoiceProcessor::UNISON_INDEX, 5.0),
+      (VoiceProcessor::DETUNE_INDEX, 50.0),
+    ]);
+    assert_eq!(unison_left, unison_right);
+    assert_ne!(unison_left, left);
+    // the loudness is kept when adding oscillators
+    assert!((rms(&unison_left) / rms(&left) - 1.0).abs() < 0.5);
+    assert_eq!(left, right);
+
+    let (left, right) = render(&[
+      (VoiceProcessor::UNISON_INDEX, 5.0),
+      (VoiceProcessor::DETUNE_INDEX, 50.0),
+      (VoiceProcessor::STEREO_SPREAD_INDEX, 1.0),
+    ]);
+    assert_ne!(left, right);
+  }
+
+  #[test]
+  fn mix_the_second_oscillator() {
+    let render = |parameters: &[(usize, f32)]| {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      harness
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .set_parameter(0, VoiceProcessor::OSC2_SEMITONES_INDEX, 7.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true));
+      for (index, value) in parameters.iter().cloned() {
+        harness.set_parameter(0, index, value);
+      }
+      let output = harness.render(4);
+      let second = harness.processor().oscs2[0].get_pitch_frequency();
+      (output.channel(0, 0).to_vec(), second)
+    };
+
+    let (single, _) = render(&[]);
+    let (mixed, second) = render(&[(VoiceProcessor::OSC2_LEVEL_INDEX, 1.0)]);
+    // a fifth above the note
+    assert!((second - 440.0 * 1.5).abs() < 1.0);
+    assert_ne!(mixed, single);
+    assert!(rms(&mixed) > rms(&single));
+
+    let (synced, _) = render(&[
+      (VoiceProcessor::OSC2_LEVEL_INDEX, 1.0),
+      (VoiceProcessor::OSC2_SYNC_INDEX, 1.0),
+    ]);
+    assert_ne!(synced, mixed);
+  }
+
+  #[test]
+  fn morph_the_wavetable() {
+    let render = |position: f32| {
+      // a silent frame followed by a square
+      let square = (0..64).map(|index| if index < 32 { 1.0 } else { -1.0 });
+      let wavetable = Wavetable::from_frames(&[vec![0.0; 64], square.collect()]).unwrap();
+      let voice = VoiceProcessor::new(48_000.0).with_wavetable(Arc::new(wavetable));
+      let mut harness = ProcessorHarness::new(voice).with_block_size(480);
+      harness
+        .set_parameter(
+          0,
+          VoiceProcessor::SHAPE_INDEX,
+          VoiceProcessor::WAVETABLE_SHAPE as f32,
+        )
+        .set_parameter(0, VoiceProcessor::WAVETABLE_POSITION_INDEX, position)
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true));
+      harness.render(1);
+      rms(harness.render(4).channel(0, 0))
+    };
+
+    assert_eq!(render(0.0), 0.0);
+    let (half, full) = (render(0.5), render(1.0));
+    assert!(half > 0.1);
+    assert!(full > half * 1.5);
+  }
+
+  #[test]
+  fn modulate_the_pulse_width() {
+    let render = |pulse_width: f32| {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      harness
+        .set_parameter(
+          0,
+          VoiceProcessor::SHAPE_INDEX,
+          VoiceProcessor::PULSE_SHAPE as f32,
+        )
+        .set_parameter(0, VoiceProcessor::PULSE_WIDTH_INDEX, pulse_width)
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true));
+      harness.render(1);
+      let output = harness.render(4);
+      let left = output.channel(0, 0);
+      left.iter().sum::<f32>() / left.len() as f32
+    };
+
+    // a square has no offset, but narrower pulses are low for longer
+    let (square, narrow) = (render(0.5), render(0.1));
+    assert!(square.abs() < 0.05, "{}", square);
+    assert!(narrow < -0.2, "{}", narrow);
+  }
+
+  fn assert_bend(harness: &ProcessorHarness<VoiceProcessor>, semitones: f32) {
+    let bend = harness.processor().oscs[0].get_pitch_bend();
+    assert!((bend - semitones).abs() < 1e-4, "{} != {}", bend, semitones);
+  }
+
+  #[test]
+  fn bend_the_pitch() {
+    let channel_voice = |timestamp, message| Event {
+      timestamp,
+      data: EventData::Midi(Message::channel_voice(0, 0, message)),
+    };
+    let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0));
+    let (second, third) = (harness.block_timestamp(2), harness.block_timestamp(4));
+    harness.send_event(
+      VoiceProcessor::EVENTS_IN_INDEX,
+      channel_voice(0, ChannelVoiceMessage::PitchBend { data: u32::MAX }),
+    );
+    harness.render(2);
+    assert_bend(&harness, 2.0);
+
+    // 12 semitones and 50 cents
+    let sensitivity = (12 << 25) | (50 << 18);
+    harness
+      .send_event(
+        VoiceProcessor::EVENTS_IN_INDEX,
+        channel_voice(
+          second,
+          ChannelVoiceMessage::RegisteredController {
+            bank: 0,
+            index: 0,
+            data: sensitivity,
+          },
+        ),
+      )
+      .send_event(
+        VoiceProcessor::EVENTS_IN_INDEX,
+        channel_voice(second, ChannelVoiceMessage::PitchBend { data: 0 }),
+      );
+    harness.render(2);
+    assert_bend(&harness, -12.5);
+
+    harness
+      .send_event(
+        VoiceProcessor::EVENTS_IN_INDEX,
+        channel_voice(third, ChannelVoiceMessage::PitchBend { data: 0x8000_0000 }),
+      )
+      .set_parameter(4, VoiceProcessor::PITCH_BEND_INDEX, 0.5);
+    harness.render(2);
+    assert_bend(&harness, 6.25);
+  }
+
+  #[test]
+  fn express_per_note_messages() {
+    let per_note = |message| Event {
+      timestamp: 0,
+      data: EventData::Midi(Message::channel_voice(0, 0, message)),
+    };
+    let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0));
+    harness.send_event(
+      VoiceProcessor::EVENTS_IN_INDEX,
+      per_note(ChannelVoiceMessage::PerNotePitchBend {
+        note: 69,
+        data: 0x4000_0000,
+      }),
+    );
+    harness.render(2);
+    assert_bend(&harness, -24.0);
+
+    let pressed = {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      harness
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .set_parameter(0, VoiceProcessor::CUTOFF_INDEX, 200.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true))
+        .send_event(
+          VoiceProcessor::EVENTS_IN_INDEX,
+          per_note(ChannelVoiceMessage::PolyPressure {
+            note: 69,
+            pressure: u32::MAX,
+          }),
+        )
+        .send_event(
+          VoiceProcessor::EVENTS_IN_INDEX,
+          per_note(ChannelVoiceMessage::RegisteredPerNoteController {
+            note: 69,
+            index: VoiceAllocatorProcessor::TIMBRE_CONTROLLER,
+            data: u32::MAX,
+          }),
+        );
+      harness.render(1);
+      rms(harness.render(4).channel(0, 0))
+    };
+    let closed = render_sustain(&[(VoiceProcessor::CUTOFF_INDEX, 200.0)]);
+    assert!(pressed > closed * 1.5);
+  }
+
+  #[test]
+  fn route_the_channel_pressure() {
+    let pressure = Event {
+      timestamp: 0,
+      data: EventData::Midi(Message::channel_voice(
+        0,
+        0,
+        ChannelVoiceMessage::ChannelPressure { pressure: u32::MAX },
+      )),
+    };
+    let render = |parameters: &[(usize, f32)]| {
+      let mut harness = ProcessorHarness::new(VoiceProcessor::new(48_000.0)).with_block_size(480);
+      harness
+        .set_parameter(0, VoiceProcessor::ATTACK_INDEX, 0.0)
+        .set_parameter(0, VoiceProcessor::SUSTAIN_INDEX, 1.0)
+        .set_parameter(0, VoiceProcessor::CUTOFF_INDEX, 200.0)
+        .set_parameter(0, VoiceProcessor::PRESSURE_AMOUNT_INDEX, 0.0)
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, note(0, true))
+        .send_event(VoiceProcessor::EVENTS_IN_INDEX, pressure);
+      for (index, value) in parameters.iter().cloned() {
+        harness.set_parameter(0, index, value);
+      }
+      harness.render(1);
+      harness.render(8).channel(0, 0).to_vec()
+    };
+
+    let unrouted = render(&[]);
+    let closed = render_sustain(&[
+      (VoiceProcessor::CUTOFF_INDEX, 200.0),
+      (VoiceProcessor::PRESSURE_AMOUNT_INDEX, 0.0),
+    ]);
+    assert!((rms(&unrouted) - closed).abs() < 1e-3);
+
+    let opened = render(&[(VoiceProcessor::PRESSURE_CUTOFF_INDEX, 36.0)]);
+    assert!(rms(&opened) > closed * 1.5);
+
+    let louder = render(&[(VoiceProcessor::PRESSURE_AMOUNT_INDEX, 1.0)]);
+    assert!(rms(&louder) > closed * 1.5);
+
+    let vibrato = render(&[(VoiceProcessor::PRESSURE_VIBRATO_INDEX, 1.0)]);
+    assert_ne!(vibrato, unrouted);
+  }
+
+  #[test]
+  fn build_voices_with_lfos() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let wavetable = Arc::new(Wavetable::basic_shapes(64).unwrap());
+    engine
+      .create_poly_module("voices", 2, VoiceProcessor::CHANNELS, |module, _index| {
+        VoiceNode::build(module, 48_000, Tuning::default(), wavetable.clone())
+      })
+      .unwrap();
+    for name in [
+      VoiceNode::NAME,
+      VoiceNode::LFO_PITCH_NAME,
+      VoiceNode::LFO_AMPLITUDE_NAME,
+      VoiceNode::LFO_CUTOFF_NAME,
+    ] {
+      let path = format!("/root/voices/voice-1/{}", name);
+      assert!(engine.find_node(&path).is_some(), "{}", path);
     }
   }
 }
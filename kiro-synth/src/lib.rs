@@ -2,6 +2,10 @@
 // pub mod _audio_handler;
 // pub mod _dca;
 // pub mod _filter;
+pub mod batch;
 pub mod config;
 pub mod engine;
 pub mod graph;
+pub mod midi_map;
+pub mod render;
+pub mod voice_scaling;
@@ -1,19 +1,217 @@
-use kiro_synth::config::Config;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use kiro_audio::{AudioConfig, AudioDriver};
+use kiro_midi::{self as midi, DriverSpec};
+use kiro_synth::batch;
+use kiro_synth::config::{Config, ConfigError, MidiConfig, VoiceScalingConfig};
 use kiro_synth::engine::SynthEngine;
 use kiro_synth::graph::SynthGraph;
+use kiro_synth::midi_map::MidiMap;
+use kiro_synth::render::{self, NoteRender};
+use kiro_synth::voice_scaling::VoiceScaler;
+
+/// Play the synth from the connected MIDI sources
+#[derive(Debug, Parser)]
+#[clap(name = "kiro-synth", version)]
+struct Args {
+  /// Config file to read instead of ~/.config/kiro/synth.toml, the options given here take precedence
+  #[clap(short, long, value_name = "FILE")]
+  config: Option<PathBuf>,
+
+  /// Number of voices [default: 8]
+  #[clap(short, long)]
+  voices: Option<usize>,
+
+  /// Name of the audio output device (the default one by default)
+  #[clap(short, long, value_name = "NAME")]
+  device: Option<String>,
+
+  /// Sample rate of the audio output [default: 44100]
+  #[clap(short = 'r', long)]
+  sample_rate: Option<u32>,
+
+  /// Buffer size of the audio output, in samples [default: 256]
+  #[clap(short, long)]
+  buffer_size: Option<usize>,
+
+  /// Regex for the names of the MIDI sources to play from (all of them by default)
+  #[clap(short, long = "source", value_name = "REGEX")]
+  sources: Vec<String>,
+
+  /// Preset to load
+  #[clap(short, long, value_name = "FILE")]
+  preset: Option<PathBuf>,
+
+  /// List the audio output devices and the MIDI sources, and exit
+  #[clap(short, long)]
+  list_devices: bool,
+
+  #[clap(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+  /// Render a note offline into a wav file, with the preset if any
+  Render {
+    /// Path of the wav file
+    file: PathBuf,
+
+    /// Note number, 60 being the middle C
+    #[clap(short, long, default_value_t = 60)]
+    note: u8,
+
+    /// Velocity of the note, from 0 to 127
+    #[clap(long, default_value_t = 100)]
+    velocity: u8,
+
+    /// Seconds the note is held
+    #[clap(long, default_value_t = 1.0)]
+    duration: f64,
+
+    /// Seconds rendered after the note is released
+    #[clap(long, default_value_t = 1.0)]
+    tail: f64,
+  },
+
+  /// Process the wav and flac files of a directory offline through an effect chain
+  Process {
+    /// Graph document with the effect chain, whose nodes are named after the built-in processors
+    chain: PathBuf,
+
+    /// Directory with the audio files to process
+    input: PathBuf,
+
+    /// Directory where the processed files are written as wav files with the same names
+    output: PathBuf,
+
+    /// Seconds rendered after the end of every file, for the effects to decay
+    #[clap(long, default_value_t = 0.0)]
+    tail: f64,
+  },
+}
+
+impl Args {
+  /// Config from the file, with the options given in the command line on top of it.
+  fn config(&self) -> Result<Config, ConfigError> {
+    let config = match self.config.as_ref() {
+      Some(path) => Config::load(path)?,
+      None => Config::load_default()?,
+    };
+    let num_voices = self.voices.unwrap_or(config.num_voices);
+    let sources = if self.sources.is_empty() {
+      config.midi.sources
+    } else {
+      self.sources.clone()
+    };
+    Ok(Config {
+      midi: MidiConfig {
+        sources,
+        ..config.midi
+      },
+      audio: AudioConfig {
+        device: self.device.clone().or(config.audio.device),
+        sample_rate: self.sample_rate.unwrap_or(config.audio.sample_rate),
+        buffer_size: self.buffer_size.unwrap_or(config.audio.buffer_size),
+      },
+      num_voices,
+      voice_scaling: VoiceScalingConfig {
+        min_voices: config.voice_scaling.min_voices.min(num_voices),
+        max_voices: num_voices,
+        ..config.voice_scaling
+      },
+      preset: self.preset.clone().or(config.preset),
+      ..config
+    })
+  }
+}
 
 fn main() -> anyhow::Result<()> {
-  let mut synth_engine = SynthEngine::new(Config::default())?;
+  let args = Args::parse();
+
+  if args.list_devices {
+    print_devices()?;
+    return Ok(());
+  }
+
+  if let Some(Command::Render {
+    file,
+    note,
+    velocity,
+    duration,
+    tail,
+  }) = &args.command
+  {
+    let config = args.config()?;
+    let note = NoteRender {
+      note: *note,
+      velocity: *velocity,
+      duration: *duration,
+      tail: *tail,
+    };
+    let channels = render::render_note(&config, &note)?;
+    render::write_wav(file, config.audio.sample_rate, &channels)?;
+    return Ok(());
+  }
+
+  if let Some(Command::Process {
+    chain,
+    input,
+    output,
+    tail,
+  }) = &args.command
+  {
+    let chain = batch::load_chain(chain)?;
+    for path in batch::process_directory(&chain, input, output, *tail)? {
+      println!("{}", path.display());
+    }
+    return Ok(());
+  }
+
+  let mut synth_engine = SynthEngine::new(args.config()?)?;
   let sample_rate = synth_engine.sample_rate();
+  let num_voices = synth_engine.config().num_voices;
+  let tuning = synth_engine.config().tuning.clone();
 
-  let _synth_graph = SynthGraph::try_new(synth_engine.engine_mut(), sample_rate, 1)?;
+  let mut synth_graph =
+    SynthGraph::try_new(synth_engine.engine_mut(), sample_rate, num_voices, &tuning)?;
+  synth_graph.set_mpe(synth_engine.config().mpe)?;
+  if let Some(preset) = synth_engine.config().preset.clone() {
+    synth_graph.load_preset(synth_engine.engine_mut(), preset)?;
+  }
+  let mut voice_scaler = VoiceScaler::new(synth_engine.config().voice_scaling.clone());
+  let render_load = synth_engine.render_load();
+  let mut midi_map = MidiMap::new();
 
   synth_engine.engine_mut().update_render_plan()?;
   synth_engine.start()?;
 
+  // the controllers are followed often, but the voices are only scaled once a second
+  let mut ticks = 0usize;
   loop {
-    std::thread::sleep(std::time::Duration::from_secs(1));
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    let events = synth_engine.controller_events().collect::<Vec<_>>();
+    for event in events {
+      midi_map.handle(synth_engine.engine_mut(), &synth_graph, &event.message)?;
+    }
+    ticks = (ticks + 1) % 100;
+    if ticks == 0 {
+      synth_graph.set_max_active_voices(voice_scaler.update(render_load.get()))?;
+    }
   }
+}
 
+fn print_devices() -> anyhow::Result<()> {
+  println!("Audio output devices:");
+  for device in AudioDriver::output_devices()? {
+    println!("  {}", device);
+  }
+  let driver = midi::drivers::create("kiro-synth")?;
+  println!("MIDI sources:");
+  for source in driver.sources() {
+    println!("  [{:08x}] {}", source.id, source.name);
+  }
   Ok(())
 }
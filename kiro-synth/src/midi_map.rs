@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+
+use kiro_engine::processor::{decode_state, encode_state};
+use kiro_engine::{Engine, MacroParameter, MacroRange};
+use kiro_midi::messages::channel_voice::{ChannelVoice, ChannelVoiceMessage};
+use kiro_midi::messages::{Message, MessageType};
+
+use crate::graph::{Error, ParamTarget, Result, SynthGraph};
+
+/// Controller of a MIDI channel that can be mapped into a parameter of the synth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MidiController {
+  ControlChange {
+    group: u8,
+    channel: u8,
+    index: u8,
+  },
+  Assignable {
+    group: u8,
+    channel: u8,
+    bank: u8,
+    index: u8,
+  },
+}
+
+impl MidiController {
+  /// Controller changed by a message, and its new value in the `0..1` range.
+  pub fn from_message(message: &Message) -> Option<(Self, f32)> {
+    let group = message.group;
+    let (channel, message) = match message.mtype {
+      MessageType::ChannelVoice(ChannelVoice { channel, message }) => (channel, message),
+      _ => return None,
+    };
+    let normalize = |data: u32| (f64::from(data) / f64::from(u32::MAX)) as f32;
+    match message {
+      ChannelVoiceMessage::ControlChange { index, data } => {
+        let controller = MidiController::ControlChange {
+          group,
+          channel,
+          index,
+        };
+        Some((controller, normalize(data)))
+      }
+      ChannelVoiceMessage::AssignableController { bank, index, data } => {
+        let controller = MidiController::Assignable {
+          group,
+          channel,
+          bank,
+          index,
+        };
+        Some((controller, normalize(data)))
+      }
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiMapping {
+  pub controller: MidiController,
+  pub target: ParamTarget,
+}
+
+/// Mappings from the controllers of the MIDI channels into the parameters of the synth,
+/// with the parameters following the whole range of their controllers.
+///
+/// A new mapping can be learnt by choosing a parameter and moving the controller wanted for it.
+#[derive(Default)]
+pub struct MidiMap {
+  mappings: Vec<(MidiMapping, MacroParameter)>,
+  learning: Option<ParamTarget>,
+}
+
+impl MidiMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn mappings(&self) -> impl Iterator<Item = &MidiMapping> {
+    self.mappings.iter().map(|(mapping, _)| mapping)
+  }
+
+  /// Map the next controller moved into a parameter, replacing its previous mapping if any.
+  pub fn learn(&mut self, target: ParamTarget) {
+    self.learning = Some(target);
+  }
+
+  /// Parameter waiting for the next controller moved, if learning.
+  pub fn learning(&self) -> Option<&ParamTarget> {
+    self.learning.as_ref()
+  }
+
+  pub fn cancel_learn(&mut self) {
+    self.learning = None;
+  }
+
+  /// Map a controller into a parameter, replacing the previous mapping of the parameter if any.
+  ///
+  /// A controller can still be mapped into several parameters.
+  pub fn map(
+    &mut self,
+    engine: &mut Engine,
+    graph: &SynthGraph,
+    mapping: MidiMapping,
+  ) -> Result<()> {
+    let nodes = graph.parameter_nodes(engine, &mapping.target.node);
+    if nodes.is_empty() {
+      return Err(Error::ParameterNotFound(mapping.target.node.clone()));
+    }
+    let id = format!("{}:{}", mapping.target.node, mapping.target.index);
+    let mut control = engine.create_macro(&id, 0.0);
+    for node in nodes.iter() {
+      let descriptor = node
+        .descriptor()?
+        .parameters
+        .get(mapping.target.index)
+        .cloned()
+        .ok_or_else(|| Error::ParameterNotFound(id.clone()))?;
+      let range = MacroRange::new(descriptor.min, descriptor.max).scale(descriptor.scale);
+      control.add_target(node, mapping.target.index, range)?;
+    }
+    self.unmap(&mapping.target);
+    self.mappings.push((mapping, control));
+    Ok(())
+  }
+
+  /// Remove the mapping of a parameter, returning whether it was mapped.
+  pub fn unmap(&mut self, target: &ParamTarget) -> bool {
+    let len = self.mappings.len();
+    self
+      .mappings
+      .retain(|(mapping, _)| &mapping.target != target);
+    len != self.mappings.len()
+  }
+
+  /// Follow a MIDI message, changing the parameters mapped from its controller,
+  /// or mapping it into the parameter being learnt.
+  ///
+  /// Returns whether the message was used by any mapping.
+  pub fn handle(
+    &mut self,
+    engine: &mut Engine,
+    graph: &SynthGraph,
+    message: &Message,
+  ) -> Result<bool> {
+    let (controller, value) = match MidiController::from_message(message) {
+      Some(changed) => changed,
+      None => return Ok(false),
+    };
+
+    if let Some(target) = self.learning.take() {
+      self.map(engine, graph, MidiMapping { controller, target })?;
+    }
+
+    let mut handled = false;
+    for (mapping, control) in self.mappings.iter_mut() {
+      if mapping.controller == controller {
+        control.set_value(value)?;
+        handled = true;
+      }
+    }
+    Ok(handled)
+  }
+
+  /// Keep the mappings, such as along with the rest of a preset.
+  pub fn save(&self) -> Result<Vec<u8>> {
+    let mappings = self.mappings().collect::<Vec<_>>();
+    Ok(encode_state(&mappings)?)
+  }
+
+  /// Replace the mappings by the ones kept with [`MidiMap::save`].
+  pub fn load(&mut self, engine: &mut Engine, graph: &SynthGraph, state: &[u8]) -> Result<()> {
+    let mappings: Vec<MidiMapping> = decode_state(state)?;
+    self.mappings.clear();
+    self.learning = None;
+    for mapping in mappings {
+      self.map(engine, graph, mapping)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kiro_engine::EngineConfig;
+  use kiro_midi::note_freq::Tuning;
+
+  use crate::graph::{MasterProcessor, VoiceProcessor};
+
+  use super::*;
+
+  fn control_change(index: u8, data: u32) -> Message {
+    Message::channel_voice(0, 0, ChannelVoiceMessage::ControlChange { index, data })
+  }
+
+  #[test]
+  fn normalize_the_controller_values() {
+    let (controller, value) = MidiController::from_message(&control_change(7, u32::MAX)).unwrap();
+    assert_eq!(
+      controller,
+      MidiController::ControlChange {
+        group: 0,
+        channel: 0,
+        index: 7
+      }
+    );
+    assert_eq!(value, 1.0);
+
+    let assignable = ChannelVoiceMessage::AssignableController {
+      bank: 1,
+      index: 2,
+      data: 0,
+    };
+    let (_, value) =
+      MidiController::from_message(&Message::channel_voice(0, 3, assignable)).unwrap();
+    assert_eq!(value, 0.0);
+
+    let pitch_bend = ChannelVoiceMessage::PitchBend { data: 0 };
+    assert!(MidiController::from_message(&Message::channel_voice(0, 0, pitch_bend)).is_none());
+  }
+
+  #[test]
+  fn learn_the_next_controller_moved() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    let mut map = MidiMap::new();
+
+    let target = ParamTarget::new(SynthGraph::MASTER_NAME, MasterProcessor::GAIN_INDEX);
+    map.learn(target.clone());
+    assert_eq!(map.learning(), Some(&target));
+    assert!(map
+      .handle(&mut engine, &graph, &control_change(7, u32::MAX))
+      .unwrap());
+    assert!(map.learning().is_none());
+
+    let mapping = map.mappings().next().unwrap();
+    assert_eq!(mapping.target, target);
+    assert!(!map
+      .handle(&mut engine, &graph, &control_change(8, 0))
+      .unwrap());
+    assert!(map
+      .handle(&mut engine, &graph, &control_change(7, 0))
+      .unwrap());
+
+    let state = map.save().unwrap();
+    let mut loaded = MidiMap::new();
+    loaded.load(&mut engine, &graph, &state).unwrap();
+    assert_eq!(
+      loaded.mappings().collect::<Vec<_>>(),
+      map.mappings().collect::<Vec<_>>()
+    );
+
+    assert!(map.unmap(&target));
+    assert_eq!(map.mappings().count(), 0);
+  }
+
+  #[test]
+  fn drive_the_parameter_of_all_the_voices() {
+    let mut engine = Engine::new(EngineConfig::default());
+    let graph = SynthGraph::try_new(&mut engine, 44_100, 2, &Tuning::default()).unwrap();
+    let mut map = MidiMap::new();
+
+    let target = ParamTarget::new("voice", VoiceProcessor::RELEASE_INDEX);
+    let controller = MidiController::ControlChange {
+      group: 0,
+      channel: 0,
+      index: 72,
+    };
+    map
+      .map(&mut engine, &graph, MidiMapping { controller, target })
+      .unwrap();
+    let (_, control) = &map.mappings[0];
+    assert_eq!(control.num_targets(), 2);
+
+    let target = ParamTarget::new("missing", 0);
+    let result = map.map(&mut engine, &graph, MidiMapping { controller, target });
+    assert!(matches!(result, Err(Error::ParameterNotFound(_))));
+  }
+}
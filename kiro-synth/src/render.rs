@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use kiro_engine::{Engine, EngineConfig, Event, EventData};
+use kiro_midi::messages::channel_voice::ChannelVoiceMessage;
+use kiro_midi::messages::Message;
+use kiro_time::SampleRate;
+
+use crate::config::Config;
+use crate::graph::{self, SynthGraph};
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error("Synth: {0}")]
+  Synth(#[from] graph::Error),
+
+  #[error("Engine: {0}")]
+  Engine(#[from] kiro_engine::Error),
+
+  #[error("Wav: {0}")]
+  Wav(#[from] hound::Error),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Note played by [`render_note`], held for some time and followed by a tail for its release.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteRender {
+  pub note: u8,
+  /// Velocity as in MIDI 1.0, from 0 to 127
+  pub velocity: u8,
+  /// Seconds the note is held
+  pub duration: f64,
+  /// Seconds rendered after the note is released
+  pub tail: f64,
+}
+
+impl Default for NoteRender {
+  fn default() -> Self {
+    Self {
+      note: 60,
+      velocity: 100,
+      duration: 1.0,
+      tail: 1.0,
+    }
+  }
+}
+
+impl NoteRender {
+  fn events(&self) -> [Event; 2] {
+    let velocity = (f32::from(self.velocity.min(127)) / 127.0 * f32::from(u16::MAX)).round() as u16;
+    let note_on = ChannelVoiceMessage::NoteOn {
+      note: self.note,
+      velocity,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    let note_off = ChannelVoiceMessage::NoteOff {
+      note: self.note,
+      velocity: 0,
+      attr_type: 0,
+      attr_data: 0,
+    };
+    [
+      Event {
+        timestamp: 0,
+        data: EventData::Midi(Message::channel_voice(0, 0, note_on)),
+      },
+      Event {
+        timestamp: (self.duration.max(0.0) * 1e9) as u64,
+        data: EventData::Midi(Message::channel_voice(0, 0, note_off)),
+      },
+    ]
+  }
+}
+
+/// Build the synth without any audio device, with the preset of the config if any,
+/// and render a note as fast as possible, returning the samples for every output channel.
+pub fn render_note(config: &Config, note: &NoteRender) -> Result<Vec<Vec<f32>>> {
+  let sample_rate = config.audio.sample_rate;
+  let mut engine = Engine::new(EngineConfig {
+    sample_rate,
+    audio_buffer_size: config.audio.buffer_size,
+    ..EngineConfig::default()
+  });
+  let graph = SynthGraph::try_new(&mut engine, sample_rate, config.num_voices, &config.tuning)?;
+  if let Some(preset) = config.preset.as_ref() {
+    graph.load_preset(&mut engine, preset)?;
+  }
+  let seconds = note.duration.max(0.0) + note.tail.max(0.0);
+  let num_samples = (seconds * f64::from(sample_rate)).round() as usize;
+  Ok(engine.render_offline(num_samples, &note.events())?)
+}
+
+/// Write the samples of some channels into a wav file of 32 bits floats.
+pub fn write_wav<P: AsRef<Path>>(
+  path: P,
+  sample_rate: SampleRate,
+  channels: &[Vec<f32>],
+) -> Result<()> {
+  let spec = hound::WavSpec {
+    channels: channels.len() as u16,
+    sample_rate,
+    bits_per_sample: 32,
+    sample_format: hound::SampleFormat::Float,
+  };
+  let mut writer = hound::WavWriter::create(path, spec)?;
+  let num_samples = channels.iter().map(Vec::len).min().unwrap_or(0);
+  for index in 0..num_samples {
+    for channel in channels {
+      writer.write_sample(channel[index])?;
+    }
+  }
+  writer.finalize()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render_a_note_into_a_wav_file() {
+    let config = Config {
+      num_voices: 2,
+      ..Config::default()
+    };
+    let note = NoteRender {
+      duration: 0.1,
+      tail: 0.1,
+      ..NoteRender::default()
+    };
+    let channels = render_note(&config, &note).unwrap();
+    assert_eq!(channels.len(), 2);
+    assert_eq!(channels[0].len(), 8820);
+    assert!(channels[0].iter().any(|sample| *sample != 0.0));
+
+    let path = std::env::temp_dir().join("kiro-synth-render-test.wav");
+    write_wav(&path, config.audio.sample_rate, &channels).unwrap();
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().channels, 2);
+    let samples = reader
+      .samples::<f32>()
+      .collect::<core::result::Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(samples.len(), 2 * 8820);
+    assert_eq!(samples[1], channels[1][0]);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,119 @@
+use crate::config::VoiceScalingConfig;
+
+/// Adapts the maximum number of active voices to the render load,
+/// so dense passages on weaker machines lose voices instead of producing dropouts.
+///
+/// The limit is only changed after the load stays out of the `[low_load, high_load]`
+/// band for `hold_updates` consecutive updates, to avoid oscillating around a threshold.
+#[derive(Debug, Clone)]
+pub struct VoiceScaler {
+  config: VoiceScalingConfig,
+  max_active_voices: usize,
+  overloaded_updates: usize,
+  underloaded_updates: usize,
+}
+
+impl VoiceScaler {
+  pub fn new(config: VoiceScalingConfig) -> Self {
+    let max_active_voices = config.max_voices;
+    Self {
+      config,
+      max_active_voices,
+      overloaded_updates: 0,
+      underloaded_updates: 0,
+    }
+  }
+
+  pub fn max_active_voices(&self) -> usize {
+    self.max_active_voices
+  }
+
+  /// Update the scaler with the last render load measured, and return the new voices limit.
+  pub fn update(&mut self, load: f32) -> usize {
+    if !self.config.enabled {
+      self.max_active_voices = self.config.max_voices;
+      return self.max_active_voices;
+    }
+
+    if load > self.config.high_load {
+      self.underloaded_updates = 0;
+      self.overloaded_updates += 1;
+      if self.overloaded_updates >= self.config.hold_updates {
+        self.overloaded_updates = 0;
+        self.max_active_voices = self
+          .max_active_voices
+          .saturating_sub(self.config.step)
+          .max(self.config.min_voices);
+      }
+    } else if load < self.config.low_load {
+      self.overloaded_updates = 0;
+      self.underloaded_updates += 1;
+      if self.underloaded_updates >= self.config.hold_updates {
+        self.underloaded_updates = 0;
+        self.max_active_voices = (self.max_active_voices + self.config.step)
+          .min(self.config.max_voices)
+          .max(self.config.min_voices);
+      }
+    } else {
+      self.overloaded_updates = 0;
+      self.underloaded_updates = 0;
+    }
+
+    self.max_active_voices
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config() -> VoiceScalingConfig {
+    VoiceScalingConfig {
+      enabled: true,
+      min_voices: 2,
+      max_voices: 8,
+      step: 2,
+      low_load: 0.5,
+      high_load: 0.8,
+      hold_updates: 2,
+    }
+  }
+
+  #[test]
+  fn scales_down_and_up_with_hysteresis() {
+    let mut scaler = VoiceScaler::new(config());
+    assert_eq!(scaler.max_active_voices(), 8);
+
+    assert_eq!(scaler.update(0.9), 8);
+    assert_eq!(scaler.update(0.9), 6);
+    assert_eq!(scaler.update(0.7), 6);
+    assert_eq!(scaler.update(0.9), 6);
+    assert_eq!(scaler.update(0.9), 4);
+
+    assert_eq!(scaler.update(0.3), 4);
+    assert_eq!(scaler.update(0.3), 6);
+  }
+
+  #[test]
+  fn respects_bounds() {
+    let mut scaler = VoiceScaler::new(config());
+    for _ in 0..20 {
+      scaler.update(1.0);
+    }
+    assert_eq!(scaler.max_active_voices(), 2);
+    for _ in 0..20 {
+      scaler.update(0.0);
+    }
+    assert_eq!(scaler.max_active_voices(), 8);
+  }
+
+  #[test]
+  fn disabled() {
+    let mut scaler = VoiceScaler::new(VoiceScalingConfig {
+      enabled: false,
+      ..config()
+    });
+    assert_eq!(scaler.update(1.0), 8);
+    assert_eq!(scaler.update(1.0), 8);
+  }
+}